@@ -24,6 +24,23 @@ mod ext {
             gas_limit: u64,
         ) -> i32;
         pub fn hd(name: *const u8, name_len: u32) -> u32;
+        pub fn hq(name: *const u8, name_len: u32, arg_len: u32) -> u32;
+        pub fn cf(
+            contract_id: *const u8,
+            fn_name: *const u8,
+            fn_name_len: u32,
+            fn_arg_len: u32,
+            gas_limit: u64,
+        ) -> i32;
+        pub fn emit(topic: *const u8, topic_len: u32, arg_len: u32);
+        pub fn emit_event(
+            topic: *const u8,
+            topic_len: u32,
+            schema: u64,
+            arg_len: u32,
+        );
+        pub fn defer(fn_name: *const u8, fn_name_len: u32, fn_arg_len: u32);
+        pub fn owner(contract_id: *const u8) -> i32;
     }
 }
 
@@ -87,3 +104,38 @@ unsafe fn out_of_bounds(_: i32) -> i32 {
     ext::hd(4398046511103 as *const u8, 2);
     0
 }
+
+// A pointer well within the memory reserved for the contract, but past the end
+// of the memory it is currently using.
+const PAST_MEMORY: *const u8 = 0x4000_0000 as *const u8;
+
+// Calls the extern whose index is written to the argument buffer with a
+// pointer past the end of the memory, in an effort to trigger an error.
+#[no_mangle]
+unsafe fn out_of_bounds_import(_: i32) -> i32 {
+    match A[0] {
+        0 => {
+            ext::hq(PAST_MEMORY, 2, 0);
+        }
+        1 => {
+            ext::hd(PAST_MEMORY, 2);
+        }
+        2 => {
+            ext::c(PAST_MEMORY, b"read_value".as_ptr(), 10, 0, 0);
+        }
+        3 => {
+            ext::c(A.as_ptr(), PAST_MEMORY, 10, 0, 0);
+        }
+        4 => {
+            ext::cf(PAST_MEMORY, b"read_value".as_ptr(), 10, 0, 0);
+        }
+        5 => ext::emit(PAST_MEMORY, 2, 0),
+        6 => ext::emit_event(PAST_MEMORY, 2, 0, 0),
+        7 => ext::defer(PAST_MEMORY, 2, 0),
+        8 => {
+            ext::owner(PAST_MEMORY);
+        }
+        _ => {}
+    }
+    0
+}
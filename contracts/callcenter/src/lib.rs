@@ -29,6 +29,40 @@ impl Callcenter {
         uplink::call(counter_id, "read_value", &()).unwrap()
     }
 
+    /// Read the values of all given counters, using a single host call
+    pub fn query_counters(
+        &self,
+        counter_ids: Vec<ContractId>,
+    ) -> Vec<Result<i64, ContractError>> {
+        let calls: Vec<_> = counter_ids
+            .iter()
+            .map(|id| (*id, "read_value", &[][..]))
+            .collect();
+
+        uplink::multi_call(&calls)
+            .into_iter()
+            .map(|res| {
+                // An archived `i64` is its little endian representation
+                res.map(|bytes| {
+                    let mut value_bytes = [0; 8];
+                    value_bytes.copy_from_slice(&bytes[..8]);
+                    i64::from_le_bytes(value_bytes)
+                })
+            })
+            .collect()
+    }
+
+    /// Count and sum the numbers fed by the `feeder` contract's `feed_num`,
+    /// reading them one at a time
+    pub fn sum_fed(
+        &self,
+        feeder: ContractId,
+        num: u32,
+    ) -> Result<(u32, u64), ContractError> {
+        let fed = uplink::call_feed::<_, u32>(feeder, "feed_num", &num)?;
+        Ok(fed.fold((0, 0), |(count, sum), n| (count + 1, sum + n as u64)))
+    }
+
     /// Increment the counter
     pub fn increment_counter(&mut self, counter_id: ContractId) {
         uplink::call(counter_id, "increment", &()).unwrap()
@@ -85,6 +119,15 @@ impl Callcenter {
         uplink::callstack()
     }
 
+    /// Return the frames of the call stack of this contract, as the contract
+    /// called, the name of the function, and the gas limit
+    pub fn return_callstack_frames(&self) -> Vec<(ContractId, String, u64)> {
+        uplink::callstack_frames()
+            .into_iter()
+            .map(|frame| (frame.contract_id, frame.fn_name, frame.limit))
+            .collect()
+    }
+
     /// Make sure that the caller of this contract is the contract itself
     pub fn call_self(&self) -> Result<bool, ContractError> {
         let self_id = uplink::self_id();
@@ -101,7 +144,38 @@ impl Callcenter {
         match n {
             0 => uplink::callstack(),
             _ => uplink::call(self_id, "call_self_n_times", &(n - 1))
-                .expect("calling self should succeed")
+                .expect("calling self should succeed"),
+        }
+    }
+
+    /// Calls itself `n` times, returning the error of the call that failed,
+    /// if any.
+    pub fn try_call_self_n_times(
+        &self,
+        n: u32,
+    ) -> Result<Vec<ContractId>, ContractError> {
+        let self_id = uplink::self_id();
+        match n {
+            0 => Ok(uplink::callstack()),
+            _ => uplink::call(self_id, "try_call_self_n_times", &(n - 1))
+                .and_then(|result| result),
+        }
+    }
+
+    /// Calls itself up to `n` times, stopping short of the maximum call depth,
+    /// and returns the depth reached.
+    pub fn call_self_within_depth(&self, n: u32) -> u32 {
+        let depth = uplink::call_depth();
+        let at_max = uplink::max_call_depth() == Some(depth);
+        match n {
+            0 => depth as u32,
+            _ if at_max => depth as u32,
+            _ => uplink::call(
+                uplink::self_id(),
+                "call_self_within_depth",
+                &(n - 1),
+            )
+            .expect("calling self within the depth should succeed"),
         }
     }
 
@@ -130,6 +204,18 @@ unsafe fn query_counter(arg_len: u32) -> u32 {
     wrap_call(arg_len, |counter_id| STATE.query_counter(counter_id))
 }
 
+/// Expose `Callcenter::query_counters()` to the host
+#[no_mangle]
+unsafe fn query_counters(arg_len: u32) -> u32 {
+    wrap_call(arg_len, |counter_ids| STATE.query_counters(counter_ids))
+}
+
+/// Expose `Callcenter::sum_fed()` to the host
+#[no_mangle]
+unsafe fn sum_fed(arg_len: u32) -> u32 {
+    wrap_call(arg_len, |(feeder, num)| STATE.sum_fed(feeder, num))
+}
+
 /// Expose `Callcenter::increment_counter()` to the host
 #[no_mangle]
 unsafe fn increment_counter(arg_len: u32) -> u32 {
@@ -154,6 +240,18 @@ unsafe fn call_self_n_times(arg_len: u32) -> u32 {
     wrap_call(arg_len, |n: u32| STATE.call_self_n_times(n))
 }
 
+/// Expose `Callcenter::try_call_self_n_times()` to the host
+#[no_mangle]
+unsafe fn try_call_self_n_times(arg_len: u32) -> u32 {
+    wrap_call(arg_len, |n: u32| STATE.try_call_self_n_times(n))
+}
+
+/// Expose `Callcenter::call_self_within_depth()` to the host
+#[no_mangle]
+unsafe fn call_self_within_depth(arg_len: u32) -> u32 {
+    wrap_call(arg_len, |n: u32| STATE.call_self_within_depth(n))
+}
+
 /// Expose `Callcenter::call_spend_with_limit` to the host
 #[no_mangle]
 unsafe fn call_spend_with_limit(arg_len: u32) -> u32 {
@@ -180,6 +278,12 @@ unsafe fn return_callstack(arg_len: u32) -> u32 {
     wrap_call(arg_len, |_: ()| STATE.return_callstack())
 }
 
+/// Expose `Callcenter::return_callstack_frames()` to the host
+#[no_mangle]
+unsafe fn return_callstack_frames(arg_len: u32) -> u32 {
+    wrap_call(arg_len, |_: ()| STATE.return_callstack_frames())
+}
+
 /// Expose `Callcenter::delegate_query()` to the host
 #[no_mangle]
 unsafe fn delegate_query(arg_len: u32) -> u32 {
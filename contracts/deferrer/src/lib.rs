@@ -0,0 +1,92 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Contract of a counter that defers its increments until after the call.
+
+#![no_std]
+
+use piecrust_uplink as uplink;
+
+/// Struct that describes the state of the deferrer contract
+pub struct Deferrer {
+    value: i64,
+}
+
+/// State of the deferrer contract
+static mut STATE: Deferrer = Deferrer { value: 0xfc };
+
+impl Deferrer {
+    /// Read the value of the counter
+    pub fn read_value(&self) -> i64 {
+        self.value
+    }
+
+    /// Increment the value of the counter by the given amount
+    pub fn increment(&mut self, by: i64) {
+        self.value += by;
+    }
+
+    /// Defer incrementing the counter by the given amount, returning the value
+    /// of the counter before the increment
+    pub fn defer_increment(&self, by: i64) -> i64 {
+        uplink::defer("increment", &by);
+        self.value
+    }
+
+    /// Defer incrementing the counter by the given amount, and then panic
+    pub fn defer_and_panic(&self, by: i64) {
+        uplink::defer("increment", &by);
+        panic!("Panic after deferring");
+    }
+
+    /// Defer a call that defers incrementing the counter by the given amount
+    pub fn defer_twice(&self, by: i64) {
+        uplink::defer("defer_increment", &by);
+    }
+
+    /// Defer a call that panics, followed by incrementing the counter by the
+    /// given amount
+    pub fn defer_panic_then_increment(&self, by: i64) {
+        uplink::defer("defer_and_panic", &by);
+        uplink::defer("increment", &by);
+    }
+}
+
+/// Expose `Deferrer::read_value()` to the host
+#[no_mangle]
+unsafe fn read_value(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |_: ()| STATE.read_value())
+}
+
+/// Expose `Deferrer::increment()` to the host
+#[no_mangle]
+unsafe fn increment(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |by| STATE.increment(by))
+}
+
+/// Expose `Deferrer::defer_increment()` to the host
+#[no_mangle]
+unsafe fn defer_increment(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |by| STATE.defer_increment(by))
+}
+
+/// Expose `Deferrer::defer_and_panic()` to the host
+#[no_mangle]
+unsafe fn defer_and_panic(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |by| STATE.defer_and_panic(by))
+}
+
+/// Expose `Deferrer::defer_twice()` to the host
+#[no_mangle]
+unsafe fn defer_twice(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |by| STATE.defer_twice(by))
+}
+
+/// Expose `Deferrer::defer_panic_then_increment()` to the host
+#[no_mangle]
+unsafe fn defer_panic_then_increment(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |by| STATE.defer_panic_then_increment(by))
+}
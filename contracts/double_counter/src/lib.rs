@@ -55,6 +55,15 @@ impl DoubleCounter {
         self.left_value = value;
         uplink::call(contract, "hello", &())
     }
+
+    /// Take over the value of the counter being upgraded as the left value,
+    /// returning it.
+    pub fn migrate_from(&mut self, counter: ContractId) -> i64 {
+        let value = uplink::call(counter, "read_value", &())
+            .expect("Reading the old counter should succeed");
+        self.left_value = value;
+        value
+    }
 }
 
 /// Expose `Counter::read_value()` to the host
@@ -75,6 +84,12 @@ unsafe fn increment_right(arg_len: u32) -> u32 {
     uplink::wrap_call(arg_len, |_: ()| STATE.increment_right())
 }
 
+/// Expose `Counter::migrate_from()` to the host
+#[no_mangle]
+unsafe fn migrate_from(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |counter| STATE.migrate_from(counter))
+}
+
 /// Expose `Counter::increment_and_call()` to the host
 #[no_mangle]
 unsafe fn increment_left_and_call(arg_len: u32) -> u32 {
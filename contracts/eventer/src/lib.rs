@@ -9,9 +9,23 @@
 #![no_std]
 
 extern crate alloc;
+use alloc::string::String;
 use alloc::vec::Vec;
 
+use bytecheck::CheckBytes;
 use piecrust_uplink as uplink;
+use rkyv::{Archive, Deserialize, Serialize};
+use uplink::{contract_event, LogLevel};
+
+contract_event! {
+    #[topic = "typed_number"]
+    #[derive(Archive, Serialize, Deserialize)]
+    #[archive_attr(derive(CheckBytes))]
+    /// Event carrying a number, emitted with its schema
+    pub struct TypedNumber {
+        pub num: u32,
+    }
+}
 
 /// Struct that describes the state of the eventer contract
 pub struct Eventer;
@@ -34,12 +48,32 @@ impl Eventer {
         }
     }
 
+    /// Emits a typed event with the given number
+    pub fn emit_num_typed(&mut self, num: u32) {
+        for i in 0..num {
+            uplink::emit_event(TypedNumber { num: i });
+        }
+    }
+
     pub fn emit_input(&mut self, input: Vec<u8>) -> (u64, u64) {
         let spent_before = uplink::spent();
         uplink::emit("input", input);
         let spent_after = uplink::spent();
         (spent_before, spent_after)
     }
+
+    /// Logs the given message at each level, from most to least severe
+    pub fn log_levels(&mut self, msg: String) {
+        for level in [
+            LogLevel::Error,
+            LogLevel::Warn,
+            LogLevel::Info,
+            LogLevel::Debug,
+            LogLevel::Trace,
+        ] {
+            uplink::log(level, &msg);
+        }
+    }
 }
 
 /// Expose `Eventer::emit_num()` to the host
@@ -54,8 +88,20 @@ unsafe fn emit_events_raw(arg_len: u32) -> u32 {
     uplink::wrap_call(arg_len, |num| STATE.emit_num_raw(num))
 }
 
+/// Expose `Eventer::emit_num_typed()` to the host
+#[no_mangle]
+unsafe fn emit_events_typed(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |num| STATE.emit_num_typed(num))
+}
+
 /// Expose `Eventer::emit_input()` to the host
 #[no_mangle]
 unsafe fn emit_input(arg_len: u32) -> u32 {
     uplink::wrap_call(arg_len, |input| STATE.emit_input(input))
 }
+
+/// Expose `Eventer::log_levels()` to the host
+#[no_mangle]
+unsafe fn log_levels(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |msg| STATE.log_levels(msg))
+}
@@ -4,11 +4,20 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
-//! Contract to get the current block height from the host.
+//! Contract to get the current block height from the host, either as an
+//! untyped or a typed metadata item.
 
 #![no_std]
 
 use piecrust_uplink as uplink;
+use rkyv::{Archive, Deserialize, Serialize};
+use uplink::session_meta;
+
+session_meta! {
+    #[name = "height"]
+    #[derive(Archive, Serialize, Deserialize)]
+    pub struct BlockHeight(pub u64);
+}
 
 /// Struct that describes the state of the everest contract
 pub struct Height;
@@ -21,6 +30,12 @@ impl Height {
     pub fn get_height(&self) -> Option<u64> {
         uplink::meta_data::<u64>("height")
     }
+
+    /// Query the host for the current block height, checking it was set with
+    /// the expected layout
+    pub fn get_typed_height(&self) -> Option<u64> {
+        uplink::meta::<BlockHeight>().map(|BlockHeight(height)| height)
+    }
 }
 
 /// Expose `Height::get_height()` to the host
@@ -28,3 +43,9 @@ impl Height {
 unsafe fn get_height(a: u32) -> u32 {
     uplink::wrap_call(a, |_: ()| STATE.get_height())
 }
+
+/// Expose `Height::get_typed_height()` to the host
+#[no_mangle]
+unsafe fn get_typed_height(a: u32) -> u32 {
+    uplink::wrap_call(a, |_: ()| STATE.get_typed_height())
+}
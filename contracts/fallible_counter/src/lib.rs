@@ -4,7 +4,8 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
-//! Contract of a counter that can panic if wanted.
+//! Contract of a counter that can panic if wanted, or fail to increment past a
+//! maximum.
 
 #![no_std]
 
@@ -32,6 +33,16 @@ impl FallibleCounter {
             panic!("Incremental panic");
         }
     }
+
+    /// Increment the value of the counter, failing if it would go over the
+    /// given maximum
+    pub fn increment_below(&mut self, max: i64) -> Result<i64, &'static str> {
+        self.value += 1;
+        if self.value > max {
+            return Err("Value over the maximum");
+        }
+        Ok(self.value)
+    }
 }
 
 /// Expose `FallibleCounter::read_value()` to the host
@@ -45,3 +56,9 @@ unsafe fn read_value(arg_len: u32) -> u32 {
 unsafe fn increment(arg_len: u32) -> u32 {
     uplink::wrap_call(arg_len, |panic: bool| STATE.increment(panic))
 }
+
+/// Expose `FallibleCounter::increment_below()` to the host
+#[no_mangle]
+unsafe fn increment_below(arg_len: u32) -> u32 {
+    uplink::wrap_call_fallible(arg_len, |max| STATE.increment_below(max))
+}
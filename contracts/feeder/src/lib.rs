@@ -4,14 +4,20 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
-//! Contract that uses the `feed` extern to report data to the host.
+//! Contract that uses the `feed` extern to report data to the host, and
+//! exchanges spilled data with it.
 
 #![no_std]
 
 extern crate alloc;
 
+use alloc::vec;
+
 use piecrust_uplink as uplink;
 
+/// The length of the chunks the spilled input is fed to the host in.
+const SPILL_CHUNK_LEN: usize = 1000;
+
 /// Struct that describes the state of the feeder contract
 pub struct Feeder;
 
@@ -32,6 +38,36 @@ impl Feeder {
             uplink::feed_raw(i.to_le_bytes());
         }
     }
+
+    /// Feed the host with the input spilled to the contract, in chunks of
+    /// [`SPILL_CHUNK_LEN`] bytes, returning its length.
+    pub fn feed_spilled(&self) -> u64 {
+        let mut chunk = vec![0u8; SPILL_CHUNK_LEN];
+        let mut offset = 0;
+
+        loop {
+            let len = uplink::read_spilled_input(offset, &mut chunk);
+            if len == 0 {
+                break;
+            }
+            uplink::feed_raw(&chunk[..len]);
+            offset += len as u64;
+        }
+
+        offset
+    }
+
+    /// Spill the input spilled to the contract back to the host, returning its
+    /// length.
+    pub fn echo_spilled(&self) -> u64 {
+        let len = uplink::spilled_input_len();
+
+        let mut input = vec![0u8; len as usize];
+        uplink::read_spilled_input(0, &mut input);
+        uplink::spill_output(&input);
+
+        len
+    }
 }
 
 /// Expose `Feeder::feed_num()` to the host
@@ -45,3 +81,15 @@ unsafe fn feed_num(arg_len: u32) -> u32 {
 unsafe fn feed_num_raw(arg_len: u32) -> u32 {
     uplink::wrap_call(arg_len, |num| STATE.feed_num_raw(num))
 }
+
+/// Expose `Feeder::feed_spilled()` to the host
+#[no_mangle]
+unsafe fn feed_spilled(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |_: ()| STATE.feed_spilled())
+}
+
+/// Expose `Feeder::echo_spilled()` to the host
+#[no_mangle]
+unsafe fn echo_spilled(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |_: ()| STATE.echo_spilled())
+}
@@ -0,0 +1,69 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Contract that forwards calls to a counter given when it is initialized.
+
+#![no_std]
+
+use piecrust_uplink as uplink;
+use uplink::{ContractId, CONTRACT_ID_BYTES};
+
+uplink::contract_interface! {
+    /// Client for the counter contract
+    struct Counter {
+        /// Read the value of the counter
+        fn read_value() -> i64;
+        /// Increment the value of the counter
+        fn increment();
+    }
+}
+
+/// Struct that describes the state of the forwarder contract
+pub struct Forwarder {
+    counter: Counter,
+}
+
+/// State of the forwarder contract
+static mut STATE: Forwarder = Forwarder {
+    counter: Counter::new(ContractId::from_bytes([0; CONTRACT_ID_BYTES])),
+};
+
+impl Forwarder {
+    /// Initialize the contract with the counter to forward to, incrementing
+    /// it once
+    pub fn init(&mut self, counter: ContractId) {
+        self.counter = Counter::new(counter);
+        self.increment();
+    }
+
+    /// Read the value of the counter
+    pub fn read_value(&self) -> i64 {
+        self.counter.read_value().unwrap()
+    }
+
+    /// Increment the value of the counter
+    pub fn increment(&mut self) {
+        self.counter.increment().unwrap()
+    }
+}
+
+/// Expose `Forwarder::init()` to the host
+#[no_mangle]
+unsafe fn init(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |counter| STATE.init(counter))
+}
+
+/// Expose `Forwarder::read_value()` to the host
+#[no_mangle]
+unsafe fn read_value(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |_: ()| STATE.read_value())
+}
+
+/// Expose `Forwarder::increment()` to the host
+#[no_mangle]
+unsafe fn increment(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |_: ()| STATE.increment())
+}
@@ -5,7 +5,7 @@
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
 //! Contract keeping a vector of bytes, and allowing the user to append to it,
-//! and query for "views" into said vector.
+//! query for "views" into said vector, and clear it.
 //!
 //! This contract does *not* use `rkyv` to serialize to and deserialize from the
 //! argument buffer. Instead it uses its own little protocol, writing bytes
@@ -60,6 +60,12 @@ impl Grower {
         })
     }
 
+    /// Zeroes the bytes of the state vector, and then empties it.
+    fn clear(&mut self) {
+        self.0.fill(0);
+        self.0.clear();
+    }
+
     /// Emplace the length of the state vector into the argument buffer.
     fn len(&self) -> usize {
         with_arg_buf(|buf| {
@@ -91,6 +97,13 @@ unsafe fn view(arg_len: u32) -> u32 {
     STATE.view(arg_len as usize) as u32
 }
 
+/// Expose `Grower::clear()` to the host
+#[no_mangle]
+unsafe fn clear(_arg_len: u32) -> u32 {
+    STATE.clear();
+    0
+}
+
 /// Expose `Grower::len()` to the host
 #[no_mangle]
 unsafe fn len(_arg_len: u32) -> u32 {
@@ -0,0 +1,86 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Contract counting the events emitted by the contracts it observes.
+
+#![no_std]
+
+extern crate alloc;
+use alloc::string::String;
+
+use piecrust_uplink as uplink;
+use uplink::{ContractId, Event};
+
+/// Struct that describes the state of the observer contract
+pub struct Observer {
+    count: u32,
+    last_topic: Option<String>,
+}
+
+/// State of the observer contract
+static mut STATE: Observer = Observer {
+    count: 0,
+    last_topic: None,
+};
+
+impl Observer {
+    /// Start observing the given contract
+    pub fn observe(&mut self, contract: ContractId) {
+        uplink::observe(contract);
+    }
+
+    /// Stop observing the given contract
+    pub fn unobserve(&mut self, contract: ContractId) {
+        uplink::unobserve(contract);
+    }
+
+    /// Start observing the given contract, and then panic
+    pub fn observe_and_panic(&mut self, contract: ContractId) {
+        uplink::observe(contract);
+        panic!("Panic after observing");
+    }
+
+    /// Count an event emitted by an observed contract
+    pub fn on_event(&mut self, event: Event) {
+        self.count += 1;
+        self.last_topic = Some(event.topic);
+    }
+
+    /// Read the number of events counted, and the topic of the last one
+    pub fn read_count(&self) -> (u32, Option<String>) {
+        (self.count, self.last_topic.clone())
+    }
+}
+
+/// Expose `Observer::observe()` to the host
+#[no_mangle]
+unsafe fn observe(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |contract| STATE.observe(contract))
+}
+
+/// Expose `Observer::unobserve()` to the host
+#[no_mangle]
+unsafe fn unobserve(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |contract| STATE.unobserve(contract))
+}
+
+/// Expose `Observer::observe_and_panic()` to the host
+#[no_mangle]
+unsafe fn observe_and_panic(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |contract| STATE.observe_and_panic(contract))
+}
+
+/// Expose `Observer::on_event()` to the host
+#[no_mangle]
+unsafe fn on_event(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |event| STATE.on_event(event))
+}
+
+/// Expose `Observer::read_count()` to the host
+#[no_mangle]
+unsafe fn read_count(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |_: ()| STATE.read_count())
+}
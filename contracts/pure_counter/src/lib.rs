@@ -0,0 +1,79 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Counter contract declaring its getters pure, so that the host may cache
+//! their results, and the types of the functions it exports.
+
+#![no_std]
+
+use piecrust_uplink as uplink;
+use uplink::{ContractError, ContractId};
+
+/// Struct that describes the state of the pure counter contract
+pub struct PureCounter {
+    value: i64,
+}
+
+/// State of the pure counter contract
+static mut STATE: PureCounter = PureCounter { value: 0xfc };
+
+uplink::pure_functions!(read_value, read_value_plus, read_other);
+
+uplink::export_schema! {
+    fn read_value(&self, ()) -> i64;
+    fn read_value_plus(&self, i64) -> i64;
+    fn read_other(&self, ContractId) -> Result<i64, ContractError>;
+    fn increment(&mut self, ());
+}
+
+impl PureCounter {
+    /// Read the value of the counter
+    pub fn read_value(&self) -> i64 {
+        self.value
+    }
+
+    /// Read the value of the counter added to the given amount
+    pub fn read_value_plus(&self, amount: i64) -> i64 {
+        self.value + amount
+    }
+
+    /// Read the value of the counter at the given contract
+    pub fn read_other(
+        &self,
+        counter: ContractId,
+    ) -> Result<i64, ContractError> {
+        uplink::call(counter, "read_value", &())
+    }
+
+    /// Increment the value of the counter by 1
+    pub fn increment(&mut self) {
+        self.value += 1;
+    }
+}
+
+/// Expose `PureCounter::read_value()` to the host
+#[no_mangle]
+unsafe fn read_value(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |_: ()| STATE.read_value())
+}
+
+/// Expose `PureCounter::read_value_plus()` to the host
+#[no_mangle]
+unsafe fn read_value_plus(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |amount| STATE.read_value_plus(amount))
+}
+
+/// Expose `PureCounter::read_other()` to the host
+#[no_mangle]
+unsafe fn read_other(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |counter| STATE.read_other(counter))
+}
+
+/// Expose `PureCounter::increment()` to the host
+#[no_mangle]
+unsafe fn increment(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |_: ()| STATE.increment())
+}
@@ -0,0 +1,45 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Contract built with a small argument buffer, by setting
+//! `PIECRUST_ARGBUF_LEN` when compiling it.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use piecrust_uplink as uplink;
+use uplink::{ContractError, ContractId};
+
+/// Return the size of the argument buffer the contract was built with
+#[no_mangle]
+unsafe fn arg_buf_len(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |_: ()| uplink::arg_buf_len() as u64)
+}
+
+/// Return the given bytes
+#[no_mangle]
+unsafe fn echo(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |bytes: Vec<u8>| bytes)
+}
+
+/// Call the given function of a contract, returning its raw return
+#[no_mangle]
+unsafe fn delegate_query(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |(contract, fn_name, fn_arg)| {
+        delegate(contract, fn_name, fn_arg)
+    })
+}
+
+fn delegate(
+    contract: ContractId,
+    fn_name: String,
+    fn_arg: Vec<u8>,
+) -> Result<Vec<u8>, ContractError> {
+    uplink::call_raw(contract, &fn_name, &fn_arg)
+}
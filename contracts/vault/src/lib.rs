@@ -0,0 +1,108 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Contract transferring out of the balance kept for it by the host.
+
+#![no_std]
+
+use piecrust_uplink as uplink;
+use uplink::{ContractError, ContractId, TransferError};
+
+/// Struct that describes the state of the vault contract
+pub struct Vault;
+
+/// State of the vault contract
+static mut STATE: Vault = Vault;
+
+impl Vault {
+    /// Read the balance of the contract
+    pub fn balance(&self) -> u64 {
+        uplink::balance()
+    }
+
+    /// Transfer the given amount to the given contract
+    pub fn transfer(
+        &mut self,
+        to: ContractId,
+        amount: u64,
+    ) -> Result<(), TransferError> {
+        uplink::transfer(to, amount)
+    }
+
+    /// Transfer the given amount to the given contract, and then panic
+    pub fn transfer_and_panic(&mut self, to: ContractId, amount: u64) {
+        uplink::transfer(to, amount).expect("Transfer should succeed");
+        panic!("Panic after transferring");
+    }
+
+    /// Call `transfer_and_panic` on the given vault, returning the error of
+    /// the failed call
+    pub fn call_transfer_and_panic(
+        &mut self,
+        vault: ContractId,
+        to: ContractId,
+        amount: u64,
+    ) -> Result<(), ContractError> {
+        uplink::call(vault, "transfer_and_panic", &(to, amount))
+    }
+
+    /// Destroy the contract, transferring its balance to the given contract
+    pub fn self_destruct(
+        &mut self,
+        beneficiary: ContractId,
+    ) -> Result<(), TransferError> {
+        uplink::self_destruct(beneficiary)
+    }
+
+    /// Destroy the contract, and then panic
+    pub fn self_destruct_and_panic(&mut self, beneficiary: ContractId) {
+        uplink::self_destruct(beneficiary)
+            .expect("Self-destructing should succeed");
+        panic!("Panic after self-destructing");
+    }
+}
+
+/// Expose `Vault::balance()` to the host
+#[no_mangle]
+unsafe fn balance(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |_: ()| STATE.balance())
+}
+
+/// Expose `Vault::transfer()` to the host
+#[no_mangle]
+unsafe fn transfer(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |(to, amount)| STATE.transfer(to, amount))
+}
+
+/// Expose `Vault::transfer_and_panic()` to the host
+#[no_mangle]
+unsafe fn transfer_and_panic(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |(to, amount)| {
+        STATE.transfer_and_panic(to, amount)
+    })
+}
+
+/// Expose `Vault::call_transfer_and_panic()` to the host
+#[no_mangle]
+unsafe fn call_transfer_and_panic(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |(vault, to, amount)| {
+        STATE.call_transfer_and_panic(vault, to, amount)
+    })
+}
+
+/// Expose `Vault::self_destruct()` to the host
+#[no_mangle]
+unsafe fn self_destruct(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |beneficiary| STATE.self_destruct(beneficiary))
+}
+
+/// Expose `Vault::self_destruct_and_panic()` to the host
+#[no_mangle]
+unsafe fn self_destruct_and_panic(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |beneficiary| {
+        STATE.self_destruct_and_panic(beneficiary)
+    })
+}
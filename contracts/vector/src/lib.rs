@@ -31,6 +31,17 @@ impl Vector {
     pub fn pop(&mut self) -> Option<i16> {
         self.a.pop()
     }
+
+    /// Allocate a vector of the given number of bytes and free it, returning
+    /// the bytes allocated on the heap and their peak during the call
+    pub fn alloc_and_free(&mut self, bytes: u32) -> (u64, u64) {
+        // Keep the allocation from being optimized away
+        let v: Vec<u8> = Vec::with_capacity(bytes as usize);
+        drop(core::hint::black_box(v));
+
+        let stats = uplink::heap_stats();
+        (stats.used as u64, stats.peak as u64)
+    }
 }
 
 /// Expose `Vector::push()` to the host
@@ -44,3 +55,9 @@ unsafe fn push(arg_len: u32) -> u32 {
 unsafe fn pop(arg_len: u32) -> u32 {
     uplink::wrap_call(arg_len, |_arg: ()| STATE.pop())
 }
+
+/// Expose `Vector::alloc_and_free()` to the host
+#[no_mangle]
+unsafe fn alloc_and_free(arg_len: u32) -> u32 {
+    uplink::wrap_call(arg_len, |arg| STATE.alloc_and_free(arg))
+}
@@ -0,0 +1,112 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Measures the cost of handling write faults on a [`Mmap`], across a range of
+//! page sizes and numbers of touched pages.
+//!
+//! The pages touched are chosen by a seeded RNG, so runs are reproducible and
+//! can be compared across machines and revisions.
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkId, Criterion,
+    Throughput,
+};
+use crumbles::Mmap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const SEED: u64 = 0xDEAD_BEEF;
+
+/// Total size of the mapped region, regardless of page size.
+const REGION_SIZE: usize = 0x1000_0000;
+
+const PAGE_SIZES: [usize; 3] = [0x1000, 0x4000, 0x10000];
+const TOUCHED_PAGES: [usize; 3] = [1, 64, 1024];
+
+/// Returns the offsets of `n` bytes, each in a distinct page.
+fn touched_offsets(page_size: usize, n: usize) -> Vec<usize> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+
+    let page_number = REGION_SIZE / page_size;
+    let mut pages = Vec::with_capacity(n);
+
+    while pages.len() < n {
+        let page = rng.gen_range(0..page_number);
+        if !pages.contains(&page) {
+            pages.push(page);
+        }
+    }
+
+    pages
+        .into_iter()
+        .map(|page| page * page_size + rng.gen_range(0..page_size))
+        .collect()
+}
+
+fn write_fault(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write_fault");
+
+    for page_size in PAGE_SIZES {
+        for n in TOUCHED_PAGES {
+            let offsets = touched_offsets(page_size, n);
+
+            let mut mmap = Mmap::new(REGION_SIZE / page_size, page_size)
+                .expect("Mapping memory should succeed");
+
+            group.throughput(Throughput::Elements(n as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("page_size_{page_size:#x}"), n),
+                &offsets,
+                |b, offsets| {
+                    b.iter(|| {
+                        mmap.snap().expect("Snapshotting should succeed");
+                        for &offset in offsets {
+                            mmap[offset] = black_box(0xFF);
+                        }
+                        mmap.revert().expect("Reverting should succeed");
+                    });
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+fn dirty_pages(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dirty_pages");
+
+    for page_size in PAGE_SIZES {
+        for n in TOUCHED_PAGES {
+            let offsets = touched_offsets(page_size, n);
+
+            let mut mmap = Mmap::new(REGION_SIZE / page_size, page_size)
+                .expect("Mapping memory should succeed");
+
+            mmap.snap().expect("Snapshotting should succeed");
+            for &offset in &offsets {
+                mmap[offset] = 0xFF;
+            }
+
+            group.throughput(Throughput::Elements(n as u64));
+            group.bench_function(
+                BenchmarkId::new(format!("page_size_{page_size:#x}"), n),
+                |b| {
+                    b.iter(|| {
+                        for (page, _, _) in mmap.dirty_pages() {
+                            black_box(page);
+                        }
+                    });
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, write_fault, dirty_pages);
+criterion_main!(benches);
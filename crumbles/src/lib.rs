@@ -29,29 +29,28 @@
 //! ```
 //!
 //! # Limitations
-//! This crate currently only builds for 64-bit Unix targets. This is because it
-//! relies on various features of `libc` which are not available in other
-//! targets.
-#![cfg(all(unix, target_pointer_width = "64"))]
+//! This crate currently only builds for 64-bit Unix and Windows targets. This
+//! is because it relies on the ability to catch accesses to protected memory,
+//! using signal handlers on Unix and vectored exception handlers on Windows.
+#![cfg(all(any(unix, windows), target_pointer_width = "64"))]
 #![deny(missing_docs)]
 #![deny(clippy::pedantic)]
 
 use std::{
-    collections::{btree_map::Entry, BTreeMap},
-    fs::OpenOptions,
-    mem::{self, MaybeUninit},
-    ops::{Deref, DerefMut},
-    os::fd::AsRawFd,
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    fs::{File, OpenOptions},
+    ops::{Deref, DerefMut, Range},
     path::PathBuf,
-    sync::{Once, OnceLock, RwLock},
-    {io, process, ptr, slice},
+    sync::{OnceLock, RwLock},
+    {io, ptr, slice},
 };
 
-use libc::{
-    c_int, sigaction, sigemptyset, siginfo_t, sigset_t, ucontext_t,
-    MAP_ANONYMOUS, MAP_FAILED, MAP_FIXED, MAP_NORESERVE, MAP_PRIVATE,
-    PROT_NONE, PROT_READ, PROT_WRITE, SA_SIGINFO,
-};
+#[cfg(unix)]
+#[path = "sys/unix.rs"]
+mod sys;
+#[cfg(windows)]
+#[path = "sys/windows.rs"]
+mod sys;
 
 /// A handle to a copy-on-write memory-mapped region that keeps track of which
 /// pages have been written to.
@@ -109,7 +108,11 @@ impl Mmap {
     /// Create a new mmap, backed partially by physical memory, and partially
     /// the files opened by the given file locator. The `file_locator` is a
     /// closure taking a page index and optionally returning the file meant to
-    /// be used as the backing for that page.
+    /// be used as the backing for that page, together with the offset of the
+    /// page in it.
+    ///
+    /// Any number of pages may be backed by the same file, at different
+    /// offsets, allowing an entire memory to be backed by a single file.
     ///
     /// The size of the memory region is specified by the caller using the
     /// number of pages - `page_number` - and the page size - `page_size`. The
@@ -143,7 +146,7 @@ impl Mmap {
     /// let mmap = unsafe {
     ///     Mmap::with_files(65536, 65536, move |page_index| {
     ///         match page_index {
-    ///             0 => Some(PathBuf::from("LICENSE")),
+    ///             0 => Some((PathBuf::from("LICENSE"), 0)),
     ///             _ => None,
     ///         }
     ///     })?
@@ -279,6 +282,117 @@ impl Mmap {
         unsafe { self.0.apply() }
     }
 
+    /// Snapshot the current state of the memory, labeling the snapshot with
+    /// the given `label`.
+    ///
+    /// A labeled snapshot behaves like one taken using [`snap`], but can
+    /// additionally be reverted to, or applied, by its label using
+    /// [`revert_to`] and [`apply_to`]. This allows a caller to ensure it is
+    /// unwinding to the snapshot it intends to.
+    ///
+    /// # Errors
+    /// If a snapshot with the same label is already in place, this function
+    /// will error with [`io::ErrorKind::AlreadyExists`], leaving the memory
+    /// untouched. It will also error in the same cases as [`snap`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::io;
+    /// # fn main() -> io::Result<()> {
+    /// use crumbles::Mmap;
+    ///
+    /// let mut mmap = Mmap::new(65536, 65536)?;
+    ///
+    /// mmap.snap_labeled(1)?;
+    /// assert!(mmap.snap_labeled(1).is_err(), "Labels must be unique");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`snap`]: Mmap::snap
+    /// [`revert_to`]: Mmap::revert_to
+    /// [`apply_to`]: Mmap::apply_to
+    pub fn snap_labeled(&mut self, label: u64) -> io::Result<()> {
+        unsafe { self.0.snap_labeled(label) }
+    }
+
+    /// Revert to the snapshot with the given `label`.
+    ///
+    /// Every snapshot taken after the labeled one is reverted as well,
+    /// discarding all changes made since the labeled snapshot was taken using
+    /// [`snap_labeled`].
+    ///
+    /// # Errors
+    /// If there is no snapshot with the given label, this function will error
+    /// with [`io::ErrorKind::NotFound`], leaving the memory untouched. It will
+    /// also error in the same cases as [`revert`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::io;
+    /// # fn main() -> io::Result<()> {
+    /// use crumbles::Mmap;
+    ///
+    /// let mut mmap = Mmap::new(65536, 65536)?;
+    ///
+    /// mmap.snap_labeled(1)?;
+    /// mmap[0] = 1;
+    /// mmap.snap()?;
+    /// mmap[1] = 1;
+    ///
+    /// mmap.revert_to(1)?;
+    /// assert_eq!(mmap[..2], [0, 0]);
+    /// assert!(mmap.revert_to(1).is_err(), "The snapshot is gone");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`snap_labeled`]: Mmap::snap_labeled
+    /// [`revert`]: Mmap::revert
+    pub fn revert_to(&mut self, label: u64) -> io::Result<()> {
+        unsafe { self.0.revert_to(label) }
+    }
+
+    /// Apply the changes made since the snapshot with the given `label`.
+    ///
+    /// Every snapshot taken after the labeled one is applied as well, merging
+    /// all changes made since the labeled snapshot was taken using
+    /// [`snap_labeled`] with the snapshot before it.
+    ///
+    /// # Errors
+    /// If there is no snapshot with the given label, this function will error
+    /// with [`io::ErrorKind::NotFound`], leaving the memory untouched. It will
+    /// also error in the same cases as [`apply`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::io;
+    /// # fn main() -> io::Result<()> {
+    /// use crumbles::Mmap;
+    ///
+    /// let mut mmap = Mmap::new(65536, 65536)?;
+    ///
+    /// mmap.snap()?;
+    /// mmap.snap_labeled(1)?;
+    /// mmap[0] = 1;
+    /// mmap.snap()?;
+    /// mmap[0x10_000] = 1; // second page
+    ///
+    /// mmap.apply_to(1)?;
+    /// assert_eq!(mmap.dirty_pages().count(), 2);
+    ///
+    /// mmap.revert()?;
+    /// assert_eq!(mmap[0], 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`snap_labeled`]: Mmap::snap_labeled
+    /// [`apply`]: Mmap::apply
+    pub fn apply_to(&mut self, label: u64) -> io::Result<()> {
+        unsafe { self.0.apply_to(label) }
+    }
+
     /// Returns an iterator over dirty memory pages and their clean
     /// counterparts, together with their offsets.
     ///
@@ -311,6 +425,245 @@ impl Mmap {
             },
         )
     }
+
+    /// Returns an iterator over the memory pages dirtied since the mmap was
+    /// created, together with their clean counterparts and their offsets.
+    ///
+    /// Unlike [`dirty_pages`], this includes the pages dirtied before each
+    /// snapshot still in place, with the clean page being the one before the
+    /// oldest of them. Pages dirtied since a reverted snapshot are not
+    /// included.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::io;
+    /// # fn main() -> io::Result<()> {
+    /// use crumbles::Mmap;
+    ///
+    /// let mut mmap = Mmap::new(65536, 65536)?;
+    ///
+    /// mmap[0] = 1; // first page
+    /// mmap.snap()?;
+    /// mmap[0x10_000] = 1; // second page
+    ///
+    /// let dirty_pages: Vec<_> =
+    ///     mmap.all_dirty_pages().map(|(_, _, index)| *index).collect();
+    ///
+    /// assert_eq!(mmap.dirty_pages().count(), 1);
+    /// assert_eq!(dirty_pages, [0, 1]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`dirty_pages`]: Mmap::dirty_pages
+    pub fn all_dirty_pages(
+        &self,
+    ) -> impl Iterator<Item = (&[u8], &[u8], &usize)> {
+        let mut clean_pages = BTreeMap::new();
+        for snapshot in &self.0.snapshots {
+            for (page_index, clean_page) in &snapshot.clean_pages {
+                clean_pages.entry(page_index).or_insert(clean_page);
+            }
+        }
+
+        clean_pages
+            .into_iter()
+            .map(move |(page_index, clean_page)| {
+                let page_size = self.0.page_size;
+                let offset = page_index * page_size;
+                (
+                    &self.0.bytes[offset..][..page_size],
+                    &clean_page[..],
+                    page_index,
+                )
+            })
+    }
+
+    /// Returns an iterator over the indices of the pages that have been hit -
+    /// either read or written - since the last snapshot, in the order they
+    /// were first hit.
+    ///
+    /// Reverting to a snapshot also resets the pages hit since it was taken.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::io;
+    /// # fn main() -> io::Result<()> {
+    /// use crumbles::Mmap;
+    ///
+    /// let mut mmap = Mmap::new(65536, 65536)?;
+    ///
+    /// mmap[0x30_000] = 1; // write to the fourth page
+    /// mmap.snap()?;
+    ///
+    /// assert_eq!(mmap[0x10_000], 0); // read from the second page
+    /// mmap[0] = 1; // write to the first page
+    ///
+    /// let hit_pages: Vec<_> = mmap.hit_pages().collect();
+    /// assert_eq!(hit_pages, [1, 0]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn hit_pages(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.last_snapshot().hit_list.iter().copied()
+    }
+
+    /// Returns an iterator over the indices of the pages that have been
+    /// accessed - either read or written - since the mmap was created, in
+    /// ascending order.
+    ///
+    /// Unlike [`dirty_pages`], this is unaffected by snapshotting, reverting,
+    /// or applying.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::io;
+    /// # fn main() -> io::Result<()> {
+    /// use crumbles::Mmap;
+    ///
+    /// let mut mmap = Mmap::new(65536, 65536)?;
+    ///
+    /// assert_eq!(mmap[0x10_000], 0); // read from the second page
+    /// mmap[0x30_000] = 1; // write to the fourth page
+    /// mmap.revert()?;
+    ///
+    /// let accessed_pages: Vec<_> = mmap.accessed_pages().collect();
+    /// assert_eq!(accessed_pages, [1, 3]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`dirty_pages`]: Mmap::dirty_pages
+    pub fn accessed_pages(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.accessed_pages()
+    }
+
+    /// Releases the physical memory held by the pages that were accessed but
+    /// never written to, returning the number of pages released.
+    ///
+    /// The pages keep their contents, and are read again from their backing
+    /// files - or as zeros, should they have none - the next time they are
+    /// accessed. Pages dirtied since the mmap was created are left untouched.
+    /// This allows long-lived mmaps to shed resident memory between uses,
+    /// without affecting their contents or the pages tracked as accessed or
+    /// dirty.
+    ///
+    /// # Errors
+    /// If the underlying call to advise the kernel fails, this function will
+    /// error. The pages released up to that point stay released.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::io;
+    /// # fn main() -> io::Result<()> {
+    /// use crumbles::Mmap;
+    ///
+    /// let mut mmap = Mmap::new(65536, 65536)?;
+    ///
+    /// assert_eq!(mmap[0x10_000], 0); // read from the second page
+    /// mmap[0] = 1; // write to the first page
+    ///
+    /// // Only the page that was read from is released
+    /// assert_eq!(mmap.reclaim()?, 1);
+    /// assert_eq!(mmap[0], 1);
+    /// assert_eq!(mmap[0x10_000], 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reclaim(&mut self) -> io::Result<usize> {
+        unsafe { self.0.reclaim() }
+    }
+
+    /// Returns a bitmap of the pages dirtied since the mmap was created, as
+    /// listed by [`all_dirty_pages`].
+    ///
+    /// The bit for each page is at the index of the page divided by 8, with
+    /// the lowest bits for the lowest pages. Trailing bytes with no bits set
+    /// are left out, keeping the bitmap compact. It can be given to
+    /// [`mark_dirty`] to dirty the same pages in another mmap.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::io;
+    /// # fn main() -> io::Result<()> {
+    /// use crumbles::Mmap;
+    ///
+    /// let mut mmap = Mmap::new(65536, 65536)?;
+    ///
+    /// mmap[0] = 1; // first page
+    /// mmap[0x90_000] = 1; // tenth page
+    ///
+    /// assert_eq!(mmap.dirty_bitmap(), [0b0000_0001, 0b0000_0010]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`all_dirty_pages`]: Mmap::all_dirty_pages
+    /// [`mark_dirty`]: Mmap::mark_dirty
+    #[must_use]
+    pub fn dirty_bitmap(&self) -> Vec<u8> {
+        let mut bitmap = Vec::new();
+
+        for (_, _, page_index) in self.all_dirty_pages() {
+            let byte_index = page_index / 8;
+            if bitmap.len() <= byte_index {
+                bitmap.resize(byte_index + 1, 0);
+            }
+            bitmap[byte_index] |= 1 << (page_index % 8);
+        }
+
+        bitmap
+    }
+
+    /// Marks the pages set in the given `bitmap` as dirty, as if they were
+    /// written to, leaving their contents untouched.
+    ///
+    /// The bitmap is laid out as the one returned by [`dirty_bitmap`]. The
+    /// pages are dirtied in the current snapshot, with their current contents
+    /// as their clean counterparts.
+    ///
+    /// # Errors
+    /// If the bitmap sets a page past the end of the mmap, this function will
+    /// error with [`io::ErrorKind::InvalidInput`], leaving the mmap untouched.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use std::io;
+    /// # fn main() -> io::Result<()> {
+    /// use crumbles::Mmap;
+    ///
+    /// let mut mmap = Mmap::new(65536, 65536)?;
+    /// mmap.mark_dirty(&[0b0000_0101])?;
+    ///
+    /// let dirty_pages: Vec<_> =
+    ///     mmap.dirty_pages().map(|(_, _, index)| *index).collect();
+    ///
+    /// assert_eq!(dirty_pages, [0, 2]);
+    /// assert_eq!(mmap[0], 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`dirty_bitmap`]: Mmap::dirty_bitmap
+    pub fn mark_dirty(&mut self, bitmap: &[u8]) -> io::Result<()> {
+        let page_number = self.0.page_number;
+        if set_bits(bitmap).any(|page_index| page_index >= page_number) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Bitmap sets pages past the last page {page_number}"),
+            ));
+        }
+
+        let page_size = self.0.page_size;
+        for page_index in set_bits(bitmap) {
+            // Reading and writing back a byte of the page has it processed
+            // like any other write, dirtying it and keeping its clean copy.
+            let byte = ptr::addr_of_mut!(self.0.bytes[page_index * page_size]);
+            unsafe { ptr::write_volatile(byte, ptr::read_volatile(byte)) };
+        }
+
+        Ok(())
+    }
 }
 
 impl AsRef<[u8]> for Mmap {
@@ -388,19 +741,40 @@ where
     closure(&mut global_map)
 }
 
-#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
 fn system_page_size() -> usize {
-    static mut PAGE_SIZE: OnceLock<usize> = OnceLock::new();
-    unsafe {
-        *PAGE_SIZE.get_or_init(|| libc::sysconf(libc::_SC_PAGESIZE) as usize)
-    }
+    sys::page_size()
+}
+
+/// Returns an iterator over the indices of the bits set in the given `bytes`,
+/// in ascending order, with the lowest bits of each byte first.
+fn set_bits(bytes: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    bytes
+        .iter()
+        .enumerate()
+        .filter(|(_, byte)| **byte != 0)
+        .flat_map(|(byte_index, byte)| {
+            (0..8)
+                .filter(move |bit_index| byte & (1 << bit_index) != 0)
+                .map(move |bit_index| byte_index * 8 + bit_index)
+        })
+}
+
+/// The accesses allowed to a region of memory.
+#[derive(Debug, Clone, Copy)]
+enum Protection {
+    None,
+    Read,
+    ReadWrite,
 }
 
 /// Contains clean pages, together with a bitset of pages that have already been
-/// hit at least one SIGSEGV - i.e. marked as having been read.
+/// hit at least one SIGSEGV - i.e. marked as having been read. The hit pages
+/// are also kept in a list, to avoid scanning the bitset to find them.
 struct Snapshot {
     clean_pages: BTreeMap<usize, Vec<u8>>,
     hit_pages: PageBits,
+    hit_list: Vec<usize>,
+    label: Option<u64>,
 }
 
 impl Snapshot {
@@ -408,6 +782,8 @@ impl Snapshot {
         Ok(Self {
             clean_pages: BTreeMap::new(),
             hit_pages: PageBits::new(page_number)?,
+            hit_list: Vec::new(),
+            label: None,
         })
     }
 }
@@ -420,21 +796,8 @@ impl PageBits {
     fn new(page_number: usize) -> io::Result<Self> {
         let page_bits = unsafe {
             let len = page_number / 8 + usize::from(page_number % 8 != 0);
-
-            let ptr = libc::mmap(
-                ptr::null_mut(),
-                len,
-                PROT_READ | PROT_WRITE,
-                MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE,
-                -1,
-                0,
-            );
-
-            if ptr == MAP_FAILED {
-                return Err(io::Error::last_os_error());
-            }
-
-            slice::from_raw_parts_mut(ptr.cast(), len)
+            let ptr = sys::alloc_zeroed(len)?;
+            slice::from_raw_parts_mut(ptr, len)
         };
 
         Ok(Self(page_bits))
@@ -473,31 +836,27 @@ impl PageBits {
 impl Drop for PageBits {
     fn drop(&mut self) {
         unsafe {
-            let ptr = self.0.as_mut_ptr();
-            let len = self.0.len();
-
-            libc::munmap(ptr.cast(), len);
+            sys::free(self.0.as_mut_ptr(), self.0.len());
         }
     }
 }
 
 /// Types that can used to locate a file for a given page.
 pub trait LocateFile: Send + Sync {
-    /// Locate a file for the given page index.
+    /// Locate a file for the given page index, together with the offset in
+    /// the file at which the page starts.
     ///
-    /// # Errors
-    /// The function may return an error to signal that there was a problem
-    /// looking up a file for the given page index, but should use `Ok(None)`
+    /// The offset must be a multiple of the system page size. Returns `None`
     /// when there is no file for the given page index.
-    fn locate_file(&mut self, page_index: usize) -> Option<PathBuf>;
+    fn locate_file(&mut self, page_index: usize) -> Option<(PathBuf, u64)>;
 }
 
 impl<F> LocateFile for F
 where
-    F: FnMut(usize) -> Option<PathBuf>,
+    F: FnMut(usize) -> Option<(PathBuf, u64)>,
     F: Send + Sync,
 {
-    fn locate_file(&mut self, page_index: usize) -> Option<PathBuf> {
+    fn locate_file(&mut self, page_index: usize) -> Option<(PathBuf, u64)> {
         self(page_index)
     }
 }
@@ -513,6 +872,13 @@ struct MmapInner {
     snapshots: Vec<Snapshot>,
 
     file_locator: Box<dyn LocateFile>,
+    /// The file last mapped, kept open so that mapping more pages of the same
+    /// file doesn't open it again.
+    last_file: Option<(PathBuf, File)>,
+    /// Pages released by [`reclaim`], to be mapped again when next accessed.
+    ///
+    /// [`reclaim`]: MmapInner::reclaim
+    reclaimed: BTreeSet<usize>,
 }
 
 impl MmapInner {
@@ -524,7 +890,7 @@ impl MmapInner {
     where
         FL: 'static + LocateFile,
     {
-        setup_action();
+        sys::setup_handler();
 
         let system_page_size = system_page_size();
         if page_size % system_page_size != 0 {
@@ -542,21 +908,8 @@ impl MmapInner {
 
         let bytes = {
             let len = page_number * page_size;
-
-            let ptr = libc::mmap(
-                ptr::null_mut(),
-                len,
-                PROT_NONE,
-                MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE,
-                -1,
-                0,
-            );
-
-            if ptr == MAP_FAILED {
-                return Err(io::Error::last_os_error());
-            }
-
-            slice::from_raw_parts_mut(ptr.cast(), len)
+            let ptr = sys::reserve(len)?;
+            slice::from_raw_parts_mut(ptr, len)
         };
 
         Ok(Self {
@@ -567,6 +920,8 @@ impl MmapInner {
             // There should always be at least one snapshot
             snapshots: vec![snapshot],
             file_locator: Box::new(file_locator),
+            last_file: None,
+            reclaimed: BTreeSet::new(),
         })
     }
 
@@ -594,34 +949,47 @@ impl MmapInner {
         let page_addr = start_addr + page_offset;
         let page_size = self.page_size;
 
+        // A page released by reclaiming it is mapped again, as if it was never
+        // mapped.
+        let remapped = self.reclaimed.remove(&page_index);
+
         // Map the file given by the file locator, if any, at the given offset.
         // If we've already mapped it, we don't need to do so again.
         self.mapped_pages.set_and_exec(
             page_index,
             |is_bit_set| -> io::Result<()> {
-                if is_bit_set {
+                if is_bit_set && !remapped {
                     return Ok(());
                 }
 
-                if let Some(path) = self.file_locator.locate_file(page_index) {
-                    let file =
-                        OpenOptions::new().read(true).write(true).open(path)?;
-
-                    let ptr = libc::mmap(
-                        page_addr as _,
-                        page_size,
-                        PROT_NONE,
-                        MAP_PRIVATE | MAP_FIXED | MAP_NORESERVE,
-                        file.as_raw_fd(),
-                        0,
-                    );
+                let Some((path, offset)) =
+                    self.file_locator.locate_file(page_index)
+                else {
+                    return sys::map(page_addr as _, page_size, None);
+                };
+
+                if offset % system_page_size() as u64 != 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "Offset {offset} must be a multiple of the system \
+                             page size"
+                        ),
+                    ));
+                }
 
-                    if ptr == MAP_FAILED {
-                        return Err(io::Error::last_os_error());
+                let file = match &self.last_file {
+                    Some((last_path, file)) if *last_path == path => file,
+                    _ => {
+                        let file = OpenOptions::new()
+                            .read(true)
+                            .write(true)
+                            .open(&path)?;
+                        &self.last_file.insert((path, file)).1
                     }
-                }
+                };
 
-                Ok(())
+                sys::map(page_addr as _, page_size, Some((file, offset)))
             },
         )?;
 
@@ -632,39 +1000,39 @@ impl MmapInner {
 
         // If the page wasn't hit before, set read only permissions for the
         // page. If it was set before, we're writing and need to set read-write
-        // permissions, and mark the page as dirty.
+        // permissions, and mark the page as dirty - unless the page was just
+        // mapped again, in which case it's being read.
         snapshot.hit_pages.set_and_exec(page_index, |is_bit_set| {
-            let mut prot = PROT_READ;
+            let mut protection = Protection::Read;
 
-            if is_bit_set {
-                prot |= PROT_WRITE;
+            if is_bit_set && !remapped {
+                protection = Protection::ReadWrite;
 
                 if let Entry::Vacant(e) = snapshot.clean_pages.entry(page_index)
                 {
+                    // The page may have been protected from reads since it
+                    // was hit, such as when a snapshot is applied, so it is
+                    // made readable before being copied.
+                    sys::protect(page_addr as _, page_size, Protection::Read)?;
+
                     let mut clean_page = vec![0; page_size];
                     clean_page.copy_from_slice(
                         &self.bytes[page_offset..][..page_size],
                     );
                     e.insert(clean_page);
                 }
+            } else if !is_bit_set {
+                snapshot.hit_list.push(page_index);
             }
 
-            if libc::mprotect(page_addr as _, page_size, prot) != 0 {
-                return Err(io::Error::last_os_error());
-            }
-
-            Ok(())
+            sys::protect(page_addr as _, page_size, protection)
         })?;
 
         Ok(())
     }
 
     unsafe fn snap(&mut self) -> io::Result<()> {
-        let len = self.bytes.len();
-
-        if libc::mprotect(self.bytes.as_mut_ptr().cast(), len, PROT_NONE) != 0 {
-            return Err(io::Error::last_os_error());
-        }
+        self.protect_none()?;
 
         self.snapshots.push(Snapshot::new(self.page_number)?);
 
@@ -672,11 +1040,7 @@ impl MmapInner {
     }
 
     unsafe fn apply(&mut self) -> io::Result<()> {
-        let len = self.bytes.len();
-
-        if libc::mprotect(self.bytes.as_mut_ptr().cast(), len, PROT_NONE) != 0 {
-            return Err(io::Error::last_os_error());
-        }
+        self.protect_none()?;
 
         let popped_snapshot = self
             .snapshots
@@ -703,128 +1067,187 @@ impl MmapInner {
         if self.snapshots.is_empty() {
             self.snapshots.push(Snapshot::new(self.page_number)?);
         } else {
-            self.last_snapshot_mut().hit_pages =
-                PageBits::new(self.page_number)?;
+            let page_number = self.page_number;
+            let snapshot = self.last_snapshot_mut();
+            snapshot.hit_pages = PageBits::new(page_number)?;
+            snapshot.hit_list.clear();
         }
 
+        let start_addr = self.bytes.as_mut_ptr() as usize;
         let page_size = self.page_size;
 
         for (page_index, clean_page) in popped_snapshot.clean_pages {
             let page_offset = page_index * page_size;
+
+            // The page is made writable directly, so that restoring it is not
+            // taken by the signal handler as a write in the snapshot below.
+            let page_addr = start_addr + page_offset;
+            sys::protect(page_addr as _, page_size, Protection::ReadWrite)?;
+
             self.bytes[page_offset..][..page_size]
                 .copy_from_slice(&clean_page[..]);
         }
 
-        let len = self.bytes.len();
+        self.protect_none()?;
 
-        if libc::mprotect(self.bytes.as_mut_ptr().cast(), len, PROT_NONE) != 0 {
-            return Err(io::Error::last_os_error());
+        Ok(())
+    }
+
+    unsafe fn snap_labeled(&mut self, label: u64) -> io::Result<()> {
+        if self.labeled_position(label).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Snapshot {label} already exists"),
+            ));
         }
 
+        self.snap()?;
+        self.last_snapshot_mut().label = Some(label);
+
         Ok(())
     }
 
-    fn last_snapshot(&self) -> &Snapshot {
-        self.snapshots
-            .last()
-            .expect("There should always be at least one snapshot")
+    unsafe fn revert_to(&mut self, label: u64) -> io::Result<()> {
+        let position = self.labeled_position(label)?;
+        for _ in position..self.snapshots.len() {
+            self.revert()?;
+        }
+        Ok(())
     }
 
-    fn last_snapshot_mut(&mut self) -> &mut Snapshot {
+    unsafe fn apply_to(&mut self, label: u64) -> io::Result<()> {
+        let position = self.labeled_position(label)?;
+        for _ in position..self.snapshots.len() {
+            self.apply()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the position of the snapshot with the given `label` in the
+    /// stack of snapshots.
+    fn labeled_position(&self, label: u64) -> io::Result<usize> {
         self.snapshots
-            .last_mut()
-            .expect("There should always be at least one snapshot")
+            .iter()
+            .rposition(|snapshot| snapshot.label == Some(label))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No snapshot {label}"),
+                )
+            })
     }
-}
 
-impl Drop for MmapInner {
-    fn drop(&mut self) {
-        unsafe {
-            let ptr = self.bytes.as_mut_ptr();
-            let len = self.bytes.len();
+    unsafe fn reclaim(&mut self) -> io::Result<usize> {
+        let dirty_pages: BTreeSet<usize> = self
+            .snapshots
+            .iter()
+            .flat_map(|snapshot| snapshot.clean_pages.keys().copied())
+            .collect();
 
-            libc::munmap(ptr.cast(), len);
-        }
-    }
-}
+        let start_addr = self.bytes.as_mut_ptr() as usize;
+        let page_size = self.page_size;
 
-static SIGNAL_HANDLER: Once = Once::new();
+        let runs =
+            self.mapped_runs(|page_index| !dirty_pages.contains(&page_index));
 
-// Sets up [`segfault_handler`] to handle SIGSEGV, and returns the previous
-// action used to handle it, if any.
-unsafe fn setup_action() -> sigaction {
-    static OLD_ACTION: OnceLock<sigaction> = OnceLock::new();
+        let mut reclaimed = 0;
+        for run in runs {
+            let addr = start_addr + run.start * page_size;
+            sys::release(addr as _, run.len() * page_size)?;
 
-    SIGNAL_HANDLER.call_once(|| {
-        let mut sa_mask = MaybeUninit::<sigset_t>::uninit();
-        sigemptyset(sa_mask.as_mut_ptr());
+            reclaimed += run.len();
+            self.reclaimed.extend(run);
+        }
 
-        let act = sigaction {
-            sa_sigaction: segfault_handler as _,
-            sa_mask: sa_mask.assume_init(),
-            sa_flags: SA_SIGINFO,
-            #[cfg(target_os = "linux")]
-            sa_restorer: None,
-        };
-        let mut old_act = MaybeUninit::<sigaction>::uninit();
+        Ok(reclaimed)
+    }
+
+    /// Makes every page inaccessible, so that the next access to each of them
+    /// is caught.
+    unsafe fn protect_none(&mut self) -> io::Result<()> {
+        let start_addr = self.bytes.as_mut_ptr() as usize;
 
-        if libc::sigaction(libc::SIGSEGV, &act, old_act.as_mut_ptr()) != 0 {
-            process::exit(1);
+        if sys::PROTECTS_RESERVED {
+            return sys::protect(
+                start_addr as _,
+                self.bytes.len(),
+                Protection::None,
+            );
         }
 
-        // On Apple Silicon for some reason SIGBUS is thrown instead of SIGSEGV.
-        // TODO should investigate properly
-        #[cfg(target_os = "macos")]
-        if libc::sigaction(libc::SIGBUS, &act, old_act.as_mut_ptr()) != 0 {
-            process::exit(2);
+        let page_size = self.page_size;
+        for run in self.mapped_runs(|_| true) {
+            let addr = start_addr + run.start * page_size;
+            sys::protect(addr as _, run.len() * page_size, Protection::None)?;
         }
 
-        OLD_ACTION.get_or_init(move || old_act.assume_init());
-    });
+        Ok(())
+    }
 
-    *OLD_ACTION.get().unwrap()
-}
+    /// Returns the contiguous runs of mapped pages, not released by reclaiming
+    /// them, passing the given `filter`.
+    fn mapped_runs<F>(&self, filter: F) -> Vec<Range<usize>>
+    where
+        F: Fn(usize) -> bool,
+    {
+        let mut runs: Vec<Range<usize>> = Vec::new();
+
+        for page_index in self.accessed_pages() {
+            if self.reclaimed.contains(&page_index) || !filter(page_index) {
+                continue;
+            }
+            match runs.last_mut() {
+                Some(run) if run.end == page_index => run.end += 1,
+                _ => runs.push(Range {
+                    start: page_index,
+                    end: page_index + 1,
+                }),
+            }
+        }
 
-/// Calls the old action that was set to handle `SIGSEGV`
-unsafe fn call_old_action(
-    sig: c_int,
-    info: *mut siginfo_t,
-    ctx: *mut ucontext_t,
-) {
-    let old_act = setup_action();
-
-    // If SA_SIGINFO is set, the old action is a `fn(c_int, *mut siginfo_t, *mut
-    // ucontext_t)`. Otherwise, it's a `fn(c_int)`.
-    if old_act.sa_flags & SA_SIGINFO == 0 {
-        let act: fn(c_int) = mem::transmute(old_act.sa_sigaction);
-        act(sig);
-    } else {
-        let act: fn(c_int, *mut siginfo_t, *mut ucontext_t) =
-            mem::transmute(old_act.sa_sigaction);
-        act(sig, info, ctx);
+        runs
     }
-}
 
-unsafe fn segfault_handler(
-    sig: c_int,
-    info: *mut siginfo_t,
-    ctx: *mut ucontext_t,
-) {
-    with_global_map(move |global_map| {
-        let si_addr = (*info).si_addr() as usize;
+    /// Returns an iterator over the indices of the pages that have been
+    /// mapped, in ascending order.
+    fn accessed_pages(&self) -> impl Iterator<Item = usize> + '_ {
+        let page_number = self.page_number;
 
-        if let Some(inner_ptr) = global_map.get(&si_addr) {
-            let inner = &mut *(*inner_ptr as *mut MmapInner);
+        set_bits(self.mapped_pages.0)
+            .filter(move |page_index| *page_index < page_number)
+    }
 
-            if inner.process_segv(si_addr).is_err() {
-                call_old_action(sig, info, ctx);
-            }
+    fn last_snapshot(&self) -> &Snapshot {
+        self.snapshots
+            .last()
+            .expect("There should always be at least one snapshot")
+    }
 
-            return;
+    fn last_snapshot_mut(&mut self) -> &mut Snapshot {
+        self.snapshots
+            .last_mut()
+            .expect("There should always be at least one snapshot")
+    }
+}
+
+impl Drop for MmapInner {
+    fn drop(&mut self) {
+        unsafe {
+            sys::free(self.bytes.as_mut_ptr(), self.bytes.len());
         }
+    }
+}
 
-        call_old_action(sig, info, ctx);
-    });
+/// Processes an access to protected memory at the given address, returning
+/// whether it was handled. Accesses outside the memory of any mmap are not.
+unsafe fn handle_fault(addr: usize) -> bool {
+    with_global_map(move |global_map| match global_map.get(&addr) {
+        Some(inner_ptr) => {
+            let inner = &mut *(*inner_ptr as *mut MmapInner);
+            inner.process_segv(addr).is_ok()
+        }
+        None => false,
+    })
 }
 
 #[cfg(test)]
@@ -907,6 +1330,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn read_after_apply() {
+        let mut mem = Mmap::new(N_PAGES, PAGE_SIZE)
+            .expect("Instantiating new memory should succeed");
+
+        // Only read the page before snapshotting, so no clean copy of it is
+        // taken
+        assert_eq!(mem[OFFSET], 0);
+
+        mem.snap().expect("Snapshotting should succeed");
+        mem.apply().expect("Applying should succeed");
+
+        assert_eq!(mem[OFFSET], 0, "Reading after applying should succeed");
+
+        mem[OFFSET] = 1;
+        assert_eq!(mem[OFFSET], 1);
+    }
+
+    #[test]
+    fn revert_after_apply() {
+        let mut mem = Mmap::new(N_PAGES, PAGE_SIZE)
+            .expect("Instantiating new memory should succeed");
+
+        mem.snap().expect("Snapshotting should succeed");
+        mem.snap().expect("Snapshotting should succeed");
+
+        mem[OFFSET] = 1;
+        mem.apply().expect("Applying should succeed");
+        mem.revert().expect("Reverting should succeed");
+
+        // Restoring the page doesn't dirty it in the snapshot below
+        assert_eq!(mem[OFFSET], 0);
+        assert_eq!(mem.all_dirty_pages().count(), 0);
+    }
+
     #[test]
     fn multi_revert() {
         let mut mem = Mmap::new(N_PAGES, PAGE_SIZE)
@@ -1049,4 +1507,125 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn with_files_at_offsets() {
+        let path = std::env::temp_dir()
+            .join(format!("crumbles-offsets-{}", std::process::id()));
+
+        let mut contents = vec![1; PAGE_SIZE];
+        contents.extend([2; PAGE_SIZE]);
+        std::fs::write(&path, &contents).expect("Writing should succeed");
+
+        // Map the pages of the file in reverse order
+        let locator_path = path.clone();
+        let mem = unsafe {
+            Mmap::with_files(N_PAGES, PAGE_SIZE, move |page_index| {
+                match page_index {
+                    0 => Some((locator_path.clone(), PAGE_SIZE as u64)),
+                    1 => Some((locator_path.clone(), 0)),
+                    _ => None,
+                }
+            })
+            .expect("Instantiating new memory should succeed")
+        };
+
+        assert_eq!(mem[..PAGE_SIZE], [2; PAGE_SIZE]);
+        assert_eq!(mem[PAGE_SIZE..][..PAGE_SIZE], [1; PAGE_SIZE]);
+        assert_eq!(mem[2 * PAGE_SIZE], 0);
+
+        std::fs::remove_file(path).expect("Removing should succeed");
+    }
+
+    #[test]
+    fn reclaim() {
+        let path = std::env::temp_dir()
+            .join(format!("crumbles-reclaim-{}", std::process::id()));
+        std::fs::write(&path, DIRT).expect("Writing should succeed");
+
+        let locator_path = path.clone();
+        let mut mem = unsafe {
+            Mmap::with_files(N_PAGES, PAGE_SIZE, move |page_index| {
+                (page_index < 2).then(|| {
+                    (locator_path.clone(), (page_index * PAGE_SIZE) as u64)
+                })
+            })
+            .expect("Instantiating new memory should succeed")
+        };
+
+        assert_eq!(mem[..2 * PAGE_SIZE], DIRT);
+        mem[OFFSET] = 0;
+        mem.snap().expect("Snapshotting should succeed");
+
+        assert_eq!(mem.reclaim().expect("Reclaiming should succeed"), 1);
+        assert_eq!(mem[..PAGE_SIZE], DIRT[..PAGE_SIZE]);
+        assert_eq!(mem[OFFSET], 0);
+        assert_eq!(mem.all_dirty_pages().count(), 1);
+
+        std::fs::remove_file(path).expect("Removing should succeed");
+    }
+
+    #[test]
+    fn labeled_snapshots() {
+        let mut mem = Mmap::new(N_PAGES, PAGE_SIZE)
+            .expect("Instantiating new memory should succeed");
+
+        mem.snap_labeled(1).expect("Snapshotting should succeed");
+        mem[OFFSET..][..DIRT.len()].copy_from_slice(&DIRT);
+        mem.snap_labeled(2).expect("Snapshotting should succeed");
+        mem[OFFSET..][..DIRT2.len()].copy_from_slice(&DIRT2);
+        mem.snap().expect("Snapshotting should succeed");
+        mem[0] = 1;
+
+        let err = mem.snap_labeled(1).expect_err("Label should be in use");
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        let err = mem.revert_to(3).expect_err("Label should not exist");
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let err = mem.apply_to(3).expect_err("Label should not exist");
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(mem[0], 1, "Misuse should leave the memory untouched");
+
+        mem.revert_to(2).expect("Reverting should succeed");
+        assert_eq!(mem[0], 0);
+        assert_eq!(mem[OFFSET..][..DIRT.len()], DIRT);
+
+        // The label can be reused once its snapshot is gone
+        mem.snap_labeled(2).expect("Snapshotting should succeed");
+        mem[0] = 1;
+        mem.apply_to(1).expect("Applying should succeed");
+        assert_eq!(mem[0], 1);
+        assert_eq!(mem.dirty_pages().count(), 4);
+
+        mem.revert_to(2)
+            .expect_err("Applied snapshots should be gone");
+    }
+
+    #[test]
+    fn dirty_bitmap() {
+        let mut mem = Mmap::new(N_PAGES, PAGE_SIZE)
+            .expect("Instantiating new memory should succeed");
+
+        mem[OFFSET..][..DIRT.len()].copy_from_slice(&DIRT);
+        mem.snap().expect("Snapshotting should succeed");
+        mem[70 * PAGE_SIZE] = 1;
+
+        let bitmap = mem.dirty_bitmap();
+        assert_eq!(bitmap.len(), 9);
+
+        let mut other = Mmap::new(N_PAGES, PAGE_SIZE)
+            .expect("Instantiating new memory should succeed");
+        other.mark_dirty(&bitmap).expect("Marking should succeed");
+
+        let dirty_pages: Vec<_> =
+            other.dirty_pages().map(|(_, _, index)| *index).collect();
+        assert_eq!(dirty_pages, [1, 2, 3, 70]);
+        assert_eq!(other.dirty_bitmap(), bitmap);
+
+        let mut too_long = vec![0; N_PAGES / 8];
+        too_long.push(1);
+        let err = other
+            .mark_dirty(&too_long)
+            .expect_err("Marking past the end should fail");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
 }
@@ -0,0 +1,202 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! The Unix backend, mapping memory with `mmap` and catching accesses to it
+//! with a `SIGSEGV` handler.
+
+use std::{
+    fs::File,
+    mem::{self, MaybeUninit},
+    os::fd::AsRawFd,
+    sync::{Once, OnceLock},
+    {io, process, ptr},
+};
+
+use libc::{
+    c_int, sigaction, sigemptyset, siginfo_t, sigset_t, ucontext_t,
+    MADV_DONTNEED, MAP_ANONYMOUS, MAP_FAILED, MAP_FIXED, MAP_NORESERVE,
+    MAP_PRIVATE, PROT_NONE, PROT_READ, PROT_WRITE, SA_SIGINFO,
+};
+
+use crate::Protection;
+
+/// Protections can be set on reserved memory that was never mapped.
+pub const PROTECTS_RESERVED: bool = true;
+
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+pub fn page_size() -> usize {
+    static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+    *PAGE_SIZE
+        .get_or_init(|| unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize })
+}
+
+/// Reserves `len` bytes of memory, inaccessible until mapped.
+pub unsafe fn reserve(len: usize) -> io::Result<*mut u8> {
+    anonymous(len, PROT_NONE)
+}
+
+/// Allocates `len` bytes of zeroed, readable and writable memory.
+pub unsafe fn alloc_zeroed(len: usize) -> io::Result<*mut u8> {
+    anonymous(len, PROT_READ | PROT_WRITE)
+}
+
+unsafe fn anonymous(len: usize, prot: c_int) -> io::Result<*mut u8> {
+    let ptr = libc::mmap(
+        ptr::null_mut(),
+        len,
+        prot,
+        MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE,
+        -1,
+        0,
+    );
+
+    if ptr == MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(ptr.cast())
+}
+
+/// Frees memory reserved or allocated by this module.
+pub unsafe fn free(ptr: *mut u8, len: usize) {
+    libc::munmap(ptr.cast(), len);
+}
+
+/// Maps the `file`, from the given `offset`, over the reserved memory at
+/// `addr`, leaving it inaccessible. Reserved memory reads as zeros when
+/// accessed, so there's nothing to do without a file.
+pub unsafe fn map(
+    addr: *mut u8,
+    len: usize,
+    file: Option<(&File, u64)>,
+) -> io::Result<()> {
+    let Some((file, offset)) = file else {
+        return Ok(());
+    };
+
+    let offset = libc::off_t::try_from(offset).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, "Offset is too large")
+    })?;
+
+    let ptr = libc::mmap(
+        addr.cast(),
+        len,
+        PROT_NONE,
+        MAP_PRIVATE | MAP_FIXED | MAP_NORESERVE,
+        file.as_raw_fd(),
+        offset,
+    );
+
+    if ptr == MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+pub unsafe fn protect(
+    addr: *mut u8,
+    len: usize,
+    protection: Protection,
+) -> io::Result<()> {
+    let prot = match protection {
+        Protection::None => PROT_NONE,
+        Protection::Read => PROT_READ,
+        Protection::ReadWrite => PROT_READ | PROT_WRITE,
+    };
+
+    if libc::mprotect(addr.cast(), len, prot) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Drops the contents of the mapped memory at `addr`, to be read again from
+/// its files - or as zeros - the next time it's accessed.
+pub unsafe fn release(addr: *mut u8, len: usize) -> io::Result<()> {
+    // Contents are dropped rather than lazily freed, so that the next access
+    // reads them from their files deterministically.
+    if libc::madvise(addr.cast(), len, MADV_DONTNEED) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+static SIGNAL_HANDLER: Once = Once::new();
+
+/// Sets up the handler of accesses to the memory of mmaps.
+pub unsafe fn setup_handler() {
+    setup_action();
+}
+
+// Sets up [`segfault_handler`] to handle SIGSEGV, and returns the previous
+// action used to handle it, if any.
+unsafe fn setup_action() -> sigaction {
+    static OLD_ACTION: OnceLock<sigaction> = OnceLock::new();
+
+    SIGNAL_HANDLER.call_once(|| {
+        let mut sa_mask = MaybeUninit::<sigset_t>::uninit();
+        sigemptyset(sa_mask.as_mut_ptr());
+
+        let act = sigaction {
+            sa_sigaction: segfault_handler as _,
+            sa_mask: sa_mask.assume_init(),
+            sa_flags: SA_SIGINFO,
+            #[cfg(target_os = "linux")]
+            sa_restorer: None,
+        };
+        let mut old_act = MaybeUninit::<sigaction>::uninit();
+
+        if libc::sigaction(libc::SIGSEGV, &act, old_act.as_mut_ptr()) != 0 {
+            process::exit(1);
+        }
+
+        // On Apple Silicon for some reason SIGBUS is thrown instead of SIGSEGV.
+        // TODO should investigate properly
+        #[cfg(target_os = "macos")]
+        if libc::sigaction(libc::SIGBUS, &act, old_act.as_mut_ptr()) != 0 {
+            process::exit(2);
+        }
+
+        OLD_ACTION.get_or_init(move || old_act.assume_init());
+    });
+
+    *OLD_ACTION.get().unwrap()
+}
+
+/// Calls the old action that was set to handle `SIGSEGV`
+unsafe fn call_old_action(
+    sig: c_int,
+    info: *mut siginfo_t,
+    ctx: *mut ucontext_t,
+) {
+    let old_act = setup_action();
+
+    // If SA_SIGINFO is set, the old action is a `fn(c_int, *mut siginfo_t, *mut
+    // ucontext_t)`. Otherwise, it's a `fn(c_int)`.
+    if old_act.sa_flags & SA_SIGINFO == 0 {
+        let act: fn(c_int) = mem::transmute(old_act.sa_sigaction);
+        act(sig);
+    } else {
+        let act: fn(c_int, *mut siginfo_t, *mut ucontext_t) =
+            mem::transmute(old_act.sa_sigaction);
+        act(sig, info, ctx);
+    }
+}
+
+unsafe fn segfault_handler(
+    sig: c_int,
+    info: *mut siginfo_t,
+    ctx: *mut ucontext_t,
+) {
+    let si_addr = (*info).si_addr() as usize;
+
+    if !crate::handle_fault(si_addr) {
+        call_old_action(sig, info, ctx);
+    }
+}
@@ -0,0 +1,168 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! The Windows backend, committing memory with `VirtualAlloc` and catching
+//! accesses to it with a vectored exception handler.
+//!
+//! Windows can't map a file over part of a reserved region, so the contents
+//! of the file backing a page are read into it when it's committed instead.
+//! Since mappings are private, and never written back to their files, this
+//! behaves the same as mapping them.
+
+use std::{
+    fs::File,
+    mem::MaybeUninit,
+    os::windows::fs::FileExt,
+    ptr, slice,
+    sync::{Once, OnceLock},
+    {io, process},
+};
+
+use windows_sys::Win32::{
+    Foundation::EXCEPTION_ACCESS_VIOLATION,
+    System::{
+        Diagnostics::Debug::{
+            AddVectoredExceptionHandler, EXCEPTION_CONTINUE_EXECUTION,
+            EXCEPTION_CONTINUE_SEARCH, EXCEPTION_POINTERS,
+        },
+        Memory::{
+            VirtualAlloc, VirtualFree, VirtualProtect, MEM_COMMIT,
+            MEM_DECOMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_NOACCESS,
+            PAGE_PROTECTION_FLAGS, PAGE_READONLY, PAGE_READWRITE,
+        },
+        SystemInformation::GetSystemInfo,
+    },
+};
+
+use crate::Protection;
+
+/// Protections can only be set on committed memory, so reserved memory that
+/// was never mapped must be skipped.
+pub const PROTECTS_RESERVED: bool = false;
+
+pub fn page_size() -> usize {
+    static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+    *PAGE_SIZE.get_or_init(|| unsafe {
+        let mut info = MaybeUninit::uninit();
+        GetSystemInfo(info.as_mut_ptr());
+        info.assume_init().dwPageSize as usize
+    })
+}
+
+/// Reserves `len` bytes of memory, inaccessible until mapped.
+pub unsafe fn reserve(len: usize) -> io::Result<*mut u8> {
+    let ptr = VirtualAlloc(ptr::null(), len, MEM_RESERVE, PAGE_NOACCESS);
+    if ptr.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ptr.cast())
+}
+
+/// Allocates `len` bytes of zeroed, readable and writable memory.
+pub unsafe fn alloc_zeroed(len: usize) -> io::Result<*mut u8> {
+    let ptr = VirtualAlloc(
+        ptr::null(),
+        len,
+        MEM_RESERVE | MEM_COMMIT,
+        PAGE_READWRITE,
+    );
+    if ptr.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ptr.cast())
+}
+
+/// Frees memory reserved or allocated by this module.
+pub unsafe fn free(ptr: *mut u8, _len: usize) {
+    VirtualFree(ptr.cast(), 0, MEM_RELEASE);
+}
+
+/// Commits the reserved memory at `addr`, filling it with the contents of the
+/// `file` from the given `offset`, and leaving it inaccessible.
+pub unsafe fn map(
+    addr: *mut u8,
+    len: usize,
+    file: Option<(&File, u64)>,
+) -> io::Result<()> {
+    if VirtualAlloc(addr.cast(), len, MEM_COMMIT, PAGE_READWRITE).is_null() {
+        return Err(io::Error::last_os_error());
+    }
+
+    if let Some((file, offset)) = file {
+        let page = slice::from_raw_parts_mut(addr, len);
+
+        // Bytes past the end of the file are left as zeros.
+        let mut read = 0;
+        while read < len {
+            match file.seek_read(&mut page[read..], offset + read as u64)? {
+                0 => break,
+                n => read += n,
+            }
+        }
+    }
+
+    protect(addr, len, Protection::None)
+}
+
+pub unsafe fn protect(
+    addr: *mut u8,
+    len: usize,
+    protection: Protection,
+) -> io::Result<()> {
+    let flags = match protection {
+        Protection::None => PAGE_NOACCESS,
+        Protection::Read => PAGE_READONLY,
+        Protection::ReadWrite => PAGE_READWRITE,
+    };
+
+    let mut old_flags: PAGE_PROTECTION_FLAGS = 0;
+    if VirtualProtect(addr.cast(), len, flags, &mut old_flags) == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Decommits the mapped memory at `addr`, to be mapped again the next time
+/// it's accessed.
+pub unsafe fn release(addr: *mut u8, len: usize) -> io::Result<()> {
+    if VirtualFree(addr.cast(), len, MEM_DECOMMIT) == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+static EXCEPTION_HANDLER: Once = Once::new();
+
+/// Sets up the handler of accesses to the memory of mmaps.
+pub unsafe fn setup_handler() {
+    EXCEPTION_HANDLER.call_once(|| {
+        // The handler is called first, ahead of any other, since it only
+        // handles accesses to the memory of mmaps.
+        if AddVectoredExceptionHandler(1, Some(exception_handler)).is_null() {
+            process::exit(1);
+        }
+    });
+}
+
+unsafe extern "system" fn exception_handler(
+    info: *mut EXCEPTION_POINTERS,
+) -> i32 {
+    let record = &*(*info).ExceptionRecord;
+    if record.ExceptionCode != EXCEPTION_ACCESS_VIOLATION {
+        return EXCEPTION_CONTINUE_SEARCH;
+    }
+
+    // The second parameter of an access violation is the address accessed.
+    let addr = record.ExceptionInformation[1];
+
+    if crate::handle_fault(addr) {
+        EXCEPTION_CONTINUE_EXECUTION
+    } else {
+        EXCEPTION_CONTINUE_SEARCH
+    }
+}
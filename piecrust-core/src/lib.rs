@@ -0,0 +1,22 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! The execution core of the [`piecrust`] virtual machine.
+//!
+//! This crate exposes the store of commits, the sessions opened on top of
+//! them, and the contracts' bytecode, modules, and memories, for building
+//! frontends other than the [`VM`] - such as debuggers, differential testers,
+//! or alternative stores - without forking the workspace.
+//!
+//! The API is semi-stable, and versioned by [`INTERNALS_VERSION`], which is
+//! bumped on every breaking change.
+//!
+//! [`VM`]: piecrust::VM
+
+#![deny(missing_docs)]
+
+pub use piecrust;
+pub use piecrust::internals::*;
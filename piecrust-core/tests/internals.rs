@@ -0,0 +1,25 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust_core::piecrust::{SessionData, VM};
+use piecrust_core::{ContractStore, INTERNALS_VERSION};
+
+#[test]
+fn store_sessions() -> Result<(), Box<dyn std::error::Error>> {
+    assert_eq!(INTERNALS_VERSION, 1);
+
+    let vm = VM::ephemeral()?;
+    let root = vm.session(SessionData::builder())?.commit()?;
+
+    let store: &ContractStore = vm.contract_store();
+    assert_eq!(store.commits(), vec![root.into()]);
+
+    // A session opened through the store sees the same state as the VM's
+    let session = store.session(root.into())?;
+    assert_eq!(session.root(), root.into());
+
+    Ok(())
+}
@@ -6,22 +6,58 @@
 
 use core::fmt::{self, Write};
 
+use crate::UPLINK_VERSION;
+
 mod allocator;
+#[cfg(feature = "dlmalloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dlmalloc")))]
+pub use allocator::*;
 
 mod handlers;
 
 mod helpers;
 pub use helpers::*;
 
+mod interface;
+
+mod pure;
+
+mod schema;
+
 mod state;
 pub use state::*;
 
+#[cfg(feature = "balance")]
+#[cfg_attr(docsrs, doc(cfg(feature = "balance")))]
+mod balance;
+#[cfg(feature = "balance")]
+pub use balance::*;
+
 #[cfg(feature = "debug")]
 #[cfg_attr(docsrs, doc(cfg(feature = "debug")))]
 mod debug;
 #[cfg(feature = "debug")]
 pub use debug::*;
 
+/// The version of this crate, embedded in a custom section of the contract so
+/// the host can know which ABI it was compiled against.
+#[used]
+#[link_section = "piecrust-uplink"]
+static UPLINK_VERSION_BYTES: [u8; UPLINK_VERSION.len()] = version_bytes();
+
+const fn version_bytes() -> [u8; UPLINK_VERSION.len()] {
+    let version = UPLINK_VERSION.as_bytes();
+    let mut bytes = [0u8; UPLINK_VERSION.len()];
+
+    let mut i = 0;
+    while i < bytes.len() {
+        bytes[i] = version[i];
+        i += 1;
+    }
+
+    bytes
+}
+
 /// A small struct that can `fmt::Write` to the argument buffer.
 ///
 /// It is just an offset to the argument buffer, representing how much has been
@@ -42,7 +78,7 @@ impl Write for ArgbufWriter {
 
         let new_ofs = self.0 + bytes_len;
 
-        if new_ofs > crate::ARGBUF_LEN {
+        if new_ofs > state::arg_buf_len() {
             return Err(fmt::Error);
         }
 
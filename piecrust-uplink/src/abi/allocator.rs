@@ -5,5 +5,118 @@
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
 #[cfg(feature = "dlmalloc")]
-#[global_allocator]
-static ALLOC: dlmalloc::GlobalDlmalloc = dlmalloc::GlobalDlmalloc;
+pub use stats::*;
+
+#[cfg(feature = "dlmalloc")]
+mod stats {
+    use core::alloc::{GlobalAlloc, Layout};
+
+    use dlmalloc::GlobalDlmalloc;
+
+    #[global_allocator]
+    static ALLOC: CountingDlmalloc = CountingDlmalloc(GlobalDlmalloc);
+
+    /// The number of bytes allocated, followed by the largest number of bytes
+    /// allocated at once since the host last reset it.
+    ///
+    /// It is exported so the host can reset the peak at the start of each
+    /// call, and report it once the call is done.
+    #[no_mangle]
+    static mut H: [u64; 2] = [0; 2];
+
+    const WASM_PAGE_SIZE: usize = 0x10000;
+
+    /// Statistics on the heap managed by the builtin allocator, as returned
+    /// by [`heap_stats`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct HeapStats {
+        /// The size of the memory the heap is allocated in, in bytes.
+        pub size: usize,
+        /// The number of bytes currently allocated.
+        pub used: usize,
+        /// The largest number of bytes allocated at once during the current
+        /// call.
+        pub peak: usize,
+    }
+
+    /// Returns statistics on the heap of the contract.
+    pub fn heap_stats() -> HeapStats {
+        let (used, peak) = unsafe { (H[0], H[1]) };
+
+        HeapStats {
+            size: memory_size() * WASM_PAGE_SIZE,
+            used: used as usize,
+            peak: peak as usize,
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn memory_size() -> usize {
+        core::arch::wasm32::memory_size(0)
+    }
+
+    #[cfg(target_arch = "wasm64")]
+    fn memory_size() -> usize {
+        core::arch::wasm64::memory_size(0)
+    }
+
+    #[cfg(not(any(target_arch = "wasm32", target_arch = "wasm64")))]
+    fn memory_size() -> usize {
+        0
+    }
+
+    fn record_alloc(size: usize) {
+        unsafe {
+            H[0] += size as u64;
+            if H[0] > H[1] {
+                H[1] = H[0];
+            }
+        }
+    }
+
+    fn record_dealloc(size: usize) {
+        unsafe {
+            H[0] -= size as u64;
+        }
+    }
+
+    /// Wraps `dlmalloc`, counting the bytes allocated.
+    struct CountingDlmalloc(GlobalDlmalloc);
+
+    unsafe impl GlobalAlloc for CountingDlmalloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = self.0.alloc(layout);
+            if !ptr.is_null() {
+                record_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            let ptr = self.0.alloc_zeroed(layout);
+            if !ptr.is_null() {
+                record_alloc(layout.size());
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            self.0.dealloc(ptr, layout);
+            record_dealloc(layout.size());
+        }
+
+        unsafe fn realloc(
+            &self,
+            ptr: *mut u8,
+            layout: Layout,
+            new_size: usize,
+        ) -> *mut u8 {
+            let new_ptr = self.0.realloc(ptr, layout, new_size);
+            if !new_ptr.is_null() {
+                record_dealloc(layout.size());
+                record_alloc(new_size);
+            }
+            new_ptr
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use crate::abi::state::with_arg_buf;
+use crate::{ContractId, TransferError, CONTRACT_ID_BYTES};
+
+mod ext {
+    extern "C" {
+        pub fn hbalance() -> u64;
+        pub fn htransfer(amount: u64) -> i32;
+        pub fn hself_destruct() -> i32;
+    }
+}
+
+/// Returns the balance of the calling contract, as kept by the host.
+pub fn balance() -> u64 {
+    unsafe { ext::hbalance() }
+}
+
+/// Transfers the given `amount` out of the balance of the calling contract,
+/// and into the balance of the contract with the given ID.
+///
+/// Transfers are undone if the call they were made in fails, and are charged
+/// for as set in the host's gas schedule.
+pub fn transfer(to: ContractId, amount: u64) -> Result<(), TransferError> {
+    with_arg_buf(|buf| buf[..CONTRACT_ID_BYTES].copy_from_slice(to.as_bytes()));
+
+    match unsafe { ext::htransfer(amount) } {
+        0 => Ok(()),
+        code => Err(TransferError::from_code(code)),
+    }
+}
+
+/// Destroys the calling contract, transferring its whole balance to the
+/// contract with the given ID.
+///
+/// The contract is removed from the state once the call it was destroyed in
+/// succeeds. Until then, it can no longer be called or transferred to. Should
+/// the call fail, the contract is not destroyed and the transfer is undone.
+///
+/// The `beneficiary` may not be the calling contract itself.
+pub fn self_destruct(beneficiary: ContractId) -> Result<(), TransferError> {
+    with_arg_buf(|buf| {
+        buf[..CONTRACT_ID_BYTES].copy_from_slice(beneficiary.as_bytes())
+    });
+
+    match unsafe { ext::hself_destruct() } {
+        0 => Ok(()),
+        code => Err(TransferError::from_code(code)),
+    }
+}
@@ -4,6 +4,8 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
+use core::fmt::Display;
+
 use crate::abi::state::with_arg_buf;
 use crate::SCRATCH_BUF_BYTES;
 
@@ -49,6 +51,35 @@ where
     })
 }
 
+/// Wrap a call to a fallible function with its respective (de)serializers,
+/// propagating its error.
+/// Checks integrity of the arguments.
+///
+/// Should the function return `Ok`, only the value it holds is returned to the
+/// caller. Should it return `Err`, the call fails with the error as the
+/// message of a [`ContractError::Panic`], just as if the contract had panicked
+/// with it, and any change made by the call is undone. To return the error to
+/// the caller without failing the call, use [`wrap_call`] with the function
+/// returning a `Result` instead.
+///
+/// Returns the length of result written to the buffer.
+///
+/// [`ContractError::Panic`]: crate::ContractError::Panic
+pub fn wrap_call_fallible<A, T, E, F>(arg_len: u32, f: F) -> u32
+where
+    A: Archive,
+    A::Archived: Deserialize<A, Infallible>
+        + for<'b> bytecheck::CheckBytes<DefaultValidator<'b>>,
+    T: for<'a> Serialize<StandardBufSerializer<'a>>,
+    E: Display,
+    F: Fn(A) -> Result<T, E>,
+{
+    wrap_call(arg_len, |arg| match f(arg) {
+        Ok(ret) => ret,
+        Err(err) => panic!("{err}"),
+    })
+}
+
 /// Wrap a call with its respective (de)serializers.
 /// Does not check the integrity of arguments.
 ///
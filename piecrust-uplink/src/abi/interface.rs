@@ -0,0 +1,104 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+/// Macro to declare a typed client for calling another contract.
+///
+/// Each function declared in the interface becomes a method of the client,
+/// calling the function of the same name with [`call_with_limit`]. The
+/// arguments are passed in the same way [`wrap_call`] expects them: `()` when
+/// there are none, the argument itself when there is one, and a tuple of them
+/// otherwise. A function without a return type returns `()`.
+///
+/// This ensures that the names of the functions called, and the types of their
+/// arguments and returns, are checked at compile time rather than at runtime.
+///
+/// # Example
+/// ```ignore
+/// use piecrust_uplink::{contract_interface, ContractId};
+///
+/// contract_interface! {
+///     /// Client for the counter contract
+///     pub struct Counter {
+///         fn read_value() -> i64;
+///         fn increment();
+///     }
+/// }
+///
+/// fn increment_twice(counter: ContractId) -> i64 {
+///     let counter = Counter::new(counter);
+///     counter.increment().unwrap();
+///     counter.with_limit(10_000).increment().unwrap();
+///     counter.read_value().unwrap()
+/// }
+/// ```
+///
+/// [`call_with_limit`]: crate::call_with_limit
+/// [`wrap_call`]: crate::wrap_call
+#[macro_export]
+macro_rules! contract_interface {
+    (@ret) => { () };
+    (@ret $ret:ty) => { $ret };
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$fn_meta:meta])*
+                fn $fn_name:ident($($arg:ident: $arg_ty:ty),* $(,)?)
+                    $(-> $ret:ty)?;
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        $vis struct $name {
+            contract: $crate::ContractId,
+            gas_limit: u64,
+        }
+
+        impl $name {
+            /// Creates a client calling the contract with the given ID.
+            pub const fn new(contract: $crate::ContractId) -> Self {
+                Self {
+                    contract,
+                    gas_limit: 0,
+                }
+            }
+
+            /// Returns the ID of the contract called.
+            pub const fn contract(&self) -> $crate::ContractId {
+                self.contract
+            }
+
+            /// Returns a client that allows each call to spend the given
+            /// `gas_limit`, as in `call_with_limit`.
+            pub const fn with_limit(self, gas_limit: u64) -> Self {
+                Self {
+                    contract: self.contract,
+                    gas_limit,
+                }
+            }
+
+            $(
+                $(#[$fn_meta])*
+                #[allow(unused_parens)]
+                pub fn $fn_name(
+                    &self,
+                    $($arg: $arg_ty),*
+                ) -> ::core::result::Result<
+                    $crate::contract_interface!(@ret $($ret)?),
+                    $crate::ContractError,
+                > {
+                    $crate::call_with_limit(
+                        self.contract,
+                        ::core::stringify!($fn_name),
+                        &($($arg),*),
+                        self.gas_limit,
+                    )
+                }
+            )*
+        }
+    };
+}
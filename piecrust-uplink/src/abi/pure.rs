@@ -0,0 +1,54 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+/// Macro to declare exported functions as pure.
+///
+/// A pure function has no side effects, and its result depends only on its
+/// argument and the state of the contract. The names given are recorded in
+/// the [`PURE_SECTION`] custom section, allowing the host to cache the results
+/// of calls to the functions and return them again without executing the
+/// contract, for as long as the contract's state doesn't change. The section
+/// is kept when contracts are stripped by `scripts/strip.sh`, but stripping
+/// it with other tools leaves every function to be executed on each call.
+///
+/// The host trusts the declaration. A function declared pure that changes
+/// the state of the contract, or reads anything besides it, may have stale
+/// results returned for it.
+///
+/// The macro may be invoked more than once in a contract, with the names given
+/// in each invocation added to the section.
+///
+/// # Example
+/// ```ignore
+/// use piecrust_uplink::pure_functions;
+///
+/// pure_functions!(read_value, read_owner);
+/// ```
+///
+/// [`PURE_SECTION`]: crate::PURE_SECTION
+#[macro_export]
+macro_rules! pure_functions {
+    ($($fn_name:ident),+ $(,)?) => {
+        const _: () = {
+            const NAMES: &str = concat!($(stringify!($fn_name), "\0"),+);
+
+            #[used]
+            #[link_section = "piecrust-pure"]
+            static PURE_NAMES: [u8; NAMES.len()] = {
+                let names = NAMES.as_bytes();
+                let mut bytes = [0u8; NAMES.len()];
+
+                let mut i = 0;
+                while i < bytes.len() {
+                    bytes[i] = names[i];
+                    i += 1;
+                }
+
+                bytes
+            };
+        };
+    };
+}
@@ -0,0 +1,76 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+/// Macro to declare the types of the argument and return of exported
+/// functions, and whether they take the contract's state mutably.
+///
+/// The declarations are recorded in the [`ABI_SECTION`] custom section,
+/// allowing the host - and tools such as wallets and explorers - to list the
+/// functions a contract exports together with their types, without the
+/// contract's interface being distributed separately. Functions without an
+/// argument take `()`, and functions declared without a return type return
+/// `()`. Functions taking `&mut self` are recorded as mutating the state.
+///
+/// The declarations are recorded as written, and are not checked against the
+/// functions themselves. The macro may be invoked more than once in a
+/// contract, with the functions declared in each invocation added to the
+/// section.
+///
+/// # Example
+/// ```ignore
+/// use piecrust_uplink::export_schema;
+///
+/// export_schema! {
+///     fn read_value(&self, ()) -> i64;
+///     fn increment(&mut self, ());
+///     fn set_value(&mut self, i64);
+/// }
+/// ```
+///
+/// [`ABI_SECTION`]: crate::ABI_SECTION
+#[macro_export]
+macro_rules! export_schema {
+    () => {};
+    (@fn $fn_name:ident, $arg:ty, $mutability:literal $(, $ret:ty)?) => {
+        const _: () = {
+            const SCHEMA: &str = concat!(
+                stringify!($fn_name), "\0",
+                stringify!($arg), "\0",
+                $(stringify!($ret),)? "\0",
+                $mutability, "\0"
+            );
+
+            #[used]
+            #[link_section = "piecrust_abi"]
+            static SCHEMA_BYTES: [u8; SCHEMA.len()] = {
+                let schema = SCHEMA.as_bytes();
+                let mut bytes = [0u8; SCHEMA.len()];
+
+                let mut i = 0;
+                while i < bytes.len() {
+                    bytes[i] = schema[i];
+                    i += 1;
+                }
+
+                bytes
+            };
+        };
+    };
+    (
+        fn $fn_name:ident(&self, $arg:ty) $(-> $ret:ty)?;
+        $($rest:tt)*
+    ) => {
+        $crate::export_schema!(@fn $fn_name, $arg, "" $(, $ret)?);
+        $crate::export_schema!($($rest)*);
+    };
+    (
+        fn $fn_name:ident(&mut self, $arg:ty) $(-> $ret:ty)?;
+        $($rest:tt)*
+    ) => {
+        $crate::export_schema!(@fn $fn_name, $arg, "mut" $(, $ret)?);
+        $crate::export_schema!($($rest)*);
+    };
+}
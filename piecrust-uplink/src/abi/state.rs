@@ -4,7 +4,9 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
+use alloc::string::String;
 use alloc::vec::Vec;
+use core::marker::PhantomData;
 use core::ptr;
 
 use rkyv::{
@@ -15,17 +17,62 @@ use rkyv::{
 };
 
 use crate::{
-    ContractError, ContractId, StandardBufSerializer, CONTRACT_ID_BYTES,
-    SCRATCH_BUF_BYTES,
+    CallFrame, ContractError, ContractEvent, ContractId, LogLevel, SessionMeta,
+    StandardBufSerializer, CONTRACT_ID_BYTES, SCRATCH_BUF_BYTES,
 };
 
 pub mod arg_buf {
-    use crate::ARGBUF_LEN;
+    use crate::{ARGBUF_LEN, MAX_ARGBUF_LEN, MIN_ARGBUF_LEN};
     use core::ptr;
     use core::slice;
 
+    /// The size of the argument buffer, as set through the
+    /// `PIECRUST_ARGBUF_LEN` environment variable when compiling.
+    pub(crate) const ARG_BUF_LEN: usize =
+        match option_env!("PIECRUST_ARGBUF_LEN") {
+            Some(len) => parse_len(len),
+            None => ARGBUF_LEN,
+        };
+
+    /// The size of the argument buffer, exported for the host to honor.
+    ///
+    /// Exported rather than recorded in a custom section, so that it survives
+    /// the bytecode being stripped.
     #[no_mangle]
-    static mut A: [u64; ARGBUF_LEN / 8] = [0; ARGBUF_LEN / 8];
+    static AL: u32 = ARG_BUF_LEN as u32;
+
+    const fn parse_len(len: &str) -> usize {
+        let bytes = len.as_bytes();
+        if bytes.is_empty() {
+            panic!("PIECRUST_ARGBUF_LEN must be a number");
+        }
+
+        let mut n = 0usize;
+        let mut i = 0;
+        while i < bytes.len() {
+            let digit = bytes[i];
+            if !digit.is_ascii_digit() {
+                panic!("PIECRUST_ARGBUF_LEN must be a number");
+            }
+            n = n * 10 + (digit - b'0') as usize;
+            if n > MAX_ARGBUF_LEN {
+                panic!("PIECRUST_ARGBUF_LEN exceeds MAX_ARGBUF_LEN");
+            }
+            i += 1;
+        }
+
+        if n < MIN_ARGBUF_LEN {
+            panic!("PIECRUST_ARGBUF_LEN is below MIN_ARGBUF_LEN");
+        }
+        if n % 8 != 0 {
+            panic!("PIECRUST_ARGBUF_LEN must be a multiple of eight");
+        }
+
+        n
+    }
+
+    #[no_mangle]
+    static mut A: [u64; ARG_BUF_LEN / 8] = [0; ARG_BUF_LEN / 8];
 
     pub fn with_arg_buf<F, R>(f: F) -> R
     where
@@ -33,7 +80,7 @@ pub mod arg_buf {
     {
         unsafe {
             let addr = ptr::addr_of_mut!(A);
-            let slice = slice::from_raw_parts_mut(addr as _, ARGBUF_LEN);
+            let slice = slice::from_raw_parts_mut(addr as _, ARG_BUF_LEN);
             f(slice)
         }
     }
@@ -41,10 +88,22 @@ pub mod arg_buf {
 
 pub(crate) use arg_buf::with_arg_buf;
 
+/// Returns the size of the contract's argument buffer, in bytes.
+///
+/// This is [`ARGBUF_LEN`] unless another size was set when compiling the
+/// contract. Calls with arguments, or returns, larger than the argument buffer
+/// of either side fail.
+///
+/// [`ARGBUF_LEN`]: crate::ARGBUF_LEN
+pub const fn arg_buf_len() -> usize {
+    arg_buf::ARG_BUF_LEN
+}
+
 mod ext {
     extern "C" {
         pub fn hq(name: *const u8, name_len: u32, arg_len: u32) -> u32;
         pub fn hd(name: *const u8, name_len: u32) -> u32;
+        pub fn hd_typed(name: *const u8, name_len: u32, schema: u64) -> u32;
 
         pub fn c(
             contract_id: *const u8,
@@ -53,12 +112,38 @@ mod ext {
             fn_arg_len: u32,
             gas_limit: u64,
         ) -> i32;
+        pub fn mc(arg_len: u32) -> i32;
+        pub fn cf(
+            contract_id: *const u8,
+            fn_name: *const u8,
+            fn_name_len: u32,
+            fn_arg_len: u32,
+            gas_limit: u64,
+        ) -> i32;
+        pub fn feed_next(handle: u32) -> i32;
 
         pub fn emit(topic: *const u8, topic_len: u32, arg_len: u32);
+        pub fn emit_event(
+            topic: *const u8,
+            topic_len: u32,
+            schema: u64,
+            arg_len: u32,
+        );
+        pub fn defer(fn_name: *const u8, fn_name_len: u32, fn_arg_len: u32);
+        pub fn hobserve();
+        pub fn hunobserve();
         pub fn feed(arg_len: u32);
+        pub fn hlog(level: u32, msg_len: u32);
+
+        pub fn spill_len() -> u64;
+        pub fn spill_read(offset: u64) -> u32;
+        pub fn spill_write(arg_len: u32);
 
         pub fn caller() -> i32;
         pub fn callstack() -> i32;
+        pub fn callstack_frames() -> i32;
+        pub fn call_depth() -> u32;
+        pub fn max_call_depth() -> u32;
         pub fn limit() -> u64;
         pub fn spent() -> u64;
         pub fn owner(contract_id: *const u8) -> i32;
@@ -219,6 +304,211 @@ pub fn call_raw_with_limit(
     })
 }
 
+/// Calls a `contract`'s `fn_name` function with the given argument `fn_arg`,
+/// returning an iterator over the items it [`feed`]s, deserialized as `T`.
+///
+/// Rather than being passed on to the host, the items fed by the called
+/// contract - and any contracts it calls in turn - are kept for the caller to
+/// read one at a time, with gas charged for each item read. This allows for
+/// collections larger than the argument buffer to be moved between contracts,
+/// provided each item fits in it. The return of the called function is
+/// discarded.
+///
+/// The called contract gets `93%` of the remaining gas, just like with
+/// [`call`].
+pub fn call_feed<A, T>(
+    contract: ContractId,
+    fn_name: &str,
+    fn_arg: &A,
+) -> Result<FeedIter<T>, ContractError>
+where
+    A: for<'a> Serialize<StandardBufSerializer<'a>>,
+    T: Archive,
+    T::Archived: Deserialize<T, Infallible>,
+{
+    let arg_len = with_arg_buf(|buf| {
+        let mut sbuf = [0u8; SCRATCH_BUF_BYTES];
+        let scratch = BufferScratch::new(&mut sbuf);
+        let ser = BufferSerializer::new(buf);
+        let mut composite =
+            CompositeSerializer::new(ser, scratch, rkyv::Infallible);
+        composite.serialize_value(fn_arg).expect("infallible");
+        composite.pos() as u32
+    });
+
+    let handle = call_feed_inner(contract, fn_name, arg_len)?;
+    Ok(FeedIter {
+        handle,
+        _marker: PhantomData,
+    })
+}
+
+/// Calls the function with name `fn_name` of the given `contract` using
+/// `fn_arg` as argument, returning an iterator over the raw items it
+/// [`feed`]s.
+///
+/// See [`call_feed`] for more details.
+pub fn call_feed_raw(
+    contract: ContractId,
+    fn_name: &str,
+    fn_arg: &[u8],
+) -> Result<RawFeedIter, ContractError> {
+    with_arg_buf(|buf| {
+        buf[..fn_arg.len()].copy_from_slice(fn_arg);
+    });
+
+    let handle = call_feed_inner(contract, fn_name, fn_arg.len() as u32)?;
+    Ok(RawFeedIter { handle })
+}
+
+/// Makes a feed call with the argument already in the argument buffer,
+/// returning the handle to the items fed.
+fn call_feed_inner(
+    contract: ContractId,
+    fn_name: &str,
+    arg_len: u32,
+) -> Result<u32, ContractError> {
+    let contract_id_ptr = contract.as_bytes().as_ptr();
+    let fn_name = fn_name.as_bytes();
+
+    let ret = unsafe {
+        ext::cf(
+            contract_id_ptr,
+            fn_name.as_ptr(),
+            fn_name.len() as u32,
+            arg_len,
+            0,
+        )
+    };
+
+    if ret < 0 {
+        return Err(with_arg_buf(|buf| ContractError::from_parts(ret, buf)));
+    }
+    Ok(ret as u32)
+}
+
+/// Moves the next item of the feed with the given `handle` into the argument
+/// buffer, returning its length.
+fn feed_next(handle: u32) -> Option<usize> {
+    let len = unsafe { ext::feed_next(handle) };
+    (len >= 0).then_some(len as usize)
+}
+
+/// An iterator over the items fed by a contract called using [`call_feed`].
+#[derive(Debug)]
+pub struct FeedIter<T> {
+    handle: u32,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Iterator for FeedIter<T>
+where
+    T: Archive,
+    T::Archived: Deserialize<T, Infallible>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let len = feed_next(self.handle)?;
+        Some(with_arg_buf(|buf| {
+            let ret = unsafe { archived_root::<T>(&buf[..len]) };
+            ret.deserialize(&mut Infallible).expect("Infallible")
+        }))
+    }
+}
+
+/// An iterator over the raw items fed by a contract called using
+/// [`call_feed_raw`].
+#[derive(Debug)]
+pub struct RawFeedIter {
+    handle: u32,
+}
+
+impl Iterator for RawFeedIter {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let len = feed_next(self.handle)?;
+        Some(with_arg_buf(|buf| buf[..len].to_vec()))
+    }
+}
+
+/// Performs a call to each of the given contracts, in order, using a single
+/// crossing to the host.
+///
+/// Each element of `calls` is made of the contract to call, the name of the
+/// function, and the argument to call it with. Every call gets `93%` of the
+/// gas remaining at the time it is made, just like with [`call_raw`], and the
+/// failure of one call does not prevent the following ones from being made.
+///
+/// The results are returned in the same order as the calls.
+///
+/// # Panics
+/// If the encoded calls, or their results, do not fit in the argument buffer.
+pub fn multi_call(
+    calls: &[(ContractId, &str, &[u8])],
+) -> Vec<Result<Vec<u8>, ContractError>> {
+    let arg_len = with_arg_buf(|buf| {
+        let mut writer = MultiCallWriter { buf, pos: 0 };
+
+        writer.write(&(calls.len() as u32).to_le_bytes());
+        for (contract, fn_name, fn_arg) in calls {
+            writer.write(contract.as_bytes());
+            writer.write(&(fn_name.len() as u32).to_le_bytes());
+            writer.write(fn_name.as_bytes());
+            writer.write(&(fn_arg.len() as u32).to_le_bytes());
+            writer.write(fn_arg);
+        }
+
+        writer.pos as u32
+    });
+
+    let n_results = unsafe { ext::mc(arg_len) };
+
+    with_arg_buf(|buf| {
+        let mut results = Vec::with_capacity(n_results as usize);
+
+        let mut pos = 0;
+        for _ in 0..n_results {
+            let mut code_bytes = [0; 4];
+            code_bytes.copy_from_slice(&buf[pos..pos + 4]);
+            let code = i32::from_le_bytes(code_bytes);
+
+            let mut len_bytes = [0; 4];
+            len_bytes.copy_from_slice(&buf[pos + 4..pos + 8]);
+            let len = u32::from_le_bytes(len_bytes) as usize;
+
+            pos += 8;
+            let data = &buf[pos..pos + len];
+            pos += len;
+
+            results.push(if code < 0 {
+                Err(ContractError::from_parts(code, data))
+            } else {
+                Ok(data.to_vec())
+            });
+        }
+
+        results
+    })
+}
+
+struct MultiCallWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl MultiCallWriter<'_> {
+    fn write(&mut self, bytes: &[u8]) {
+        let end = self.pos + bytes.len();
+        if end > self.buf.len() {
+            panic!("multi call arguments exceed the argument buffer");
+        }
+        self.buf[self.pos..end].copy_from_slice(bytes);
+        self.pos = end;
+    }
+}
+
 /// Returns data made available by the host under the given name. The type `D`
 /// must be correctly specified, otherwise undefined behavior will occur.
 pub fn meta_data<D>(name: &str) -> Option<D>
@@ -242,6 +532,29 @@ where
     }
 }
 
+/// Returns the typed metadata item made available by the host, if it is set.
+///
+/// The host checks the item was set with the same schema as the one of `M`,
+/// failing the call otherwise. See [`SessionMeta`] for more details.
+pub fn meta<M>() -> Option<M>
+where
+    M: SessionMeta,
+    M::Archived: Deserialize<M, Infallible>,
+{
+    let name = M::NAME.as_ptr();
+    let name_len = M::NAME.len() as u32;
+
+    unsafe {
+        match ext::hd_typed(name, name_len, M::SCHEMA_HASH) as usize {
+            0 => None,
+            arg_pos => Some(with_arg_buf(|buf| {
+                let ret = archived_root::<M>(&buf[..arg_pos]);
+                ret.deserialize(&mut Infallible).expect("Infallible")
+            })),
+        }
+    }
+}
+
 /// Return the given contract's owner, if the contract exists.
 pub fn owner<const N: usize>(contract: ContractId) -> Option<[u8; N]> {
     let contract_id_ptr = contract.as_bytes().as_ptr();
@@ -306,6 +619,66 @@ pub fn callstack() -> Vec<ContractId> {
     })
 }
 
+/// Returns the frames of the calling stack, starting with the current call and
+/// ending with the first.
+///
+/// Unlike [`callstack`], each frame also includes the name of the function
+/// called and the gas limit the call was given, allowing a contract to check
+/// not only which contract called it, but through which function.
+pub fn callstack_frames() -> Vec<CallFrame> {
+    let n = unsafe { ext::callstack_frames() };
+    with_arg_buf(|buf| {
+        let mut frames = Vec::with_capacity(n as usize);
+        let mut ofs = 0;
+
+        for _ in 0..n {
+            let mut id_bytes = [0; CONTRACT_ID_BYTES];
+            id_bytes.copy_from_slice(&buf[ofs..][..CONTRACT_ID_BYTES]);
+            ofs += CONTRACT_ID_BYTES;
+
+            let mut limit_bytes = [0; 8];
+            limit_bytes.copy_from_slice(&buf[ofs..][..8]);
+            ofs += 8;
+
+            let mut len_bytes = [0; 4];
+            len_bytes.copy_from_slice(&buf[ofs..][..4]);
+            ofs += 4;
+
+            let name_len = u32::from_le_bytes(len_bytes) as usize;
+            let fn_name = String::from_utf8_lossy(&buf[ofs..][..name_len]);
+            ofs += name_len;
+
+            frames.push(CallFrame {
+                contract_id: ContractId::from_bytes(id_bytes),
+                fn_name: fn_name.into_owned(),
+                limit: u64::from_le_bytes(limit_bytes),
+            });
+        }
+
+        frames
+    })
+}
+
+/// Returns the depth of the current call in the calling stack, with the first
+/// contract called being at a depth of one.
+pub fn call_depth() -> usize {
+    unsafe { ext::call_depth() as usize }
+}
+
+/// Returns the maximum depth the calling stack may reach, if the host set one.
+///
+/// Calls to other contracts made at this depth fail with
+/// [`ContractError::CallDepthExceeded`], so contracts delegating to others can
+/// check the depth left using [`call_depth`] beforehand.
+///
+/// [`ContractError::CallDepthExceeded`]: crate::ContractError::CallDepthExceeded
+pub fn max_call_depth() -> Option<usize> {
+    match unsafe { ext::max_call_depth() } {
+        0 => None,
+        depth => Some(depth as usize),
+    }
+}
+
 /// Returns the gas limit with which the contact was called.
 pub fn limit() -> u64 {
     unsafe { ext::limit() }
@@ -316,6 +689,80 @@ pub fn spent() -> u64 {
     unsafe { ext::spent() }
 }
 
+/// Defers a call to this contract's `fn_name` function, with the given
+/// argument `fn_arg`, until after the outermost call succeeds.
+///
+/// Deferred calls are run by the host in the order they are deferred, once
+/// the outermost call has completed, with whatever gas it left unspent. They
+/// are dropped if the call that deferred them fails, or if any of the calls
+/// it is nested in do. Each deferred call succeeds or fails on its own, and
+/// may defer further calls.
+///
+/// Calls deferred while a contract is being initialized are never run.
+pub fn defer<A>(fn_name: &str, fn_arg: &A)
+where
+    A: for<'a> Serialize<StandardBufSerializer<'a>>,
+{
+    let arg_len = with_arg_buf(|buf| {
+        let mut sbuf = [0u8; SCRATCH_BUF_BYTES];
+        let scratch = BufferScratch::new(&mut sbuf);
+        let ser = BufferSerializer::new(buf);
+        let mut composite =
+            CompositeSerializer::new(ser, scratch, rkyv::Infallible);
+        composite.serialize_value(fn_arg).expect("infallible");
+        composite.pos() as u32
+    });
+
+    let fn_name = fn_name.as_bytes();
+    unsafe { ext::defer(fn_name.as_ptr(), fn_name.len() as u32, arg_len) }
+}
+
+/// Defers a call to this contract's `fn_name` function, with the given raw
+/// argument `fn_arg`, until after the outermost call succeeds.
+///
+/// See [`defer`] for more details.
+pub fn defer_raw(fn_name: &str, fn_arg: impl AsRef<[u8]>) {
+    let arg_len = with_arg_buf(|buf| {
+        let fn_arg = fn_arg.as_ref();
+        buf[..fn_arg.len()].copy_from_slice(fn_arg);
+        fn_arg.len() as u32
+    });
+
+    let fn_name = fn_name.as_bytes();
+    unsafe { ext::defer(fn_name.as_ptr(), fn_name.len() as u32, arg_len) }
+}
+
+/// Registers this contract as an observer of the events emitted by the
+/// `contract` with the given ID.
+///
+/// Once the outermost call succeeds, the host notifies observers of every
+/// event emitted by the contracts they observe, by calling their `on_event`
+/// function with the [`Event`] as argument. Observers are notified in the
+/// order the events were emitted, and for each event in the order of their
+/// IDs. Each notification succeeds or fails on its own, with a bounded amount
+/// of gas.
+///
+/// The registration only takes effect once the call it was made in succeeds,
+/// and lasts for the rest of the session.
+///
+/// [`Event`]: crate::Event
+pub fn observe(contract: ContractId) {
+    with_arg_buf(|buf| {
+        buf[..CONTRACT_ID_BYTES].copy_from_slice(contract.as_bytes())
+    });
+    unsafe { ext::hobserve() }
+}
+
+/// Stops observing the events emitted by the `contract` with the given ID.
+///
+/// See [`observe`] for more details.
+pub fn unobserve(contract: ContractId) {
+    with_arg_buf(|buf| {
+        buf[..CONTRACT_ID_BYTES].copy_from_slice(contract.as_bytes())
+    });
+    unsafe { ext::hunobserve() }
+}
+
 /// Emits an event with the given data, serializing it using [`rkyv`].
 pub fn emit<D>(topic: &str, data: D)
 where
@@ -338,6 +785,31 @@ where
     });
 }
 
+/// Emits the given typed event, serializing it using [`rkyv`].
+///
+/// The event is emitted with the topic of its type, and carries the hash of
+/// its schema so that the host may decode it. See [`ContractEvent`] for more
+/// details.
+pub fn emit_event<E: ContractEvent>(event: E) {
+    with_arg_buf(|buf| {
+        let mut sbuf = [0u8; SCRATCH_BUF_BYTES];
+        let scratch = BufferScratch::new(&mut sbuf);
+        let ser = BufferSerializer::new(buf);
+        let mut composite =
+            CompositeSerializer::new(ser, scratch, rkyv::Infallible);
+
+        composite.serialize_value(&event).unwrap();
+        let arg_len = composite.pos() as u32;
+
+        let topic_ptr = E::TOPIC.as_ptr();
+        let topic_len = E::TOPIC.len() as u32;
+
+        unsafe {
+            ext::emit_event(topic_ptr, topic_len, E::SCHEMA_HASH, arg_len)
+        }
+    });
+}
+
 /// Emits an event with the given data.
 pub fn emit_raw(topic: &str, data: impl AsRef<[u8]>) {
     with_arg_buf(|buf| {
@@ -355,11 +827,32 @@ pub fn emit_raw(topic: &str, data: impl AsRef<[u8]>) {
     });
 }
 
+/// Logs a message at the given level.
+///
+/// Unlike the `debug!` macro, logging is available in production builds. Each
+/// byte logged is charged for, regardless of whether the host keeps the
+/// message, and the host may drop messages below its level of interest or past
+/// its limit of messages per call.
+///
+/// Messages longer than the argument buffer are truncated.
+pub fn log(level: LogLevel, msg: &str) {
+    with_arg_buf(|buf| {
+        let mut msg_len = msg.len().min(buf.len());
+        while !msg.is_char_boundary(msg_len) {
+            msg_len -= 1;
+        }
+        buf[..msg_len].copy_from_slice(&msg.as_bytes()[..msg_len]);
+
+        unsafe { ext::hlog(level as u32, msg_len as u32) }
+    });
+}
+
 /// Feeds the host with data, serializing it using [`rkyv`].
 ///
-/// This is only allowed to be called in the context of a `feed_call`, and
-/// will error out otherwise. It is meant for contracts to be able to report
-/// large amounts of data to the host, in the span of a single call.
+/// This is only allowed to be called in the context of a `feed_call`, or of a
+/// [`call_feed`] made by another contract, and will error out otherwise. It is
+/// meant for contracts to be able to report large amounts of data to the host,
+/// or to their caller, in the span of a single call.
 pub fn feed<D>(data: D)
 where
     for<'a> D: Serialize<StandardBufSerializer<'a>>,
@@ -380,9 +873,10 @@ where
 
 /// Feeds the host with data.
 ///
-/// This is only allowed to be called in the context of a `feed_call`, and
-/// will error out otherwise. It is meant for contracts to be able to report
-/// large amounts of data to the host, in the span of a single call.
+/// This is only allowed to be called in the context of a `feed_call`, or of a
+/// [`call_feed`] made by another contract, and will error out otherwise. It is
+/// meant for contracts to be able to report large amounts of data to the host,
+/// or to their caller, in the span of a single call.
 pub fn feed_raw(data: impl AsRef<[u8]>) {
     with_arg_buf(|buf| {
         let data = data.as_ref();
@@ -395,3 +889,53 @@ pub fn feed_raw(data: impl AsRef<[u8]>) {
         unsafe { ext::feed(arg_len) }
     });
 }
+
+/// Returns the length of the input spilled by the host, or zero if no input
+/// was spilled.
+///
+/// Hosts spill arguments too large for the argument buffer to a temporary
+/// file, and call the contract with an empty argument instead. The input is
+/// then read in chunks using [`read_spilled_input`].
+pub fn spilled_input_len() -> u64 {
+    unsafe { ext::spill_len() }
+}
+
+/// Reads the input spilled by the host, starting at the given `offset`, into
+/// `buf`. Returns the number of bytes read, which is only less than the
+/// length of `buf` when the end of the input is reached.
+///
+/// See [`spilled_input_len`] for more details.
+pub fn read_spilled_input(mut offset: u64, buf: &mut [u8]) -> usize {
+    let mut n_read = 0;
+
+    while n_read < buf.len() {
+        let len = unsafe { ext::spill_read(offset) } as usize;
+        if len == 0 {
+            break;
+        }
+
+        let len = len.min(buf.len() - n_read);
+        with_arg_buf(|arg_buf| {
+            buf[n_read..][..len].copy_from_slice(&arg_buf[..len])
+        });
+
+        n_read += len;
+        offset += len as u64;
+    }
+
+    n_read
+}
+
+/// Appends data to the output spilled to the host, returned alongside the
+/// result of the call.
+///
+/// This allows contracts to return more data than fits the argument buffer,
+/// without the host keeping it all in memory.
+pub fn spill_output(data: impl AsRef<[u8]>) {
+    for chunk in data.as_ref().chunks(arg_buf_len()) {
+        with_arg_buf(|buf| {
+            buf[..chunk.len()].copy_from_slice(chunk);
+            unsafe { ext::spill_write(chunk.len() as u32) }
+        });
+    }
+}
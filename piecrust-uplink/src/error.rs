@@ -14,6 +14,8 @@ use alloc::string::String;
 use core::fmt::{Display, Formatter};
 use core::str;
 
+use crate::{ContractId, CONTRACT_ID_BYTES};
+
 /// The error possibly returned on an inter-contract-call.
 //
 // We do **not use rkyv** to pass it to the contract from the VM. Instead, we
@@ -28,6 +30,15 @@ pub enum ContractError {
     Panic(String),
     OutOfGas,
     DoesNotExist,
+    CallDepthExceeded,
+    MemoryAccessOutOfBounds,
+    /// The called `contract` returned `len` bytes, more than the `max_len`
+    /// bytes the argument buffers of the caller and callee can hold.
+    ReturnOverflow {
+        contract: ContractId,
+        len: u32,
+        max_len: u32,
+    },
     Unknown,
 }
 
@@ -55,10 +66,29 @@ impl ContractError {
             msg
         }
 
+        fn get_u32(slice: &[u8]) -> u32 {
+            let mut bytes = [0u8; 4];
+            bytes.copy_from_slice(&slice[..4]);
+            u32::from_le_bytes(bytes)
+        }
+
         match code {
             -1 => Self::Panic(get_msg(slice)),
             -2 => Self::OutOfGas,
             -3 => Self::DoesNotExist,
+            -4 => Self::CallDepthExceeded,
+            -5 => Self::MemoryAccessOutOfBounds,
+            -6 => {
+                let mut contract = [0u8; CONTRACT_ID_BYTES];
+                contract.copy_from_slice(&slice[..CONTRACT_ID_BYTES]);
+                let slice = &slice[CONTRACT_ID_BYTES..];
+
+                Self::ReturnOverflow {
+                    contract: ContractId::from_bytes(contract),
+                    len: get_u32(slice),
+                    max_len: get_u32(&slice[4..]),
+                }
+            }
             i32::MIN => Self::Unknown,
             _ => unreachable!("The host must guarantee that the code is valid"),
         }
@@ -84,6 +114,20 @@ impl ContractError {
             }
             Self::OutOfGas => -2,
             Self::DoesNotExist => -3,
+            Self::CallDepthExceeded => -4,
+            Self::MemoryAccessOutOfBounds => -5,
+            Self::ReturnOverflow {
+                contract,
+                len,
+                max_len,
+            } => {
+                let slice = &mut slice[..CONTRACT_ID_BYTES + 8];
+                slice[..CONTRACT_ID_BYTES].copy_from_slice(contract.as_bytes());
+                let slice = &mut slice[CONTRACT_ID_BYTES..];
+                slice[..4].copy_from_slice(&len.to_le_bytes());
+                slice[4..].copy_from_slice(&max_len.to_le_bytes());
+                -6
+            }
             Self::Unknown => i32::MIN,
         }
     }
@@ -95,6 +139,9 @@ impl From<ContractError> for i32 {
             ContractError::Panic(_) => -1,
             ContractError::OutOfGas => -2,
             ContractError::DoesNotExist => -3,
+            ContractError::CallDepthExceeded => -4,
+            ContractError::MemoryAccessOutOfBounds => -5,
+            ContractError::ReturnOverflow { .. } => -6,
             ContractError::Unknown => i32::MIN,
         }
     }
@@ -108,7 +155,78 @@ impl Display for ContractError {
             ContractError::DoesNotExist => {
                 write!(f, "Contract does not exist")
             }
+            ContractError::CallDepthExceeded => {
+                write!(f, "Call depth exceeded")
+            }
+            ContractError::MemoryAccessOutOfBounds => {
+                write!(f, "Memory access out of bounds")
+            }
+            ContractError::ReturnOverflow {
+                contract,
+                len,
+                max_len,
+            } => {
+                write!(
+                    f,
+                    "Contract {contract} returned {len} bytes, more than the \
+                     {max_len} that fit the argument buffer"
+                )
+            }
             ContractError::Unknown => write!(f, "Unknown"),
         }
     }
 }
+
+/// The error possibly returned when transferring out of a contract's balance,
+/// or when a contract destroys itself.
+//
+// Like `ContractError`, it is passed from the VM to the contract as a negative
+// return code.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Archive, Serialize, Deserialize,
+)]
+#[archive_attr(derive(CheckBytes))]
+pub enum TransferError {
+    InsufficientBalance,
+    RecipientDoesNotExist,
+    BalanceOverflow,
+}
+
+impl TransferError {
+    /// Returns a transfer error from a return `code`.
+    #[cfg(all(feature = "abi", feature = "balance"))]
+    pub(crate) fn from_code(code: i32) -> Self {
+        match code {
+            -1 => Self::InsufficientBalance,
+            -2 => Self::RecipientDoesNotExist,
+            -3 => Self::BalanceOverflow,
+            _ => unreachable!("The host must guarantee that the code is valid"),
+        }
+    }
+}
+
+impl From<TransferError> for i32 {
+    fn from(err: TransferError) -> Self {
+        match err {
+            TransferError::InsufficientBalance => -1,
+            TransferError::RecipientDoesNotExist => -2,
+            TransferError::BalanceOverflow => -3,
+        }
+    }
+}
+
+impl Display for TransferError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TransferError::InsufficientBalance => {
+                write!(f, "Insufficient balance")
+            }
+            TransferError::RecipientDoesNotExist => {
+                write!(f, "Recipient does not exist")
+            }
+            TransferError::BalanceOverflow => {
+                write!(f, "Recipient balance overflow")
+            }
+        }
+    }
+}
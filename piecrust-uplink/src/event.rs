@@ -0,0 +1,140 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use bytecheck::CheckBytes;
+use rkyv::validation::validators::DefaultValidator;
+use rkyv::{Archive, Deserialize, Infallible, Serialize};
+
+use crate::{Event, StandardBufSerializer};
+
+/// The payload of an event with a known topic and layout.
+///
+/// Events emitted using [`emit_event`] carry the hash of their [`SCHEMA`],
+/// allowing hosts to recognize and decode them using [`Event::decode`],
+/// instead of relying on the topic alone to know the layout of the data.
+///
+/// Usually implemented using the [`contract_event!`] macro.
+///
+/// [`emit_event`]: crate::emit_event
+/// [`SCHEMA`]: ContractEvent::SCHEMA
+/// [`contract_event!`]: crate::contract_event
+pub trait ContractEvent:
+    Archive + for<'a> Serialize<StandardBufSerializer<'a>>
+{
+    /// The topic the event is emitted with.
+    const TOPIC: &'static str;
+    /// A description of the layout of the event, changing whenever the layout
+    /// does.
+    const SCHEMA: &'static str;
+    /// The hash of the [`SCHEMA`], carried by the emitted events.
+    ///
+    /// [`SCHEMA`]: ContractEvent::SCHEMA
+    const SCHEMA_HASH: u64 = schema_hash(Self::SCHEMA);
+}
+
+/// Computes the hash of an event schema, using 64-bit FNV-1a.
+///
+/// This is meant to tell schemas apart, not to resist collisions made on
+/// purpose.
+pub const fn schema_hash(schema: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    let bytes = schema.as_bytes();
+    let mut hash = OFFSET_BASIS;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(PRIME);
+        i += 1;
+    }
+
+    hash
+}
+
+impl Event {
+    /// Returns whether the event was emitted with the topic and schema of the
+    /// given event type.
+    pub fn is<E: ContractEvent>(&self) -> bool {
+        self.topic == E::TOPIC && self.schema == Some(E::SCHEMA_HASH)
+    }
+
+    /// Decodes the data of the event as the given event type, returning `None`
+    /// if the event is of a different type, or its data is invalid.
+    pub fn decode<E>(&self) -> Option<E>
+    where
+        E: ContractEvent,
+        E::Archived: for<'a> CheckBytes<DefaultValidator<'a>>
+            + Deserialize<E, Infallible>,
+    {
+        if !self.is::<E>() {
+            return None;
+        }
+        let archived = rkyv::check_archived_root::<E>(&self.data).ok()?;
+        archived.deserialize(&mut Infallible).ok()
+    }
+}
+
+/// Macro to declare an event type, implementing [`ContractEvent`] for it.
+///
+/// The topic is given by a leading `#[topic = "..."]` attribute, and the
+/// schema is made of the name of the struct together with the names and
+/// types of its fields, as written. The struct must derive the `rkyv` traits
+/// itself, with its archived type checked using `bytecheck`, to be decoded by
+/// the host.
+///
+/// # Example
+/// ```ignore
+/// use bytecheck::CheckBytes;
+/// use piecrust_uplink::{contract_event, emit_event, ContractId};
+/// use rkyv::{Archive, Deserialize, Serialize};
+///
+/// contract_event! {
+///     #[topic = "transfer"]
+///     #[derive(Archive, Serialize, Deserialize)]
+///     #[archive_attr(derive(CheckBytes))]
+///     pub struct Transfer {
+///         pub to: ContractId,
+///         pub amount: u64,
+///     }
+/// }
+///
+/// fn transfer(to: ContractId, amount: u64) {
+///     emit_event(Transfer { to, amount });
+/// }
+/// ```
+#[macro_export]
+macro_rules! contract_event {
+    (
+        #[topic = $topic:literal]
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident: $field_ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $field_ty,
+            )*
+        }
+
+        impl $crate::ContractEvent for $name {
+            const TOPIC: &'static str = $topic;
+            const SCHEMA: &'static str = concat!(
+                stringify!($name),
+                " {",
+                $(" ", stringify!($field), ": ", stringify!($field_ty), ",",)*
+                " }"
+            );
+        }
+    };
+}
@@ -37,6 +37,21 @@
 //! - [`call`] to call another contract
 //! - [`emit`] to emit events
 //!
+//! Calls to other contracts can be made typed by declaring their interface
+//! with [`contract_interface!`], and functions without side effects declared
+//! with [`pure_functions!`], so that the host may cache their results. The
+//! types and mutability of the functions a contract exports can be declared
+//! with [`export_schema!`], for the host and other tools to read. Metadata
+//! items made available by the host can be read with their layout checked,
+//! by declaring them with [`session_meta!`] and reading them with [`meta`].
+//!
+//! The argument buffer is [`ARGBUF_LEN`] bytes long by default. Contracts
+//! exchanging larger payloads can set its size when compiled, through the
+//! `PIECRUST_ARGBUF_LEN` environment variable, to any multiple of eight between
+//! [`MIN_ARGBUF_LEN`] and [`MAX_ARGBUF_LEN`]. The size is exported as the
+//! [`ARGBUF_LEN_EXPORT`] global for the host to honor, and can be read back
+//! using [`arg_buf_len`].
+//!
 //! The functions in this crate are wrappers around a particular way of calling
 //! the WASM imports. Take a look at the [externs] for a full view of what is
 //! available.
@@ -51,7 +66,8 @@
 //! must use the `abi` feature:
 //!
 //! - `abi` for writing contracts
-//! - `dlmalloc` to using the builtin allocator
+//! - `dlmalloc` to using the builtin allocator, with [`heap_stats`] reporting
+//!   its usage
 //! - `debug` for writing contracts with debug capabilities such as the
 //!   [`debug!`] macro, and logging panics to stdout
 //!
@@ -62,6 +78,7 @@
 #![allow(internal_features)]
 #![feature(lang_items, panic_info_message)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
+#![cfg_attr(target_arch = "wasm64", feature(simd_wasm64))]
 #![no_std]
 
 extern crate alloc;
@@ -75,6 +92,12 @@ pub use abi::*;
 mod types;
 pub use types::*;
 
+mod event;
+pub use event::*;
+
+mod session_meta;
+pub use session_meta::*;
+
 mod error;
 pub use error::*;
 
@@ -84,5 +107,45 @@ mod serde_support;
 /// How many bytes to use for scratch space when serializing
 pub const SCRATCH_BUF_BYTES: usize = 1024;
 
-/// The size of the argument buffer in bytes
+/// The size of the argument buffer in bytes, unless the contract declares
+/// another
 pub const ARGBUF_LEN: usize = 64 * 1024;
+
+/// The smallest argument buffer a contract may declare, in bytes
+pub const MIN_ARGBUF_LEN: usize = 4 * 1024;
+
+/// The largest argument buffer a contract may declare, in bytes
+pub const MAX_ARGBUF_LEN: usize = 16 * 1024 * 1024;
+
+/// The version of this crate, which defines the ABI between contracts and the
+/// host.
+pub const UPLINK_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The name under which a contract may export a second memory, to be used as
+/// scratch space.
+///
+/// Unlike the contract's main memory, exported as `memory`, the scratch memory
+/// starts zeroed on every call and is never committed to the state. It must
+/// be the memory with index one.
+pub const SCRATCH_MEMORY: &str = "scratch";
+
+/// The name of the custom section in which contracts compiled with the `abi`
+/// feature record the version of this crate.
+pub const UPLINK_VERSION_SECTION: &str = "piecrust-uplink";
+
+/// The name of the global exported by contracts compiled with the `abi`
+/// feature, pointing to the size of their argument buffer as a little endian
+/// `u32`. Contracts without it have an argument buffer of [`ARGBUF_LEN`] bytes.
+pub const ARGBUF_LEN_EXPORT: &str = "AL";
+
+/// The name of the custom section in which contracts declare their pure
+/// functions, using `pure_functions!`, as the names of the functions each
+/// followed by a zero byte.
+pub const PURE_SECTION: &str = "piecrust-pure";
+
+/// The name of the custom section in which contracts declare the types of the
+/// functions they export, using `export_schema!`, as the name of each
+/// function, the type of its argument, the type of its return, and `mut` if
+/// it takes the state mutably, each followed by a zero byte. An empty return
+/// type stands for `()`.
+pub const ABI_SECTION: &str = "piecrust_abi";
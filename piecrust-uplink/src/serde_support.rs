@@ -49,11 +49,16 @@ impl Serialize for Event {
         &self,
         serializer: S,
     ) -> Result<S::Ok, S::Error> {
-        let mut struct_ser = serializer.serialize_struct("Event", 3)?;
+        let len = if self.schema.is_some() { 4 } else { 3 };
+        let mut struct_ser = serializer.serialize_struct("Event", len)?;
         struct_ser.serialize_field("source", &self.source)?;
         struct_ser.serialize_field("topic", &self.topic)?;
         struct_ser
             .serialize_field("data", &BASE64_STANDARD.encode(&self.data))?;
+        match self.schema {
+            Some(schema) => struct_ser.serialize_field("schema", &schema)?,
+            None => struct_ser.skip_field("schema")?,
+        }
         struct_ser.end()
     }
 }
@@ -71,8 +76,9 @@ impl<'de> Deserialize<'de> for Event {
                 &self,
                 formatter: &mut alloc::fmt::Formatter,
             ) -> alloc::fmt::Result {
-                formatter
-                    .write_str("a struct with fields: source, topic, and data")
+                formatter.write_str(
+                    "a struct with fields: source, topic, data, and schema",
+                )
             }
 
             fn visit_map<A: MapAccess<'de>>(
@@ -80,6 +86,7 @@ impl<'de> Deserialize<'de> for Event {
                 mut map: A,
             ) -> Result<Self::Value, A::Error> {
                 let (mut source, mut topic, mut data) = (None, None, None);
+                let mut schema = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         "source" => {
@@ -106,10 +113,18 @@ impl<'de> Deserialize<'de> for Event {
                             }
                             data = Some(map.next_value()?);
                         }
+                        "schema" => {
+                            if schema.is_some() {
+                                return Err(SerdeError::duplicate_field(
+                                    "schema",
+                                ));
+                            }
+                            schema = Some(map.next_value()?);
+                        }
                         field => {
                             return Err(SerdeError::unknown_field(
                                 field,
-                                &["source", "topic", "data"],
+                                &["source", "topic", "data", "schema"],
                             ))
                         }
                     };
@@ -127,13 +142,14 @@ impl<'de> Deserialize<'de> for Event {
                     topic: topic
                         .ok_or_else(|| SerdeError::missing_field("topic"))?,
                     data,
+                    schema,
                 })
             }
         }
 
         deserializer.deserialize_struct(
             "Event",
-            &["source", "topic", "data"],
+            &["source", "topic", "data", "schema"],
             StructVisitor,
         )
     }
@@ -0,0 +1,118 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use rkyv::{Archive, Serialize};
+
+use crate::{schema_hash, StandardBufSerializer};
+
+/// A metadata item made available by the host, with a known name and layout.
+///
+/// The host sets typed items together with the hash of their [`SCHEMA`], and
+/// contracts reading them using [`meta`] have the hash checked against the
+/// one they were built with. A contract built against a different layout of
+/// the item fails the call, instead of misreading the item.
+///
+/// Usually implemented using the [`session_meta!`] macro.
+///
+/// [`meta`]: crate::meta
+/// [`SCHEMA`]: SessionMeta::SCHEMA
+/// [`session_meta!`]: crate::session_meta
+pub trait SessionMeta:
+    Archive + for<'a> Serialize<StandardBufSerializer<'a>>
+{
+    /// The name the item is set under.
+    const NAME: &'static str;
+    /// A description of the layout of the item, changing whenever the layout
+    /// does.
+    const SCHEMA: &'static str;
+    /// The hash of the [`SCHEMA`], checked when the item is read.
+    ///
+    /// [`SCHEMA`]: SessionMeta::SCHEMA
+    const SCHEMA_HASH: u64 = schema_hash(Self::SCHEMA);
+}
+
+/// Macro to declare a metadata item type, implementing [`SessionMeta`] for
+/// it.
+///
+/// The name is given by a leading `#[name = "..."]` attribute, and the schema
+/// is made of the name of the struct together with the names - or positions -
+/// and types of its fields, as written. The struct must derive the `rkyv`
+/// traits itself.
+///
+/// # Example
+/// ```ignore
+/// use piecrust_uplink::{meta, session_meta};
+/// use rkyv::{Archive, Deserialize, Serialize};
+///
+/// session_meta! {
+///     #[name = "height"]
+///     #[derive(Archive, Serialize, Deserialize)]
+///     pub struct BlockHeight(pub u64);
+/// }
+///
+/// fn height() -> Option<u64> {
+///     meta::<BlockHeight>().map(|BlockHeight(height)| height)
+/// }
+/// ```
+#[macro_export]
+macro_rules! session_meta {
+    (
+        #[name = $item_name:literal]
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident: $field_ty:ty
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $field_ty,
+            )*
+        }
+
+        impl $crate::SessionMeta for $name {
+            const NAME: &'static str = $item_name;
+            const SCHEMA: &'static str = concat!(
+                stringify!($name),
+                " {",
+                $(" ", stringify!($field), ": ", stringify!($field_ty), ",",)*
+                " }"
+            );
+        }
+    };
+    (
+        #[name = $item_name:literal]
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident(
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field_ty:ty
+            ),* $(,)?
+        );
+    ) => {
+        $(#[$meta])*
+        $vis struct $name(
+            $(
+                $(#[$field_meta])*
+                $field_vis $field_ty,
+            )*
+        );
+
+        impl $crate::SessionMeta for $name {
+            const NAME: &'static str = $item_name;
+            const SCHEMA: &'static str = concat!(
+                stringify!($name),
+                "(",
+                $(stringify!($field_ty), ",",)*
+                ")"
+            );
+        }
+    };
+}
@@ -33,6 +33,81 @@ pub struct Event {
     pub source: ContractId,
     pub topic: String,
     pub data: Vec<u8>,
+    /// The hash of the schema of the data, if the event was emitted as a
+    /// [`ContractEvent`].
+    ///
+    /// [`ContractEvent`]: crate::ContractEvent
+    pub schema: Option<u64>,
+}
+
+/// A frame of the call stack, as returned by `callstack_frames`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CallFrame {
+    /// The contract called.
+    pub contract_id: ContractId,
+    /// The name of the function called.
+    pub fn_name: String,
+    /// The gas limit the call was given.
+    pub limit: u64,
+}
+
+/// The severity of a message logged by a contract, from most to least severe.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Archive,
+    Serialize,
+    Deserialize,
+)]
+#[archive_attr(derive(CheckBytes))]
+#[repr(u8)]
+pub enum LogLevel {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+    Trace = 4,
+}
+
+impl TryFrom<u32> for LogLevel {
+    type Error = u32;
+
+    fn try_from(level: u32) -> Result<Self, u32> {
+        Ok(match level {
+            0 => Self::Error,
+            1 => Self::Warn,
+            2 => Self::Info,
+            3 => Self::Debug,
+            4 => Self::Trace,
+            _ => return Err(level),
+        })
+    }
+}
+
+/// A message logged by a contract.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Archive,
+    Serialize,
+    Deserialize,
+)]
+#[archive_attr(derive(CheckBytes))]
+pub struct Log {
+    pub source: ContractId,
+    pub level: LogLevel,
+    pub msg: String,
 }
 
 /// Type with `rkyv` serialization capabilities for specific types.
@@ -127,3 +202,57 @@ impl core::fmt::Display for ContractId {
         Ok(())
     }
 }
+
+impl core::str::FromStr for ContractId {
+    type Err = ParseContractIdError;
+
+    /// Parses a [`ContractId`] from its hex representation, optionally
+    /// prefixed with `0x`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix("0x").unwrap_or(s);
+        if s.len() != 2 * CONTRACT_ID_BYTES {
+            return Err(ParseContractIdError::InvalidLength(s.len()));
+        }
+
+        fn nibble(c: u8) -> Result<u8, ParseContractIdError> {
+            match c {
+                b'0'..=b'9' => Ok(c - b'0'),
+                b'a'..=b'f' => Ok(c - b'a' + 10),
+                b'A'..=b'F' => Ok(c - b'A' + 10),
+                _ => Err(ParseContractIdError::InvalidCharacter(c as char)),
+            }
+        }
+
+        let mut bytes = [0u8; CONTRACT_ID_BYTES];
+        for (byte, pair) in bytes.iter_mut().zip(s.as_bytes().chunks(2)) {
+            *byte = nibble(pair[0])? << 4 | nibble(pair[1])?;
+        }
+
+        Ok(Self(bytes))
+    }
+}
+
+/// The error returned when parsing a [`ContractId`] from a string fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseContractIdError {
+    /// The string, stripped of any `0x` prefix, is not twice as long as
+    /// [`CONTRACT_ID_BYTES`].
+    InvalidLength(usize),
+    /// The string contains a character that is not a hex digit.
+    InvalidCharacter(char),
+}
+
+impl core::fmt::Display for ParseContractIdError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(
+                f,
+                "Invalid contract ID length: expected {} hex digits, got {len}",
+                2 * CONTRACT_ID_BYTES
+            ),
+            Self::InvalidCharacter(c) => {
+                write!(f, "Invalid character in contract ID: {c:?}")
+            }
+        }
+    }
+}
@@ -23,6 +23,7 @@ fn rand_event(rng: &mut StdRng) -> Event {
         source: rand_contract_id(rng),
         topic: "a-contract-topic".into(),
         data: data.into(),
+        schema: Some(rng.next_u64()),
     }
 }
 
@@ -79,6 +80,7 @@ fn serde_event_fields() {
         source: ContractId::from_bytes([0; CONTRACT_ID_BYTES]),
         topic: String::new(),
         data: Vec::new(),
+        schema: None,
     };
     let ser = serde_json::to_string(&event).unwrap();
     assert_eq!(serde_json_string, ser);
@@ -0,0 +1,107 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Session call throughput, both for direct calls from the host and for calls
+//! crossing contract boundaries.
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkId, Criterion,
+    Throughput,
+};
+use piecrust::{contract_bytecode, ContractData, ContractId, SessionData, VM};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+/// Number of calls performed in each session.
+const CALLS: [u64; 3] = [1, 16, 256];
+
+fn direct(c: &mut Criterion) {
+    let vm = VM::ephemeral().expect("Ephemeral VM should succeed");
+
+    let mut session = vm
+        .session(SessionData::builder())
+        .expect("Instantiating session should succeed");
+    let id = session
+        .deploy(
+            contract_bytecode!("counter"),
+            ContractData::builder().owner(OWNER),
+            LIMIT,
+        )
+        .expect("Deployment should succeed");
+    let root = session.commit().expect("Committing should succeed");
+
+    let mut group = c.benchmark_group("direct");
+
+    for n in CALLS {
+        group.throughput(Throughput::Elements(n));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, &n| {
+            b.iter(|| {
+                let mut session = vm
+                    .session(SessionData::builder().base(root))
+                    .expect("Instantiating session should succeed");
+                for _ in 0..n {
+                    session
+                        .call::<(), ()>(id, "increment", &(), LIMIT)
+                        .expect("Incrementing should succeed");
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+fn inter_contract(c: &mut Criterion) {
+    let vm = VM::ephemeral().expect("Ephemeral VM should succeed");
+
+    let mut session = vm
+        .session(SessionData::builder())
+        .expect("Instantiating session should succeed");
+    let counter_id = session
+        .deploy(
+            contract_bytecode!("counter"),
+            ContractData::builder().owner(OWNER),
+            LIMIT,
+        )
+        .expect("Deployment should succeed");
+    let center_id = session
+        .deploy(
+            contract_bytecode!("callcenter"),
+            ContractData::builder().owner(OWNER),
+            LIMIT,
+        )
+        .expect("Deployment should succeed");
+    let root = session.commit().expect("Committing should succeed");
+
+    let mut group = c.benchmark_group("inter_contract");
+
+    for n in CALLS {
+        group.throughput(Throughput::Elements(n));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, &n| {
+            b.iter(|| {
+                let mut session = vm
+                    .session(SessionData::builder().base(root))
+                    .expect("Instantiating session should succeed");
+                for _ in 0..n {
+                    session
+                        .call::<ContractId, ()>(
+                            center_id,
+                            "increment_counter",
+                            black_box(&counter_id),
+                            LIMIT,
+                        )
+                        .expect("Incrementing should succeed");
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, direct, inter_contract);
+criterion_main!(benches);
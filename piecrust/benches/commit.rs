@@ -0,0 +1,185 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Commit writing and diffing, across a range of dirtied pages per commit.
+//!
+//! Commits are written with and without zero pages elided - the only way the
+//! store compacts what it writes - for both incompressible random data and
+//! zeroes. Diffs between commits are computed at each tracking page size,
+//! which is the granularity changes are found at.
+//!
+//! The bytes written to the contract's memory are produced by a seeded RNG, so
+//! the datasets - and therefore the work done on commit - are the same across
+//! runs.
+
+use std::fmt::{self, Display, Formatter};
+
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId,
+    Criterion, Throughput,
+};
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Root, Session, SessionData, VM,
+};
+use piecrust_uplink::ARGBUF_LEN;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+const SEED: u64 = 0xDEAD_BEEF;
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 10_000_000_000;
+
+/// Number of argument buffers appended to the contract's state before each
+/// commit. Each one amounts to roughly one page of memory.
+const APPENDS: [usize; 4] = [1, 8, 64, 256];
+
+/// Whether zero pages are elided from commits.
+const ELIDE_ZERO_PAGES: [bool; 2] = [false, true];
+
+/// The granularities diffs are computed at.
+const TRACKING_PAGE_SIZES: [usize; 3] = [0x1000, 0x4000, 0x10000];
+
+/// The contents of the pages dirtied before each commit.
+#[derive(Debug, Clone, Copy)]
+enum Data {
+    Random,
+    Zeroes,
+}
+
+impl Display for Data {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Data::Random => f.write_str("random"),
+            Data::Zeroes => f.write_str("zeroes"),
+        }
+    }
+}
+
+fn dataset(data: Data, n: usize) -> Vec<Vec<u8>> {
+    let mut rng = StdRng::seed_from_u64(SEED);
+
+    (0..n)
+        .map(|_| {
+            let mut bytes = vec![0; ARGBUF_LEN];
+            if let Data::Random = data {
+                rng.fill_bytes(&mut bytes);
+            }
+            bytes
+        })
+        .collect()
+}
+
+/// Spawns a VM with the grower contract deployed, returning its ID and the
+/// root of the commit it was deployed in.
+fn grower_vm(elide_zero_pages: bool) -> (VM, ContractId, Root) {
+    let vm = VM::ephemeral().expect("Ephemeral VM should succeed");
+    vm.set_elide_zero_pages(elide_zero_pages);
+
+    let mut session = vm
+        .session(SessionData::builder())
+        .expect("Instantiating session should succeed");
+    let id = session
+        .deploy(
+            contract_bytecode!("grower"),
+            ContractData::builder().owner(OWNER),
+            LIMIT,
+        )
+        .expect("Deployment should succeed");
+    let root = session.commit().expect("Committing should succeed");
+
+    (vm, id, root)
+}
+
+fn dirty_session(
+    vm: &VM,
+    root: Root,
+    id: ContractId,
+    dataset: &[Vec<u8>],
+) -> Session {
+    let mut session = vm
+        .session(SessionData::builder().base(root))
+        .expect("Instantiating session should succeed");
+
+    for bytes in dataset {
+        session
+            .call_raw(id, "append", bytes.clone(), LIMIT)
+            .expect("Appending should succeed");
+    }
+
+    session
+}
+
+fn commit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("commit");
+    group.sample_size(10);
+
+    for elide in ELIDE_ZERO_PAGES {
+        let (vm, id, root) = grower_vm(elide);
+
+        for data in [Data::Random, Data::Zeroes] {
+            for n in APPENDS {
+                let dataset = dataset(data, n);
+
+                group.throughput(Throughput::Bytes((n * ARGBUF_LEN) as u64));
+                group.bench_with_input(
+                    BenchmarkId::new(format!("{data}/elide_{elide}"), n),
+                    &dataset,
+                    |b, dataset| {
+                        b.iter_batched(
+                            || dirty_session(&vm, root, id, dataset),
+                            |session| {
+                                session
+                                    .commit()
+                                    .expect("Committing should succeed")
+                            },
+                            BatchSize::PerIteration,
+                        );
+                    },
+                );
+            }
+        }
+    }
+
+    group.finish();
+}
+
+fn diff(c: &mut Criterion) {
+    let (vm, id, root) = grower_vm(false);
+
+    let mut group = c.benchmark_group("diff");
+    group.sample_size(10);
+
+    for n in APPENDS {
+        let dataset = dataset(Data::Random, n);
+        let new_root = dirty_session(&vm, root, id, &dataset)
+            .commit()
+            .expect("Committing should succeed");
+
+        for page_size in TRACKING_PAGE_SIZES {
+            vm.set_tracking_page_size(page_size);
+
+            group.throughput(Throughput::Bytes((n * ARGBUF_LEN) as u64));
+            group.bench_with_input(
+                BenchmarkId::new(format!("page_size_{page_size:#x}"), n),
+                &new_root,
+                |b, &new_root| {
+                    b.iter(|| {
+                        black_box(
+                            vm.diff_commits(root, new_root)
+                                .expect("Diffing should succeed"),
+                        )
+                    });
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, commit, diff);
+criterion_main!(benches);
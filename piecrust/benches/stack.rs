@@ -5,51 +5,66 @@
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use piecrust::{contract_bytecode, VM};
+use piecrust::{contract_bytecode, ContractData, SessionData, VM};
 
 const SAMPLE_SIZE: usize = 10240;
 
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
 fn config() -> Criterion {
     Criterion::default().sample_size(SAMPLE_SIZE)
 }
 
 fn push(c: &mut Criterion) {
-    let mut vm = VM::ephemeral().expect("Ephemeral VM should succeed");
+    let vm = VM::ephemeral().expect("Ephemeral VM should succeed");
 
-    let id = vm
-        .deploy(contract_bytecode!("stack"))
-        .expect("Deployment should succeed");
+    let mut session = vm
+        .session(SessionData::builder())
+        .expect("Instantiating session should succeed");
 
-    let mut session = vm.session();
+    let id = session
+        .deploy(
+            contract_bytecode!("stack"),
+            ContractData::builder().owner(OWNER),
+            LIMIT,
+        )
+        .expect("Deployment should succeed");
 
     c.bench_function("push", |b| {
         b.iter(|| {
             session
-                .call::<i32, ()>(id, "push", black_box(42))
+                .call::<i32, ()>(id, "push", black_box(&42), LIMIT)
                 .expect("Pushing should succeed");
         });
     });
 }
 
 fn pop(c: &mut Criterion) {
-    let mut vm = VM::ephemeral().expect("Ephemeral VM should succeed");
+    let vm = VM::ephemeral().expect("Ephemeral VM should succeed");
 
-    let id = vm
-        .deploy(contract_bytecode!("stack"))
-        .expect("Deployment should succeed");
+    let mut session = vm
+        .session(SessionData::builder())
+        .expect("Instantiating session should succeed");
 
-    let mut session = vm.session();
+    let id = session
+        .deploy(
+            contract_bytecode!("stack"),
+            ContractData::builder().owner(OWNER),
+            LIMIT,
+        )
+        .expect("Deployment should succeed");
 
     for _ in 0..SAMPLE_SIZE {
         session
-            .call(id, "push", black_box(42))
-            .expect("Pushing should succeed")
+            .call::<i32, ()>(id, "push", black_box(&42), LIMIT)
+            .expect("Pushing should succeed");
     }
 
     c.bench_function("pop", |b| {
         b.iter(|| {
             session
-                .call::<(), Option<i32>>(id, "pop", ())
+                .call::<(), Option<i32>>(id, "pop", &(), LIMIT)
                 .expect("Popping should succeed");
         });
     });
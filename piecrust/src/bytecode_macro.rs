@@ -4,6 +4,13 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
+/// Includes the bytecode of an in-tree test contract, as built by `make
+/// contracts`.
+///
+/// The contracts are built with a pinned compiler and fixed flags, and `make
+/// check-contract-hashes` verifies them against the hashes recorded in
+/// `contracts/hashes.sha256`, ensuring tests always run against the same
+/// bytecode.
 #[macro_export]
 macro_rules! contract_bytecode {
     ($name:literal) => {
@@ -0,0 +1,183 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::cell::Cell;
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::vm::MAX_WASM_STACK;
+
+/// The native stack reserved for the host functions and engine frames of each
+/// call, on top of what its WASM may use.
+const HOST_STACK_PER_CALL: usize = 0x40000;
+
+/// The native stack reserved for each call in a chain of nested calls.
+///
+/// Entering a contract not already executing gives it a fresh allowance of
+/// [`MAX_WASM_STACK`], so each contract in a chain may use that much on top
+/// of the contracts below it.
+const STACK_PER_CALL: usize = MAX_WASM_STACK + HOST_STACK_PER_CALL;
+
+/// The largest native stack a call thread is spawned with.
+///
+/// Reserving [`STACK_PER_CALL`] for each call a session allows would come to
+/// hundreds of MiB for its default depth, while real chains use a fraction of
+/// their allowance. Chains deeper than the stack fits are instead stopped by
+/// measuring the stack left before each nested call - see
+/// [`has_stack_for_call`].
+const MAX_STACK: usize = 0x400_0000;
+
+/// The native stack left out when measuring what is left, for the frames the
+/// thread starts with.
+const STACK_SLACK: usize = 0x10000;
+
+thread_local! {
+    /// The lowest address of the stack of the call thread running on the
+    /// current thread, or zero if it isn't one.
+    static STACK_FLOOR: Cell<usize> = Cell::new(0);
+}
+
+/// Returns the address of the top of the native stack, roughly.
+#[inline(never)]
+fn stack_pointer() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+/// Returns whether there's enough native stack left to make another nested
+/// call, always being true on threads other than call threads.
+pub(crate) fn has_stack_for_call() -> bool {
+    let floor = STACK_FLOOR.with(Cell::get);
+    stack_pointer().saturating_sub(floor) >= STACK_PER_CALL
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A thread running the calls the host makes into contracts, on a native
+/// stack sized to fit a chain of nested calls as deep as a session allows.
+///
+/// Nested calls re-enter the engine on the stack of the call that made them,
+/// so running them on the thread of the host would leave the depth a chain
+/// can reach to the size of its stack, and overflowing it would abort the
+/// whole process.
+///
+/// The thread stops once it's dropped.
+pub(crate) struct CallThread {
+    jobs: mpsc::Sender<Job>,
+    stack_size: usize,
+}
+
+impl CallThread {
+    /// Spawns a thread able to run calls nesting up to `max_depth` deep, as
+    /// long as they fit in [`MAX_STACK`].
+    pub(crate) fn spawn(max_depth: usize) -> io::Result<Self> {
+        // The calls are made on top of the frames of the thread itself
+        let stack_size = max_depth
+            .saturating_add(1)
+            .saturating_mul(STACK_PER_CALL)
+            .min(MAX_STACK);
+
+        let (jobs, rx) = mpsc::channel::<Job>();
+        thread::Builder::new()
+            .name(String::from("piecrust-calls"))
+            .stack_size(stack_size)
+            .spawn(move || {
+                let floor = stack_pointer() + STACK_SLACK - stack_size;
+                STACK_FLOOR.with(|f| f.set(floor));
+
+                for job in rx {
+                    job();
+                }
+            })?;
+
+        Ok(Self { jobs, stack_size })
+    }
+
+    /// Runs `f` on the thread, blocking until it returns.
+    ///
+    /// If `f` is still running after the given `timeout`, `on_timeout` is
+    /// called, and is expected to make it return early. If `f` panics, the
+    /// panic is resumed on the calling thread.
+    pub(crate) fn run<R, F, T>(
+        &self,
+        f: F,
+        timeout: Option<Duration>,
+        on_timeout: T,
+    ) -> R
+    where
+        R: Send + 'static,
+        F: FnOnce() -> R + Send + 'static,
+        T: FnOnce(),
+    {
+        let (tx, rx) = mpsc::sync_channel(1);
+
+        self.jobs
+            .send(Box::new(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(f));
+                let _ = tx.send(result);
+            }))
+            .expect("The call thread should be running");
+
+        let result = match timeout.map(|timeout| rx.recv_timeout(timeout)) {
+            Some(Ok(result)) => Ok(result),
+            Some(Err(RecvTimeoutError::Timeout)) => {
+                on_timeout();
+                rx.recv()
+            }
+            Some(Err(RecvTimeoutError::Disconnected)) => Err(mpsc::RecvError),
+            None => rx.recv(),
+        };
+
+        match result.expect("The call thread should return a result") {
+            Ok(ret) => ret,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl Debug for CallThread {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallThread")
+            .field("stack_size", &self.stack_size)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The stack used by each recursion, leaving room for the copies of it
+    /// made by unoptimized builds within what a call may use.
+    const FRAME: usize = MAX_WASM_STACK / 4;
+
+    /// Recurses until there's no stack left for another call, returning the
+    /// depth reached.
+    fn recurse(depth: usize) -> usize {
+        if !has_stack_for_call() {
+            return depth;
+        }
+        let frame = std::hint::black_box([0u8; FRAME]);
+        recurse(depth + 1) + frame[depth % FRAME] as usize
+    }
+
+    #[test]
+    fn stack_capped() {
+        assert!(has_stack_for_call(), "Other threads are never limited");
+
+        let thread = CallThread::spawn(usize::MAX)
+            .expect("Spawning the thread should succeed");
+        assert_eq!(thread.stack_size, MAX_STACK);
+
+        // Running out of stack stops the recursion, rather than overflowing
+        let depth = thread.run(|| recurse(0), None, || {});
+        assert!(depth >= MAX_STACK / STACK_PER_CALL - 1);
+    }
+}
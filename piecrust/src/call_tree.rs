@@ -28,14 +28,15 @@ impl CallTree {
         Self(None)
     }
 
-    /// Push an element to the call tree.
+    /// Push an element to the call tree, recording the name of the function
+    /// called.
     ///
     /// This pushes a new child to the current node, and advances to it.
-    pub(crate) fn push(&mut self, elem: CallTreeElem) {
+    pub(crate) fn push(&mut self, elem: CallTreeElem, fn_name: String) {
         match self.0 {
-            None => self.0 = Some(CallTreeNode::new(elem)),
+            None => self.0 = Some(CallTreeNode::new(elem, fn_name)),
             Some(inner) => unsafe {
-                let node = CallTreeNode::with_parent(elem, inner);
+                let node = CallTreeNode::with_parent(elem, fn_name, inner);
                 (*inner).children.push(node);
                 self.0 = Some(node)
             },
@@ -115,6 +116,26 @@ impl CallTree {
         v
     }
 
+    /// Returns the contract id, function name, and gas limit of each call,
+    /// from the current node up to the root.
+    pub(crate) fn call_frames(&self) -> Vec<(&ContractId, &str, u64)> {
+        let mut v = Vec::new();
+        let mut current = self.0;
+
+        while let Some(inner) = current {
+            unsafe {
+                v.push((
+                    &(*inner).elem.contract_id,
+                    (*inner).fn_name.as_str(),
+                    (*inner).elem.limit,
+                ));
+                current = (*inner).parent;
+            }
+        }
+
+        v
+    }
+
     /// Clears the call tree of all elements.
     pub(crate) fn clear(&mut self) {
         unsafe {
@@ -134,6 +155,14 @@ impl CallTree {
     /// Returns an iterator over the call tree, starting from the rightmost
     /// leaf, and proceeding to the top of the current position of the tree.
     pub fn iter(&self) -> impl Iterator<Item = &CallTreeElem> {
+        self.iter_named().map(|(elem, _)| elem)
+    }
+
+    /// Returns an iterator over the call tree together with the name of the
+    /// function called in each element, in the same order as [`iter`].
+    ///
+    /// [`iter`]: CallTree::iter
+    pub fn iter_named(&self) -> impl Iterator<Item = (&CallTreeElem, &str)> {
         CallTreeIter {
             tree: self.0.map(|root| unsafe {
                 let mut node = root;
@@ -185,22 +214,29 @@ unsafe fn free_tree(root: *mut CallTreeNode) {
 
 struct CallTreeNode {
     elem: CallTreeElem,
+    fn_name: String,
     children: Vec<*mut Self>,
     parent: Option<*mut Self>,
 }
 
 impl CallTreeNode {
-    fn new(elem: CallTreeElem) -> *mut Self {
+    fn new(elem: CallTreeElem, fn_name: String) -> *mut Self {
         Box::leak(Box::new(Self {
             elem,
+            fn_name,
             children: Vec::new(),
             parent: None,
         }))
     }
 
-    fn with_parent(elem: CallTreeElem, parent: *mut Self) -> *mut Self {
+    fn with_parent(
+        elem: CallTreeElem,
+        fn_name: String,
+        parent: *mut Self,
+    ) -> *mut Self {
         Box::leak(Box::new(Self {
             elem,
+            fn_name,
             children: Vec::new(),
             parent: Some(parent),
         }))
@@ -217,7 +253,7 @@ struct CallTreeIter<'a> {
 }
 
 impl<'a> Iterator for CallTreeIter<'a> {
-    type Item = &'a CallTreeElem;
+    type Item = (&'a CallTreeElem, &'a str);
 
     fn next(&mut self) -> Option<Self::Item> {
         // SAFETY: This is safe since we guarantee that the tree exists between
@@ -227,7 +263,7 @@ impl<'a> Iterator for CallTreeIter<'a> {
             let tree = self.tree.as_mut()?;
 
             let node = tree.node;
-            let elem = &(*node).elem;
+            let elem = (&(*node).elem, (*node).fn_name.as_str());
 
             if node == tree.root {
                 self.tree = None;
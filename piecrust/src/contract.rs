@@ -8,15 +8,39 @@ use std::sync::Arc;
 
 use bytecheck::CheckBytes;
 use dusk_wasmtime::{Engine, Module};
-use piecrust_uplink::ContractId;
-use rkyv::{Archive, Deserialize, Serialize};
+use piecrust_uplink::{
+    ContractId, ARGBUF_LEN, CONTRACT_ID_BYTES, PURE_SECTION, SCRATCH_BUF_BYTES,
+    SCRATCH_MEMORY,
+};
+use rkyv::ser::serializers::{BufferScratch, BufferSerializer};
+use rkyv::ser::Serializer;
+use rkyv::{Archive, Deserialize, Infallible, Serialize};
 
 use crate::error::Error;
+use crate::types::StandardBufSerializer;
+
+mod exports;
+mod features;
+mod nans;
+mod provenance;
+mod sections;
+mod validation;
+
+pub use exports::{ExportInfo, FnSchema};
+pub(crate) use features::denied_feature;
+pub use features::{WasmFeature, WasmFeatures};
+pub use provenance::{Producer, Provenance, Version};
+pub use validation::{InvalidReason, ValidationConfig};
 
 pub struct ContractData<'a, A> {
     pub(crate) contract_id: Option<ContractId>,
     pub(crate) init_arg: Option<&'a A>,
     pub(crate) owner: Option<Vec<u8>>,
+    pub(crate) nonce: u64,
+    pub(crate) deploy_limit: Option<u64>,
+    pub(crate) init_limit: Option<u64>,
+    pub(crate) owner_only: Vec<String>,
+    pub(crate) pure: Vec<String>,
 }
 
 // `()` is done on purpose, since by default it should be that the initializer
@@ -31,6 +55,11 @@ impl<'a> ContractData<'a, ()> {
             contract_id: None,
             init_arg: None,
             owner: None,
+            nonce: 0,
+            deploy_limit: None,
+            init_limit: None,
+            owner_only: Vec::new(),
+            pure: Vec::new(),
         }
     }
 }
@@ -45,6 +74,11 @@ pub struct ContractDataBuilder<'a, A> {
     contract_id: Option<ContractId>,
     owner: Option<Vec<u8>>,
     init_arg: Option<&'a A>,
+    nonce: u64,
+    deploy_limit: Option<u64>,
+    init_limit: Option<u64>,
+    owner_only: Vec<String>,
+    pure: Vec<String>,
 }
 
 impl<'a, A> ContractDataBuilder<'a, A> {
@@ -62,6 +96,11 @@ impl<'a, A> ContractDataBuilder<'a, A> {
             contract_id: self.contract_id,
             owner: self.owner,
             init_arg: Some(arg),
+            nonce: self.nonce,
+            deploy_limit: self.deploy_limit,
+            init_limit: self.init_limit,
+            owner_only: self.owner_only,
+            pure: self.pure,
         }
     }
 
@@ -77,20 +116,329 @@ impl<'a, A> ContractDataBuilder<'a, A> {
         self
     }
 
+    /// Set the deployment nonce, recorded in the contract's metadata.
+    ///
+    /// When no contract ID is set, a non-zero nonce is hashed together with
+    /// the bytecode to compute the ID, allowing the same bytecode to be
+    /// deployed more than once.
+    pub fn nonce(mut self, nonce: u64) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    /// Set the gas limit for deploying the contract, out of which the
+    /// session's [`GasSchedule::deploy_byte_cost`] is charged for each byte
+    /// of bytecode. Defaults to the limit passed to the deployment.
+    ///
+    /// [`GasSchedule::deploy_byte_cost`]: crate::GasSchedule::deploy_byte_cost
+    pub fn deploy_limit(mut self, limit: u64) -> Self {
+        self.deploy_limit = Some(limit);
+        self
+    }
+
+    /// Set the gas limit for calling the contract's `init` function. Defaults
+    /// to the limit passed to the deployment.
+    pub fn init_limit(mut self, limit: u64) -> Self {
+        self.init_limit = Some(limit);
+        self
+    }
+
+    /// Only allow the contract's owner to call the given functions.
+    ///
+    /// The host refuses calls to these functions whose sender isn't the owner
+    /// with [`Error::UnauthorizedCall`], before the contract is ever called.
+    /// The sender of an inter-contract call is the calling contract, while
+    /// the sender of a call made by the host is declared in the
+    /// [`SENDER_META`] item of the session's metadata.
+    ///
+    /// [`SENDER_META`]: crate::SENDER_META
+    pub fn owner_only<I, S>(mut self, fn_names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.owner_only = fn_names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Declare the given functions pure, in addition to those the bytecode
+    /// declares in its [`PURE_SECTION`] custom section.
+    ///
+    /// The results of calls made by the host to pure functions are cached by
+    /// the session, and returned again without executing the contract for as
+    /// long as the state of the contracts the call touched stays the same.
+    /// See [`Session::call`] for more details.
+    ///
+    /// [`PURE_SECTION`]: piecrust_uplink::PURE_SECTION
+    /// [`Session::call`]: crate::Session::call
+    pub fn pure<I, S>(mut self, fn_names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.pure = fn_names.into_iter().map(Into::into).collect();
+        self
+    }
+
     pub fn build(self) -> ContractData<'a, A> {
         ContractData {
             contract_id: self.contract_id,
             init_arg: self.init_arg,
             owner: self.owner,
+            nonce: self.nonce,
+            deploy_limit: self.deploy_limit,
+            init_limit: self.init_limit,
+            owner_only: self.owner_only,
+            pure: self.pure,
+        }
+    }
+}
+
+/// A contract to be deployed as part of a bundle, using
+/// [`Session::deploy_bundle`].
+///
+/// Contracts in a bundle can refer to each other in their initializer
+/// arguments using [`placeholder`] IDs, since the ID of a contract may not be
+/// known before the bundle is put together.
+///
+/// [`Session::deploy_bundle`]: crate::Session::deploy_bundle
+/// [`placeholder`]: DeploySpec::placeholder
+#[derive(Debug, Clone, PartialEq, Eq, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes))]
+pub struct DeploySpec {
+    pub(crate) bytecode: Vec<u8>,
+    pub(crate) contract_id: Option<ContractId>,
+    pub(crate) init_arg: Option<Vec<u8>>,
+    pub(crate) owner: Vec<u8>,
+    pub(crate) gas_limit: u64,
+}
+
+impl DeploySpec {
+    /// Specify the deployment of the given `bytecode`, owned by the given
+    /// `owner`, with both its deployment and its initialization executed with
+    /// the given `gas_limit`.
+    pub fn new(
+        bytecode: impl Into<Vec<u8>>,
+        owner: impl Into<Vec<u8>>,
+        gas_limit: u64,
+    ) -> Self {
+        Self {
+            bytecode: bytecode.into(),
+            contract_id: None,
+            init_arg: None,
+            owner: owner.into(),
+            gas_limit,
+        }
+    }
+
+    /// Set the deployment contract ID.
+    pub fn contract_id(mut self, id: ContractId) -> Self {
+        self.contract_id = Some(id);
+        self
+    }
+
+    /// Set the serialized initializer argument for deployment.
+    ///
+    /// Any [`placeholder`] ID in the argument is replaced by the ID of the
+    /// contract it stands for before the argument is passed to `init`.
+    ///
+    /// [`placeholder`]: DeploySpec::placeholder
+    pub fn init_arg(mut self, arg: impl Into<Vec<u8>>) -> Self {
+        self.init_arg = Some(arg.into());
+        self
+    }
+
+    /// Returns the placeholder ID standing for the contract at the given
+    /// `index` in a bundle.
+    pub const fn placeholder(index: u32) -> ContractId {
+        let mut bytes = [0xff; CONTRACT_ID_BYTES];
+        let index = index.to_le_bytes();
+
+        let mut i = 0;
+        while i < index.len() {
+            bytes[CONTRACT_ID_BYTES - index.len() + i] = index[i];
+            i += 1;
+        }
+
+        ContractId::from_bytes(bytes)
+    }
+
+    /// Returns the ID the contract will be deployed with - computed from its
+    /// bytecode if none was set.
+    pub(crate) fn id(&self) -> ContractId {
+        self.contract_id
+            .unwrap_or_else(|| gen_contract_id(&self.bytecode, 0))
+    }
+}
+
+/// Computes the ID of a contract deployed without one, from its `bytecode`
+/// and deployment `nonce`.
+///
+/// A zero nonce leaves the ID as the hash of the bytecode alone.
+pub(crate) fn gen_contract_id(bytecode: &[u8], nonce: u64) -> ContractId {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(bytecode);
+    if nonce != 0 {
+        hasher.update(&nonce.to_le_bytes());
+    }
+    ContractId::from_bytes(hasher.finalize().into())
+}
+
+/// Replaces every [`placeholder`] ID in the given `arg` by the ID at the same
+/// index in `ids`.
+///
+/// [`placeholder`]: DeploySpec::placeholder
+pub(crate) fn resolve_placeholders(arg: &mut [u8], ids: &[ContractId]) {
+    if arg.len() < CONTRACT_ID_BYTES {
+        return;
+    }
+
+    let mut i = 0;
+    while i <= arg.len() - CONTRACT_ID_BYTES {
+        let window = &mut arg[i..][..CONTRACT_ID_BYTES];
+
+        let resolved = ids.iter().enumerate().find_map(|(index, id)| {
+            let placeholder = DeploySpec::placeholder(index as u32);
+            (*window == *placeholder.as_bytes()).then_some(id)
+        });
+
+        match resolved {
+            Some(id) => {
+                window.copy_from_slice(id.as_bytes());
+                i += CONTRACT_ID_BYTES;
+            }
+            None => i += 1,
         }
     }
 }
 
+/// Metadata of a contract, maintained by the host and persisted alongside its
+/// bytecode.
+///
+/// The metadata is set when the contract is deployed, and never changes
+/// after, with the exception of a [`migration`] moving it to the ID of the
+/// contract it replaces.
+///
+/// [`migration`]: crate::Session::migrate
 #[derive(Archive, Serialize, Deserialize, Debug, Clone)]
 #[archive_attr(derive(CheckBytes))]
 pub struct ContractMetadata {
     pub contract_id: ContractId,
     pub owner: Vec<u8>,
+    /// The nonce given when deploying the contract.
+    pub nonce: u64,
+    /// The serialized argument passed to the contract's `init` function, if
+    /// any was given.
+    pub init_arg: Option<Vec<u8>>,
+    /// The functions only the owner of the contract may call.
+    pub owner_only: Vec<String>,
+    /// The functions declared pure, whose results the host may cache.
+    pub pure: Vec<String>,
+    /// How the contract's bytecode was produced. This is not persisted with
+    /// the rest of the metadata, but read back from the bytecode on load.
+    #[with(rkyv::with::Skip)]
+    pub provenance: Provenance,
+}
+
+impl ContractMetadata {
+    /// Serializes the metadata to be persisted.
+    pub(crate) fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        // The initializer argument may take up the whole argument buffer on
+        // its own, and the names of the owner-only and pure functions are
+        // unbounded.
+        let len = ARGBUF_LEN
+            + self.init_arg.as_ref().map_or(0, Vec::len)
+            + self.owner_only.iter().map(String::len).sum::<usize>()
+            + self.pure.iter().map(String::len).sum::<usize>();
+        let mut buf = vec![0u8; len];
+        let mut sbuf = [0u8; SCRATCH_BUF_BYTES];
+
+        let ser = BufferSerializer::new(&mut buf[..]);
+        let scratch = BufferScratch::new(&mut sbuf);
+
+        let mut serializer =
+            StandardBufSerializer::new(ser, scratch, Infallible);
+        serializer.serialize_value(self)?;
+
+        let pos = serializer.pos();
+        buf.truncate(pos);
+
+        Ok(buf)
+    }
+}
+
+/// The layout the metadata of contracts was persisted with, before their pure
+/// functions were recorded.
+#[derive(Archive, Deserialize, Debug)]
+#[archive_attr(derive(CheckBytes))]
+pub(crate) struct RestrictedContractMetadata {
+    contract_id: ContractId,
+    owner: Vec<u8>,
+    nonce: u64,
+    init_arg: Option<Vec<u8>>,
+    owner_only: Vec<String>,
+}
+
+impl From<RestrictedContractMetadata> for ContractMetadata {
+    fn from(restricted: RestrictedContractMetadata) -> Self {
+        Self {
+            contract_id: restricted.contract_id,
+            owner: restricted.owner,
+            nonce: restricted.nonce,
+            init_arg: restricted.init_arg,
+            owner_only: restricted.owner_only,
+            pure: Vec::new(),
+            provenance: Provenance::default(),
+        }
+    }
+}
+
+/// The layout the metadata of contracts was persisted with, before the
+/// functions only their owner may call were recorded.
+#[derive(Archive, Deserialize, Debug)]
+#[archive_attr(derive(CheckBytes))]
+pub(crate) struct NoncedContractMetadata {
+    contract_id: ContractId,
+    owner: Vec<u8>,
+    nonce: u64,
+    init_arg: Option<Vec<u8>>,
+}
+
+impl From<NoncedContractMetadata> for ContractMetadata {
+    fn from(nonced: NoncedContractMetadata) -> Self {
+        Self {
+            contract_id: nonced.contract_id,
+            owner: nonced.owner,
+            nonce: nonced.nonce,
+            init_arg: nonced.init_arg,
+            owner_only: Vec::new(),
+            pure: Vec::new(),
+            provenance: Provenance::default(),
+        }
+    }
+}
+
+/// The layout the metadata of contracts was persisted with, before the nonce
+/// and initializer argument were recorded.
+#[derive(Archive, Deserialize, Debug)]
+#[archive_attr(derive(CheckBytes))]
+pub(crate) struct LegacyContractMetadata {
+    contract_id: ContractId,
+    owner: Vec<u8>,
+}
+
+impl From<LegacyContractMetadata> for ContractMetadata {
+    fn from(legacy: LegacyContractMetadata) -> Self {
+        Self {
+            contract_id: legacy.contract_id,
+            owner: legacy.owner,
+            nonce: 0,
+            init_arg: None,
+            owner_only: Vec::new(),
+            pure: Vec::new(),
+            provenance: Provenance::default(),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -107,7 +455,15 @@ impl WrappedContract {
         let serialized = match module {
             Some(obj) => obj.as_ref().to_vec(),
             _ => {
-                let contract = Module::new(engine, bytecode.as_ref())?;
+                let bytecode = bytecode.as_ref();
+                let contract =
+                    Module::new(engine, bytecode).map_err(|err| {
+                        match denied_feature(engine, bytecode) {
+                            Some(feature) => Error::DeniedWasmFeature(feature),
+                            None => Error::from(err),
+                        }
+                    })?;
+                check_memory_indices(&contract, bytecode)?;
                 contract.serialize()?.to_vec()
             }
         };
@@ -121,3 +477,39 @@ impl WrappedContract {
         &self.serialized
     }
 }
+
+/// Returns the names of the functions the given `bytecode` declares pure.
+///
+/// Names that aren't valid UTF-8 are skipped, since no function could be
+/// called by them.
+pub(crate) fn pure_fns(bytecode: &[u8]) -> Vec<String> {
+    let Some(section) = sections::custom_section(bytecode, PURE_SECTION) else {
+        return Vec::new();
+    };
+
+    section
+        .split(|byte| *byte == 0)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| String::from_utf8(name.to_vec()).ok())
+        .collect()
+}
+
+/// Ensures that, when a contract declares a scratch memory, its main memory
+/// comes first.
+///
+/// Memories are created in the order of their indices, which the session
+/// relies on to tell the main memory apart from the scratch memory.
+fn check_memory_indices(module: &Module, bytecode: &[u8]) -> Result<(), Error> {
+    let has_scratch = module
+        .exports()
+        .any(|exp| exp.name() == SCRATCH_MEMORY && exp.ty().memory().is_some());
+
+    if has_scratch
+        && (sections::exported_memory(bytecode, "memory") != Some(0)
+            || sections::exported_memory(bytecode, SCRATCH_MEMORY) != Some(1))
+    {
+        return Err(Error::InvalidMemory);
+    }
+
+    Ok(())
+}
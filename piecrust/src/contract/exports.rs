@@ -0,0 +1,92 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::collections::BTreeMap;
+
+use piecrust_uplink::ABI_SECTION;
+
+use super::sections::{custom_section, exported_functions};
+
+/// A function exported by a contract, as listed by
+/// [`Session::contract_exports`].
+///
+/// [`Session::contract_exports`]: crate::Session::contract_exports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportInfo {
+    /// The name of the function.
+    pub name: String,
+    /// The types of the function, if the contract declares them.
+    pub schema: Option<FnSchema>,
+}
+
+impl ExportInfo {
+    /// Reads the functions exported by the given `bytecode`, in the order they
+    /// are exported, together with the declarations made for them in its
+    /// [`ABI_SECTION`].
+    ///
+    /// A malformed section is read up to where it stops making sense, and
+    /// declarations made for functions that aren't exported are ignored.
+    pub fn from_bytecode(bytecode: &[u8]) -> Vec<Self> {
+        let mut schemas = schemas(bytecode);
+
+        exported_functions(bytecode)
+            .into_iter()
+            .map(|name| Self {
+                name: name.into(),
+                schema: schemas.remove(name),
+            })
+            .collect()
+    }
+}
+
+/// The types of the argument and return of a function, and whether it takes
+/// the contract's state mutably, as declared by a contract using uplink's
+/// `export_schema!`.
+///
+/// Declarations are given as written in the contract's source, and are not
+/// checked against the function itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FnSchema {
+    /// The type of the function's argument.
+    pub arg: String,
+    /// The type of the function's return.
+    pub ret: String,
+    /// Whether the function takes the state mutably, and may therefore change
+    /// it.
+    pub mutates: bool,
+}
+
+fn schemas(bytecode: &[u8]) -> BTreeMap<&str, FnSchema> {
+    let mut schemas = BTreeMap::new();
+
+    let Some(section) = custom_section(bytecode, ABI_SECTION) else {
+        return schemas;
+    };
+
+    let mut fields = section.split(|byte| *byte == 0).map(std::str::from_utf8);
+    while let (
+        Some(Ok(name)),
+        Some(Ok(arg)),
+        Some(Ok(ret)),
+        Some(Ok(mutability)),
+    ) = (fields.next(), fields.next(), fields.next(), fields.next())
+    {
+        let ret = match ret {
+            "" => "()",
+            ret => ret,
+        };
+        schemas.insert(
+            name,
+            FnSchema {
+                arg: arg.into(),
+                ret: ret.into(),
+                mutates: mutability == "mut",
+            },
+        );
+    }
+
+    schemas
+}
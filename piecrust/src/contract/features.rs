@@ -0,0 +1,201 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fmt::{self, Display, Formatter};
+
+use dusk_wasmtime::{Config, Engine, Module};
+
+use super::sections;
+
+/// A WebAssembly proposal that contracts may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum WasmFeature {
+    /// Bulk memory operations, such as `memory.copy` and `memory.fill`.
+    BulkMemory,
+    /// Reference types, and multiple tables.
+    ReferenceTypes,
+    /// Fixed-width 128-bit SIMD.
+    Simd,
+    /// Threads, shared memories and atomic operations.
+    Threads,
+    /// Functions and blocks returning multiple values.
+    MultiValue,
+}
+
+impl WasmFeature {
+    /// Every feature that can be configured.
+    pub const ALL: [WasmFeature; 5] = [
+        WasmFeature::BulkMemory,
+        WasmFeature::ReferenceTypes,
+        WasmFeature::Simd,
+        WasmFeature::Threads,
+        WasmFeature::MultiValue,
+    ];
+
+    /// The name of the feature, as known to the WebAssembly specification.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WasmFeature::BulkMemory => "bulk-memory",
+            WasmFeature::ReferenceTypes => "reference-types",
+            WasmFeature::Simd => "simd",
+            WasmFeature::Threads => "threads",
+            WasmFeature::MultiValue => "multi-value",
+        }
+    }
+
+    fn enable(&self, config: &mut Config) {
+        match self {
+            WasmFeature::BulkMemory => config.wasm_bulk_memory(true),
+            WasmFeature::ReferenceTypes => config.wasm_reference_types(true),
+            WasmFeature::Simd => config.wasm_simd(true),
+            WasmFeature::Threads => config,
+            WasmFeature::MultiValue => config.wasm_multi_value(true),
+        };
+    }
+}
+
+impl Display for WasmFeature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The WebAssembly proposals accepted in contract bytecode.
+///
+/// Every feature is set explicitly on the engine, as opposed to relying on its
+/// defaults, which may change between versions and cause nodes to disagree on
+/// what bytecode is valid. Bytecode using a denied feature is rejected when
+/// deployed with [`Error::DeniedWasmFeature`], and modules compiled with a
+/// different set of features fail to load.
+///
+/// By default every feature is allowed, except for [threads], which the engine
+/// is built without support for, and which are therefore always denied.
+///
+/// Reference types depend on bulk memory operations, so denying the latter
+/// also denies the former.
+///
+/// [`Error::DeniedWasmFeature`]: crate::Error::DeniedWasmFeature
+/// [threads]: WasmFeature::Threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WasmFeatures {
+    bulk_memory: bool,
+    reference_types: bool,
+    simd: bool,
+    multi_value: bool,
+}
+
+impl Default for WasmFeatures {
+    fn default() -> Self {
+        Self {
+            bulk_memory: true,
+            reference_types: true,
+            simd: true,
+            multi_value: true,
+        }
+    }
+}
+
+impl WasmFeatures {
+    /// Allows the given `feature`, along with the features it depends on.
+    ///
+    /// Allowing [threads] has no effect.
+    ///
+    /// [threads]: WasmFeature::Threads
+    pub fn allow(self, feature: WasmFeature) -> Self {
+        self.set(feature, true)
+    }
+
+    /// Denies the given `feature`, along with the features depending on it.
+    pub fn deny(self, feature: WasmFeature) -> Self {
+        self.set(feature, false)
+    }
+
+    /// Returns whether the given `feature` is allowed.
+    pub fn is_allowed(&self, feature: WasmFeature) -> bool {
+        match feature {
+            WasmFeature::BulkMemory => self.bulk_memory,
+            WasmFeature::ReferenceTypes => self.reference_types,
+            WasmFeature::Simd => self.simd,
+            WasmFeature::Threads => false,
+            WasmFeature::MultiValue => self.multi_value,
+        }
+    }
+
+    fn set(mut self, feature: WasmFeature, allowed: bool) -> Self {
+        match feature {
+            WasmFeature::BulkMemory => {
+                self.bulk_memory = allowed;
+                self.reference_types &= allowed;
+            }
+            WasmFeature::ReferenceTypes => {
+                self.reference_types = allowed;
+                self.bulk_memory |= allowed;
+            }
+            WasmFeature::Simd => self.simd = allowed,
+            WasmFeature::Threads => {}
+            WasmFeature::MultiValue => self.multi_value = allowed,
+        }
+        self
+    }
+
+    /// Sets the features on the given engine `config`.
+    pub(crate) fn apply(&self, config: &mut Config) {
+        config.wasm_bulk_memory(self.bulk_memory);
+        config.wasm_reference_types(self.reference_types);
+        config.wasm_simd(self.simd);
+        config.wasm_multi_value(self.multi_value);
+    }
+}
+
+/// Returns the first feature used by the given `bytecode` that the `engine`
+/// denies, if any.
+///
+/// This is meant to be called after the engine rejected the bytecode, to name
+/// the reason. It is too costly to call on every deployment, since each
+/// feature is tested by validating the bytecode with a differently configured
+/// engine.
+pub(crate) fn denied_feature(
+    engine: &Engine,
+    bytecode: &[u8],
+) -> Option<WasmFeature> {
+    if sections::has_shared_memory(bytecode) {
+        return Some(WasmFeature::Threads);
+    }
+    if Module::validate(engine, bytecode).is_ok() {
+        return None;
+    }
+
+    let validates = |config: Config| match Engine::new(&config) {
+        Ok(engine) => Module::validate(&engine, bytecode).is_ok(),
+        Err(_) => false,
+    };
+
+    let mut config = engine.config().clone();
+    WasmFeatures::default().apply(&mut config);
+    if !validates(config) {
+        return None;
+    }
+
+    // The features the bytecode fails to validate without
+    let used: Vec<_> = WasmFeature::ALL
+        .into_iter()
+        .filter(|feature| {
+            let mut config = engine.config().clone();
+            WasmFeatures::default().deny(*feature).apply(&mut config);
+            *feature != WasmFeature::Threads && !validates(config)
+        })
+        .collect();
+
+    // A used feature is denied if allowing every other used feature on top of
+    // the engine's configuration is not enough for the bytecode to validate.
+    used.iter().copied().find(|feature| {
+        let mut config = engine.config().clone();
+        for other in used.iter().filter(|other| *other != feature) {
+            other.enable(&mut config);
+        }
+        !validates(config)
+    })
+}
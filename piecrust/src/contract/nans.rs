@@ -0,0 +1,328 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Canonicalization of the NaNs produced by float instructions.
+//!
+//! The sign and payload of a NaN produced by an arithmetic float instruction
+//! depend on the platform. Bytecode is made deterministic by following every
+//! such instruction with a check of its result, replacing it with the
+//! canonical NaN of its type if it is a NaN.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use super::sections::{self, Reader, WASM_HEADER_LEN};
+use super::validation::{
+    scan_instructions, CODE_SECTION_ID, F32, F64, FUNC_TYPE_FORM,
+    TYPE_SECTION_ID, V128,
+};
+
+const FUNCTION_SECTION_ID: u8 = 3;
+
+const SELECT: u8 = 0x1b;
+const LOCAL_GET: u8 = 0x20;
+const LOCAL_SET: u8 = 0x21;
+const F32_CONST: u8 = 0x43;
+const F64_CONST: u8 = 0x44;
+const F32_NE: u8 = 0x5c;
+const F64_NE: u8 = 0x62;
+
+const SIMD_PREFIX: u8 = 0xfd;
+const V128_CONST: u8 = 12;
+const F32X4_NE: u8 = 66;
+const F64X2_NE: u8 = 72;
+const V128_BITSELECT: u8 = 82;
+
+const CANONICAL_F32: u32 = 0x7fc0_0000;
+const CANONICAL_F64: u64 = 0x7ff8_0000_0000_0000;
+
+/// The type of the value produced by an instruction that may produce a NaN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum NanType {
+    Float32,
+    Float64,
+    Float32x4,
+    Float64x2,
+}
+
+impl NanType {
+    /// Returns the type of NaN the given numeric instruction may produce, if
+    /// any.
+    ///
+    /// Instructions only operating on the bits of floats - such as `abs`,
+    /// `neg`, `copysign`, and reinterpretations - are deterministic, and so
+    /// are conversions from integers, which never produce NaNs.
+    pub fn of_numeric(opcode: u8) -> Option<Self> {
+        match opcode {
+            // ceil, floor, trunc, nearest, sqrt, add, sub, mul, div, min, max
+            0x8d..=0x97 => Some(Self::Float32),
+            0x9b..=0xa5 => Some(Self::Float64),
+            // f32.demote_f64
+            0xb6 => Some(Self::Float32),
+            // f64.promote_f32
+            0xbb => Some(Self::Float64),
+            _ => None,
+        }
+    }
+
+    /// Returns the type of NaN the given instruction prefixed with `0xfd` may
+    /// produce, if any.
+    pub fn of_simd(opcode: u32) -> Option<Self> {
+        match opcode {
+            // f32x4.demote_f64x2_zero, ceil, floor, trunc, nearest, sqrt,
+            // add, sub, mul, div, min, max
+            94 | 103..=106 | 227..=233 => Some(Self::Float32x4),
+            // f64x2.promote_low_f32x4, ceil, floor, trunc, nearest, sqrt,
+            // add, sub, mul, div, min, max
+            95 | 116 | 117 | 122 | 148 | 239..=245 => Some(Self::Float64x2),
+            _ => None,
+        }
+    }
+
+    fn val_type(self) -> u8 {
+        match self {
+            Self::Float32 => F32,
+            Self::Float64 => F64,
+            Self::Float32x4 | Self::Float64x2 => V128,
+        }
+    }
+
+    /// Writes the instructions replacing the value on top of the stack with
+    /// the canonical NaN if it is a NaN, using the given `local` to hold it.
+    fn write_canonicalization(self, out: &mut Vec<u8>, local: u32) {
+        out.push(LOCAL_SET);
+        write_u32(out, local);
+
+        match self {
+            Self::Float32 => {
+                out.push(F32_CONST);
+                out.extend(CANONICAL_F32.to_le_bytes());
+            }
+            Self::Float64 => {
+                out.push(F64_CONST);
+                out.extend(CANONICAL_F64.to_le_bytes());
+            }
+            Self::Float32x4 => {
+                out.extend([SIMD_PREFIX, V128_CONST]);
+                for _ in 0..4 {
+                    out.extend(CANONICAL_F32.to_le_bytes());
+                }
+            }
+            Self::Float64x2 => {
+                out.extend([SIMD_PREFIX, V128_CONST]);
+                for _ in 0..2 {
+                    out.extend(CANONICAL_F64.to_le_bytes());
+                }
+            }
+        }
+
+        // The value itself, and the comparison with itself that only a NaN
+        // fails
+        for _ in 0..3 {
+            out.push(LOCAL_GET);
+            write_u32(out, local);
+        }
+
+        match self {
+            Self::Float32 => out.extend([F32_NE, SELECT]),
+            Self::Float64 => out.extend([F64_NE, SELECT]),
+            Self::Float32x4 => {
+                out.extend([SIMD_PREFIX, F32X4_NE, SIMD_PREFIX, V128_BITSELECT])
+            }
+            Self::Float64x2 => {
+                out.extend([SIMD_PREFIX, F64X2_NE, SIMD_PREFIX, V128_BITSELECT])
+            }
+        }
+    }
+}
+
+/// An instruction that may produce a NaN, found when scanning a function body.
+pub(super) struct NanOp {
+    /// The number of bytes of the body left after the instruction.
+    pub remaining: usize,
+    pub ty: NanType,
+}
+
+/// Returns the given `bytecode` with the NaNs produced by its float
+/// instructions canonicalized, or `None` if the bytecode isn't understood.
+///
+/// Bytecode without any instruction that may produce a NaN is borrowed as is.
+/// Otherwise only the code section is rewritten, keeping the bytes of all
+/// other sections.
+pub(crate) fn canonicalize_nans(bytecode: &[u8]) -> Option<Cow<[u8]>> {
+    let mut n_params = Vec::new();
+    let mut func_types = Vec::new();
+    let mut code = None;
+
+    // The code section is laid out from the end of the previous section to
+    // the end of its payload.
+    let mut code_range = 0..0;
+    let mut section_end = WASM_HEADER_LEN;
+
+    for (id, payload) in sections::sections(bytecode) {
+        let payload_end = payload.as_ptr() as usize
+            - bytecode.as_ptr() as usize
+            + payload.len();
+        match id {
+            TYPE_SECTION_ID => n_params = param_counts(payload)?,
+            FUNCTION_SECTION_ID => func_types = function_types(payload)?,
+            CODE_SECTION_ID => {
+                code = Some(payload);
+                code_range = section_end..payload_end;
+            }
+            _ => {}
+        }
+        section_end = payload_end;
+    }
+    if section_end != bytecode.len() {
+        return None;
+    }
+
+    let Some(code) = code else {
+        return Some(Cow::Borrowed(bytecode));
+    };
+
+    let mut reader = Reader::new(code);
+    let n_bodies = reader.u32()?;
+    if n_bodies as usize != func_types.len() {
+        return None;
+    }
+
+    let mut new_code = Vec::with_capacity(code.len());
+    write_u32(&mut new_code, n_bodies);
+
+    let mut changed = false;
+    for type_index in func_types {
+        let len = reader.u32()? as usize;
+        let body = reader.bytes(len)?;
+        let n_params = *n_params.get(type_index as usize)?;
+
+        let body = match canonicalize_body(body, n_params)? {
+            Some(body) => {
+                changed = true;
+                Cow::Owned(body)
+            }
+            None => Cow::Borrowed(body),
+        };
+        write_u32(&mut new_code, body.len() as u32);
+        new_code.extend_from_slice(&body);
+    }
+    if !reader.is_empty() {
+        return None;
+    }
+
+    if !changed {
+        return Some(Cow::Borrowed(bytecode));
+    }
+
+    let mut canonicalized = Vec::with_capacity(bytecode.len() + new_code.len());
+    canonicalized.extend_from_slice(&bytecode[..code_range.start]);
+    canonicalized.push(CODE_SECTION_ID);
+    write_u32(&mut canonicalized, new_code.len() as u32);
+    canonicalized.extend(new_code);
+    canonicalized.extend_from_slice(&bytecode[code_range.end..]);
+
+    Some(Cow::Owned(canonicalized))
+}
+
+/// Canonicalizes the NaNs produced in the given function `body`, returning
+/// `Some(None)` if it produces none, and `None` if it isn't understood.
+///
+/// The canonicalized values are held in locals declared after all others, one
+/// for each type of value.
+fn canonicalize_body(body: &[u8], n_params: u32) -> Option<Option<Vec<u8>>> {
+    let mut reader = Reader::new(body);
+
+    let n_entries = reader.u32()?;
+    let entries_start = body.len() - reader.len();
+
+    let mut n_locals = n_params;
+    for _ in 0..n_entries {
+        n_locals = n_locals.checked_add(reader.u32()?)?;
+        reader.byte()?;
+    }
+    let instructions_start = body.len() - reader.len();
+
+    let mut nans = Vec::new();
+    scan_instructions(&mut reader, &mut BTreeSet::new(), &mut nans, false)?;
+    if nans.is_empty() {
+        return Some(None);
+    }
+
+    let mut temp_types = Vec::new();
+    for op in &nans {
+        let val_type = op.ty.val_type();
+        if !temp_types.contains(&val_type) {
+            temp_types.push(val_type);
+        }
+    }
+    let temp_local = |ty: NanType| {
+        let position = temp_types.iter().position(|t| *t == ty.val_type());
+        n_locals + position.expect("There is a local for every type") as u32
+    };
+    n_locals.checked_add(temp_types.len() as u32)?;
+
+    let mut canonicalized = Vec::with_capacity(body.len() + nans.len() * 16);
+
+    write_u32(&mut canonicalized, n_entries + temp_types.len() as u32);
+    canonicalized.extend_from_slice(&body[entries_start..instructions_start]);
+    for val_type in &temp_types {
+        write_u32(&mut canonicalized, 1);
+        canonicalized.push(*val_type);
+    }
+
+    let mut copied = instructions_start;
+    for op in nans {
+        let end = body.len() - op.remaining;
+        canonicalized.extend_from_slice(&body[copied..end]);
+        op.ty
+            .write_canonicalization(&mut canonicalized, temp_local(op.ty));
+        copied = end;
+    }
+    canonicalized.extend_from_slice(&body[copied..]);
+
+    Some(Some(canonicalized))
+}
+
+/// Returns the number of parameters of each type in the type section.
+fn param_counts(payload: &[u8]) -> Option<Vec<u32>> {
+    let mut payload = Reader::new(payload);
+
+    let n_types = payload.u32()?;
+    let mut counts = Vec::with_capacity(n_types as usize);
+    for _ in 0..n_types {
+        if payload.byte()? != FUNC_TYPE_FORM {
+            return None;
+        }
+        let n_params = payload.u32()?;
+        payload.bytes(n_params as usize)?;
+        let n_results = payload.u32()?;
+        payload.bytes(n_results as usize)?;
+        counts.push(n_params);
+    }
+
+    Some(counts)
+}
+
+/// Returns the index of the type of each function in the function section.
+fn function_types(payload: &[u8]) -> Option<Vec<u32>> {
+    let mut payload = Reader::new(payload);
+
+    let n_functions = payload.u32()?;
+    (0..n_functions).map(|_| payload.u32()).collect()
+}
+
+fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
@@ -0,0 +1,144 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fmt;
+
+use piecrust_uplink::UPLINK_VERSION_SECTION;
+
+use super::sections::{custom_section, Reader};
+
+const PRODUCERS_SECTION: &str = "producers";
+
+/// A tool involved in producing a contract's bytecode, as recorded in its
+/// `producers` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Producer {
+    pub name: String,
+    pub version: String,
+}
+
+/// Information on how a contract's bytecode was produced.
+///
+/// This is read from the `producers` custom section emitted by compilers and
+/// linkers, and from the section in which `piecrust-uplink` records its own
+/// version. Both are kept when contracts are stripped by `scripts/strip.sh`,
+/// but other tools may remove them, in which case the corresponding fields are
+/// left empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    /// The source languages the contract was written in.
+    pub language: Vec<Producer>,
+    /// The tools that processed the bytecode, such as `rustc` or `wasm-opt`.
+    pub processed_by: Vec<Producer>,
+    /// The SDKs used to produce the bytecode.
+    pub sdk: Vec<Producer>,
+    /// The version of `piecrust-uplink` the contract was compiled against.
+    pub uplink_version: Option<String>,
+}
+
+impl Provenance {
+    /// Reads the provenance of the given `bytecode`.
+    ///
+    /// Malformed sections are ignored, as are unknown fields in the `producers`
+    /// section.
+    pub fn from_bytecode(bytecode: &[u8]) -> Self {
+        let mut provenance = Self::default();
+
+        if let Some(producers) = custom_section(bytecode, PRODUCERS_SECTION) {
+            provenance.read_producers(producers);
+        }
+
+        provenance.uplink_version =
+            custom_section(bytecode, UPLINK_VERSION_SECTION)
+                .and_then(|version| std::str::from_utf8(version).ok())
+                .map(String::from);
+
+        provenance
+    }
+
+    /// Returns the uplink version parsed into its numeric components.
+    pub fn uplink_version(&self) -> Option<Version> {
+        self.uplink_version.as_deref().and_then(Version::parse)
+    }
+
+    fn read_producers(&mut self, section: &[u8]) -> Option<()> {
+        let mut reader = Reader::new(section);
+
+        let n_fields = reader.u32()?;
+        for _ in 0..n_fields {
+            let field = reader.name()?;
+            let n_values = reader.u32()?;
+
+            let mut producers = Vec::new();
+            for _ in 0..n_values {
+                let name = reader.name()?;
+                let version = reader.name()?;
+                producers.push(Producer {
+                    name: name.into(),
+                    version: version.into(),
+                });
+            }
+
+            match field {
+                "language" => self.language = producers,
+                "processed-by" => self.processed_by = producers,
+                "sdk" => self.sdk = producers,
+                _ => {}
+            }
+        }
+
+        Some(())
+    }
+}
+
+/// A `major.minor.patch` version number.
+///
+/// Any pre-release or build suffix is ignored when parsing, and consequently
+/// when comparing versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses a version of the form `major.minor.patch`.
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.split(['-', '+']).next().unwrap_or_default();
+
+        let mut parts = version.split('.').map(str::parse);
+        let major = parts.next()?.ok()?;
+        let minor = parts.next()?.ok()?;
+        let patch = parts.next()?.ok()?;
+
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl From<(u32, u32, u32)> for Version {
+    fn from((major, minor, patch): (u32, u32, u32)) -> Self {
+        Self::new(major, minor, patch)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
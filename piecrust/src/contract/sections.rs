@@ -0,0 +1,239 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Reading of the sections of WebAssembly bytecode.
+
+const WASM_MAGIC: &[u8] = b"\0asm";
+pub(super) const WASM_HEADER_LEN: usize = 8;
+const CUSTOM_SECTION_ID: u8 = 0;
+const MEMORY_SECTION_ID: u8 = 5;
+const EXPORT_SECTION_ID: u8 = 7;
+const FUNCTION_EXPORT_KIND: u8 = 0;
+const MEMORY_EXPORT_KIND: u8 = 2;
+const LIMITS_HAS_MAX: u8 = 0x01;
+const LIMITS_SHARED: u8 = 0x02;
+
+/// Returns an iterator over the sections of the given `bytecode`, as pairs of
+/// ID and payload.
+///
+/// The bytecode is not validated - that is left to the engine - and iteration
+/// simply stops at the first malformed section.
+pub(crate) fn sections(bytecode: &[u8]) -> Sections<'_> {
+    let mut reader = Reader::new(bytecode);
+
+    if reader.bytes(WASM_HEADER_LEN).map(|h| &h[..4]) != Some(WASM_MAGIC) {
+        reader = Reader::new(&[]);
+    }
+
+    Sections { reader }
+}
+
+/// Returns an iterator over the custom sections of the given `bytecode`, as
+/// pairs of name and contents.
+pub(crate) fn custom_sections(bytecode: &[u8]) -> CustomSections<'_> {
+    CustomSections {
+        sections: sections(bytecode),
+    }
+}
+
+/// Returns the contents of the first custom section with the given `name`.
+pub(crate) fn custom_section<'a>(
+    bytecode: &'a [u8],
+    name: &str,
+) -> Option<&'a [u8]> {
+    custom_sections(bytecode)
+        .find(|(section_name, _)| *section_name == name)
+        .map(|(_, contents)| contents)
+}
+
+/// Returns the index of the memory exported under the given `name`.
+pub(crate) fn exported_memory(bytecode: &[u8], name: &str) -> Option<u32> {
+    let (_, payload) =
+        sections(bytecode).find(|(id, _)| *id == EXPORT_SECTION_ID)?;
+    let mut payload = Reader::new(payload);
+
+    let n_exports = payload.u32()?;
+    for _ in 0..n_exports {
+        let export_name = payload.name()?;
+        let kind = payload.byte()?;
+        let index = payload.u32()?;
+
+        if kind == MEMORY_EXPORT_KIND && export_name == name {
+            return Some(index);
+        }
+    }
+
+    None
+}
+
+/// Returns the names of the functions exported by the given `bytecode`, in
+/// the order they are exported.
+pub(crate) fn exported_functions(bytecode: &[u8]) -> Vec<&str> {
+    let mut functions = Vec::new();
+
+    let Some((_, payload)) =
+        sections(bytecode).find(|(id, _)| *id == EXPORT_SECTION_ID)
+    else {
+        return functions;
+    };
+    let mut payload = Reader::new(payload);
+
+    let n_exports = payload.u32().unwrap_or(0);
+    for _ in 0..n_exports {
+        let (Some(name), Some(kind), Some(_)) =
+            (payload.name(), payload.byte(), payload.u32())
+        else {
+            break;
+        };
+
+        if kind == FUNCTION_EXPORT_KIND {
+            functions.push(name);
+        }
+    }
+
+    functions
+}
+
+/// Returns whether any of the memories defined in the given `bytecode` is
+/// shared.
+pub(crate) fn has_shared_memory(bytecode: &[u8]) -> bool {
+    let Some((_, payload)) =
+        sections(bytecode).find(|(id, _)| *id == MEMORY_SECTION_ID)
+    else {
+        return false;
+    };
+    let mut payload = Reader::new(payload);
+
+    let n_memories = payload.u32().unwrap_or(0);
+    for _ in 0..n_memories {
+        let Some(flags) = payload.byte() else {
+            break;
+        };
+        if flags & LIMITS_SHARED != 0 {
+            return true;
+        }
+
+        // The limits of 64-bit memories don't fit in a `u32`
+        if payload.skip_leb().is_none() {
+            break;
+        }
+        if flags & LIMITS_HAS_MAX != 0 && payload.skip_leb().is_none() {
+            break;
+        }
+    }
+
+    false
+}
+
+pub(crate) struct Sections<'a> {
+    reader: Reader<'a>,
+}
+
+impl<'a> Iterator for Sections<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reader.is_empty() {
+            return None;
+        }
+
+        let id = self.reader.byte()?;
+        let len = self.reader.u32()? as usize;
+        let payload = self.reader.bytes(len)?;
+
+        Some((id, payload))
+    }
+}
+
+pub(crate) struct CustomSections<'a> {
+    sections: Sections<'a>,
+}
+
+impl<'a> Iterator for CustomSections<'a> {
+    type Item = (&'a str, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (id, payload) in self.sections.by_ref() {
+            if id == CUSTOM_SECTION_ID {
+                let mut payload = Reader::new(payload);
+                let name = payload.name()?;
+                return Some((name, payload.rest()));
+            }
+        }
+
+        None
+    }
+}
+
+/// A reader of the primitives used in encoding WebAssembly bytecode.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The number of bytes left to read.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn peek(&self) -> Option<u8> {
+        self.bytes.first().copied()
+    }
+
+    pub fn byte(&mut self) -> Option<u8> {
+        let (byte, rest) = self.bytes.split_first()?;
+        self.bytes = rest;
+        Some(*byte)
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.bytes.len() {
+            return None;
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(bytes)
+    }
+
+    /// Reads an unsigned LEB128 encoded 32-bit integer.
+    pub fn u32(&mut self) -> Option<u32> {
+        let mut value = 0u32;
+
+        for i in 0..5 {
+            let byte = self.byte()?;
+            value |= u32::from(byte & 0x7f).checked_shl(7 * i)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+
+        None
+    }
+
+    /// Skips over an LEB128 encoded integer of any width.
+    pub fn skip_leb(&mut self) -> Option<()> {
+        while self.byte()? & 0x80 != 0 {}
+        Some(())
+    }
+
+    /// Reads a length prefixed UTF-8 string.
+    pub fn name(&mut self) -> Option<&'a str> {
+        let len = self.u32()? as usize;
+        core::str::from_utf8(self.bytes(len)?).ok()
+    }
+
+    pub fn rest(&mut self) -> &'a [u8] {
+        core::mem::take(&mut self.bytes)
+    }
+}
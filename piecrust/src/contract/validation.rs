@@ -0,0 +1,543 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+
+use super::nans::{canonicalize_nans, NanOp, NanType};
+use super::sections::{self, Reader};
+
+pub(super) const TYPE_SECTION_ID: u8 = 1;
+const IMPORT_SECTION_ID: u8 = 2;
+const GLOBAL_SECTION_ID: u8 = 6;
+pub(super) const CODE_SECTION_ID: u8 = 10;
+
+pub(super) const FUNC_TYPE_FORM: u8 = 0x60;
+const EMPTY_BLOCK_TYPE: u8 = 0x40;
+
+pub(super) const F32: u8 = 0x7d;
+pub(super) const F64: u8 = 0x7c;
+pub(super) const V128: u8 = 0x7b;
+
+const LIMITS_HAS_MAX: u8 = 0x01;
+const LIMITS_SHARED: u8 = 0x02;
+const MEMARG_HAS_MEMORY: u32 = 0x40;
+
+/// A reason for bytecode to be rejected when deployed, as configured by a
+/// [`ValidationConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum InvalidReason {
+    /// Floating point types or instructions are used. Their results are only
+    /// deterministic up to the bit patterns of NaNs.
+    Floats,
+    /// Fixed-width 128-bit SIMD is used.
+    Simd,
+    /// Bulk memory operations, such as `memory.copy`, are used.
+    BulkMemory,
+    /// Shared memories or atomic operations are used.
+    Threads,
+}
+
+impl Display for InvalidReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InvalidReason::Floats => "floats",
+            InvalidReason::Simd => "simd",
+            InvalidReason::BulkMemory => "bulk-memory",
+            InvalidReason::Threads => "threads",
+        })
+    }
+}
+
+/// Rules bytecode must follow to be deployed, checked in a single pass over
+/// the bytecode before it is compiled.
+///
+/// Bytecode breaking any rule is rejected with [`Error::InvalidBytecode`],
+/// listing every rule broken. By default, floats, SIMD, and bulk memory
+/// operations are allowed, and [threads] are always rejected.
+///
+/// By default, allowed floats are deterministic: the bytecode is rewritten
+/// when deployed so that every NaN produced by a float instruction is
+/// replaced by the canonical NaN, and its sign and payload don't depend on
+/// the platform. This can be turned off with
+/// [`nan_canonicalization`](Self::nan_canonicalization) by hosts that don't
+/// need nodes to agree on the results of calls, saving the cost of the
+/// checks.
+///
+/// The configuration is set on the [`VM`] using
+/// [`VM::set_validation_config`], and only applies to contracts deployed
+/// after it is set.
+///
+/// [`Error::InvalidBytecode`]: crate::Error::InvalidBytecode
+/// [threads]: InvalidReason::Threads
+/// [`VM`]: crate::VM
+/// [`VM::set_validation_config`]: crate::VM::set_validation_config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ValidationConfig {
+    floats: bool,
+    simd: bool,
+    bulk_memory: bool,
+    nan_canonicalization: bool,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            floats: true,
+            simd: true,
+            bulk_memory: true,
+            nan_canonicalization: true,
+        }
+    }
+}
+
+impl ValidationConfig {
+    /// A configuration rejecting everything that can be rejected.
+    pub fn strict() -> Self {
+        Self {
+            floats: false,
+            simd: false,
+            bulk_memory: false,
+            nan_canonicalization: true,
+        }
+    }
+
+    /// Sets whether floating point types and instructions are allowed.
+    pub fn floats(mut self, allowed: bool) -> Self {
+        self.floats = allowed;
+        self
+    }
+
+    /// Sets whether SIMD is allowed.
+    pub fn simd(mut self, allowed: bool) -> Self {
+        self.simd = allowed;
+        self
+    }
+
+    /// Sets whether bulk memory operations are allowed.
+    pub fn bulk_memory(mut self, allowed: bool) -> Self {
+        self.bulk_memory = allowed;
+        self
+    }
+
+    /// Sets whether the NaNs produced by float instructions are
+    /// canonicalized.
+    pub fn nan_canonicalization(mut self, enabled: bool) -> Self {
+        self.nan_canonicalization = enabled;
+        self
+    }
+
+    /// Returns whether the NaNs produced by float instructions are
+    /// canonicalized.
+    pub fn canonicalizes_nans(&self) -> bool {
+        self.nan_canonicalization
+    }
+
+    /// Returns whether bytecode using whatever the given `reason` names is
+    /// allowed.
+    pub fn is_allowed(&self, reason: InvalidReason) -> bool {
+        match reason {
+            InvalidReason::Floats => self.floats,
+            InvalidReason::Simd => self.simd,
+            InvalidReason::BulkMemory => self.bulk_memory,
+            InvalidReason::Threads => false,
+        }
+    }
+
+    /// Checks the given `bytecode`, returning every reason it is rejected
+    /// for, if any.
+    ///
+    /// Malformed bytecode is not rejected here, but by the engine when the
+    /// bytecode is compiled.
+    pub fn validate(&self, bytecode: &[u8]) -> Result<(), Vec<InvalidReason>> {
+        let reasons: Vec<_> = used(bytecode)
+            .into_iter()
+            .filter(|reason| !self.is_allowed(*reason))
+            .collect();
+
+        if reasons.is_empty() {
+            return Ok(());
+        }
+        Err(reasons)
+    }
+
+    /// Returns the given `bytecode` with the NaNs produced by its float
+    /// instructions canonicalized, if that is enabled.
+    ///
+    /// Bytecode using floats in a way that isn't understood can't be
+    /// canonicalized, and is rejected for using floats.
+    pub(crate) fn canonicalize<'a>(
+        &self,
+        bytecode: &'a [u8],
+    ) -> Result<Cow<'a, [u8]>, Vec<InvalidReason>> {
+        if !self.nan_canonicalization {
+            return Ok(Cow::Borrowed(bytecode));
+        }
+
+        match canonicalize_nans(bytecode) {
+            Some(bytecode) => Ok(bytecode),
+            None if used(bytecode).contains(&InvalidReason::Floats) => {
+                Err(vec![InvalidReason::Floats])
+            }
+            None => Ok(Cow::Borrowed(bytecode)),
+        }
+    }
+}
+
+impl Display for ValidationConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let rejected = [
+            InvalidReason::Floats,
+            InvalidReason::Simd,
+            InvalidReason::BulkMemory,
+            InvalidReason::Threads,
+        ]
+        .into_iter()
+        .filter(|reason| !self.is_allowed(*reason));
+
+        for (i, reason) in rejected.enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{reason}")?;
+        }
+        if !self.nan_canonicalization {
+            f.write_str("; nans not canonicalized")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns everything used by the given `bytecode` that may be rejected.
+///
+/// Scanning a section stops at the first thing that isn't understood, such as
+/// an instruction from a proposal the engine doesn't support, leaving it to
+/// the engine to reject.
+fn used(bytecode: &[u8]) -> BTreeSet<InvalidReason> {
+    let mut used = BTreeSet::new();
+
+    if sections::has_shared_memory(bytecode) {
+        used.insert(InvalidReason::Threads);
+    }
+
+    for (id, payload) in sections::sections(bytecode) {
+        let mut payload = Reader::new(payload);
+        // Whatever was found before scanning stopped is still reported
+        let _ = match id {
+            TYPE_SECTION_ID => scan_types(&mut payload, &mut used),
+            IMPORT_SECTION_ID => scan_imports(&mut payload, &mut used),
+            GLOBAL_SECTION_ID => scan_globals(&mut payload, &mut used),
+            CODE_SECTION_ID => scan_code(&mut payload, &mut used),
+            _ => Some(()),
+        };
+    }
+
+    used
+}
+
+fn scan_types(
+    payload: &mut Reader,
+    used: &mut BTreeSet<InvalidReason>,
+) -> Option<()> {
+    let n_types = payload.u32()?;
+    for _ in 0..n_types {
+        if payload.byte()? != FUNC_TYPE_FORM {
+            return None;
+        }
+        // Parameters, then results
+        for _ in 0..2 {
+            let n_types = payload.u32()?;
+            for _ in 0..n_types {
+                scan_val_type(payload.byte()?, used);
+            }
+        }
+    }
+    Some(())
+}
+
+fn scan_imports(
+    payload: &mut Reader,
+    used: &mut BTreeSet<InvalidReason>,
+) -> Option<()> {
+    let n_imports = payload.u32()?;
+    for _ in 0..n_imports {
+        payload.name()?;
+        payload.name()?;
+        match payload.byte()? {
+            // Function
+            0x00 => {
+                payload.u32()?;
+            }
+            // Table
+            0x01 => {
+                payload.byte()?;
+                scan_limits(payload, used)?;
+            }
+            // Memory
+            0x02 => scan_limits(payload, used)?,
+            // Global
+            0x03 => {
+                scan_val_type(payload.byte()?, used);
+                payload.byte()?;
+            }
+            _ => return None,
+        }
+    }
+    Some(())
+}
+
+fn scan_limits(
+    payload: &mut Reader,
+    used: &mut BTreeSet<InvalidReason>,
+) -> Option<()> {
+    let flags = payload.byte()?;
+    if flags & LIMITS_SHARED != 0 {
+        used.insert(InvalidReason::Threads);
+    }
+    payload.skip_leb()?;
+    if flags & LIMITS_HAS_MAX != 0 {
+        payload.skip_leb()?;
+    }
+    Some(())
+}
+
+fn scan_globals(
+    payload: &mut Reader,
+    used: &mut BTreeSet<InvalidReason>,
+) -> Option<()> {
+    let n_globals = payload.u32()?;
+    for _ in 0..n_globals {
+        scan_val_type(payload.byte()?, used);
+        payload.byte()?;
+        scan_instructions(payload, used, &mut Vec::new(), true)?;
+    }
+    Some(())
+}
+
+fn scan_code(
+    payload: &mut Reader,
+    used: &mut BTreeSet<InvalidReason>,
+) -> Option<()> {
+    let n_bodies = payload.u32()?;
+    for _ in 0..n_bodies {
+        let len = payload.u32()? as usize;
+        let mut body = Reader::new(payload.bytes(len)?);
+
+        let n_locals = body.u32()?;
+        for _ in 0..n_locals {
+            body.u32()?;
+            scan_val_type(body.byte()?, used);
+        }
+
+        // A body that isn't understood doesn't stop the others from being
+        // scanned, since their lengths are known.
+        let _ = scan_instructions(&mut body, used, &mut Vec::new(), false);
+    }
+    Some(())
+}
+
+fn scan_val_type(val_type: u8, used: &mut BTreeSet<InvalidReason>) {
+    match val_type {
+        F32 | F64 => {
+            used.insert(InvalidReason::Floats);
+        }
+        V128 => {
+            used.insert(InvalidReason::Simd);
+        }
+        _ => {}
+    }
+}
+
+/// Scans instructions until the reader is empty, or, if `const_expr` is set,
+/// until the `end` of a constant expression.
+///
+/// The instructions that may produce a NaN with a platform dependent bit
+/// pattern are pushed to `nans`.
+pub(super) fn scan_instructions(
+    reader: &mut Reader,
+    used: &mut BTreeSet<InvalidReason>,
+    nans: &mut Vec<NanOp>,
+    const_expr: bool,
+) -> Option<()> {
+    while !reader.is_empty() {
+        let opcode = reader.byte()?;
+        let mut nan_type = None;
+        match opcode {
+            // end
+            0x0b if const_expr => return Some(()),
+            // unreachable, nop, else, end, return, drop, select, integer
+            // numeric instructions, and ref.is_null
+            0x00 | 0x01 | 0x05 | 0x0b | 0x0f | 0x1a | 0x1b | 0xd1 => {}
+            // block, loop, if
+            0x02..=0x04 => {
+                let block_type = reader.peek()?;
+                if matches!(block_type, EMPTY_BLOCK_TYPE | 0x6f..=0x7f) {
+                    scan_val_type(reader.byte()?, used);
+                } else {
+                    reader.skip_leb()?;
+                }
+            }
+            // br, br_if, call, return_call, local, global, and table accesses,
+            // memory.size, memory.grow, ref.func
+            0x0c | 0x0d | 0x10 | 0x12 | 0x20..=0x26 | 0x3f | 0x40 | 0xd2 => {
+                reader.u32()?;
+            }
+            // br_table
+            0x0e => {
+                let n_labels = reader.u32()?;
+                for _ in 0..=n_labels {
+                    reader.u32()?;
+                }
+            }
+            // call_indirect, return_call_indirect
+            0x11 | 0x13 => {
+                reader.u32()?;
+                reader.u32()?;
+            }
+            // typed select
+            0x1c => {
+                let n_types = reader.u32()?;
+                for _ in 0..n_types {
+                    scan_val_type(reader.byte()?, used);
+                }
+            }
+            // loads and stores
+            0x28..=0x3e => {
+                if matches!(opcode, 0x2a | 0x2b | 0x38 | 0x39) {
+                    used.insert(InvalidReason::Floats);
+                }
+                skip_memarg(reader)?;
+            }
+            // i32.const, i64.const
+            0x41 | 0x42 => reader.skip_leb()?,
+            // f32.const
+            0x43 => {
+                used.insert(InvalidReason::Floats);
+                reader.bytes(4)?;
+            }
+            // f64.const
+            0x44 => {
+                used.insert(InvalidReason::Floats);
+                reader.bytes(8)?;
+            }
+            // numeric instructions
+            0x45..=0xc4 => {
+                if is_float_numeric(opcode) {
+                    used.insert(InvalidReason::Floats);
+                }
+                nan_type = NanType::of_numeric(opcode);
+            }
+            // ref.null
+            0xd0 => {
+                reader.byte()?;
+            }
+            0xfc => scan_misc(reader, used)?,
+            0xfd => {
+                used.insert(InvalidReason::Simd);
+                nan_type = NanType::of_simd(skip_simd(reader)?);
+            }
+            0xfe => {
+                used.insert(InvalidReason::Threads);
+                // atomic.fence takes a single reserved byte
+                if reader.u32()? == 0x03 {
+                    reader.byte()?;
+                } else {
+                    skip_memarg(reader)?;
+                }
+            }
+            _ => return None,
+        }
+        if let Some(ty) = nan_type {
+            nans.push(NanOp {
+                remaining: reader.len(),
+                ty,
+            });
+        }
+    }
+    Some(())
+}
+
+/// Returns whether the given numeric instruction, without immediates,
+/// operates on or produces floats.
+fn is_float_numeric(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        // comparisons
+        0x5b..=0x66
+        // arithmetic
+        | 0x8b..=0xa6
+        // truncations to integers
+        | 0xa8..=0xab
+        | 0xae..=0xb1
+        // conversions, demotion, promotion, and reinterpretations
+        | 0xb2..=0xbf
+    )
+}
+
+/// Scans an instruction prefixed with `0xfc`.
+fn scan_misc(
+    reader: &mut Reader,
+    used: &mut BTreeSet<InvalidReason>,
+) -> Option<()> {
+    match reader.u32()? {
+        // saturating truncations
+        0..=7 => {
+            used.insert(InvalidReason::Floats);
+        }
+        // memory.init, memory.copy, table.init, table.copy
+        8 | 10 | 12 | 14 => {
+            used.insert(InvalidReason::BulkMemory);
+            reader.u32()?;
+            reader.u32()?;
+        }
+        // data.drop, memory.fill, elem.drop
+        9 | 11 | 13 => {
+            used.insert(InvalidReason::BulkMemory);
+            reader.u32()?;
+        }
+        // table.grow, table.size, table.fill
+        15..=17 => {
+            reader.u32()?;
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Skips over the immediates of an instruction prefixed with `0xfd`,
+/// returning its opcode.
+fn skip_simd(reader: &mut Reader) -> Option<u32> {
+    let opcode = reader.u32()?;
+    match opcode {
+        // loads and stores
+        0..=11 | 92 | 93 => skip_memarg(reader)?,
+        // v128.const, i8x16.shuffle
+        12 | 13 => {
+            reader.bytes(16)?;
+        }
+        // lane extractions and replacements
+        21..=34 => {
+            reader.byte()?;
+        }
+        // lane loads and stores
+        84..=91 => {
+            skip_memarg(reader)?;
+            reader.byte()?;
+        }
+        _ => {}
+    }
+    Some(opcode)
+}
+
+fn skip_memarg(reader: &mut Reader) -> Option<()> {
+    let align = reader.u32()?;
+    if align & MEMARG_HAS_MEMORY != 0 {
+        reader.u32()?;
+    }
+    // Offsets into 64-bit memories don't fit in a `u32`
+    reader.skip_leb()
+}
@@ -0,0 +1,151 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fmt::{self, Debug, Formatter};
+
+use piecrust_uplink::ContractId;
+
+use crate::instance::WrappedInstance;
+
+/// Callbacks invoked while running a call made using
+/// [`Session::call_with_debugger`].
+///
+/// Execution of the contract is paused for as long as a callback runs, giving
+/// the debugger the chance to inspect the argument buffer and memory of the
+/// contract through the [`DebugFrame`] it is passed. A debugger stepping
+/// through a call may simply block in each callback, until it is told to
+/// continue.
+///
+/// [`Session::call_with_debugger`]: crate::Session::call_with_debugger
+pub trait Debugger {
+    /// Called when a contract function is entered, with the argument already
+    /// written to the contract's argument buffer.
+    fn on_enter(&mut self, frame: &DebugFrame) {
+        let _ = frame;
+    }
+
+    /// Called when a contract function is exited, successfully or not. On
+    /// success, the return is left in the contract's argument buffer.
+    fn on_exit(&mut self, frame: &DebugFrame, success: bool) {
+        let _ = (frame, success);
+    }
+
+    /// Called when a contract function calls the host function with the given
+    /// `name`, before the host function runs.
+    fn on_host_call(&mut self, frame: &DebugFrame, name: &str) {
+        let _ = (frame, name);
+    }
+}
+
+/// The contract function being executed when a [`Debugger`] is called back.
+pub struct DebugFrame<'a> {
+    pub(crate) contract: ContractId,
+    pub(crate) fn_name: &'a str,
+    pub(crate) depth: usize,
+    pub(crate) gas_limit: u64,
+    pub(crate) gas_remaining: u64,
+    pub(crate) instance: &'a WrappedInstance,
+}
+
+impl<'a> DebugFrame<'a> {
+    /// The contract being executed.
+    pub fn contract(&self) -> ContractId {
+        self.contract
+    }
+
+    /// The name of the function being executed.
+    pub fn fn_name(&self) -> &str {
+        self.fn_name
+    }
+
+    /// The depth of the call in the calling stack, with the contract called by
+    /// the host being at a depth of one.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The gas the call is allowed to spend.
+    pub fn gas_limit(&self) -> u64 {
+        self.gas_limit
+    }
+
+    /// The gas the call has left to spend.
+    pub fn gas_remaining(&self) -> u64 {
+        self.gas_remaining
+    }
+
+    /// Inspect the argument buffer of the contract.
+    pub fn with_arg_buf<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        self.instance.with_arg_buf(f)
+    }
+
+    /// Inspect the memory of the contract, up to its current length.
+    pub fn with_memory<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        let len = self.instance.mem_len();
+        self.instance.with_memory(|memory| f(&memory[..len]))
+    }
+}
+
+impl<'a> Debug for DebugFrame<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugFrame")
+            .field("contract", &self.contract)
+            .field("fn_name", &self.fn_name)
+            .field("depth", &self.depth)
+            .field("gas_limit", &self.gas_limit)
+            .field("gas_remaining", &self.gas_remaining)
+            .finish()
+    }
+}
+
+/// The event a [`Debugger`] is called back on.
+pub(crate) enum DebugEvent<'a> {
+    Enter,
+    Exit { success: bool },
+    HostCall { name: &'a str },
+}
+
+/// The debugger set on a session for the duration of a call, if any.
+#[derive(Default)]
+pub(crate) struct ActiveDebugger(Option<&'static mut dyn Debugger>);
+
+impl ActiveDebugger {
+    pub fn set(&mut self, debugger: Option<&'static mut dyn Debugger>) {
+        self.0 = debugger;
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn notify(&mut self, frame: &DebugFrame, event: DebugEvent) {
+        if let Some(debugger) = &mut self.0 {
+            match event {
+                DebugEvent::Enter => debugger.on_enter(frame),
+                DebugEvent::Exit { success } => {
+                    debugger.on_exit(frame, success)
+                }
+                DebugEvent::HostCall { name } => {
+                    debugger.on_host_call(frame, name)
+                }
+            }
+        }
+    }
+}
+
+impl Debug for ActiveDebugger {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ActiveDebugger")
+            .field(&self.is_set())
+            .finish()
+    }
+}
@@ -0,0 +1,153 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
+
+use dusk_wasmtime::Engine;
+use piecrust_uplink::{ARGBUF_LEN, UPLINK_VERSION};
+
+use crate::contract::ValidationConfig;
+use crate::gas::{GasSchedule, InstructionCosts};
+use crate::store::STORE_VERSION;
+use crate::vm::HostQueries;
+
+const PIECRUST_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A canonical description of everything affecting the determinism of
+/// execution.
+///
+/// Two deployments are only guaranteed to produce the same results, given the
+/// same calls on the same state, if their environments are equal. The
+/// environment is displayed one field per line, and can be summarized in a
+/// single [`digest`], allowing it to be compared across deployments at a
+/// glance.
+///
+/// It is obtained using either [`VM::environment`] or
+/// [`Session::environment`].
+///
+/// [`digest`]: Environment::digest
+/// [`VM::environment`]: crate::VM::environment
+/// [`Session::environment`]: crate::Session::environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Environment {
+    /// The version of piecrust.
+    pub piecrust_version: &'static str,
+    /// Hash of the version and configuration of the engine compiling and
+    /// running contracts.
+    pub engine_hash: [u8; 32],
+    /// Hash of the [`GasSchedule`] in use.
+    pub gas_schedule_hash: [u8; 32],
+    /// Hash of the [`InstructionCosts`] the engine meters contracts with.
+    pub instruction_costs_hash: [u8; 32],
+    /// The version of `piecrust-uplink` defining the ABI with contracts.
+    pub abi_version: &'static str,
+    /// The length of the argument buffer shared with contracts.
+    pub argbuf_len: usize,
+    /// The version of the on-disk format of the store.
+    pub store_version: u32,
+    /// Hash of the names of the host queries registered.
+    pub host_queries_hash: [u8; 32],
+    /// The rules bytecode must follow to be deployed.
+    pub validation: ValidationConfig,
+    /// Whether pages containing only zeroes are left out of commits, which
+    /// changes their roots.
+    pub elide_zero_pages: bool,
+}
+
+impl Environment {
+    pub(crate) fn new(
+        engine: &Engine,
+        gas_schedule: &GasSchedule,
+        instruction_costs: &InstructionCosts,
+        host_queries: &HostQueries,
+        validation: ValidationConfig,
+        elide_zero_pages: bool,
+    ) -> Self {
+        let mut hasher = StableHasher::new();
+        engine.precompile_compatibility_hash().hash(&mut hasher);
+        let engine_hash = hasher.digest();
+
+        let mut hasher = blake3::Hasher::new();
+        for name in host_queries.names() {
+            hasher.update(&(name.len() as u64).to_le_bytes());
+            hasher.update(name.as_bytes());
+        }
+        let host_queries_hash = hasher.finalize().into();
+
+        Self {
+            piecrust_version: PIECRUST_VERSION,
+            engine_hash,
+            gas_schedule_hash: gas_schedule.hash(),
+            instruction_costs_hash: instruction_costs.hash(),
+            abi_version: UPLINK_VERSION,
+            argbuf_len: ARGBUF_LEN,
+            store_version: STORE_VERSION,
+            host_queries_hash,
+            validation,
+            elide_zero_pages,
+        }
+    }
+
+    /// Returns a hash summarizing all fields of the environment.
+    pub fn digest(&self) -> [u8; 32] {
+        blake3::hash(self.to_string().as_bytes()).into()
+    }
+}
+
+impl Display for Environment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "piecrust_version: {}", self.piecrust_version)?;
+        writeln!(f, "engine_hash: {}", hex::encode(self.engine_hash))?;
+        writeln!(
+            f,
+            "gas_schedule_hash: {}",
+            hex::encode(self.gas_schedule_hash)
+        )?;
+        writeln!(
+            f,
+            "instruction_costs_hash: {}",
+            hex::encode(self.instruction_costs_hash)
+        )?;
+        writeln!(f, "abi_version: {}", self.abi_version)?;
+        writeln!(f, "argbuf_len: {}", self.argbuf_len)?;
+        writeln!(f, "store_version: {}", self.store_version)?;
+        writeln!(
+            f,
+            "host_queries_hash: {}",
+            hex::encode(self.host_queries_hash)
+        )?;
+        writeln!(f, "validation: {}", self.validation)?;
+        write!(f, "elide_zero_pages: {}", self.elide_zero_pages)
+    }
+}
+
+/// Feeds values implementing [`Hash`] into `blake3`, as opposed to the
+/// randomly seeded hasher of the standard library.
+struct StableHasher(blake3::Hasher);
+
+impl StableHasher {
+    fn new() -> Self {
+        Self(blake3::Hasher::new())
+    }
+
+    fn digest(&self) -> [u8; 32] {
+        self.0.finalize().into()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        let digest = self.digest();
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+}
@@ -6,9 +6,15 @@
 
 use std::borrow::Cow;
 use std::sync::{mpsc, Arc};
+use std::time::Duration;
 use thiserror::Error;
 
 use piecrust_uplink::{ContractError, ContractId};
+
+use crate::contract::{InvalidReason, Version, WasmFeature};
+use crate::root::Root;
+use crate::session::CheckpointId;
+use crate::store::{CommitHasDependents, StoreFull};
 use rkyv::ser::serializers::{
     BufferSerializerError, CompositeSerializerError, FixedSizeScratchError,
 };
@@ -24,24 +30,46 @@ pub type Compo = CompositeSerializerError<
 pub enum Error {
     #[error("Argument buffer overflow: {len} > {max_len}")]
     ArgumentBufferOverflow { len: usize, max_len: usize },
+    #[error("Call denied: {0}")]
+    CallDenied(String),
+    #[error("Call depth exceeded, the limit is {0}")]
+    CallDepthExceeded(usize),
+    #[error("Failed spawning the call thread: {0}")]
+    CallThreadFailure(Arc<std::io::Error>),
     #[error("Commit error: {0}")]
     CommitError(Cow<'static, str>),
+    #[error("Commit has dependent commits: {0:?}")]
+    CommitHasDependents(Vec<Root>),
     #[error(transparent)]
     CompositeSerializerError(Arc<Compo>),
     #[error(transparent)]
     ContractCacheError(Arc<std::io::Error>),
     #[error("Contract does not exist: {0}")]
     ContractDoesNotExist(ContractId),
+    #[error("Contracts locked by another session: {0:?}")]
+    ContractsLocked(Vec<ContractId>),
+    #[error("Denied WASM feature: {0}")]
+    DeniedWasmFeature(WasmFeature),
     #[error(transparent)]
     FeedPulled(mpsc::SendError<Vec<u8>>),
     #[error(transparent)]
     Infallible(std::convert::Infallible),
     #[error("InitalizationError: {0}")]
     InitalizationError(Cow<'static, str>),
+    #[error(
+        "Instruction costs mismatch: commits were made with {}, but the VM uses {}",
+        hex::encode(.found),
+        hex::encode(.expected)
+    )]
+    InstructionCostsMismatch { expected: [u8; 32], found: [u8; 32] },
     #[error("Invalid global")]
     InvalidArgumentBuffer,
+    #[error("Invalid bytecode, using: {0:?}")]
+    InvalidBytecode(Vec<InvalidReason>),
     #[error("Invalid function: {0}")]
     InvalidFunction(String),
+    #[error("Invalid log level: {0}")]
+    InvalidLogLevel(u32),
     #[error("Invalid memory")]
     InvalidMemory,
     #[error("Memory access out of bounds: offset {offset}, length {len}, memory length {mem_len}")]
@@ -50,11 +78,23 @@ pub enum Error {
         len: usize,
         mem_len: usize,
     },
+    #[error("Memory limit exceeded, the limit is {0} pages")]
+    MemoryLimitExceeded(usize),
     #[error("Snapshot failure: {reason:?} {io}")]
     MemorySnapshotFailure {
         reason: Option<Arc<Self>>,
         io: Arc<std::io::Error>,
     },
+    #[error("Merge conflict on contracts: {0:?}")]
+    MergeConflict(Vec<ContractId>),
+    #[error(
+        "Metadata schema mismatch: {name} was set with schema {found:?}, but the contract expects {expected}"
+    )]
+    MetaSchemaMismatch {
+        name: String,
+        expected: u64,
+        found: Option<u64>,
+    },
     #[error("Missing feed")]
     MissingFeed,
     #[error("Missing host data: {0}")]
@@ -67,14 +107,54 @@ pub enum Error {
     Panic(String),
     #[error(transparent)]
     PersistenceError(Arc<std::io::Error>),
+    #[error(
+        "Replay base mismatch: the journal was recorded on {expected:?}, but the session is based on {found:?}"
+    )]
+    ReplayBaseMismatch {
+        expected: Option<Root>,
+        found: Option<Root>,
+    },
+    #[error(
+        "Replay divergence: expected root {expected}, but reached {found}"
+    )]
+    ReplayDivergence { expected: Root, found: Root },
     #[error(transparent)]
     RestoreError(Arc<std::io::Error>),
+    #[error("Contract {contract} returned {len} bytes, more than the {max_len} that fit the argument buffer")]
+    ReturnOverflow {
+        contract: ContractId,
+        len: usize,
+        max_len: usize,
+    },
     #[error(transparent)]
     RuntimeError(dusk_wasmtime::Error),
     #[error("Session error: {0}")]
     SessionError(Cow<'static, str>),
+    #[error("Shutting down timed out after {0:?}")]
+    ShutdownTimeout(Duration),
+    #[error(transparent)]
+    SpillError(Arc<std::io::Error>),
+    #[error("Spilled output overflow: {len} > {max_len}")]
+    SpillOverflow { len: usize, max_len: usize },
+    #[error(transparent)]
+    StoreFull(Arc<std::io::Error>),
+    #[error("Call timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("Too many contracts instantiated in a call, the limit is {0}")]
+    TooManyInstances(usize),
     #[error("Too many memories: {0}")]
     TooManyMemories(usize),
+    #[error("Only the owner of contract {0} may call {1}")]
+    UnauthorizedCall(ContractId, String),
+    #[error("Upgrade of contract {0} is not authorized by its owner")]
+    UpgradeUnauthorized(ContractId),
+    #[error("Unknown checkpoint: {0:?}")]
+    UnknownCheckpoint(CheckpointId),
+    #[error("Unsupported uplink version {found:?}, requires {required}")]
+    UnsupportedUplinkVersion {
+        required: Version,
+        found: Option<String>,
+    },
     #[error(transparent)]
     Utf8(std::str::Utf8Error),
     #[error("ValidationError")]
@@ -91,6 +171,23 @@ impl Error {
             err => err,
         }
     }
+
+    /// Converts an error returned by the store, distinguishing the store
+    /// running out of disk space, and commits with dependents refusing to be
+    /// deleted, from other failures.
+    pub(crate) fn from_store(err: std::io::Error) -> Self {
+        if let Some(dependents) = CommitHasDependents::get(&err) {
+            let dependents = dependents
+                .iter()
+                .map(|hash| Root::from_bytes((*hash).into()))
+                .collect();
+            return Error::CommitHasDependents(dependents);
+        }
+        if StoreFull::is(&err) {
+            return Error::StoreFull(Arc::new(err));
+        }
+        Error::PersistenceError(Arc::new(err))
+    }
 }
 
 impl From<std::convert::Infallible> for Error {
@@ -129,6 +226,19 @@ impl From<Error> for ContractError {
             Error::OutOfGas => Self::OutOfGas,
             Error::Panic(msg) => Self::Panic(msg),
             Error::ContractDoesNotExist(_) => Self::DoesNotExist,
+            Error::CallDepthExceeded(_) => Self::CallDepthExceeded,
+            Error::MemoryAccessOutOfBounds { .. } => {
+                Self::MemoryAccessOutOfBounds
+            }
+            Error::ReturnOverflow {
+                contract,
+                len,
+                max_len,
+            } => Self::ReturnOverflow {
+                contract,
+                len: len as u32,
+                max_len: max_len as u32,
+            },
             _ => Self::Unknown,
         }
     }
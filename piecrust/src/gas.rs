@@ -0,0 +1,180 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use crate::config::BYTE_STORE_COST;
+
+mod report;
+
+pub(crate) use report::replay;
+pub use report::{CallGas, GasDelta, GasReport, ReplayCall};
+
+/// The gas costs charged by a [`Session`] on top of those of executing WASM.
+///
+/// Each inter-contract call requires the session to snapshot the callee's
+/// memory, so that it can be reverted should the call fail. The work needed to
+/// revert grows with both how deep a call goes and with how many distinct
+/// contracts it touches, and the surcharges in this schedule allow for pricing
+/// that work into the call.
+///
+/// The default schedule charges nothing.
+///
+/// [`Session`]: crate::Session
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GasSchedule {
+    /// Gas charged to the caller on each inter-contract call, multiplied by
+    /// the depth of the callee. A contract called directly by the host is at
+    /// depth zero.
+    pub call_depth_surcharge: u64,
+    /// Gas charged to the caller on each inter-contract call, multiplied by
+    /// the number of distinct contracts instantiated during the call,
+    /// including the callee.
+    pub call_breadth_surcharge: u64,
+    /// Gas charged to a contract for each item it feeds to the host during a
+    /// feeder call, or to its caller during a feed call. The caller is charged
+    /// the same for each item it reads.
+    pub feed_surcharge: u64,
+    /// Gas charged to a contract for each transfer it makes out of its
+    /// balance.
+    pub transfer_cost: u64,
+    /// Gas charged to a contract each time it calls a host function, before
+    /// the function runs.
+    pub host_call_cost: u64,
+    /// Gas charged to a contract for each byte it reads from its spilled
+    /// input, or writes to its spilled output.
+    pub spill_byte_cost: u64,
+    /// Gas charged for each byte of bytecode deployed, out of the deploy
+    /// limit, before the bytecode is compiled.
+    pub deploy_byte_cost: u64,
+    /// Gas refunded for each page of memory a call frees, by leaving only
+    /// zeroes in a page that held data before it.
+    pub page_free_refund: u64,
+    /// Gas refunded for each contract destroying itself during a call.
+    pub self_destruct_refund: u64,
+    /// The largest refund a call is given, as a percentage of the gas it
+    /// spent. No refunds are given when zero.
+    pub max_refund_percent: u64,
+}
+
+impl GasSchedule {
+    /// Returns the surcharge for an inter-contract call reaching the given
+    /// `depth`, with the given number of contracts `instantiated`.
+    pub fn call_surcharge(&self, depth: usize, instantiated: usize) -> u64 {
+        let depth = self.call_depth_surcharge.saturating_mul(depth as u64);
+        let breadth = self
+            .call_breadth_surcharge
+            .saturating_mul(instantiated as u64);
+
+        depth.saturating_add(breadth)
+    }
+
+    /// Returns the gas charged for deploying a contract with bytecode of the
+    /// given length.
+    pub fn deploy_cost(&self, bytecode_len: usize) -> u64 {
+        self.deploy_byte_cost.saturating_mul(bytecode_len as u64)
+    }
+
+    /// Returns the gas charged for spilling, or reading spilled, data of the
+    /// given length.
+    pub fn spill_cost(&self, len: usize) -> u64 {
+        self.spill_byte_cost.saturating_mul(len as u64)
+    }
+
+    /// Returns the refund for a call freeing the given number of `pages` and
+    /// destroying the given number of contracts, before it is capped.
+    pub fn refund(&self, pages: usize, destroyed: usize) -> u64 {
+        let pages = self.page_free_refund.saturating_mul(pages as u64);
+        let destroyed =
+            self.self_destruct_refund.saturating_mul(destroyed as u64);
+
+        pages.saturating_add(destroyed)
+    }
+
+    /// Returns the largest refund given to a call that spent `gas_spent`.
+    pub fn refund_cap(&self, gas_spent: u64) -> u64 {
+        let percent = self.max_refund_percent.min(100) as u128;
+        (gas_spent as u128 * percent / 100) as u64
+    }
+
+    /// Returns a hash of the schedule, together with the costs the engine
+    /// charges for storing bytes in memory.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&BYTE_STORE_COST.to_le_bytes());
+        hasher.update(&self.call_depth_surcharge.to_le_bytes());
+        hasher.update(&self.call_breadth_surcharge.to_le_bytes());
+        // Schedules not charging for feeding or transfers hash as they did
+        // before those costs were introduced.
+        if self.feed_surcharge != 0 {
+            hasher.update(&self.feed_surcharge.to_le_bytes());
+        }
+        if self.transfer_cost != 0 {
+            hasher.update(&self.transfer_cost.to_le_bytes());
+        }
+        if self.host_call_cost != 0 {
+            hasher.update(&self.host_call_cost.to_le_bytes());
+        }
+        if self.deploy_byte_cost != 0 {
+            hasher.update(&self.deploy_byte_cost.to_le_bytes());
+        }
+        if self.spill_byte_cost != 0 {
+            hasher.update(&self.spill_byte_cost.to_le_bytes());
+        }
+        if self.max_refund_percent != 0 {
+            hasher.update(&self.page_free_refund.to_le_bytes());
+            hasher.update(&self.self_destruct_refund.to_le_bytes());
+            hasher.update(&self.max_refund_percent.to_le_bytes());
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// The gas costs of executing WASM instructions, by class of instruction.
+///
+/// Instructions are metered by the engine as they are compiled, so the costs
+/// are fixed for the lifetime of a [`VM`], and are set using
+/// [`VmBuilder::instruction_costs`]. Any instruction not in one of the classes
+/// below costs one gas, except for those doing no work - such as `nop` or
+/// `end` - which are free.
+///
+/// The default costs are the ones the engine has always charged.
+///
+/// [`VM`]: crate::VM
+/// [`VmBuilder::instruction_costs`]: crate::VmBuilder::instruction_costs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InstructionCosts {
+    /// Gas charged for each byte a store instruction writes, counting at least
+    /// the width of the value being stored.
+    pub store_byte: u64,
+    /// Gas charged for each load instruction.
+    pub load: u64,
+    /// Gas charged for each direct or indirect function call.
+    pub call: u64,
+    /// Gas charged for each `memory.grow` instruction.
+    pub memory_grow: u64,
+}
+
+impl Default for InstructionCosts {
+    fn default() -> Self {
+        Self {
+            store_byte: BYTE_STORE_COST as u64,
+            load: 1,
+            call: 1,
+            memory_grow: 1,
+        }
+    }
+}
+
+impl InstructionCosts {
+    /// Returns a hash of the costs.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.store_byte.to_le_bytes());
+        hasher.update(&self.load.to_le_bytes());
+        hasher.update(&self.call.to_le_bytes());
+        hasher.update(&self.memory_grow.to_le_bytes());
+        hasher.finalize().into()
+    }
+}
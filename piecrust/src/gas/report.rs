@@ -0,0 +1,212 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::collections::BTreeMap;
+
+use piecrust_uplink::ContractId;
+
+use crate::session::Session;
+
+/// A call to be replayed when producing a [`GasReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayCall {
+    /// The contract called.
+    pub contract: ContractId,
+    /// The name of the function called.
+    pub fn_name: String,
+    /// The serialized argument of the call.
+    pub fn_arg: Vec<u8>,
+    /// The gas limit of the call.
+    pub gas_limit: u64,
+}
+
+/// The gas spent on something under each of the two schedules compared in a
+/// [`GasReport`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GasDelta {
+    /// The gas spent under the baseline schedule.
+    pub baseline: u64,
+    /// The gas spent under the proposed schedule.
+    pub proposed: u64,
+}
+
+impl GasDelta {
+    /// Returns how much more gas is spent under the proposed schedule than
+    /// under the baseline. Negative if the proposed schedule is cheaper.
+    pub fn delta(&self) -> i128 {
+        i128::from(self.proposed) - i128::from(self.baseline)
+    }
+
+    fn add(&mut self, other: GasDelta) {
+        self.baseline += other.baseline;
+        self.proposed += other.proposed;
+    }
+}
+
+/// The gas spent by a single replayed call under each schedule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallGas {
+    /// The contract called.
+    pub contract: ContractId,
+    /// The name of the function called.
+    pub fn_name: String,
+    /// The gas spent by the call, including the calls it deferred.
+    pub gas: GasDelta,
+    /// Whether the call succeeded under the baseline schedule.
+    pub baseline_ok: bool,
+    /// Whether the call succeeded under the proposed schedule.
+    pub proposed_ok: bool,
+}
+
+/// A comparison of the gas spent by the same calls under two different
+/// [`GasSchedule`]s, produced by [`VM::gas_report`].
+///
+/// The gas of each contract and function is the gas spent in their own
+/// execution, excluding the inter-contract calls they make. A call that fails
+/// is accounted entirely to the function called by the host, since the calls
+/// it made are discarded with it.
+///
+/// [`GasSchedule`]: crate::GasSchedule
+/// [`VM::gas_report`]: crate::VM::gas_report
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GasReport {
+    /// The gas spent by each call replayed, in the order they were replayed.
+    pub calls: Vec<CallGas>,
+    /// The gas spent in each contract.
+    pub contracts: BTreeMap<ContractId, GasDelta>,
+    /// The gas spent in each function of each contract.
+    pub functions: BTreeMap<(ContractId, String), GasDelta>,
+}
+
+impl GasReport {
+    /// Builds a report from the replays of the same calls under the baseline
+    /// and the proposed schedules.
+    pub(crate) fn new(
+        baseline: Vec<Replayed>,
+        proposed: Vec<Replayed>,
+    ) -> Self {
+        let mut report = Self::default();
+
+        for (baseline, proposed) in baseline.into_iter().zip(proposed) {
+            report.calls.push(CallGas {
+                contract: baseline.contract,
+                fn_name: baseline.fn_name,
+                gas: GasDelta {
+                    baseline: baseline.gas_spent,
+                    proposed: proposed.gas_spent,
+                },
+                baseline_ok: baseline.ok,
+                proposed_ok: proposed.ok,
+            });
+
+            for ((contract, fn_name), spent) in baseline.spent {
+                let delta = GasDelta {
+                    baseline: spent,
+                    proposed: 0,
+                };
+                report.account(contract, fn_name, delta);
+            }
+            for ((contract, fn_name), spent) in proposed.spent {
+                let delta = GasDelta {
+                    baseline: 0,
+                    proposed: spent,
+                };
+                report.account(contract, fn_name, delta);
+            }
+        }
+
+        report
+    }
+
+    /// Returns the gas spent by all the calls replayed.
+    pub fn total(&self) -> GasDelta {
+        let mut total = GasDelta::default();
+        for call in &self.calls {
+            total.add(call.gas);
+        }
+        total
+    }
+
+    fn account(
+        &mut self,
+        contract: ContractId,
+        fn_name: String,
+        gas: GasDelta,
+    ) {
+        self.contracts.entry(contract).or_default().add(gas);
+        self.functions
+            .entry((contract, fn_name))
+            .or_default()
+            .add(gas);
+    }
+}
+
+/// The outcome of replaying a single call.
+pub(crate) struct Replayed {
+    contract: ContractId,
+    fn_name: String,
+    gas_spent: u64,
+    ok: bool,
+    spent: Vec<((ContractId, String), u64)>,
+}
+
+/// Replays the given `calls` in order in the given `session`, recording the
+/// gas spent by each.
+pub(crate) fn replay(
+    session: &mut Session,
+    calls: &[ReplayCall],
+) -> Vec<Replayed> {
+    calls
+        .iter()
+        .map(|call| {
+            let contract = call.contract;
+            let fn_name = call.fn_name.clone();
+
+            match session.call_raw(
+                contract,
+                &fn_name,
+                call.fn_arg.clone(),
+                call.gas_limit,
+            ) {
+                Ok(receipt) => {
+                    let mut spent: Vec<_> = receipt
+                        .call_tree
+                        .iter_named()
+                        .map(|(elem, fn_name)| {
+                            ((elem.contract_id, fn_name.to_owned()), elem.spent)
+                        })
+                        .collect();
+                    spent.extend(receipt.deferred.into_iter().map(
+                        |deferred| {
+                            (
+                                (deferred.contract, deferred.fn_name),
+                                deferred.gas_spent,
+                            )
+                        },
+                    ));
+
+                    Replayed {
+                        contract,
+                        fn_name,
+                        gas_spent: receipt.gas_spent,
+                        ok: true,
+                        spent,
+                    }
+                }
+                Err(_) => {
+                    let gas_spent = session.take_failed_spent();
+                    Replayed {
+                        contract,
+                        spent: vec![((contract, fn_name.clone()), gas_spent)],
+                        fn_name,
+                        gas_spent,
+                        ok: false,
+                    }
+                }
+            }
+        })
+        .collect()
+}
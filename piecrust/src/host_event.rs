@@ -0,0 +1,46 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use bytecheck::CheckBytes;
+use piecrust_uplink::ContractId;
+use rkyv::{Archive, Deserialize, Serialize};
+
+/// An event emitted by the VM itself, as opposed to one emitted by a contract,
+/// recording a change to the lifecycle of a contract.
+///
+/// Host events are logged by the [`Session`] in the order the changes happen,
+/// and are available using [`Session::host_events`]. This allows indexers to
+/// follow the contracts in the state without inferring their lifecycle from
+/// state diffs.
+///
+/// [`Session`]: crate::Session
+/// [`Session::host_events`]: crate::Session::host_events
+#[derive(Debug, Clone, PartialEq, Eq, Archive, Deserialize, Serialize)]
+#[archive_attr(derive(CheckBytes))]
+#[non_exhaustive]
+pub enum HostEvent {
+    /// A contract was deployed.
+    Deploy {
+        contract_id: ContractId,
+        owner: Vec<u8>,
+        /// The `blake3` hash of the contract's bytecode.
+        bytecode_hash: [u8; 32],
+    },
+    /// A contract was migrated to new bytecode.
+    Migrate {
+        contract_id: ContractId,
+        /// The `blake3` hash of the contract's new bytecode.
+        bytecode_hash: [u8; 32],
+    },
+    /// The owner of a contract was changed.
+    OwnerChange {
+        contract_id: ContractId,
+        old_owner: Vec<u8>,
+        new_owner: Vec<u8>,
+    },
+    /// A contract was removed, either by the host or by destroying itself.
+    Remove { contract_id: ContractId },
+}
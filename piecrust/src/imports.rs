@@ -14,12 +14,13 @@ use crate::contract::ContractMetadata;
 use dusk_wasmtime::{
     Caller, Extern, Func, Module, Result as WasmtimeResult, Store,
 };
-use piecrust_uplink::{
-    ContractError, ContractId, ARGBUF_LEN, CONTRACT_ID_BYTES,
-};
+use piecrust_uplink::{ContractError, ContractId, LogLevel, CONTRACT_ID_BYTES};
 
 use crate::config::BYTE_STORE_COST;
+#[cfg(feature = "debug")]
+use crate::debugger::DebugEvent;
 use crate::instance::{Env, WrappedInstance};
+use crate::interceptor::{CallOutcome, InterceptedCall};
 use crate::Error;
 
 pub const GAS_PASS_PCT: u64 = 93;
@@ -44,6 +45,15 @@ impl Imports {
                     return Err(Error::InvalidFunction(import_name.to_string()))
                 }
                 Some(func) => {
+                    let cost = store.data().gas_schedule().host_call_cost;
+                    let func = match cost {
+                        0 => func,
+                        _ => Self::charged(store, func, cost),
+                    };
+                    // Instances outlive the calls they're made for, so the
+                    // debugger is checked for on each host call instead.
+                    #[cfg(feature = "debug")]
+                    let func = Self::debugged(store, import_name, func);
                     imports.push(func.into());
                 }
             }
@@ -52,14 +62,57 @@ impl Imports {
         Ok(imports)
     }
 
+    /// Wraps the given import so that the contract calling it is charged the
+    /// given `cost` before the host function runs.
+    fn charged(store: &mut Store<Env>, func: Func, cost: u64) -> Func {
+        let ty = func.ty(&*store);
+
+        Func::new(store, ty, move |mut caller, params, results| {
+            let instance = caller.data_mut().self_instance();
+
+            let gas_remaining = instance.get_remaining_gas();
+            if cost > gas_remaining {
+                instance.set_remaining_gas(0);
+                Err(Error::OutOfGas)?;
+            }
+            instance.set_remaining_gas(gas_remaining - cost);
+
+            func.call(&mut caller, params, results)
+        })
+    }
+
+    /// Wraps the given import so that the debugger set for the call being
+    /// made, if any, is called back before the host function runs.
+    #[cfg(feature = "debug")]
+    fn debugged(store: &mut Store<Env>, name: &str, func: Func) -> Func {
+        let name = name.to_string();
+        let ty = func.ty(&*store);
+
+        Func::new(store, ty, move |mut caller, params, results| {
+            caller
+                .data_mut()
+                .debug_event(DebugEvent::HostCall { name: &name });
+            func.call(&mut caller, params, results)
+        })
+    }
+
     fn import(store: &mut Store<Env>, name: &str, is_64: bool) -> Option<Func> {
         Some(match name {
             "caller" => Func::wrap(store, caller),
             "callstack" => Func::wrap(store, callstack),
+            "callstack_frames" => Func::wrap(store, callstack_frames),
+            "call_depth" => Func::wrap(store, call_depth),
+            "max_call_depth" => Func::wrap(store, max_call_depth),
             "c" => match is_64 {
                 false => Func::wrap(store, wasm32::c),
                 true => Func::wrap(store, wasm64::c),
             },
+            "mc" => Func::wrap(store, mc),
+            "cf" => match is_64 {
+                false => Func::wrap(store, wasm32::cf),
+                true => Func::wrap(store, wasm64::cf),
+            },
+            "feed_next" => Func::wrap(store, feed_next),
             "hq" => match is_64 {
                 false => Func::wrap(store, wasm32::hq),
                 true => Func::wrap(store, wasm64::hq),
@@ -68,11 +121,32 @@ impl Imports {
                 false => Func::wrap(store, wasm32::hd),
                 true => Func::wrap(store, wasm64::hd),
             },
+            "hd_typed" => match is_64 {
+                false => Func::wrap(store, wasm32::hd_typed),
+                true => Func::wrap(store, wasm64::hd_typed),
+            },
             "emit" => match is_64 {
                 false => Func::wrap(store, wasm32::emit),
                 true => Func::wrap(store, wasm64::emit),
             },
+            "emit_event" => match is_64 {
+                false => Func::wrap(store, wasm32::emit_event),
+                true => Func::wrap(store, wasm64::emit_event),
+            },
+            "defer" => match is_64 {
+                false => Func::wrap(store, wasm32::defer),
+                true => Func::wrap(store, wasm64::defer),
+            },
+            "hobserve" => Func::wrap(store, hobserve),
+            "hunobserve" => Func::wrap(store, hunobserve),
             "feed" => Func::wrap(store, feed),
+            "hbalance" => Func::wrap(store, hbalance),
+            "htransfer" => Func::wrap(store, htransfer),
+            "hself_destruct" => Func::wrap(store, hself_destruct),
+            "hlog" => Func::wrap(store, hlog),
+            "spill_len" => Func::wrap(store, spill_len),
+            "spill_read" => Func::wrap(store, spill_read),
+            "spill_write" => Func::wrap(store, spill_write),
             "limit" => Func::wrap(store, limit),
             "spent" => Func::wrap(store, spent),
             "panic" => Func::wrap(store, panic),
@@ -88,43 +162,30 @@ impl Imports {
     }
 }
 
-pub fn check_ptr(
+/// Reads the UTF-8 string of `len` bytes starting at `offset` in the memory
+/// of the given `instance`.
+fn read_str(
     instance: &WrappedInstance,
     offset: usize,
     len: usize,
-) -> Result<(), Error> {
-    let mem_len = instance.with_memory(|mem| mem.len());
-
-    let end =
-        offset
-            .checked_add(len)
-            .ok_or(Error::MemoryAccessOutOfBounds {
-                offset,
-                len,
-                mem_len,
-            })?;
-
-    if end >= mem_len {
-        return Err(Error::MemoryAccessOutOfBounds {
-            offset,
-            len,
-            mem_len,
-        });
-    }
-
-    Ok(())
+) -> Result<String, Error> {
+    instance
+        .with_memory_slice(offset, len, |buf| {
+            core::str::from_utf8(buf).map(ToOwned::to_owned)
+        })?
+        .map_err(Error::Utf8)
 }
 
 pub fn check_arg(
     instance: &WrappedInstance,
     arg_len: u32,
 ) -> Result<(), Error> {
-    let mem_len = instance.with_memory(|mem| mem.len());
+    let mem_len = instance.mem_len();
 
     let arg_ofs = instance.arg_buffer_offset();
     let arg_len = arg_len as usize;
 
-    if arg_len > ARGBUF_LEN {
+    if arg_len > instance.arg_buffer_len() {
         return Err(Error::MemoryAccessOutOfBounds {
             offset: arg_ofs,
             len: arg_len,
@@ -153,22 +214,16 @@ pub(crate) fn hq(
 
     let instance = env.self_instance();
 
-    let name_len = name_len as usize;
-
-    check_ptr(instance, name_ofs, name_len)?;
+    let name = read_str(instance, name_ofs, name_len as usize)?;
     check_arg(instance, arg_len)?;
 
-    let name = instance.with_memory(|buf| {
-        // performance: use a dedicated buffer here?
-        core::str::from_utf8(&buf[name_ofs..][..name_len])
-            .map(ToOwned::to_owned)
-    })?;
-
     // Get the host query if it exists.
     let host_query =
         env.host_query(&name).ok_or(Error::MissingHostQuery(name))?;
     let mut arg: Box<dyn Any> = Box::new(());
 
+    let instance = env.self_instance();
+
     // Price the query, allowing for an early exit if the gas is insufficient.
     let query_cost = instance.with_arg_buf(|arg_buf| {
         let arg_len = arg_len as usize;
@@ -197,20 +252,57 @@ pub(crate) fn hd(
 
     let instance = env.self_instance();
 
-    let name_len = name_len as usize;
+    let name = read_str(instance, name_ofs, name_len as usize)?;
 
-    check_ptr(instance, name_ofs, name_len)?;
+    let data = env.meta(&name).unwrap_or_default();
 
-    let name = instance.with_memory(|buf| {
-        // performance: use a dedicated buffer here?
-        core::str::from_utf8(&buf[name_ofs..][..name_len])
-            .map(ToOwned::to_owned)
-    })?;
+    write_meta(env.self_instance(), &data)
+}
 
-    let data = env.meta(&name).unwrap_or_default();
+pub(crate) fn hd_typed(
+    mut fenv: Caller<Env>,
+    name_ofs: usize,
+    name_len: u32,
+    schema: u64,
+) -> WasmtimeResult<u32> {
+    let env = fenv.data_mut();
+
+    let instance = env.self_instance();
+
+    let name = read_str(instance, name_ofs, name_len as usize)?;
+
+    let Some(data) = env.meta(&name) else {
+        return Ok(0);
+    };
+
+    let found = env.meta_schema(&name);
+    if found != Some(schema) {
+        Err(Error::MetaSchemaMismatch {
+            name: name.to_string(),
+            expected: schema,
+            found,
+        })?;
+    }
+
+    write_meta(env.self_instance(), &data)
+}
+
+/// Writes the value of a metadata item to the argument buffer, returning its
+/// length.
+fn write_meta(
+    instance: &mut WrappedInstance,
+    data: &[u8],
+) -> WasmtimeResult<u32> {
+    let max_len = instance.arg_buffer_len();
+    if data.len() > max_len {
+        Err(Error::ArgumentBufferOverflow {
+            len: data.len(),
+            max_len,
+        })?;
+    }
 
     instance.with_arg_buf_mut(|buf| {
-        buf[..data.len()].copy_from_slice(&data);
+        buf[..data.len()].copy_from_slice(data);
     });
 
     Ok(data.len() as u32)
@@ -228,15 +320,146 @@ pub(crate) fn c(
 
     let instance = env.self_instance();
 
-    let name_len = name_len as usize;
+    let (callee_id, name, arg) =
+        read_call(instance, callee_ofs, name_ofs, name_len, arg_len)?;
+
+    let ret = match call_contract(env, callee_id, &name, &arg, gas_limit)? {
+        Ok(ret_len) => {
+            // copy back result, through the host since the callee may be the
+            // caller itself
+            let ret = env
+                .instance(&callee_id)
+                .expect("callee instance should exist")
+                .read_bytes_from_arg_buffer(ret_len as u32);
+            env.self_instance().write_argument(&ret);
+            ret_len
+        }
+        Err(c_err) => {
+            env.self_instance().with_arg_buf_mut(|buf| {
+                c_err.to_parts(buf);
+            });
+            c_err.into()
+        }
+    };
+
+    Ok(ret)
+}
+
+/// Performs a call just like [`c`], but capturing the items fed by the callee -
+/// and any contracts it calls in turn - instead of passing them on to the
+/// host. On success, the items are kept for the caller to read one at a time
+/// using [`feed_next`], and the handle to them is returned.
+pub(crate) fn cf(
+    mut fenv: Caller<Env>,
+    callee_ofs: usize,
+    name_ofs: usize,
+    name_len: u32,
+    arg_len: u32,
+    gas_limit: u64,
+) -> WasmtimeResult<i32> {
+    let env = fenv.data_mut();
+
+    let instance = env.self_instance();
+
+    let (callee_id, name, arg) =
+        read_call(instance, callee_ofs, name_ofs, name_len, arg_len)?;
+
+    env.capture_feed();
+    let result = call_contract(env, callee_id, &name, &arg, gas_limit);
+    let items = env.release_feed();
+
+    let ret = match result? {
+        Ok(_) => {
+            let caller_id = *env.self_contract_id();
+            env.open_feed(caller_id, items) as i32
+        }
+        Err(c_err) => {
+            env.self_instance().with_arg_buf_mut(|buf| {
+                c_err.to_parts(buf);
+            });
+            c_err.into()
+        }
+    };
+
+    Ok(ret)
+}
+
+/// Reads the ID of the contract to call, the name of the function, and the
+/// argument of a call made by the given `instance`.
+fn read_call(
+    instance: &WrappedInstance,
+    callee_ofs: usize,
+    name_ofs: usize,
+    name_len: u32,
+    arg_len: u32,
+) -> Result<(ContractId, Vec<u8>, Vec<u8>), Error> {
+    let callee_id = read_contract_id(instance, callee_ofs)?;
+    let name =
+        instance.with_memory_slice(name_ofs, name_len as usize, |buf| {
+            buf.to_vec()
+        })?;
 
-    check_ptr(instance, callee_ofs, CONTRACT_ID_BYTES)?;
-    check_ptr(instance, name_ofs, name_len)?;
     check_arg(instance, arg_len)?;
+    let arg = instance.read_bytes_from_arg_buffer(arg_len);
 
-    let argbuf_ofs = instance.arg_buffer_offset();
+    Ok((callee_id, name, arg))
+}
 
-    let caller_remaining = instance.get_remaining_gas();
+/// Reads the contract ID starting at `offset` in the memory of the given
+/// `instance`.
+fn read_contract_id(
+    instance: &WrappedInstance,
+    offset: usize,
+) -> Result<ContractId, Error> {
+    instance.with_memory_slice(offset, CONTRACT_ID_BYTES, |buf| {
+        let mut contract_id_bytes = [0u8; CONTRACT_ID_BYTES];
+        contract_id_bytes.copy_from_slice(buf);
+        ContractId::from_bytes(contract_id_bytes)
+    })
+}
+
+/// Performs a call to the `name` function of the `callee_id` contract with the
+/// given `arg`, allowing it to spend the given `gas_limit`. A limit of zero, or
+/// one larger than the gas remaining, results in the callee getting
+/// [`GAS_PASS_PCT`] percent of the remaining gas.
+///
+/// Before the call is made, the caller is charged the surcharge given by the
+/// session's [`GasSchedule`], and runs out of gas if it cannot afford it.
+///
+/// On success, the length of the return is returned, left in the argument
+/// buffer of the callee. The gas spent and the call tree are accounted for in
+/// either case.
+///
+/// Exceeding the call depth the session allows fails the call with
+/// [`ContractError::CallDepthExceeded`], before any gas is charged, so the
+/// caller can react to it. Errors the caller should not be able to recover
+/// from, such as exceeding the number of instances the session allows or the
+/// call timing out, are returned in the outer result and abort the whole
+/// call.
+///
+/// [`GasSchedule`]: crate::GasSchedule
+fn call_contract(
+    env: &mut Env,
+    callee_id: ContractId,
+    name: &[u8],
+    arg: &[u8],
+    gas_limit: u64,
+) -> Result<Result<i32, ContractError>, Error> {
+    env.check_instance_limit(&callee_id)?;
+    if let Err(err) = env.check_call_depth() {
+        return Ok(Err(ContractError::from(err)));
+    }
+
+    let surcharge = env.call_surcharge(&callee_id);
+
+    let instance = env.self_instance();
+    let caller_buf_len = instance.arg_buffer_len();
+    let gas_remaining = instance.get_remaining_gas();
+    if gas_remaining < surcharge {
+        instance.set_remaining_gas(0);
+        return Ok(Err(ContractError::OutOfGas));
+    }
+    let caller_remaining = gas_remaining - surcharge;
 
     let callee_limit = if gas_limit > 0 && gas_limit < caller_remaining {
         gas_limit
@@ -246,23 +469,44 @@ pub(crate) fn c(
         div + rem
     };
 
-    enum WithMemoryError {
+    // A denied call is not charged the surcharge.
+    let fn_name = String::from_utf8_lossy(name);
+    let intercepted = InterceptedCall {
+        contract: callee_id,
+        fn_name: &fn_name,
+        arg_len: arg.len(),
+        gas_limit: callee_limit,
+        depth: env.call_ids().len(),
+    };
+    if let Err(err) = env.intercept_before(&intercepted) {
+        return Ok(Err(ContractError::from(err)));
+    }
+    env.self_instance().set_remaining_gas(caller_remaining);
+
+    enum CallError {
         BeforePush(Error),
         AfterPush(Error),
     }
 
-    let with_memory = |memory: &mut [u8]| -> Result<_, WithMemoryError> {
-        let arg_buf = &memory[argbuf_ofs..][..ARGBUF_LEN];
-
-        let mut callee_bytes = [0; CONTRACT_ID_BYTES];
-        callee_bytes.copy_from_slice(
-            &memory[callee_ofs..callee_ofs + CONTRACT_ID_BYTES],
-        );
-        let callee_id = ContractId::from_bytes(callee_bytes);
-
+    // Calls deferred, observations made, notifications queued, and removals
+    // marked by the callee are dropped if it fails, and the transfers it made
+    // are undone.
+    let deferred_len = env.deferred_len();
+    let observations_len = env.observations_len();
+    let notifications_len = env.notifications_len();
+    let transfers_len = env.transfers_len();
+    let removals_len = env.removals_len();
+
+    let mut call = || -> Result<_, CallError> {
+        // The name is only checked to be valid once the call is on the stack,
+        // so it is recorded as given.
         let callee_stack_element = env
-            .push_callstack(callee_id, callee_limit)
-            .map_err(WithMemoryError::BeforePush)?;
+            .push_callstack(
+                callee_id,
+                fn_name.clone().into_owned(),
+                callee_limit,
+            )
+            .map_err(CallError::BeforePush)?;
         let callee = env
             .instance(&callee_stack_element.contract_id)
             .expect("callee instance should exist");
@@ -273,23 +517,40 @@ pub(crate) fn c(
                 reason: None,
                 io: Arc::new(err),
             })
-            .map_err(WithMemoryError::AfterPush)?;
-
-        let name = core::str::from_utf8(&memory[name_ofs..][..name_len])
-            .map_err(|e| WithMemoryError::AfterPush(e.into()))?;
-
-        let arg = &arg_buf[..arg_len as usize];
+            .map_err(CallError::AfterPush)?;
+        callee.reset_heap_peak();
+
+        let name = core::str::from_utf8(name)
+            .map_err(|e| CallError::AfterPush(e.into()))?;
+
+        // The argument buffers of the caller and callee may differ in size
+        let max_len = callee.arg_buffer_len();
+        if arg.len() > max_len {
+            return Err(CallError::AfterPush(Error::ArgumentBufferOverflow {
+                len: arg.len(),
+                max_len,
+            }));
+        }
 
         callee.write_argument(arg);
         let ret_len = callee
             .call(name, arg.len() as u32, callee_limit)
             .map_err(Error::normalize)
-            .map_err(WithMemoryError::AfterPush)?;
-        check_arg(callee, ret_len as u32)
-            .map_err(WithMemoryError::AfterPush)?;
-
-        // copy back result
-        callee.read_argument(&mut memory[argbuf_ofs..][..ret_len as usize]);
+            .map_err(CallError::AfterPush)?;
+
+        // The length returned is the one the callee claims to have written to
+        // its argument buffer, and is checked against what both its buffer and
+        // the caller's can hold, so the caller never reads past what was
+        // written.
+        let max_len = caller_buf_len.min(callee.arg_buffer_len());
+        if ret_len < 0 || ret_len as usize > max_len {
+            return Err(CallError::AfterPush(Error::ReturnOverflow {
+                contract: callee_id,
+                len: ret_len as u32 as usize,
+                max_len,
+            }));
+        }
+        check_arg(callee, ret_len as u32).map_err(CallError::AfterPush)?;
 
         let callee_remaining = callee.get_remaining_gas();
         let callee_spent = callee_limit - callee_remaining;
@@ -297,20 +558,36 @@ pub(crate) fn c(
         Ok((ret_len, callee_spent))
     };
 
-    let ret = match instance.with_memory_mut(with_memory) {
+    let result = call();
+
+    let outcome = match &result {
+        Ok((_, callee_spent)) => CallOutcome {
+            gas_spent: *callee_spent,
+            success: true,
+        },
+        Err(CallError::BeforePush(_)) => CallOutcome {
+            gas_spent: 0,
+            success: false,
+        },
+        Err(CallError::AfterPush(_)) => CallOutcome {
+            gas_spent: callee_limit,
+            success: false,
+        },
+    };
+    env.intercept_after(&intercepted, outcome);
+
+    match result {
         Ok((ret_len, callee_spent)) => {
             env.move_up_call_tree(callee_spent);
-            instance.set_remaining_gas(caller_remaining - callee_spent);
-            ret_len
+            env.self_instance()
+                .set_remaining_gas(caller_remaining - callee_spent);
+            Ok(Ok(ret_len))
         }
-        Err(WithMemoryError::BeforePush(err)) => {
-            let c_err = ContractError::from(err);
-            instance.with_arg_buf_mut(|buf| {
-                c_err.to_parts(buf);
-            });
-            c_err.into()
-        }
-        Err(WithMemoryError::AfterPush(mut err)) => {
+        Err(CallError::BeforePush(err)) => Ok(Err(ContractError::from(err))),
+        Err(CallError::AfterPush(mut err)) => {
+            if let Error::OutOfGas = err {
+                env.record_out_of_gas();
+            }
             if let Err(io_err) = env.revert_callstack() {
                 err = Error::MemorySnapshotFailure {
                     reason: Some(Arc::new(err)),
@@ -318,31 +595,145 @@ pub(crate) fn c(
                 };
             }
             env.move_up_prune_call_tree();
-            instance.set_remaining_gas(caller_remaining - callee_limit);
+            env.truncate_deferred(deferred_len);
+            env.truncate_observations(observations_len);
+            env.truncate_notifications(notifications_len);
+            env.revert_transfers(transfers_len);
+            env.truncate_removals(removals_len);
+            env.self_instance()
+                .set_remaining_gas(caller_remaining - callee_limit);
+
+            if let Error::TooManyInstances(_)
+            | Error::MemoryLimitExceeded(_)
+            | Error::Timeout(_) = err
+            {
+                return Err(err);
+            }
 
-            let c_err = ContractError::from(err);
-            instance.with_arg_buf_mut(|buf| {
-                c_err.to_parts(buf);
-            });
-            c_err.into()
+            Ok(Err(ContractError::from(err)))
         }
+    }
+}
+
+/// Performs multiple calls in a single crossing. The argument buffer contains
+/// the number of calls, followed by each call's contract ID, name, and
+/// argument. The results are written back to the argument buffer in the same
+/// order, each prefixed by its return code and length, and the number of
+/// results is returned.
+fn mc(mut fenv: Caller<Env>, arg_len: u32) -> WasmtimeResult<i32> {
+    let env = fenv.data_mut();
+
+    let instance = env.self_instance();
+
+    check_arg(instance, arg_len)?;
+
+    let request = instance.with_arg_buf(|buf| buf[..arg_len as usize].to_vec());
+    let mut reader = MultiCallReader {
+        buf: &request,
+        pos: 0,
     };
 
-    Ok(ret)
+    let n_calls = reader.read_u32()?;
+
+    let mut calls = Vec::new();
+    for _ in 0..n_calls {
+        let mut callee_bytes = [0; CONTRACT_ID_BYTES];
+        callee_bytes.copy_from_slice(reader.read(CONTRACT_ID_BYTES)?);
+        let callee_id = ContractId::from_bytes(callee_bytes);
+
+        let name_len = reader.read_u32()? as usize;
+        let name = reader.read(name_len)?;
+
+        let arg_len = reader.read_u32()? as usize;
+        let arg = reader.read(arg_len)?;
+
+        calls.push((callee_id, name, arg));
+    }
+
+    let mut response = Vec::new();
+    for (callee_id, name, arg) in calls {
+        match call_contract(env, callee_id, name, arg, 0)? {
+            Ok(ret_len) => {
+                response.extend(ret_len.to_le_bytes());
+                response.extend((ret_len as u32).to_le_bytes());
+                let callee = env
+                    .instance(&callee_id)
+                    .expect("callee instance should exist");
+                callee.with_arg_buf(|buf| {
+                    response.extend(&buf[..ret_len as usize]);
+                });
+            }
+            Err(c_err) => {
+                let mut parts = match &c_err {
+                    ContractError::Panic(msg) => vec![0; 4 + msg.len()],
+                    ContractError::ReturnOverflow { .. } => {
+                        vec![0; CONTRACT_ID_BYTES + 8]
+                    }
+                    _ => Vec::new(),
+                };
+                let code = c_err.to_parts(&mut parts);
+
+                response.extend(code.to_le_bytes());
+                response.extend((parts.len() as u32).to_le_bytes());
+                response.extend(parts);
+            }
+        }
+    }
+
+    let instance = env.self_instance();
+
+    let max_len = instance.arg_buffer_len();
+    if response.len() > max_len {
+        Err(Error::ArgumentBufferOverflow {
+            len: response.len(),
+            max_len,
+        })?;
+    }
+
+    instance.with_arg_buf_mut(|buf| {
+        buf[..response.len()].copy_from_slice(&response);
+    });
+
+    Ok(n_calls as i32)
+}
+
+struct MultiCallReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> MultiCallReader<'a> {
+    fn read(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos + len;
+        if end > self.buf.len() {
+            return Err(Error::ArgumentBufferOverflow {
+                len: end,
+                max_len: self.buf.len(),
+            });
+        }
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.read(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
 }
 
 pub(crate) fn emit(
     mut fenv: Caller<Env>,
     topic_ofs: usize,
     topic_len: u32,
+    schema: Option<u64>,
     arg_len: u32,
 ) -> WasmtimeResult<()> {
     let env = fenv.data_mut();
     let instance = env.self_instance();
 
-    let topic_len = topic_len as usize;
-
-    check_ptr(instance, topic_ofs, topic_len)?;
+    let topic = read_str(instance, topic_ofs, topic_len as usize)?;
     check_arg(instance, arg_len)?;
 
     // charge for each byte emitted in an event
@@ -360,19 +751,78 @@ pub(crate) fn emit(
         Vec::from(&buf[..arg_len])
     });
 
-    let topic = instance.with_memory(|buf| {
-        // performance: use a dedicated buffer here?
-        core::str::from_utf8(&buf[topic_ofs..][..topic_len])
-            .map(ToOwned::to_owned)
-    })?;
+    env.emit(topic, data, schema);
+
+    Ok(())
+}
+
+pub(crate) fn defer(
+    mut fenv: Caller<Env>,
+    name_ofs: usize,
+    name_len: u32,
+    arg_len: u32,
+) -> WasmtimeResult<()> {
+    let env = fenv.data_mut();
+    let instance = env.self_instance();
+
+    let fn_name = read_str(instance, name_ofs, name_len as usize)?;
+    check_arg(instance, arg_len)?;
+
+    // charge for each byte kept until the deferred call is made
+    let gas_remaining = instance.get_remaining_gas();
+    let gas_cost = BYTE_STORE_COST as u64 * (name_len as u64 + arg_len as u64);
 
-    env.emit(topic, data);
+    if gas_cost > gas_remaining {
+        instance.set_remaining_gas(0);
+        Err(Error::OutOfGas)?;
+    }
+    instance.set_remaining_gas(gas_remaining - gas_cost);
+
+    let arg = instance.with_arg_buf(|buf| {
+        let arg_len = arg_len as usize;
+        Vec::from(&buf[..arg_len])
+    });
+
+    env.defer(fn_name, arg);
+
+    Ok(())
+}
+
+fn hobserve(fenv: Caller<Env>) -> WasmtimeResult<()> {
+    set_observing(fenv, true)
+}
+
+fn hunobserve(fenv: Caller<Env>) -> WasmtimeResult<()> {
+    set_observing(fenv, false)
+}
+
+fn set_observing(mut fenv: Caller<Env>, observe: bool) -> WasmtimeResult<()> {
+    let env = fenv.data_mut();
+    let instance = env.self_instance();
+
+    // charge for the contract ID kept until the call succeeds
+    let gas_remaining = instance.get_remaining_gas();
+    let gas_cost = BYTE_STORE_COST as u64 * CONTRACT_ID_BYTES as u64;
+
+    if gas_cost > gas_remaining {
+        instance.set_remaining_gas(0);
+        Err(Error::OutOfGas)?;
+    }
+    instance.set_remaining_gas(gas_remaining - gas_cost);
+
+    let observed = instance.with_arg_buf(|buf| {
+        let mut bytes = [0; CONTRACT_ID_BYTES];
+        bytes.copy_from_slice(&buf[..CONTRACT_ID_BYTES]);
+        ContractId::from_bytes(bytes)
+    });
+
+    env.observe(observed, observe);
 
     Ok(())
 }
 
-fn caller(env: Caller<Env>) -> i32 {
-    let env = env.data();
+fn caller(mut fenv: Caller<Env>) -> i32 {
+    let env = fenv.data_mut();
 
     match env.nth_from_top(1) {
         Some(call_tree_elem) => {
@@ -387,27 +837,79 @@ fn caller(env: Caller<Env>) -> i32 {
     }
 }
 
-fn callstack(env: Caller<Env>) -> i32 {
-    let env = env.data();
+fn callstack(mut fenv: Caller<Env>) -> WasmtimeResult<i32> {
+    let env = fenv.data_mut();
+    let call_ids: Vec<ContractId> =
+        env.call_ids().into_iter().copied().collect();
     let instance = env.self_instance();
 
+    let len = call_ids.len() * CONTRACT_ID_BYTES;
+    let max_len = instance.arg_buffer_len();
+    if len > max_len {
+        Err(Error::ArgumentBufferOverflow { len, max_len })?;
+    }
+
     let mut i = 0usize;
-    for contract_id in env.call_ids() {
+    for contract_id in call_ids {
         instance.with_arg_buf_mut(|buf| {
             buf[i * CONTRACT_ID_BYTES..(i + 1) * CONTRACT_ID_BYTES]
                 .copy_from_slice(contract_id.as_bytes());
         });
         i += 1;
     }
-    i as i32
+    Ok(i as i32)
+}
+
+/// Writes the frames of the call stack to the argument buffer, from the
+/// current call up to the first, and returns the number of frames. Each frame
+/// is the contract ID, the gas limit of the call, and the length of the name
+/// of the function called followed by the name itself.
+fn callstack_frames(mut fenv: Caller<Env>) -> WasmtimeResult<i32> {
+    let env = fenv.data_mut();
+
+    let frames = env.call_frames();
+    let n_frames = frames.len();
+
+    let mut response = Vec::new();
+    for (contract_id, fn_name, limit) in &frames {
+        response.extend(contract_id.as_bytes());
+        response.extend(limit.to_le_bytes());
+        response.extend((fn_name.len() as u32).to_le_bytes());
+        response.extend(fn_name.as_bytes());
+    }
+
+    let instance = env.self_instance();
+
+    let max_len = instance.arg_buffer_len();
+    if response.len() > max_len {
+        Err(Error::ArgumentBufferOverflow {
+            len: response.len(),
+            max_len,
+        })?;
+    }
+
+    instance.with_arg_buf_mut(|buf| {
+        buf[..response.len()].copy_from_slice(&response);
+    });
+
+    Ok(n_frames as i32)
 }
 
 fn feed(mut fenv: Caller<Env>, arg_len: u32) -> WasmtimeResult<()> {
     let env = fenv.data_mut();
+    let gas_cost = env.gas_schedule().feed_surcharge;
     let instance = env.self_instance();
 
     check_arg(instance, arg_len)?;
 
+    let gas_remaining = instance.get_remaining_gas();
+
+    if gas_cost > gas_remaining {
+        instance.set_remaining_gas(0);
+        Err(Error::OutOfGas)?;
+    }
+    instance.set_remaining_gas(gas_remaining - gas_cost);
+
     let data = instance.with_arg_buf(|buf| {
         let arg_len = arg_len as usize;
         Vec::from(&buf[..arg_len])
@@ -416,6 +918,193 @@ fn feed(mut fenv: Caller<Env>, arg_len: u32) -> WasmtimeResult<()> {
     Ok(env.push_feed(data)?)
 }
 
+/// Moves the next item of the feed with the given `handle` into the argument
+/// buffer, charging the caller for it. Returns the length of the item, or -1
+/// if there are no items left.
+fn feed_next(mut fenv: Caller<Env>, handle: u32) -> WasmtimeResult<i32> {
+    let env = fenv.data_mut();
+
+    let contract = *env.self_contract_id();
+    let item = match env.next_fed(contract, handle as usize) {
+        Some(item) => item,
+        None => return Ok(-1),
+    };
+
+    let gas_cost = env.gas_schedule().feed_surcharge;
+    let instance = env.self_instance();
+
+    let gas_remaining = instance.get_remaining_gas();
+
+    if gas_cost > gas_remaining {
+        instance.set_remaining_gas(0);
+        Err(Error::OutOfGas)?;
+    }
+    instance.set_remaining_gas(gas_remaining - gas_cost);
+
+    instance.with_arg_buf_mut(|buf| {
+        buf[..item.len()].copy_from_slice(&item);
+    });
+
+    Ok(item.len() as i32)
+}
+
+fn hbalance(fenv: Caller<Env>) -> u64 {
+    let env = fenv.data();
+    env.balance(env.self_contract_id())
+}
+
+/// Transfers the given `amount` out of the calling contract's balance, to the
+/// contract whose ID is in the argument buffer. Returns zero on success, and
+/// the code of the [`TransferError`] otherwise.
+///
+/// [`TransferError`]: piecrust_uplink::TransferError
+fn htransfer(mut fenv: Caller<Env>, amount: u64) -> WasmtimeResult<i32> {
+    let env = fenv.data_mut();
+    let gas_cost = env.gas_schedule().transfer_cost;
+    let instance = env.self_instance();
+
+    let gas_remaining = instance.get_remaining_gas();
+
+    if gas_cost > gas_remaining {
+        instance.set_remaining_gas(0);
+        Err(Error::OutOfGas)?;
+    }
+    instance.set_remaining_gas(gas_remaining - gas_cost);
+
+    let to = instance.with_arg_buf(|buf| {
+        let mut bytes = [0; CONTRACT_ID_BYTES];
+        bytes.copy_from_slice(&buf[..CONTRACT_ID_BYTES]);
+        ContractId::from_bytes(bytes)
+    });
+
+    let from = *env.self_contract_id();
+    Ok(match env.transfer(from, to, amount) {
+        Ok(()) => 0,
+        Err(err) => err.into(),
+    })
+}
+
+/// Transfers the calling contract's whole balance to the contract whose ID is
+/// in the argument buffer, and marks the calling contract to be removed once
+/// the call succeeds. Returns zero on success, and the code of the
+/// [`TransferError`] otherwise.
+///
+/// [`TransferError`]: piecrust_uplink::TransferError
+fn hself_destruct(mut fenv: Caller<Env>) -> WasmtimeResult<i32> {
+    let env = fenv.data_mut();
+    let gas_cost = env.gas_schedule().transfer_cost;
+    let instance = env.self_instance();
+
+    let gas_remaining = instance.get_remaining_gas();
+
+    if gas_cost > gas_remaining {
+        instance.set_remaining_gas(0);
+        Err(Error::OutOfGas)?;
+    }
+    instance.set_remaining_gas(gas_remaining - gas_cost);
+
+    let beneficiary = instance.with_arg_buf(|buf| {
+        let mut bytes = [0; CONTRACT_ID_BYTES];
+        bytes.copy_from_slice(&buf[..CONTRACT_ID_BYTES]);
+        ContractId::from_bytes(bytes)
+    });
+
+    let contract = *env.self_contract_id();
+    Ok(match env.self_destruct(contract, beneficiary) {
+        Ok(()) => 0,
+        Err(err) => err.into(),
+    })
+}
+
+fn spill_len(fenv: Caller<Env>) -> u64 {
+    fenv.data()
+        .spilled_input()
+        .map_or(0, |spill| spill.len() as u64)
+}
+
+/// Copies the spilled input, starting at `offset`, into the argument buffer,
+/// charging for each byte copied. Returns the number of bytes copied.
+fn spill_read(mut fenv: Caller<Env>, offset: u64) -> WasmtimeResult<u32> {
+    let env = fenv.data_mut();
+    let max_len = env.self_instance().arg_buffer_len();
+
+    let input = env.spilled_input().map_or(&[][..], |spill| &spill[..]);
+    let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+    let chunk = input.get(offset..).unwrap_or_default();
+    let chunk = chunk[..chunk.len().min(max_len)].to_vec();
+
+    let gas_cost = env.gas_schedule().spill_cost(chunk.len());
+    let instance = env.self_instance();
+
+    let gas_remaining = instance.get_remaining_gas();
+
+    if gas_cost > gas_remaining {
+        instance.set_remaining_gas(0);
+        Err(Error::OutOfGas)?;
+    }
+    instance.set_remaining_gas(gas_remaining - gas_cost);
+
+    instance.write_argument(&chunk);
+
+    Ok(chunk.len() as u32)
+}
+
+/// Appends the argument buffer to the spilled output, charging for each byte
+/// appended.
+fn spill_write(mut fenv: Caller<Env>, arg_len: u32) -> WasmtimeResult<()> {
+    let env = fenv.data_mut();
+    let gas_cost = env.gas_schedule().spill_cost(arg_len as usize);
+    let instance = env.self_instance();
+
+    check_arg(instance, arg_len)?;
+
+    let gas_remaining = instance.get_remaining_gas();
+
+    if gas_cost > gas_remaining {
+        instance.set_remaining_gas(0);
+        Err(Error::OutOfGas)?;
+    }
+    instance.set_remaining_gas(gas_remaining - gas_cost);
+
+    let data = instance.with_arg_buf(|buf| {
+        let arg_len = arg_len as usize;
+        Vec::from(&buf[..arg_len])
+    });
+
+    Ok(env.spill_output(&data)?)
+}
+
+fn hlog(mut fenv: Caller<Env>, level: u32, msg_len: u32) -> WasmtimeResult<()> {
+    let env = fenv.data_mut();
+    let instance = env.self_instance();
+
+    check_arg(instance, msg_len)?;
+
+    let level = LogLevel::try_from(level).map_err(Error::InvalidLogLevel)?;
+
+    // charge for each byte logged, whether or not the host keeps the message,
+    // so the gas spent doesn't depend on the host's configuration
+    let gas_remaining = instance.get_remaining_gas();
+    let gas_cost = BYTE_STORE_COST as u64 * msg_len as u64;
+
+    if gas_cost > gas_remaining {
+        instance.set_remaining_gas(0);
+        Err(Error::OutOfGas)?;
+    }
+    instance.set_remaining_gas(gas_remaining - gas_cost);
+
+    let msg = instance.with_arg_buf(|buf| {
+        let slice = &buf[..msg_len as usize];
+        std::str::from_utf8(slice)
+            .map(ToOwned::to_owned)
+            .map_err(Error::Utf8)
+    })?;
+
+    env.log(level, msg);
+
+    Ok(())
+}
+
 #[cfg(feature = "debug")]
 fn hdebug(mut fenv: Caller<Env>, msg_len: u32) -> WasmtimeResult<()> {
     let env = fenv.data_mut();
@@ -423,37 +1112,42 @@ fn hdebug(mut fenv: Caller<Env>, msg_len: u32) -> WasmtimeResult<()> {
 
     check_arg(instance, msg_len)?;
 
-    Ok(instance.with_arg_buf(|buf| {
+    let msg = instance.with_arg_buf(|buf| {
         let slice = &buf[..msg_len as usize];
+        std::str::from_utf8(slice)
+            .map(ToOwned::to_owned)
+            .map_err(Error::Utf8)
+    })?;
 
-        let msg = match std::str::from_utf8(slice) {
-            Ok(msg) => msg,
-            Err(err) => return Err(Error::Utf8(err)),
-        };
+    println!("CONTRACT DEBUG {msg}");
+    env.register_debug(msg);
 
-        env.register_debug(msg);
-        println!("CONTRACT DEBUG {msg}");
+    Ok(())
+}
 
-        Ok(())
-    })?)
+fn call_depth(fenv: Caller<Env>) -> u32 {
+    fenv.data().call_depth() as u32
+}
+
+fn max_call_depth(fenv: Caller<Env>) -> u32 {
+    fenv.data().max_call_depth() as u32
 }
 
 fn limit(fenv: Caller<Env>) -> u64 {
     fenv.data().limit()
 }
 
-fn spent(fenv: Caller<Env>) -> u64 {
-    let env = fenv.data();
-    let instance = env.self_instance();
+fn spent(mut fenv: Caller<Env>) -> u64 {
+    let env = fenv.data_mut();
 
     let limit = env.limit();
-    let remaining = instance.get_remaining_gas();
+    let remaining = env.self_instance().get_remaining_gas();
 
     limit - remaining
 }
 
-fn panic(fenv: Caller<Env>, arg_len: u32) -> WasmtimeResult<()> {
-    let env = fenv.data();
+fn panic(mut fenv: Caller<Env>, arg_len: u32) -> WasmtimeResult<()> {
+    let env = fenv.data_mut();
     let instance = env.self_instance();
 
     check_arg(instance, arg_len)?;
@@ -473,7 +1167,7 @@ fn panic(fenv: Caller<Env>, arg_len: u32) -> WasmtimeResult<()> {
 fn get_metadata(
     env: &mut Env,
     contract_id_ofs: usize,
-) -> Option<&ContractMetadata> {
+) -> Result<Option<&ContractMetadata>, Error> {
     // The null pointer is always zero, so we can use this to check if the
     // caller wants their own ID.
     if contract_id_ofs == 0 {
@@ -483,34 +1177,23 @@ fn get_metadata(
             .contract_metadata(&self_id)
             .expect("contract metadata should exist");
 
-        Some(contract_metadata)
+        Ok(Some(contract_metadata))
     } else {
         let instance = env.self_instance();
+        let contract_id = read_contract_id(instance, contract_id_ofs)?;
 
-        let contract_id = instance.with_memory(|memory| {
-            let mut contract_id_bytes = [0u8; CONTRACT_ID_BYTES];
-            contract_id_bytes.copy_from_slice(
-                &memory[contract_id_ofs..][..CONTRACT_ID_BYTES],
-            );
-            ContractId::from_bytes(contract_id_bytes)
-        });
-
-        env.contract_metadata(&contract_id)
+        Ok(env.contract_metadata(&contract_id))
     }
 }
 
 fn owner(mut fenv: Caller<Env>, mod_id_ofs: usize) -> WasmtimeResult<i32> {
-    let instance = fenv.data().self_instance();
-    check_ptr(instance, mod_id_ofs, CONTRACT_ID_BYTES)?;
     let env = fenv.data_mut();
-    match get_metadata(env, mod_id_ofs) {
+    match get_metadata(env, mod_id_ofs)? {
         None => Ok(0),
         Some(metadata) => {
-            let owner = metadata.owner.as_slice();
+            let owner = metadata.owner.clone();
 
-            instance.with_arg_buf_mut(|arg| {
-                arg[..owner.len()].copy_from_slice(owner)
-            });
+            env.self_instance().write_argument(&owner);
 
             Ok(1)
         }
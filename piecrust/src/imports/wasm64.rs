@@ -26,6 +26,15 @@ pub(crate) fn hd(
     imports::hd(fenv, name_ofs as usize, name_len)
 }
 
+pub(crate) fn hd_typed(
+    fenv: Caller<Env>,
+    name_ofs: u64,
+    name_len: u32,
+    schema: u64,
+) -> WasmtimeResult<u32> {
+    imports::hd_typed(fenv, name_ofs as usize, name_len, schema)
+}
+
 pub(crate) fn c(
     fenv: Caller<Env>,
     mod_id_ofs: u64,
@@ -44,13 +53,50 @@ pub(crate) fn c(
     )
 }
 
+pub(crate) fn cf(
+    fenv: Caller<Env>,
+    mod_id_ofs: u64,
+    name_ofs: u64,
+    name_len: u32,
+    arg_len: u32,
+    gas_limit: u64,
+) -> WasmtimeResult<i32> {
+    imports::cf(
+        fenv,
+        mod_id_ofs as usize,
+        name_ofs as usize,
+        name_len,
+        arg_len,
+        gas_limit,
+    )
+}
+
 pub(crate) fn emit(
     fenv: Caller<Env>,
     topic_ofs: u64,
     topic_len: u32,
     arg_len: u32,
 ) -> WasmtimeResult<()> {
-    imports::emit(fenv, topic_ofs as usize, topic_len, arg_len)
+    imports::emit(fenv, topic_ofs as usize, topic_len, None, arg_len)
+}
+
+pub(crate) fn emit_event(
+    fenv: Caller<Env>,
+    topic_ofs: u64,
+    topic_len: u32,
+    schema: u64,
+    arg_len: u32,
+) -> WasmtimeResult<()> {
+    imports::emit(fenv, topic_ofs as usize, topic_len, Some(schema), arg_len)
+}
+
+pub(crate) fn defer(
+    fenv: Caller<Env>,
+    name_ofs: u64,
+    name_len: u32,
+    arg_len: u32,
+) -> WasmtimeResult<()> {
+    imports::defer(fenv, name_ofs as usize, name_len, arg_len)
 }
 
 pub(crate) fn owner(fenv: Caller<Env>, mod_id_ofs: u64) -> WasmtimeResult<i32> {
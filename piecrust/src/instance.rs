@@ -4,25 +4,54 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
+use std::fmt::{self, Debug, Formatter};
 use std::io;
 use std::ops::{Deref, DerefMut};
 
-use dusk_wasmtime::{Instance, Module, Mutability, Store, ValType};
-use piecrust_uplink::{ContractId, Event, ARGBUF_LEN};
+use dusk_wasmtime::{
+    Instance, Module, Mutability, ResourceLimiter, Result as WasmtimeResult,
+    Store, Trap, UpdateDeadline, ValType,
+};
+use piecrust_uplink::{
+    ContractId, Event, Log, LogLevel, ARGBUF_LEN, ARGBUF_LEN_EXPORT,
+    MAX_ARGBUF_LEN, MIN_ARGBUF_LEN, SCRATCH_MEMORY,
+};
 
 use crate::contract::WrappedContract;
+#[cfg(feature = "debug")]
+use crate::debugger::DebugEvent;
 use crate::imports::Imports;
-use crate::session::Session;
-use crate::store::Memory;
+use crate::session::{Deferred, Observation, Session};
+use crate::store::{Memory, PAGE_SIZE};
 use crate::Error;
 
+/// The length of the heap counters exported by contracts, holding the bytes
+/// allocated on the heap followed by their peak.
+const HEAP_COUNTERS_LEN: usize = 16;
+
 pub struct WrappedInstance {
     instance: Instance,
     arg_buf_ofs: usize,
+    arg_buf_len: usize,
+    // The offset of the heap counters exported by contracts built with the
+    // builtin allocator, and whether their peak was reset for this call.
+    heap_ofs: Option<usize>,
+    heap_reset: bool,
     store: Store<Env>,
     memory: Memory,
 }
 
+impl Debug for WrappedInstance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WrappedInstance")
+            .field("arg_buf_ofs", &self.arg_buf_ofs)
+            .field("arg_buf_len", &self.arg_buf_len)
+            .field("heap_ofs", &self.heap_ofs)
+            .field("memory", &self.memory)
+            .finish()
+    }
+}
+
 pub(crate) struct Env {
     self_id: ContractId,
     session: Session,
@@ -43,22 +72,16 @@ impl DerefMut for Env {
 }
 
 impl Env {
-    pub fn self_instance<'b>(&self) -> &'b mut WrappedInstance {
+    pub fn self_instance(&mut self) -> &mut WrappedInstance {
         let stack_element = self
             .session
             .nth_from_top(0)
             .expect("there should be at least one element in the call stack");
-        self.instance(&stack_element.contract_id)
+        self.session
+            .instance(&stack_element.contract_id)
             .expect("instance should exist")
     }
 
-    pub fn instance<'b>(
-        &self,
-        contract_id: &ContractId,
-    ) -> Option<&'b mut WrappedInstance> {
-        self.session.instance(contract_id)
-    }
-
     pub fn limit(&self) -> u64 {
         self.session
             .nth_from_top(0)
@@ -66,21 +89,80 @@ impl Env {
             .limit
     }
 
-    pub fn emit(&mut self, topic: String, data: Vec<u8>) {
+    pub fn emit(&mut self, topic: String, data: Vec<u8>, schema: Option<u64>) {
         let event = Event {
             source: self.self_id,
             topic,
             data,
+            schema,
         };
 
         self.session.push_event(event);
     }
 
+    pub fn defer(&mut self, fn_name: String, arg: Vec<u8>) {
+        let deferred = Deferred {
+            contract: self.self_id,
+            fn_name,
+            arg,
+        };
+
+        self.session.push_deferred(deferred);
+    }
+
+    pub fn observe(&mut self, observed: ContractId, observe: bool) {
+        let observation = Observation {
+            observer: self.self_id,
+            observed,
+            observe,
+        };
+
+        self.session.push_observation(observation);
+    }
+
+    pub fn log(&mut self, level: LogLevel, msg: String) {
+        let log = Log {
+            source: self.self_id,
+            level,
+            msg,
+        };
+
+        self.session.push_log(log);
+    }
+
     pub fn self_contract_id(&self) -> &ContractId {
         &self.self_id
     }
 }
 
+/// Enforces the session's limit on the number of pages a memory may grow to.
+/// Erroring, rather than refusing growth, aborts the call instead of leaving
+/// `memory.grow` to fail in the contract.
+impl ResourceLimiter for Env {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> WasmtimeResult<bool> {
+        if let Some(max_pages) = self.session.max_memory_pages() {
+            if desired > max_pages * PAGE_SIZE {
+                return Err(Error::MemoryLimitExceeded(max_pages).into());
+            }
+        }
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        _desired: u32,
+        _maximum: Option<u32>,
+    ) -> WasmtimeResult<bool> {
+        Ok(true)
+    }
+}
+
 impl WrappedInstance {
     pub fn new(
         session: Session,
@@ -99,23 +181,40 @@ impl WrappedInstance {
         let module =
             unsafe { Module::deserialize(&engine, contract.as_bytes())? };
         let mut store = Store::new(&engine, env);
+        store.limiter(|env| env);
+        // The session moves the epoch forward once a call times out. Every
+        // instance sharing the engine then reaches its deadline, and only
+        // those of the session are trapped, the rest carrying on.
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(|store| {
+            if store.data().is_interrupted() {
+                return Err(Trap::Interrupt.into());
+            }
+            Ok(UpdateDeadline::Continue(1))
+        });
 
-        // Ensure there is at most one memory exported, and that it is called
-        // "memory".
-        let n_memories = module
+        // Ensure there is one memory exported called "memory", and at most one
+        // other called "scratch".
+        let memories: Vec<_> = module
             .exports()
-            .filter(|exp| exp.ty().memory().is_some())
-            .count();
+            .filter_map(|exp| {
+                exp.ty().memory().map(|ty| (exp.name(), ty.is_64()))
+            })
+            .collect();
+
+        let n_memories = memories.len();
+        let has_scratch =
+            memories.iter().any(|(name, _)| *name == SCRATCH_MEMORY);
 
-        if n_memories != 1 {
+        if n_memories != 1 + usize::from(has_scratch) {
             return Err(Error::TooManyMemories(n_memories));
         }
 
-        let is_64 = module
-            .exports()
-            .filter_map(|exp| exp.ty().memory().map(|mem_ty| mem_ty.is_64()))
-            .next()
-            .unwrap();
+        let is_64 = memories
+            .iter()
+            .find(|(name, _)| *name == "memory")
+            .map(|(_, is_64)| *is_64)
+            .ok_or(Error::InvalidMemory)?;
 
         // Ensure that every exported function has a signature that matches the
         // calling convention `F: I32 -> I32`.
@@ -170,10 +269,52 @@ impl WrappedInstance {
             _ => return Err(Error::InvalidArgumentBuffer),
         };
 
-        if arg_buf_ofs + ARGBUF_LEN >= memory.len() {
+        // Contracts declaring the size of their argument buffer export a
+        // global named `AL`, pointing to the size as a little endian `u32`.
+        let arg_buf_len =
+            match instance.get_global(&mut store, ARGBUF_LEN_EXPORT) {
+                Some(global) => {
+                    let ty = global.ty(&mut store);
+
+                    if ty.mutability() != Mutability::Const {
+                        return Err(Error::InvalidArgumentBuffer);
+                    }
+
+                    let val = global.get(&mut store);
+                    let len_ofs = if is_64 {
+                        val.i64().ok_or(Error::InvalidArgumentBuffer)? as usize
+                    } else {
+                        val.i32().ok_or(Error::InvalidArgumentBuffer)? as usize
+                    };
+
+                    let len_bytes: [u8; 4] = memory
+                        .get(len_ofs..)
+                        .and_then(|bytes| bytes.get(..4))
+                        .and_then(|bytes| bytes.try_into().ok())
+                        .ok_or(Error::InvalidArgumentBuffer)?;
+                    checked_arg_buf_len(u32::from_le_bytes(len_bytes))?
+                }
+                None => ARGBUF_LEN,
+            };
+
+        if arg_buf_ofs + arg_buf_len >= memory.len() {
             return Err(Error::InvalidArgumentBuffer);
         }
 
+        // Contracts built with the builtin allocator export a global named
+        // `H`, pointing to their heap counters. Any other global by that name
+        // is ignored.
+        let heap_ofs = instance
+            .get_global(&mut store, "H")
+            .filter(|global| {
+                global.ty(&mut store).mutability() == Mutability::Const
+            })
+            .and_then(|global| match is_64 {
+                true => global.get(&mut store).i64().map(|ofs| ofs as usize),
+                false => global.get(&mut store).i32().map(|ofs| ofs as usize),
+            })
+            .filter(|ofs| ofs + HEAP_COUNTERS_LEN < memory.len());
+
         // A memory is no longer new after one instantiation
         memory.is_new = false;
 
@@ -181,6 +322,9 @@ impl WrappedInstance {
             store,
             instance,
             arg_buf_ofs,
+            arg_buf_len,
+            heap_ofs,
+            heap_reset: false,
             memory,
         };
 
@@ -202,16 +346,36 @@ impl WrappedInstance {
         Ok(())
     }
 
+    /// Returns the indices of the pages read or written since the last
+    /// snapshot.
+    pub(crate) fn hit_pages(&self) -> impl Iterator<Item = usize> + '_ {
+        self.memory.hit_pages()
+    }
+
+    /// Returns the indices of the pages written since the last snapshot.
+    pub(crate) fn dirty_pages(&self) -> impl Iterator<Item = usize> + '_ {
+        self.memory
+            .dirty_pages()
+            .map(|(_, _, page_index)| *page_index)
+    }
+
+    /// Returns the number of pages written since the last snapshot that held
+    /// data before it, and hold only zeroes now.
+    pub(crate) fn freed_pages(&self) -> usize {
+        let is_zero = |page: &[u8]| page.iter().all(|byte| *byte == 0);
+        self.memory
+            .dirty_pages()
+            .filter(|(page, clean_page, _)| {
+                is_zero(page) && !is_zero(clean_page)
+            })
+            .count()
+    }
+
     // Write argument into instance
     pub(crate) fn write_argument(&mut self, arg: &[u8]) {
         self.with_arg_buf_mut(|buf| buf[..arg.len()].copy_from_slice(arg))
     }
 
-    // Read argument from instance
-    pub(crate) fn read_argument(&mut self, arg: &mut [u8]) {
-        self.with_arg_buf(|buf| arg.copy_from_slice(&buf[..arg.len()]))
-    }
-
     pub(crate) fn read_bytes_from_arg_buffer(&self, arg_len: u32) -> Vec<u8> {
         self.with_arg_buf(|abuf| {
             let slice = &abuf[..arg_len as usize];
@@ -233,6 +397,34 @@ impl WrappedInstance {
         f(&mut self.memory)
     }
 
+    /// Calls `f` with the `len` bytes of memory starting at `offset`, erroring
+    /// if any of them lie past the current length of the memory.
+    ///
+    /// Pointers passed by a contract must be read through this, since the
+    /// memory is mapped past its current length.
+    pub(crate) fn with_memory_slice<F, R>(
+        &self,
+        offset: usize,
+        len: usize,
+        f: F,
+    ) -> Result<R, Error>
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        let mem_len = self.mem_len();
+
+        match offset.checked_add(len) {
+            Some(end) if end <= mem_len => {
+                Ok(self.with_memory(|memory| f(&memory[offset..end])))
+            }
+            _ => Err(Error::MemoryAccessOutOfBounds {
+                offset,
+                len,
+                mem_len,
+            }),
+        }
+    }
+
     /// Returns the current length of the memory.
     pub(crate) fn mem_len(&self) -> usize {
         self.memory.current_len
@@ -243,14 +435,47 @@ impl WrappedInstance {
         self.memory.current_len = len;
     }
 
+    /// Resets the peak of the heap counters to the bytes currently allocated,
+    /// if it wasn't already reset since the instance was created.
+    ///
+    /// This must be done after the memory is snapshotted, so that the reset is
+    /// undone should the call fail.
+    pub(crate) fn reset_heap_peak(&mut self) {
+        if self.heap_reset {
+            return;
+        }
+        self.heap_reset = true;
+
+        if let Some(ofs) = self.heap_ofs {
+            self.with_memory_mut(|memory| {
+                let counters = &mut memory[ofs..][..HEAP_COUNTERS_LEN];
+                let (used, peak) = counters.split_at_mut(8);
+                if used != peak {
+                    peak.copy_from_slice(used);
+                }
+            });
+        }
+    }
+
+    /// Returns the largest number of bytes allocated on the heap since its
+    /// peak was reset, if the contract is built with the builtin allocator.
+    pub(crate) fn heap_peak(&self) -> Option<usize> {
+        self.heap_ofs.map(|ofs| {
+            self.with_memory(|memory| {
+                let mut peak = [0u8; 8];
+                peak.copy_from_slice(&memory[ofs + 8..][..8]);
+                u64::from_le_bytes(peak) as usize
+            })
+        })
+    }
+
     pub(crate) fn with_arg_buf<F, R>(&self, f: F) -> R
     where
         F: FnOnce(&[u8]) -> R,
     {
         let offset = self.arg_buf_ofs;
-        self.with_memory(
-            |memory_bytes| f(&memory_bytes[offset..][..ARGBUF_LEN]),
-        )
+        let len = self.arg_buf_len;
+        self.with_memory(|memory_bytes| f(&memory_bytes[offset..][..len]))
     }
 
     pub(crate) fn with_arg_buf_mut<F, R>(&mut self, f: F) -> R
@@ -258,8 +483,9 @@ impl WrappedInstance {
         F: FnOnce(&mut [u8]) -> R,
     {
         let offset = self.arg_buf_ofs;
+        let len = self.arg_buf_len;
         self.with_memory_mut(|memory_bytes| {
-            f(&mut memory_bytes[offset..][..ARGBUF_LEN])
+            f(&mut memory_bytes[offset..][..len])
         })
     }
 
@@ -272,7 +498,7 @@ impl WrappedInstance {
                 return Err(Error::MemoryAccessOutOfBounds {
                     offset: 0,
                     len: buf.len(),
-                    mem_len: ARGBUF_LEN,
+                    mem_len: arg_buffer.len(),
                 });
             }
 
@@ -295,8 +521,20 @@ impl WrappedInstance {
 
         self.set_remaining_gas(limit);
 
-        fun.call(&mut self.store, arg_len)
-            .map_err(|e| map_call_err(self, e))
+        #[cfg(feature = "debug")]
+        self.store.data_mut().debug_event(DebugEvent::Enter);
+
+        #[allow(clippy::let_and_return)]
+        let ret = fun
+            .call(&mut self.store, arg_len)
+            .map_err(|e| map_call_err(self, e));
+
+        #[cfg(feature = "debug")]
+        self.store.data_mut().debug_event(DebugEvent::Exit {
+            success: ret.is_ok(),
+        });
+
+        ret
     }
 
     pub fn set_remaining_gas(&mut self, limit: u64) {
@@ -332,7 +570,7 @@ impl WrappedInstance {
                             }
 
                             let buf_start = self.arg_buf_ofs;
-                            let buf_end = buf_start + ARGBUF_LEN;
+                            let buf_end = buf_start + self.arg_buf_len;
 
                             if ofs + i >= buf_start && ofs + i < buf_end {
                                 print!("{byte:02x}");
@@ -352,6 +590,24 @@ impl WrappedInstance {
     pub fn arg_buffer_offset(&self) -> usize {
         self.arg_buf_ofs
     }
+
+    /// The size of the argument buffer, in bytes.
+    pub fn arg_buffer_len(&self) -> usize {
+        self.arg_buf_len
+    }
+}
+
+/// Returns the size of the argument buffer declared by a contract, refusing
+/// one that is not a multiple of eight, or out of the bounds set by uplink,
+/// with [`Error::InvalidArgumentBuffer`].
+fn checked_arg_buf_len(len: u32) -> Result<usize, Error> {
+    let len = len as usize;
+
+    if !(MIN_ARGBUF_LEN..=MAX_ARGBUF_LEN).contains(&len) || len % 8 != 0 {
+        return Err(Error::InvalidArgumentBuffer);
+    }
+
+    Ok(len)
 }
 
 fn map_call_err(
@@ -361,6 +617,11 @@ fn map_call_err(
     if instance.get_remaining_gas() == 0 {
         return Error::OutOfGas;
     }
+    if let Some(Trap::Interrupt) = err.downcast_ref::<Trap>() {
+        if let Some(timeout) = instance.store.data().call_timeout() {
+            return Error::Timeout(timeout);
+        }
+    }
 
     err.into()
 }
@@ -0,0 +1,106 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fmt::{self, Debug, Formatter};
+
+use piecrust_uplink::ContractId;
+
+/// A call to a contract, as seen by a [`CallInterceptor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterceptedCall<'a> {
+    /// The contract called.
+    pub contract: ContractId,
+    /// The name of the function called.
+    pub fn_name: &'a str,
+    /// The length of the argument passed to the function.
+    pub arg_len: usize,
+    /// The gas the call is allowed to spend.
+    pub gas_limit: u64,
+    /// The number of calls on the stack when the call is made. Calls made by
+    /// the host are at depth zero, and the calls they make to other contracts
+    /// at depth one.
+    pub depth: usize,
+}
+
+/// How an intercepted call ended, passed to [`CallInterceptor::after_call`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallOutcome {
+    /// The gas spent by the call, including the calls it made.
+    pub gas_spent: u64,
+    /// Whether the call succeeded.
+    pub success: bool,
+}
+
+/// Hooks invoked around every contract call made in a [`Session`], whether
+/// made by the host or by another contract.
+///
+/// This allows the host to trace calls, enforce allow and deny lists, or
+/// rate limit calls to contracts, without changes to the VM. It is set on a
+/// session using [`Session::set_call_interceptor`].
+///
+/// [`Session`]: crate::Session
+/// [`Session::set_call_interceptor`]: crate::Session::set_call_interceptor
+pub trait CallInterceptor: Send {
+    /// Called before a contract is called. Returning an error denies the
+    /// call, which then fails with [`Error::CallDenied`] without spending
+    /// any gas.
+    ///
+    /// A contract whose call to another contract is denied sees the call fail,
+    /// and may recover from it.
+    ///
+    /// [`Error::CallDenied`]: crate::Error::CallDenied
+    fn before_call(&mut self, call: &InterceptedCall) -> Result<(), String> {
+        let _ = call;
+        Ok(())
+    }
+
+    /// Called after a call allowed by [`before_call`] ends, successfully or
+    /// not.
+    ///
+    /// [`before_call`]: CallInterceptor::before_call
+    fn after_call(&mut self, call: &InterceptedCall, outcome: &CallOutcome) {
+        let _ = (call, outcome);
+    }
+}
+
+/// The interceptor set on a session, if any.
+#[derive(Default)]
+pub(crate) struct Interceptor(Option<Box<dyn CallInterceptor>>);
+
+impl Interceptor {
+    pub fn set(
+        &mut self,
+        interceptor: Option<Box<dyn CallInterceptor>>,
+    ) -> Option<Box<dyn CallInterceptor>> {
+        std::mem::replace(&mut self.0, interceptor)
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn before_call(
+        &mut self,
+        call: &InterceptedCall,
+    ) -> Result<(), String> {
+        match &mut self.0 {
+            Some(interceptor) => interceptor.before_call(call),
+            None => Ok(()),
+        }
+    }
+
+    pub fn after_call(&mut self, call: &InterceptedCall, outcome: CallOutcome) {
+        if let Some(interceptor) = &mut self.0 {
+            interceptor.after_call(call, &outcome);
+        }
+    }
+}
+
+impl Debug for Interceptor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Interceptor").field(&self.is_set()).finish()
+    }
+}
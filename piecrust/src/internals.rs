@@ -0,0 +1,31 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Access to the internals of the VM, for building alternative frontends.
+//!
+//! This exposes the layer underneath [`Session`] - the store of commits, the
+//! sessions opened on top of them, and the contracts' bytecode, modules, and
+//! memories - for tools such as debuggers, differential testers, or
+//! alternative stores, that need more than the VM offers.
+//!
+//! These items are semi-stable: they may change between minor versions of
+//! this crate, but every breaking change to them bumps [`INTERNALS_VERSION`].
+//! Tools should check it to make sure they're built against a known version.
+//!
+//! Only available with the `internals` feature enabled, and re-exported by
+//! the `piecrust-core` crate.
+//!
+//! [`Session`]: crate::Session
+
+pub use dusk_wasmtime::Engine;
+
+pub use crate::store::{
+    Bytecode, ContractDataEntry, ContractSession, ContractStore, Hash, Memory,
+    Metadata, Module, Reply, StoreFull, MIN_TRACKING_PAGE_SIZE, PAGE_SIZE,
+};
+
+/// The version of the internals API, bumped on every breaking change to it.
+pub const INTERNALS_VERSION: u32 = 1;
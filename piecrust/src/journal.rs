@@ -0,0 +1,157 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::io;
+
+use bytecheck::CheckBytes;
+use piecrust_uplink::ContractId;
+use rkyv::{Archive, Deserialize, Serialize};
+
+use crate::contract::DeploySpec;
+use crate::root::Root;
+
+/// A record of the operations performed on a session, in the order they were
+/// performed, to be re-executed using [`Session::replay`].
+///
+/// Sessions keep a journal when opened with [`SessionDataBuilder::journal`],
+/// and it is taken from them using [`Session::take_journal`], which seals it
+/// with the state root reached.
+///
+/// [`Session::replay`]: crate::Session::replay
+/// [`Session::take_journal`]: crate::Session::take_journal
+/// [`SessionDataBuilder::journal`]: crate::SessionDataBuilder::journal
+#[derive(Debug, Clone, PartialEq, Eq, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes))]
+pub struct Journal {
+    /// The commit the session was based on.
+    pub base: Option<Root>,
+    /// The metadata the session was opened with.
+    pub meta: Vec<(String, Vec<u8>)>,
+    /// The schema hashes of the typed items among the metadata the session
+    /// was opened with.
+    pub schemas: Vec<(String, u64)>,
+    /// The operations performed on the session.
+    pub entries: Vec<JournalEntry>,
+    /// The state root the session reached, if the journal was sealed.
+    pub root: Option<Root>,
+}
+
+impl Journal {
+    pub(crate) fn new(
+        base: Option<Root>,
+        meta: Vec<(String, Vec<u8>)>,
+        schemas: Vec<(String, u64)>,
+    ) -> Self {
+        Self {
+            base,
+            meta,
+            schemas,
+            entries: Vec::new(),
+            root: None,
+        }
+    }
+
+    /// Serializes the journal, so it can be stored or sent elsewhere to be
+    /// replayed.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        rkyv::to_bytes::<_, 1024>(self)
+            .map(|bytes| bytes.to_vec())
+            .map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Failed serializing journal: {err}"),
+                )
+            })
+    }
+
+    /// Deserializes a journal serialized using [`to_bytes`].
+    ///
+    /// [`to_bytes`]: Journal::to_bytes
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        rkyv::from_bytes(bytes).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid journal: {err}"),
+            )
+        })
+    }
+}
+
+/// An operation performed on a session, as recorded in a [`Journal`].
+///
+/// Only operations performed by the host are recorded, with everything done
+/// in the course of one - such as the checkpoint taken by an upgrade - being
+/// re-executed along with it.
+#[derive(Debug, Clone, PartialEq, Eq, Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes))]
+pub enum JournalEntry {
+    /// A contract deployment, with its initializer argument serialized.
+    Deploy {
+        contract_id: ContractId,
+        bytecode: Vec<u8>,
+        init_arg: Option<Vec<u8>>,
+        owner: Vec<u8>,
+        nonce: u64,
+        owner_only: Vec<String>,
+        pure: Vec<String>,
+        deploy_limit: u64,
+        init_limit: u64,
+    },
+    /// A deployment of a bundle of contracts.
+    DeployBundle(Vec<DeploySpec>),
+    /// A call, with its argument serialized.
+    Call {
+        contract: ContractId,
+        fn_name: String,
+        fn_arg: Vec<u8>,
+        gas_limit: u64,
+    },
+    /// A call whose argument was streamed from an input.
+    CallSpilled {
+        contract: ContractId,
+        fn_name: String,
+        input: Vec<u8>,
+        gas_limit: u64,
+    },
+    /// An upgrade of a contract, with the argument of the migration function
+    /// serialized, and the owner's authorization of it.
+    Upgrade {
+        contract: ContractId,
+        bytecode: Vec<u8>,
+        authorization: Vec<u8>,
+        migration_fn: String,
+        fn_arg: Vec<u8>,
+        gas_limit: u64,
+    },
+    /// The end of a migration, with the contract deployed at `new_contract`
+    /// taking the place of `contract`.
+    Migrate {
+        contract: ContractId,
+        new_contract: ContractId,
+        bytecode: Vec<u8>,
+    },
+    /// The balance of a contract being set.
+    SetBalance { contract: ContractId, balance: u64 },
+    /// The removal of a contract.
+    RemoveContract(ContractId),
+    /// A metadata item being set, with its value serialized, and the hash of
+    /// its schema if it is typed.
+    SetMeta {
+        name: String,
+        value: Vec<u8>,
+        schema: Option<u64>,
+    },
+    /// The removal of a metadata item.
+    RemoveMeta(String),
+    /// A checkpoint being taken.
+    Checkpoint,
+    /// A revert to a checkpoint, identified by its position among the
+    /// checkpoints in place, from the oldest.
+    RevertTo(u32),
+    /// A release of a checkpoint, identified by its position among the
+    /// checkpoints in place, from the oldest.
+    Release(u32),
+}
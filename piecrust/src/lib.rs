@@ -80,6 +80,40 @@
 //! proposal. 32-bit contracts have a maximum memory size of 4GiB, while 64-bit
 //! contracts have a maximum memory size of 4TiB.
 //!
+//! The address width is detected for each contract when it is deployed, by
+//! looking at the type of its exported memory. Its memory and the imports it
+//! is linked against are chosen to match, meaning that 32 and 64-bit contracts
+//! can be deployed - and call each other - in the same session.
+//!
+//! # WASM Features
+//!
+//! The WASM proposals contracts may use are set explicitly when creating a
+//! [`VM`], using [`WasmFeatures`]. Bytecode using a denied feature fails to
+//! deploy with an error naming the feature.
+//!
+//! # Profiling
+//!
+//! With the `perfmap` feature enabled, the VM writes the address range and name
+//! of every contract function it compiles - or loads from disk - to
+//! `/tmp/perf-<pid>.map`. Linux `perf`, and tools built on it such as
+//! flamegraphs, use this file to attribute samples taken in contract code to
+//! the WASM functions they come from:
+//!
+//! ```text
+//! cargo build --release --features perfmap
+//! perf record -g ./target/release/my-node
+//! perf script | stackcollapse-perf.pl | flamegraph.pl > flame.svg
+//! ```
+//!
+//! Function names are taken from the contracts' `name` section, so contracts
+//! should be built without stripping it for the names to be meaningful.
+//!
+//! # Testing Contracts
+//!
+//! With the `testing` feature enabled, the `testing` module offers a harness
+//! for contract authors' tests, deploying contracts by name on an ephemeral
+//! VM, moving the block height forward, and checking the events emitted.
+//!
 //! # Usage
 //! ```
 //! use piecrust::{contract_bytecode, ContractData, SessionData, VM};
@@ -117,23 +151,67 @@
 
 #[macro_use]
 mod bytecode_macro;
+mod call_thread;
 mod call_tree;
 mod config;
 mod contract;
+#[cfg(feature = "debug")]
+mod debugger;
+mod environment;
 mod error;
+mod gas;
+mod host_event;
 mod imports;
 mod instance;
+mod interceptor;
+#[cfg(feature = "internals")]
+pub mod internals;
+mod journal;
+mod maintenance;
+mod root;
 mod session;
+mod spill;
 mod store;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod types;
+mod upgrade;
 mod vm;
 
 pub use call_tree::{CallTree, CallTreeElem};
-pub use contract::{ContractData, ContractDataBuilder};
+pub use contract::{
+    ContractData, ContractDataBuilder, ContractMetadata, DeploySpec,
+    ExportInfo, FnSchema, InvalidReason, Producer, Provenance,
+    ValidationConfig, Version, WasmFeature, WasmFeatures,
+};
+#[cfg(feature = "debug")]
+pub use debugger::{DebugFrame, Debugger};
+pub use environment::Environment;
 pub use error::Error;
-pub use session::{CallReceipt, Session, SessionData};
-pub use store::PageOpening;
-pub use vm::{HostQuery, VM};
+pub use gas::{
+    CallGas, GasDelta, GasReport, GasSchedule, InstructionCosts, ReplayCall,
+};
+pub use host_event::HostEvent;
+pub use interceptor::{CallInterceptor, CallOutcome, InterceptedCall};
+pub use journal::{Journal, JournalEntry};
+pub use maintenance::{MaintenanceCall, MaintenancePlan, MaintenanceReceipt};
+pub use root::{ParseRootError, Root, ROOT_BYTES};
+pub use session::{
+    CallReceipt, CheckpointId, DeferredCall, DeployReceipt, FeedPolicy,
+    HeapPeak, MemoryGrowth, Notification, OutOfGasFrame, OutOfGasTrace,
+    PageStats, Session, SessionData, DEFAULT_MAX_CALL_DEPTH,
+    DEFAULT_NOTIFICATION_GAS_LIMIT, DEFAULT_PURE_CACHE_CAPACITY, SENDER_META,
+};
+pub use spill::Spill;
+pub use store::{
+    check_layout, layout_spec, verify_proof, CommitDiff, CommitInfo,
+    ContractDiff, ContractHeat, FsBackend, FsckCheck, FsckFinding, FsckLevel,
+    FsckReport, HeatMap, HeatSummary, LayoutIssue, LayoutReport, LayoutRule,
+    MemoryBackend, MerkleProof, PageHeat, PageOpening, Priority, Scheduler,
+    StorageBackend,
+};
+pub use upgrade::{UpgradeAuthorizer, UpgradeRequest};
+pub use vm::{HostQuery, HostQuerySignature, VmBuilder, VM};
 
 // re-export the contents of the `piecrust-uplink` crate wholesale, ensuring
 // this is the only crate we need to define and use a VM.
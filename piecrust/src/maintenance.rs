@@ -0,0 +1,145 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust_uplink::ContractId;
+use rkyv::Serialize;
+
+use crate::root::Root;
+use crate::session::{CallReceipt, Session, SessionData};
+use crate::types::StandardBufSerializer;
+use crate::Error;
+
+/// A call made by the host as part of a [`MaintenancePlan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaintenanceCall {
+    /// The contract called.
+    pub contract: ContractId,
+    /// The name of the function called.
+    pub fn_name: String,
+    /// The serialized argument of the call.
+    pub fn_arg: Vec<u8>,
+    /// The gas limit of the call.
+    pub gas_limit: u64,
+}
+
+/// A sequence of calls to be made by the host on top of a commit, such as the
+/// hooks run by a set of contracts at the end of each epoch.
+///
+/// Run using [`VM::run_maintenance`].
+///
+/// [`VM::run_maintenance`]: crate::VM::run_maintenance
+#[derive(Debug)]
+pub struct MaintenancePlan {
+    data: SessionData,
+    calls: Vec<MaintenanceCall>,
+}
+
+impl MaintenancePlan {
+    /// Creates a new, empty, plan, to be run in a session with the given
+    /// `data`.
+    ///
+    /// Any base commit in the `data` is ignored in favor of the root passed to
+    /// [`VM::run_maintenance`].
+    ///
+    /// [`VM::run_maintenance`]: crate::VM::run_maintenance
+    pub fn new(data: impl Into<SessionData>) -> Self {
+        Self {
+            data: data.into(),
+            calls: Vec::new(),
+        }
+    }
+
+    /// Appends a call to the given `contract` to the plan, serializing its
+    /// argument.
+    pub fn call<A>(
+        self,
+        contract: ContractId,
+        fn_name: &str,
+        fn_arg: &A,
+        gas_limit: u64,
+    ) -> Result<Self, Error>
+    where
+        A: for<'b> Serialize<StandardBufSerializer<'b>>,
+    {
+        let fn_arg = Session::serialize_data(fn_arg)?;
+        Ok(self.call_raw(contract, fn_name, fn_arg, gas_limit))
+    }
+
+    /// Appends a call to the given `contract` to the plan, with an already
+    /// serialized argument.
+    pub fn call_raw<V: Into<Vec<u8>>>(
+        mut self,
+        contract: ContractId,
+        fn_name: &str,
+        fn_arg: V,
+        gas_limit: u64,
+    ) -> Self {
+        self.calls.push(MaintenanceCall {
+            contract,
+            fn_name: fn_name.into(),
+            fn_arg: fn_arg.into(),
+            gas_limit,
+        });
+        self
+    }
+
+    /// Returns the calls in the plan, in the order they will be made.
+    pub fn calls(&self) -> &[MaintenanceCall] {
+        &self.calls
+    }
+
+    pub(crate) fn into_parts(self) -> (SessionData, Vec<MaintenanceCall>) {
+        (self.data, self.calls)
+    }
+}
+
+/// The outcome of running a [`MaintenancePlan`].
+#[derive(Debug)]
+pub struct MaintenanceReceipt {
+    /// The root of the commit written after all calls were made.
+    pub root: Root,
+    /// The result of each call in the plan, in order.
+    ///
+    /// A failed call has its changes reverted, but does not stop the calls
+    /// after it from being made.
+    pub calls: Vec<Result<CallReceipt<Vec<u8>>, Error>>,
+    /// The gas spent by all calls in the plan.
+    ///
+    /// This is accounted for separately from the gas spent in any other
+    /// session, and is never charged to a caller.
+    pub gas_spent: u64,
+}
+
+/// The result of a call in a [`MaintenancePlan`].
+type CallResult = Result<CallReceipt<Vec<u8>>, Error>;
+
+/// Makes all the calls in the plan, in order, returning their results and the
+/// total gas spent.
+pub(crate) fn run(
+    session: &mut Session,
+    calls: Vec<MaintenanceCall>,
+) -> (Vec<CallResult>, u64) {
+    let mut gas_spent = 0u64;
+
+    let results = calls
+        .into_iter()
+        .map(|call| {
+            let result = session.call_raw(
+                call.contract,
+                &call.fn_name,
+                call.fn_arg,
+                call.gas_limit,
+            );
+            gas_spent = gas_spent.saturating_add(match &result {
+                Ok(receipt) => receipt.gas_spent,
+                Err(_) => session.take_failed_spent(),
+            });
+            result
+        })
+        .collect();
+
+    (results, gas_spent)
+}
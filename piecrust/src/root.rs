@@ -0,0 +1,166 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
+
+use bytecheck::CheckBytes;
+use rkyv::{Archive, Deserialize, Serialize};
+
+/// The length of a [`Root`] in bytes.
+pub const ROOT_BYTES: usize = 32;
+
+/// The root of a commit, identifying the state of all contracts in it.
+///
+/// Roots are returned as a `Root` by methods such as [`Session::commit`], and
+/// methods taking a root accept anything convertible into one, so a plain
+/// array of bytes can be passed as well. A [`ContractId`], while also made of
+/// 32 bytes, is not a root and must be explicitly converted to be used as one.
+///
+/// [`Session::commit`]: crate::Session::commit
+/// [`ContractId`]: crate::ContractId
+#[derive(
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Archive,
+    Serialize,
+    Deserialize,
+)]
+#[archive_attr(derive(CheckBytes))]
+pub struct Root([u8; ROOT_BYTES]);
+
+impl Root {
+    /// Creates a new [`Root`] from an array of bytes.
+    pub const fn from_bytes(bytes: [u8; ROOT_BYTES]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the array of bytes that make up the [`Root`].
+    pub const fn to_bytes(self) -> [u8; ROOT_BYTES] {
+        self.0
+    }
+
+    /// Returns a reference to the array of bytes that make up the [`Root`].
+    pub fn as_bytes(&self) -> &[u8; ROOT_BYTES] {
+        &self.0
+    }
+}
+
+impl From<[u8; ROOT_BYTES]> for Root {
+    fn from(bytes: [u8; ROOT_BYTES]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<Root> for [u8; ROOT_BYTES] {
+    fn from(root: Root) -> Self {
+        root.0
+    }
+}
+
+impl From<Root> for crate::store::Hash {
+    fn from(root: Root) -> Self {
+        root.0.into()
+    }
+}
+
+impl From<crate::store::Hash> for Root {
+    fn from(hash: crate::store::Hash) -> Self {
+        Self(hash.into())
+    }
+}
+
+impl AsRef<[u8]> for Root {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl PartialEq<[u8; ROOT_BYTES]> for Root {
+    fn eq(&self, other: &[u8; ROOT_BYTES]) -> bool {
+        self.0.eq(other)
+    }
+}
+
+impl PartialEq<Root> for [u8; ROOT_BYTES] {
+    fn eq(&self, other: &Root) -> bool {
+        self.eq(&other.0)
+    }
+}
+
+impl Debug for Root {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Display for Root {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "0x")?
+        }
+        for byte in self.0 {
+            write!(f, "{:02x}", &byte)?
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Root {
+    type Err = ParseRootError;
+
+    /// Parses a [`Root`] from its hex representation, optionally prefixed
+    /// with `0x`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix("0x").unwrap_or(s);
+        if s.len() != 2 * ROOT_BYTES {
+            return Err(ParseRootError::InvalidLength(s.len()));
+        }
+
+        let mut bytes = [0u8; ROOT_BYTES];
+        hex::decode_to_slice(s, &mut bytes).map_err(|err| match err {
+            hex::FromHexError::InvalidHexCharacter { c, .. } => {
+                ParseRootError::InvalidCharacter(c)
+            }
+            _ => ParseRootError::InvalidLength(s.len()),
+        })?;
+
+        Ok(Self(bytes))
+    }
+}
+
+/// The error returned when parsing a [`Root`] from a string fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseRootError {
+    /// The string, stripped of any `0x` prefix, is not twice as long as
+    /// [`ROOT_BYTES`].
+    InvalidLength(usize),
+    /// The string contains a character that is not a hex digit.
+    InvalidCharacter(char),
+}
+
+impl Display for ParseRootError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(
+                f,
+                "Invalid root length: expected {} hex digits, got {len}",
+                2 * ROOT_BYTES
+            ),
+            Self::InvalidCharacter(c) => {
+                write!(f, "Invalid character in root: {c:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseRootError {}
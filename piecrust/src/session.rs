@@ -5,15 +5,21 @@
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::io::Read;
 use std::mem;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc, Arc};
+use std::time::Duration;
 
 use bytecheck::CheckBytes;
 use dusk_wasmtime::{Engine, LinearMemory, MemoryCreator, MemoryType};
 use piecrust_uplink::{
-    ContractId, Event, ARGBUF_LEN, CONTRACT_ID_BYTES, SCRATCH_BUF_BYTES,
+    ContractId, Event, Log, LogLevel, SessionMeta, TransferError, ARGBUF_LEN,
+    CONTRACT_ID_BYTES, SCRATCH_BUF_BYTES,
 };
 use rkyv::ser::serializers::{
     BufferScratch, BufferSerializer, CompositeSerializer,
@@ -24,16 +30,47 @@ use rkyv::{
     Deserialize, Infallible, Serialize,
 };
 
+use crate::call_thread::{self, CallThread};
 use crate::call_tree::{CallTree, CallTreeElem};
-use crate::contract::{ContractData, ContractMetadata, WrappedContract};
+use crate::contract::{
+    gen_contract_id, pure_fns, resolve_placeholders, ContractData,
+    ContractMetadata, DeploySpec, ExportInfo, Provenance, ValidationConfig,
+    Version, WrappedContract,
+};
+#[cfg(feature = "debug")]
+use crate::debugger::{ActiveDebugger, DebugEvent, DebugFrame, Debugger};
+use crate::environment::Environment;
 use crate::error::Error::{self, InitalizationError, PersistenceError};
+use crate::gas::{GasSchedule, InstructionCosts};
+use crate::host_event::HostEvent;
 use crate::instance::WrappedInstance;
-use crate::store::{ContractSession, PageOpening, PAGE_SIZE};
+use crate::interceptor::{
+    CallInterceptor, CallOutcome, InterceptedCall, Interceptor,
+};
+use crate::journal::{Journal, JournalEntry};
+use crate::root::Root;
+use crate::spill::{Spill, SpillWriter};
+use crate::store::{
+    ContractSession, ContractsSnapshot, Memory, MerkleProof, PageOpening,
+    PAGE_SIZE,
+};
 use crate::types::StandardBufSerializer;
-use crate::vm::{HostQueries, HostQuery};
+use crate::upgrade::{Authorizer, UpgradeRequest};
+use crate::vm::{HostQueries, HostQuery, HostQuerySignature};
 
 const MAX_META_SIZE: usize = ARGBUF_LEN;
 pub const INIT_METHOD: &str = "init";
+/// The function called on observers to notify them of an event.
+pub const ON_EVENT_METHOD: &str = "on_event";
+/// The default gas limit of each notification of an observer.
+pub const DEFAULT_NOTIFICATION_GAS_LIMIT: u64 = 100_000;
+/// The default number of results of calls to pure functions a session caches.
+pub const DEFAULT_PURE_CACHE_CAPACITY: usize = 1024;
+/// The default maximum depth of the call stack in a single call.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 256;
+/// The metadata item declaring the sender of calls made by the host, checked
+/// against the owner of a contract when calling its owner-only functions.
+pub const SENDER_META: &str = "sender";
 
 unsafe impl Send for Session {}
 
@@ -88,24 +125,222 @@ struct SessionInner {
     current: ContractId,
 
     call_tree: CallTree,
-    instances: BTreeMap<ContractId, *mut WrappedInstance>,
+    instances: Instances,
+    // The number of memories created for the instance being created.
+    instance_memories: usize,
     debug: Vec<String>,
     data: SessionData,
 
     contract_session: ContractSession,
+    instruction_costs: InstructionCosts,
     host_queries: HostQueries,
+    upgrade_authorizer: Authorizer,
+    validation: ValidationConfig,
     buffer: Vec<u8>,
 
-    feeder: Option<mpsc::Sender<Vec<u8>>>,
+    feeder: Option<Feeder>,
+    // The items fed during each feed call made by a contract, from the
+    // outermost to the innermost.
+    feed_captures: Vec<Vec<Vec<u8>>>,
+    // The items fed during feed calls that returned, kept for the contract
+    // that made each call to read. Indexed by the handle given to it.
+    open_feeds: Vec<OpenFeed>,
+    spilled_input: Option<Spill>,
+    spilled_output: Option<SpillWriter>,
+    event_subscriber: Option<mpsc::Sender<Event>>,
+    events: Vec<Event>,
+    logs: Vec<Log>,
+    host_events: Vec<HostEvent>,
+    out_of_gas: Option<OutOfGasTrace>,
+    deferred: Vec<Deferred>,
+    // The observers of each contract, kept for the rest of the session.
+    observers: BTreeMap<ContractId, BTreeSet<ContractId>>,
+    // Changes to the observers made during a call, applied if it succeeds.
+    observations: Vec<Observation>,
+    // Events emitted by observed contracts, delivered after a call succeeds.
+    notifications: Vec<Event>,
+    // The gas spent by the last call made, if it failed.
+    failed_spent: u64,
+    // The refund earned by the calls that succeeded since the host last made
+    // a call, before it is capped.
+    gas_refund: u64,
+    // The balance of each contract, kept for the rest of the session.
+    balances: BTreeMap<ContractId, u64>,
+    // Transfers made during a call, undone if it fails.
+    transfers: Vec<Transfer>,
+    // Contracts that destroyed themselves during a call, removed if it
+    // succeeds.
+    removals: Vec<ContractId>,
+    interceptor: Interceptor,
+    #[cfg(feature = "debug")]
+    debugger: ActiveDebugger,
+    // The checkpoints in place, from oldest to newest.
+    checkpoints: Vec<Checkpoint>,
+    next_checkpoint: u64,
+    // The results of calls made by the host to pure functions.
+    pure_results: BTreeMap<PureCall, PureResult>,
+    // The contract whose pure function is being called by the host, whose
+    // writes to its memory aren't counted as changes to its state.
+    pure_contract: Option<ContractId>,
+    // The version of the state of each contract that changed, set to the
+    // number of changes made to the session when it last did.
+    state_versions: BTreeMap<ContractId, u64>,
+    // The number of changes made to the session, and the number as of the
+    // last change that couldn't be tracked per contract.
+    state_changes: u64,
+    state_epoch: u64,
+    /// The journal of the operations performed on the session, if it keeps
+    /// one.
+    journal: Option<Journal>,
+    // The thread calls are made on, spawned by the first call.
+    call_thread: Option<CallThread>,
+    // Set once the call being made times out, so that its instances are
+    // interrupted when the epoch of the engine moves forward.
+    interrupted: AtomicBool,
+}
+
+/// The instances of the contracts in the call tree of a session, until it is
+/// cleared.
+///
+/// Each instance is allocated once, when its contract is first called, and
+/// addressed by the index of its slot. The allocations are owned through raw
+/// pointers rather than boxes, so that moving the arena - or the session
+/// holding it - asserts nothing about them, and never invalidates a reference
+/// to an instance a running call is still using.
+#[derive(Default)]
+struct Instances {
+    indices: BTreeMap<ContractId, usize>,
+    slots: Vec<NonNull<WrappedInstance>>,
+}
+
+impl Instances {
+    fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn contains(&self, contract: &ContractId) -> bool {
+        self.indices.contains_key(contract)
+    }
+
+    /// Takes ownership of the `instance` of the given `contract`.
+    ///
+    /// # Panics
+    /// If the contract already has an instance.
+    fn insert(&mut self, contract: ContractId, instance: WrappedInstance) {
+        if self.indices.contains_key(&contract) {
+            panic!("Contract already in the stack: {contract:?}");
+        }
+
+        let slot = NonNull::from(Box::leak(Box::new(instance)));
+        self.indices.insert(contract, self.slots.len());
+        self.slots.push(slot);
+    }
+
+    fn get_mut(
+        &mut self,
+        contract: &ContractId,
+    ) -> Option<&mut WrappedInstance> {
+        let index = *self.indices.get(contract)?;
+        // SAFETY: the slot was allocated by `insert`, and is only freed by
+        // `clear`, which can't be called while the returned reference -
+        // borrowing the arena mutably - is alive.
+        Some(unsafe { self.slots[index].as_mut() })
+    }
+
+    /// Drops every instance.
+    fn clear(&mut self) {
+        self.indices.clear();
+        for slot in self.slots.drain(..) {
+            // SAFETY: the slot was allocated by `insert`, and is removed from
+            // the arena as it's freed, so it is only ever freed once.
+            drop(unsafe { Box::from_raw(slot.as_ptr()) });
+        }
+    }
+}
+
+impl Drop for Instances {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl Debug for Instances {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.indices.iter().map(|(contract, index)| {
+                // SAFETY: slots in the arena are always allocated
+                (contract, unsafe { self.slots[*index].as_ref() })
+            }))
+            .finish()
+    }
+}
+
+/// The state of a session when a checkpoint was taken.
+#[derive(Debug)]
+struct Checkpoint {
+    id: CheckpointId,
+    contracts: ContractsSnapshot,
+    n_host_events: usize,
+    observers: BTreeMap<ContractId, BTreeSet<ContractId>>,
+    balances: BTreeMap<ContractId, u64>,
+}
+
+/// The items fed to a contract by another it made a feed call to, read one at
+/// a time.
+#[derive(Debug)]
+struct OpenFeed {
+    contract: ContractId,
+    items: VecDeque<Vec<u8>>,
+}
+
+/// The channel the data fed by a contract is sent through.
+#[derive(Debug)]
+enum Feeder {
+    Unbounded(mpsc::Sender<Vec<u8>>),
+    Bounded(mpsc::SyncSender<Vec<u8>>, FeedPolicy),
+}
+
+/// What a bounded feeder call does with the data fed by a contract while the
+/// channel is full.
+///
+/// See [`Session::bounded_feeder_call`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FeedPolicy {
+    /// Block the contract until the receiver makes room in the channel.
+    Block,
+    /// Drop the data, letting the contract continue.
+    Drop,
+}
+
+/// A call made by the host to a pure function.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct PureCall {
+    contract: ContractId,
+    fn_name: String,
+    arg: Vec<u8>,
+}
+
+/// The result of a call to a pure function, valid for as long as the contracts
+/// it touched remain at the same versions.
+#[derive(Debug)]
+struct PureResult {
+    versions: Vec<(ContractId, u64)>,
+    gas_spent: u64,
     events: Vec<Event>,
+    logs: Vec<Log>,
+    data: Vec<u8>,
 }
 
+/// Identifies a checkpoint taken in a session using [`Session::checkpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CheckpointId(u64);
+
 unsafe impl MemoryCreator for Session {
     /// This new memory is created for the contract currently at the top of the
     /// call tree.
     fn new_memory(
         &self,
-        _ty: MemoryType,
+        ty: MemoryType,
         minimum: usize,
         _maximum: Option<usize>,
         _reserved_size_in_bytes: Option<usize>,
@@ -115,6 +350,20 @@ unsafe impl MemoryCreator for Session {
 
         let session = self.clone();
 
+        // Memories are created in order of their index, and the contract's
+        // main memory is guaranteed to come first. Any other memory is its
+        // scratch memory, which only lives as long as the instance.
+        let index = session.inner.instance_memories;
+        session.inner.instance_memories += 1;
+
+        if index > 0 {
+            let mut memory = Memory::new(ty.is_64()).map_err(|err| {
+                format!("Failed to create scratch memory: {err:?}")
+            })?;
+            memory.current_len = minimum;
+            return Ok(Box::new(memory));
+        }
+
         let contract_data =
             session.inner.contract_session.contract(contract).map_err(
                 |err| format!("Failed to get contract from session: {err:?}"),
@@ -135,21 +384,72 @@ unsafe impl MemoryCreator for Session {
 impl Session {
     pub(crate) fn new(
         engine: Engine,
+        instruction_costs: InstructionCosts,
         contract_session: ContractSession,
         host_queries: HostQueries,
+        upgrade_authorizer: Authorizer,
+        validation: ValidationConfig,
         data: SessionData,
     ) -> Self {
+        let journal = data.journal.then(|| {
+            let meta = data
+                .data
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect();
+            let schemas = data
+                .schemas
+                .iter()
+                .map(|(name, schema)| (name.to_string(), *schema))
+                .collect();
+            Journal::new(data.base.map(Root::from), meta, schemas)
+        });
+
         let inner = SessionInner {
             current: ContractId::from_bytes([0; CONTRACT_ID_BYTES]),
             call_tree: CallTree::new(),
-            instances: BTreeMap::new(),
+            instances: Instances::default(),
+            instance_memories: 0,
             debug: vec![],
             data,
             contract_session,
+            instruction_costs,
             host_queries,
+            upgrade_authorizer,
+            validation,
             buffer: vec![0; PAGE_SIZE],
             feeder: None,
+            feed_captures: vec![],
+            open_feeds: vec![],
+            spilled_input: None,
+            spilled_output: None,
+            event_subscriber: None,
             events: vec![],
+            logs: vec![],
+            host_events: vec![],
+            out_of_gas: None,
+            deferred: vec![],
+            observers: BTreeMap::new(),
+            observations: vec![],
+            notifications: vec![],
+            failed_spent: 0,
+            gas_refund: 0,
+            balances: BTreeMap::new(),
+            transfers: vec![],
+            removals: vec![],
+            interceptor: Interceptor::default(),
+            #[cfg(feature = "debug")]
+            debugger: ActiveDebugger::default(),
+            checkpoints: vec![],
+            next_checkpoint: 0,
+            pure_results: BTreeMap::new(),
+            pure_contract: None,
+            state_versions: BTreeMap::new(),
+            state_changes: 0,
+            state_epoch: 0,
+            journal,
+            call_thread: None,
+            interrupted: AtomicBool::new(false),
         };
 
         // This implementation purposefully boxes and leaks the `SessionInner`.
@@ -198,8 +498,12 @@ impl Session {
     /// proposal are accepted in just the same way as 32-bit contracts, and
     /// their handling is totally transparent.
     ///
-    /// Since a deployment may execute some contract initialization code, that
-    /// code will be metered and executed with the given `gas_limit`.
+    /// Deploying is charged [`GasSchedule::deploy_byte_cost`] for each byte
+    /// of the `bytecode`, and since a deployment may execute some contract
+    /// initialization code, that code will be metered as well. Each is given
+    /// its own limit, set using [`ContractDataBuilder::deploy_limit`] and
+    /// [`ContractDataBuilder::init_limit`] respectively, with the given
+    /// `gas_limit` used for either one left unset.
     ///
     /// # Errors
     /// It is possible that a collision between contract IDs occurs, even for
@@ -209,8 +513,13 @@ impl Session {
     ///
     /// If such a collision occurs, [`PersistenceError`] will be returned.
     ///
+    /// Should the bytecode cost more than the deploy limit,
+    /// [`Error::OutOfGas`] is returned before it is compiled.
+    ///
     /// [`ContractId`]: ContractId
     /// [`PersistenceError`]: PersistenceError
+    /// [`ContractDataBuilder::deploy_limit`]: crate::ContractDataBuilder::deploy_limit
+    /// [`ContractDataBuilder::init_limit`]: crate::ContractDataBuilder::init_limit
     ///
     /// # Panics
     /// If `deploy_data` does not specify an owner, this will panic.
@@ -220,6 +529,30 @@ impl Session {
         deploy_data: D,
         gas_limit: u64,
     ) -> Result<ContractId, Error>
+    where
+        A: 'a + for<'b> Serialize<StandardBufSerializer<'b>>,
+        D: Into<ContractData<'a, A>>,
+    {
+        self.deploy_with_receipt(bytecode, deploy_data, gas_limit)
+            .map(|receipt| receipt.contract_id)
+    }
+
+    /// Deploy a contract, returning a [`DeployReceipt`] with its
+    /// [`ContractId`], together with the gas spent deploying and initializing
+    /// it, and the events emitted and pages touched by its initialization.
+    ///
+    /// Behaves just like [`deploy`] otherwise.
+    ///
+    /// [`deploy`]: Session::deploy
+    ///
+    /// # Panics
+    /// If `deploy_data` does not specify an owner, this will panic.
+    pub fn deploy_with_receipt<'a, A, D>(
+        &mut self,
+        bytecode: &[u8],
+        deploy_data: D,
+        gas_limit: u64,
+    ) -> Result<DeployReceipt, Error>
     where
         A: 'a + for<'b> Serialize<StandardBufSerializer<'b>>,
         D: Into<ContractData<'a, A>>,
@@ -239,15 +572,39 @@ impl Session {
             init_arg = Some(self.inner.buffer[0..pos].to_vec());
         }
 
-        self.deploy_raw(
-            deploy_data.contract_id,
-            bytecode,
-            init_arg,
-            deploy_data
-                .owner
-                .expect("Owner must be specified when deploying a contract"),
-            gas_limit,
-        )
+        let contract_id = deploy_data
+            .contract_id
+            .unwrap_or_else(|| gen_contract_id(bytecode, deploy_data.nonce));
+        let owner = deploy_data
+            .owner
+            .expect("Owner must be specified when deploying a contract");
+        let deploy_limit = deploy_data.deploy_limit.unwrap_or(gas_limit);
+        let init_limit = deploy_data.init_limit.unwrap_or(gas_limit);
+
+        let entry = self.journal_entry(|| JournalEntry::Deploy {
+            contract_id,
+            bytecode: bytecode.to_vec(),
+            init_arg: init_arg.clone(),
+            owner: owner.clone(),
+            nonce: deploy_data.nonce,
+            owner_only: deploy_data.owner_only.clone(),
+            pure: deploy_data.pure.clone(),
+            deploy_limit,
+            init_limit,
+        });
+        self.journaled(entry, |session| {
+            session.do_deploy(
+                contract_id,
+                bytecode,
+                init_arg,
+                owner,
+                deploy_data.nonce,
+                deploy_data.owner_only,
+                deploy_data.pure,
+                deploy_limit,
+                init_limit,
+            )
+        })
     }
 
     /// Deploy a contract, returning its [`ContractId`]. If ID is not provided,
@@ -255,8 +612,10 @@ impl Session {
     /// the `memory64` proposal are accepted in just the same way as 32-bit
     /// contracts, and their handling is totally transparent.
     ///
-    /// Since a deployment may execute some contract initialization code, that
-    /// code will be metered and executed with the given `gas_limit`.
+    /// The given `gas_limit` is used both for deploying the bytecode, and for
+    /// the contract's initialization, as in [`deploy`].
+    ///
+    /// [`deploy`]: Session::deploy
     ///
     /// # Errors
     /// It is possible that a collision between contract IDs occurs, even for
@@ -276,13 +635,117 @@ impl Session {
         owner: Vec<u8>,
         gas_limit: u64,
     ) -> Result<ContractId, Error> {
-        let contract_id = contract_id.unwrap_or({
-            let hash = blake3::hash(bytecode);
-            ContractId::from_bytes(hash.into())
+        let contract_id =
+            contract_id.unwrap_or_else(|| gen_contract_id(bytecode, 0));
+
+        let entry = self.journal_entry(|| JournalEntry::Deploy {
+            contract_id,
+            bytecode: bytecode.to_vec(),
+            init_arg: init_arg.clone(),
+            owner: owner.clone(),
+            nonce: 0,
+            owner_only: Vec::new(),
+            pure: Vec::new(),
+            deploy_limit: gas_limit,
+            init_limit: gas_limit,
         });
-        self.do_deploy(contract_id, bytecode, init_arg, owner, gas_limit)?;
+        self.journaled(entry, |session| {
+            session.do_deploy(
+                contract_id,
+                bytecode,
+                init_arg,
+                owner,
+                0,
+                Vec::new(),
+                Vec::new(),
+                gas_limit,
+                gas_limit,
+            )
+        })
+        .map(|receipt| receipt.contract_id)
+    }
+
+    /// Deploy a bundle of contracts atomically, returning their
+    /// [`ContractId`]s in the same order as the bundle.
+    ///
+    /// The contracts are deployed and initialized in order, and either all of
+    /// them are, or - should any fail - none are. In the latter case, any
+    /// change made to other contracts while initializing the bundle is undone
+    /// as well.
+    ///
+    /// Contracts may refer to each other in their initializer arguments using
+    /// [`DeploySpec::placeholder`], which is replaced with the ID of the
+    /// contract at the placeholder's index in the bundle.
+    ///
+    /// # Errors
+    /// The first error returned in deploying a contract of the bundle, as in
+    /// [`deploy_raw`].
+    ///
+    /// [`deploy_raw`]: Session::deploy_raw
+    pub fn deploy_bundle(
+        &mut self,
+        bundle: Vec<DeploySpec>,
+    ) -> Result<Vec<ContractId>, Error> {
+        let entry =
+            self.journal_entry(|| JournalEntry::DeployBundle(bundle.clone()));
+        self.journaled(entry, |session| session.do_deploy_bundle(bundle))
+    }
+
+    fn do_deploy_bundle(
+        &mut self,
+        bundle: Vec<DeploySpec>,
+    ) -> Result<Vec<ContractId>, Error> {
+        let ids: Vec<_> = bundle.iter().map(DeploySpec::id).collect();
+
+        let snapshot = self
+            .inner
+            .contract_session
+            .snap_contracts()
+            .map_err(|err| PersistenceError(Arc::new(err)))?;
+        let n_host_events = self.inner.host_events.len();
+        let observers = self.inner.observers.clone();
+
+        let mut deployed = Ok(());
+        for (spec, id) in bundle.into_iter().zip(&ids) {
+            let init_arg = spec.init_arg.map(|mut arg| {
+                resolve_placeholders(&mut arg, &ids);
+                arg
+            });
+
+            deployed = self
+                .do_deploy(
+                    *id,
+                    &spec.bytecode,
+                    init_arg,
+                    spec.owner,
+                    0,
+                    Vec::new(),
+                    Vec::new(),
+                    spec.gas_limit,
+                    spec.gas_limit,
+                )
+                .map(drop);
+            if deployed.is_err() {
+                break;
+            }
+        }
 
-        Ok(contract_id)
+        if let Err(err) = deployed {
+            self.inner.host_events.truncate(n_host_events);
+            self.inner.observers = observers;
+            self.inner
+                .contract_session
+                .revert_contracts(snapshot)
+                .map_err(|err| PersistenceError(Arc::new(err)))?;
+            return Err(err);
+        }
+
+        self.inner
+            .contract_session
+            .apply_contracts(snapshot)
+            .map_err(|err| PersistenceError(Arc::new(err)))?;
+
+        Ok(ids)
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -292,51 +755,160 @@ impl Session {
         bytecode: &[u8],
         arg: Option<Vec<u8>>,
         owner: Vec<u8>,
-        gas_limit: u64,
-    ) -> Result<(), Error> {
+        nonce: u64,
+        owner_only: Vec<String>,
+        mut pure: Vec<String>,
+        deploy_limit: u64,
+        init_limit: u64,
+    ) -> Result<DeployReceipt, Error> {
+        #[cfg(feature = "spans")]
+        let span = tracing::info_span!(
+            "deploy",
+            contract = %contract_id,
+            deploy_limit,
+            init_limit,
+            gas_spent = tracing::field::Empty,
+        )
+        .entered();
+
         if self.inner.contract_session.contract_deployed(contract_id) {
             return Err(InitalizationError(
                 "Deployed error already exists".into(),
             ));
         }
+        let was_destroyed =
+            self.inner.contract_session.is_destroyed(&contract_id);
+
+        // The bytecode is charged for before it is compiled, so that the work
+        // of compiling it is bounded by the deploy limit.
+        let deploy_gas_spent =
+            self.inner.data.gas_schedule.deploy_cost(bytecode.len());
+        if deploy_gas_spent > deploy_limit {
+            return Err(Error::OutOfGas);
+        }
+
+        let provenance = Provenance::from_bytecode(bytecode);
+        if let Some(required) = self.inner.data.min_uplink_version {
+            if !matches!(provenance.uplink_version(), Some(v) if v >= required)
+            {
+                return Err(Error::UnsupportedUplinkVersion {
+                    required,
+                    found: provenance.uplink_version,
+                });
+            }
+        }
+
+        self.inner
+            .validation
+            .validate(bytecode)
+            .map_err(Error::InvalidBytecode)?;
+
+        // The bytecode stored is the one compiled, so that compiling it again
+        // gives the same module.
+        let compiled = self
+            .inner
+            .validation
+            .canonicalize(bytecode)
+            .map_err(Error::InvalidBytecode)?;
 
         let wrapped_contract =
-            WrappedContract::new(&self.engine, bytecode, None::<&[u8]>)?;
-        let contract_metadata = ContractMetadata { contract_id, owner };
-        let metadata_bytes = Self::serialize_data(&contract_metadata)?;
+            WrappedContract::new(&self.engine, &compiled, None::<&[u8]>)?;
+
+        for name in pure_fns(bytecode) {
+            if !pure.contains(&name) {
+                pure.push(name);
+            }
+        }
+
+        let n_host_events = self.inner.host_events.len();
+        let host_event = HostEvent::Deploy {
+            contract_id,
+            owner: owner.clone(),
+            bytecode_hash: blake3::hash(bytecode).into(),
+        };
+        let contract_metadata = ContractMetadata {
+            contract_id,
+            owner,
+            nonce,
+            init_arg: arg.clone(),
+            owner_only,
+            pure,
+            provenance,
+        };
+        let metadata_bytes = contract_metadata.to_bytes()?;
 
         self.inner
             .contract_session
             .deploy(
                 contract_id,
-                bytecode,
+                compiled.as_ref(),
                 wrapped_contract.as_bytes(),
                 contract_metadata,
                 metadata_bytes.as_slice(),
             )
             .map_err(|err| PersistenceError(Arc::new(err)))?;
+        self.record_changed(contract_id);
 
-        let instantiate = || {
+        let instantiate = || -> Result<_, Error> {
             self.create_instance(contract_id)?;
-            let instance =
-                self.instance(&contract_id).expect("instance should exist");
+            let has_init = self
+                .instance(&contract_id)
+                .expect("instance should exist")
+                .is_function_exported(INIT_METHOD);
+
+            let mut gas_spent = 0;
+            let mut page_stats = PageStats::default();
 
-            if instance.is_function_exported(INIT_METHOD) {
+            if has_init {
                 // If no argument was provided, we call the init method anyway,
                 // but with an empty argument. The alternative is to panic, but
                 // that assumes that the caller of `deploy` knows that the
                 // contract has an init method in the first place, which might
                 // not be the case, such as when ingesting untrusted bytecode.
                 let arg = arg.unwrap_or_default();
-                self.call_inner(contract_id, INIT_METHOD, arg, gas_limit)?;
+                (_, gas_spent, _, _, _, page_stats) =
+                    self.call_inner(contract_id, INIT_METHOD, arg, init_limit)?;
             }
 
-            Ok(())
+            Ok((gas_spent, page_stats))
         };
 
-        instantiate().map_err(|err| {
+        let instantiated = instantiate();
+
+        // Calls deferred during initialization are never run, and observers
+        // are never notified of the events it emitted.
+        self.inner.deferred.clear();
+        self.inner.notifications.clear();
+
+        let events = mem::take(&mut self.inner.events);
+        let logs = mem::take(&mut self.inner.logs);
+
+        let (init_gas_spent, page_stats) = instantiated.map_err(|err| {
             self.inner.contract_session.remove_contract(&contract_id);
+            // A contract removed earlier in the session stays removed
+            if was_destroyed {
+                self.inner.contract_session.destroy(contract_id);
+            }
             err
+        })?;
+
+        // The deployment precedes anything that happened during initialization
+        self.inner.host_events.insert(n_host_events, host_event);
+        let host_events = self.inner.host_events[n_host_events..].to_vec();
+
+        #[cfg(feature = "spans")]
+        span.record("gas_spent", deploy_gas_spent + init_gas_spent);
+
+        Ok(DeployReceipt {
+            contract_id,
+            deploy_gas_spent,
+            deploy_gas_limit: deploy_limit,
+            init_gas_spent,
+            init_gas_limit: init_limit,
+            events,
+            host_events,
+            logs,
+            page_stats,
         })
     }
 
@@ -346,6 +918,25 @@ impl Session {
     /// the state. They are also metered, and will execute with the given
     /// `gas_limit`. This value should never be 0.
     ///
+    /// # Pure functions
+    /// The results of calls to functions declared pure - either in the
+    /// contract's bytecode or with [`ContractDataBuilder::pure`] - are cached,
+    /// and returned again for calls with the same argument without executing
+    /// the contract. A result is only returned for as long as none of the
+    /// contracts the call touched changed since, whether by writing to their
+    /// memory, their balance changing, or being removed, and as long as it
+    /// was reached within the given `gas_limit`. Changes that can't be traced
+    /// to a contract, such as reverting to a checkpoint or setting a metadata
+    /// item, discard all cached results.
+    ///
+    /// A receipt returned from the cache has the same data, events, logs, and
+    /// gas spent as the one of the call that was executed, and is marked as
+    /// [`cached`]. Since no contract was executed, it has an empty call tree
+    /// and touched no pages. Only calls made by the host are cached.
+    ///
+    /// [`ContractDataBuilder::pure`]: crate::ContractDataBuilder::pure
+    /// [`cached`]: CallReceipt::cached
+    ///
     /// # Errors
     /// The call may error during execution for a wide array of reasons, the
     /// most common ones being running against the gas limit and a contract
@@ -387,11 +978,86 @@ impl Session {
         receipt.deserialize()
     }
 
+    /// Execute a call on the current state of this session, reverting all the
+    /// changes it made once it returns.
+    ///
+    /// The receipt - including the events emitted and the gas spent - is the
+    /// same as the one returned by [`call`], but the memories of the contracts
+    /// touched by the call, their balances, and the host events are left as
+    /// they were before it. This allows for simulating calls on top of a
+    /// session without having to open a new one.
+    ///
+    /// [`call`]: Session::call
+    pub fn simulate<A, R>(
+        &mut self,
+        contract: ContractId,
+        fn_name: &str,
+        fn_arg: &A,
+        gas_limit: u64,
+    ) -> Result<CallReceipt<R>, Error>
+    where
+        A: for<'b> Serialize<StandardBufSerializer<'b>>,
+        A::Archived: for<'b> CheckBytes<DefaultValidator<'b>>,
+        R: Archive,
+        R::Archived: Deserialize<R, Infallible>
+            + for<'b> CheckBytes<DefaultValidator<'b>>,
+    {
+        let checkpoint = self.checkpoint()?;
+        let receipt = self.call(contract, fn_name, fn_arg, gas_limit);
+        self.revert_to(checkpoint)?;
+        receipt
+    }
+
+    /// Execute a call on the current state of this session, calling back the
+    /// given `debugger` as it runs.
+    ///
+    /// The debugger is called back on entering and exiting each contract
+    /// function, including those of the contracts called by others, and on
+    /// each call a contract makes to the host. Execution is paused for as long
+    /// as a callback runs. See [`Debugger`] for more details.
+    ///
+    /// For more information about calls see [`call`].
+    ///
+    /// [`call`]: Session::call
+    #[cfg(feature = "debug")]
+    pub fn call_with_debugger<A, R>(
+        &mut self,
+        contract: ContractId,
+        fn_name: &str,
+        fn_arg: &A,
+        gas_limit: u64,
+        debugger: &mut dyn Debugger,
+    ) -> Result<CallReceipt<R>, Error>
+    where
+        A: for<'b> Serialize<StandardBufSerializer<'b>>,
+        A::Archived: for<'b> CheckBytes<DefaultValidator<'b>>,
+        R: Archive,
+        R::Archived: Deserialize<R, Infallible>
+            + for<'b> CheckBytes<DefaultValidator<'b>>,
+    {
+        // SAFETY: the debugger is removed from the session before returning,
+        // so it is never used past the lifetime of the borrow.
+        let debugger = unsafe {
+            mem::transmute::<&mut dyn Debugger, &'static mut dyn Debugger>(
+                debugger,
+            )
+        };
+
+        self.inner.debugger.set(Some(debugger));
+        let receipt = self.call(contract, fn_name, fn_arg, gas_limit);
+        self.inner.debugger.set(None);
+
+        receipt
+    }
+
     /// Execute a raw call on the current state of this session.
     ///
     /// Raw calls do not specify the type of the argument or of the return. The
     /// caller is responsible for serializing the argument as the target
-    /// `contract` expects.
+    /// `contract` expects, and the bytes returned by the contract are left as
+    /// they are in the receipt's `data`. This mirrors `uplink::call_raw`,
+    /// and allows for proxying calls - such as those coming from transactions
+    /// - without deserializing and serializing them again.
     ///
     /// For more information about calls see [`call`].
     ///
@@ -407,21 +1073,483 @@ impl Session {
             return Err(InitalizationError("init call not allowed".into()));
         }
 
-        let (data, gas_spent, call_tree) =
-            self.call_inner(contract, fn_name, fn_arg.into(), gas_limit)?;
+        let mut fn_arg = fn_arg.into();
+        let entry = self.journal_entry(|| JournalEntry::Call {
+            contract,
+            fn_name: fn_name.into(),
+            fn_arg: fn_arg.clone(),
+            gas_limit,
+        });
+
+        if matches!(self.inner.data.spill_threshold, Some(t) if fn_arg.len() > t)
+        {
+            self.inner.spilled_input = Some(
+                Spill::from_reader(&fn_arg[..])
+                    .map_err(|err| Error::SpillError(Arc::new(err)))?,
+            );
+            fn_arg = Vec::new();
+        }
+
+        let receipt = self.call_raw_inner(contract, fn_name, fn_arg, gas_limit);
+        self.record(entry);
+
+        self.inner.spilled_input = None;
+        let spilled = self
+            .inner
+            .spilled_output
+            .take()
+            .map(SpillWriter::finish)
+            .transpose()
+            .map_err(|err| Error::SpillError(Arc::new(err)));
+
+        let mut receipt = receipt?;
+        receipt.spilled = spilled?;
+        Ok(receipt)
+    }
+
+    /// Execute a raw call on the current state of this session, streaming the
+    /// argument from the given `input`.
+    ///
+    /// The input is spilled to a temporary file instead of being passed in the
+    /// contract's argument buffer, and the contract is called with an empty
+    /// argument. It is then read by the contract in chunks using
+    /// `uplink::read_spilled_input`, allowing arguments of any size - such as
+    /// those of multi-megabyte migrations - without holding them in memory.
+    ///
+    /// For more information about calls see [`call_raw`].
+    ///
+    /// [`call_raw`]: Session::call_raw
+    pub fn call_spilled<R: Read>(
+        &mut self,
+        contract: ContractId,
+        fn_name: &str,
+        input: R,
+        gas_limit: u64,
+    ) -> Result<CallReceipt<Vec<u8>>, Error> {
+        if fn_name == INIT_METHOD {
+            return Err(InitalizationError("init call not allowed".into()));
+        }
+
+        let spill = Spill::from_reader(input)
+            .map_err(|err| Error::SpillError(Arc::new(err)))?;
+        let entry = self.journal_entry(|| JournalEntry::CallSpilled {
+            contract,
+            fn_name: fn_name.into(),
+            input: spill.to_vec(),
+            gas_limit,
+        });
+
+        self.inner.spilled_input = Some(spill);
+        self.journaled(entry, |session| {
+            session.call_raw(contract, fn_name, Vec::new(), gas_limit)
+        })
+    }
+
+    fn call_raw_inner(
+        &mut self,
+        contract: ContractId,
+        fn_name: &str,
+        fn_arg: Vec<u8>,
+        gas_limit: u64,
+    ) -> Result<CallReceipt<Vec<u8>>, Error> {
+        #[cfg(feature = "spans")]
+        let span = tracing::info_span!(
+            "call",
+            contract = %contract,
+            fn_name,
+            gas_limit,
+            gas_spent = tracing::field::Empty,
+        )
+        .entered();
+
+        self.inner.notifications.clear();
+        self.inner.gas_refund = 0;
+
+        let pure_call = self.pure_call(contract, fn_name, &fn_arg)?;
+        if let Some(call) = &pure_call {
+            if let Some(receipt) = self.cached_receipt(call, gas_limit)? {
+                #[cfg(feature = "spans")]
+                span.record("gas_spent", receipt.gas_spent);
+
+                return Ok(receipt);
+            }
+        }
+
+        let n_host_events = self.inner.host_events.len();
+
+        self.inner.pure_contract = pure_call.as_ref().map(|call| call.contract);
+        let result = self.call_inner(contract, fn_name, fn_arg, gas_limit);
+        self.inner.pure_contract = None;
+
+        let (
+            data,
+            mut gas_spent,
+            call_tree,
+            memory_growth,
+            heap_peaks,
+            page_stats,
+        ) = result?;
+        let deferred = self.run_deferred(gas_limit, &mut gas_spent);
+        let notifications = self.run_notifications(gas_limit, &mut gas_spent);
+        let gas_refunded = mem::take(&mut self.inner.gas_refund)
+            .min(self.gas_schedule().refund_cap(gas_spent));
+        gas_spent -= gas_refunded;
         let events = mem::take(&mut self.inner.events);
+        let host_events = self.inner.host_events[n_host_events..].to_vec();
+        let logs = mem::take(&mut self.inner.logs);
+        let out_of_gas = self.inner.out_of_gas.take();
 
-        Ok(CallReceipt {
+        #[cfg(feature = "spans")]
+        span.record("gas_spent", gas_spent);
+
+        let receipt = CallReceipt {
             gas_limit,
             gas_spent,
+            gas_refunded,
             events,
+            host_events,
+            logs,
+            memory_growth,
+            heap_peaks,
+            page_stats,
+            out_of_gas,
+            deferred,
+            notifications,
             call_tree,
+            spilled: None,
+            cached: false,
             data,
-        })
+        };
+
+        if let Some(call) = pure_call {
+            self.cache_result(call, &receipt);
+        }
+
+        Ok(receipt)
     }
 
-    /// Migrates a `contract` to a new `bytecode`, performing modifications to
-    /// its state as specified by the closure.
+    /// Returns the call to be made, if the function called is pure and its
+    /// result may be cached.
+    ///
+    /// Results are neither cached nor returned from the cache when the call
+    /// has to run for anything besides its result, such as when it's fed
+    /// from, debugged, or its events are streamed.
+    fn pure_call(
+        &mut self,
+        contract: ContractId,
+        fn_name: &str,
+        fn_arg: &[u8],
+    ) -> Result<Option<PureCall>, Error> {
+        #[cfg(feature = "debug")]
+        if self.inner.debugger.is_set() {
+            return Ok(None);
+        }
+
+        if self.inner.data.pure_cache_capacity == 0
+            || self.inner.spilled_input.is_some()
+            || self.inner.feeder.is_some()
+            || self.inner.event_subscriber.is_some()
+        {
+            return Ok(None);
+        }
+
+        let Some(contract_data) = self
+            .inner
+            .contract_session
+            .contract(contract)
+            .map_err(|err| PersistenceError(Arc::new(err)))?
+        else {
+            return Ok(None);
+        };
+
+        let is_pure = contract_data
+            .metadata
+            .data()
+            .pure
+            .iter()
+            .any(|f| f == fn_name);
+
+        Ok(is_pure.then(|| PureCall {
+            contract,
+            fn_name: fn_name.into(),
+            arg: fn_arg.to_vec(),
+        }))
+    }
+
+    /// Returns the receipt of the given pure `call` from the cache, if its
+    /// result is still valid and was reached within the `gas_limit`.
+    ///
+    /// The call is still passed to the interceptor, and refused if the sender
+    /// isn't allowed to make it, just as if it were executed.
+    fn cached_receipt(
+        &mut self,
+        call: &PureCall,
+        gas_limit: u64,
+    ) -> Result<Option<CallReceipt<Vec<u8>>>, Error> {
+        let Some(result) = self.inner.pure_results.get(call) else {
+            return Ok(None);
+        };
+
+        let is_stale = result.versions.iter().any(|(contract, version)| {
+            self.state_version(contract) != *version
+        });
+        if is_stale {
+            self.inner.pure_results.remove(call);
+            return Ok(None);
+        }
+        if result.gas_spent > gas_limit {
+            return Ok(None);
+        }
+
+        let gas_spent = result.gas_spent;
+        let events = result.events.clone();
+        let logs = result.logs.clone();
+        let data = result.data.clone();
+
+        let intercepted = InterceptedCall {
+            contract: call.contract,
+            fn_name: &call.fn_name,
+            arg_len: call.arg.len(),
+            gas_limit,
+            depth: 0,
+        };
+        self.intercept_before(&intercepted)?;
+        if let Err(err) = self.check_owner_only(call.contract, &call.fn_name) {
+            let outcome = CallOutcome {
+                gas_spent: 0,
+                success: false,
+            };
+            self.intercept_after(&intercepted, outcome);
+            return Err(err);
+        }
+        let outcome = CallOutcome {
+            gas_spent,
+            success: true,
+        };
+        self.intercept_after(&intercepted, outcome);
+
+        Ok(Some(CallReceipt {
+            gas_limit,
+            gas_spent,
+            gas_refunded: 0,
+            events,
+            host_events: Vec::new(),
+            logs,
+            memory_growth: Vec::new(),
+            heap_peaks: Vec::new(),
+            page_stats: PageStats::default(),
+            out_of_gas: None,
+            deferred: Vec::new(),
+            notifications: Vec::new(),
+            call_tree: CallTree::new(),
+            spilled: None,
+            cached: true,
+            data,
+        }))
+    }
+
+    /// Caches the result of the given pure `call`, along with the versions of
+    /// the contracts it touched.
+    ///
+    /// Calls whose receipt carries more than their result - calls they
+    /// deferred, notifications, a recovered out of gas, a refund, or spilled
+    /// output - are left out of the cache.
+    fn cache_result(&mut self, call: PureCall, receipt: &CallReceipt<Vec<u8>>) {
+        if !receipt.deferred.is_empty()
+            || !receipt.notifications.is_empty()
+            || receipt.gas_refunded != 0
+            || receipt.out_of_gas.is_some()
+            || self.inner.spilled_output.is_some()
+        {
+            return;
+        }
+
+        let contracts: BTreeSet<_> = receipt
+            .call_tree
+            .iter()
+            .map(|elem| elem.contract_id)
+            .collect();
+        let versions = contracts
+            .into_iter()
+            .map(|contract| (contract, self.state_version(&contract)))
+            .collect();
+
+        let results = &mut self.inner.pure_results;
+        if !results.contains_key(&call)
+            && results.len() >= self.inner.data.pure_cache_capacity
+        {
+            results.pop_first();
+        }
+        results.insert(
+            call,
+            PureResult {
+                versions,
+                gas_spent: receipt.gas_spent,
+                events: receipt.events.clone(),
+                logs: receipt.logs.clone(),
+                data: receipt.data.clone(),
+            },
+        );
+    }
+
+    /// Returns the version of the state of the given `contract`, which changes
+    /// every time the contract does.
+    fn state_version(&self, contract: &ContractId) -> u64 {
+        let version = self.inner.state_versions.get(contract).copied();
+        version.unwrap_or(0).max(self.inner.state_epoch)
+    }
+
+    /// Records that the state of the given `contract` changed, outdating the
+    /// cached results of the pure calls that touched it.
+    fn record_changed(&mut self, contract: ContractId) {
+        self.inner.state_changes += 1;
+        self.inner
+            .state_versions
+            .insert(contract, self.inner.state_changes);
+    }
+
+    /// Records that the state changed in a way that isn't tracked per
+    /// contract, outdating all cached results of pure calls.
+    fn record_all_changed(&mut self) {
+        self.inner.state_changes += 1;
+        self.inner.state_epoch = self.inner.state_changes;
+        self.inner.state_versions.clear();
+        self.inner.pure_results.clear();
+    }
+
+    /// Runs the calls deferred during a call that succeeded, in the order they
+    /// were deferred, each given the gas the calls before it left unspent.
+    ///
+    /// A deferred call that fails leaves no trace, other than the gas it
+    /// spent, and drops the calls it deferred itself.
+    fn run_deferred(
+        &mut self,
+        gas_limit: u64,
+        gas_spent: &mut u64,
+    ) -> Vec<DeferredCall> {
+        let mut pending = VecDeque::from(mem::take(&mut self.inner.deferred));
+        let mut deferred_calls = Vec::with_capacity(pending.len());
+
+        while let Some(deferred) = pending.pop_front() {
+            let Deferred {
+                contract,
+                fn_name,
+                arg,
+            } = deferred;
+
+            let limit = gas_limit - *gas_spent;
+            let n_events = self.inner.events.len();
+            let n_logs = self.inner.logs.len();
+            let n_notifications = self.inner.notifications.len();
+
+            let result = if fn_name == INIT_METHOD {
+                Err(InitalizationError("init call not allowed".into()))
+            } else if limit == 0 {
+                Err(Error::OutOfGas)
+            } else {
+                self.call_inner(contract, &fn_name, arg, limit)
+            };
+
+            let (result, spent) = match result {
+                Ok((data, spent, ..)) => {
+                    pending.extend(self.inner.deferred.drain(..));
+                    (Ok(data), spent)
+                }
+                Err(err) => {
+                    self.inner.deferred.clear();
+                    self.inner.events.truncate(n_events);
+                    self.inner.logs.truncate(n_logs);
+                    self.inner.notifications.truncate(n_notifications);
+                    (Err(err), mem::take(&mut self.inner.failed_spent))
+                }
+            };
+
+            *gas_spent += spent;
+            deferred_calls.push(DeferredCall {
+                contract,
+                fn_name,
+                gas_spent: spent,
+                result,
+            });
+        }
+
+        deferred_calls
+    }
+
+    /// Notifies observers of the events emitted by the contracts they observe
+    /// during a call that succeeded, including the calls it deferred.
+    ///
+    /// Events are delivered in the order they were emitted, and each event to
+    /// its observers in the order of their IDs, by calling their
+    /// [`ON_EVENT_METHOD`]. Each notification is given at most the session's
+    /// notification gas limit, out of the gas the call left unspent.
+    ///
+    /// A notification that fails leaves no trace, other than the gas it
+    /// spent. Events emitted and calls deferred by observers while being
+    /// notified are not themselves delivered or run, so that observers can't
+    /// notify each other indefinitely.
+    fn run_notifications(
+        &mut self,
+        gas_limit: u64,
+        gas_spent: &mut u64,
+    ) -> Vec<Notification> {
+        let events = mem::take(&mut self.inner.notifications);
+        let mut notifications = Vec::new();
+
+        for event in events {
+            let observers: Vec<_> = self
+                .inner
+                .observers
+                .get(&event.source)
+                .map(|observers| observers.iter().copied().collect())
+                .unwrap_or_default();
+            if observers.is_empty() {
+                continue;
+            }
+
+            for observer in observers {
+                let limit = (gas_limit - *gas_spent)
+                    .min(self.inner.data.notification_gas_limit);
+                let n_events = self.inner.events.len();
+                let n_logs = self.inner.logs.len();
+
+                // An event too large to fit in the argument buffer fails to
+                // serialize, failing the notification.
+                let result = Self::serialize_data(&event).and_then(|arg| {
+                    if limit == 0 {
+                        return Err(Error::OutOfGas);
+                    }
+                    self.call_inner(observer, ON_EVENT_METHOD, arg, limit)
+                });
+
+                self.inner.deferred.clear();
+                self.inner.notifications.clear();
+
+                let (result, spent) = match result {
+                    Ok((_, spent, ..)) => (Ok(()), spent),
+                    Err(err) => {
+                        self.inner.events.truncate(n_events);
+                        self.inner.logs.truncate(n_logs);
+                        (Err(err), mem::take(&mut self.inner.failed_spent))
+                    }
+                };
+
+                *gas_spent += spent;
+                notifications.push(Notification {
+                    observer,
+                    source: event.source,
+                    topic: event.topic.clone(),
+                    gas_spent: spent,
+                    result,
+                });
+            }
+        }
+
+        notifications
+    }
+
+    /// Migrates a `contract` to a new `bytecode`, performing modifications to
+    /// its state as specified by the closure.
     ///
     /// The closure takes a contract ID of where the new contract will be
     /// available during the migration, and a mutable reference to a session,
@@ -461,28 +1589,261 @@ impl Session {
 
         // If the contract being replaced exists, and the caller did not specify
         // an owner, set the owner to the owner of the contract being replaced.
+        let mut old_owner = None;
         if let Some(old_contract_data) = self
             .inner
             .contract_session
             .contract(contract)
             .map_err(|err| PersistenceError(Arc::new(err)))?
         {
+            let owner = old_contract_data.metadata.data().owner.clone();
             if new_contract_data.owner.is_none() {
-                new_contract_data.owner =
-                    Some(old_contract_data.metadata.data().owner.clone());
+                new_contract_data.owner = Some(owner.clone());
             }
+            old_owner = Some(owner);
         }
+        let new_owner = new_contract_data.owner.clone();
 
         let new_contract =
             self.deploy(bytecode, new_contract_data, deploy_gas_limit)?;
 
         closure(new_contract, &mut self)?;
 
+        let entry = self.journal_entry(|| JournalEntry::Migrate {
+            contract,
+            new_contract,
+            bytecode: bytecode.to_vec(),
+        });
+        self.journaled(entry, |session| {
+            session.swap_migrated(contract, new_contract, bytecode)
+        })?;
+        if let (Some(old_owner), Some(new_owner)) = (old_owner, new_owner) {
+            if old_owner != new_owner {
+                self.inner.host_events.push(HostEvent::OwnerChange {
+                    contract_id: contract,
+                    old_owner,
+                    new_owner,
+                });
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Upgrades a `contract` to a new `bytecode`, keeping its ID, and running
+    /// the `migration_fn` of the new contract with the given `fn_arg` to carry
+    /// its state over.
+    ///
+    /// The upgrade must be authorized by the contract's current owner. The
+    /// given `authorization` - typically the owner's signature over the
+    /// [`digest`] of the upgrade - is checked by the [`UpgradeAuthorizer`] set
+    /// with [`VM::set_upgrade_authorizer`], and the upgrade is refused if
+    /// there is none. The owner remains the contract's owner afterwards, with
+    /// the same functions only they may call. Only the functions the new
+    /// bytecode declares are pure. The new bytecode is deployed under a
+    /// temporary ID - initialized just like in
+    /// [`deploy`] - while the old contract stays at its ID for the duration
+    /// of the migration, so the migration function can read the old state
+    /// by calling into it.
+    ///
+    /// The upgrade is atomic. Only once the migration function succeeds, and
+    /// its result is deserialized, does the new contract take the place of
+    /// the old one. Should any of them fail, all their changes are undone and
+    /// the old contract is left as it was. The deployment and the migration
+    /// share the given `gas_limit`, and the receipt returned is that of the
+    /// migration call, including the gas spent in deploying.
+    ///
+    /// # Errors
+    /// If the contract does not exist, [`Error::ContractDoesNotExist`] is
+    /// returned. If the `authorization` is not accepted by the authorizer,
+    /// or there is no authorizer, [`Error::UpgradeUnauthorized`] is returned.
+    /// Otherwise, any error returned in deploying the new bytecode or calling
+    /// the migration function is returned.
+    ///
+    /// [`deploy`]: Session::deploy
+    /// [`digest`]: crate::UpgradeRequest::digest
+    /// [`UpgradeAuthorizer`]: crate::UpgradeAuthorizer
+    /// [`VM::set_upgrade_authorizer`]: crate::VM::set_upgrade_authorizer
+    pub fn upgrade<A, R>(
+        &mut self,
+        contract: ContractId,
+        bytecode: &[u8],
+        authorization: impl AsRef<[u8]>,
+        migration_fn: &str,
+        fn_arg: &A,
+        gas_limit: u64,
+    ) -> Result<CallReceipt<R>, Error>
+    where
+        A: for<'b> Serialize<StandardBufSerializer<'b>>,
+        A::Archived: for<'b> CheckBytes<DefaultValidator<'b>>,
+        R: Archive,
+        R::Archived: Deserialize<R, Infallible>
+            + for<'b> CheckBytes<DefaultValidator<'b>>,
+    {
+        let fn_arg = Self::serialize_data(fn_arg)?;
+        let authorization = authorization.as_ref();
+
+        let entry = self.journal_entry(|| JournalEntry::Upgrade {
+            contract,
+            bytecode: bytecode.to_vec(),
+            authorization: authorization.to_vec(),
+            migration_fn: migration_fn.into(),
+            fn_arg: fn_arg.clone(),
+            gas_limit,
+        });
+        // A failed upgrade leaves no trace, so it is only recorded if it
+        // succeeds. This keeps replaying the journal - which has no type to
+        // deserialize the result into - from applying an upgrade that failed
+        // in deserializing.
+        self.journaled_ok(entry, |session| {
+            session.do_upgrade(
+                contract,
+                bytecode,
+                authorization,
+                migration_fn,
+                fn_arg,
+                gas_limit,
+                CallReceipt::deserialize,
+            )
+        })
+    }
+
+    /// Performs an upgrade, passing the receipt of the migration through
+    /// `finish` before the new contract takes the place of the old one, and
+    /// undoing the upgrade should it fail.
+    #[allow(clippy::too_many_arguments)]
+    fn do_upgrade<T, F>(
+        &mut self,
+        contract: ContractId,
+        bytecode: &[u8],
+        authorization: &[u8],
+        migration_fn: &str,
+        fn_arg: Vec<u8>,
+        gas_limit: u64,
+        finish: F,
+    ) -> Result<CallReceipt<T>, Error>
+    where
+        F: FnOnce(CallReceipt<Vec<u8>>) -> Result<CallReceipt<T>, Error>,
+    {
+        if migration_fn == INIT_METHOD {
+            return Err(InitalizationError("init call not allowed".into()));
+        }
+
+        let contract_data = self
+            .inner
+            .contract_session
+            .contract(contract)
+            .map_err(|err| PersistenceError(Arc::new(err)))?
+            .ok_or(Error::ContractDoesNotExist(contract))?;
+        let contract_owner = contract_data.metadata.data().owner.clone();
+        let owner_only = contract_data.metadata.data().owner_only.clone();
+        let request = UpgradeRequest {
+            contract,
+            owner: &contract_owner,
+            bytecode,
+            migration_fn,
+            fn_arg: &fn_arg,
+        };
+        if !self
+            .inner
+            .upgrade_authorizer
+            .authorize(&request, authorization)
+        {
+            return Err(Error::UpgradeUnauthorized(contract));
+        }
+
+        // The temporary ID depends on the contract upgraded, so that the same
+        // bytecode can be used to upgrade different contracts.
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(bytecode);
+        hasher.update(contract.as_bytes());
+        let new_contract = ContractId::from_bytes(hasher.finalize().into());
+
+        let checkpoint = self.checkpoint()?;
+
+        // Initialization gets whatever is left after deploying the bytecode.
+        let init_limit = gas_limit
+            .saturating_sub(self.gas_schedule().deploy_cost(bytecode.len()));
+
+        let migrated = self
+            .do_deploy(
+                new_contract,
+                bytecode,
+                None,
+                contract_owner,
+                0,
+                owner_only,
+                Vec::new(),
+                gas_limit,
+                init_limit,
+            )
+            .and_then(|deploy_receipt| {
+                let gas_spent = deploy_receipt.deploy_gas_spent
+                    + deploy_receipt.init_gas_spent;
+                if gas_spent >= gas_limit {
+                    return Err(Error::OutOfGas);
+                }
+
+                let mut receipt = self.call_raw(
+                    new_contract,
+                    migration_fn,
+                    fn_arg,
+                    gas_limit - gas_spent,
+                )?;
+                receipt.gas_limit = gas_limit;
+                receipt.gas_spent += gas_spent;
+
+                finish(receipt)
+            });
+
+        let receipt = match migrated {
+            Ok(receipt) => receipt,
+            Err(err) => {
+                self.revert_to(checkpoint)?;
+                return Err(err);
+            }
+        };
+        self.release(checkpoint)?;
+
+        self.swap_migrated(contract, new_contract, bytecode)?;
+
+        Ok(receipt)
+    }
+
+    /// Moves the contract deployed at `new_contract` for a migration to the ID
+    /// of the `contract` it replaces, recording the migration.
+    fn swap_migrated(
+        &mut self,
+        contract: ContractId,
+        new_contract: ContractId,
+        bytecode: &[u8],
+    ) -> Result<(), Error> {
         self.inner
             .contract_session
             .replace(contract, new_contract)?;
+        self.record_changed(contract);
 
-        Ok(self)
+        // Anything transferred to the new contract during the migration is
+        // credited to the ID it now lives at.
+        if let Some(balance) = self.inner.balances.remove(&new_contract) {
+            let balance = self.balance(&contract).saturating_add(balance);
+            self.set_balance(contract, balance);
+        }
+
+        // The contract deployed for the migration now lives at the ID of the
+        // contract it replaced, so its deployment is recorded as a migration.
+        self.inner.host_events.retain(|event| match event {
+            HostEvent::Deploy { contract_id, .. } => {
+                *contract_id != new_contract
+            }
+            _ => true,
+        });
+        self.inner.host_events.push(HostEvent::Migrate {
+            contract_id: contract,
+            bytecode_hash: blake3::hash(bytecode).into(),
+        });
+
+        Ok(())
     }
 
     /// Execute a *feeder* call on the current state of this session.
@@ -507,7 +1868,7 @@ impl Session {
         R::Archived: Deserialize<R, Infallible>
             + for<'b> CheckBytes<DefaultValidator<'b>>,
     {
-        self.inner.feeder = Some(feeder);
+        self.inner.feeder = Some(Feeder::Unbounded(feeder));
         let r = self.call(contract, fn_name, fn_arg, gas_limit);
         self.inner.feeder = None;
         r
@@ -528,7 +1889,64 @@ impl Session {
         gas_limit: u64,
         feeder: mpsc::Sender<Vec<u8>>,
     ) -> Result<CallReceipt<Vec<u8>>, Error> {
-        self.inner.feeder = Some(feeder);
+        self.inner.feeder = Some(Feeder::Unbounded(feeder));
+        let r = self.call_raw(contract, fn_name, fn_arg, gas_limit);
+        self.inner.feeder = None;
+        r
+    }
+
+    /// Execute a *feeder* call on the current state of this session, feeding
+    /// through a bounded channel.
+    ///
+    /// The capacity of the channel is chosen when creating it using
+    /// [`mpsc::sync_channel`], and the `policy` decides what happens to the
+    /// data fed while it is full. This keeps a contract feeding large amounts
+    /// of data from exhausting the memory of the host when the receiver falls
+    /// behind.
+    ///
+    /// See [`feeder_call`] for more information on this type of call.
+    ///
+    /// [`feeder_call`]: Session::feeder_call
+    pub fn bounded_feeder_call<A, R>(
+        &mut self,
+        contract: ContractId,
+        fn_name: &str,
+        fn_arg: &A,
+        gas_limit: u64,
+        feeder: mpsc::SyncSender<Vec<u8>>,
+        policy: FeedPolicy,
+    ) -> Result<CallReceipt<R>, Error>
+    where
+        A: for<'b> Serialize<StandardBufSerializer<'b>>,
+        A::Archived: for<'b> CheckBytes<DefaultValidator<'b>>,
+        R: Archive,
+        R::Archived: Deserialize<R, Infallible>
+            + for<'b> CheckBytes<DefaultValidator<'b>>,
+    {
+        self.inner.feeder = Some(Feeder::Bounded(feeder, policy));
+        let r = self.call(contract, fn_name, fn_arg, gas_limit);
+        self.inner.feeder = None;
+        r
+    }
+
+    /// Execute a raw *feeder* call on the current state of this session,
+    /// feeding through a bounded channel.
+    ///
+    /// See [`bounded_feeder_call`] and [`call_raw`] for more information of
+    /// this type of call.
+    ///
+    /// [`bounded_feeder_call`]: Session::bounded_feeder_call
+    /// [`call_raw`]: Session::call_raw
+    pub fn bounded_feeder_call_raw<V: Into<Vec<u8>>>(
+        &mut self,
+        contract: ContractId,
+        fn_name: &str,
+        fn_arg: V,
+        gas_limit: u64,
+        feeder: mpsc::SyncSender<Vec<u8>>,
+        policy: FeedPolicy,
+    ) -> Result<CallReceipt<Vec<u8>>, Error> {
+        self.inner.feeder = Some(Feeder::Bounded(feeder, policy));
         let r = self.call_raw(contract, fn_name, fn_arg, gas_limit);
         self.inner.feeder = None;
         r
@@ -549,62 +1967,897 @@ impl Session {
             .map(|data| data.memory.current_len))
     }
 
-    pub(crate) fn instance<'a>(
-        &self,
-        contract_id: &ContractId,
-    ) -> Option<&'a mut WrappedInstance> {
-        self.inner.instances.get(contract_id).map(|instance| {
-            // SAFETY: We guarantee that the instance exists since we're in
-            // control over if it is dropped with the session.
-            unsafe { &mut **instance }
-        })
+    /// Returns the IDs of all contracts in the session's state, both those
+    /// inherited from its base commit and those deployed during the session,
+    /// in ascending order.
+    ///
+    /// Together with [`contract_memory`], this allows walking the state of
+    /// every contract without calling into them.
+    ///
+    /// [`contract_memory`]: Session::contract_memory
+    pub fn contract_ids(&self) -> impl Iterator<Item = ContractId> {
+        self.inner.contract_session.contract_ids().into_iter()
     }
 
-    fn clear_stack_and_instances(&mut self) {
-        self.inner.call_tree.clear();
-
-        while !self.inner.instances.is_empty() {
-            let (_, instance) = self.inner.instances.pop_first().unwrap();
-            unsafe {
-                let _ = Box::from_raw(instance);
-            };
-        }
+    /// Returns a reader over the raw memory of the given contract, up to its
+    /// current length, as it stands in the session.
+    ///
+    /// If the contract does not exist, it will return `None`.
+    pub fn contract_memory(
+        &mut self,
+        contract_id: ContractId,
+    ) -> Result<Option<impl Read + '_>, Error> {
+        self.inner
+            .contract_session
+            .contract_memory(contract_id)
+            .map_err(|err| PersistenceError(Arc::new(err)))
     }
 
-    /// Return the state root of the current state of the session.
+    /// Returns the balance of the given contract.
     ///
-    /// The state root is the root of a merkle tree whose leaves are the hashes
-    /// of the state of of each contract, ordered by their contract ID.
+    /// Balances are kept by the session for each contract, with contracts
+    /// whose balance was never set holding none. Contracts read their own
+    /// balance and transfer out of it using uplink's `balance` and `transfer`,
+    /// available with its `balance` feature, without calling into a separate
+    /// contract to do so.
     ///
-    /// It also doubles as the ID of a commit - the commit root.
-    pub fn root(&self) -> [u8; 32] {
-        self.inner.contract_session.root().into()
+    /// Like observers, balances last for the rest of the session and are not
+    /// part of the state committed. The host is expected to seed them using
+    /// [`set_balance`], and to persist them as it sees fit using
+    /// [`balances`].
+    ///
+    /// [`set_balance`]: Session::set_balance
+    /// [`balances`]: Session::balances
+    pub fn balance(&self, contract: &ContractId) -> u64 {
+        self.inner.balances.get(contract).copied().unwrap_or(0)
     }
 
-    /// Returns an iterator over the pages (and their indices) of a contract's
-    /// memory, together with a proof of their inclusion in the state.
+    /// Sets the balance of the given contract.
     ///
-    /// The proof is a Merkle inclusion proof, and the caller is able to verify
-    /// it by using [`verify`], and matching the root with the one returned by
-    /// [`root`].
+    /// See [`balance`] for more details.
     ///
-    /// [`verify`]: PageOpening::verify
-    /// [`root`]: Session::root
-    pub fn memory_pages(
-        &self,
-        contract: ContractId,
-    ) -> Option<impl Iterator<Item = (usize, &[u8], PageOpening)>> {
-        self.inner.contract_session.memory_pages(contract)
-    }
+    /// [`balance`]: Session::balance
+    pub fn set_balance(&mut self, contract: ContractId, balance: u64) {
+        match balance {
+            0 => self.inner.balances.remove(&contract),
+            _ => self.inner.balances.insert(contract, balance),
+        };
+        self.record_changed(contract);
 
-    pub(crate) fn push_event(&mut self, event: Event) {
-        self.inner.events.push(event);
+        let entry = self
+            .journal_entry(|| JournalEntry::SetBalance { contract, balance });
+        self.record(entry);
     }
 
-    pub(crate) fn push_feed(&mut self, data: Vec<u8>) -> Result<(), Error> {
-        let feed = self.inner.feeder.as_ref().ok_or(Error::MissingFeed)?;
-        feed.send(data).map_err(Error::FeedPulled)
-    }
+    /// Returns the contracts with a balance, together with their balance, in
+    /// ascending order of their IDs.
+    ///
+    /// See [`balance`] for more details.
+    ///
+    /// [`balance`]: Session::balance
+    pub fn balances(&self) -> impl Iterator<Item = (&ContractId, u64)> {
+        self.inner
+            .balances
+            .iter()
+            .map(|(contract, balance)| (contract, *balance))
+    }
+
+    /// Transfers the given `amount` out of the balance of the `from` contract,
+    /// and into that of the `to` contract, recording it so it can be undone
+    /// should the call it was made in fail.
+    pub(crate) fn transfer(
+        &mut self,
+        from: ContractId,
+        to: ContractId,
+        amount: u64,
+    ) -> Result<(), TransferError> {
+        if !self.inner.contract_session.contract_deployed(to)
+            || self.inner.removals.contains(&to)
+        {
+            return Err(TransferError::RecipientDoesNotExist);
+        }
+
+        let from_balance = self.balance(&from);
+        if from_balance < amount {
+            return Err(TransferError::InsufficientBalance);
+        }
+
+        if from != to {
+            let to_balance = self
+                .balance(&to)
+                .checked_add(amount)
+                .ok_or(TransferError::BalanceOverflow)?;
+
+            self.set_balance(from, from_balance - amount);
+            self.set_balance(to, to_balance);
+        }
+
+        self.inner.transfers.push(Transfer { from, to, amount });
+
+        Ok(())
+    }
+
+    pub(crate) fn transfers_len(&self) -> usize {
+        self.inner.transfers.len()
+    }
+
+    /// Undoes the transfers made since there were `len` of them, from the
+    /// newest to the oldest.
+    pub(crate) fn revert_transfers(&mut self, len: usize) {
+        let len = len.min(self.inner.transfers.len());
+        for transfer in self.inner.transfers.split_off(len).into_iter().rev() {
+            let Transfer { from, to, amount } = transfer;
+            if from != to {
+                let to_balance = self.balance(&to) - amount;
+                let from_balance = self.balance(&from) + amount;
+
+                self.set_balance(to, to_balance);
+                self.set_balance(from, from_balance);
+            }
+        }
+    }
+
+    /// Transfers the whole balance of the `contract` to the `beneficiary`, and
+    /// marks the contract to be removed once the call succeeds.
+    ///
+    /// From then on, the contract can no longer be called or transferred to
+    /// during the call.
+    pub(crate) fn self_destruct(
+        &mut self,
+        contract: ContractId,
+        beneficiary: ContractId,
+    ) -> Result<(), TransferError> {
+        if contract == beneficiary {
+            return Err(TransferError::RecipientDoesNotExist);
+        }
+
+        let balance = self.balance(&contract);
+        self.transfer(contract, beneficiary, balance)?;
+
+        if !self.inner.removals.contains(&contract) {
+            self.inner.removals.push(contract);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn removals_len(&self) -> usize {
+        self.inner.removals.len()
+    }
+
+    pub(crate) fn truncate_removals(&mut self, len: usize) {
+        self.inner.removals.truncate(len);
+    }
+
+    /// Removes the given contract from the state, so that it is left out of
+    /// the next commit and can no longer be called.
+    ///
+    /// The contract's balance is dropped, and it stops observing other
+    /// contracts as well as being observed. A [`HostEvent::Remove`] is
+    /// emitted, and the removal can be undone by reverting to a
+    /// [`checkpoint`] taken before it. The contract may be deployed again
+    /// under the same ID afterwards.
+    ///
+    /// # Errors
+    /// If the contract doesn't exist, [`Error::ContractDoesNotExist`] is
+    /// returned.
+    ///
+    /// [`checkpoint`]: Session::checkpoint
+    pub fn remove_contract(
+        &mut self,
+        contract: ContractId,
+    ) -> Result<(), Error> {
+        if !self.inner.contract_session.contract_deployed(contract) {
+            return Err(Error::ContractDoesNotExist(contract));
+        }
+
+        self.destroy_contract(contract);
+
+        let entry =
+            self.journal_entry(|| JournalEntry::RemoveContract(contract));
+        self.record(entry);
+
+        Ok(())
+    }
+
+    fn destroy_contract(&mut self, contract: ContractId) {
+        self.inner.contract_session.destroy(contract);
+        self.inner.balances.remove(&contract);
+        self.record_changed(contract);
+
+        self.inner.observers.remove(&contract);
+        self.inner.observers.retain(|_, observers| {
+            observers.remove(&contract);
+            !observers.is_empty()
+        });
+
+        self.inner.host_events.push(HostEvent::Remove {
+            contract_id: contract,
+        });
+    }
+
+    /// Returns the instance of the given contract, if it's in the call tree.
+    pub(crate) fn instance(
+        &mut self,
+        contract_id: &ContractId,
+    ) -> Option<&mut WrappedInstance> {
+        self.inner.instances.get_mut(contract_id)
+    }
+
+    /// Locks the given `contracts` for this session, so that no other
+    /// session may lock them until this one is committed or dropped.
+    ///
+    /// Locks are advisory - they don't stop any session from calling a
+    /// contract. They allow the host to run sessions it knows to touch
+    /// disjoint sets of contracts concurrently, on the same base, and later
+    /// [`merge`] them into a single commit.
+    ///
+    /// # Errors
+    /// If any of the contracts is locked by another session,
+    /// [`Error::ContractsLocked`] is returned with them, and none of the
+    /// given contracts are locked.
+    ///
+    /// [`merge`]: Session::merge
+    pub fn lock_contracts(
+        &mut self,
+        contracts: &[ContractId],
+    ) -> Result<(), Error> {
+        self.inner
+            .contract_session
+            .lock_contracts(contracts)
+            .map_err(Error::ContractsLocked)
+    }
+
+    /// Returns the contracts locked by this session.
+    pub fn locked_contracts(&self) -> impl Iterator<Item = &ContractId> {
+        self.inner.contract_session.locked_contracts().iter()
+    }
+
+    /// Merges the changes made in the `other` session into this one, so that
+    /// committing this session commits the changes made by both.
+    ///
+    /// The changes to contracts, the host events, and the contracts locked by
+    /// `other` are merged, with any checkpoint in place in `other` released.
+    /// Everything else about the other session, such as the observers
+    /// registered in it and the balances of its contracts, is dropped along
+    /// with it.
+    ///
+    /// # Errors
+    /// Both sessions must have the same base. If a contract was loaded in
+    /// both sessions and written to by either, [`Error::MergeConflict`] is
+    /// returned with all such contracts, and this session is left untouched.
+    pub fn merge(&mut self, mut other: Session) -> Result<(), Error> {
+        other.clear_stack_and_instances();
+        if let Some(checkpoint) = other.inner.checkpoints.first() {
+            other.release(checkpoint.id)?;
+        }
+        self.inner
+            .contract_session
+            .merge(&mut other.inner.contract_session)?;
+        self.inner.host_events.append(&mut other.inner.host_events);
+        self.record_all_changed();
+
+        // The operations performed on the other session - including releasing
+        // its checkpoints above - are journaled as if they were performed on
+        // this one, on top of the checkpoints in place in it.
+        let n_checkpoints = self.inner.checkpoints.len() as u32;
+        if let (Some(journal), Some(merged)) =
+            (&mut self.inner.journal, other.inner.journal.take())
+        {
+            for entry in merged.entries {
+                journal.entries.push(match entry {
+                    JournalEntry::RevertTo(position) => {
+                        JournalEntry::RevertTo(n_checkpoints + position)
+                    }
+                    JournalEntry::Release(position) => {
+                        JournalEntry::Release(n_checkpoints + position)
+                    }
+                    entry => entry,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Takes a checkpoint of the session, returning its ID.
+    ///
+    /// All changes made to the session after a checkpoint - by calls,
+    /// deployments, and merges alike - can be undone using [`revert_to`],
+    /// allowing the host to drop a group of calls that should only go through
+    /// together. Checkpoints nest, and the changes made after one are kept
+    /// until it is [`release`]d or the session is committed.
+    ///
+    /// [`revert_to`]: Session::revert_to
+    /// [`release`]: Session::release
+    pub fn checkpoint(&mut self) -> Result<CheckpointId, Error> {
+        let contracts = self
+            .inner
+            .contract_session
+            .snap_contracts()
+            .map_err(|err| PersistenceError(Arc::new(err)))?;
+
+        let id = CheckpointId(self.inner.next_checkpoint);
+        self.inner.next_checkpoint += 1;
+
+        self.inner.checkpoints.push(Checkpoint {
+            id,
+            contracts,
+            n_host_events: self.inner.host_events.len(),
+            observers: self.inner.observers.clone(),
+            balances: self.inner.balances.clone(),
+        });
+
+        let entry = self.journal_entry(|| JournalEntry::Checkpoint);
+        self.record(entry);
+
+        Ok(id)
+    }
+
+    /// Undoes all changes made to the session since the given checkpoint was
+    /// taken, discarding it along with any checkpoint taken after it.
+    ///
+    /// # Errors
+    /// If the checkpoint was already reverted or released, or was not taken in
+    /// this session, [`Error::UnknownCheckpoint`] is returned.
+    pub fn revert_to(&mut self, checkpoint: CheckpointId) -> Result<(), Error> {
+        let checkpoints = self.take_checkpoints(checkpoint)?;
+
+        let position = self.inner.checkpoints.len() as u32;
+        let entry = self.journal_entry(|| JournalEntry::RevertTo(position));
+        self.record(entry);
+
+        for checkpoint in checkpoints {
+            self.inner
+                .contract_session
+                .revert_contracts(checkpoint.contracts)
+                .map_err(|err| PersistenceError(Arc::new(err)))?;
+            self.inner.host_events.truncate(checkpoint.n_host_events);
+            self.inner.observers = checkpoint.observers;
+            self.inner.balances = checkpoint.balances;
+            self.record_all_changed();
+        }
+        Ok(())
+    }
+
+    /// Keeps all changes made to the session since the given checkpoint was
+    /// taken, discarding it along with any checkpoint taken after it.
+    ///
+    /// # Errors
+    /// If the checkpoint was already reverted or released, or was not taken in
+    /// this session, [`Error::UnknownCheckpoint`] is returned.
+    pub fn release(&mut self, checkpoint: CheckpointId) -> Result<(), Error> {
+        let checkpoints = self.take_checkpoints(checkpoint)?;
+
+        let position = self.inner.checkpoints.len() as u32;
+        let entry = self.journal_entry(|| JournalEntry::Release(position));
+        self.record(entry);
+
+        for checkpoint in checkpoints {
+            self.inner
+                .contract_session
+                .apply_contracts(checkpoint.contracts)
+                .map_err(|err| PersistenceError(Arc::new(err)))?;
+        }
+        Ok(())
+    }
+
+    /// Removes the given checkpoint and all checkpoints taken after it,
+    /// returning them from newest to oldest.
+    fn take_checkpoints(
+        &mut self,
+        checkpoint: CheckpointId,
+    ) -> Result<Vec<Checkpoint>, Error> {
+        let index = self
+            .inner
+            .checkpoints
+            .iter()
+            .position(|c| c.id == checkpoint)
+            .ok_or(Error::UnknownCheckpoint(checkpoint))?;
+
+        let mut taken = self.inner.checkpoints.split_off(index);
+        taken.reverse();
+        Ok(taken)
+    }
+
+    fn clear_stack_and_instances(&mut self) {
+        self.inner.call_tree.clear();
+        self.inner.instances.clear();
+    }
+
+    /// Return the state root of the current state of the session.
+    ///
+    /// The state root is the root of a merkle tree whose leaves are the hashes
+    /// of the state of of each contract, ordered by their contract ID.
+    ///
+    /// It also doubles as the ID of a commit - the commit root.
+    ///
+    /// The tree is kept between calls, with only the pages written since the
+    /// last call hashed again, so that computing the root after each call
+    /// costs in proportion to what the call wrote rather than the whole state.
+    pub fn root(&self) -> Root {
+        self.inner.contract_session.root().into()
+    }
+
+    /// Returns the journal kept by the session, if it keeps one.
+    ///
+    /// See [`SessionDataBuilder::journal`] for more details.
+    pub fn journal(&self) -> Option<&Journal> {
+        self.inner.journal.as_ref()
+    }
+
+    /// Takes the journal kept by the session, sealing it with the current
+    /// state root, so that replaying it checks the root reached. The session
+    /// stops keeping a journal afterwards.
+    ///
+    /// Returns `None` if the session doesn't keep a journal.
+    pub fn take_journal(&mut self) -> Option<Journal> {
+        let root = self.root();
+        self.inner.journal.take().map(|mut journal| {
+            journal.root = Some(root);
+            journal
+        })
+    }
+
+    /// Re-executes the operations recorded in the given `journal`, returning
+    /// the state root reached.
+    ///
+    /// The session should be opened on the same base, and with the same
+    /// [`SessionData`] - other than the metadata, which is set to the one the
+    /// journal was recorded with - as the session the journal was recorded
+    /// in. Each operation is re-executed regardless of whether it fails, since
+    /// failing is part of what was recorded. This allows for reproducing the
+    /// state a session reached deterministically, such as when a node
+    /// computes a divergent state root.
+    ///
+    /// Call interceptors and debuggers are not part of a journal, and calls
+    /// made with them are replayed without them. The operations replayed are
+    /// not recorded in the journal of this session, if it keeps one.
+    ///
+    /// # Errors
+    /// If the session isn't opened on the base the journal was recorded on,
+    /// [`Error::ReplayBaseMismatch`] is returned before anything is replayed.
+    /// If the journal is sealed, and the root reached differs from the one it
+    /// was sealed with, [`Error::ReplayDivergence`] is returned.
+    pub fn replay(&mut self, journal: &Journal) -> Result<Root, Error> {
+        let base = self.inner.data.base.map(Root::from);
+        if base != journal.base {
+            return Err(Error::ReplayBaseMismatch {
+                expected: journal.base,
+                found: base,
+            });
+        }
+
+        self.journaled(None, |session| {
+            for (name, value) in &journal.meta {
+                let schema = journal
+                    .schemas
+                    .iter()
+                    .find(|(schema_name, _)| schema_name == name)
+                    .map(|(_, schema)| *schema);
+                session.inner.data.set(name.clone(), value.clone(), schema);
+            }
+            session.record_all_changed();
+
+            for entry in &journal.entries {
+                // Errors are part of the execution recorded, and are
+                // therefore expected.
+                let _ = session.replay_entry(entry);
+            }
+        });
+
+        let root = self.root();
+        match journal.root {
+            Some(expected) if expected != root => {
+                Err(Error::ReplayDivergence {
+                    expected,
+                    found: root,
+                })
+            }
+            _ => Ok(root),
+        }
+    }
+
+    fn replay_entry(&mut self, entry: &JournalEntry) -> Result<(), Error> {
+        match entry {
+            JournalEntry::Deploy {
+                contract_id,
+                bytecode,
+                init_arg,
+                owner,
+                nonce,
+                owner_only,
+                pure,
+                deploy_limit,
+                init_limit,
+            } => self
+                .do_deploy(
+                    *contract_id,
+                    bytecode,
+                    init_arg.clone(),
+                    owner.clone(),
+                    *nonce,
+                    owner_only.clone(),
+                    pure.clone(),
+                    *deploy_limit,
+                    *init_limit,
+                )
+                .map(drop),
+            JournalEntry::DeployBundle(bundle) => {
+                self.do_deploy_bundle(bundle.clone()).map(drop)
+            }
+            JournalEntry::Call {
+                contract,
+                fn_name,
+                fn_arg,
+                gas_limit,
+            } => self
+                .call_raw(*contract, fn_name, fn_arg.clone(), *gas_limit)
+                .map(drop),
+            JournalEntry::CallSpilled {
+                contract,
+                fn_name,
+                input,
+                gas_limit,
+            } => self
+                .call_spilled(*contract, fn_name, &input[..], *gas_limit)
+                .map(drop),
+            JournalEntry::Upgrade {
+                contract,
+                bytecode,
+                authorization,
+                migration_fn,
+                fn_arg,
+                gas_limit,
+            } => self
+                .do_upgrade(
+                    *contract,
+                    bytecode,
+                    authorization,
+                    migration_fn,
+                    fn_arg.clone(),
+                    *gas_limit,
+                    Ok,
+                )
+                .map(drop),
+            JournalEntry::Migrate {
+                contract,
+                new_contract,
+                bytecode,
+            } => self.swap_migrated(*contract, *new_contract, bytecode),
+            JournalEntry::SetBalance { contract, balance } => {
+                self.set_balance(*contract, *balance);
+                Ok(())
+            }
+            JournalEntry::RemoveContract(contract) => {
+                self.remove_contract(*contract)
+            }
+            JournalEntry::SetMeta {
+                name,
+                value,
+                schema,
+            } => {
+                self.record_all_changed();
+                self.inner.data.set(name.clone(), value.clone(), *schema);
+                Ok(())
+            }
+            JournalEntry::RemoveMeta(name) => {
+                self.remove_meta(name.clone());
+                Ok(())
+            }
+            JournalEntry::Checkpoint => self.checkpoint().map(drop),
+            JournalEntry::RevertTo(position) => {
+                let checkpoint = self
+                    .inner
+                    .checkpoints
+                    .get(*position as usize)
+                    .map(|checkpoint| checkpoint.id);
+                match checkpoint {
+                    Some(checkpoint) => self.revert_to(checkpoint),
+                    None => Ok(()),
+                }
+            }
+            JournalEntry::Release(position) => {
+                let checkpoint = self
+                    .inner
+                    .checkpoints
+                    .get(*position as usize)
+                    .map(|checkpoint| checkpoint.id);
+                match checkpoint {
+                    Some(checkpoint) => self.release(checkpoint),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Builds a journal entry if the session keeps a journal, so that nothing
+    /// is cloned into an entry otherwise.
+    fn journal_entry(
+        &self,
+        entry: impl FnOnce() -> JournalEntry,
+    ) -> Option<JournalEntry> {
+        self.inner.journal.as_ref().map(|_| entry())
+    }
+
+    /// Records the given entry in the journal, if the session keeps one.
+    fn record(&mut self, entry: Option<JournalEntry>) {
+        if let (Some(journal), Some(entry)) = (&mut self.inner.journal, entry) {
+            journal.entries.push(entry);
+        }
+    }
+
+    /// Performs an operation with the journal set aside, so that the
+    /// operations it performs in turn are not recorded on their own, and
+    /// records the given entry for it instead.
+    fn journaled<T>(
+        &mut self,
+        entry: Option<JournalEntry>,
+        operation: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let journal = self.inner.journal.take();
+        let result = operation(self);
+        self.inner.journal = journal;
+
+        self.record(entry);
+        result
+    }
+
+    /// Like [`journaled`], but only records the entry if the operation
+    /// succeeds, for operations leaving no trace when they fail.
+    ///
+    /// [`journaled`]: Session::journaled
+    fn journaled_ok<T>(
+        &mut self,
+        entry: Option<JournalEntry>,
+        operation: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let journal = self.inner.journal.take();
+        let result = operation(self);
+        self.inner.journal = journal;
+
+        if result.is_ok() {
+            self.record(entry);
+        }
+        result
+    }
+
+    /// Returns an iterator over the pages (and their indices) of a contract's
+    /// memory, together with a proof of their inclusion in the state.
+    ///
+    /// The proof is a Merkle inclusion proof, and the caller is able to verify
+    /// it by using [`verify`], and matching the root with the one returned by
+    /// [`root`].
+    ///
+    /// [`verify`]: PageOpening::verify
+    /// [`root`]: Session::root
+    pub fn memory_pages(
+        &self,
+        contract: ContractId,
+    ) -> Option<impl Iterator<Item = (usize, &[u8], PageOpening)>> {
+        self.inner.contract_session.memory_pages(contract)
+    }
+
+    /// Returns a Merkle proof of the state of the contract's memory, to be
+    /// checked against the [`root`] using [`verify_proof`].
+    ///
+    /// Returns `None` if the contract doesn't exist.
+    ///
+    /// [`root`]: Session::root
+    /// [`verify_proof`]: crate::verify_proof
+    pub fn prove_contract_state(
+        &self,
+        contract_id: ContractId,
+    ) -> Option<MerkleProof> {
+        self.inner.contract_session.prove_contract(contract_id)
+    }
+
+    /// Returns the [`HostEvent`]s emitted by the VM during this session, in
+    /// the order they were emitted.
+    pub fn host_events(&self) -> &[HostEvent] {
+        &self.inner.host_events
+    }
+
+    /// Subscribe to the events emitted by contracts in this session,
+    /// receiving them through the returned channel as they are emitted.
+    ///
+    /// While subscribed, events are no longer gathered in the [`CallReceipt`]
+    /// of a call, allowing for large volumes of events to be processed
+    /// without buffering them in the session. Since they are sent before the
+    /// call completes, events emitted by calls that later fail are sent as
+    /// well.
+    ///
+    /// Subscribing again replaces the previous subscription. Dropping the
+    /// receiver unsubscribes.
+    pub fn subscribe_events(&mut self) -> mpsc::Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.inner.event_subscriber = Some(sender);
+        receiver
+    }
+
+    /// Stop sending events to the current subscriber, if any, gathering them
+    /// in call receipts again.
+    pub fn unsubscribe_events(&mut self) {
+        self.inner.event_subscriber = None;
+    }
+
+    pub(crate) fn push_event(&mut self, event: Event) {
+        if self.inner.observers.contains_key(&event.source) {
+            self.inner.notifications.push(event.clone());
+        }
+
+        let event = match &self.inner.event_subscriber {
+            Some(subscriber) => match subscriber.send(event) {
+                Ok(()) => return,
+                // The receiver was dropped, unsubscribing
+                Err(mpsc::SendError(event)) => {
+                    self.inner.event_subscriber = None;
+                    event
+                }
+            },
+            None => event,
+        };
+
+        self.inner.events.push(event);
+    }
+
+    pub(crate) fn push_deferred(&mut self, deferred: Deferred) {
+        self.inner.deferred.push(deferred);
+    }
+
+    pub(crate) fn deferred_len(&self) -> usize {
+        self.inner.deferred.len()
+    }
+
+    pub(crate) fn truncate_deferred(&mut self, len: usize) {
+        self.inner.deferred.truncate(len);
+    }
+
+    pub(crate) fn push_observation(&mut self, observation: Observation) {
+        self.inner.observations.push(observation);
+    }
+
+    pub(crate) fn observations_len(&self) -> usize {
+        self.inner.observations.len()
+    }
+
+    pub(crate) fn truncate_observations(&mut self, len: usize) {
+        self.inner.observations.truncate(len);
+    }
+
+    pub(crate) fn notifications_len(&self) -> usize {
+        self.inner.notifications.len()
+    }
+
+    pub(crate) fn truncate_notifications(&mut self, len: usize) {
+        self.inner.notifications.truncate(len);
+    }
+
+    /// Applies the changes to the observers made during a call that
+    /// succeeded, in the order they were made.
+    fn apply_observations(&mut self) {
+        for observation in mem::take(&mut self.inner.observations) {
+            let Observation {
+                observer,
+                observed,
+                observe,
+            } = observation;
+
+            if observe {
+                self.inner
+                    .observers
+                    .entry(observed)
+                    .or_default()
+                    .insert(observer);
+            } else if let Some(observers) =
+                self.inner.observers.get_mut(&observed)
+            {
+                observers.remove(&observer);
+                if observers.is_empty() {
+                    self.inner.observers.remove(&observed);
+                }
+            }
+        }
+    }
+
+    /// Records a log, unless it is less severe than the session's log level or
+    /// the limit of logs in the call has been reached.
+    pub(crate) fn push_log(&mut self, log: Log) {
+        if let Some(log_level) = self.inner.data.log_level {
+            if log.level > log_level {
+                return;
+            }
+        }
+        if let Some(max_logs) = self.inner.data.max_logs {
+            if self.inner.logs.len() >= max_logs {
+                return;
+            }
+        }
+        self.inner.logs.push(log);
+    }
+
+    pub(crate) fn spilled_input(&self) -> Option<&Spill> {
+        self.inner.spilled_input.as_ref()
+    }
+
+    pub(crate) fn spill_output(&mut self, data: &[u8]) -> Result<(), Error> {
+        let writer = match &mut self.inner.spilled_output {
+            Some(writer) => writer,
+            None => self.inner.spilled_output.insert(
+                SpillWriter::new()
+                    .map_err(|err| Error::SpillError(Arc::new(err)))?,
+            ),
+        };
+        if let Some(max_len) = self.inner.data.max_spilled_output {
+            let len = writer.len().saturating_add(data.len());
+            if len > max_len {
+                return Err(Error::SpillOverflow { len, max_len });
+            }
+        }
+        writer
+            .write(data)
+            .map_err(|err| Error::SpillError(Arc::new(err)))
+    }
+
+    pub(crate) fn push_feed(&mut self, data: Vec<u8>) -> Result<(), Error> {
+        if let Some(capture) = self.inner.feed_captures.last_mut() {
+            capture.push(data);
+            return Ok(());
+        }
+        match self.inner.feeder.as_ref().ok_or(Error::MissingFeed)? {
+            Feeder::Unbounded(feed) => feed.send(data),
+            Feeder::Bounded(feed, FeedPolicy::Block) => feed.send(data),
+            Feeder::Bounded(feed, FeedPolicy::Drop) => {
+                match feed.try_send(data) {
+                    Ok(()) | Err(mpsc::TrySendError::Full(_)) => Ok(()),
+                    Err(mpsc::TrySendError::Disconnected(data)) => {
+                        Err(mpsc::SendError(data))
+                    }
+                }
+            }
+        }
+        .map_err(Error::FeedPulled)
+    }
+
+    /// Starts capturing the data fed by contracts, for a feed call being made
+    /// by a contract.
+    pub(crate) fn capture_feed(&mut self) {
+        self.inner.feed_captures.push(Vec::new());
+    }
+
+    /// Stops capturing the data fed for the innermost feed call, returning the
+    /// items fed during it.
+    pub(crate) fn release_feed(&mut self) -> Vec<Vec<u8>> {
+        self.inner.feed_captures.pop().unwrap_or_default()
+    }
+
+    /// Keeps the given `items` for the `contract` to read, returning the
+    /// handle to them.
+    pub(crate) fn open_feed(
+        &mut self,
+        contract: ContractId,
+        items: Vec<Vec<u8>>,
+    ) -> usize {
+        self.inner.open_feeds.push(OpenFeed {
+            contract,
+            items: items.into(),
+        });
+        self.inner.open_feeds.len() - 1
+    }
+
+    /// Takes the next item of the feed with the given `handle`, if it was
+    /// opened by the given `contract` and has items left.
+    pub(crate) fn next_fed(
+        &mut self,
+        contract: ContractId,
+        handle: usize,
+    ) -> Option<Vec<u8>> {
+        self.inner
+            .open_feeds
+            .get_mut(handle)
+            .filter(|feed| feed.contract == contract)
+            .and_then(|feed| feed.items.pop_front())
+    }
 
     fn new_instance(
         &mut self,
@@ -624,6 +2877,7 @@ impl Session {
         )?;
 
         self.inner.current = contract_id;
+        self.inner.instance_memories = 0;
 
         let instance = WrappedInstance::new(
             self.clone(),
@@ -635,8 +2889,8 @@ impl Session {
         Ok(instance)
     }
 
-    pub(crate) fn host_query(&self, name: &str) -> Option<&dyn HostQuery> {
-        self.inner.host_queries.get(name)
+    pub(crate) fn host_query(&self, name: &str) -> Option<Arc<dyn HostQuery>> {
+        self.inner.host_queries.get_shared(name)
     }
 
     pub(crate) fn nth_from_top(&self, n: usize) -> Option<CallTreeElem> {
@@ -647,6 +2901,274 @@ impl Session {
         self.inner.call_tree.call_ids()
     }
 
+    pub(crate) fn call_frames(&self) -> Vec<(&ContractId, &str, u64)> {
+        self.inner.call_tree.call_frames()
+    }
+
+    /// Returns where the last call made in this session ran out of gas, if it
+    /// failed with [`Error::OutOfGas`].
+    ///
+    /// Calls that succeed report any inter-contract call running out of gas
+    /// in [`CallReceipt::out_of_gas`] instead.
+    pub fn out_of_gas_trace(&self) -> Option<&OutOfGasTrace> {
+        self.inner.out_of_gas.as_ref()
+    }
+
+    /// Records the frames of the call stack as it ran out of gas, replacing
+    /// any previously recorded.
+    pub(crate) fn record_out_of_gas(&mut self) {
+        let inner = &mut *self.inner;
+        let instances = &mut inner.instances;
+
+        let frames = inner
+            .call_tree
+            .call_frames()
+            .into_iter()
+            .map(|(contract_id, fn_name, limit)| {
+                let remaining = instances
+                    .get_mut(contract_id)
+                    .map_or(0, |instance| instance.get_remaining_gas());
+                OutOfGasFrame {
+                    contract_id: *contract_id,
+                    fn_name: fn_name.to_owned(),
+                    limit,
+                    spent: limit.saturating_sub(remaining),
+                }
+            })
+            .collect();
+
+        self.inner.out_of_gas = Some(OutOfGasTrace { frames });
+    }
+
+    /// Takes the gas spent by the last call that failed, if it was not
+    /// already taken.
+    pub(crate) fn take_failed_spent(&mut self) -> u64 {
+        mem::take(&mut self.inner.failed_spent)
+    }
+
+    /// Returns the [`GasSchedule`] used by the session.
+    pub fn gas_schedule(&self) -> &GasSchedule {
+        &self.inner.data.gas_schedule
+    }
+
+    /// Returns a description of everything affecting the determinism of
+    /// execution in this session.
+    pub fn environment(&self) -> Environment {
+        Environment::new(
+            &self.engine,
+            &self.inner.data.gas_schedule,
+            &self.inner.instruction_costs,
+            &self.inner.host_queries,
+            self.inner.validation,
+            self.inner.contract_session.elide_zero_pages(),
+        )
+    }
+
+    /// Registers a [host `query`] with the given `name` in this session only,
+    /// replacing any registered under the same name with the VM.
+    ///
+    /// Other sessions - including those spawned afterwards - are unaffected,
+    /// allowing for instance simulation sessions to mock queries used by
+    /// contracts, while sessions running in consensus use the real ones.
+    ///
+    /// Queries registered in a session are not recorded in its [`Journal`],
+    /// and only their names are part of its [`environment`].
+    ///
+    /// [host `query`]: HostQuery
+    /// [`environment`]: Session::environment
+    pub fn register_host_query<Q, S>(&mut self, name: S, query: Q)
+    where
+        Q: 'static + HostQuery,
+        S: Into<Cow<'static, str>>,
+    {
+        self.inner.pure_results.clear();
+        self.inner.host_queries.insert(name, query);
+    }
+
+    /// Registers a [host `query`] with the given `name` in this session only,
+    /// charging the contract the gas given by `price` for the length of the
+    /// argument.
+    ///
+    /// See [`register_host_query`] and [`VM::register_priced_host_query`] for
+    /// more details.
+    ///
+    /// [host `query`]: HostQuery
+    /// [`register_host_query`]: Session::register_host_query
+    /// [`VM::register_priced_host_query`]: crate::VM::register_priced_host_query
+    pub fn register_priced_host_query<Q, S, P>(
+        &mut self,
+        name: S,
+        price: P,
+        query: Q,
+    ) where
+        Q: 'static + HostQuery,
+        S: Into<Cow<'static, str>>,
+        P: 'static + Send + Sync + Fn(usize) -> u64,
+    {
+        self.inner.pure_results.clear();
+        self.inner.host_queries.insert_priced(name, price, query);
+    }
+
+    /// Registers a host query with the given `name` in this session only,
+    /// taking an argument of type `A` and returning a value of type `R`.
+    ///
+    /// See [`register_host_query`] and [`VM::register_typed_host_query`] for
+    /// more details.
+    ///
+    /// [`register_host_query`]: Session::register_host_query
+    /// [`VM::register_typed_host_query`]: crate::VM::register_typed_host_query
+    pub fn register_typed_host_query<A, R, S, C, H>(
+        &mut self,
+        name: S,
+        cost: C,
+        handler: H,
+    ) where
+        A: 'static + Archive,
+        A::Archived: Deserialize<A, Infallible>
+            + for<'b> CheckBytes<DefaultValidator<'b>>,
+        R: 'static + for<'a> Serialize<StandardBufSerializer<'a>>,
+        S: Into<Cow<'static, str>>,
+        C: 'static + Send + Sync + Fn(&A) -> u64,
+        H: 'static + Send + Sync + Fn(A) -> R,
+    {
+        self.inner.pure_results.clear();
+        self.inner.host_queries.insert_typed(name, cost, handler);
+    }
+
+    /// Removes the host query with the given `name` from this session only,
+    /// returning whether there was one.
+    ///
+    /// Contracts calling the query afterwards fail with
+    /// [`Error::MissingHostQuery`].
+    pub fn remove_host_query(&mut self, name: &str) -> bool {
+        self.inner.pure_results.clear();
+        self.inner.host_queries.remove(name)
+    }
+
+    /// Returns the names of the host queries available in this session, in
+    /// order, together with their signatures if they were registered with
+    /// one.
+    pub fn host_queries(
+        &self,
+    ) -> impl Iterator<Item = (&str, Option<HostQuerySignature>)> {
+        self.inner.host_queries.iter()
+    }
+
+    /// Errors if calling the given `callee` would instantiate more contracts
+    /// than the session allows in a single call.
+    pub(crate) fn check_instance_limit(
+        &self,
+        callee: &ContractId,
+    ) -> Result<(), Error> {
+        if let Some(max_instances) = self.inner.data.max_instances {
+            if !self.inner.instances.contains(callee)
+                && self.inner.instances.len() >= max_instances
+            {
+                return Err(Error::TooManyInstances(max_instances));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the interceptor invoked around every contract call made in this
+    /// session, including calls between contracts, returning the one
+    /// previously set.
+    ///
+    /// See [`CallInterceptor`] for more details.
+    pub fn set_call_interceptor(
+        &mut self,
+        interceptor: Box<dyn CallInterceptor>,
+    ) -> Option<Box<dyn CallInterceptor>> {
+        self.inner.interceptor.set(Some(interceptor))
+    }
+
+    /// Removes the interceptor set on this session, returning it.
+    pub fn remove_call_interceptor(
+        &mut self,
+    ) -> Option<Box<dyn CallInterceptor>> {
+        self.inner.interceptor.set(None)
+    }
+
+    /// Passes the given `call` to the interceptor before it is made, erroring
+    /// if the interceptor denies it.
+    pub(crate) fn intercept_before(
+        &mut self,
+        call: &InterceptedCall,
+    ) -> Result<(), Error> {
+        self.inner
+            .interceptor
+            .before_call(call)
+            .map_err(Error::CallDenied)
+    }
+
+    /// Passes the `outcome` of the given `call` to the interceptor.
+    pub(crate) fn intercept_after(
+        &mut self,
+        call: &InterceptedCall,
+        outcome: CallOutcome,
+    ) {
+        self.inner.interceptor.after_call(call, outcome);
+    }
+
+    /// Returns the depth of the contract currently at the top of the stack,
+    /// with the contract called by the host being at a depth of one.
+    pub(crate) fn call_depth(&self) -> usize {
+        self.inner.call_tree.call_ids().len()
+    }
+
+    /// Returns the maximum depth of the call stack.
+    pub(crate) fn max_call_depth(&self) -> usize {
+        self.inner.data.max_call_depth
+    }
+
+    /// Returns the maximum number of pages a memory may grow to, if the
+    /// session sets one.
+    pub(crate) fn max_memory_pages(&self) -> Option<usize> {
+        self.inner.data.max_memory_pages
+    }
+
+    /// Returns the time each call made by the host may run for, if the
+    /// session sets one.
+    pub(crate) fn call_timeout(&self) -> Option<Duration> {
+        self.inner.data.call_timeout
+    }
+
+    /// Returns whether the call being made timed out, and its instances
+    /// should be interrupted.
+    pub(crate) fn is_interrupted(&self) -> bool {
+        self.inner.interrupted.load(Ordering::Acquire)
+    }
+
+    /// Errors if calling another contract from the one currently at the top
+    /// of the stack would nest deeper than the session allows, or than the
+    /// native stack left fits.
+    pub(crate) fn check_call_depth(&self) -> Result<(), Error> {
+        let max_call_depth = self.inner.data.max_call_depth;
+        let depth = self.inner.call_tree.call_ids().len();
+        if depth >= max_call_depth {
+            return Err(Error::CallDepthExceeded(max_call_depth));
+        }
+        if !call_thread::has_stack_for_call() {
+            return Err(Error::CallDepthExceeded(depth));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the surcharge for calling the given `callee` from the contract
+    /// currently at the top of the stack.
+    pub(crate) fn call_surcharge(&self, callee: &ContractId) -> u64 {
+        let depth = self.inner.call_tree.call_ids().len();
+
+        let mut instantiated = self.inner.instances.len();
+        if !self.inner.instances.contains(callee) {
+            instantiated += 1;
+        }
+
+        self.gas_schedule().call_surcharge(depth, instantiated)
+    }
+
     /// Creates a new instance of the given contract, returning its memory
     /// length.
     fn create_instance(
@@ -654,14 +3176,13 @@ impl Session {
         contract: ContractId,
     ) -> Result<usize, Error> {
         let instance = self.new_instance(contract)?;
-        if self.inner.instances.get(&contract).is_some() {
-            panic!("Contract already in the stack: {contract:?}");
-        }
-
         let mem_len = instance.mem_len();
 
-        let instance = Box::new(instance);
-        let instance = Box::leak(instance) as *mut WrappedInstance;
+        let arg_buf_ofs = instance.arg_buffer_offset();
+        let arg_buf = arg_buf_ofs..arg_buf_ofs + instance.arg_buffer_len();
+        self.inner
+            .contract_session
+            .record_arg_buf(contract, arg_buf);
 
         self.inner.instances.insert(contract, instance);
         Ok(mem_len)
@@ -670,27 +3191,39 @@ impl Session {
     pub(crate) fn push_callstack(
         &mut self,
         contract_id: ContractId,
+        fn_name: String,
         limit: u64,
     ) -> Result<CallTreeElem, Error> {
-        let instance = self.instance(&contract_id);
+        if self.inner.removals.contains(&contract_id) {
+            return Err(Error::ContractDoesNotExist(contract_id));
+        }
+        self.check_owner_only(contract_id, &fn_name)?;
 
-        match instance {
-            Some(instance) => {
-                self.inner.call_tree.push(CallTreeElem {
-                    contract_id,
-                    limit,
-                    spent: 0,
-                    mem_len: instance.mem_len(),
-                });
+        let mem_len = self.instance(&contract_id).map(|i| i.mem_len());
+
+        match mem_len {
+            Some(mem_len) => {
+                self.inner.call_tree.push(
+                    CallTreeElem {
+                        contract_id,
+                        limit,
+                        spent: 0,
+                        mem_len,
+                    },
+                    fn_name,
+                );
             }
             None => {
                 let mem_len = self.create_instance(contract_id)?;
-                self.inner.call_tree.push(CallTreeElem {
-                    contract_id,
-                    limit,
-                    spent: 0,
-                    mem_len,
-                });
+                self.inner.call_tree.push(
+                    CallTreeElem {
+                        contract_id,
+                        limit,
+                        spent: 0,
+                        mem_len,
+                    },
+                    fn_name,
+                );
             }
         }
 
@@ -701,6 +3234,42 @@ impl Session {
             .expect("We just pushed an element to the stack"))
     }
 
+    /// Errors if the given function of the contract may only be called by its
+    /// owner, and the sender of the call isn't the owner.
+    ///
+    /// The sender is the contract at the top of the stack, or the one
+    /// declared in the [`SENDER_META`] metadata item for calls made by the
+    /// host.
+    fn check_owner_only(
+        &mut self,
+        contract_id: ContractId,
+        fn_name: &str,
+    ) -> Result<(), Error> {
+        let Some(contract) = self
+            .inner
+            .contract_session
+            .contract(contract_id)
+            .map_err(|err| PersistenceError(Arc::new(err)))?
+        else {
+            return Ok(());
+        };
+
+        let metadata = contract.metadata.data();
+        if !metadata.owner_only.iter().any(|name| name == fn_name) {
+            return Ok(());
+        }
+
+        let sender = match self.nth_from_top(0) {
+            Some(caller) => Some(caller.contract_id.as_bytes().to_vec()),
+            None => self.meta(SENDER_META),
+        };
+        if sender.as_deref() != Some(metadata.owner.as_slice()) {
+            return Err(Error::UnauthorizedCall(contract_id, fn_name.into()));
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn move_up_call_tree(&mut self, spent: u64) {
         self.inner.call_tree.move_up(spent);
     }
@@ -710,9 +3279,11 @@ impl Session {
     }
 
     pub(crate) fn revert_callstack(&mut self) -> Result<(), std::io::Error> {
-        for elem in self.inner.call_tree.iter() {
-            let instance = self
-                .instance(&elem.contract_id)
+        let inner = &mut *self.inner;
+        for elem in inner.call_tree.iter() {
+            let instance = inner
+                .instances
+                .get_mut(&elem.contract_id)
                 .expect("instance should exist");
             instance.revert()?;
             instance.set_len(elem.mem_len);
@@ -723,12 +3294,99 @@ impl Session {
 
     /// Commits the given session to disk, consuming the session and returning
     /// its state root.
-    pub fn commit(self) -> Result<[u8; 32], Error> {
+    pub fn commit(self) -> Result<Root, Error> {
         self.inner
             .contract_session
             .commit()
             .map(Into::into)
-            .map_err(|err| PersistenceError(Arc::new(err)))
+            .map_err(Error::from_store)
+    }
+
+    /// Commits only the changes made to the given `contracts` in the session,
+    /// consuming the session and returning the new state root.
+    ///
+    /// Every other contract is left as it is in the base commit - including
+    /// contracts deployed in the session, which are not part of the commit at
+    /// all. This allows for using contracts as scratch space, without
+    /// persisting their state.
+    ///
+    /// # Errors
+    /// If any of the given `contracts` is neither in the base commit nor
+    /// deployed in the session, or if committing fails.
+    pub fn commit_partial(
+        self,
+        contracts: &[ContractId],
+    ) -> Result<Root, Error> {
+        for contract in contracts {
+            let contract_session = &mut self.inner.contract_session;
+            if !contract_session.contract_deployed(*contract)
+                && !contract_session.is_destroyed(contract)
+            {
+                return Err(Error::ContractDoesNotExist(*contract));
+            }
+        }
+
+        self.inner
+            .contract_session
+            .commit_partial(contracts)
+            .map(Into::into)
+            .map_err(Error::from_store)
+    }
+
+    /// Commit the current state of the session to the VM, without blocking the
+    /// current thread while the commit is written.
+    ///
+    /// The state is handed off immediately, and the returned future resolves
+    /// to the root of the commit once it is written.
+    pub fn commit_async(self) -> impl Future<Output = Result<Root, Error>> {
+        let reply = self.inner.contract_session.commit_async();
+
+        async move {
+            let result = reply.await;
+
+            // The session holds its base commit until the commit is written.
+            drop(self);
+
+            result.map(Into::into).map_err(Error::from_store)
+        }
+    }
+
+    /// Returns true if a debugger is set for the call being made.
+    #[cfg(feature = "debug")]
+    pub(crate) fn is_debugging(&self) -> bool {
+        self.inner.debugger.is_set()
+    }
+
+    /// Calls back the debugger set for the call being made, if any, with the
+    /// contract function at the top of the stack.
+    #[cfg(feature = "debug")]
+    pub(crate) fn debug_event(&mut self, event: DebugEvent) {
+        if !self.is_debugging() {
+            return;
+        }
+
+        let depth = self.call_depth();
+        let (contract, fn_name, gas_limit) = match self.call_frames().first() {
+            Some((contract, fn_name, limit)) => {
+                (**contract, fn_name.to_string(), *limit)
+            }
+            None => return,
+        };
+        let inner = &mut *self.inner;
+        let instance = inner
+            .instances
+            .get_mut(&contract)
+            .expect("instance on the stack should exist");
+
+        let frame = DebugFrame {
+            contract,
+            fn_name: &fn_name,
+            depth,
+            gas_limit,
+            gas_remaining: instance.get_remaining_gas(),
+            instance,
+        };
+        inner.debugger.notify(&frame, event);
     }
 
     #[cfg(feature = "debug")]
@@ -761,7 +3419,49 @@ impl Session {
         V: for<'a> Serialize<StandardBufSerializer<'a>>,
     {
         let data = Self::serialize_data(&value)?;
-        Ok(self.inner.data.set(name, data))
+        Ok(self.set_meta_data(name.into(), data, None))
+    }
+
+    /// Set the value of a typed metadata item, under its name and together
+    /// with the hash of its schema.
+    ///
+    /// Contracts reading the item using uplink's `meta` fail the call should
+    /// they expect a different schema. See [`SessionMeta`] for more details.
+    ///
+    /// Returns the previous value of the metadata item.
+    pub fn set_meta_typed<M>(
+        &mut self,
+        value: M,
+    ) -> Result<Option<Vec<u8>>, Error>
+    where
+        M: SessionMeta,
+    {
+        let data = Self::serialize_data(&value)?;
+        Ok(self.set_meta_data(M::NAME.into(), data, Some(M::SCHEMA_HASH)))
+    }
+
+    /// Returns the hash of the schema a metadata item was set with, if it was
+    /// set as a typed item.
+    pub fn meta_schema(&self, name: &str) -> Option<u64> {
+        self.inner.data.get_schema(name)
+    }
+
+    fn set_meta_data(
+        &mut self,
+        name: Cow<'static, str>,
+        data: Vec<u8>,
+        schema: Option<u64>,
+    ) -> Option<Vec<u8>> {
+        self.record_all_changed();
+
+        let entry = self.journal_entry(|| JournalEntry::SetMeta {
+            name: name.to_string(),
+            value: data.clone(),
+            schema,
+        });
+        self.record(entry);
+
+        self.inner.data.set(name, data, schema)
     }
 
     /// Remove a metadata item.
@@ -771,6 +3471,13 @@ impl Session {
     where
         S: Into<Cow<'static, str>>,
     {
+        let name: Cow<'static, str> = name.into();
+        self.record_all_changed();
+
+        let entry =
+            self.journal_entry(|| JournalEntry::RemoveMeta(name.to_string()));
+        self.record(entry);
+
         self.inner.data.remove(name)
     }
 
@@ -793,14 +3500,105 @@ impl Session {
         Ok(buf[..pos].to_vec())
     }
 
+    /// Calls the given function of a contract's instance on the session's call
+    /// thread, spawning the thread if it isn't running yet.
+    ///
+    /// Nested calls are made on the same thread, so a chain of calls is bound
+    /// by the depth the session allows, rather than by the stack of the thread
+    /// calling the session.
+    fn call_on_thread(
+        &mut self,
+        contract: ContractId,
+        fname: &str,
+        arg_len: u32,
+        limit: u64,
+    ) -> Result<i32, Error> {
+        if self.inner.call_thread.is_none() {
+            let thread = CallThread::spawn(self.max_call_depth())
+                .map_err(|err| Error::CallThreadFailure(Arc::new(err)))?;
+            self.inner.call_thread = Some(thread);
+        }
+
+        let mut session = self.clone();
+        let fname = fname.to_owned();
+        #[cfg(feature = "spans")]
+        let span = tracing::Span::current();
+
+        // The engine is shared with other sessions, so moving its epoch
+        // forward has every instance check whether it should be interrupted,
+        // and only those of this session are.
+        self.inner.interrupted.store(false, Ordering::Release);
+        let interrupted = &self.inner.interrupted;
+        let engine = &self.engine;
+        let on_timeout = || {
+            interrupted.store(true, Ordering::Release);
+            engine.increment_epoch();
+        };
+
+        self.inner
+            .call_thread
+            .as_ref()
+            .expect("The call thread was just spawned")
+            .run(
+                move || {
+                    #[cfg(feature = "spans")]
+                    let _span = span.entered();
+
+                    session
+                        .instance(&contract)
+                        .expect("instance should exist")
+                        .call(&fname, arg_len, limit)
+                },
+                self.call_timeout(),
+                on_timeout,
+            )
+    }
+
+    #[allow(clippy::type_complexity)]
     fn call_inner(
         &mut self,
         contract: ContractId,
         fname: &str,
         fdata: Vec<u8>,
         limit: u64,
-    ) -> Result<(Vec<u8>, u64, CallTree), Error> {
-        let stack_element = self.push_callstack(contract, limit)?;
+    ) -> Result<
+        (
+            Vec<u8>,
+            u64,
+            CallTree,
+            Vec<MemoryGrowth>,
+            Vec<HeapPeak>,
+            PageStats,
+        ),
+        Error,
+    > {
+        self.inner.out_of_gas = None;
+        self.inner.deferred.clear();
+        self.inner.observations.clear();
+        self.inner.transfers.clear();
+        self.inner.removals.clear();
+        self.inner.open_feeds.clear();
+        self.inner.failed_spent = 0;
+
+        let call = InterceptedCall {
+            contract,
+            fn_name: fname,
+            arg_len: fdata.len(),
+            gas_limit: limit,
+            depth: 0,
+        };
+        self.intercept_before(&call)?;
+
+        let stack_element = self
+            .push_callstack(contract, fname.to_owned(), limit)
+            .map_err(|err| {
+                let outcome = CallOutcome {
+                    gas_spent: 0,
+                    success: false,
+                };
+                self.intercept_after(&call, outcome);
+                err
+            })?;
         let instance = self
             .instance(&stack_element.contract_id)
             .expect("instance should exist");
@@ -811,11 +3609,30 @@ impl Session {
                 reason: None,
                 io: Arc::new(err),
             })?;
+        instance.reset_heap_peak();
 
+        // The instance is borrowed again only once the call thread is done
+        // with it.
         let arg_len = instance.write_bytes_to_arg_buffer(&fdata)?;
-        let ret_len = instance
-            .call(fname, arg_len, limit)
+        let ret_len = self
+            .call_on_thread(contract, fname, arg_len, limit)
+            .map_err(Error::normalize)
             .map_err(|err| {
+                let remaining = self
+                    .instance(&contract)
+                    .expect("instance should exist")
+                    .get_remaining_gas();
+                self.inner.failed_spent = limit.saturating_sub(remaining);
+                let outcome = CallOutcome {
+                    gas_spent: self.inner.failed_spent,
+                    success: false,
+                };
+                self.intercept_after(&call, outcome);
+                if let Error::OutOfGas = err {
+                    self.record_out_of_gas();
+                }
+                self.revert_transfers(0);
+                self.inner.removals.clear();
                 if let Err(io_err) = self.revert_callstack() {
                     return Error::MemorySnapshotFailure {
                         reason: Some(Arc::new(err)),
@@ -825,38 +3642,182 @@ impl Session {
                 self.move_up_prune_call_tree();
                 self.clear_stack_and_instances();
                 err
-            })
-            .map_err(Error::normalize)?;
+            })?;
+
+        let instance = self.instance(&contract).expect("instance should exist");
         let ret = instance.read_bytes_from_arg_buffer(ret_len as u32);
 
         let spent = limit - instance.get_remaining_gas();
+        let outcome = CallOutcome {
+            gas_spent: spent,
+            success: true,
+        };
+        self.intercept_after(&call, outcome);
 
-        for elem in self.inner.call_tree.iter() {
-            let instance = self
-                .instance(&elem.contract_id)
+        // A contract may appear in the call tree more than once, with the
+        // length of its memory when each call started. Memories never shrink,
+        // so the smallest is the length before the whole call.
+        //
+        // Each appearance also took a snapshot of the contract's memory, so
+        // the pages it touched are gathered from each snapshot before it is
+        // applied.
+        //
+        // The pages freed are counted against the oldest snapshot of each
+        // contract, which is the last to be applied.
+        //
+        // The heap peaks are read before any snapshot is applied, since
+        // applying one protects the memory until it is accessed again.
+        let mut heap_peaks = BTreeMap::new();
+        let mut lens_before = BTreeMap::new();
+        let mut pages_touched = BTreeMap::new();
+        let mut pages_freed = BTreeMap::new();
+        let inner = &mut *self.inner;
+        for elem in inner.call_tree.iter() {
+            heap_peaks.entry(elem.contract_id).or_insert_with(|| {
+                inner
+                    .instances
+                    .get_mut(&elem.contract_id)
+                    .expect("instance should exist")
+                    .heap_peak()
+            });
+        }
+        let heap_peaks = heap_peaks
+            .into_iter()
+            .filter_map(|(contract, peak)| {
+                peak.map(|peak| HeapPeak { contract, peak })
+            })
+            .collect();
+
+        for elem in inner.call_tree.iter() {
+            let instance = inner
+                .instances
+                .get_mut(&elem.contract_id)
                 .expect("instance should exist");
+
+            let (read, written): &mut (BTreeSet<_>, BTreeSet<_>) =
+                pages_touched.entry(elem.contract_id).or_default();
+            read.extend(instance.hit_pages());
+            written.extend(instance.dirty_pages());
+            pages_freed.insert(elem.contract_id, instance.freed_pages());
+
             instance
                 .apply()
                 .map_err(|err| Error::MemorySnapshotFailure {
                     reason: None,
                     io: Arc::new(err),
                 })?;
+
+            lens_before
+                .entry(elem.contract_id)
+                .and_modify(|len: &mut usize| *len = (*len).min(elem.mem_len))
+                .or_insert(elem.mem_len);
         }
-        self.clear_stack_and_instances();
 
+        let memory_growth: Vec<_> = lens_before
+            .into_iter()
+            .filter_map(|(contract, old_len)| {
+                let new_len = self
+                    .instance(&contract)
+                    .expect("instance should exist")
+                    .mem_len();
+                (new_len > old_len).then_some(MemoryGrowth {
+                    contract,
+                    old_len,
+                    new_len,
+                })
+            })
+            .collect();
+
+        for (contract, (_, written)) in &pages_touched {
+            self.inner
+                .contract_session
+                .record_written(*contract, written.iter().copied());
+            if !written.is_empty()
+                && self.inner.pure_contract != Some(*contract)
+            {
+                self.record_changed(*contract);
+            }
+        }
+
+        let mut page_stats = PageStats::default();
+        for (read, written) in pages_touched.values() {
+            page_stats.pages_read += read.len();
+            page_stats.pages_written += written.len();
+        }
+        for growth in &memory_growth {
+            page_stats.bytes_grown += growth.new_len - growth.old_len;
+        }
+
+        let refund = self
+            .gas_schedule()
+            .refund(pages_freed.values().sum(), self.inner.removals.len());
+        self.inner.gas_refund = self.inner.gas_refund.saturating_add(refund);
+
+        // The call tree is taken before the stack is cleared, so that the
+        // receipt reports the contracts the call went through.
         let mut call_tree = CallTree::new();
         mem::swap(&mut self.inner.call_tree, &mut call_tree);
         call_tree.update_spent(spent);
 
-        Ok((ret, spent, call_tree))
+        self.clear_stack_and_instances();
+        self.apply_observations();
+        self.inner.transfers.clear();
+        for contract in mem::take(&mut self.inner.removals) {
+            self.destroy_contract(contract);
+        }
+
+        Ok((ret, spent, call_tree, memory_growth, heap_peaks, page_stats))
+    }
+
+    /// Hint that the given `contracts` are likely to be called soon, such as
+    /// when the host knows the upcoming transactions.
+    ///
+    /// The contracts are loaded in the background - opening their files,
+    /// deserializing their modules, and setting up their memories - so that
+    /// the I/O overlaps with the current execution. A call to a contract
+    /// picks up its prefetched state, waiting on the load if it is underway.
+    ///
+    /// This is purely an optimization, and does not affect execution.
+    /// Contracts that don't exist in the base commit are ignored.
+    pub fn hint_next_contracts(&mut self, contracts: &[ContractId]) {
+        self.inner.contract_session.prefetch(contracts);
     }
 
+    /// Returns the metadata of the contract with the given `contract_id`,
+    /// including the [`Provenance`] of its bytecode.
     pub fn contract_metadata(
         &mut self,
         contract_id: &ContractId,
     ) -> Option<&ContractMetadata> {
         self.inner.contract_session.contract_metadata(contract_id)
     }
+
+    /// Returns the functions exported by the given contract, in the order its
+    /// bytecode exports them, together with their types and mutability where
+    /// the contract declares them using uplink's `export_schema!`.
+    ///
+    /// See [`ExportInfo::from_bytecode`] for reading them from bytecode that
+    /// isn't deployed.
+    ///
+    /// This allows for wallets, explorers, and other tools to learn how to
+    /// call a contract without its interface being distributed separately.
+    ///
+    /// # Errors
+    /// If the contract does not exist, [`Error::ContractDoesNotExist`] is
+    /// returned.
+    pub fn contract_exports(
+        &mut self,
+        contract: ContractId,
+    ) -> Result<Vec<ExportInfo>, Error> {
+        let contract_data = self
+            .inner
+            .contract_session
+            .contract(contract)
+            .map_err(|err| PersistenceError(Arc::new(err)))?
+            .ok_or(Error::ContractDoesNotExist(contract))?;
+
+        Ok(ExportInfo::from_bytecode(contract_data.bytecode.as_ref()))
+    }
 }
 
 /// The receipt given for a call execution using one of either [`call`] or
@@ -866,15 +3827,51 @@ impl Session {
 /// [`call_raw`]: [`Session::call_raw`]
 #[derive(Debug)]
 pub struct CallReceipt<T> {
-    /// The amount of gas spent in the execution of the call.
+    /// The amount of gas spent in the execution of the call, including the
+    /// calls it deferred, with the refund already taken off.
     pub gas_spent: u64,
     /// The limit used in during this execution.
     pub gas_limit: u64,
+    /// The gas refunded for the pages of memory freed and the contracts
+    /// destroyed during the call, capped by the session's [`GasSchedule`].
+    ///
+    /// [`GasSchedule`]: crate::GasSchedule
+    pub gas_refunded: u64,
 
     /// The events emitted during the execution of the call.
     pub events: Vec<Event>,
+    /// The host events emitted during the execution of the call, including
+    /// the calls it deferred, such as the removal of contracts destroying
+    /// themselves.
+    pub host_events: Vec<HostEvent>,
+    /// The logs kept during the execution of the call.
+    pub logs: Vec<Log>,
+    /// The contracts whose memory grew during the execution of the call.
+    pub memory_growth: Vec<MemoryGrowth>,
+    /// The peak heap usage of the contracts built with the builtin allocator
+    /// during the execution of the call.
+    pub heap_peaks: Vec<HeapPeak>,
+    /// The pages of memory touched during the execution of the call.
+    pub page_stats: PageStats,
+    /// The last inter-contract call that ran out of gas during the execution,
+    /// and that its caller recovered from.
+    pub out_of_gas: Option<OutOfGasTrace>,
+    /// The calls deferred during the execution, in the order they were run.
+    pub deferred: Vec<DeferredCall>,
+    /// The notifications of observers of the events emitted during the
+    /// execution, in the order they were delivered.
+    pub notifications: Vec<Notification>,
     /// The call tree produced during the execution.
     pub call_tree: CallTree,
+    /// The output spilled by the contracts called, if any.
+    ///
+    /// See [`Session::call_spilled`] for more details.
+    pub spilled: Option<Spill>,
+    /// Whether the receipt was returned from the results cached for a pure
+    /// function, without executing the call.
+    ///
+    /// See [`Session::call`] for more details.
+    pub cached: bool,
 
     /// The data returned by the called contract.
     pub data: T,
@@ -895,24 +3892,215 @@ impl CallReceipt<Vec<u8>> {
         Ok(CallReceipt {
             gas_spent: self.gas_spent,
             gas_limit: self.gas_limit,
+            gas_refunded: self.gas_refunded,
             events: self.events,
+            host_events: self.host_events,
+            logs: self.logs,
+            memory_growth: self.memory_growth,
+            heap_peaks: self.heap_peaks,
+            page_stats: self.page_stats,
+            out_of_gas: self.out_of_gas,
+            deferred: self.deferred,
+            notifications: self.notifications,
             call_tree: self.call_tree,
+            spilled: self.spilled,
+            cached: self.cached,
             data,
         })
     }
 }
 
+/// The receipt given for a contract deployment using
+/// [`deploy_with_receipt`].
+///
+/// [`deploy_with_receipt`]: Session::deploy_with_receipt
+#[derive(Debug)]
+pub struct DeployReceipt {
+    /// The ID of the deployed contract.
+    pub contract_id: ContractId,
+    /// The amount of gas charged for deploying the contract's bytecode.
+    pub deploy_gas_spent: u64,
+    /// The limit used for deploying the bytecode.
+    pub deploy_gas_limit: u64,
+    /// The amount of gas spent in initializing the contract. Zero if the
+    /// contract has no `init` method.
+    pub init_gas_spent: u64,
+    /// The limit used during initialization.
+    pub init_gas_limit: u64,
+
+    /// The events emitted during initialization.
+    pub events: Vec<Event>,
+    /// The host events emitted by the deployment, starting with the
+    /// [`HostEvent::Deploy`] of the contract, followed by those emitted
+    /// during initialization.
+    pub host_events: Vec<HostEvent>,
+    /// The logs kept during initialization.
+    pub logs: Vec<Log>,
+    /// The pages of memory touched during initialization, including the
+    /// number of pages dirtied.
+    pub page_stats: PageStats,
+}
+
+/// Where a call ran out of gas.
+///
+/// The frames go from the call that ran out of gas up to the first call made,
+/// and are the same for every execution of the same call on the same state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfGasTrace {
+    pub frames: Vec<OutOfGasFrame>,
+}
+
+/// A frame of the call stack in an [`OutOfGasTrace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfGasFrame {
+    /// The contract called.
+    pub contract_id: ContractId,
+    /// The name of the function called.
+    pub fn_name: String,
+    /// The gas limit the call was given.
+    pub limit: u64,
+    /// The gas spent by the call itself, excluding the calls it was still
+    /// waiting on.
+    pub spent: u64,
+}
+
+/// The growth of a contract's memory during a call.
+///
+/// Memories are only ever grown, and the growth is kept by subsequent calls and
+/// commits, allowing hosts to price it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryGrowth {
+    /// The contract whose memory grew.
+    pub contract: ContractId,
+    /// The length of the memory before the call, in bytes.
+    pub old_len: usize,
+    /// The length of the memory after the call, in bytes.
+    pub new_len: usize,
+}
+
+/// The largest number of bytes a contract had allocated on its heap at once
+/// during a call.
+///
+/// It is only reported for contracts built with the builtin allocator of
+/// `piecrust-uplink`, through its `dlmalloc` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapPeak {
+    /// The contract whose heap usage peaked.
+    pub contract: ContractId,
+    /// The peak number of bytes allocated.
+    pub peak: usize,
+}
+
+/// A call deferred by a contract, to be run after the call it was deferred in.
+#[derive(Debug)]
+pub(crate) struct Deferred {
+    pub contract: ContractId,
+    pub fn_name: String,
+    pub arg: Vec<u8>,
+}
+
+/// A call deferred by a contract, and run after the outermost call succeeded.
+#[derive(Debug)]
+pub struct DeferredCall {
+    /// The contract called, which is also the contract that deferred the call.
+    pub contract: ContractId,
+    /// The name of the function called.
+    pub fn_name: String,
+    /// The gas spent by the call.
+    pub gas_spent: u64,
+    /// The data returned by the call, or the error it failed with.
+    pub result: Result<Vec<u8>, Error>,
+}
+
+/// A change to the observers of a contract, made by a contract during a call.
+#[derive(Debug)]
+pub(crate) struct Observation {
+    pub observer: ContractId,
+    pub observed: ContractId,
+    pub observe: bool,
+}
+
+/// A transfer between the balances of two contracts, made during a call.
+#[derive(Debug)]
+struct Transfer {
+    from: ContractId,
+    to: ContractId,
+    amount: u64,
+}
+
+/// The notification of an observer of an event, delivered after the outermost
+/// call succeeded.
+#[derive(Debug)]
+pub struct Notification {
+    /// The contract notified.
+    pub observer: ContractId,
+    /// The contract that emitted the event.
+    pub source: ContractId,
+    /// The topic of the event.
+    pub topic: String,
+    /// The gas spent by the notification.
+    pub gas_spent: u64,
+    /// The error the notification failed with, if any.
+    pub result: Result<(), Error>,
+}
+
+/// The pages of memory touched by a call, summed over all contracts it called.
+///
+/// Pages are counted once per contract, no matter how many times the contract
+/// was called. Pages touched by inter-contract calls that failed, and were
+/// therefore reverted, are not counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PageStats {
+    /// The number of pages read or written to. Pages written to are also read.
+    pub pages_read: usize,
+    /// The number of pages written to.
+    pub pages_written: usize,
+    /// The number of bytes the memories grew by.
+    pub bytes_grown: usize,
+}
+
 #[derive(Debug, Default)]
 pub struct SessionData {
     data: BTreeMap<Cow<'static, str>, Vec<u8>>,
+    // The schema hashes of the items set as typed items.
+    schemas: BTreeMap<Cow<'static, str>, u64>,
     pub base: Option<[u8; 32]>,
+    pub(crate) fallbacks: Vec<[u8; 32]>,
+    min_uplink_version: Option<Version>,
+    gas_schedule: GasSchedule,
+    max_instances: Option<usize>,
+    max_call_depth: usize,
+    max_memory_pages: Option<usize>,
+    call_timeout: Option<Duration>,
+    log_level: Option<LogLevel>,
+    max_logs: Option<usize>,
+    notification_gas_limit: u64,
+    spill_threshold: Option<usize>,
+    max_spilled_output: Option<usize>,
+    pure_cache_capacity: usize,
+    journal: bool,
 }
 
 impl SessionData {
     pub fn builder() -> SessionDataBuilder {
         SessionDataBuilder {
             data: BTreeMap::new(),
+            schemas: BTreeMap::new(),
             base: None,
+            fallbacks: Vec::new(),
+            min_uplink_version: None,
+            gas_schedule: GasSchedule::default(),
+            max_instances: None,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_memory_pages: None,
+            call_timeout: None,
+            log_level: None,
+            max_logs: None,
+            notification_gas_limit: DEFAULT_NOTIFICATION_GAS_LIMIT,
+            spill_threshold: None,
+            max_spilled_output: None,
+            pure_cache_capacity: DEFAULT_PURE_CACHE_CAPACITY,
+            journal: false,
         }
     }
 
@@ -920,18 +4108,34 @@ impl SessionData {
         self.data.get(name).cloned()
     }
 
-    fn set<S>(&mut self, name: S, data: Vec<u8>) -> Option<Vec<u8>>
+    fn get_schema(&self, name: &str) -> Option<u64> {
+        self.schemas.get(name).copied()
+    }
+
+    fn set<S>(
+        &mut self,
+        name: S,
+        data: Vec<u8>,
+        schema: Option<u64>,
+    ) -> Option<Vec<u8>>
     where
         S: Into<Cow<'static, str>>,
     {
-        self.data.insert(name.into(), data)
+        let name = name.into();
+        match schema {
+            Some(schema) => self.schemas.insert(name.clone(), schema),
+            None => self.schemas.remove(&name),
+        };
+        self.data.insert(name, data)
     }
 
     fn remove<S>(&mut self, name: S) -> Option<Vec<u8>>
     where
         S: Into<Cow<'static, str>>,
     {
-        self.data.remove(&name.into())
+        let name = name.into();
+        self.schemas.remove(&name);
+        self.data.remove(&name)
     }
 }
 
@@ -943,7 +4147,22 @@ impl From<SessionDataBuilder> for SessionData {
 
 pub struct SessionDataBuilder {
     data: BTreeMap<Cow<'static, str>, Vec<u8>>,
+    schemas: BTreeMap<Cow<'static, str>, u64>,
     base: Option<[u8; 32]>,
+    fallbacks: Vec<[u8; 32]>,
+    min_uplink_version: Option<Version>,
+    gas_schedule: GasSchedule,
+    max_instances: Option<usize>,
+    max_call_depth: usize,
+    max_memory_pages: Option<usize>,
+    call_timeout: Option<Duration>,
+    log_level: Option<LogLevel>,
+    max_logs: Option<usize>,
+    notification_gas_limit: u64,
+    spill_threshold: Option<usize>,
+    max_spilled_output: Option<usize>,
+    pure_cache_capacity: usize,
+    journal: bool,
 }
 
 impl SessionDataBuilder {
@@ -951,21 +4170,228 @@ impl SessionDataBuilder {
     where
         S: Into<Cow<'static, str>>,
         V: for<'a> Serialize<StandardBufSerializer<'a>>,
+    {
+        let name = name.into();
+        let data = Session::serialize_data(&value)?;
+        self.schemas.remove(&name);
+        self.data.insert(name, data);
+        Ok(self)
+    }
+
+    /// Insert a typed metadata item, under its name and together with the
+    /// hash of its schema.
+    ///
+    /// Contracts reading the item using uplink's `meta` fail the call should
+    /// they expect a different schema. See [`SessionMeta`] for more details.
+    pub fn insert_typed<M>(mut self, value: M) -> Result<Self, Error>
+    where
+        M: SessionMeta,
     {
         let data = Session::serialize_data(&value)?;
-        self.data.insert(name.into(), data);
+        self.schemas.insert(M::NAME.into(), M::SCHEMA_HASH);
+        self.data.insert(M::NAME.into(), data);
         Ok(self)
     }
 
-    pub fn base(mut self, base: [u8; 32]) -> Self {
-        self.base = Some(base);
+    pub fn base(mut self, base: impl Into<Root>) -> Self {
+        self.base = Some(base.into().into());
+        self
+    }
+
+    /// Read contracts that are not in the base commit from the given
+    /// `fallbacks`, looked up in order.
+    ///
+    /// New state is still written on top of the base, with contracts read
+    /// from a fallback written in full once they are written to. This allows
+    /// for contracts not used in a while to be left in older commits, such as
+    /// archival snapshots, while remaining callable.
+    pub fn fallbacks<I, R>(mut self, fallbacks: I) -> Self
+    where
+        I: IntoIterator<Item = R>,
+        R: Into<Root>,
+    {
+        self.fallbacks = fallbacks
+            .into_iter()
+            .map(|fallback| fallback.into().into())
+            .collect();
+        self
+    }
+
+    /// Reject deployments of contracts compiled against a `piecrust-uplink`
+    /// older than the given `version`.
+    ///
+    /// The version is read from the bytecode's [`Provenance`], meaning that
+    /// contracts whose custom sections have been stripped are rejected as
+    /// well.
+    pub fn min_uplink_version(mut self, version: impl Into<Version>) -> Self {
+        self.min_uplink_version = Some(version.into());
+        self
+    }
+
+    /// Set the [`GasSchedule`] used by the session.
+    pub fn gas_schedule(mut self, gas_schedule: GasSchedule) -> Self {
+        self.gas_schedule = gas_schedule;
+        self
+    }
+
+    /// Limit the number of distinct contracts a single call may instantiate,
+    /// including the contract called.
+    ///
+    /// An inter-contract call that would instantiate a contract past the limit
+    /// aborts the whole call with [`Error::TooManyInstances`]. Since the
+    /// contract called always counts, a limit of zero is treated as one.
+    pub fn max_instances(mut self, max_instances: usize) -> Self {
+        self.max_instances = Some(max_instances.max(1));
+        self
+    }
+
+    /// Limit the depth of the call stack in a single call, with the contract
+    /// called being at a depth of one.
+    ///
+    /// An inter-contract call that would nest past the limit fails with
+    /// [`ContractError::CallDepthExceeded`], which the caller may handle,
+    /// rather than leaving deep recursion to be stopped by running out of gas.
+    /// Contracts can check the depth left beforehand, using uplink's
+    /// `call_depth` and `max_call_depth`. Since the contract called is always
+    /// on the stack, a limit of zero is treated as one.
+    ///
+    /// Calls are made on a thread whose native stack is sized to fit a chain
+    /// of calls as deep as the limit, reserving around 768KiB of address
+    /// space for each call, up to 64MiB. A chain using more of the stack than
+    /// is left for another call fails the same way, at whatever depth it
+    /// reached. Defaults to [`DEFAULT_MAX_CALL_DEPTH`].
+    ///
+    /// [`ContractError::CallDepthExceeded`]: crate::ContractError::CallDepthExceeded
+    /// [`DEFAULT_MAX_CALL_DEPTH`]: crate::DEFAULT_MAX_CALL_DEPTH
+    pub fn max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.max_call_depth = max_call_depth.max(1);
+        self
+    }
+
+    /// Limit the number of WASM pages a contract's memory may grow to in a
+    /// call.
+    ///
+    /// Growing a memory past the limit aborts the whole call with
+    /// [`Error::MemoryLimitExceeded`], keeping a contract from exhausting the
+    /// memory of the host well within its gas limit. Memories already larger
+    /// than the limit are left as they are, but can't grow any further.
+    pub fn max_memory_pages(mut self, max_memory_pages: usize) -> Self {
+        self.max_memory_pages = Some(max_memory_pages);
+        self
+    }
+
+    /// Limit the wall-clock time each call made by the host may run for,
+    /// regardless of the gas it has left.
+    ///
+    /// A call running past the timeout is interrupted, together with every
+    /// call it made, and fails with [`Error::Timeout`]. This guards the host
+    /// against calls priced far below the time they take. Deferred calls,
+    /// notifications, and the initialization of a deployed contract are each
+    /// given the timeout of their own. Calls made by other sessions of the
+    /// same [`VM`] are unaffected.
+    ///
+    /// Whether a call times out depends on the machine running it, so the
+    /// host must not let it affect consensus.
+    ///
+    /// [`VM`]: crate::VM
+    pub fn call_timeout(mut self, timeout: Duration) -> Self {
+        self.call_timeout = Some(timeout);
+        self
+    }
+
+    /// Only keep logs at the given `level` or more severe, dropping the rest.
+    ///
+    /// Contracts are charged for logging regardless, so the level doesn't
+    /// affect the gas spent.
+    pub fn log_level(mut self, level: LogLevel) -> Self {
+        self.log_level = Some(level);
+        self
+    }
+
+    /// Limit the number of logs kept in a single call, dropping any logged
+    /// past the limit.
+    ///
+    /// Contracts are charged for logging regardless, so the limit doesn't
+    /// affect the gas spent.
+    pub fn max_logs(mut self, max_logs: usize) -> Self {
+        self.max_logs = Some(max_logs);
+        self
+    }
+
+    /// Limit the gas given to each notification of an observer, out of the
+    /// gas left unspent by the call the event was emitted in.
+    ///
+    /// Defaults to [`DEFAULT_NOTIFICATION_GAS_LIMIT`].
+    ///
+    /// [`DEFAULT_NOTIFICATION_GAS_LIMIT`]: crate::DEFAULT_NOTIFICATION_GAS_LIMIT
+    pub fn notification_gas_limit(mut self, limit: u64) -> Self {
+        self.notification_gas_limit = limit;
+        self
+    }
+
+    /// Spill the arguments of raw calls longer than the given number of
+    /// bytes to a temporary file, as in [`Session::call_spilled`], instead of
+    /// passing them in the argument buffer.
+    ///
+    /// By default arguments are never spilled, and calls with arguments
+    /// longer than the argument buffer fail.
+    pub fn spill_threshold(mut self, bytes: usize) -> Self {
+        self.spill_threshold = Some(bytes);
+        self
+    }
+
+    /// Limit the number of bytes contracts may spill as the output of a call.
+    /// A contract spilling past the limit has its call fail with
+    /// [`Error::SpillOverflow`].
+    ///
+    /// By default spilled output is unlimited.
+    pub fn max_spilled_output(mut self, bytes: usize) -> Self {
+        self.max_spilled_output = Some(bytes);
+        self
+    }
+
+    /// Limit the number of results of calls to pure functions the session
+    /// caches, evicting older ones to make room for new ones. A capacity of
+    /// zero turns the cache off.
+    ///
+    /// Defaults to [`DEFAULT_PURE_CACHE_CAPACITY`].
+    ///
+    /// [`DEFAULT_PURE_CACHE_CAPACITY`]: crate::DEFAULT_PURE_CACHE_CAPACITY
+    pub fn pure_cache_capacity(mut self, capacity: usize) -> Self {
+        self.pure_cache_capacity = capacity;
+        self
+    }
+
+    /// Keep a [`Journal`] of the operations performed on the session, so that
+    /// they can be re-executed on another session using [`Session::replay`].
+    ///
+    /// The journal is taken using [`Session::take_journal`].
+    ///
+    /// [`Journal`]: crate::Journal
+    pub fn journal(mut self) -> Self {
+        self.journal = true;
         self
     }
 
     fn build(&self) -> SessionData {
         SessionData {
             data: self.data.clone(),
+            schemas: self.schemas.clone(),
             base: self.base,
+            fallbacks: self.fallbacks.clone(),
+            min_uplink_version: self.min_uplink_version,
+            gas_schedule: self.gas_schedule,
+            max_instances: self.max_instances,
+            max_call_depth: self.max_call_depth,
+            max_memory_pages: self.max_memory_pages,
+            call_timeout: self.call_timeout,
+            log_level: self.log_level,
+            max_logs: self.max_logs,
+            notification_gas_limit: self.notification_gas_limit,
+            spill_threshold: self.spill_threshold,
+            max_spilled_output: self.max_spilled_output,
+            pure_cache_capacity: self.pure_cache_capacity,
+            journal: self.journal,
         }
     }
 }
@@ -0,0 +1,99 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fmt::{self, Debug, Formatter};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::ops::Deref;
+
+use memmap2::Mmap;
+
+/// Data exchanged with a contract through a temporary file, rather than
+/// through its argument buffer.
+///
+/// The file is memory mapped, and removed once the spill is dropped.
+pub struct Spill {
+    // Empty files cannot be mapped
+    mmap: Option<Mmap>,
+}
+
+impl Spill {
+    /// Spills all the data read from the given `reader` to a temporary file.
+    pub(crate) fn from_reader<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut writer = SpillWriter::new()?;
+        io::copy(&mut reader, &mut writer.writer)?;
+        writer.finish()
+    }
+
+    fn from_file(file: File) -> io::Result<Self> {
+        let mmap = match file.metadata()?.len() {
+            0 => None,
+            // SAFETY: the file is an unnamed temporary file, and therefore
+            // can't be modified by any other process.
+            _ => Some(unsafe { Mmap::map(&file)? }),
+        };
+        Ok(Self { mmap })
+    }
+}
+
+impl Deref for Spill {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        self.mmap.as_deref().unwrap_or_default()
+    }
+}
+
+impl AsRef<[u8]> for Spill {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Debug for Spill {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Spill").field("len", &self.len()).finish()
+    }
+}
+
+/// Writes data to be spilled, as it is produced.
+pub(crate) struct SpillWriter {
+    writer: BufWriter<File>,
+    len: usize,
+}
+
+impl SpillWriter {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(tempfile::tempfile()?),
+            len: 0,
+        })
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.writer.write_all(data)?;
+        self.len += data.len();
+        Ok(())
+    }
+
+    /// The number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn finish(self) -> io::Result<Spill> {
+        let file = self.writer.into_inner().map_err(|err| err.into_error())?;
+        Spill::from_file(file)
+    }
+}
+
+impl Debug for SpillWriter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpillWriter")
+            .field("len", &self.len)
+            .finish()
+    }
+}
@@ -6,11 +6,23 @@
 
 //! A library for dealing with memories in trees.
 
+mod backend;
 mod bytecode;
+mod cold;
 mod commit;
+mod diff;
+mod export;
+mod fsck;
+mod heat;
+mod index;
+mod info;
+mod layout;
+mod locks;
 mod memory;
 mod metadata;
 mod module;
+mod reply;
+mod scheduler;
 mod session;
 mod tree;
 
@@ -19,54 +31,116 @@ use std::collections::btree_map::Entry::*;
 use std::collections::btree_map::Keys;
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{Debug, Formatter};
-use std::fs::{create_dir_all, OpenOptions};
-use std::io::{BufReader, BufWriter};
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{mpsc, Arc, Mutex};
-use std::{fs, io, thread};
+use std::time::{Duration, Instant};
+use std::{fmt, fs, io, panic, thread};
 
 use dusk_wasmtime::Engine;
 use piecrust_uplink::ContractId;
-use session::ContractDataEntry;
-use tree::{Hash, NewContractIndex};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use tree::NewContractIndex;
 
+use crate::contract::denied_feature;
+use crate::gas::InstructionCosts;
 use crate::store::commit::Hulk;
+use crate::store::index::{CommitIndex, IndexedCommits};
+use crate::store::reply::{reply_channel, Replier};
+use crate::store::scheduler::Exclusive;
 use crate::store::tree::{
-    position_from_contract, BaseInfo, ContractIndexElement, ContractsMerkle,
-    TreePos,
+    contract_leaf, position_from_contract, BaseInfo, ContractIndexElement,
+    ContractsMerkle, TreePos,
 };
+pub use backend::{FsBackend, MemoryBackend, StorageBackend};
 pub use bytecode::Bytecode;
-pub use memory::{Memory, PAGE_SIZE};
+pub use diff::{CommitDiff, ContractDiff};
+pub use fsck::{FsckCheck, FsckFinding, FsckLevel, FsckReport};
+pub use heat::{ContractHeat, HeatMap, HeatSummary, PageHeat};
+pub use info::CommitInfo;
+pub use layout::{
+    check_layout, layout_spec, LayoutIssue, LayoutReport, LayoutRule,
+};
+pub use locks::{ContractLocks, HeldLocks};
+pub use memory::{Memory, MIN_TRACKING_PAGE_SIZE, PAGE_SIZE};
 pub use metadata::Metadata;
 pub use module::Module;
-pub use session::ContractSession;
-pub use tree::PageOpening;
+pub use reply::Reply;
+pub use scheduler::{Priority, Scheduler};
+pub use session::{ContractDataEntry, ContractSession, ContractsSnapshot};
+pub use tree::{verify_proof, Hash, MerkleProof, PageOpening};
 
 const BYTECODE_DIR: &str = "bytecode";
+const CODE_DIR: &str = "code";
 const MEMORY_DIR: &str = "memory";
 const LEAF_DIR: &str = "leaf";
 const BASE_FILE: &str = "base";
 const TREE_POS_FILE: &str = "tree_pos";
 const TREE_POS_OPT_FILE: &str = "tree_pos_opt";
 const ELEMENT_FILE: &str = "element";
+const REMOVED_FILE: &str = "removed";
 const OBJECTCODE_EXTENSION: &str = "a";
 const METADATA_EXTENSION: &str = "m";
 const MAIN_DIR: &str = "main";
+const HEAT_MAP_FILE: &str = "heatmap";
+const COMMIT_INDEX_FILE: &str = "commits";
+const VERSION_FILE: &str = "version";
+const INSTRUCTION_COSTS_FILE: &str = "instruction_costs";
+
+/// How often the state of the sync loop is checked while shutting down.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The most threads the contracts of a single commit are written with.
+const MAX_WRITE_THREADS: usize = 8;
+/// The number of contracts each thread writing a commit is given at the
+/// least, below which spawning it would cost more than it saves.
+const MIN_CONTRACTS_PER_WRITE_THREAD: usize = 4;
+
+/// The version of the on-disk format of the store, to be bumped whenever the
+/// layout of the files it writes changes.
+///
+/// Version 2 added the commit index, and the bytecode shared by the contracts
+/// deployed with it in the `code` directory. Stores written before that record
+/// no version, and are migrated when opened.
+///
+/// Version 3 binds the leaves of the state tree to the IDs of their contracts,
+/// changing the roots of all commits.
+pub(crate) const STORE_VERSION: u32 = 3;
 
 /// A store for all contract commits.
 pub struct ContractStore {
     sync_loop: Option<thread::JoinHandle<()>>,
+    scheduler: Scheduler,
+    heat_map: HeatMap,
+    locks: ContractLocks,
     engine: Engine,
+    min_free_space: Arc<AtomicU64>,
+    elide_zero_pages: Arc<AtomicBool>,
+    tracking_page_size: Arc<AtomicUsize>,
+    cold_dir: Mutex<Option<PathBuf>>,
+    instruction_costs: [u8; 32],
 
     call: Option<mpsc::Sender<Call>>,
     root_dir: PathBuf,
-    pub commit_store: Arc<Mutex<CommitStore>>,
+    pub(crate) commit_store: Arc<Mutex<CommitStore>>,
 }
 
 impl Debug for ContractStore {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ContractStore")
             .field("sync_loop", &self.sync_loop)
+            .field("scheduler", &self.scheduler)
+            .field("heat_map", &self.heat_map)
+            .field("locks", &self.locks)
+            .field("min_free_space", &self.min_free_space)
+            .field("elide_zero_pages", &self.elide_zero_pages)
+            .field("tracking_page_size", &self.tracking_page_size)
+            .field("cold_dir", &self.cold_dir)
+            .field("instruction_costs", &hex::encode(self.instruction_costs))
             .field("call", &self.call)
             .field("root_dir", &self.root_dir)
             .finish()
@@ -77,6 +151,23 @@ impl Debug for ContractStore {
 pub struct CommitStore {
     commits: BTreeMap<Hash, Commit>,
     main_index: NewContractIndex,
+    code: BTreeMap<(Hash, ContractId), ContractCode>,
+    index: Option<CommitIndex>,
+    // The hash of the instruction costs the finalized state was made with.
+    main_instruction_costs: Option<[u8; 32]>,
+}
+
+/// The parts of a contract loaded from a commit that never change, and can
+/// therefore be shared between all sessions spawned off of it.
+///
+/// The memory of the contract is not included, since each session needs its
+/// own copy-on-write mapping of it. The clean pages of these mappings are
+/// already shared between sessions, since they all map the same files.
+#[derive(Debug, Clone)]
+pub struct ContractCode {
+    pub bytecode: Bytecode,
+    pub module: Module,
+    pub metadata: Metadata,
 }
 
 impl CommitStore {
@@ -84,13 +175,36 @@ impl CommitStore {
         Self {
             commits: BTreeMap::new(),
             main_index: NewContractIndex::new(),
+            code: BTreeMap::new(),
+            index: None,
+            main_instruction_costs: None,
         }
     }
 
     pub fn insert_commit(&mut self, hash: Hash, commit: Commit) {
+        if let Some(index) = &mut self.index {
+            let result = index.record_insert(&hash, commit.base.as_ref());
+            self.check_index(result);
+        }
         self.commits.insert(hash, commit);
     }
 
+    /// Sets the index recording the commits inserted and removed from now on.
+    fn set_index(&mut self, index: CommitIndex) {
+        self.index = Some(index);
+    }
+
+    /// Discards the index if recording a change failed, since it would no
+    /// longer match the commits on disk.
+    fn check_index(&mut self, result: io::Result<()>) {
+        if let Err(err) = result {
+            tracing::warn!("discarding commit index: {err}");
+            if let Some(index) = self.index.take() {
+                index.discard();
+            }
+        }
+    }
+
     pub fn get_commit(&self, hash: &Hash) -> Option<&Commit> {
         self.commits.get(hash)
     }
@@ -101,6 +215,10 @@ impl CommitStore {
         contract_id: &ContractId,
     ) -> (Option<*const ContractIndexElement>, Option<Hash>) {
         match self.commits.get(hash) {
+            // A contract removed in a commit is not looked up in its bases
+            Some(commit) if commit.index.is_removed(contract_id) => {
+                (None, None)
+            }
             Some(commit) => {
                 let e = commit.index.get(contract_id);
                 (e.map(|a| a as *const ContractIndexElement), commit.base)
@@ -118,6 +236,9 @@ impl CommitStore {
         contract_id: &ContractId,
     ) -> (Option<*mut ContractIndexElement>, Option<Hash>) {
         match self.commits.get_mut(hash) {
+            Some(commit) if commit.index.is_removed(contract_id) => {
+                (None, None)
+            }
             Some(commit) => {
                 let e = commit.index.get_mut(contract_id);
                 (e.map(|a| a as *mut ContractIndexElement), commit.base)
@@ -129,6 +250,53 @@ impl CommitStore {
         }
     }
 
+    pub fn get_index_and_base(
+        &self,
+        hash: &Hash,
+    ) -> (&NewContractIndex, Option<Hash>) {
+        match self.commits.get(hash) {
+            Some(commit) => (&commit.index, commit.base),
+            None => (&self.main_index, None),
+        }
+    }
+
+    /// Returns the commits written on top of the given one, either directly
+    /// or through other commits, with each coming before its own dependents.
+    pub fn dependents(&self, hash: &Hash) -> Vec<Hash> {
+        let mut dependents = Vec::new();
+        let mut parents = vec![*hash];
+
+        while let Some(parent) = parents.pop() {
+            for (root, commit) in &self.commits {
+                if commit.base == Some(parent) {
+                    dependents.push(*root);
+                    parents.push(*root);
+                }
+            }
+        }
+
+        dependents
+    }
+
+    /// Returns the commit with the given `hash` followed by its ancestors, up
+    /// to and including `until` - or up to the oldest one if `until` is not
+    /// among them.
+    pub fn chain(&self, hash: &Hash, until: &Hash) -> Vec<Hash> {
+        let mut chain = Vec::new();
+        let mut maybe_hash = Some(*hash);
+        while let Some(hash) = maybe_hash {
+            let Some(commit) = self.commits.get(&hash) else {
+                break;
+            };
+            chain.push(hash);
+            if hash == *until {
+                break;
+            }
+            maybe_hash = commit.base;
+        }
+        chain
+    }
+
     pub fn contains_key(&self, hash: &Hash) -> bool {
         self.commits.contains_key(hash)
     }
@@ -140,6 +308,35 @@ impl CommitStore {
     pub fn remove_commit(&mut self, hash: &Hash) {
         if let Some(commit) = self.commits.remove(hash) {
             commit.index.move_into(&mut self.main_index);
+            if let Some(index) = &mut self.index {
+                let result = index.record_remove(hash);
+                self.check_index(result);
+            }
+        }
+        self.code.retain(|(root, _), _| root != hash);
+    }
+
+    /// Returns the code of the given `contract` as loaded by a session spawned
+    /// off the commit with the given `root`, if any.
+    pub fn get_code(
+        &self,
+        root: &Hash,
+        contract: &ContractId,
+    ) -> Option<&ContractCode> {
+        self.code.get(&(*root, *contract))
+    }
+
+    /// Keeps the code of the given `contract` as loaded from the commit with
+    /// the given `root`, for other sessions spawned off it to share. Nothing is
+    /// kept if the commit is not in the store.
+    pub fn insert_code(
+        &mut self,
+        root: Hash,
+        contract: ContractId,
+        code: ContractCode,
+    ) {
+        if self.commits.contains_key(&root) {
+            self.code.entry((root, contract)).or_insert(code);
         }
     }
 
@@ -150,6 +347,10 @@ impl CommitStore {
     ) {
         self.main_index.insert_contract_index(contract_id, element);
     }
+
+    pub fn remove_main_index(&mut self, contract_id: &ContractId) {
+        self.main_index.remove_contract_index(contract_id);
+    }
 }
 
 impl ContractStore {
@@ -157,7 +358,8 @@ impl ContractStore {
     ///
     /// This also starts the synchronization loop, which is used to align
     /// [`commit`]s, [`delete`]s, and [`session spawning`] to avoid deleting
-    /// commits in use by a session.
+    /// commits in use by a session. The work itself is carried out by the
+    /// store's [`Scheduler`].
     ///
     /// [`commit`]: ContractSession::commit
     /// [`delete`]: ContractStore::delete_commit
@@ -167,16 +369,39 @@ impl ContractStore {
 
         fs::create_dir_all(root_dir)?;
 
+        let scheduler = Scheduler::new();
+        let heat_map =
+            HeatMap::load(root_dir.join(HEAT_MAP_FILE), scheduler.clone());
+
         Ok(Self {
             sync_loop: None,
+            scheduler,
+            heat_map,
+            locks: ContractLocks::default(),
             engine,
+            min_free_space: Arc::new(AtomicU64::new(0)),
+            elide_zero_pages: Arc::new(AtomicBool::new(false)),
+            tracking_page_size: Arc::new(AtomicUsize::new(PAGE_SIZE)),
+            cold_dir: Mutex::new(None),
+            instruction_costs: InstructionCosts::default().hash(),
             call: None,
             root_dir: root_dir.into(),
             commit_store: Arc::new(Mutex::new(CommitStore::new())),
         })
     }
 
-    pub fn finish_new(&mut self) -> io::Result<()> {
+    /// Finishes loading the store, reading the commits in its directory and
+    /// starting the synchronization loop, with the commits it makes written
+    /// to - and deleted from - the given `backend`.
+    ///
+    /// The store reads the commits it starts with, as well as the contracts
+    /// sessions load, from its directory. Sessions can therefore only be
+    /// spawned off commits written to a backend storing them in the store's
+    /// layout, such as an [`FsBackend`] on the same directory.
+    pub fn finish_new<B>(&mut self, backend: B) -> io::Result<()>
+    where
+        B: StorageBackend + Send + 'static,
+    {
         let loop_root_dir = self.root_dir.to_path_buf();
         let (call, calls) = mpsc::channel();
         let commit_store = self.commit_store.clone();
@@ -186,37 +411,163 @@ impl ContractStore {
         tracing::trace!("after read_all_commit");
 
         let commit_store = self.commit_store.clone();
+        let scheduler = self.scheduler.clone();
+        let min_free_space = self.min_free_space.clone();
+        let instruction_costs = self.instruction_costs;
+
+        // The contracts of large commits are split between the threads of
+        // this pool to be written.
+        let write_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(MAX_WRITE_THREADS)
+            .thread_name(|index| format!("PiecrustWrite{index}"))
+            .build()
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
 
         // The thread is given a name to allow for easily identifying it while
         // debugging.
         let sync_loop = thread::Builder::new()
             .name(String::from("PiecrustSync"))
-            .spawn(|| sync_loop(loop_root_dir, commit_store, calls))?;
+            .spawn(move || {
+                sync_loop(
+                    loop_root_dir,
+                    commit_store,
+                    scheduler,
+                    min_free_space,
+                    instruction_costs,
+                    backend,
+                    write_pool,
+                    calls,
+                )
+            })?;
 
         self.sync_loop = Some(sync_loop);
         self.call = Some(call);
         Ok(())
     }
 
+    /// Stops the synchronization loop and waits for the work it handed off to
+    /// the [`Scheduler`] to finish, for at most the given `timeout`.
+    ///
+    /// The loop only stops once every session spawned off the store is
+    /// dropped. Once it does, the heat map and the directory holding the
+    /// commits are flushed to disk.
+    ///
+    /// Errors with [`io::ErrorKind::TimedOut`] if the loop or the scheduler
+    /// is not done in time, in which case they are left running in the
+    /// background. No more sessions can be spawned off the store either way.
+    pub fn shutdown(&mut self, timeout: Duration) -> io::Result<()> {
+        let deadline = Instant::now() + timeout;
+        let timed_out = || {
+            io::Error::new(io::ErrorKind::TimedOut, "Shutting down timed out")
+        };
+
+        // The loop exits once every sender is dropped, including those held
+        // by sessions.
+        self.call = None;
+
+        if let Some(sync_loop) = self.sync_loop.take() {
+            while !sync_loop.is_finished() {
+                let now = Instant::now();
+                if now >= deadline {
+                    self.sync_loop = Some(sync_loop);
+                    return Err(timed_out());
+                }
+                thread::sleep((deadline - now).min(SHUTDOWN_POLL_INTERVAL));
+            }
+            sync_loop.join().map_err(|_| {
+                io::Error::new(io::ErrorKind::Other, "Sync loop panicked")
+            })?;
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if !self.scheduler.wait_idle(remaining) {
+            return Err(timed_out());
+        }
+
+        if self.heat_map.is_enabled() {
+            self.heat_map.persist()?;
+        }
+
+        let main_dir = self.root_dir.join(MAIN_DIR);
+        if main_dir.exists() {
+            fs::File::open(main_dir)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+
     /// Create a new [`ContractSession`] with the given `base` commit.
     ///
     /// Errors if the given base commit does not exist in the store.
     pub fn session(&self, base: Hash) -> io::Result<ContractSession> {
         tracing::trace!("session creation started");
-        let base_commit_hash = self
-            .call_with_replier(|replier| Call::CommitHold { base, replier })
-            .ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    format!("No such base commit: {}", hex::encode(base)),
-                )
-            })?;
+        let reply = self
+            .call_with_replier(|replier| Call::CommitHold { base, replier });
 
-        let r = Ok(self.session_with_base(Some(base_commit_hash)));
+        let r = self.session_with_held_base(base, reply.wait());
         tracing::trace!("session creation finished");
         r
     }
 
+    /// Create a new [`ContractSession`] with the given `base` commit, without
+    /// blocking the current thread.
+    ///
+    /// See [`session`] for more details.
+    ///
+    /// [`session`]: ContractStore::session
+    pub async fn session_async(
+        &self,
+        base: Hash,
+    ) -> io::Result<ContractSession> {
+        let reply = self
+            .call_with_replier(|replier| Call::CommitHold { base, replier });
+
+        self.session_with_held_base(base, reply.await)
+    }
+
+    /// Adds the given `fallbacks` to the `session`, for it to read contracts
+    /// from when they are not in its base commit. The fallbacks are held
+    /// until the session drops.
+    ///
+    /// Errors if any of the fallbacks does not exist in the store.
+    ///
+    /// See [`ContractSession::fallbacks`] for more details.
+    pub fn add_fallbacks(
+        &self,
+        session: &mut ContractSession,
+        fallbacks: &[Hash],
+    ) -> io::Result<()> {
+        for fallback in fallbacks {
+            let reply = self.call_with_replier(|replier| Call::CommitHold {
+                base: *fallback,
+                replier,
+            });
+            self.add_held_fallback(session, *fallback, reply.wait())?;
+        }
+        Ok(())
+    }
+
+    /// Adds the given `fallbacks` to the `session`, without blocking the
+    /// current thread.
+    ///
+    /// See [`add_fallbacks`] for more details.
+    ///
+    /// [`add_fallbacks`]: ContractStore::add_fallbacks
+    pub async fn add_fallbacks_async(
+        &self,
+        session: &mut ContractSession,
+        fallbacks: &[Hash],
+    ) -> io::Result<()> {
+        for fallback in fallbacks {
+            let reply = self.call_with_replier(|replier| Call::CommitHold {
+                base: *fallback,
+                replier,
+            });
+            self.add_held_fallback(session, *fallback, reply.await)?;
+        }
+        Ok(())
+    }
+
     /// Create a new [`ContractSession`] that has no base commit.
     ///
     /// For session with a base commit, please see [`session`].
@@ -228,9 +579,65 @@ impl ContractStore {
 
     /// Returns the roots of the commits that are currently in the store.
     pub fn commits(&self) -> Vec<Hash> {
+        self.commits_async().wait()
+    }
+
+    /// Returns the roots of the commits that are currently in the store,
+    /// without blocking the current thread.
+    pub fn commits_async(&self) -> Reply<Vec<Hash>> {
         self.call_with_replier(|replier| Call::GetCommits { replier })
     }
 
+    /// Returns information on the commits that are currently in the store.
+    pub fn commit_infos(&self) -> Vec<CommitInfo> {
+        let main_dir = self.root_dir.join(MAIN_DIR);
+        self.commits()
+            .into_iter()
+            .filter_map(|root| {
+                info::commit_info(&main_dir, root, &self.commit_store)
+            })
+            .collect()
+    }
+
+    /// Returns the differences between the states of the `old` and `new`
+    /// commits.
+    ///
+    /// Changed pages are reported at the [tracking page size].
+    ///
+    /// Errors if either commit is not in the store, or if the pages of a
+    /// modified contract can't be read.
+    ///
+    /// [tracking page size]: ContractStore::set_tracking_page_size
+    pub fn diff_commits(&self, old: Hash, new: Hash) -> io::Result<CommitDiff> {
+        diff::diff_commits(
+            &self.root_dir,
+            &self.commit_store,
+            old,
+            new,
+            self.tracking_page_size(),
+        )
+    }
+
+    /// Returns information on the commits that are currently in the store,
+    /// without blocking the current thread.
+    ///
+    /// A commit deleted after the roots are gathered is left out.
+    pub fn commit_infos_async(&self) -> impl Future<Output = Vec<CommitInfo>> {
+        let reply = self.commits_async();
+        let main_dir = self.root_dir.join(MAIN_DIR);
+        let commit_store = self.commit_store.clone();
+
+        async move {
+            reply
+                .await
+                .into_iter()
+                .filter_map(|root| {
+                    info::commit_info(&main_dir, root, &commit_store)
+                })
+                .collect()
+        }
+    }
+
     /// Deletes a given `commit` from the store.
     ///
     /// If a `ContractSession` is currently using the given commit as a base,
@@ -239,19 +646,387 @@ impl ContractStore {
     ///
     /// It will block until the operation is completed.
     pub fn delete_commit(&self, commit: Hash) -> io::Result<()> {
-        self.call_with_replier(|replier| Call::CommitDelete { commit, replier })
+        self.delete_commit_async(commit).wait()
+    }
+
+    /// Deletes a given `commit` from the store, without blocking the current
+    /// thread.
+    ///
+    /// See [`delete_commit`] for more details.
+    ///
+    /// [`delete_commit`]: ContractStore::delete_commit
+    pub fn delete_commit_async(&self, commit: Hash) -> Reply<io::Result<()>> {
+        self.call_with_replier(|replier| Call::CommitDelete {
+            commit,
+            checked: false,
+            replier,
+        })
+    }
+
+    /// Deletes a given `commit` from the store, unless other commits were
+    /// written on top of it.
+    ///
+    /// The error returned when there are such commits is marked as
+    /// [`CommitHasDependents`], listing them. See [`delete_commit`] for more
+    /// details.
+    ///
+    /// [`delete_commit`]: ContractStore::delete_commit
+    pub fn delete_commit_checked(&self, commit: Hash) -> io::Result<()> {
+        self.call_with_replier(|replier| Call::CommitDelete {
+            commit,
+            checked: true,
+            replier,
+        })
+        .wait()
+    }
+
+    /// Deletes a given `commit` from the store, together with all commits
+    /// written on top of it, either directly or through other commits.
+    ///
+    /// The dependents are deleted before the commits they depend on. See
+    /// [`delete_commit`] for more details.
+    ///
+    /// [`delete_commit`]: ContractStore::delete_commit
+    pub fn delete_commit_cascade(&self, commit: Hash) -> io::Result<()> {
+        let replies = self
+            .call_with_replier(|replier| Call::CommitDeleteCascade {
+                commit,
+                replier,
+            })
+            .wait();
+
+        // Wait for all deletions, returning the first error encountered
+        replies
+            .into_iter()
+            .map(Reply::wait)
+            .fold(Ok(()), Result::and)
     }
 
     /// Finalizes commit
     ///
     /// The commit will become a "current" commit
     pub fn finalize_commit(&self, commit: Hash) -> io::Result<()> {
+        self.finalize_commit_async(commit).wait()
+    }
+
+    /// Finalizes commit, without blocking the current thread.
+    ///
+    /// See [`finalize_commit`] for more details.
+    ///
+    /// [`finalize_commit`]: ContractStore::finalize_commit
+    pub fn finalize_commit_async(&self, commit: Hash) -> Reply<io::Result<()>> {
         self.call_with_replier(|replier| Call::CommitFinalize {
             commit,
             replier,
         })
     }
 
+    /// Rebuilds the memory of the given `contract` as of the given `commit`,
+    /// restoring any pages that are missing or corrupt.
+    ///
+    /// The pages are looked for in the directories of the commit's ancestors,
+    /// then in the main memory directory, and lastly in the directories of
+    /// any other commit. A page is only restored if its hash matches the one
+    /// recorded in the commit.
+    ///
+    /// Every commit in the store is held while reconstructing, so none of
+    /// them is deleted, finalized, or moved from under it.
+    ///
+    /// Returns the number of pages restored. Errors if the commit or the
+    /// contract don't exist, or if a page can't be found anywhere in the store.
+    pub fn reconstruct(
+        &self,
+        commit: Hash,
+        contract: ContractId,
+    ) -> io::Result<usize> {
+        self.call_with_replier(|replier| Call::CommitReconstruct {
+            commit,
+            contract,
+            replier,
+        })
+        .wait()
+    }
+
+    /// Folds the chain of commits from `from` up to `to` into `to`, making it
+    /// independent of them.
+    ///
+    /// Every commit only stores the pages and elements it changed, and looks
+    /// up the rest in its ancestors. Squashing copies everything `to` would
+    /// find in the commits from `to` down to `from` into its own directories,
+    /// and makes `from`'s base its base, shortening the chain walked when
+    /// loading contracts.
+    ///
+    /// The commits in between are left untouched, and may be deleted
+    /// independently afterwards - they are held while squashing, so they are
+    /// not removed from under it. Errors if `from` is not an ancestor of `to`,
+    /// or if any commit in the chain is being removed.
+    pub fn squash_commits(&self, from: Hash, to: Hash) -> io::Result<()> {
+        self.call_with_replier(|replier| Call::CommitSquash {
+            from,
+            to,
+            replier,
+        })
+        .wait()
+    }
+
+    /// Writes the commit with the given `root` to the `writer`, in a portable
+    /// format that can be read back by [`import_commit`].
+    ///
+    /// The commit is held for the duration of the export, so it is not deleted
+    /// or finalized from under it.
+    ///
+    /// [`import_commit`]: ContractStore::import_commit
+    pub fn export_commit<W: io::Write>(
+        &self,
+        root: Hash,
+        writer: W,
+    ) -> io::Result<()> {
+        let session = self.session(root)?;
+
+        let commit = self
+            .commit_store
+            .lock()
+            .unwrap()
+            .get_commit(&root)
+            .cloned()
+            .expect("Held commit should be in the store");
+        let main_dir = self.root_dir.join(MAIN_DIR);
+        let result = export::export_commit(&main_dir, root, &commit, writer);
+
+        drop(session);
+        result
+    }
+
+    /// Writes the commit with the given `root` to the `backend`, with every
+    /// page of every contract in it, and without a base.
+    ///
+    /// The commit is held for the duration of the write, so it is not deleted
+    /// or finalized from under it.
+    pub fn archive_commit<B: StorageBackend + ?Sized>(
+        &self,
+        root: Hash,
+        backend: &mut B,
+    ) -> io::Result<()> {
+        let session = self.session(root)?;
+
+        let commit = self
+            .commit_store
+            .lock()
+            .unwrap()
+            .get_commit(&root)
+            .cloned()
+            .expect("Held commit should be in the store");
+        let main_dir = self.root_dir.join(MAIN_DIR);
+        let result = backend::archive_commit(&main_dir, root, &commit, backend);
+
+        drop(session);
+        result
+    }
+
+    /// Reads a commit written by [`export_commit`] from the `reader`, and adds
+    /// it to the store. Returns the root of the commit.
+    ///
+    /// The commit is added without a base, and its root is checked to match
+    /// the exported one. If a commit with the same root is already in the
+    /// store, the rest of the stream is not read.
+    ///
+    /// [`export_commit`]: ContractStore::export_commit
+    pub fn import_commit<R: io::Read>(&self, reader: R) -> io::Result<Hash> {
+        let main_dir = self.root_dir.join(MAIN_DIR);
+        let (root, commit) = export::import_commit(
+            &self.engine,
+            &main_dir,
+            &self.commit_store,
+            reader,
+        )?;
+
+        if let Some(commit) = commit {
+            self.commit_store
+                .lock()
+                .unwrap()
+                .insert_commit(root, commit);
+        }
+
+        Ok(root)
+    }
+
+    /// Checks the integrity of every commit in the store, at the given
+    /// `level`, reporting the checks each failed.
+    ///
+    /// The commits are held while they are checked, so that they are not
+    /// deleted or finalized from under it. Deletions and finalizations
+    /// requested in the meantime are carried out once it finishes.
+    pub fn fsck(&self, level: FsckLevel) -> FsckReport {
+        // Commits deleted or finalized since being listed are skipped
+        let held: Vec<_> = self
+            .commits()
+            .into_iter()
+            .filter_map(|root| Some((root, self.session(root).ok()?)))
+            .collect();
+
+        let mut checker = fsck::Checker::new(&self.root_dir, level);
+        for (root, _) in &held {
+            let commit = self
+                .commit_store
+                .lock()
+                .unwrap()
+                .get_commit(root)
+                .cloned()
+                .expect("Held commit should be in the store");
+            checker.check_commit(*root, &commit);
+        }
+
+        drop(held);
+        checker.into_report()
+    }
+
+    /// Checks the integrity of every commit in the store, like [`fsck`], and
+    /// then deletes the commits that failed any check, together with all
+    /// commits written on top of them.
+    ///
+    /// [`fsck`]: ContractStore::fsck
+    pub fn fsck_repair(&self, level: FsckLevel) -> io::Result<FsckReport> {
+        let mut report = self.fsck(level);
+
+        let mut dropped = BTreeSet::new();
+        let mut roots = Vec::new();
+        {
+            let commit_store = self.commit_store.lock().unwrap();
+            for root in report.broken_commits() {
+                let root = Hash::from(root);
+                if dropped.insert(root) {
+                    roots.push(root);
+                    dropped.extend(commit_store.dependents(&root));
+                }
+            }
+        }
+
+        for root in roots {
+            self.delete_commit_cascade(root)?;
+        }
+
+        report.dropped = dropped.into_iter().map(Into::into).collect();
+        Ok(report)
+    }
+
+    /// Set the free disk space, in bytes, below which commits are refused
+    /// before anything is written. Zero, the default, disables the check.
+    ///
+    /// Refused commits fail with an error marked as [`StoreFull`], as do
+    /// commits that run out of disk space while being written.
+    pub fn set_min_free_space(&self, bytes: u64) {
+        self.min_free_space.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Set whether pages containing only zeroes are left out of commits, as
+    /// opposed to being written and hashed like any other page. Off by
+    /// default.
+    ///
+    /// Since it changes the roots of commits, this must be set the same way
+    /// by every deployment expected to agree on them. It is picked up by
+    /// sessions created after it is set.
+    pub fn set_elide_zero_pages(&self, elide: bool) {
+        self.elide_zero_pages.store(elide, Ordering::Relaxed);
+    }
+
+    /// Returns whether pages containing only zeroes are left out of commits.
+    pub fn elide_zero_pages(&self) -> bool {
+        self.elide_zero_pages.load(Ordering::Relaxed)
+    }
+
+    /// Set the hash of the instruction costs recorded in every commit made
+    /// from now on. Must be called before [`finish_new`] to take effect.
+    ///
+    /// [`finish_new`]: ContractStore::finish_new
+    pub(crate) fn set_instruction_costs(&mut self, hash: [u8; 32]) {
+        self.instruction_costs = hash;
+    }
+
+    /// Set the granularity at which changes to memories are reported, in
+    /// bytes. Defaults to [`PAGE_SIZE`].
+    ///
+    /// Pages are still stored and hashed whole, but are split into pages of
+    /// the given size when diffed, so that scattered small writes show up as
+    /// a few small pages instead of whole ones.
+    ///
+    /// # Panics
+    /// If `bytes` is not a power of two between [`MIN_TRACKING_PAGE_SIZE`] and
+    /// [`PAGE_SIZE`].
+    pub fn set_tracking_page_size(&self, bytes: usize) {
+        assert!(
+            bytes.is_power_of_two()
+                && (MIN_TRACKING_PAGE_SIZE..=PAGE_SIZE).contains(&bytes),
+            "Tracking page size must be a power of two between \
+             {MIN_TRACKING_PAGE_SIZE} and {PAGE_SIZE}, got {bytes}"
+        );
+        self.tracking_page_size.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Returns the granularity at which changes to memories are reported, in
+    /// bytes.
+    pub fn tracking_page_size(&self) -> usize {
+        self.tracking_page_size.load(Ordering::Relaxed)
+    }
+
+    /// Set the directory commits are moved to when cooled, typically on a
+    /// slower and cheaper volume than the one the store is in.
+    ///
+    /// Commits already cooled stay where they are when this changes.
+    pub fn set_cold_dir<P: AsRef<Path>>(&self, dir: P) {
+        *self.cold_dir.lock().unwrap() = Some(dir.as_ref().into());
+    }
+
+    /// Moves the files of the given `commit` to the [cold directory],
+    /// leaving links to them in their place.
+    ///
+    /// Cold commits are used like any other - sessions can be based on them,
+    /// and they can be deleted, finalized, or squashed - with their files
+    /// being read from the cold directory instead.
+    ///
+    /// Errors if no cold directory is set, if the commit doesn't exist, or if
+    /// it is being used as the base of a session.
+    ///
+    /// [cold directory]: ContractStore::set_cold_dir
+    pub fn cool_commit(&self, commit: Hash) -> io::Result<()> {
+        let cold_dir =
+            self.cold_dir.lock().unwrap().clone().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "No cold directory set",
+                )
+            })?;
+
+        self.call_with_replier(|replier| Call::CommitMove {
+            commit,
+            cold_dir: Some(cold_dir),
+            replier,
+        })
+        .wait()
+    }
+
+    /// Moves the files of the given `commit` back from where they were
+    /// [cooled] to. Warming a commit that isn't cold does nothing.
+    ///
+    /// Errors if the commit doesn't exist, or if it is being used as the base
+    /// of a session.
+    ///
+    /// [cooled]: ContractStore::cool_commit
+    pub fn warm_commit(&self, commit: Hash) -> io::Result<()> {
+        self.call_with_replier(|replier| Call::CommitMove {
+            commit,
+            cold_dir: None,
+            replier,
+        })
+        .wait()
+    }
+
+    /// Returns whether the given `commit` is [cold].
+    ///
+    /// [cold]: ContractStore::cool_commit
+    pub fn is_commit_cold(&self, commit: Hash) -> io::Result<bool> {
+        cold::is_cold(&self.root_dir.join(MAIN_DIR), commit)
+    }
+
     /// Return the handle to the thread running the store's synchronization
     /// loop.
     pub fn sync_loop(&self) -> &thread::Thread {
@@ -261,16 +1036,26 @@ impl ContractStore {
             .thread()
     }
 
+    /// Return the scheduler running the store's background work.
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    /// Return the heat map recording the pages accessed by sessions.
+    pub fn heat_map(&self) -> &HeatMap {
+        &self.heat_map
+    }
+
     /// Return the path to the VM directory.
     pub fn root_dir(&self) -> &Path {
         &self.root_dir
     }
 
-    fn call_with_replier<T, F>(&self, closure: F) -> T
+    fn call_with_replier<T, F>(&self, closure: F) -> Reply<T>
     where
-        F: FnOnce(mpsc::SyncSender<T>) -> Call,
+        F: FnOnce(Replier<T>) -> Call,
     {
-        let (replier, receiver) = mpsc::sync_channel(1);
+        let (replier, reply) = reply_channel();
 
         self.call
             .as_ref()
@@ -280,9 +1065,51 @@ impl ContractStore {
                 "The receiver should never be dropped while there are senders",
             );
 
-        receiver
-            .recv()
-            .expect("The sender should never be dropped without responding")
+        reply
+    }
+
+    /// Create a session on top of the given `base`, once the synchronization
+    /// loop has replied to the request to hold it.
+    fn session_with_held_base(
+        &self,
+        base: Hash,
+        held: Option<Hash>,
+    ) -> io::Result<ContractSession> {
+        let base_commit_hash = held.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("No such base commit: {}", hex::encode(base)),
+            )
+        })?;
+
+        Ok(self.session_with_base(Some(base_commit_hash)))
+    }
+
+    /// Add the given `fallback` to the `session`, once the synchronization
+    /// loop has replied to the request to hold it.
+    fn add_held_fallback(
+        &self,
+        session: &mut ContractSession,
+        fallback: Hash,
+        held: Option<Hash>,
+    ) -> io::Result<()> {
+        let fallback_hash = held.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("No such fallback commit: {}", hex::encode(fallback)),
+            )
+        })?;
+
+        let commit = self
+            .commit_store
+            .lock()
+            .unwrap()
+            .get_commit(&fallback_hash)
+            .cloned()
+            .expect("A held commit should be in the store");
+        session.add_fallback(commit);
+
+        Ok(())
     }
 
     fn session_with_base(&self, base: Option<Hash>) -> ContractSession {
@@ -295,10 +1122,23 @@ impl ContractStore {
             base_commit,
             self.call.as_ref().expect("call should exist").clone(),
             self.commit_store.clone(),
+            self.heat_map.sample(),
+            self.scheduler.clone(),
+            self.locks.holder(),
+            self.elide_zero_pages(),
         )
     }
 }
 
+/// Loads the commits in the store's main directory into the `commit_store`.
+///
+/// The commits recorded in the store's commit index are loaded without being
+/// validated again, since they already were when they were first written or
+/// loaded. The directory is only scanned, validating every commit, if the
+/// index is missing, corrupt, or lists commits that can't be loaded. Commits
+/// on disk but missing from the index - written just before a crash - are
+/// validated and loaded too. Either way, a compacted index is written once
+/// the commits are loaded.
 fn read_all_commits<P: AsRef<Path>>(
     engine: &Engine,
     root_dir: P,
@@ -306,29 +1146,181 @@ fn read_all_commits<P: AsRef<Path>>(
 ) -> io::Result<()> {
     let root_dir = root_dir.as_ref();
 
-    let root_dir = root_dir.join(MAIN_DIR);
-    fs::create_dir_all(&root_dir)?;
+    check_store_version(root_dir)?;
+
+    let main_dir = root_dir.join(MAIN_DIR);
+    fs::create_dir_all(&main_dir)?;
+
+    commit_store.lock().unwrap().main_instruction_costs =
+        instruction_costs_from_path(main_dir.join(INSTRUCTION_COSTS_FILE))?;
+
+    let index_path = root_dir.join(COMMIT_INDEX_FILE);
+    let indexed = match CommitIndex::load(&index_path)? {
+        Some(indexed) => {
+            read_indexed_commits(engine, &main_dir, &indexed, &commit_store)
+        }
+        None => None,
+    };
+
+    match indexed {
+        Some(commits) => {
+            let indexed_dirs: BTreeSet<_> = commits
+                .iter()
+                .map(|commit| main_dir.join(hex::encode(*commit.root())))
+                .collect();
+
+            for commit in commits {
+                let root = *commit.root();
+                commit_store.lock().unwrap().insert_commit(root, commit);
+            }
+
+            for commit_dir in commit_dirs(&main_dir)? {
+                if indexed_dirs.contains(&commit_dir) {
+                    continue;
+                }
+
+                let dir = commit_dir.display();
+                tracing::warn!("commit index is missing {dir}");
+                let commit =
+                    read_commit(engine, &commit_dir, commit_store.clone())?;
+                let root = *commit.root();
+                commit_store.lock().unwrap().insert_commit(root, commit);
+            }
+        }
+        None => {
+            tracing::trace!("scanning commits");
+            scan_commits(engine, &main_dir, commit_store.clone())?;
+        }
+    }
+
+    let mut commit_store = commit_store.lock().unwrap();
+    let commits: IndexedCommits = commit_store
+        .commits
+        .iter()
+        .map(|(root, commit)| (*root, commit.base))
+        .collect();
+    let index = CommitIndex::create(index_path, &commits)?;
+    commit_store.set_index(index);
 
-    for entry in fs::read_dir(root_dir)? {
+    Ok(())
+}
+
+/// Checks that the store at the given `root_dir` was written with the layout of
+/// [`STORE_VERSION`], recording the version if the store is new.
+///
+/// A store with commits but no recorded version was written before versions
+/// were recorded. Its commits are read just the same - lacking only an index,
+/// which is rebuilt by scanning them - so it is migrated by sharing the
+/// bytecode of its contracts, and then recording the version. Stores of any
+/// other version are refused.
+fn check_store_version(root_dir: &Path) -> io::Result<()> {
+    let path = root_dir.join(VERSION_FILE);
+
+    let found = match fs::read_to_string(&path) {
+        Ok(version) => version.trim().to_string(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let main_dir = root_dir.join(MAIN_DIR);
+            let is_new = match fs::read_dir(&main_dir) {
+                Ok(mut entries) => entries.next().is_none(),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => true,
+                Err(err) => return Err(err),
+            };
+            if !is_new {
+                tracing::info!("migrating store to version {STORE_VERSION}");
+                bytecode::share_existing(
+                    &main_dir.join(BYTECODE_DIR),
+                    &main_dir.join(CODE_DIR),
+                )?;
+            }
+
+            let tmp_path = path.with_extension("tmp");
+            fs::write(&tmp_path, STORE_VERSION.to_string())?;
+            return fs::rename(tmp_path, path);
+        }
+        Err(err) => return Err(err),
+    };
+
+    if found != STORE_VERSION.to_string() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Store version {found} is not the supported version \
+                 {STORE_VERSION}"
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Loads the commits recorded in the commit index, returning `None` if any of
+/// them can't be loaded, or has a different base than recorded.
+fn read_indexed_commits(
+    engine: &Engine,
+    main_dir: &Path,
+    indexed: &IndexedCommits,
+    commit_store: &Arc<Mutex<CommitStore>>,
+) -> Option<Vec<Commit>> {
+    let mut commits = Vec::with_capacity(indexed.len());
+
+    for (root, base) in indexed {
+        let commit_dir = main_dir.join(hex::encode(root));
+        match commit_from_dir(engine, commit_dir, commit_store.clone(), false) {
+            Ok(commit) if commit.base == *base => commits.push(commit),
+            Ok(_) => {
+                let root = hex::encode(root);
+                tracing::warn!("commit index has a stale base for {root}");
+                return None;
+            }
+            Err(err) => {
+                let root = hex::encode(root);
+                tracing::warn!("commit index lists unreadable {root}: {err}");
+                return None;
+            }
+        }
+    }
+
+    Some(commits)
+}
+
+/// Loads every commit found in the `main_dir`, validating each of them.
+fn scan_commits(
+    engine: &Engine,
+    main_dir: &Path,
+    commit_store: Arc<Mutex<CommitStore>>,
+) -> io::Result<()> {
+    for commit_dir in commit_dirs(main_dir)? {
+        tracing::trace!("before read_commit");
+        let commit = read_commit(engine, commit_dir, commit_store.clone())?;
+        tracing::trace!("before read_commit");
+        let root = *commit.root();
+        commit_store.lock().unwrap().insert_commit(root, commit);
+    }
+
+    Ok(())
+}
+
+/// Lists the directories of the commits in the `main_dir`, without reading
+/// them.
+fn commit_dirs(main_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+
+    for entry in fs::read_dir(main_dir)? {
         let entry = entry?;
         if entry.path().is_dir() {
             let filename = entry.file_name();
             if filename == MEMORY_DIR
                 || filename == BYTECODE_DIR
+                || filename == CODE_DIR
                 || filename == LEAF_DIR
             {
                 continue;
             }
-            tracing::trace!("before read_commit");
-            let commit =
-                read_commit(engine, entry.path(), commit_store.clone())?;
-            tracing::trace!("before read_commit");
-            let root = *commit.root();
-            commit_store.lock().unwrap().insert_commit(root, commit);
+            dirs.push(entry.path());
         }
     }
 
-    Ok(())
+    Ok(dirs)
 }
 
 fn read_commit<P: AsRef<Path>>(
@@ -337,7 +1329,7 @@ fn read_commit<P: AsRef<Path>>(
     commit_store: Arc<Mutex<CommitStore>>,
 ) -> io::Result<Commit> {
     let commit_dir = commit_dir.as_ref();
-    let commit = commit_from_dir(engine, commit_dir, commit_store)?;
+    let commit = commit_from_dir(engine, commit_dir, commit_store, true)?;
     Ok(commit)
 }
 
@@ -392,10 +1384,17 @@ fn contract_id_from_hex<S: AsRef<str>>(contract_id: S) -> ContractId {
     ContractId::from_bytes(bytes)
 }
 
+/// Loads the commit in the given `dir`, reconstructing the memory pages of its
+/// contracts that are missing. If `validate` is set, the bytecode of each
+/// contract is checked as well, compiling modules that are missing.
+///
+/// Pages are checked either way, since a page found missing only once a
+/// session maps it can't be reported as an error.
 fn commit_from_dir<P: AsRef<Path>>(
     engine: &Engine,
     dir: P,
     commit_store: Arc<Mutex<CommitStore>>,
+    validate: bool,
 ) -> io::Result<Commit> {
     let dir = dir.as_ref();
     let mut commit_id: Option<String> = None;
@@ -439,73 +1438,123 @@ fn commit_from_dir<P: AsRef<Path>>(
     )?;
     tracing::trace!("after index_merkle_from_path");
 
+    validate_contracts(engine, main_dir, maybe_hash, &index, validate)?;
+
+    let base = if let Some(ref hash_hex) = commit_id {
+        let base_info_path = main_dir.join(hash_hex).join(BASE_FILE);
+        base_from_path(base_info_path)?.maybe_base
+    } else {
+        None
+    };
+
+    let instruction_costs =
+        instruction_costs_from_path(dir.join(INSTRUCTION_COSTS_FILE))?;
+
+    Ok(Commit {
+        index,
+        contracts_merkle,
+        maybe_hash,
+        commit_store: Some(commit_store),
+        base,
+        instruction_costs,
+    })
+}
+
+/// Checks that every contract in the `index` of the commit with the given
+/// `maybe_hash` has its memory pages, reconstructing pages that are missing.
+/// If `check_code` is set, their bytecode is checked as well.
+fn validate_contracts(
+    engine: &Engine,
+    main_dir: &Path,
+    maybe_hash: Option<Hash>,
+    index: &NewContractIndex,
+    check_code: bool,
+) -> io::Result<()> {
     let bytecode_dir = main_dir.join(BYTECODE_DIR);
     let memory_dir = main_dir.join(MEMORY_DIR);
 
     for (contract, contract_index) in index.iter() {
         let contract_hex = hex::encode(contract);
 
-        // Check that all contracts in the index file have a corresponding
-        // bytecode and memory pages specified.
-        let bytecode_path = bytecode_dir.join(&contract_hex);
-        if !bytecode_path.is_file() {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Non-existing bytecode for contract: {contract_hex}"),
-            ));
-        }
-
-        let module_path = bytecode_path.with_extension(OBJECTCODE_EXTENSION);
-
-        // SAFETY it is safe to deserialize the file here, since we don't use
-        // the module here. We just want to check if the file is valid.
-        if Module::from_file(engine, &module_path).is_err() {
-            let bytecode = Bytecode::from_file(bytecode_path)?;
-            let module = Module::from_bytecode(engine, bytecode.as_ref())
-                .map_err(|err| {
-                    io::Error::new(io::ErrorKind::InvalidData, err)
-                })?;
-            fs::write(module_path, module.serialize())?;
+        if check_code {
+            validate_code(engine, &bytecode_dir, &contract_hex)?;
         }
 
         let contract_memory_dir = memory_dir.join(&contract_hex);
 
-        for page_index in contract_index.page_indices() {
-            let main_page_path = page_path(&contract_memory_dir, *page_index);
-            if !main_page_path.is_file() {
+        let missing_page =
+            contract_index.page_indices().iter().any(|page_index| {
+                let main_page_path =
+                    page_path(&contract_memory_dir, *page_index);
+                if main_page_path.is_file() {
+                    return false;
+                }
                 let path = ContractSession::find_page(
                     *page_index,
                     maybe_hash,
                     &contract_memory_dir,
                     main_dir,
                 );
-                let found = path.map(|p| p.is_file()).unwrap_or(false);
-                if !found {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!(
-                            "Non-existing memory for contract: {contract_hex}"
-                        ),
-                    ));
-                }
-            }
+                !path.map(|p| p.is_file()).unwrap_or(false)
+            });
+
+        // Missing pages may still be found in the directories of other
+        // commits, such as after a partial restore of the disk.
+        if missing_page {
+            let restored = reconstruct_memory(
+                main_dir,
+                maybe_hash,
+                contract,
+                contract_index,
+            )?;
+            tracing::trace!(
+                "restored {restored} pages of contract: {contract_hex}"
+            );
         }
     }
 
-    let base = if let Some(ref hash_hex) = commit_id {
-        let base_info_path = main_dir.join(hash_hex).join(BASE_FILE);
-        base_from_path(base_info_path)?.maybe_base
-    } else {
-        None
-    };
+    Ok(())
+}
 
-    Ok(Commit {
-        index,
-        contracts_merkle,
-        maybe_hash,
-        commit_store: Some(commit_store),
-        base,
-    })
+/// Checks that the contract with the given hex-encoded ID has its bytecode,
+/// compiling its module if it's missing or invalid.
+fn validate_code(
+    engine: &Engine,
+    bytecode_dir: &Path,
+    contract_hex: &str,
+) -> io::Result<()> {
+    let bytecode_path = bytecode_dir.join(contract_hex);
+    if !bytecode_path.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Non-existing bytecode for contract: {contract_hex}"),
+        ));
+    }
+
+    let module_path = bytecode_path.with_extension(OBJECTCODE_EXTENSION);
+
+    // SAFETY it is safe to deserialize the file here, since we don't use
+    // the module here. We just want to check if the file is valid.
+    if Module::from_file(engine, &module_path).is_err() {
+        let bytecode = Bytecode::from_file(bytecode_path)?;
+        let module = Module::from_bytecode(engine, bytecode.as_ref()).map_err(
+            |err| match denied_feature(engine, bytecode.as_ref()) {
+                Some(feature) => io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "contract {contract_hex} uses denied WASM \
+                         feature: {feature}"
+                    ),
+                ),
+                None => io::Error::new(io::ErrorKind::InvalidData, err),
+            },
+        )?;
+        // The module may be linked to ones shared with other contracts,
+        // so it's replaced rather than written to.
+        bytecode::write_replacing(&module_path, &module.serialize())?;
+    }
+
+    Ok(())
 }
 
 fn index_merkle_from_path(
@@ -534,7 +1583,9 @@ fn index_merkle_from_path(
                 0,
             )
             .unwrap_or((contract_leaf_path.join(ELEMENT_FILE), 0));
-            if element_path.is_file() {
+            if element_path.ends_with(REMOVED_FILE) {
+                index.mark_removed(&contract_id);
+            } else if element_path.is_file() {
                 let element_bytes = fs::read(&element_path)?;
                 let element: ContractIndexElement =
                     rkyv::from_bytes(&element_bytes).map_err(|err| {
@@ -589,6 +1640,27 @@ fn base_from_path<P: AsRef<Path>>(path: P) -> io::Result<BaseInfo> {
     Ok(base_info)
 }
 
+/// Reads the hash of the instruction costs recorded for a commit, if any.
+fn instruction_costs_from_path(
+    path: impl AsRef<Path>,
+) -> io::Result<Option<[u8; 32]>> {
+    let path = path.as_ref();
+
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let hash = <[u8; 32]>::try_from(bytes.as_slice()).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid instruction costs file \"{path:?}\""),
+        )
+    })?;
+
+    Ok(Some(hash))
+}
+
 fn tree_pos_from_path(
     path: impl AsRef<Path>,
     opt_path: impl AsRef<Path>,
@@ -619,6 +1691,9 @@ pub(crate) struct Commit {
     maybe_hash: Option<Hash>,
     commit_store: Option<Arc<Mutex<CommitStore>>>,
     base: Option<Hash>,
+    // The hash of the instruction costs the commit was made with, unless it
+    // was made before they were recorded.
+    instruction_costs: Option<[u8; 32]>,
 }
 
 impl Commit {
@@ -632,6 +1707,7 @@ impl Commit {
             maybe_hash: None,
             commit_store: Some(commit_store.clone()),
             base: maybe_base,
+            instruction_costs: None,
         }
     }
 
@@ -644,6 +1720,8 @@ impl Commit {
         for contract_id in contract_ids {
             if let Some(a) = self.index.get(contract_id) {
                 index.insert_contract_index(contract_id, a.clone());
+            } else if self.index.is_removed(contract_id) {
+                index.mark_removed(contract_id);
             }
         }
         Self {
@@ -652,6 +1730,7 @@ impl Commit {
             maybe_hash: self.maybe_hash,
             commit_store: self.commit_store.clone(),
             base: self.base,
+            instruction_costs: self.instruction_costs,
         }
     }
 
@@ -663,6 +1742,7 @@ impl Commit {
         let contract = self.index.remove_contract_index(contract_id)?;
 
         let pos = position_from_contract(contract_id);
+        let contract_id = *contract_id;
 
         let (iter, tree) = contract.page_indices_and_tree();
         Some(iter.map(move |page_index| {
@@ -678,6 +1758,7 @@ impl Commit {
             (
                 page_index,
                 PageOpening {
+                    contract_id,
                     tree: tree_opening,
                     inner: page_opening,
                 },
@@ -685,7 +1766,48 @@ impl Commit {
         }))
     }
 
-    pub fn insert(&mut self, contract_id: ContractId, memory: &Memory) {
+    /// Returns a proof of the leaf of the given contract, with the root of the
+    /// tree of its memory pages, or `None` if the contract has no leaf.
+    pub fn contract_proof(
+        &self,
+        contract_id: &ContractId,
+        memory_root: Hash,
+    ) -> Option<MerkleProof> {
+        let pos = position_from_contract(contract_id);
+        let opening = self.contracts_merkle.opening(pos)?;
+
+        Some(MerkleProof::new(*contract_id, memory_root, opening))
+    }
+
+    /// Inserts the dirty pages of the given `memory` into the tree of the
+    /// contract.
+    ///
+    /// With `elide_zero_pages` set, pages containing only zeroes are removed
+    /// from the tree instead, so that a page explicitly written with zeroes
+    /// hashes the same as one never written at all.
+    pub fn insert(
+        &mut self,
+        contract_id: ContractId,
+        memory: &Memory,
+        elide_zero_pages: bool,
+    ) {
+        // Pages dirtied before a snapshot still in place - such as a session
+        // checkpoint - are included as well.
+        let dirty_pages = memory
+            .all_dirty_pages()
+            .map(|(dirty_page, _, page_index)| (dirty_page, *page_index));
+        self.insert_pages(contract_id, memory, dirty_pages, elide_zero_pages);
+    }
+
+    /// Inserts the given `pages` of `memory`, together with their indices,
+    /// into the tree of the contract, leaving all other pages as they are.
+    pub fn insert_pages<'a>(
+        &mut self,
+        contract_id: ContractId,
+        memory: &Memory,
+        pages: impl IntoIterator<Item = (&'a [u8], usize)>,
+        elide_zero_pages: bool,
+    ) {
         if self.index_get(&contract_id).is_none() {
             self.index.insert_contract_index(
                 &contract_id,
@@ -698,25 +1820,42 @@ impl Commit {
 
         element.set_len(memory.current_len);
 
-        for (dirty_page, _, page_index) in memory.dirty_pages() {
-            let hash = Hash::new(dirty_page);
-            element.insert_page_index_hash(
-                *page_index,
-                *page_index as u64,
-                hash,
-            );
+        for (page, page_index) in pages {
+            if elide_zero_pages && is_zero_page(page) {
+                element.remove_page_index(page_index);
+                continue;
+            }
+            let hash = Hash::new(page);
+            element.insert_page_index_hash(page_index, page_index as u64, hash);
         }
 
         let root = *element.tree().root();
         let pos = position_from_contract(&contract_id);
-        let internal_pos = contracts_merkle.insert(pos, root);
+        let leaf = contract_leaf(&contract_id, &root);
+        let internal_pos = contracts_merkle.insert(pos, leaf);
         element.set_hash(Some(root));
         element.set_int_pos(Some(internal_pos));
     }
 
-    pub fn remove_and_insert(&mut self, contract: ContractId, memory: &Memory) {
+    pub fn remove_and_insert(
+        &mut self,
+        contract: ContractId,
+        memory: &Memory,
+        elide_zero_pages: bool,
+    ) {
         self.index.remove_contract_index(&contract);
-        self.insert(contract, memory);
+        self.insert(contract, memory, elide_zero_pages);
+    }
+
+    /// Removes the given contract from the commit, along with its leaf in the
+    /// contracts tree, hiding it in the commit's bases.
+    pub fn remove_contract(&mut self, contract: &ContractId) {
+        if self.index_get(contract).is_none() {
+            return;
+        }
+        self.index.mark_removed(contract);
+        self.contracts_merkle
+            .remove(position_from_contract(contract));
     }
 
     pub fn root(&self) -> Ref<Hash> {
@@ -739,6 +1878,16 @@ impl Commit {
         .map(|a| unsafe { &*a })
     }
 
+    /// Returns the IDs of all contracts in the commit, including those
+    /// inherited from its bases.
+    pub fn contract_ids(&self) -> BTreeSet<ContractId> {
+        Hulk::deep_contract_ids(
+            &self.index,
+            self.commit_store.clone(),
+            self.base,
+        )
+    }
+
     pub fn element_and_merkle_mut(
         &mut self,
         contract_id: &ContractId,
@@ -759,62 +1908,167 @@ impl Commit {
 pub(crate) enum Call {
     Commit {
         contracts: BTreeMap<ContractId, ContractDataEntry>,
+        removed: BTreeSet<ContractId>,
         base: Option<Commit>,
-        replier: mpsc::SyncSender<io::Result<Hash>>,
+        elide_zero_pages: bool,
+        replier: Replier<io::Result<Hash>>,
     },
     GetCommits {
-        replier: mpsc::SyncSender<Vec<Hash>>,
+        replier: Replier<Vec<Hash>>,
     },
     CommitDelete {
         commit: Hash,
-        replier: mpsc::SyncSender<io::Result<()>>,
+        checked: bool,
+        replier: Replier<io::Result<()>>,
+    },
+    CommitDeleteCascade {
+        commit: Hash,
+        replier: Replier<Vec<Reply<io::Result<()>>>>,
     },
     CommitFinalize {
         commit: Hash,
-        replier: mpsc::SyncSender<io::Result<()>>,
+        replier: Replier<io::Result<()>>,
     },
     CommitHold {
         base: Hash,
-        replier: mpsc::SyncSender<Option<Hash>>,
+        replier: Replier<Option<Hash>>,
+    },
+    CommitReconstruct {
+        commit: Hash,
+        contract: ContractId,
+        replier: Replier<io::Result<usize>>,
+    },
+    CommitSquash {
+        from: Hash,
+        to: Hash,
+        replier: Replier<io::Result<()>>,
+    },
+    CommitMove {
+        commit: Hash,
+        cold_dir: Option<PathBuf>,
+        replier: Replier<io::Result<()>>,
     },
     SessionDrop(Hash),
 }
 
-fn sync_loop<P: AsRef<Path>>(
+#[cfg(feature = "spans")]
+impl Call {
+    /// The name of the call, as recorded in the spans of the sync loop.
+    fn name(&self) -> &'static str {
+        match self {
+            Call::Commit { .. } => "commit",
+            Call::GetCommits { .. } => "get_commits",
+            Call::CommitDelete { .. } => "commit_delete",
+            Call::CommitDeleteCascade { .. } => "commit_delete_cascade",
+            Call::CommitFinalize { .. } => "commit_finalize",
+            Call::CommitHold { .. } => "commit_hold",
+            Call::CommitReconstruct { .. } => "commit_reconstruct",
+            Call::CommitSquash { .. } => "commit_squash",
+            Call::CommitMove { .. } => "commit_move",
+            Call::SessionDrop(_) => "session_drop",
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sync_loop<P, B>(
     root_dir: P,
     commit_store: Arc<Mutex<CommitStore>>,
+    scheduler: Scheduler,
+    min_free_space: Arc<AtomicU64>,
+    instruction_costs: [u8; 32],
+    backend: B,
+    write_pool: ThreadPool,
     calls: mpsc::Receiver<Call>,
-) {
+) where
+    P: AsRef<Path>,
+    B: StorageBackend + Send + 'static,
+{
     let root_dir = root_dir.as_ref();
 
+    let backend = Arc::new(Mutex::new(backend));
+    let write_pool = Arc::new(write_pool);
+
     let mut sessions = BTreeMap::new();
 
     let mut delete_bag = BTreeMap::new();
 
+    // Commits for which a deletion or finalization has been scheduled, but not
+    // yet completed. Sessions may not be opened on top of these.
+    let removing = Arc::new(Mutex::new(BTreeSet::new()));
+
     for call in calls {
+        #[cfg(feature = "spans")]
+        let _span =
+            tracing::debug_span!("sync_loop", call = call.name()).entered();
+
         match call {
             // Writes a session to disk and adds it to the map of existing
             // commits.
             Call::Commit {
                 contracts,
+                removed,
                 base,
+                elide_zero_pages,
                 replier,
             } => {
-                tracing::trace!("writing commit started");
-                let io_result = write_commit(
-                    root_dir,
-                    commit_store.clone(),
-                    base,
-                    contracts,
+                let root_dir = root_dir.to_path_buf();
+                let commit_store = commit_store.clone();
+                let backend = backend.clone();
+                let write_pool = write_pool.clone();
+                let min_free_space = min_free_space.load(Ordering::Relaxed);
+
+                #[cfg(feature = "spans")]
+                let span = tracing::info_span!(
+                    "write_commit",
+                    base = ?base
+                        .as_ref()
+                        .map(|base| hex::encode(base.root().as_bytes())),
+                    contracts = contracts.len(),
+                    root = tracing::field::Empty,
+                );
+
+                scheduler.submit(
+                    Priority::High,
+                    Exclusive::Writes,
+                    move || {
+                        #[cfg(feature = "spans")]
+                        let _span = span.enter();
+
+                        tracing::trace!("writing commit started");
+                        let io_result =
+                            check_free_space(&root_dir, min_free_space)
+                                .and_then(|_| {
+                                    write_commit(
+                                        commit_store,
+                                        &backend,
+                                        &write_pool,
+                                        base,
+                                        contracts,
+                                        removed,
+                                        elide_zero_pages,
+                                        instruction_costs,
+                                    )
+                                });
+                        match &io_result {
+                            Ok(hash) => {
+                                #[cfg(feature = "spans")]
+                                span.record(
+                                    "root",
+                                    hex::encode(hash.as_bytes()).as_str(),
+                                );
+                                tracing::trace!(
+                                    "writing commit finished: {:?}",
+                                    hex::encode(hash.as_bytes())
+                                )
+                            }
+                            Err(e) => {
+                                tracing::trace!("writing commit failed {:?}", e)
+                            }
+                        }
+                        let _ = replier.send(io_result);
+                    },
                 );
-                match &io_result {
-                    Ok(hash) => tracing::trace!(
-                        "writing commit finished: {:?}",
-                        hex::encode(hash.as_bytes())
-                    ),
-                    Err(e) => tracing::trace!("writing commit failed {:?}", e),
-                }
-                let _ = replier.send(io_result);
             }
             // Copy all commits and send them back to the caller.
             Call::GetCommits { replier } => {
@@ -827,11 +2081,26 @@ fn sync_loop<P: AsRef<Path>>(
             // Delete a commit from disk. If the commit is currently in use - as
             // in it is held by at least one session using `Call::SessionHold` -
             // queue it for deletion once no session is holding it.
+            //
+            // A checked deletion fails if there are commits written on top of
+            // the one being deleted.
             Call::CommitDelete {
                 commit: root,
+                checked,
                 replier,
             } => {
-                tracing::trace!("delete commit started");
+                if checked {
+                    let dependents =
+                        commit_store.lock().unwrap().dependents(&root);
+                    if !dependents.is_empty() {
+                        let _ = replier.send(Err(io::Error::new(
+                            io::ErrorKind::Other,
+                            CommitHasDependents(dependents),
+                        )));
+                        continue;
+                    }
+                }
+
                 if sessions.contains_key(&root) {
                     match delete_bag.entry(root) {
                         Vacant(entry) => {
@@ -845,17 +2114,57 @@ fn sync_loop<P: AsRef<Path>>(
                     continue;
                 }
 
-                let io_result = delete_commit_dir(root_dir, root);
-                commit_store.lock().unwrap().remove_commit(&root);
-                tracing::trace!("delete commit finished");
-                let _ = replier.send(io_result);
+                schedule_delete(
+                    &scheduler,
+                    &backend,
+                    &commit_store,
+                    &removing,
+                    root,
+                    replier,
+                );
+            }
+            // Delete a commit from disk together with all of its dependents,
+            // the deepest first. Each deletion is carried out as in
+            // `Call::CommitDelete`, and the caller is handed the replies to
+            // all of them.
+            Call::CommitDeleteCascade {
+                commit: root,
+                replier,
+            } => {
+                let mut roots = commit_store.lock().unwrap().dependents(&root);
+                roots.reverse();
+                roots.push(root);
+
+                let mut replies = Vec::with_capacity(roots.len());
+                for root in roots {
+                    let (delete_replier, reply) = reply_channel();
+                    replies.push(reply);
+
+                    if sessions.contains_key(&root) {
+                        delete_bag
+                            .entry(root)
+                            .or_insert_with(Vec::new)
+                            .push(delete_replier);
+                        continue;
+                    }
+
+                    schedule_delete(
+                        &scheduler,
+                        &backend,
+                        &commit_store,
+                        &removing,
+                        root,
+                        delete_replier,
+                    );
+                }
+
+                let _ = replier.send(replies);
             }
             // Finalize commit
             Call::CommitFinalize {
                 commit: root,
                 replier,
             } => {
-                tracing::trace!("finalizing commit started");
                 if sessions.contains_key(&root) {
                     match delete_bag.entry(root) {
                         Vacant(entry) => {
@@ -869,37 +2178,179 @@ fn sync_loop<P: AsRef<Path>>(
                     continue;
                 }
 
-                let mut commit_store = commit_store.lock().unwrap();
-                if let Some(commit) = commit_store.get_commit(&root) {
-                    tracing::trace!(
-                        "finalizing commit proper started {}",
-                        hex::encode(root.as_bytes())
+                let root_dir = root_dir.to_path_buf();
+                let commit_store = commit_store.clone();
+                let removing = removing.clone();
+
+                removing.lock().unwrap().insert(root);
+                scheduler.submit(
+                    Priority::Normal,
+                    Exclusive::Commit(root),
+                    move || {
+                        tracing::trace!("finalizing commit started");
+                        let mut commit_store = commit_store.lock().unwrap();
+                        if let Some(commit) = commit_store.get_commit(&root) {
+                            tracing::trace!(
+                                "finalizing commit proper started {}",
+                                hex::encode(root.as_bytes())
+                            );
+                            let removed = commit.index.removed().clone();
+                            let instruction_costs = commit.instruction_costs;
+                            let io_result =
+                                finalize_commit(root, root_dir, commit);
+                            match &io_result {
+                                Ok(_) => tracing::trace!(
+                                    "finalizing commit proper finished: {:?}",
+                                    hex::encode(root.as_bytes())
+                                ),
+                                Err(e) => tracing::trace!(
+                                    "finalizing commit proper failed {:?}",
+                                    e
+                                ),
+                            }
+                            commit_store.remove_commit(&root);
+                            for contract in &removed {
+                                commit_store.remove_main_index(contract);
+                            }
+                            if io_result.is_ok() {
+                                commit_store.main_instruction_costs =
+                                    instruction_costs;
+                            }
+                            removing.lock().unwrap().remove(&root);
+                            tracing::trace!("finalizing commit finished");
+                            let _ = replier.send(io_result);
+                        } else {
+                            removing.lock().unwrap().remove(&root);
+                            tracing::trace!("finalizing commit finished");
+                            let _ = replier.send(Ok(()));
+                        }
+                    },
+                );
+            }
+            // Rebuild the memory of a contract in a commit, from the pages
+            // found elsewhere in the store.
+            //
+            // Pages may be taken from any commit, so the reconstruction holds
+            // all of them, keeping them from being deleted, finalized, or
+            // moved from under it.
+            Call::CommitReconstruct {
+                commit: root,
+                contract,
+                replier,
+            } => {
+                let root_dir = root_dir.to_path_buf();
+                let commit_store = commit_store.clone();
+
+                let mut exclusive: Vec<_> = commit_store
+                    .lock()
+                    .unwrap()
+                    .keys()
+                    .copied()
+                    .map(Exclusive::Commit)
+                    .collect();
+                if !exclusive.contains(&Exclusive::Commit(root)) {
+                    exclusive.push(Exclusive::Commit(root));
+                }
+
+                scheduler.submit_all(Priority::High, exclusive, move || {
+                    tracing::trace!("reconstructing memory started");
+                    let io_result = reconstruct_contract(
+                        root_dir,
+                        &commit_store,
+                        root,
+                        contract,
                     );
-                    let io_result = finalize_commit(root, root_dir, commit);
-                    match &io_result {
-                        Ok(_) => tracing::trace!(
-                            "finalizing commit proper finished: {:?}",
-                            hex::encode(root.as_bytes())
-                        ),
-                        Err(e) => tracing::trace!(
-                            "finalizing commit proper failed {:?}",
-                            e
-                        ),
-                    }
-                    commit_store.remove_commit(&root);
-                    tracing::trace!("finalizing commit finished");
+                    tracing::trace!("reconstructing memory finished");
                     let _ = replier.send(io_result);
+                });
+            }
+            // Fold the chain of commits between two commits into the later
+            // one, so it no longer depends on them.
+            //
+            // Every commit in the chain is read from, so the squash holds all
+            // of them, keeping them from being deleted, finalized, or moved
+            // from under it.
+            Call::CommitSquash { from, to, replier } => {
+                let mut chain = commit_store.lock().unwrap().chain(&to, &from);
+                chain.push(from);
+
+                {
+                    let removing = removing.lock().unwrap();
+                    if chain.iter().any(|hash| removing.contains(hash)) {
+                        let _ = replier.send(Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "Cannot squash a commit being removed",
+                        )));
+                        continue;
+                    }
+                }
+
+                let root_dir = root_dir.to_path_buf();
+                let commit_store = commit_store.clone();
+
+                chain.dedup();
+                scheduler.submit_all(
+                    Priority::Normal,
+                    chain.into_iter().map(Exclusive::Commit).collect(),
+                    move || {
+                        tracing::trace!("squashing commits started");
+                        let io_result =
+                            squash_commits(root_dir, &commit_store, from, to);
+                        tracing::trace!("squashing commits finished");
+                        let _ = replier.send(io_result);
+                    },
+                );
+            }
+            // Move the files of a commit to the cold directory, or back from
+            // it if none is given.
+            Call::CommitMove {
+                commit: root,
+                cold_dir,
+                replier,
+            } => {
+                let err = if !commit_store.lock().unwrap().contains_key(&root)
+                    || removing.lock().unwrap().contains(&root)
+                {
+                    Some(format!("No such commit: {}", hex::encode(root)))
+                } else if sessions.contains_key(&root) {
+                    Some(String::from("Cannot move a commit in use"))
                 } else {
-                    tracing::trace!("finalizing commit finished");
-                    let _ = replier.send(Ok(()));
+                    None
+                };
+                if let Some(err) = err {
+                    let _ = replier.send(Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        err,
+                    )));
+                    continue;
                 }
+
+                let main_dir = root_dir.join(MAIN_DIR);
+
+                scheduler.submit(
+                    Priority::Low,
+                    Exclusive::Commit(root),
+                    move || {
+                        tracing::trace!("moving commit started");
+                        let io_result = match cold_dir {
+                            Some(cold_dir) => {
+                                cold::cool_commit(&main_dir, &cold_dir, root)
+                            }
+                            None => cold::warm_commit(&main_dir, root),
+                        };
+                        tracing::trace!("moving commit finished");
+                        let _ = replier.send(io_result);
+                    },
+                );
             }
             // Increment the hold count of a commit to prevent it from deletion
             // on a `Call::CommitDelete`.
             Call::CommitHold { base, replier } => {
                 tracing::trace!("hold commit open session started");
                 let mut maybe_base = None;
-                if commit_store.lock().unwrap().contains_key(&base) {
+                if commit_store.lock().unwrap().contains_key(&base)
+                    && !removing.lock().unwrap().contains(&base)
+                {
                     maybe_base = Some(base);
 
                     match sessions.entry(base) {
@@ -934,10 +2385,14 @@ fn sync_loop<P: AsRef<Path>>(
                                 Vacant(_) => {}
                                 Occupied(entry) => {
                                     for replier in entry.remove() {
-                                        let io_result =
-                                            delete_commit_dir(root_dir, base);
-                                        commit_store.lock().unwrap().remove_commit(&base);
-                                        let _ = replier.send(io_result);
+                                        schedule_delete(
+                                            &scheduler,
+                                            &backend,
+                                            &commit_store,
+                                            &removing,
+                                            base,
+                                            replier,
+                                        );
                                     }
                                 }
                             }
@@ -948,16 +2403,49 @@ fn sync_loop<P: AsRef<Path>>(
             }
         }
     }
+
+    scheduler.shutdown();
 }
 
-fn write_commit<P: AsRef<Path>>(
-    root_dir: P,
+/// Schedule the deletion of the commit with the given `root`, replying to the
+/// `replier` once it's done.
+fn schedule_delete<B: StorageBackend + Send + 'static>(
+    scheduler: &Scheduler,
+    backend: &Arc<Mutex<B>>,
+    commit_store: &Arc<Mutex<CommitStore>>,
+    removing: &Arc<Mutex<BTreeSet<Hash>>>,
+    root: Hash,
+    replier: Replier<io::Result<()>>,
+) {
+    let backend = backend.clone();
+    let commit_store = commit_store.clone();
+    let removing = removing.clone();
+
+    removing.lock().unwrap().insert(root);
+    scheduler.submit(Priority::Low, Exclusive::Commit(root), move || {
+        tracing::trace!("delete commit started");
+        let io_result = backend.lock().unwrap().delete(root.into());
+        commit_store.lock().unwrap().remove_commit(&root);
+        removing.lock().unwrap().remove(&root);
+        tracing::trace!("delete commit finished");
+        let _ = replier.send(io_result);
+    });
+}
+
+/// Writes a commit to a fork of the given `store_backend`, joining it back
+/// once the commit is complete. The commit records the hash of the
+/// `instruction_costs` it was made with.
+#[allow(clippy::too_many_arguments)]
+fn write_commit<B: StorageBackend + Send>(
     commit_store: Arc<Mutex<CommitStore>>,
+    store_backend: &Mutex<B>,
+    write_pool: &ThreadPool,
     base: Option<Commit>,
     commit_contracts: BTreeMap<ContractId, ContractDataEntry>,
+    removed: BTreeSet<ContractId>,
+    elide_zero_pages: bool,
+    instruction_costs: [u8; 32],
 ) -> io::Result<Hash> {
-    let root_dir = root_dir.as_ref();
-
     let base_info = BaseInfo {
         maybe_base: base.as_ref().map(|base| *base.root()),
         ..Default::default()
@@ -982,16 +2470,32 @@ fn write_commit<P: AsRef<Path>>(
         base.unwrap_or(Commit::new(&commit_store, base_info.maybe_base));
     for (contract_id, contract_data) in &commit_contracts {
         if contract_data.is_new {
-            commit.remove_and_insert(*contract_id, &contract_data.memory);
+            commit.remove_and_insert(
+                *contract_id,
+                &contract_data.memory,
+                elide_zero_pages,
+            );
         } else {
-            commit.insert(*contract_id, &contract_data.memory);
+            contract_data.insert_into(
+                &mut commit,
+                *contract_id,
+                elide_zero_pages,
+            );
         }
     }
+    // Only contracts that are in the commit's chain leave a removal behind
+    let removed: Vec<ContractId> = removed
+        .into_iter()
+        .filter(|contract| commit.index_get(contract).is_some())
+        .collect();
+    for contract in &removed {
+        commit.remove_contract(contract);
+    }
 
     let root = *commit.root();
-    let root_hex = hex::encode(root);
     commit.maybe_hash = Some(root);
     commit.base = base_info.maybe_base;
+    commit.instruction_costs = Some(instruction_costs);
 
     // Don't write the commit if it already exists on disk. This may happen if
     // the same transactions on the same base commit for example.
@@ -999,166 +2503,615 @@ fn write_commit<P: AsRef<Path>>(
         return Ok(root);
     }
 
-    write_commit_inner(root_dir, &commit, commit_contracts, root_hex, base_info)
-        .map(|_| {
+    let mut contracts: Vec<ContractId> =
+        commit_contracts.keys().copied().collect();
+    contracts.extend(&removed);
+    let mut backend = store_backend.lock().unwrap().fork();
+
+    let written = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        write_commit_inner(
+            &mut backend,
+            write_pool,
+            &commit,
+            commit_contracts,
+            &removed,
+            base_info,
+            elide_zero_pages,
+        )
+    }));
+    // A panic while writing leaves a partially written commit as much as an
+    // error does, so it is cleaned up before being resumed.
+    let written = written.unwrap_or_else(|payload| {
+        backend.remove_partial(root.into(), &contracts);
+        panic::resume_unwind(payload)
+    });
+
+    match written {
+        Ok(()) => {
+            store_backend.lock().unwrap().join(backend);
             commit_store.lock().unwrap().insert_commit(root, commit);
-            root
-        })
+            Ok(root)
+        }
+        Err(err) => {
+            // A partially written commit would fail to load on restart, so
+            // anything written is removed before reporting the error.
+            backend.remove_partial(root.into(), &contracts);
+
+            if is_out_of_space(&err) {
+                return Err(io::Error::new(err.kind(), StoreFull(Some(err))));
+            }
+            Err(err)
+        }
+    }
 }
 
-/// Writes a commit to disk.
-fn write_commit_inner<P: AsRef<Path>, S: AsRef<str>>(
-    root_dir: P,
+/// Marks an error as caused by the store running out of disk space, either
+/// while writing a commit, or by having less free space than required before
+/// starting one.
+#[derive(Debug)]
+pub struct StoreFull(Option<io::Error>);
+
+impl StoreFull {
+    /// Returns true if the given error is marked as [`StoreFull`].
+    pub fn is(err: &io::Error) -> bool {
+        err.get_ref().map_or(false, |err| err.is::<StoreFull>())
+    }
+}
+
+impl fmt::Display for StoreFull {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            Some(err) => write!(f, "Store is out of disk space: {err}"),
+            None => write!(f, "Store has less free disk space than required"),
+        }
+    }
+}
+
+impl std::error::Error for StoreFull {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.as_ref().map(|err| err as _)
+    }
+}
+
+/// Marks an error as caused by deleting a commit that other commits were
+/// written on top of, listing them.
+#[derive(Debug)]
+pub struct CommitHasDependents(pub Vec<Hash>);
+
+impl CommitHasDependents {
+    /// Returns the dependents listed in the given error, if it is marked as
+    /// [`CommitHasDependents`].
+    pub fn get(err: &io::Error) -> Option<&[Hash]> {
+        err.get_ref()?
+            .downcast_ref::<CommitHasDependents>()
+            .map(|err| &err.0[..])
+    }
+}
+
+impl fmt::Display for CommitHasDependents {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Commit has {} dependent commits", self.0.len())
+    }
+}
+
+impl std::error::Error for CommitHasDependents {}
+
+/// Returns true if the error is the operating system reporting the disk is
+/// full.
+fn is_out_of_space(err: &io::Error) -> bool {
+    // ENOSPC on Linux and macOS
+    #[cfg(unix)]
+    const OUT_OF_SPACE: &[i32] = &[28];
+    // ERROR_HANDLE_DISK_FULL and ERROR_DISK_FULL
+    #[cfg(windows)]
+    const OUT_OF_SPACE: &[i32] = &[39, 112];
+    #[cfg(not(any(unix, windows)))]
+    const OUT_OF_SPACE: &[i32] = &[];
+
+    err.raw_os_error()
+        .map_or(false, |code| OUT_OF_SPACE.contains(&code))
+}
+
+/// Returns true if the given `page` contains only zeroes.
+fn is_zero_page(page: &[u8]) -> bool {
+    page.iter().all(|byte| *byte == 0)
+}
+
+/// Errors with [`StoreFull`] if there is less free space than `min_free_space`
+/// on the disk the store is in.
+fn check_free_space(root_dir: &Path, min_free_space: u64) -> io::Result<()> {
+    if min_free_space == 0 {
+        return Ok(());
+    }
+
+    match fs2::available_space(root_dir) {
+        Ok(available) if available < min_free_space => {
+            Err(io::Error::new(io::ErrorKind::Other, StoreFull(None)))
+        }
+        Ok(_) => Ok(()),
+        Err(err) => {
+            tracing::warn!("failed checking free disk space: {err}");
+            Ok(())
+        }
+    }
+}
+
+/// Writes a commit to the given `backend`.
+///
+/// Commits changing many contracts have them split between the threads of the
+/// `write_pool`, each writing to its own fork of the `backend`. The base is
+/// only linked once every contract is written, with the contracts hinted in
+/// the same order regardless of how many threads wrote them. The `removed`
+/// contracts are hinted last.
+///
+/// Should a thread fail or panic, what every fork wrote is still handed back
+/// to the `backend` before the error is returned or the panic resumed, so
+/// that it can be removed.
+fn write_commit_inner<B: StorageBackend + Send>(
+    backend: &mut B,
+    write_pool: &ThreadPool,
     commit: &Commit,
     commit_contracts: BTreeMap<ContractId, ContractDataEntry>,
-    commit_id: S,
+    removed: &[ContractId],
     mut base_info: BaseInfo,
+    elide_zero_pages: bool,
 ) -> io::Result<()> {
-    let root_dir = root_dir.as_ref();
+    let root: [u8; 32] = (*commit.root()).into();
 
-    struct Directories {
-        main_dir: PathBuf,
-        bytecode_main_dir: PathBuf,
-        memory_main_dir: PathBuf,
-        leaf_main_dir: PathBuf,
+    // The elements are serialized up front, since the commit itself can't be
+    // shared between threads.
+    let mut contracts = Vec::with_capacity(commit_contracts.len());
+    for (contract, contract_data) in &commit_contracts {
+        let element = match commit.index.get(contract) {
+            Some(element) => Some(export::element_to_bytes(element)?),
+            None => None,
+        };
+        contracts.push((*contract, contract_data, element));
     }
 
-    let directories = {
-        let main_dir = root_dir.join(MAIN_DIR);
-        fs::create_dir_all(&main_dir)?;
-
-        let bytecode_main_dir = main_dir.join(BYTECODE_DIR);
-        fs::create_dir_all(&bytecode_main_dir)?;
+    let threads = (contracts.len() / MIN_CONTRACTS_PER_WRITE_THREAD)
+        .clamp(1, write_pool.current_num_threads());
 
-        let memory_main_dir = main_dir.join(MEMORY_DIR);
-        fs::create_dir_all(&memory_main_dir)?;
-
-        let leaf_main_dir = main_dir.join(LEAF_DIR);
-        fs::create_dir_all(&leaf_main_dir)?;
+    let hints = if threads == 1 {
+        write_contracts(backend, root, &contracts, elide_zero_pages)?
+    } else {
+        let chunk_len = contracts.len().div_ceil(threads);
+        let chunks: Vec<_> = contracts
+            .chunks(chunk_len)
+            .map(|chunk| (backend.fork(), chunk))
+            .collect();
+
+        // The results are collected in the order of the chunks.
+        let results: Vec<_> = write_pool.install(|| {
+            chunks
+                .into_par_iter()
+                .map(|(mut fork, chunk)| {
+                    // Panics are caught, so that the fork is returned with
+                    // what it wrote either way.
+                    let result =
+                        panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                            write_contracts(
+                                &mut fork,
+                                root,
+                                chunk,
+                                elide_zero_pages,
+                            )
+                        }));
+                    (fork, result)
+                })
+                .collect()
+        });
 
-        Directories {
-            main_dir,
-            bytecode_main_dir,
-            memory_main_dir,
-            leaf_main_dir,
+        // Every fork is joined before any error is returned or panic is
+        // resumed, so that everything they wrote can be removed.
+        let mut hints = Vec::with_capacity(contracts.len());
+        let mut first_err = None;
+        let mut first_panic = None;
+        for (fork, result) in results {
+            backend.join(fork);
+            match result {
+                Ok(Ok(chunk_hints)) => hints.extend(chunk_hints),
+                Ok(Err(err)) => {
+                    first_err.get_or_insert(err);
+                }
+                Err(payload) => {
+                    first_panic.get_or_insert(payload);
+                }
+            }
+        }
+        if let Some(payload) = first_panic {
+            panic::resume_unwind(payload);
         }
+        if let Some(err) = first_err {
+            return Err(err);
+        }
+
+        hints
     };
+    base_info.contract_hints.extend(hints);
 
-    // Write the dirty pages contracts of contracts to disk.
-    for (contract, contract_data) in &commit_contracts {
-        let contract_hex = hex::encode(contract);
+    for contract in removed {
+        backend.put_removal(root, *contract)?;
+        base_info.contract_hints.push(*contract);
+    }
 
-        let memory_main_dir = directories.memory_main_dir.join(&contract_hex);
-        fs::create_dir_all(&memory_main_dir)?;
+    if let Some(instruction_costs) = commit.instruction_costs {
+        backend.put_instruction_costs(root, instruction_costs)?;
+    }
 
-        let leaf_main_dir = directories.leaf_main_dir.join(&contract_hex);
-        fs::create_dir_all(&leaf_main_dir)?;
+    let base_info_bytes = backend::base_info_to_bytes(&base_info)?;
+    let mut tree_pos = Vec::new();
+    commit.contracts_merkle.tree_pos().marshall(&mut tree_pos)?;
+    backend.link_base(root, &base_info_bytes, &tree_pos)
+}
 
-        let mut pages = BTreeSet::new();
+/// Writes the given `contracts` of the commit with the given `root` to the
+/// `backend`, each with its data and serialized element, if it has one.
+///
+/// Returns the contracts that were deployed or written to, in order.
+fn write_contracts<B: StorageBackend>(
+    backend: &mut B,
+    root: [u8; 32],
+    contracts: &[(ContractId, &ContractDataEntry, Option<Vec<u8>>)],
+    elide_zero_pages: bool,
+) -> io::Result<Vec<ContractId>> {
+    let mut hints = Vec::new();
+
+    for (contract, contract_data, element) in contracts {
+        // A contract read from a fallback commit is left out of the commit
+        // unless its memory changed.
+        if contract_data.fallback_pages.is_some() && !contract_data.is_written()
+        {
+            continue;
+        }
 
         let mut dirty = false;
-        // Write dirty pages and keep track of the page indices.
-        for (dirty_page, _, page_index) in contract_data.memory.dirty_pages() {
-            let page_path: PathBuf =
-                page_path_main(&memory_main_dir, *page_index, &commit_id)?;
-            fs::write(page_path, dirty_page)?;
-            pages.insert(*page_index);
+        for (dirty_page, _, page_index) in
+            contract_data.memory.all_dirty_pages()
+        {
+            // Zero pages are left out of the contract's index, and read back
+            // as zeroes without needing a file.
             dirty = true;
+            if elide_zero_pages && is_zero_page(dirty_page) {
+                continue;
+            }
+            backend.put_memory_page(
+                root,
+                *contract,
+                *page_index,
+                dirty_page,
+            )?;
         }
 
-        let bytecode_main_path =
-            directories.bytecode_main_dir.join(&contract_hex);
-        let module_main_path =
-            bytecode_main_path.with_extension(OBJECTCODE_EXTENSION);
-        let metadata_main_path =
-            bytecode_main_path.with_extension(METADATA_EXTENSION);
+        // A contract written to after being read from a fallback commit has
+        // none of its pages in this commit's chain, so the ones left clean
+        // are written as well.
+        match &contract_data.fallback_pages {
+            Some(fallback_pages) if dirty => {
+                let memory = &contract_data.memory;
+                let dirty_pages: BTreeSet<usize> = memory
+                    .all_dirty_pages()
+                    .map(|(_, _, page_index)| *page_index)
+                    .collect();
+                for page_index in fallback_pages.difference(&dirty_pages) {
+                    let page = &memory[page_index * PAGE_SIZE..][..PAGE_SIZE];
+                    if elide_zero_pages && is_zero_page(page) {
+                        continue;
+                    }
+                    backend.put_memory_page(
+                        root,
+                        *contract,
+                        *page_index,
+                        page,
+                    )?;
+                }
+            }
+            _ => {}
+        }
 
         // If the contract is new, we write the bytecode, module, and metadata
-        // files to disk.
+        // as well.
         if contract_data.is_new {
-            // we write them to the main location
-            fs::write(bytecode_main_path, &contract_data.bytecode)?;
-            fs::write(module_main_path, &contract_data.module.serialize())?;
-            fs::write(metadata_main_path, &contract_data.metadata)?;
+            backend.put_bytecode(
+                *contract,
+                contract_data.bytecode.as_ref(),
+                &contract_data.module.serialize(),
+                contract_data.metadata.as_ref(),
+            )?;
             dirty = true;
         }
-        if dirty {
-            base_info.contract_hints.push(*contract);
+
+        if let Some(element) = element {
+            backend.put_element(root, *contract, element)?;
         }
-    }
 
-    tracing::trace!("persisting index started");
-    for (contract_id, element) in commit.index.iter() {
-        if commit_contracts.contains_key(contract_id) {
-            let element_dir_path = directories
-                .leaf_main_dir
-                .join(hex::encode(contract_id.as_bytes()))
-                .join(commit_id.as_ref());
-            let element_file_path = element_dir_path.join(ELEMENT_FILE);
-            create_dir_all(element_dir_path)?;
-            let element_bytes =
-                rkyv::to_bytes::<_, 128>(element).map_err(|err| {
-                    io::Error::new(
-                        io::ErrorKind::InvalidData,
-                        format!("Failed serializing element file: {err}"),
-                    )
-                })?;
-            fs::write(&element_file_path, element_bytes)?;
+        if dirty {
+            hints.push(*contract);
         }
     }
-    tracing::trace!("persisting index finished");
 
-    let base_main_path =
-        base_path_main(&directories.main_dir, commit_id.as_ref())?;
-    let base_info_bytes =
-        rkyv::to_bytes::<_, 128>(&base_info).map_err(|err| {
+    Ok(hints)
+}
+
+/// Rebuilds the memory of the given `contract` in the commit with the given
+/// `root`.
+fn reconstruct_contract<P: AsRef<Path>>(
+    root_dir: P,
+    commit_store: &Arc<Mutex<CommitStore>>,
+    root: Hash,
+    contract: ContractId,
+) -> io::Result<usize> {
+    let commit = commit_store
+        .lock()
+        .unwrap()
+        .get_commit(&root)
+        .cloned()
+        .ok_or_else(|| {
             io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Failed serializing base info file: {err}"),
+                io::ErrorKind::NotFound,
+                format!("No such commit: {}", hex::encode(root)),
             )
         })?;
-    fs::write(base_main_path, base_info_bytes)?;
 
-    let tree_pos_opt_path =
-        tree_pos_path_main(&directories.main_dir, commit_id.as_ref())?;
+    let element = commit.index_get(&contract).cloned().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No such contract: {}", hex::encode(contract)),
+        )
+    })?;
+
+    let main_dir = root_dir.as_ref().join(MAIN_DIR);
+    reconstruct_memory(&main_dir, Some(root), &contract, &element)
+}
 
-    let f = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(tree_pos_opt_path)?;
-    let mut buf_f = BufWriter::new(f);
-    commit.contracts_merkle.tree_pos().marshall(&mut buf_f)?;
+/// Restores the pages of the given `contract` that are missing or corrupt as
+/// of the given `commit`, returning how many were restored.
+///
+/// A page is taken from the first of the commit's ancestors, the main memory
+/// directory, or any other commit, whose copy matches the hash in the
+/// contract's `element`. It is written to the directory of the closest commit
+/// in the chain that touched the contract, since that is where it is looked
+/// for first.
+fn reconstruct_memory(
+    main_dir: &Path,
+    commit: Option<Hash>,
+    contract: &ContractId,
+    element: &ContractIndexElement,
+) -> io::Result<usize> {
+    let contract_hex = hex::encode(contract);
+    let memory_dir = main_dir.join(MEMORY_DIR).join(&contract_hex);
+
+    // The commit and its ancestors, marked with whether they touched the
+    // contract.
+    let mut chain = Vec::new();
+    let mut maybe_commit = commit;
+    while let Some(hash) = maybe_commit {
+        let hash_hex = hex::encode(hash);
+        let base_info_path = main_dir.join(&hash_hex).join(BASE_FILE);
+        let Ok(base_info) = base_from_path(base_info_path) else {
+            break;
+        };
+        chain.push((hash_hex, base_info.contract_hints.contains(contract)));
+        maybe_commit = base_info.maybe_base;
+    }
 
-    Ok(())
+    let target_dir = chain.iter().find(|(_, touched)| *touched).map_or_else(
+        || memory_dir.clone(),
+        |(hash_hex, _)| memory_dir.join(hash_hex),
+    );
+
+    let mut source_dirs: Vec<PathBuf> = chain
+        .iter()
+        .map(|(hash_hex, _)| memory_dir.join(hash_hex))
+        .collect();
+    source_dirs.push(memory_dir.clone());
+    if memory_dir.is_dir() {
+        for entry in fs::read_dir(&memory_dir)? {
+            let path = entry?.path();
+            if path.is_dir() && !source_dirs.contains(&path) {
+                source_dirs.push(path);
+            }
+        }
+    }
+
+    let read_page = |path: PathBuf, page_index: usize| {
+        let page = fs::read(path).ok()?;
+        element
+            .tree()
+            .contains_page(page_index as u64, &page)
+            .then_some(page)
+    };
+
+    let mut restored = 0;
+
+    for page_index in element.page_indices().iter().copied() {
+        let path = ContractSession::find_page(
+            page_index,
+            commit,
+            &memory_dir,
+            main_dir,
+        )
+        .unwrap_or_else(|| page_path(&memory_dir, page_index));
+
+        if read_page(path, page_index).is_some() {
+            continue;
+        }
+
+        let page = source_dirs
+            .iter()
+            .find_map(|dir| read_page(page_path(dir, page_index), page_index))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Cannot reconstruct page {page_index} of contract: {contract_hex}"
+                    ),
+                )
+            })?;
+
+        fs::create_dir_all(&target_dir)?;
+        fs::write(page_path(&target_dir, page_index), page)?;
+        restored += 1;
+    }
+
+    Ok(restored)
 }
 
-/// Delete the given commit's directory.
-fn delete_commit_dir<P: AsRef<Path>>(
+/// Copies the pages and elements that the commit `to` finds in its ancestors
+/// up to `from` into its own directories, and rebases it onto `from`'s base.
+fn squash_commits<P: AsRef<Path>>(
     root_dir: P,
-    root: Hash,
+    commit_store: &Arc<Mutex<CommitStore>>,
+    from: Hash,
+    to: Hash,
 ) -> io::Result<()> {
-    let root = hex::encode(root);
-    let root_main_dir = root_dir.as_ref().join(MAIN_DIR);
-    let commit_dir = root_main_dir.join(&root);
-    if commit_dir.exists() {
-        let base_info_path = commit_dir.join(BASE_FILE);
-        let base_info = base_from_path(base_info_path)?;
-        for contract_hint in base_info.contract_hints {
-            let contract_hex = hex::encode(contract_hint);
-            let commit_mem_path = root_main_dir
-                .join(MEMORY_DIR)
-                .join(&contract_hex)
-                .join(&root);
-            fs::remove_dir_all(&commit_mem_path)?;
-            let commit_leaf_path =
-                root_main_dir.join(LEAF_DIR).join(&contract_hex).join(&root);
-            fs::remove_dir_all(&commit_leaf_path)?;
+    let main_dir = root_dir.as_ref().join(MAIN_DIR);
+
+    let mut commit = commit_store
+        .lock()
+        .unwrap()
+        .get_commit(&to)
+        .cloned()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No such commit: {}", hex::encode(to)),
+            )
+        })?;
+
+    if from == to {
+        return Ok(());
+    }
+
+    let to_hex = hex::encode(to);
+    let base_info_path = main_dir.join(&to_hex).join(BASE_FILE);
+    let mut base_info = base_from_path(&base_info_path)?;
+
+    // The ancestors of `to`, from the closest one down to `from`, together
+    // with the contracts they touched.
+    let mut chain = Vec::new();
+    let mut maybe_commit = base_info.maybe_base;
+    let new_base = loop {
+        let hash = match maybe_commit {
+            Some(hash) => hash,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Commit {} is not an ancestor of {to_hex}",
+                        hex::encode(from)
+                    ),
+                ))
+            }
+        };
+        let hash_hex = hex::encode(hash);
+        let info = base_from_path(main_dir.join(&hash_hex).join(BASE_FILE))?;
+        chain.push((hash_hex, info.contract_hints));
+        if hash == from {
+            break info.maybe_base;
+        }
+        maybe_commit = info.maybe_base;
+    };
+
+    // Create the directories of the newly touched contracts, and record them
+    // before copying, so a failure midway leaves a commit that can be deleted.
+    let mut contracts = Vec::new();
+    for (_, hints) in &chain {
+        for contract in hints {
+            if !base_info.contract_hints.contains(contract) {
+                let contract_hex = hex::encode(contract);
+                fs::create_dir_all(
+                    main_dir.join(MEMORY_DIR).join(&contract_hex).join(&to_hex),
+                )?;
+                fs::create_dir_all(
+                    main_dir.join(LEAF_DIR).join(&contract_hex).join(&to_hex),
+                )?;
+                base_info.contract_hints.push(*contract);
+                contracts.push(*contract);
+            }
+        }
+    }
+    write_base_info(&base_info_path, &base_info)?;
+
+    // Copy whatever `to` would find in its closest ancestor. A contract
+    // removed along the way is found in none of the ones further down.
+    for (hash_hex, hints) in &chain {
+        for contract in hints {
+            let contract_hex = hex::encode(contract);
+
+            let leaf_dir = main_dir.join(LEAF_DIR).join(&contract_hex);
+            let dst_leaf_dir = leaf_dir.join(&to_hex);
+            if dst_leaf_dir.join(REMOVED_FILE).is_file() {
+                continue;
+            }
+            let src_leaf_dir = leaf_dir.join(hash_hex);
+            if src_leaf_dir.join(REMOVED_FILE).is_file() {
+                if !dst_leaf_dir.join(ELEMENT_FILE).exists() {
+                    fs::write(dst_leaf_dir.join(REMOVED_FILE), [])?;
+                }
+                continue;
+            }
+
+            let memory_dir = main_dir.join(MEMORY_DIR).join(&contract_hex);
+            let src_dir = memory_dir.join(hash_hex);
+            let dst_dir = memory_dir.join(&to_hex);
+            if src_dir.is_dir() {
+                for entry in fs::read_dir(&src_dir)? {
+                    let filename = entry?.file_name();
+                    let dst_path = dst_dir.join(&filename);
+                    if !dst_path.exists() {
+                        fs::copy(src_dir.join(&filename), dst_path)?;
+                    }
+                }
+            }
+
+            let src_path = src_leaf_dir.join(ELEMENT_FILE);
+            let dst_path = dst_leaf_dir.join(ELEMENT_FILE);
+            if src_path.is_file() && !dst_path.exists() {
+                fs::copy(src_path, dst_path)?;
+            }
+        }
+    }
+
+    // The elements found through the chain are now in the commit's own index,
+    // as are the removals.
+    let elements: Vec<_> = contracts
+        .into_iter()
+        .map(|contract| (contract, commit.index_get(&contract).cloned()))
+        .collect();
+    for (contract, element) in elements {
+        match element {
+            Some(element) => {
+                commit.index.insert_contract_index(&contract, element)
+            }
+            None => commit.index.mark_removed(&contract),
         }
-        fs::remove_dir_all(&commit_dir)?;
     }
+
+    base_info.maybe_base = new_base;
+    write_base_info(&base_info_path, &base_info)?;
+
+    commit.base = new_base;
+    commit_store.lock().unwrap().insert_commit(to, commit);
+
     Ok(())
 }
 
+/// Writes the base info to a temporary file first, and then moves it into
+/// place, so sessions walking the chain never read a partial file.
+fn write_base_info(path: &Path, base_info: &BaseInfo) -> io::Result<()> {
+    let bytes = rkyv::to_bytes::<_, 128>(base_info).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed serializing base info file: {err}"),
+        )
+    })?;
+
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(tmp_path, path)
+}
+
 /// Finalize commit
 fn finalize_commit<P: AsRef<Path>>(
     root: Hash,
@@ -1166,6 +3119,11 @@ fn finalize_commit<P: AsRef<Path>>(
     _commit: &Commit,
 ) -> io::Result<()> {
     let main_dir = root_dir.as_ref().join(MAIN_DIR);
+
+    // The files of a cold commit are moved into the main directory, so they
+    // must be brought back first.
+    cold::warm_commit(&main_dir, root)?;
+
     let root = hex::encode(root);
     let commit_path = main_dir.join(&root);
     let base_info_path = commit_path.join(BASE_FILE);
@@ -1178,27 +3136,74 @@ fn finalize_commit<P: AsRef<Path>>(
         let src_path =
             main_dir.join(MEMORY_DIR).join(&contract_hex).join(&root);
         let dst_path = main_dir.join(MEMORY_DIR).join(&contract_hex);
-        for entry in fs::read_dir(&src_path)? {
-            let filename = entry?.file_name();
-            let src_file_path = src_path.join(&filename);
-            let dst_file_path = dst_path.join(&filename);
-            if src_file_path.is_file() {
-                fs::rename(src_file_path, dst_file_path)?;
+        // The directory is missing if all pages written were zero pages.
+        if src_path.is_dir() {
+            for entry in fs::read_dir(&src_path)? {
+                let filename = entry?.file_name();
+                let src_file_path = src_path.join(&filename);
+                let dst_file_path = dst_path.join(&filename);
+                if src_file_path.is_file() {
+                    fs::rename(src_file_path, dst_file_path)?;
+                }
             }
+            fs::remove_dir(&src_path)?;
         }
-        fs::remove_dir(&src_path)?;
         // LEAF
         let src_leaf_path =
             main_dir.join(LEAF_DIR).join(&contract_hex).join(&root);
-        let dst_leaf_path = main_dir.join(LEAF_DIR).join(contract_hex);
+        let dst_leaf_path = main_dir.join(LEAF_DIR).join(&contract_hex);
         let src_leaf_file_path = src_leaf_path.join(ELEMENT_FILE);
         let dst_leaf_file_path = dst_leaf_path.join(ELEMENT_FILE);
         if src_leaf_file_path.is_file() {
             fs::rename(src_leaf_file_path, dst_leaf_file_path)?;
+        } else if src_leaf_path.join(REMOVED_FILE).is_file() {
+            // A removed contract leaves nothing behind in the main directories
+            if dst_leaf_file_path.is_file() {
+                fs::remove_file(dst_leaf_file_path)?;
+            }
+            if dst_path.is_dir() {
+                for entry in fs::read_dir(&dst_path)? {
+                    let path = entry?.path();
+                    if path.is_file() {
+                        fs::remove_file(path)?;
+                    }
+                }
+            }
+            fs::remove_file(src_leaf_path.join(REMOVED_FILE))?;
+            fs::remove_dir(&src_leaf_path)?;
+
+            // Its bytecode is only removed once no other commit holds it
+            if fs::read_dir(&dst_leaf_path)?.next().is_none() {
+                fs::remove_dir(&dst_leaf_path)?;
+                let _ = fs::remove_dir(&dst_path);
+
+                let bytecode_path =
+                    main_dir.join(BYTECODE_DIR).join(&contract_hex);
+                for path in [
+                    bytecode_path.with_extension(OBJECTCODE_EXTENSION),
+                    bytecode_path.with_extension(METADATA_EXTENSION),
+                    bytecode_path,
+                ] {
+                    if path.is_file() {
+                        fs::remove_file(path)?;
+                    }
+                }
+            }
+            continue;
         }
         fs::remove_dir(src_leaf_path)?;
     }
 
+    // The finalized state was made with the same instruction costs as the
+    // commit, or with unknown ones if the commit predates recording them.
+    let instruction_costs_path = commit_path.join(INSTRUCTION_COSTS_FILE);
+    let main_instruction_costs_path = main_dir.join(INSTRUCTION_COSTS_FILE);
+    if instruction_costs_path.is_file() {
+        fs::rename(instruction_costs_path, main_instruction_costs_path)?;
+    } else if main_instruction_costs_path.is_file() {
+        fs::remove_file(main_instruction_costs_path)?;
+    }
+
     fs::remove_file(base_info_path)?;
     let _ = fs::remove_file(tree_pos_path);
     let _ = fs::remove_file(tree_pos_opt_path);
@@ -0,0 +1,625 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Backends commits are persisted to.
+//!
+//! A commit is written to a backend as the bytecode of each new contract, the
+//! dirty memory pages and index element of each contract it changed, the
+//! contracts it removed, the hash of the instruction costs it was made with,
+//! and finally the information linking it to its base.
+//! Everything is passed to the backend already serialized, leaving it free to
+//! store the bytes however it sees fit.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use piecrust_uplink::ContractId;
+
+use crate::root::Root;
+use crate::store::session::ContractSession;
+use crate::store::tree::{BaseInfo, Hash};
+use crate::store::{
+    base_from_path, base_path_main, bytecode, cold, export, page_path,
+    page_path_main, tree_pos_path_main, Commit, BASE_FILE, BYTECODE_DIR,
+    CODE_DIR, ELEMENT_FILE, INSTRUCTION_COSTS_FILE, LEAF_DIR, MAIN_DIR,
+    MEMORY_DIR, METADATA_EXTENSION, OBJECTCODE_EXTENSION, REMOVED_FILE,
+};
+
+/// A place commits can be persisted to.
+///
+/// Methods are called in the order a commit is written, with [`link_base`]
+/// always called last. A commit is not complete until it is.
+///
+/// A commit is written to a [`fork`] of the backend, which is [`join`]ed back
+/// once the commit is complete, or has [`remove_partial`] called on it should
+/// the commit fail. Commits changing many contracts are split between forks
+/// written to from multiple threads.
+///
+/// [`link_base`]: StorageBackend::link_base
+/// [`fork`]: StorageBackend::fork
+/// [`join`]: StorageBackend::join
+/// [`remove_partial`]: StorageBackend::remove_partial
+pub trait StorageBackend {
+    /// Returns a backend storing commits in the same place, to be written to
+    /// - possibly from another thread - and [`join`]ed back once done.
+    ///
+    /// [`join`]: StorageBackend::join
+    fn fork(&self) -> Self
+    where
+        Self: Sized;
+
+    /// Takes over what was written through the given `fork`.
+    fn join(&mut self, fork: Self)
+    where
+        Self: Sized;
+
+    /// Removes whatever was written to this backend of a `commit` that failed
+    /// to be written entirely, touching only the shared data - such as
+    /// bytecode - created in the process. The `contracts` are the ones the
+    /// commit changed or removed.
+    fn remove_partial(&mut self, commit: [u8; 32], contracts: &[ContractId]);
+
+    /// Stores the bytecode of a newly deployed `contract`, together with its
+    /// compiled module and metadata. These are shared by every commit the
+    /// contract is part of.
+    fn put_bytecode(
+        &mut self,
+        contract: ContractId,
+        bytecode: &[u8],
+        module: &[u8],
+        metadata: &[u8],
+    ) -> io::Result<()>;
+
+    /// Stores a memory `page` of the given `contract` as of the given
+    /// `commit`.
+    fn put_memory_page(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+        page_index: usize,
+        page: &[u8],
+    ) -> io::Result<()>;
+
+    /// Stores the serialized index `element` of the given `contract` as of the
+    /// given `commit`.
+    fn put_element(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+        element: &[u8],
+    ) -> io::Result<()>;
+
+    /// Records that the given `contract` was removed as of the given
+    /// `commit`, hiding it in the commit's bases.
+    fn put_removal(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+    ) -> io::Result<()>;
+
+    /// Stores the `hash` of the [`InstructionCosts`] the `commit` was made
+    /// with, so that it is not built upon with different ones.
+    ///
+    /// [`InstructionCosts`]: crate::InstructionCosts
+    fn put_instruction_costs(
+        &mut self,
+        commit: [u8; 32],
+        hash: [u8; 32],
+    ) -> io::Result<()>;
+
+    /// Stores the serialized information linking the `commit` to its base,
+    /// together with the positions of the leaves of its contracts tree.
+    fn link_base(
+        &mut self,
+        commit: [u8; 32],
+        base_info: &[u8],
+        tree_pos: &[u8],
+    ) -> io::Result<()>;
+
+    /// Deletes everything stored for the given `commit`, except for the
+    /// bytecode shared with other commits.
+    fn delete(&mut self, commit: [u8; 32]) -> io::Result<()>;
+}
+
+/// Stores commits in the directory layout read by the [`VM`] on startup.
+///
+/// [`VM`]: crate::VM
+#[derive(Debug)]
+pub struct FsBackend {
+    main_dir: PathBuf,
+    created: Vec<PathBuf>,
+}
+
+impl FsBackend {
+    /// Creates a backend storing commits under the given `root_dir`.
+    pub fn new<P: AsRef<Path>>(root_dir: P) -> Self {
+        Self {
+            main_dir: root_dir.as_ref().join(MAIN_DIR),
+            created: Vec::new(),
+        }
+    }
+}
+
+impl StorageBackend for FsBackend {
+    fn fork(&self) -> Self {
+        Self {
+            main_dir: self.main_dir.clone(),
+            created: Vec::new(),
+        }
+    }
+
+    // The files created through the fork are taken over, so they are removed
+    // along with the rest should the commit fail.
+    fn join(&mut self, fork: Self) {
+        self.created.extend(fork.created);
+    }
+
+    fn remove_partial(&mut self, commit: [u8; 32], contracts: &[ContractId]) {
+        let root_hex = hex::encode(commit);
+
+        for contract in contracts {
+            let contract_hex = hex::encode(contract);
+            let _ = fs::remove_dir_all(
+                self.main_dir
+                    .join(MEMORY_DIR)
+                    .join(&contract_hex)
+                    .join(&root_hex),
+            );
+            let _ = fs::remove_dir_all(
+                self.main_dir
+                    .join(LEAF_DIR)
+                    .join(&contract_hex)
+                    .join(&root_hex),
+            );
+        }
+        for path in self.created.drain(..) {
+            let _ = fs::remove_file(path);
+        }
+        let _ = fs::remove_dir_all(self.main_dir.join(root_hex));
+    }
+
+    fn put_bytecode(
+        &mut self,
+        contract: ContractId,
+        bytecode: &[u8],
+        module: &[u8],
+        metadata: &[u8],
+    ) -> io::Result<()> {
+        let bytecode_dir = self.main_dir.join(BYTECODE_DIR);
+        let code_dir = self.main_dir.join(CODE_DIR);
+        fs::create_dir_all(&bytecode_dir)?;
+        fs::create_dir_all(&code_dir)?;
+
+        let bytecode_path = bytecode_dir.join(hex::encode(contract));
+        let module_path = bytecode_path.with_extension(OBJECTCODE_EXTENSION);
+        let metadata_path = bytecode_path.with_extension(METADATA_EXTENSION);
+
+        // The bytecode and module are stored once for all contracts deployed
+        // with the same bytecode, and linked to from the contract's own files.
+        let shared_path = bytecode::shared_path(&code_dir, bytecode);
+
+        // Files that already exist may be shared with other commits, so only
+        // the ones created here are removed should the commit fail.
+        bytecode::link_shared(
+            &shared_path,
+            bytecode,
+            &bytecode_path,
+            &mut self.created,
+        )?;
+        bytecode::link_shared(
+            &shared_path.with_extension(OBJECTCODE_EXTENSION),
+            module,
+            &module_path,
+            &mut self.created,
+        )?;
+
+        if !metadata_path.exists() {
+            self.created.push(metadata_path.clone());
+        }
+        fs::write(metadata_path, metadata)
+    }
+
+    fn put_memory_page(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+        page_index: usize,
+        page: &[u8],
+    ) -> io::Result<()> {
+        let memory_dir =
+            self.main_dir.join(MEMORY_DIR).join(hex::encode(contract));
+        let path = page_path_main(memory_dir, page_index, hex::encode(commit))?;
+        fs::write(path, page)
+    }
+
+    fn put_element(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+        element: &[u8],
+    ) -> io::Result<()> {
+        let contract_hex = hex::encode(contract);
+
+        // Contracts always have a memory directory, even when none of their
+        // pages were written in this commit.
+        fs::create_dir_all(self.main_dir.join(MEMORY_DIR).join(&contract_hex))?;
+
+        let element_dir = self
+            .main_dir
+            .join(LEAF_DIR)
+            .join(&contract_hex)
+            .join(hex::encode(commit));
+        fs::create_dir_all(&element_dir)?;
+        fs::write(element_dir.join(ELEMENT_FILE), element)
+    }
+
+    fn put_removal(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+    ) -> io::Result<()> {
+        // The removal is marked by an empty file where the element would be
+        let leaf_dir = self
+            .main_dir
+            .join(LEAF_DIR)
+            .join(hex::encode(contract))
+            .join(hex::encode(commit));
+        fs::create_dir_all(&leaf_dir)?;
+        fs::write(leaf_dir.join(REMOVED_FILE), [])
+    }
+
+    fn put_instruction_costs(
+        &mut self,
+        commit: [u8; 32],
+        hash: [u8; 32],
+    ) -> io::Result<()> {
+        let commit_dir = self.main_dir.join(hex::encode(commit));
+        fs::create_dir_all(&commit_dir)?;
+        fs::write(commit_dir.join(INSTRUCTION_COSTS_FILE), hash)
+    }
+
+    fn link_base(
+        &mut self,
+        commit: [u8; 32],
+        base_info: &[u8],
+        tree_pos: &[u8],
+    ) -> io::Result<()> {
+        let root_hex = hex::encode(commit);
+        fs::write(base_path_main(&self.main_dir, &root_hex)?, base_info)?;
+        fs::write(tree_pos_path_main(&self.main_dir, &root_hex)?, tree_pos)?;
+
+        // The commit is complete, so the files created for it are no longer
+        // to be removed.
+        self.created.clear();
+        Ok(())
+    }
+
+    fn delete(&mut self, commit: [u8; 32]) -> io::Result<()> {
+        let root = hex::encode(commit);
+        let commit_dir = self.main_dir.join(&root);
+        if commit_dir.exists() {
+            let base_info = base_from_path(commit_dir.join(BASE_FILE))?;
+            for contract_hint in base_info.contract_hints {
+                let contract_hex = hex::encode(contract_hint);
+                let commit_mem_path = self
+                    .main_dir
+                    .join(MEMORY_DIR)
+                    .join(&contract_hex)
+                    .join(&root);
+                cold::remove_dir(&commit_mem_path)?;
+                let commit_leaf_path = self
+                    .main_dir
+                    .join(LEAF_DIR)
+                    .join(&contract_hex)
+                    .join(&root);
+                cold::remove_dir(&commit_leaf_path)?;
+            }
+            cold::remove_dir(&commit_dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Stores commits in memory.
+///
+/// Useful in tests, and as a starting point for backends storing commits
+/// elsewhere.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryBackend {
+    bytecode: BTreeMap<ContractId, StoredBytecode>,
+    pages: BTreeMap<([u8; 32], ContractId, usize), Vec<u8>>,
+    elements: BTreeMap<([u8; 32], ContractId), Vec<u8>>,
+    removals: BTreeSet<([u8; 32], ContractId)>,
+    instruction_costs: BTreeMap<[u8; 32], [u8; 32]>,
+    bases: BTreeMap<[u8; 32], (Vec<u8>, Vec<u8>)>,
+    // Contracts whose bytecode was stored for a commit not yet complete.
+    created: BTreeSet<ContractId>,
+}
+
+#[derive(Debug, Clone)]
+struct StoredBytecode {
+    bytecode: Vec<u8>,
+    module: Vec<u8>,
+    metadata: Vec<u8>,
+}
+
+impl MemoryBackend {
+    /// Creates an empty backend.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the roots of the commits completely stored.
+    pub fn commits(&self) -> Vec<Root> {
+        self.bases.keys().copied().map(Root::from).collect()
+    }
+
+    /// Returns the bytecode stored for the given `contract`.
+    pub fn bytecode(&self, contract: ContractId) -> Option<&[u8]> {
+        self.bytecode.get(&contract).map(|s| s.bytecode.as_slice())
+    }
+
+    /// Returns the compiled module stored for the given `contract`.
+    pub fn module(&self, contract: ContractId) -> Option<&[u8]> {
+        self.bytecode.get(&contract).map(|s| s.module.as_slice())
+    }
+
+    /// Returns the metadata stored for the given `contract`.
+    pub fn metadata(&self, contract: ContractId) -> Option<&[u8]> {
+        self.bytecode.get(&contract).map(|s| s.metadata.as_slice())
+    }
+
+    /// Returns the memory page stored for the given `contract` as of the given
+    /// `commit`.
+    pub fn memory_page(
+        &self,
+        commit: impl Into<Root>,
+        contract: ContractId,
+        page_index: usize,
+    ) -> Option<&[u8]> {
+        let commit = commit.into().into();
+        self.pages
+            .get(&(commit, contract, page_index))
+            .map(Vec::as_slice)
+    }
+
+    /// Returns the indices of the memory pages stored for the given `contract`
+    /// as of the given `commit`.
+    pub fn page_indices(
+        &self,
+        commit: impl Into<Root>,
+        contract: ContractId,
+    ) -> Vec<usize> {
+        let commit = commit.into().into();
+        self.pages
+            .range((commit, contract, 0)..=(commit, contract, usize::MAX))
+            .map(|((_, _, page_index), _)| *page_index)
+            .collect()
+    }
+
+    /// Returns the index element stored for the given `contract` as of the
+    /// given `commit`.
+    pub fn element(
+        &self,
+        commit: impl Into<Root>,
+        contract: ContractId,
+    ) -> Option<&[u8]> {
+        let commit = commit.into().into();
+        self.elements.get(&(commit, contract)).map(Vec::as_slice)
+    }
+
+    /// Returns the contracts an index element is stored for as of the given
+    /// `commit`.
+    pub fn contracts(&self, commit: impl Into<Root>) -> Vec<ContractId> {
+        let commit = commit.into();
+        self.elements
+            .keys()
+            .filter(|(root, _)| *root == commit)
+            .map(|(_, contract)| *contract)
+            .collect()
+    }
+
+    /// Returns the hash of the instruction costs the given `commit` was made
+    /// with.
+    pub fn instruction_costs(
+        &self,
+        commit: impl Into<Root>,
+    ) -> Option<[u8; 32]> {
+        let commit: [u8; 32] = commit.into().into();
+        self.instruction_costs.get(&commit).copied()
+    }
+
+    /// Returns the contracts removed as of the given `commit`.
+    pub fn removals(&self, commit: impl Into<Root>) -> Vec<ContractId> {
+        let commit = commit.into();
+        self.removals
+            .iter()
+            .filter(|(root, _)| *root == commit)
+            .map(|(_, contract)| *contract)
+            .collect()
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    // Forks start out empty, with what they store moved over when joined.
+    fn fork(&self) -> Self {
+        Self::default()
+    }
+
+    fn join(&mut self, fork: Self) {
+        self.bytecode.extend(fork.bytecode);
+        self.pages.extend(fork.pages);
+        self.elements.extend(fork.elements);
+        self.removals.extend(fork.removals);
+        self.instruction_costs.extend(fork.instruction_costs);
+        self.bases.extend(fork.bases);
+        self.created.extend(fork.created);
+    }
+
+    fn remove_partial(&mut self, commit: [u8; 32], contracts: &[ContractId]) {
+        for contract in contracts {
+            if self.created.remove(contract) {
+                self.bytecode.remove(contract);
+            }
+        }
+        let _ = self.delete(commit);
+    }
+
+    fn put_bytecode(
+        &mut self,
+        contract: ContractId,
+        bytecode: &[u8],
+        module: &[u8],
+        metadata: &[u8],
+    ) -> io::Result<()> {
+        if !self.bytecode.contains_key(&contract) {
+            self.created.insert(contract);
+        }
+        self.bytecode.insert(
+            contract,
+            StoredBytecode {
+                bytecode: bytecode.to_vec(),
+                module: module.to_vec(),
+                metadata: metadata.to_vec(),
+            },
+        );
+        Ok(())
+    }
+
+    fn put_memory_page(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+        page_index: usize,
+        page: &[u8],
+    ) -> io::Result<()> {
+        self.pages
+            .insert((commit, contract, page_index), page.to_vec());
+        Ok(())
+    }
+
+    fn put_element(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+        element: &[u8],
+    ) -> io::Result<()> {
+        self.elements.insert((commit, contract), element.to_vec());
+        Ok(())
+    }
+
+    fn put_removal(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+    ) -> io::Result<()> {
+        self.removals.insert((commit, contract));
+        Ok(())
+    }
+
+    fn put_instruction_costs(
+        &mut self,
+        commit: [u8; 32],
+        hash: [u8; 32],
+    ) -> io::Result<()> {
+        self.instruction_costs.insert(commit, hash);
+        Ok(())
+    }
+
+    fn link_base(
+        &mut self,
+        commit: [u8; 32],
+        base_info: &[u8],
+        tree_pos: &[u8],
+    ) -> io::Result<()> {
+        self.bases
+            .insert(commit, (base_info.to_vec(), tree_pos.to_vec()));
+        self.created.clear();
+        Ok(())
+    }
+
+    fn delete(&mut self, commit: [u8; 32]) -> io::Result<()> {
+        self.pages.retain(|(root, _, _), _| *root != commit);
+        self.elements.retain(|(root, _), _| *root != commit);
+        self.removals.retain(|(root, _)| *root != commit);
+        self.instruction_costs.remove(&commit);
+        self.bases.remove(&commit);
+        Ok(())
+    }
+}
+
+/// Writes the commit with the given `root` to the `backend`.
+///
+/// The commit is written whole, with every page of every contract in it and
+/// without a base, so the backend holds everything needed to restore it.
+pub(crate) fn archive_commit<B: StorageBackend + ?Sized>(
+    main_dir: &Path,
+    root: Hash,
+    commit: &Commit,
+    backend: &mut B,
+) -> io::Result<()> {
+    let root_bytes: [u8; 32] = root.into();
+    let mut base_info = BaseInfo::default();
+
+    for (contract, element) in export::commit_contracts(main_dir, commit)? {
+        let contract_hex = hex::encode(contract);
+
+        let bytecode_path = main_dir.join(BYTECODE_DIR).join(&contract_hex);
+        backend.put_bytecode(
+            contract,
+            &fs::read(&bytecode_path)?,
+            &fs::read(bytecode_path.with_extension(OBJECTCODE_EXTENSION))?,
+            &fs::read(bytecode_path.with_extension(METADATA_EXTENSION))?,
+        )?;
+
+        let memory_dir = main_dir.join(MEMORY_DIR).join(&contract_hex);
+        for page_index in element.page_indices() {
+            let path = ContractSession::find_page(
+                *page_index,
+                Some(root),
+                &memory_dir,
+                main_dir,
+            )
+            .unwrap_or_else(|| page_path(&memory_dir, *page_index));
+            backend.put_memory_page(
+                root_bytes,
+                contract,
+                *page_index,
+                &fs::read(path)?,
+            )?;
+        }
+
+        backend.put_element(
+            root_bytes,
+            contract,
+            &export::element_to_bytes(&element)?,
+        )?;
+        base_info.contract_hints.push(contract);
+    }
+
+    if let Some(instruction_costs) = commit.instruction_costs {
+        backend.put_instruction_costs(root_bytes, instruction_costs)?;
+    }
+
+    let mut tree_pos = Vec::new();
+    commit.contracts_merkle.tree_pos().marshall(&mut tree_pos)?;
+    backend.link_base(root_bytes, &base_info_to_bytes(&base_info)?, &tree_pos)
+}
+
+/// Serializes the given `base_info`, as passed to
+/// [`StorageBackend::link_base`].
+pub(crate) fn base_info_to_bytes(base_info: &BaseInfo) -> io::Result<Vec<u8>> {
+    rkyv::to_bytes::<_, 128>(base_info)
+        .map(|bytes| bytes.into_vec())
+        .map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed serializing base info file: {err}"),
+            )
+        })
+}
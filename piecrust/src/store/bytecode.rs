@@ -4,14 +4,28 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
-use std::fs::File;
+use std::ffi::OsString;
+use std::fs::{self, File};
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use memmap2::{Mmap, MmapOptions};
 
+use crate::store::OBJECTCODE_EXTENSION;
+
+/// Counts the temporary files created by this process, keeping their names
+/// unique.
+static TMP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
 /// WASM bytecode belonging to a given contract.
+///
+/// Bytecode read from the store maps the file shared by all contracts
+/// deployed with the same bytecode. Shared files are never written to once in
+/// place - only ever replaced - so the mapping stays valid for as long as the
+/// handle lives, even if the file is removed.
 #[derive(Debug, Clone)]
 pub struct Bytecode {
     mmap: Arc<Mmap>,
@@ -30,6 +44,25 @@ impl Bytecode {
         })
     }
 
+    /// Maps the copy of the given `bytes` shared in the `code_dir`, if there
+    /// is one, and copies them otherwise.
+    pub(crate) fn shared<B: AsRef<[u8]>>(
+        code_dir: &Path,
+        bytes: B,
+    ) -> io::Result<Self> {
+        let bytes = bytes.as_ref();
+
+        match File::open(shared_path(code_dir, bytes)) {
+            Ok(file) if file.metadata()?.len() == bytes.len() as u64 => {
+                let mmap = unsafe { Mmap::map(&file)? };
+                Ok(Self {
+                    mmap: Arc::new(mmap),
+                })
+            }
+            _ => Self::new(bytes),
+        }
+    }
+
     pub(crate) fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let file = File::open(path)?;
         let mmap = unsafe { Mmap::map(&file)? };
@@ -45,3 +78,116 @@ impl AsRef<[u8]> for Bytecode {
         &self.mmap
     }
 }
+
+/// Returns the path of the file the given `bytecode` is shared in, named by
+/// its hash.
+pub(crate) fn shared_path(code_dir: &Path, bytecode: &[u8]) -> PathBuf {
+    code_dir.join(blake3::hash(bytecode).to_hex().as_str())
+}
+
+/// Links the file at `link` to the file at `shared`, writing the given `bytes`
+/// to the latter first unless it already holds them.
+///
+/// Should hard links not be supported, the `bytes` are copied to `link`
+/// instead. The paths that didn't exist before are pushed to `created`.
+pub(crate) fn link_shared(
+    shared: &Path,
+    bytes: &[u8],
+    link: &Path,
+    created: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let existing = match fs::read(shared) {
+        Ok(existing) => Some(existing),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err),
+    };
+    if existing.as_deref() != Some(bytes) {
+        write_replacing(shared, bytes)?;
+        if existing.is_none() {
+            created.push(shared.to_path_buf());
+        }
+    }
+
+    if !link.exists() {
+        created.push(link.to_path_buf());
+    }
+    replace(link, |tmp| {
+        fs::hard_link(shared, tmp).or_else(|_| fs::write(tmp, bytes))
+    })
+}
+
+/// Links the bytecode and module files of each contract in the
+/// `bytecode_dir` to the ones shared in the `code_dir`, as they would have
+/// been if written by this version of the store.
+///
+/// Used for migrating stores written before bytecode was shared. Modules
+/// differing from the one already shared for the same bytecode are left as
+/// they are, and running it again after an interruption is harmless.
+pub(crate) fn share_existing(
+    bytecode_dir: &Path,
+    code_dir: &Path,
+) -> io::Result<()> {
+    if !bytecode_dir.is_dir() {
+        return Ok(());
+    }
+    fs::create_dir_all(code_dir)?;
+
+    // Nothing is cleaned up on failure, since every file written is valid.
+    let mut created = Vec::new();
+
+    for entry in fs::read_dir(bytecode_dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_some() {
+            continue;
+        }
+
+        let bytecode = fs::read(&path)?;
+        let shared = shared_path(code_dir, &bytecode);
+        link_shared(&shared, &bytecode, &path, &mut created)?;
+
+        let module_path = path.with_extension(OBJECTCODE_EXTENSION);
+        let module = match fs::read(&module_path) {
+            Ok(module) => module,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        let shared_module = shared.with_extension(OBJECTCODE_EXTENSION);
+        match fs::read(&shared_module) {
+            Ok(existing) if existing != module => {}
+            _ => link_shared(
+                &shared_module,
+                &module,
+                &module_path,
+                &mut created,
+            )?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the given `bytes` to a new file replacing the one at `path`, so
+/// that any mapping of the old one, or link to it, is left untouched.
+pub(crate) fn write_replacing(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    replace(path, |tmp| fs::write(tmp, bytes))
+}
+
+/// Creates a temporary file using `create`, and renames it over `path`.
+fn replace<F>(path: &Path, create: F) -> io::Result<()>
+where
+    F: FnOnce(&Path) -> io::Result<()>,
+{
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        TMP_COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp_path = path.with_file_name(name);
+
+    let result = create(&tmp_path).and_then(|_| fs::rename(&tmp_path, path));
+    // Renaming a link over another one to the same file does nothing, leaving
+    // the temporary file in place, so it's removed regardless.
+    let _ = fs::remove_file(tmp_path);
+    result
+}
@@ -0,0 +1,175 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Relocation of commits to and from a cold directory.
+//!
+//! A commit is spread over its own directory, and a directory per contract it
+//! changed in each of the memory and leaf directories. Cooling a commit moves
+//! each of these to the same place under the cold directory, and leaves a
+//! symbolic link behind, so everything reading the commit through the main
+//! directory keeps working unchanged. Warming it moves them back.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::store::tree::Hash;
+use crate::store::{base_from_path, BASE_FILE, LEAF_DIR, MEMORY_DIR};
+
+/// Moves the directories of the commit with the given `root` from the
+/// `main_dir` to the `cold_dir`, linking to them from where they were.
+///
+/// Directories that are already cold are left in place, making it possible to
+/// finish moving a commit that was only partially moved.
+pub(crate) fn cool_commit(
+    main_dir: &Path,
+    cold_dir: &Path,
+    root: Hash,
+) -> io::Result<()> {
+    fs::create_dir_all(cold_dir)?;
+    let cold_dir = fs::canonicalize(cold_dir)?;
+
+    // The commit's own directory goes last, since it is what marks a commit
+    // as cold.
+    for rel_path in commit_dirs(main_dir, root)? {
+        let path = main_dir.join(&rel_path);
+        if !is_dir(&path)? {
+            continue;
+        }
+
+        let cold_path = cold_dir.join(&rel_path);
+        if let Some(parent) = cold_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        move_dir(&path, &cold_path)?;
+        link_dir(&cold_path, &path)?;
+    }
+
+    Ok(())
+}
+
+/// Moves the directories of the commit with the given `root` back into the
+/// `main_dir` from wherever they were cooled to.
+pub(crate) fn warm_commit(main_dir: &Path, root: Hash) -> io::Result<()> {
+    // Directories are moved back in the reverse order they were cooled in.
+    for rel_path in commit_dirs(main_dir, root)?.into_iter().rev() {
+        let path = main_dir.join(&rel_path);
+        if !is_link(&path)? {
+            continue;
+        }
+
+        let cold_path = fs::read_link(&path)?;
+        unlink_dir(&path)?;
+        move_dir(&cold_path, &path)?;
+    }
+
+    Ok(())
+}
+
+/// Returns whether the commit with the given `root` has been cooled.
+pub(crate) fn is_cold(main_dir: &Path, root: Hash) -> io::Result<bool> {
+    is_link(&main_dir.join(hex::encode(root)))
+}
+
+/// Removes the directory at the given `path`, along with the directory it
+/// links to if it's cold. Does nothing if there is no directory at `path`.
+pub(crate) fn remove_dir(path: &Path) -> io::Result<()> {
+    if is_link(path)? {
+        let cold_path = fs::read_link(path)?;
+        fs::remove_dir_all(cold_path)?;
+        return unlink_dir(path);
+    }
+
+    match fs::remove_dir_all(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// The paths of the directories of the commit with the given `root`, relative
+/// to the main directory. The commit's own directory is last.
+fn commit_dirs(main_dir: &Path, root: Hash) -> io::Result<Vec<PathBuf>> {
+    let root_hex = hex::encode(root);
+    let base_info = base_from_path(main_dir.join(&root_hex).join(BASE_FILE))?;
+
+    let mut dirs = Vec::with_capacity(2 * base_info.contract_hints.len() + 1);
+    for contract in base_info.contract_hints {
+        let contract_hex = hex::encode(contract);
+        dirs.push(
+            PathBuf::from(MEMORY_DIR)
+                .join(&contract_hex)
+                .join(&root_hex),
+        );
+        dirs.push(PathBuf::from(LEAF_DIR).join(&contract_hex).join(&root_hex));
+    }
+    dirs.push(PathBuf::from(root_hex));
+
+    Ok(dirs)
+}
+
+fn is_link(path: &Path) -> io::Result<bool> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => Ok(metadata.file_type().is_symlink()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+fn is_dir(path: &Path) -> io::Result<bool> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => Ok(metadata.is_dir()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Moves a directory, copying it over if it is to be moved across volumes.
+fn move_dir(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    copy_dir(from, to)?;
+    fs::remove_dir_all(from)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let to = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to)?;
+        } else {
+            fs::copy(entry.path(), to)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn link_dir(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn link_dir(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_dir(original, link)
+}
+
+#[cfg(unix)]
+fn unlink_dir(link: &Path) -> io::Result<()> {
+    fs::remove_file(link)
+}
+
+#[cfg(windows)]
+fn unlink_dir(link: &Path) -> io::Result<()> {
+    fs::remove_dir(link)
+}
@@ -7,6 +7,7 @@
 use crate::store::tree::{ContractIndexElement, Hash, NewContractIndex};
 use crate::store::CommitStore;
 use piecrust_uplink::ContractId;
+use std::collections::BTreeSet;
 use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone)]
@@ -19,6 +20,9 @@ impl Hulk {
         commit_store: Option<Arc<Mutex<CommitStore>>>,
         base: Option<Hash>,
     ) -> Option<*const ContractIndexElement> {
+        if index.is_removed(&contract_id) {
+            return None;
+        }
         if let Some(e) = index.get(&contract_id) {
             return Some(e);
         }
@@ -41,6 +45,9 @@ impl Hulk {
         commit_store: Option<Arc<Mutex<CommitStore>>>,
         base: Option<Hash>,
     ) -> Option<*mut ContractIndexElement> {
+        if index.is_removed(&contract_id) {
+            return None;
+        }
         if let Some(e) = index.get_mut(&contract_id) {
             return Some(e);
         }
@@ -56,4 +63,30 @@ impl Hulk {
             base = commit_base?;
         }
     }
+
+    pub fn deep_contract_ids(
+        index: &NewContractIndex,
+        commit_store: Option<Arc<Mutex<CommitStore>>>,
+        base: Option<Hash>,
+    ) -> BTreeSet<ContractId> {
+        let mut contract_ids: BTreeSet<_> =
+            index.contract_ids().copied().collect();
+        let (Some(mut base), Some(commit_store)) = (base, commit_store) else {
+            return contract_ids;
+        };
+        // Contracts removed in a commit are hidden in all of its bases.
+        let mut removed = index.removed().clone();
+        let commit_store = commit_store.lock().unwrap();
+        loop {
+            let (index, commit_base) = commit_store.get_index_and_base(&base);
+            contract_ids.extend(
+                index.contract_ids().filter(|id| !removed.contains(id)),
+            );
+            removed.extend(index.removed());
+            match commit_base {
+                Some(commit_base) => base = commit_base,
+                None => return contract_ids,
+            }
+        }
+    }
 }
@@ -0,0 +1,210 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Differences between the states of two commits, as found on disk.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use piecrust_uplink::ContractId;
+
+use crate::store::tree::{ContractIndexElement, Hash};
+use crate::store::{
+    Commit, CommitStore, ContractSession, MAIN_DIR, MEMORY_DIR, PAGE_SIZE,
+};
+
+/// The differences between the states of two commits, as returned by
+/// [`VM::diff_commits`].
+///
+/// [`VM::diff_commits`]: crate::VM::diff_commits
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommitDiff {
+    /// The contracts in the new commit, but not in the old one.
+    pub added: BTreeSet<ContractId>,
+    /// The contracts in the old commit, but not in the new one.
+    pub removed: BTreeSet<ContractId>,
+    /// The contracts in both commits whose memory differs between them.
+    pub modified: BTreeMap<ContractId, ContractDiff>,
+}
+
+impl CommitDiff {
+    /// Returns whether the two commits have the same state.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+    }
+}
+
+/// The differences between the memories of a contract in two commits.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ContractDiff {
+    /// The length of the memory in the old commit, in bytes.
+    pub old_len: usize,
+    /// The length of the memory in the new commit, in bytes.
+    pub new_len: usize,
+    /// The size of the pages in `changed_pages`, in bytes.
+    pub page_size: usize,
+    /// The indices of the pages whose contents differ, in ascending order.
+    ///
+    /// Pages never written to are taken to be zeroed.
+    pub changed_pages: Vec<usize>,
+}
+
+/// Computes the differences between the commits with roots `old` and `new`,
+/// reporting changed pages of `page_size` bytes.
+pub(crate) fn diff_commits(
+    root_dir: &Path,
+    commit_store: &Arc<Mutex<CommitStore>>,
+    old: Hash,
+    new: Hash,
+    page_size: usize,
+) -> io::Result<CommitDiff> {
+    let (old_commit, new_commit) = {
+        let commit_store = commit_store.lock().unwrap();
+        (
+            get_commit(&commit_store, &old)?,
+            get_commit(&commit_store, &new)?,
+        )
+    };
+
+    let old_ids = old_commit.contract_ids();
+    let new_ids = new_commit.contract_ids();
+
+    let mut diff = CommitDiff {
+        added: new_ids.difference(&old_ids).copied().collect(),
+        removed: old_ids.difference(&new_ids).copied().collect(),
+        ..CommitDiff::default()
+    };
+
+    let main_dir = root_dir.join(MAIN_DIR);
+    for contract in old_ids.intersection(&new_ids) {
+        let (Some(old_elem), Some(new_elem)) = (
+            old_commit.index_get(contract),
+            new_commit.index_get(contract),
+        ) else {
+            continue;
+        };
+
+        if *old_elem.tree().root() == *new_elem.tree().root()
+            && old_elem.len() == new_elem.len()
+        {
+            continue;
+        }
+
+        let memory_dir = main_dir.join(MEMORY_DIR).join(hex::encode(contract));
+        let pages = PageLocator {
+            main_dir: &main_dir,
+            memory_dir: &memory_dir,
+            page_size,
+        };
+
+        diff.modified.insert(
+            *contract,
+            ContractDiff {
+                old_len: old_elem.len(),
+                new_len: new_elem.len(),
+                page_size,
+                changed_pages: pages.changed(old, old_elem, new, new_elem)?,
+            },
+        );
+    }
+
+    Ok(diff)
+}
+
+fn get_commit(commit_store: &CommitStore, root: &Hash) -> io::Result<Commit> {
+    commit_store.get_commit(root).cloned().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Commit not found: {}", hex::encode(root)),
+        )
+    })
+}
+
+/// Finds the pages of a contract's memory on disk, and splits them into pages
+/// of `page_size` bytes.
+struct PageLocator<'a> {
+    main_dir: &'a Path,
+    memory_dir: &'a Path,
+    page_size: usize,
+}
+
+impl PageLocator<'_> {
+    fn path(&self, commit: Hash, page_index: usize) -> PathBuf {
+        ContractSession::find_page(
+            page_index,
+            Some(commit),
+            self.memory_dir,
+            self.main_dir,
+        )
+        .unwrap_or(self.memory_dir.join(format!("{page_index}")))
+    }
+
+    /// Returns the indices of the pages that differ between the elements of a
+    /// contract in the `old` and `new` commits.
+    fn changed(
+        &self,
+        old: Hash,
+        old_elem: &ContractIndexElement,
+        new: Hash,
+        new_elem: &ContractIndexElement,
+    ) -> io::Result<Vec<usize>> {
+        let mut changed = Vec::new();
+
+        let page_indices =
+            old_elem.page_indices().union(new_elem.page_indices());
+        for &page_index in page_indices {
+            let in_old = old_elem.page_indices().contains(&page_index);
+            let in_new = new_elem.page_indices().contains(&page_index);
+
+            let (old_page, new_page) = match (in_old, in_new) {
+                // Both commits sharing the same file have the same page, and
+                // otherwise the page in the old commit is checked against the
+                // tree of the new one before comparing their contents.
+                (true, true) => {
+                    let old_path = self.path(old, page_index);
+                    let new_path = self.path(new, page_index);
+                    if old_path == new_path {
+                        continue;
+                    }
+                    let old_page = fs::read(old_path)?;
+                    if new_elem
+                        .tree()
+                        .contains_page(page_index as u64, &old_page)
+                    {
+                        continue;
+                    }
+                    (old_page, fs::read(new_path)?)
+                }
+                // A page missing from a commit is zeroed, so it only changed
+                // where the other commit wrote something other than zeroes.
+                (true, false) => {
+                    (fs::read(self.path(old, page_index))?, vec![0; PAGE_SIZE])
+                }
+                (false, true) => {
+                    (vec![0; PAGE_SIZE], fs::read(self.path(new, page_index))?)
+                }
+                (false, false) => unreachable!("page is in one of the sets"),
+            };
+
+            let pages_per_page = PAGE_SIZE / self.page_size;
+            let chunks = old_page
+                .chunks(self.page_size)
+                .zip(new_page.chunks(self.page_size));
+            for (i, (old_chunk, new_chunk)) in chunks.enumerate() {
+                if old_chunk != new_chunk {
+                    changed.push(page_index * pages_per_page + i);
+                }
+            }
+        }
+
+        Ok(changed)
+    }
+}
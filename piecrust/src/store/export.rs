@@ -0,0 +1,411 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Streaming export and import of commits, for syncing state between nodes.
+//!
+//! A commit is written as a header, followed by the hash of the instruction
+//! costs it was made with - empty if it was made before they were recorded -
+//! the positions of the leaves of the contracts tree, and then by each
+//! contract in the commit in turn:
+//!
+//! ```text
+//! magic | version | root | instruction costs | tree positions |
+//!     contract count | contracts...
+//!
+//! contract := id | bytecode | metadata | element | page count | pages...
+//! page     := index | bytes
+//! ```
+//!
+//! All integers are little endian, and variable length fields are prefixed
+//! with their length as a `u32`. Contracts are written one at a time, so
+//! neither side ever holds more than one page in memory.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use dusk_wasmtime::Engine;
+use piecrust_uplink::ContractId;
+
+use crate::store::session::ContractSession;
+use crate::store::tree::{
+    contract_leaf, position_from_contract, BaseInfo, ContractIndexElement,
+    Hash, TreePos,
+};
+use crate::store::{
+    base_path_main, contract_id_from_hex, page_path, page_path_main,
+    read_commit, tree_pos_path_main, Commit, CommitStore, BYTECODE_DIR,
+    ELEMENT_FILE, INSTRUCTION_COSTS_FILE, LEAF_DIR, MEMORY_DIR,
+    METADATA_EXTENSION, OBJECTCODE_EXTENSION, PAGE_SIZE,
+};
+
+const MAGIC: &[u8; 4] = b"PCCX";
+const FORMAT_VERSION: u32 = 2;
+
+/// Writes the commit with the given `root` to the `writer`.
+pub(crate) fn export_commit<W: Write>(
+    main_dir: &Path,
+    root: Hash,
+    commit: &Commit,
+    writer: W,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+
+    writer.write_all(MAGIC)?;
+    write_u32(&mut writer, FORMAT_VERSION)?;
+    writer.write_all(root.as_bytes())?;
+
+    let instruction_costs: &[u8] = match &commit.instruction_costs {
+        Some(hash) => hash,
+        None => &[],
+    };
+    write_bytes(&mut writer, instruction_costs)?;
+
+    let mut tree_pos = Vec::new();
+    commit.contracts_merkle.tree_pos().marshall(&mut tree_pos)?;
+    write_bytes(&mut writer, &tree_pos)?;
+
+    let contracts = commit_contracts(main_dir, commit)?;
+    write_u32(&mut writer, contracts.len() as u32)?;
+
+    for (contract, element) in contracts {
+        let contract_hex = hex::encode(contract);
+
+        let bytecode_path = main_dir.join(BYTECODE_DIR).join(&contract_hex);
+        let metadata_path = bytecode_path.with_extension(METADATA_EXTENSION);
+        let memory_dir = main_dir.join(MEMORY_DIR).join(&contract_hex);
+
+        writer.write_all(contract.as_bytes())?;
+        write_bytes(&mut writer, &fs::read(bytecode_path)?)?;
+        write_bytes(&mut writer, &fs::read(metadata_path)?)?;
+        write_bytes(&mut writer, &element_to_bytes(&element)?)?;
+
+        write_u32(&mut writer, element.page_indices().len() as u32)?;
+        for page_index in element.page_indices() {
+            let path = ContractSession::find_page(
+                *page_index,
+                Some(root),
+                &memory_dir,
+                main_dir,
+            )
+            .unwrap_or_else(|| page_path(&memory_dir, *page_index));
+
+            write_u64(&mut writer, *page_index as u64)?;
+            write_bytes(&mut writer, &fs::read(path)?)?;
+        }
+    }
+
+    writer.flush()
+}
+
+/// Reads a commit from the `reader`, writes it to the store, and returns it
+/// together with its root.
+///
+/// Returns `None` if a commit with the same root is already in the store, in
+/// which case the rest of the stream is not read.
+pub(crate) fn import_commit<R: Read>(
+    engine: &Engine,
+    main_dir: &Path,
+    commit_store: &Arc<Mutex<CommitStore>>,
+    reader: R,
+) -> io::Result<(Hash, Option<Commit>)> {
+    let mut reader = BufReader::new(reader);
+
+    let magic: [u8; 4] = read_array(&mut reader)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("Not a commit export stream"));
+    }
+    let version = read_u32(&mut reader)?;
+    if version != FORMAT_VERSION {
+        return Err(invalid_data(format!(
+            "Unsupported commit export version: {version}"
+        )));
+    }
+    let root = Hash::from(read_array::<_, 32>(&mut reader)?);
+
+    if commit_store.lock().unwrap().contains_key(&root) {
+        return Ok((root, None));
+    }
+
+    let root_hex = hex::encode(root);
+    let mut contracts = Vec::new();
+
+    let result = write_contracts(main_dir, &root_hex, &mut reader, |id| {
+        contracts.push(id);
+    })
+    .and_then(|_| {
+        let commit = read_commit(
+            engine,
+            main_dir.join(&root_hex),
+            commit_store.clone(),
+        )?;
+        let imported_root = *commit.root();
+        if imported_root != root {
+            return Err(invalid_data(format!(
+                "Imported commit has a different root: {}",
+                hex::encode(imported_root)
+            )));
+        }
+        Ok(commit)
+    });
+
+    match result {
+        Ok(commit) => Ok((root, Some(commit))),
+        Err(err) => {
+            // Remove anything written so far, leaving the store untouched.
+            for contract in contracts {
+                let contract_hex = hex::encode(contract);
+                let _ = fs::remove_dir_all(
+                    main_dir
+                        .join(MEMORY_DIR)
+                        .join(&contract_hex)
+                        .join(&root_hex),
+                );
+                let _ = fs::remove_dir_all(
+                    main_dir.join(LEAF_DIR).join(&contract_hex).join(&root_hex),
+                );
+            }
+            let _ = fs::remove_dir_all(main_dir.join(&root_hex));
+            Err(err)
+        }
+    }
+}
+
+/// Reads the contracts of a commit from the `reader`, checking them against
+/// the tree positions, and writes them under the directory of the commit.
+fn write_contracts<R: Read, F: FnMut(ContractId)>(
+    main_dir: &Path,
+    root_hex: &str,
+    reader: &mut R,
+    mut written: F,
+) -> io::Result<()> {
+    let instruction_costs = read_bytes(reader, 32)?;
+    if !instruction_costs.is_empty() && instruction_costs.len() != 32 {
+        return Err(invalid_data("Invalid instruction costs hash"));
+    }
+
+    let tree_pos_bytes = read_bytes(reader, u32::MAX as usize)?;
+    let tree_pos = TreePos::unmarshall(&mut tree_pos_bytes.as_slice())?;
+    let leaves: BTreeMap<u32, (Hash, u64)> = tree_pos
+        .iter()
+        .map(|(int_pos, leaf)| (*int_pos, *leaf))
+        .collect();
+
+    let mut base_info = BaseInfo::default();
+    let mut seen = BTreeSet::new();
+
+    let contract_count = read_u32(reader)?;
+    for _ in 0..contract_count {
+        let contract = ContractId::from_bytes(read_array(reader)?);
+        if !seen.insert(contract) {
+            return Err(invalid_data("Duplicate contract in commit export"));
+        }
+        let contract_hex = hex::encode(contract);
+
+        let bytecode = read_bytes(reader, u32::MAX as usize)?;
+        let metadata = read_bytes(reader, u32::MAX as usize)?;
+        let element_bytes = read_bytes(reader, u32::MAX as usize)?;
+        let element: ContractIndexElement = rkyv::from_bytes(&element_bytes)
+            .map_err(|err| {
+                invalid_data(format!(
+                    "Invalid element for contract {contract_hex}: {err}"
+                ))
+            })?;
+
+        // The element must be the one the contracts tree commits to, and its
+        // hash the root of its page tree, bound to the contract in its leaf.
+        let leaf = element
+            .int_pos()
+            .and_then(|int_pos| leaves.get(&(int_pos as u32)));
+        let tree_root = *element.tree().root();
+        if element.hash() != Some(tree_root)
+            || leaf
+                != Some(&(
+                    contract_leaf(&contract, &tree_root),
+                    position_from_contract(&contract),
+                ))
+        {
+            return Err(invalid_data(format!(
+                "Element of contract {contract_hex} is not in the commit"
+            )));
+        }
+
+        written(contract);
+
+        let bytecode_path = main_dir.join(BYTECODE_DIR).join(&contract_hex);
+        write_bytecode_file(&bytecode_path, &bytecode)?;
+        write_bytecode_file(
+            &bytecode_path.with_extension(METADATA_EXTENSION),
+            &metadata,
+        )?;
+
+        let memory_dir = main_dir.join(MEMORY_DIR).join(&contract_hex);
+        fs::create_dir_all(memory_dir.join(root_hex))?;
+
+        let page_count = read_u32(reader)?;
+        for _ in 0..page_count {
+            let page_index = read_u64(reader)? as usize;
+            let page = read_bytes(reader, PAGE_SIZE)?;
+
+            if !element.page_indices().contains(&page_index)
+                || !element.tree().contains_page(page_index as u64, &page)
+            {
+                return Err(invalid_data(format!(
+                    "Page {page_index} of contract {contract_hex} is not in \
+                     the commit"
+                )));
+            }
+
+            fs::write(
+                page_path_main(&memory_dir, page_index, root_hex)?,
+                page,
+            )?;
+        }
+
+        let element_dir =
+            main_dir.join(LEAF_DIR).join(&contract_hex).join(root_hex);
+        fs::create_dir_all(&element_dir)?;
+        fs::write(element_dir.join(ELEMENT_FILE), element_bytes)?;
+
+        base_info.contract_hints.push(contract);
+    }
+
+    let base_info_bytes =
+        rkyv::to_bytes::<_, 128>(&base_info).map_err(|err| {
+            invalid_data(format!("Failed serializing base info file: {err}"))
+        })?;
+    fs::write(base_path_main(main_dir, root_hex)?, base_info_bytes)?;
+    fs::write(tree_pos_path_main(main_dir, root_hex)?, tree_pos_bytes)?;
+    if !instruction_costs.is_empty() {
+        fs::write(
+            main_dir.join(root_hex).join(INSTRUCTION_COSTS_FILE),
+            instruction_costs,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Returns all contracts visible in the given commit, together with their
+/// elements.
+pub(super) fn commit_contracts(
+    main_dir: &Path,
+    commit: &Commit,
+) -> io::Result<Vec<(ContractId, ContractIndexElement)>> {
+    let mut contracts: BTreeSet<ContractId> =
+        commit.index.iter().map(|(contract, _)| *contract).collect();
+
+    let leaf_dir = main_dir.join(LEAF_DIR);
+    if leaf_dir.is_dir() {
+        for entry in fs::read_dir(leaf_dir)? {
+            let entry = entry?;
+            if entry.path().is_dir() {
+                let contract_hex = entry.file_name();
+                contracts.insert(contract_id_from_hex(
+                    contract_hex.to_string_lossy(),
+                ));
+            }
+        }
+    }
+
+    Ok(contracts
+        .into_iter()
+        .filter_map(|contract| {
+            let element = commit.index_get(&contract)?.clone();
+            Some((contract, element))
+        })
+        .collect())
+}
+
+/// Writes a file shared between all commits, such as a contract's bytecode,
+/// unless it already exists. Errors if an existing file has different
+/// contents, since that would change the contract in other commits.
+fn write_bytecode_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if path.is_file() {
+        if fs::read(path)? != bytes {
+            return Err(invalid_data(format!(
+                "Conflicting contract file already in the store: {path:?}"
+            )));
+        }
+        return Ok(());
+    }
+
+    fs::create_dir_all(path.parent().expect("Parent should exist"))?;
+    fs::write(path, bytes)?;
+
+    // Any stale module is compiled afresh when the commit is read.
+    let _ = fs::remove_file(path.with_extension(OBJECTCODE_EXTENSION));
+
+    Ok(())
+}
+
+pub(super) fn element_to_bytes(
+    element: &ContractIndexElement,
+) -> io::Result<Vec<u8>> {
+    rkyv::to_bytes::<_, 128>(element)
+        .map(|bytes| bytes.into_vec())
+        .map_err(|err| {
+            invalid_data(format!("Failed serializing element file: {err}"))
+        })
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(
+    err: E,
+) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn write_u32<W: Write>(writer: &mut W, n: u32) -> io::Result<()> {
+    writer.write_all(&n.to_le_bytes())
+}
+
+fn write_u64<W: Write>(writer: &mut W, n: u64) -> io::Result<()> {
+    writer.write_all(&n.to_le_bytes())
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(bytes.len())
+        .map_err(|_| invalid_data("Field too large for a commit export"))?;
+    write_u32(writer, len)?;
+    writer.write_all(bytes)
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0u8; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    read_array(reader).map(u32::from_le_bytes)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    read_array(reader).map(u64::from_le_bytes)
+}
+
+/// Reads a length prefixed field, erroring if it is longer than `max_len`.
+///
+/// The field is read incrementally, so a corrupt length can't cause a large
+/// allocation up front.
+fn read_bytes<R: Read>(reader: &mut R, max_len: usize) -> io::Result<Vec<u8>> {
+    let len = read_u32(reader)? as usize;
+    if len > max_len {
+        return Err(invalid_data(format!(
+            "Field of {len} bytes exceeds the maximum of {max_len}"
+        )));
+    }
+
+    let mut bytes = Vec::new();
+    reader.by_ref().take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(bytes)
+}
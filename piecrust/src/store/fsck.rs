@@ -0,0 +1,461 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Integrity checks of the commits of a store that is in use.
+//!
+//! Unlike [`check_layout`], which walks the directory of a store no VM is
+//! using, these checks go through the commits a store has loaded, reading the
+//! files each of them needs to be opened by a session. Commits are held while
+//! they are checked, so they may run alongside sessions and commits.
+//!
+//! [`check_layout`]: crate::check_layout
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use piecrust_uplink::ContractId;
+
+use crate::root::Root;
+use crate::store::bytecode;
+use crate::store::session::ContractSession;
+use crate::store::tree::{contract_leaf, ContractIndexElement, Hash};
+use crate::store::{
+    base_from_path, page_path, tree_pos_from_path, Commit, BASE_FILE,
+    BYTECODE_DIR, CODE_DIR, ELEMENT_FILE, LEAF_DIR, MAIN_DIR, MEMORY_DIR,
+    METADATA_EXTENSION, OBJECTCODE_EXTENSION, TREE_POS_FILE, TREE_POS_OPT_FILE,
+};
+
+/// How thoroughly the commits of a store are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FsckLevel {
+    /// Check that the files of every commit are present, and that its index
+    /// is consistent, without reading any memory or bytecode.
+    Quick,
+    /// Also read every page and bytecode file, checking them against the
+    /// hashes they are recorded with.
+    Full,
+}
+
+/// A check of the integrity of a commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FsckCheck {
+    /// Commit directories hold their files, all of which decode.
+    CommitFiles,
+    /// Contracts have their bytecode files.
+    BytecodeFiles,
+    /// Pages of elements are present.
+    Page,
+    /// Elements are the leaves of their commit's tree.
+    Leaf,
+    /// Pages hash to the leaves of their element's tree.
+    PageHash,
+    /// Bytecode files are linked to the shared code they were written with.
+    SharedCode,
+}
+
+impl FsckCheck {
+    /// Every check, in the order they are documented.
+    pub const ALL: [FsckCheck; 6] = [
+        FsckCheck::CommitFiles,
+        FsckCheck::BytecodeFiles,
+        FsckCheck::Page,
+        FsckCheck::Leaf,
+        FsckCheck::PageHash,
+        FsckCheck::SharedCode,
+    ];
+
+    /// A short, stable identifier of the check.
+    pub fn code(&self) -> &'static str {
+        match self {
+            FsckCheck::CommitFiles => "commit-files",
+            FsckCheck::BytecodeFiles => "bytecode-files",
+            FsckCheck::Page => "page",
+            FsckCheck::Leaf => "leaf",
+            FsckCheck::PageHash => "page-hash",
+            FsckCheck::SharedCode => "shared-code",
+        }
+    }
+
+    /// The least level the check is made at.
+    pub fn level(&self) -> FsckLevel {
+        match self {
+            FsckCheck::CommitFiles
+            | FsckCheck::BytecodeFiles
+            | FsckCheck::Page
+            | FsckCheck::Leaf => FsckLevel::Quick,
+            FsckCheck::PageHash | FsckCheck::SharedCode => FsckLevel::Full,
+        }
+    }
+}
+
+impl Display for FsckCheck {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// A failed [`FsckCheck`] of a commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsckFinding {
+    /// The check that failed.
+    pub check: FsckCheck,
+    /// The root of the commit that failed it.
+    pub commit: Root,
+    /// The contract at fault, if the check concerns one.
+    pub contract: Option<ContractId>,
+    /// The path at fault, relative to the root directory of the store.
+    pub path: PathBuf,
+    /// What is wrong with the path.
+    pub detail: String,
+}
+
+impl Display for FsckFinding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}",
+            self.check,
+            hex::encode(self.commit),
+            self.path.display(),
+            self.detail
+        )
+    }
+}
+
+/// The outcome of checking the commits of a store.
+///
+/// Its [`Display`] implementation is machine-readable: a line with the counts
+/// of commits checked, findings, and commits dropped, followed by a line per
+/// finding with the check's code, the commit, the path, and the detail,
+/// separated by tabs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FsckReport {
+    /// The number of commits checked.
+    pub commits: usize,
+    /// The failed checks, in the order they were made.
+    pub findings: Vec<FsckFinding>,
+    /// The commits dropped to repair the store, if it was asked to.
+    pub dropped: Vec<Root>,
+}
+
+impl FsckReport {
+    /// Returns whether every commit passed every check.
+    pub fn is_ok(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Returns the findings of the given `check`.
+    pub fn findings_with(
+        &self,
+        check: FsckCheck,
+    ) -> impl Iterator<Item = &FsckFinding> {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.check == check)
+    }
+
+    /// Returns the commits that failed any check.
+    pub fn broken_commits(&self) -> BTreeSet<Root> {
+        self.findings.iter().map(|finding| finding.commit).collect()
+    }
+}
+
+impl Display for FsckReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "commits={}\tfindings={}\tdropped={}",
+            self.commits,
+            self.findings.len(),
+            self.dropped.len()
+        )?;
+        for finding in &self.findings {
+            writeln!(f, "{finding}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the commits given to it, gathering the findings in a report.
+pub(super) struct Checker {
+    level: FsckLevel,
+    root_dir: PathBuf,
+    main_dir: PathBuf,
+    // The bytecode files are shared by every commit including the contract,
+    // so they are only checked once.
+    bytecode: BTreeMap<ContractId, Vec<(FsckCheck, PathBuf, String)>>,
+    report: FsckReport,
+}
+
+impl Checker {
+    pub(super) fn new(root_dir: &Path, level: FsckLevel) -> Self {
+        Self {
+            level,
+            root_dir: root_dir.to_path_buf(),
+            main_dir: root_dir.join(MAIN_DIR),
+            bytecode: BTreeMap::new(),
+            report: FsckReport::default(),
+        }
+    }
+
+    pub(super) fn into_report(self) -> FsckReport {
+        self.report
+    }
+
+    fn finding(
+        &mut self,
+        check: FsckCheck,
+        commit: Hash,
+        contract: Option<ContractId>,
+        path: &Path,
+        detail: impl Display,
+    ) {
+        let path = path.strip_prefix(&self.root_dir).unwrap_or(path);
+        self.report.findings.push(FsckFinding {
+            check,
+            commit: commit.into(),
+            contract,
+            path: path.to_path_buf(),
+            detail: detail.to_string(),
+        });
+    }
+
+    /// Checks the given `commit`, which should be held for the duration.
+    pub(super) fn check_commit(&mut self, root: Hash, commit: &Commit) {
+        self.report.commits += 1;
+
+        let root_hex = hex::encode(root);
+        let dir = self.main_dir.join(root_hex);
+
+        let base_path = dir.join(BASE_FILE);
+        if let Err(err) = base_from_path(&base_path) {
+            self.finding(FsckCheck::CommitFiles, root, None, &base_path, err);
+        }
+
+        let tree_pos_path = dir.join(TREE_POS_FILE);
+        let tree_pos_opt_path = dir.join(TREE_POS_OPT_FILE);
+        if let Err(err) = tree_pos_from_path(&tree_pos_path, tree_pos_opt_path)
+        {
+            self.finding(
+                FsckCheck::CommitFiles,
+                root,
+                None,
+                &tree_pos_path,
+                err,
+            );
+        }
+
+        // The hash at each internal position of the commit's tree
+        let leaves: BTreeMap<u64, Hash> = commit
+            .contracts_merkle
+            .tree_pos()
+            .iter()
+            .map(|(int_pos, (hash, _))| (u64::from(*int_pos), *hash))
+            .collect();
+
+        for (contract, element) in commit.index.iter() {
+            self.check_bytecode(root, *contract);
+            self.check_leaf(root, *contract, element, &leaves);
+            self.check_pages(root, *contract, element);
+        }
+    }
+
+    fn check_bytecode(&mut self, root: Hash, contract: ContractId) {
+        let main_dir = &self.main_dir;
+        let level = self.level;
+        let findings = self
+            .bytecode
+            .entry(contract)
+            .or_insert_with(|| bytecode_findings(main_dir, contract, level))
+            .clone();
+
+        for (check, path, detail) in findings {
+            self.finding(check, root, Some(contract), &path, detail);
+        }
+    }
+
+    fn check_leaf(
+        &mut self,
+        root: Hash,
+        contract: ContractId,
+        element: &ContractIndexElement,
+        leaves: &BTreeMap<u64, Hash>,
+    ) {
+        let path = self
+            .main_dir
+            .join(LEAF_DIR)
+            .join(hex::encode(contract))
+            .join(hex::encode(root))
+            .join(ELEMENT_FILE);
+
+        let (Some(int_pos), Some(hash)) = (element.int_pos(), element.hash())
+        else {
+            self.finding(
+                FsckCheck::Leaf,
+                root,
+                Some(contract),
+                &path,
+                "no position",
+            );
+            return;
+        };
+
+        if hash != *element.tree().root() {
+            self.finding(
+                FsckCheck::Leaf,
+                root,
+                Some(contract),
+                &path,
+                "hash differs from the root of its pages",
+            );
+        }
+
+        match leaves.get(&int_pos) {
+            Some(leaf) if *leaf == contract_leaf(&contract, &hash) => {}
+            Some(_) => self.finding(
+                FsckCheck::Leaf,
+                root,
+                Some(contract),
+                &path,
+                format!("hash differs from the leaf at position {int_pos}"),
+            ),
+            None => self.finding(
+                FsckCheck::Leaf,
+                root,
+                Some(contract),
+                &path,
+                format!("position {int_pos} not in the commit's tree"),
+            ),
+        }
+    }
+
+    fn check_pages(
+        &mut self,
+        root: Hash,
+        contract: ContractId,
+        element: &ContractIndexElement,
+    ) {
+        let memory_dir =
+            self.main_dir.join(MEMORY_DIR).join(hex::encode(contract));
+
+        for page_index in element.page_indices().iter().copied() {
+            let path = ContractSession::find_page(
+                page_index,
+                Some(root),
+                &memory_dir,
+                &self.main_dir,
+            )
+            .unwrap_or_else(|| page_path(&memory_dir, page_index));
+
+            if self.level == FsckLevel::Quick {
+                if !path.is_file() {
+                    self.finding(
+                        FsckCheck::Page,
+                        root,
+                        Some(contract),
+                        &path,
+                        "missing",
+                    );
+                }
+                continue;
+            }
+
+            match fs::read(&path) {
+                Ok(page) => {
+                    if !element.tree().contains_page(page_index as u64, &page) {
+                        self.finding(
+                            FsckCheck::PageHash,
+                            root,
+                            Some(contract),
+                            &path,
+                            "hash differs from the leaf of the page",
+                        );
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => self
+                    .finding(
+                        FsckCheck::Page,
+                        root,
+                        Some(contract),
+                        &path,
+                        "missing",
+                    ),
+                Err(err) => self.finding(
+                    FsckCheck::Page,
+                    root,
+                    Some(contract),
+                    &path,
+                    err,
+                ),
+            }
+        }
+    }
+}
+
+/// Checks the bytecode files of the given `contract`, and, at the full level,
+/// that they are still linked to the shared code named by their hash.
+fn bytecode_findings(
+    main_dir: &Path,
+    contract: ContractId,
+    level: FsckLevel,
+) -> Vec<(FsckCheck, PathBuf, String)> {
+    let mut findings = Vec::new();
+
+    let bytecode_path = main_dir.join(BYTECODE_DIR).join(hex::encode(contract));
+    let objectcode_path = bytecode_path.with_extension(OBJECTCODE_EXTENSION);
+    let metadata_path = bytecode_path.with_extension(METADATA_EXTENSION);
+
+    for path in [&bytecode_path, &objectcode_path, &metadata_path] {
+        if !path.is_file() {
+            findings.push((
+                FsckCheck::BytecodeFiles,
+                path.clone(),
+                String::from("missing"),
+            ));
+        }
+    }
+
+    if level == FsckLevel::Quick || !findings.is_empty() {
+        return findings;
+    }
+
+    let bytecode = match fs::read(&bytecode_path) {
+        Ok(bytecode) => bytecode,
+        Err(err) => {
+            findings.push((
+                FsckCheck::BytecodeFiles,
+                bytecode_path,
+                err.to_string(),
+            ));
+            return findings;
+        }
+    };
+
+    let shared_path =
+        bytecode::shared_path(&main_dir.join(CODE_DIR), &bytecode);
+    for (path, shared_path) in [
+        (bytecode_path, shared_path.clone()),
+        (
+            objectcode_path,
+            shared_path.with_extension(OBJECTCODE_EXTENSION),
+        ),
+    ] {
+        let detail = match (fs::read(&path), fs::read(&shared_path)) {
+            (Ok(bytes), Ok(shared)) if bytes == shared => continue,
+            (Ok(_), Ok(_)) => String::from("differs from the shared code"),
+            (Ok(_), Err(err)) if err.kind() == io::ErrorKind::NotFound => {
+                String::from("dangling link to missing shared code")
+            }
+            (Err(err), _) | (_, Err(err)) => err.to_string(),
+        };
+        findings.push((FsckCheck::SharedCode, path, detail));
+    }
+
+    findings
+}
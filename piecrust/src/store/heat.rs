@@ -0,0 +1,332 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Aggregation of page access frequencies across sessions.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::{fs, io};
+
+use bytecheck::CheckBytes;
+use piecrust_uplink::ContractId;
+use rkyv::{Archive, Deserialize, Serialize};
+
+use crate::store::scheduler::{Exclusive, Priority, Scheduler};
+use crate::store::Memory;
+
+/// The number of sampled sessions after which a summary is persisted.
+const DEFAULT_PERSIST_INTERVAL: u64 = 64;
+
+/// How often a page of a contract's memory was accessed.
+#[derive(
+    Archive, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq,
+)]
+#[archive_attr(derive(CheckBytes))]
+pub struct PageHeat {
+    /// The index of the page in the contract's memory.
+    pub index: u64,
+    /// The number of sampled sessions in which the page was accessed.
+    pub accesses: u64,
+}
+
+/// How often the pages of a contract's memory were accessed.
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[archive_attr(derive(CheckBytes))]
+pub struct ContractHeat {
+    pub contract_id: ContractId,
+    /// The number of sampled sessions in which the contract was loaded.
+    pub sessions: u64,
+    /// The pages accessed, ordered by their index.
+    pub pages: Vec<PageHeat>,
+}
+
+impl ContractHeat {
+    /// Returns the total number of page accesses, summed over all pages.
+    pub fn accesses(&self) -> u64 {
+        self.pages.iter().map(|page| page.accesses).sum()
+    }
+}
+
+/// A summary of the page accesses recorded by a [`HeatMap`].
+#[derive(
+    Archive, Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq,
+)]
+#[archive_attr(derive(CheckBytes))]
+pub struct HeatSummary {
+    /// The number of sessions sampled.
+    pub sessions: u64,
+    /// The contracts loaded in the sampled sessions, ordered by their ID.
+    pub contracts: Vec<ContractHeat>,
+}
+
+impl HeatSummary {
+    /// Returns the contracts ordered from the most to the least accessed.
+    pub fn hottest(&self) -> Vec<&ContractHeat> {
+        let mut contracts: Vec<_> = self.contracts.iter().collect();
+        contracts.sort_by_key(|contract| Reverse(contract.accesses()));
+        contracts
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    sessions: u64,
+    pages: BTreeMap<u64, u64>,
+}
+
+struct State {
+    sample_rate: Option<u32>,
+    seen: u64,
+    sessions: u64,
+    contracts: BTreeMap<ContractId, Counts>,
+    persist_interval: u64,
+    unpersisted: u64,
+}
+
+impl State {
+    fn summary(&self) -> HeatSummary {
+        HeatSummary {
+            sessions: self.sessions,
+            contracts: self
+                .contracts
+                .iter()
+                .map(|(contract_id, counts)| ContractHeat {
+                    contract_id: *contract_id,
+                    sessions: counts.sessions,
+                    pages: counts
+                        .pages
+                        .iter()
+                        .map(|(index, accesses)| PageHeat {
+                            index: *index,
+                            accesses: *accesses,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    path: PathBuf,
+    scheduler: Scheduler,
+    // Held while writing, so concurrent persists don't clobber each other's
+    // temporary file.
+    writing: Mutex<()>,
+}
+
+/// Aggregates how often the pages of each contract's memory are accessed,
+/// across many sessions.
+///
+/// Recording is opt-in, since finding the accessed pages of the memories loaded
+/// in a session carries a cost. Once [`enable`]d, one in every `sample_rate`
+/// sessions is sampled, and the pages read or written to by it are counted
+/// when it is either committed or dropped.
+///
+/// A [`summary`] is periodically persisted to the store by the [`Scheduler`],
+/// and loaded back when the store is opened, meaning it survives restarts.
+/// This allows operators to tell which contracts' state is hot, both to size
+/// memory and to decide what to preload.
+///
+/// [`enable`]: HeatMap::enable
+/// [`summary`]: HeatMap::summary
+#[derive(Clone)]
+pub struct HeatMap {
+    shared: Arc<Shared>,
+}
+
+impl Debug for HeatMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let state = self.state();
+        f.debug_struct("HeatMap")
+            .field("sample_rate", &state.sample_rate)
+            .field("sessions", &state.sessions)
+            .field("contracts", &state.contracts.len())
+            .field("path", &self.shared.path)
+            .finish()
+    }
+}
+
+impl HeatMap {
+    /// Creates a heat map persisted at the given `path`, loading any summary
+    /// previously persisted there.
+    ///
+    /// A summary that cannot be read is discarded, starting afresh.
+    pub(crate) fn load<P: AsRef<Path>>(path: P, scheduler: Scheduler) -> Self {
+        let path = path.as_ref().to_path_buf();
+
+        let summary = match read_summary(&path) {
+            Ok(summary) => summary,
+            Err(err) => {
+                if err.kind() != io::ErrorKind::NotFound {
+                    tracing::trace!("discarding heat map summary: {err}");
+                }
+                HeatSummary::default()
+            }
+        };
+
+        let contracts = summary
+            .contracts
+            .into_iter()
+            .map(|contract| {
+                let counts = Counts {
+                    sessions: contract.sessions,
+                    pages: contract
+                        .pages
+                        .into_iter()
+                        .map(|page| (page.index, page.accesses))
+                        .collect(),
+                };
+                (contract.contract_id, counts)
+            })
+            .collect();
+
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    sample_rate: None,
+                    seen: 0,
+                    sessions: summary.sessions,
+                    contracts,
+                    persist_interval: DEFAULT_PERSIST_INTERVAL,
+                    unpersisted: 0,
+                }),
+                path,
+                scheduler,
+                writing: Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Start recording page accesses, sampling one in every `sample_rate`
+    /// sessions.
+    ///
+    /// A `sample_rate` of zero is treated as one, sampling every session.
+    pub fn enable(&self, sample_rate: u32) {
+        self.state().sample_rate = Some(sample_rate.max(1));
+    }
+
+    /// Stop recording page accesses. Sessions already sampled are still
+    /// recorded.
+    pub fn disable(&self) {
+        self.state().sample_rate = None;
+    }
+
+    /// Returns true if page accesses are being recorded.
+    pub fn is_enabled(&self) -> bool {
+        self.state().sample_rate.is_some()
+    }
+
+    /// Sets the number of sampled sessions after which a summary is persisted.
+    ///
+    /// An `interval` of zero is treated as one.
+    pub fn set_persist_interval(&self, interval: u64) {
+        self.state().persist_interval = interval.max(1);
+    }
+
+    /// Returns a summary of the page accesses recorded so far, including the
+    /// ones loaded from the store.
+    pub fn summary(&self) -> HeatSummary {
+        self.state().summary()
+    }
+
+    /// Discard all page accesses recorded so far.
+    ///
+    /// The summary in the store is overwritten on the next persist.
+    pub fn reset(&self) {
+        let mut state = self.state();
+        state.sessions = 0;
+        state.contracts.clear();
+    }
+
+    /// Persist a summary of the page accesses recorded so far to the store,
+    /// without waiting for the next periodic write.
+    pub fn persist(&self) -> io::Result<()> {
+        let _writing = self.shared.writing.lock().unwrap();
+        let summary = self.summary();
+        write_summary(&self.shared.path, &summary)
+    }
+
+    /// Returns a handle to the heat map if the next session should be sampled.
+    pub(crate) fn sample(&self) -> Option<HeatMap> {
+        let mut state = self.state();
+        let sample_rate = state.sample_rate?;
+
+        let seen = state.seen;
+        state.seen += 1;
+
+        (seen % u64::from(sample_rate) == 0).then(|| self.clone())
+    }
+
+    /// Records the pages accessed in the given memories, as a single session.
+    pub(crate) fn record<'a, I>(&self, memories: I)
+    where
+        I: IntoIterator<Item = (ContractId, &'a Memory)>,
+    {
+        let mut state = self.state();
+
+        for (contract_id, memory) in memories {
+            let counts = state.contracts.entry(contract_id).or_default();
+            counts.sessions += 1;
+
+            for page_index in memory.accessed_pages() {
+                *counts.pages.entry(page_index as u64).or_default() += 1;
+            }
+        }
+
+        state.sessions += 1;
+        state.unpersisted += 1;
+
+        if state.unpersisted >= state.persist_interval {
+            state.unpersisted = 0;
+            drop(state);
+
+            let heat_map = self.clone();
+            self.shared.scheduler.submit(
+                Priority::Low,
+                Exclusive::HeatMap,
+                move || {
+                    if let Err(err) = heat_map.persist() {
+                        tracing::trace!("persisting heat map failed: {err}");
+                    }
+                },
+            );
+        }
+    }
+
+    fn state(&self) -> MutexGuard<State> {
+        self.shared.state.lock().unwrap()
+    }
+}
+
+fn read_summary(path: &Path) -> io::Result<HeatSummary> {
+    let bytes = fs::read(path)?;
+    rkyv::from_bytes(&bytes).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid heat map summary file: {err}"),
+        )
+    })
+}
+
+/// Writes the summary to a temporary file first, and then moves it into
+/// place, so a crash mid-write doesn't leave a truncated summary behind.
+fn write_summary(path: &Path, summary: &HeatSummary) -> io::Result<()> {
+    let bytes = rkyv::to_bytes::<_, 1024>(summary).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed serializing heat map summary: {err}"),
+        )
+    })?;
+
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(tmp_path, path)
+}
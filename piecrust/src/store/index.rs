@@ -0,0 +1,172 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! An append-only index of the commits in a store, and their bases.
+//!
+//! Every commit added to or removed from the store appends a fixed size
+//! record to the index file, so a store can be loaded without scanning and
+//! validating the directory of each commit. Each record carries a checksum,
+//! allowing a file that is corrupted, or was only partially written, to be
+//! detected and discarded in favor of a scan.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::store::tree::Hash;
+
+const INSERT: u8 = 0;
+const REMOVE: u8 = 1;
+
+const HASH_LEN: usize = 32;
+const CHECKSUM_LEN: usize = 8;
+
+/// The kind of the record, the root of the commit, whether it has a base, and
+/// the base itself, followed by the checksum of all of them.
+const RECORD_LEN: usize = 1 + HASH_LEN + 1 + HASH_LEN + CHECKSUM_LEN;
+
+/// The commits of a store, indexed by their root, along with their bases.
+pub(crate) type IndexedCommits = BTreeMap<Hash, Option<Hash>>;
+
+/// An open commit index, appending a record for each change to the commits of
+/// a store.
+#[derive(Debug)]
+pub(crate) struct CommitIndex {
+    path: PathBuf,
+    file: File,
+}
+
+impl CommitIndex {
+    /// Reads the commits recorded in the index at the given `path`.
+    ///
+    /// Returns `None` if there is no index, or if it is corrupt.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Option<IndexedCommits>> {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(None)
+            }
+            Err(err) => return Err(err),
+        };
+
+        if bytes.len() % RECORD_LEN != 0 {
+            return Ok(None);
+        }
+
+        let mut commits = IndexedCommits::new();
+        for record in bytes.chunks_exact(RECORD_LEN) {
+            let Some((kind, root, base)) = decode(record) else {
+                return Ok(None);
+            };
+            match kind {
+                INSERT => {
+                    commits.insert(root, base);
+                }
+                REMOVE => {
+                    commits.remove(&root);
+                }
+                _ => return Ok(None),
+            }
+        }
+
+        Ok(Some(commits))
+    }
+
+    /// Writes a new index at the given `path` holding the given `commits`,
+    /// replacing the existing one, if any, and opens it to append to.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        commits: &IndexedCommits,
+    ) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        let mut bytes = Vec::with_capacity(commits.len() * RECORD_LEN);
+        for (root, base) in commits {
+            bytes.extend(encode(INSERT, root, base.as_ref()));
+        }
+
+        // The index is written to a temporary file first, so a failure never
+        // leaves a partial index behind.
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(tmp_path, path)?;
+
+        let file = OpenOptions::new().append(true).open(path)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+
+    /// Records the insertion of the commit with the given `root` and `base`.
+    ///
+    /// A commit inserted again, such as after its base changes, replaces the
+    /// earlier record.
+    pub fn record_insert(
+        &mut self,
+        root: &Hash,
+        base: Option<&Hash>,
+    ) -> io::Result<()> {
+        self.file.write_all(&encode(INSERT, root, base))
+    }
+
+    /// Records the removal of the commit with the given `root`.
+    pub fn record_remove(&mut self, root: &Hash) -> io::Result<()> {
+        self.file.write_all(&encode(REMOVE, root, None))
+    }
+
+    /// Removes the index from disk, so that the store is scanned the next
+    /// time it's loaded.
+    pub fn discard(self) {
+        let _ = fs::remove_file(self.path);
+    }
+}
+
+fn encode(kind: u8, root: &Hash, base: Option<&Hash>) -> [u8; RECORD_LEN] {
+    let mut record = [0u8; RECORD_LEN];
+
+    record[0] = kind;
+    record[1..][..HASH_LEN].copy_from_slice(root.as_bytes());
+    if let Some(base) = base {
+        record[1 + HASH_LEN] = 1;
+        record[2 + HASH_LEN..][..HASH_LEN].copy_from_slice(base.as_bytes());
+    }
+
+    let checksum = checksum(&record[..RECORD_LEN - CHECKSUM_LEN]);
+    record[RECORD_LEN - CHECKSUM_LEN..].copy_from_slice(&checksum);
+
+    record
+}
+
+fn decode(record: &[u8]) -> Option<(u8, Hash, Option<Hash>)> {
+    let (data, checksum_bytes) = record.split_at(RECORD_LEN - CHECKSUM_LEN);
+    if checksum(data) != checksum_bytes {
+        return None;
+    }
+
+    let mut root = [0u8; HASH_LEN];
+    root.copy_from_slice(&data[1..][..HASH_LEN]);
+
+    let base = match data[1 + HASH_LEN] {
+        0 => None,
+        1 => {
+            let mut base = [0u8; HASH_LEN];
+            base.copy_from_slice(&data[2 + HASH_LEN..][..HASH_LEN]);
+            Some(Hash::from(base))
+        }
+        _ => return None,
+    };
+
+    Some((data[0], Hash::from(root), base))
+}
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    checksum.copy_from_slice(&blake3::hash(data).as_bytes()[..CHECKSUM_LEN]);
+    checksum
+}
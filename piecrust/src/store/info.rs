@@ -0,0 +1,105 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Information on the commits in a store, as found on disk.
+
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::root::Root;
+use crate::store::tree::Hash;
+use crate::store::{
+    base_from_path, CommitStore, BASE_FILE, LEAF_DIR, MEMORY_DIR,
+};
+
+/// Information on a commit, as returned by [`VM::commit_infos`].
+///
+/// [`VM::commit_infos`]: crate::VM::commit_infos
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitInfo {
+    /// The root of the commit.
+    pub root: Root,
+    /// The root of the commit this commit was written on top of, if any. The
+    /// parent may have since been finalized or deleted.
+    pub parent: Option<Root>,
+    /// When the commit was written, if the filesystem records it.
+    pub created: Option<SystemTime>,
+    /// The number of contracts in the state of the commit, including those
+    /// inherited from its parent.
+    pub contracts: usize,
+    /// The size of the files written for this commit, in bytes.
+    ///
+    /// Bytecode is shared between commits, and is therefore not included.
+    /// Neither are the files of a [cold] commit.
+    ///
+    /// [cold]: crate::VM::cool_commit
+    pub size: u64,
+}
+
+impl CommitInfo {
+    /// Returns whether the commit only holds the changes from its parent, as
+    /// opposed to the entire state.
+    pub fn is_diff(&self) -> bool {
+        self.parent.is_some()
+    }
+}
+
+/// Gathers the information on the commit with the given `root`, or returns
+/// `None` if it is not in the store.
+pub(crate) fn commit_info(
+    main_dir: &Path,
+    root: Hash,
+    commit_store: &Mutex<CommitStore>,
+) -> Option<CommitInfo> {
+    let (parent, contracts) = {
+        let commit_store = commit_store.lock().unwrap();
+        let commit = commit_store.get_commit(&root)?;
+        (commit.base, commit.contracts_merkle.len() as usize)
+    };
+
+    let root_hex = hex::encode(root);
+    let commit_dir = main_dir.join(&root_hex);
+
+    let created = fs::metadata(&commit_dir)
+        .and_then(|metadata| metadata.created().or(metadata.modified()))
+        .ok();
+
+    let mut size = dir_size(&commit_dir);
+    if let Ok(base_info) = base_from_path(commit_dir.join(BASE_FILE)) {
+        for contract in base_info.contract_hints {
+            let contract_hex = hex::encode(contract);
+            for dir in [MEMORY_DIR, LEAF_DIR] {
+                let dir =
+                    main_dir.join(dir).join(&contract_hex).join(&root_hex);
+                size += dir_size(dir);
+            }
+        }
+    }
+
+    Some(CommitInfo {
+        root: root.into(),
+        parent: parent.map(Into::into),
+        created,
+        contracts,
+        size,
+    })
+}
+
+/// Sums the size of the files directly in the given directory, counting
+/// nothing if it can't be read.
+fn dir_size<P: AsRef<Path>>(dir: P) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+
+    entries
+        .filter_map(|entry| entry.ok()?.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
@@ -0,0 +1,843 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! The on-disk layout of a store, and a checker for its invariants.
+//!
+//! A store's root directory holds a `main` directory with everything the VM
+//! persists, a `version` file with the version of the layout described here,
+//! and possibly a `heatmap` file and a `commits` file indexing the commits in
+//! the `main` directory. The `main` directory holds:
+//!
+//! - `bytecode/<contract>`, along with `<contract>.a` and `<contract>.m`, for
+//!   the bytecode, compiled module, and metadata of each contract ever
+//!   committed.
+//! - `code/<hash>` and `code/<hash>.a` for the bytecode and compiled module
+//!   shared by every contract deployed with the bytecode of the given hash,
+//!   which the files of the contracts in `bytecode` are hard links to.
+//! - `memory/<contract>/<page>` for the finalized memory pages of a contract,
+//!   and `memory/<contract>/<commit>/<page>` for the pages written by each
+//!   commit that is not finalized yet.
+//! - `leaf/<contract>/element` for the finalized index element of a contract,
+//!   and `leaf/<contract>/<commit>/element` for the element written by each
+//!   commit that is not finalized yet. Commits that removed the contract hold
+//!   an empty `leaf/<contract>/<commit>/removed` file in place of the element.
+//! - `<commit>/base`, naming the commit's base and the contracts it changed,
+//!   and `<commit>/tree_pos` or `<commit>/tree_pos_opt`, with the positions of
+//!   the contracts in its merkle tree. Commits made since instruction costs are
+//!   recorded also hold `<commit>/instruction_costs`, with the hash of the
+//!   costs they were made with.
+//! - `instruction_costs`, with the hash of the costs the finalized state was
+//!   made with, if it was made since they are recorded.
+//!
+//! Contracts and commits are named by the hex encoding of their ID and root,
+//! and pages by their decimal index. Reading a page or element of a commit
+//! looks in the commit's own directory, then in those of its bases, and
+//! finally in the finalized files. Cooled commits leave symbolic links behind
+//! in place of their directories.
+//!
+//! The rules are listed by [`LayoutRule`], and rendered by [`layout_spec`].
+//! They are checked by [`check_layout`], which is meant to be run by operators
+//! on a store no VM is currently using.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display, Formatter, Write};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use piecrust_uplink::ContractId;
+
+use crate::store::session::ContractSession;
+use crate::store::tree::{contract_leaf, ContractIndexElement, Hash};
+use crate::store::{
+    base_from_path, instruction_costs_from_path, tree_pos_from_path, BASE_FILE,
+    BYTECODE_DIR, CODE_DIR, COMMIT_INDEX_FILE, ELEMENT_FILE, HEAT_MAP_FILE,
+    INSTRUCTION_COSTS_FILE, LEAF_DIR, MAIN_DIR, MEMORY_DIR, METADATA_EXTENSION,
+    OBJECTCODE_EXTENSION, REMOVED_FILE, STORE_VERSION, TREE_POS_FILE,
+    TREE_POS_OPT_FILE, VERSION_FILE,
+};
+
+/// A structural rule of the on-disk layout of a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LayoutRule {
+    /// The root directory holds the main directory.
+    MainDir,
+    /// The store records the version of its layout.
+    Version,
+    /// Only known entries are present.
+    EntryName,
+    /// Links resolve to directories.
+    Link,
+    /// Commit directories hold their files.
+    CommitFiles,
+    /// Base chains are acyclic.
+    BaseChain,
+    /// Contracts have their bytecode files.
+    BytecodeFiles,
+    /// Index elements are present and valid.
+    Element,
+    /// Hinted contracts have elements.
+    Hint,
+    /// Pages of elements are present.
+    Page,
+    /// Elements agree with their commit's tree positions.
+    TreePos,
+    /// Commit directories of contracts belong to a commit.
+    Orphan,
+    /// No temporary files remain.
+    Temporary,
+}
+
+impl LayoutRule {
+    /// Every rule, in the order they are documented.
+    pub const ALL: [LayoutRule; 13] = [
+        LayoutRule::MainDir,
+        LayoutRule::Version,
+        LayoutRule::EntryName,
+        LayoutRule::Link,
+        LayoutRule::CommitFiles,
+        LayoutRule::BaseChain,
+        LayoutRule::BytecodeFiles,
+        LayoutRule::Element,
+        LayoutRule::Hint,
+        LayoutRule::Page,
+        LayoutRule::TreePos,
+        LayoutRule::Orphan,
+        LayoutRule::Temporary,
+    ];
+
+    /// A short, stable identifier of the rule.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LayoutRule::MainDir => "main-dir",
+            LayoutRule::Version => "version",
+            LayoutRule::EntryName => "entry-name",
+            LayoutRule::Link => "link",
+            LayoutRule::CommitFiles => "commit-files",
+            LayoutRule::BaseChain => "base-chain",
+            LayoutRule::BytecodeFiles => "bytecode-files",
+            LayoutRule::Element => "element",
+            LayoutRule::Hint => "hint",
+            LayoutRule::Page => "page",
+            LayoutRule::TreePos => "tree-pos",
+            LayoutRule::Orphan => "orphan",
+            LayoutRule::Temporary => "temporary",
+        }
+    }
+
+    /// The rule, as documented in [`layout_spec`].
+    pub fn description(&self) -> &'static str {
+        match self {
+            LayoutRule::MainDir => {
+                "The root directory holds a `main` directory and a `version` \
+                 file, and optionally a `heatmap` file and a `commits` file."
+            }
+            LayoutRule::Version => {
+                "The `version` file holds the version of the layout, which is \
+                 the one the VM supports."
+            }
+            LayoutRule::EntryName => {
+                "`main` only holds the `bytecode`, `code`, `memory`, and \
+                 `leaf` directories, a directory per commit named by its \
+                 root, and optionally an `instruction_costs` file. Contracts are named by their ID, shared code by the \
+                 hash of its bytecode, and pages by their decimal index."
+            }
+            LayoutRule::Link => {
+                "Symbolic links, left behind by cooling a commit, resolve to \
+                 directories."
+            }
+            LayoutRule::CommitFiles => {
+                "A commit directory holds a `base` file, a `tree_pos` or \
+                 `tree_pos_opt` file, and optionally an `instruction_costs` \
+                 file, all of which decode."
+            }
+            LayoutRule::BaseChain => {
+                "Following the bases of a commit never leads back to it."
+            }
+            LayoutRule::BytecodeFiles => {
+                "Every contract in `memory` or `leaf` has its bytecode, along \
+                 with its compiled module and metadata, in `bytecode`."
+            }
+            LayoutRule::Element => {
+                "A contract's leaf directory holds only an `element` file and \
+                 commit directories holding one, or a `removed` file if the \
+                 commit removed the contract. Elements all decode."
+            }
+            LayoutRule::Hint => {
+                "Every contract named in the `base` file of a commit has an \
+                 element, or a `removed` file, written by the commit."
+            }
+            LayoutRule::Page => {
+                "Every page of an element is found in its commit, one of its \
+                 bases, or the contract's finalized pages."
+            }
+            LayoutRule::TreePos => {
+                "Every element written by a commit is at the position, and has \
+                 the hash bound to its contract, recorded in the commit's tree \
+                 positions."
+            }
+            LayoutRule::Orphan => {
+                "Every commit directory in `memory` or `leaf` belongs to a \
+                 commit in `main`."
+            }
+            LayoutRule::Temporary => {
+                "No temporary files, left by interrupted writes, remain."
+            }
+        }
+    }
+}
+
+impl Display for LayoutRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// Renders the rules of the on-disk layout as a Markdown list, for inclusion
+/// in documentation.
+///
+/// The list is generated from the same rules [`check_layout`] checks, so the
+/// two can't drift apart.
+pub fn layout_spec() -> String {
+    LayoutRule::ALL
+        .iter()
+        .fold(String::new(), |mut spec, rule| {
+            let _ =
+                writeln!(spec, "- `{}`: {}", rule.code(), rule.description());
+            spec
+        })
+}
+
+/// A violation of a [`LayoutRule`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutIssue {
+    /// The rule violated.
+    pub rule: LayoutRule,
+    /// The path at fault, relative to the root directory of the store.
+    pub path: PathBuf,
+    /// What is wrong with the path.
+    pub detail: String,
+}
+
+impl Display for LayoutIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}", self.rule, self.path.display(), self.detail)
+    }
+}
+
+/// The outcome of [`check_layout`].
+///
+/// Its [`Display`] implementation is machine-readable: a line with the counts
+/// of commits, contracts, and issues, followed by a line per issue with the
+/// rule's code, the path, and the detail, separated by tabs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LayoutReport {
+    /// The number of commits found.
+    pub commits: usize,
+    /// The number of contracts found.
+    pub contracts: usize,
+    /// The violations found, in the order they were found.
+    pub issues: Vec<LayoutIssue>,
+}
+
+impl LayoutReport {
+    /// Returns whether the store follows every rule.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns the issues violating the given `rule`.
+    pub fn issues_with(
+        &self,
+        rule: LayoutRule,
+    ) -> impl Iterator<Item = &LayoutIssue> {
+        self.issues.iter().filter(move |issue| issue.rule == rule)
+    }
+}
+
+impl Display for LayoutReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "commits={}\tcontracts={}\tissues={}",
+            self.commits,
+            self.contracts,
+            self.issues.len()
+        )?;
+        for issue in &self.issues {
+            writeln!(f, "{issue}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the store at the given `root_dir` against every [`LayoutRule`].
+///
+/// The store should not be in use by a VM while it is checked, since files
+/// being written or moved would be reported as issues.
+///
+/// # Errors
+/// Only if the root directory itself can't be read. Anything wrong within it
+/// is reported as an issue.
+pub fn check_layout<P: AsRef<Path>>(root_dir: P) -> io::Result<LayoutReport> {
+    let mut checker = Checker {
+        root_dir: root_dir.as_ref().to_path_buf(),
+        main_dir: root_dir.as_ref().join(MAIN_DIR),
+        report: LayoutReport::default(),
+    };
+    checker.check()?;
+    Ok(checker.report)
+}
+
+struct Checker {
+    root_dir: PathBuf,
+    main_dir: PathBuf,
+    report: LayoutReport,
+}
+
+/// What is known of a commit after checking its directory.
+struct CommitEntry {
+    base: Option<Hash>,
+    hints: Vec<ContractId>,
+    // The hash at each internal position of the commit's tree.
+    tree_pos: Option<BTreeMap<u64, Hash>>,
+}
+
+impl Checker {
+    fn issue(&mut self, rule: LayoutRule, path: &Path, detail: impl Display) {
+        let path = path.strip_prefix(&self.root_dir).unwrap_or(path);
+        self.report.issues.push(LayoutIssue {
+            rule,
+            path: path.to_path_buf(),
+            detail: detail.to_string(),
+        });
+    }
+
+    fn check(&mut self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.root_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if self.check_temporary(&name, &entry.path()) {
+                continue;
+            }
+            if name != MAIN_DIR
+                && name != VERSION_FILE
+                && name != HEAT_MAP_FILE
+                && name != COMMIT_INDEX_FILE
+            {
+                self.issue(LayoutRule::MainDir, &entry.path(), "unexpected");
+            }
+        }
+
+        self.check_version();
+
+        let main_dir = self.main_dir.clone();
+        if !main_dir.is_dir() {
+            self.issue(LayoutRule::MainDir, &main_dir, "missing");
+            return Ok(());
+        }
+
+        let mut commits = BTreeMap::new();
+        let Some(entries) = self.read_dir(&main_dir) else {
+            return Ok(());
+        };
+        for (name, path) in entries {
+            if name == BYTECODE_DIR
+                || name == CODE_DIR
+                || name == MEMORY_DIR
+                || name == LEAF_DIR
+                || name == INSTRUCTION_COSTS_FILE
+            {
+                continue;
+            }
+            if self.check_temporary(&name, &path) {
+                continue;
+            }
+            let Some(root) = parse_hex(&name).map(Hash::from) else {
+                self.issue(LayoutRule::EntryName, &path, "unexpected");
+                continue;
+            };
+            if !self.check_dir(&path) {
+                continue;
+            }
+            if let Some(commit) = self.check_commit_dir(&path) {
+                commits.insert(root, commit);
+            }
+        }
+        self.report.commits = commits.len();
+
+        let cyclic = self.check_base_chains(&commits);
+        let contracts = self.check_bytecode_dir();
+        self.check_code_dir();
+        self.report.contracts = contracts.len();
+        self.check_contract_dirs(MEMORY_DIR, &contracts, &commits);
+        self.check_contract_dirs(LEAF_DIR, &contracts, &commits);
+        self.check_elements(&commits, &cyclic);
+
+        Ok(())
+    }
+
+    /// Reads the entries of the given directory, sorted by name, reporting an
+    /// issue if it can't be read.
+    fn read_dir(&mut self, dir: &Path) -> Option<Vec<(String, PathBuf)>> {
+        let entries = fs::read_dir(dir).and_then(|entries| {
+            entries
+                .map(|entry| {
+                    let entry = entry?;
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    Ok((name, entry.path()))
+                })
+                .collect::<io::Result<Vec<_>>>()
+        });
+
+        match entries {
+            Ok(mut entries) => {
+                entries.sort();
+                Some(entries)
+            }
+            Err(err) => {
+                self.issue(LayoutRule::EntryName, dir, err);
+                None
+            }
+        }
+    }
+
+    /// Checks that the given path is a directory, or a link to one.
+    fn check_dir(&mut self, path: &Path) -> bool {
+        let is_link = fs::symlink_metadata(path)
+            .map(|metadata| metadata.file_type().is_symlink())
+            .unwrap_or(false);
+
+        match (path.is_dir(), is_link) {
+            (true, _) => true,
+            (false, true) => {
+                self.issue(LayoutRule::Link, path, "does not resolve");
+                false
+            }
+            (false, false) => {
+                self.issue(LayoutRule::EntryName, path, "not a directory");
+                false
+            }
+        }
+    }
+
+    /// Reports the entry with the given name if it is a temporary file.
+    fn check_version(&mut self) {
+        let path = self.root_dir.join(VERSION_FILE);
+        match fs::read_to_string(&path) {
+            Ok(version) => {
+                let version = version.trim();
+                if version != STORE_VERSION.to_string() {
+                    self.issue(
+                        LayoutRule::Version,
+                        &path,
+                        format!("found {version}, expected {STORE_VERSION}"),
+                    );
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.issue(LayoutRule::Version, &path, "missing");
+            }
+            Err(err) => self.issue(LayoutRule::Version, &path, err),
+        }
+    }
+
+    fn check_temporary(&mut self, name: &str, path: &Path) -> bool {
+        let is_temporary = name.ends_with(".tmp");
+        if is_temporary {
+            self.issue(LayoutRule::Temporary, path, "leftover");
+        }
+        is_temporary
+    }
+
+    fn check_commit_dir(&mut self, dir: &Path) -> Option<CommitEntry> {
+        let entries = self.read_dir(dir)?;
+        for (name, path) in &entries {
+            if self.check_temporary(name, path) {
+                continue;
+            }
+            if name != BASE_FILE
+                && name != TREE_POS_FILE
+                && name != TREE_POS_OPT_FILE
+                && name != INSTRUCTION_COSTS_FILE
+            {
+                self.issue(LayoutRule::CommitFiles, path, "unexpected");
+            }
+        }
+
+        let base_path = dir.join(BASE_FILE);
+        let base_info = match base_from_path(&base_path) {
+            Ok(base_info) => base_info,
+            Err(err) => {
+                self.issue(LayoutRule::CommitFiles, &base_path, err);
+                return None;
+            }
+        };
+
+        let instruction_costs_path = dir.join(INSTRUCTION_COSTS_FILE);
+        if let Err(err) = instruction_costs_from_path(&instruction_costs_path) {
+            self.issue(LayoutRule::CommitFiles, &instruction_costs_path, err);
+        }
+
+        let tree_pos_path = dir.join(TREE_POS_FILE);
+        let tree_pos_opt_path = dir.join(TREE_POS_OPT_FILE);
+        let tree_pos =
+            match tree_pos_from_path(&tree_pos_path, tree_pos_opt_path) {
+                Ok(tree_pos) => Some(
+                    tree_pos
+                        .iter()
+                        .map(|(int_pos, (hash, _))| {
+                            (u64::from(*int_pos), *hash)
+                        })
+                        .collect(),
+                ),
+                Err(err) => {
+                    self.issue(LayoutRule::CommitFiles, &tree_pos_path, err);
+                    None
+                }
+            };
+
+        Some(CommitEntry {
+            base: base_info.maybe_base,
+            hints: base_info.contract_hints,
+            tree_pos,
+        })
+    }
+
+    /// Checks that the base chains of the `commits` are acyclic, returning
+    /// the commits whose chain is not.
+    fn check_base_chains(
+        &mut self,
+        commits: &BTreeMap<Hash, CommitEntry>,
+    ) -> BTreeSet<Hash> {
+        let mut cyclic = BTreeSet::new();
+
+        for root in commits.keys() {
+            let mut seen = BTreeSet::from([*root]);
+            let mut maybe_base = commits[root].base;
+
+            while let Some(base) = maybe_base {
+                if !seen.insert(base) {
+                    let path = self.main_dir.join(hex::encode(root));
+                    let detail =
+                        format!("cycles through {}", hex::encode(base));
+                    self.issue(LayoutRule::BaseChain, &path, detail);
+                    cyclic.insert(*root);
+                    break;
+                }
+                // A base that is not in the store was finalized or deleted,
+                // ending the chain.
+                maybe_base = commits.get(&base).and_then(|commit| commit.base);
+            }
+        }
+
+        cyclic
+    }
+
+    /// Checks the bytecode directory, returning the contracts found in it.
+    fn check_bytecode_dir(&mut self) -> BTreeSet<ContractId> {
+        let dir = self.main_dir.join(BYTECODE_DIR);
+        let mut contracts = BTreeSet::new();
+
+        if !dir.is_dir() {
+            return contracts;
+        }
+        let Some(entries) = self.read_dir(&dir) else {
+            return contracts;
+        };
+
+        let mut files = BTreeMap::<ContractId, BTreeSet<String>>::new();
+        for (name, path) in entries {
+            if self.check_temporary(&name, &path) {
+                continue;
+            }
+            let (stem, extension) = match name.split_once('.') {
+                Some((stem, extension)) => (stem, extension),
+                None => (name.as_str(), ""),
+            };
+            let known_extension = extension.is_empty()
+                || extension == OBJECTCODE_EXTENSION
+                || extension == METADATA_EXTENSION;
+
+            match parse_hex(stem) {
+                Some(bytes) if known_extension && path.is_file() => {
+                    let contract = ContractId::from_bytes(bytes);
+                    files.entry(contract).or_default().insert(extension.into());
+                }
+                _ => self.issue(LayoutRule::EntryName, &path, "unexpected"),
+            }
+        }
+
+        for (contract, extensions) in files {
+            for extension in ["", OBJECTCODE_EXTENSION, METADATA_EXTENSION] {
+                if !extensions.contains(extension) {
+                    let path = dir.join(hex::encode(contract));
+                    let path = match extension {
+                        "" => path,
+                        extension => path.with_extension(extension),
+                    };
+                    self.issue(LayoutRule::BytecodeFiles, &path, "missing");
+                }
+            }
+            if extensions.contains("") {
+                contracts.insert(contract);
+            }
+        }
+
+        contracts
+    }
+
+    /// Checks the naming of the files holding shared code.
+    fn check_code_dir(&mut self) {
+        let dir = self.main_dir.join(CODE_DIR);
+        if !dir.is_dir() {
+            return;
+        }
+        let Some(entries) = self.read_dir(&dir) else {
+            return;
+        };
+
+        for (name, path) in entries {
+            if self.check_temporary(&name, &path) {
+                continue;
+            }
+            let stem = name
+                .strip_suffix(&format!(".{OBJECTCODE_EXTENSION}"))
+                .unwrap_or(&name);
+            if parse_hex(stem).is_none() || !path.is_file() {
+                self.issue(LayoutRule::EntryName, &path, "unexpected");
+            }
+        }
+    }
+
+    /// Checks the naming of the contract directories under the memory or
+    /// leaf directory, and of their commit directories.
+    fn check_contract_dirs(
+        &mut self,
+        kind: &str,
+        contracts: &BTreeSet<ContractId>,
+        commits: &BTreeMap<Hash, CommitEntry>,
+    ) {
+        let dir = self.main_dir.join(kind);
+        if !dir.is_dir() {
+            return;
+        }
+        let Some(entries) = self.read_dir(&dir) else {
+            return;
+        };
+
+        for (name, path) in entries {
+            let Some(bytes) = parse_hex(&name) else {
+                self.issue(LayoutRule::EntryName, &path, "unexpected");
+                continue;
+            };
+            if !self.check_dir(&path) {
+                continue;
+            }
+            if !contracts.contains(&ContractId::from_bytes(bytes)) {
+                self.issue(LayoutRule::BytecodeFiles, &path, "no bytecode");
+            }
+
+            let Some(entries) = self.read_dir(&path) else {
+                continue;
+            };
+            for (name, path) in entries {
+                if let Some(root) = parse_hex(&name).map(Hash::from) {
+                    if !self.check_dir(&path) {
+                        continue;
+                    }
+                    if !commits.contains_key(&root) {
+                        self.issue(LayoutRule::Orphan, &path, "no commit");
+                    }
+                    let Some(entries) = self.read_dir(&path) else {
+                        continue;
+                    };
+                    for (name, path) in entries {
+                        self.check_contract_file(kind, &name, &path, true);
+                    }
+                    continue;
+                }
+
+                self.check_contract_file(kind, &name, &path, false);
+            }
+        }
+    }
+
+    /// Checks a file of a contract under the memory or leaf directory, which
+    /// is a page or an element respectively. The leaf directory of a commit
+    /// may hold a removal marker instead.
+    fn check_contract_file(
+        &mut self,
+        kind: &str,
+        name: &str,
+        path: &Path,
+        in_commit: bool,
+    ) {
+        if self.check_temporary(name, path) {
+            return;
+        }
+        let (known, rule) = match kind {
+            MEMORY_DIR => {
+                (name.parse::<usize>().is_ok(), LayoutRule::EntryName)
+            }
+            _ => (
+                name == ELEMENT_FILE || (in_commit && name == REMOVED_FILE),
+                LayoutRule::Element,
+            ),
+        };
+        if !known || !path.is_file() {
+            self.issue(rule, path, "unexpected");
+        }
+    }
+
+    /// Checks the elements of every contract, both finalized and written by
+    /// each commit, along with the pages they refer to.
+    ///
+    /// The pages of commits whose base chain is `cyclic` are not checked,
+    /// since they can't be looked up.
+    fn check_elements(
+        &mut self,
+        commits: &BTreeMap<Hash, CommitEntry>,
+        cyclic: &BTreeSet<Hash>,
+    ) {
+        let leaf_dir = self.main_dir.join(LEAF_DIR);
+        let memory_dir = self.main_dir.join(MEMORY_DIR);
+        if !leaf_dir.is_dir() {
+            return;
+        }
+        let Some(entries) = self.read_dir(&leaf_dir) else {
+            return;
+        };
+
+        let mut written = BTreeSet::new();
+        for (name, contract_leaf_dir) in entries {
+            let Some(bytes) = parse_hex(&name) else {
+                continue;
+            };
+            let contract = ContractId::from_bytes(bytes);
+            let contract_memory_dir = memory_dir.join(&name);
+
+            let finalized = contract_leaf_dir.join(ELEMENT_FILE);
+            if finalized.is_file() {
+                if let Some(element) = self.read_element(&finalized) {
+                    self.check_pages(&element, None, &contract_memory_dir);
+                }
+            }
+
+            for (root, commit) in commits {
+                let commit_leaf_dir = contract_leaf_dir.join(hex::encode(root));
+                if commit_leaf_dir.join(REMOVED_FILE).is_file() {
+                    written.insert((*root, contract));
+                    continue;
+                }
+                let path = commit_leaf_dir.join(ELEMENT_FILE);
+                if !path.is_file() {
+                    continue;
+                }
+                written.insert((*root, contract));
+
+                let Some(element) = self.read_element(&path) else {
+                    continue;
+                };
+                if !cyclic.contains(root) {
+                    let commit = Some(*root);
+                    self.check_pages(&element, commit, &contract_memory_dir);
+                }
+                if let Some(tree_pos) = &commit.tree_pos {
+                    self.check_tree_pos(contract, &element, tree_pos, &path);
+                }
+            }
+        }
+
+        for (root, commit) in commits {
+            for contract in &commit.hints {
+                if !written.contains(&(*root, *contract)) {
+                    let path = leaf_dir
+                        .join(hex::encode(contract))
+                        .join(hex::encode(root))
+                        .join(ELEMENT_FILE);
+                    self.issue(LayoutRule::Hint, &path, "missing");
+                }
+            }
+        }
+    }
+
+    fn read_element(&mut self, path: &Path) -> Option<ContractIndexElement> {
+        let decoded = fs::read(path).and_then(|bytes| {
+            rkyv::from_bytes(&bytes).map_err(|err| {
+                io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+            })
+        });
+        match decoded {
+            Ok(element) => Some(element),
+            Err(err) => {
+                self.issue(LayoutRule::Element, path, err);
+                None
+            }
+        }
+    }
+
+    /// Checks that every page of the `element` can be found, reading as of
+    /// the given commit.
+    fn check_pages(
+        &mut self,
+        element: &ContractIndexElement,
+        commit: Option<Hash>,
+        memory_dir: &Path,
+    ) {
+        for page_index in element.page_indices() {
+            let found = ContractSession::find_page(
+                *page_index,
+                commit,
+                memory_dir,
+                &self.main_dir,
+            )
+            .unwrap_or_else(|| memory_dir.join(format!("{page_index}")))
+            .is_file();
+
+            if !found {
+                let path = match commit {
+                    Some(root) => memory_dir.join(hex::encode(root)),
+                    None => memory_dir.to_path_buf(),
+                };
+                let path = path.join(format!("{page_index}"));
+                self.issue(LayoutRule::Page, &path, "missing");
+            }
+        }
+    }
+
+    fn check_tree_pos(
+        &mut self,
+        contract: ContractId,
+        element: &ContractIndexElement,
+        tree_pos: &BTreeMap<u64, Hash>,
+        path: &Path,
+    ) {
+        let (Some(int_pos), Some(hash)) = (element.int_pos(), element.hash())
+        else {
+            self.issue(LayoutRule::TreePos, path, "no position");
+            return;
+        };
+
+        match tree_pos.get(&int_pos) {
+            Some(recorded) if *recorded == contract_leaf(&contract, &hash) => {}
+            Some(_) => self.issue(LayoutRule::TreePos, path, "hash mismatch"),
+            None => self.issue(
+                LayoutRule::TreePos,
+                path,
+                format!("position {int_pos} not recorded"),
+            ),
+        }
+    }
+}
+
+/// Parses the hex encoding of a contract ID or commit root.
+fn parse_hex(name: &str) -> Option<[u8; 32]> {
+    if name.len() != 64 {
+        return None;
+    }
+    hex::decode(name).ok()?.try_into().ok()
+}
@@ -0,0 +1,104 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use piecrust_uplink::ContractId;
+
+/// Advisory locks on contracts, shared between all sessions of a store.
+///
+/// Sessions that are to be run concurrently, and later merged, lock the
+/// contracts they intend to touch up front. A contract may only be locked by
+/// one session at a time, so sessions that manage to lock their contracts are
+/// known to touch disjoint sets of them. Nothing stops a session from
+/// touching a contract it didn't lock - conflicts are then caught when
+/// merging.
+#[derive(Debug, Clone, Default)]
+pub struct ContractLocks {
+    held: Arc<Mutex<BTreeMap<ContractId, u64>>>,
+    next_holder: Arc<AtomicU64>,
+}
+
+impl ContractLocks {
+    /// Returns a new, empty, set of locks held by a single session.
+    pub fn holder(&self) -> HeldLocks {
+        HeldLocks {
+            locks: self.clone(),
+            holder: self.next_holder.fetch_add(1, Ordering::Relaxed),
+            contracts: BTreeSet::new(),
+        }
+    }
+}
+
+/// The locks held by a session, released when dropped.
+#[derive(Debug)]
+pub struct HeldLocks {
+    locks: ContractLocks,
+    holder: u64,
+    contracts: BTreeSet<ContractId>,
+}
+
+impl HeldLocks {
+    /// Locks all the given `contracts`, or none of them if any is held by
+    /// another session, in which case the contracts held are returned.
+    ///
+    /// Locking a contract already held is a no-op.
+    pub fn acquire(
+        &mut self,
+        contracts: &[ContractId],
+    ) -> Result<(), Vec<ContractId>> {
+        let mut held = self.locks.held.lock().unwrap();
+
+        let taken: Vec<_> = contracts
+            .iter()
+            .filter(|contract| {
+                matches!(held.get(contract), Some(holder) if *holder != self.holder)
+            })
+            .copied()
+            .collect();
+        if !taken.is_empty() {
+            return Err(taken);
+        }
+
+        for contract in contracts {
+            held.insert(*contract, self.holder);
+            self.contracts.insert(*contract);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the contracts locked.
+    pub fn contracts(&self) -> &BTreeSet<ContractId> {
+        &self.contracts
+    }
+
+    /// Takes over the locks held by `other`, leaving it with none.
+    pub fn absorb(&mut self, other: &mut HeldLocks) {
+        let mut held = self.locks.held.lock().unwrap();
+        for contract in mem::take(&mut other.contracts) {
+            held.insert(contract, self.holder);
+            self.contracts.insert(contract);
+        }
+    }
+}
+
+impl Drop for HeldLocks {
+    fn drop(&mut self) {
+        if self.contracts.is_empty() {
+            return;
+        }
+        let mut held = self.locks.held.lock().unwrap();
+        for contract in &self.contracts {
+            if held.get(contract) == Some(&self.holder) {
+                held.remove(contract);
+            }
+        }
+    }
+}
@@ -17,6 +17,10 @@ use dusk_wasmtime::LinearMemory;
 
 pub const PAGE_SIZE: usize = 0x10000;
 
+/// The smallest size changes to memory can be tracked at, matching the page
+/// size of most systems.
+pub const MIN_TRACKING_PAGE_SIZE: usize = 0x1000;
+
 const WASM32_MAX_PAGES: usize = 0x10000;
 const WASM64_MAX_PAGES: usize = 0x4000000;
 
@@ -108,6 +112,16 @@ impl Memory {
     pub fn is_64(&self) -> bool {
         self.inner.is_64
     }
+
+    /// Returns the indices of the pages read or written to since the memory
+    /// was loaded, in ascending order.
+    pub fn accessed_pages(&self) -> impl Iterator<Item = usize> + '_ {
+        let n_pages = self.inner.current_len.div_ceil(PAGE_SIZE);
+        self.inner
+            .mmap
+            .accessed_pages()
+            .take_while(move |page_index| *page_index < n_pages)
+    }
 }
 
 /// This implementation of clone is dangerous, and must be accompanied by the
@@ -10,9 +10,13 @@ use std::sync::Arc;
 use std::{io, mem};
 
 use memmap2::{Mmap, MmapOptions};
+use piecrust_uplink::ContractId;
 
-use crate::contract::ContractMetadata;
-use crate::{Error, Session};
+use crate::contract::{
+    ContractMetadata, LegacyContractMetadata, NoncedContractMetadata,
+    Provenance, RestrictedContractMetadata,
+};
+use crate::Error;
 
 /// Contract metadata pertaining to a given contract but maintained by the host.
 #[derive(Debug, Clone)]
@@ -38,16 +42,46 @@ impl Metadata {
         })
     }
 
-    pub(crate) fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    /// Reads the metadata of the given `contract` from the file at `path`.
+    pub(crate) fn from_file<P: AsRef<Path>>(
+        path: P,
+        contract: ContractId,
+    ) -> io::Result<Self> {
         let file = File::open(path)?;
 
         let mmap = unsafe { Mmap::map(&file)? };
-        let data = rkyv::from_bytes(&mmap).map_err(|_| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                "contract metadata invalid in file",
-            )
-        })?;
+
+        // Metadata persisted in older layouts is still read, with whatever
+        // they didn't record left unset. Since any layout might happen to be
+        // valid when reading another, the ID of the contract is checked as
+        // well.
+        let data = rkyv::from_bytes::<ContractMetadata>(&mmap)
+            .ok()
+            .filter(|data| data.contract_id == contract)
+            .or_else(|| {
+                rkyv::from_bytes::<RestrictedContractMetadata>(&mmap)
+                    .ok()
+                    .map(ContractMetadata::from)
+                    .filter(|data| data.contract_id == contract)
+            })
+            .or_else(|| {
+                rkyv::from_bytes::<NoncedContractMetadata>(&mmap)
+                    .ok()
+                    .map(ContractMetadata::from)
+                    .filter(|data| data.contract_id == contract)
+            })
+            .or_else(|| {
+                rkyv::from_bytes::<LegacyContractMetadata>(&mmap)
+                    .ok()
+                    .map(ContractMetadata::from)
+                    .filter(|data| data.contract_id == contract)
+            })
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "contract metadata invalid in file",
+                )
+            })?;
 
         Ok(Self {
             mmap: Arc::new(mmap),
@@ -59,11 +93,15 @@ impl Metadata {
         &self.data
     }
 
+    pub(crate) fn set_provenance(&mut self, provenance: Provenance) {
+        self.data.provenance = provenance;
+    }
+
     pub(crate) fn set_data(
         &mut self,
         data: ContractMetadata,
     ) -> Result<(), Error> {
-        let bytes = Session::serialize_data(&data)?;
+        let bytes = data.to_bytes()?;
 
         let mut new = Self::new(bytes, data)
             .map_err(|err| Error::PersistenceError(Arc::new(err)))?;
@@ -9,6 +9,7 @@ use std::ops::Deref;
 use std::path::Path;
 
 use dusk_wasmtime::Engine;
+use piecrust_uplink::SCRATCH_MEMORY;
 
 /// WASM object code belonging to a given contract.
 #[derive(Debug, Clone)]
@@ -17,9 +18,10 @@ pub struct Module {
 }
 
 fn check_single_memory(module: &dusk_wasmtime::Module) -> io::Result<()> {
-    // Ensure the module only has one memory
+    // Ensure the module only has one memory, other than the scratch memory
     let n_memories = module
         .exports()
+        .filter(|exp| exp.name() != SCRATCH_MEMORY)
         .filter_map(|exp| exp.ty().memory().map(|_| ()))
         .count();
     if n_memories != 1 {
@@ -99,6 +101,7 @@ impl Module {
     pub(crate) fn is_64(&self) -> bool {
         self.module
             .exports()
+            .filter(|exp| exp.name() != SCRATCH_MEMORY)
             .filter_map(|exp| exp.ty().memory().map(|mem_ty| mem_ty.is_64()))
             .next()
             .expect("We guarantee the module has one memory")
@@ -0,0 +1,129 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Replies to calls made to the store's synchronization loop.
+//!
+//! A reply can be either blocked on, or awaited as a [`Future`], allowing the
+//! store to be driven from async code without dedicating a thread to waiting
+//! on it.
+
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+struct State<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    cond: Condvar,
+}
+
+/// Creates a replier, given to the synchronization loop, and the reply it
+/// sends to.
+pub(crate) fn reply_channel<T>() -> (Replier<T>, Reply<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            value: None,
+            waker: None,
+            closed: false,
+        }),
+        cond: Condvar::new(),
+    });
+
+    (
+        Replier {
+            shared: shared.clone(),
+        },
+        Reply { shared },
+    )
+}
+
+/// Sends a single value to a [`Reply`].
+pub(crate) struct Replier<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Replier<T> {
+    /// Sends the `value`, waking whoever is waiting on the reply.
+    ///
+    /// Errors with the value if the reply has been dropped.
+    pub fn send(self, value: T) -> Result<(), T> {
+        if Arc::strong_count(&self.shared) == 1 {
+            return Err(value);
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        state.value = Some(value);
+        Ok(())
+    }
+}
+
+impl<T> Drop for Replier<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed = true;
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.shared.cond.notify_all();
+    }
+}
+
+/// The reply to an operation on the store.
+///
+/// The reply can be waited on using [`wait`], or awaited from async code.
+///
+/// [`wait`]: Reply::wait
+pub struct Reply<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Reply<T> {
+    /// Blocks the current thread until the reply arrives.
+    pub fn wait(self) -> T {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(value) = state.value.take() {
+                return value;
+            }
+            if state.closed {
+                panic!("The replier should never be dropped without replying");
+            }
+            state = self.shared.cond.wait(state).unwrap();
+        }
+    }
+}
+
+impl<T> Future for Reply<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.shared.state.lock().unwrap();
+
+        if let Some(value) = state.value.take() {
+            return Poll::Ready(value);
+        }
+        if state.closed {
+            panic!("The replier should never be dropped without replying");
+        }
+
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Debug for Reply<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Reply").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,362 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! Scheduling of the store's background work.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Debug, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use piecrust_uplink::ContractId;
+
+use crate::store::tree::Hash;
+
+/// The number of workers a scheduler is started with.
+const DEFAULT_CONCURRENCY: usize = 2;
+
+/// Priority of a job run by the [`Scheduler`].
+///
+/// Jobs with a higher priority are always picked before jobs with a lower one,
+/// and jobs with the same priority are picked in the order they were
+/// submitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Work a caller is actively waiting on, such as writing a commit.
+    High,
+    /// Work that changes the layout of commits on disk, such as finalizing.
+    Normal,
+    /// Work that only reclaims space, such as deleting a commit.
+    Low,
+}
+
+/// Resources a job needs exclusive access to. Two jobs holding the same
+/// resource are never run concurrently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Exclusive {
+    /// Writing new commits to disk.
+    Writes,
+    /// Operating on the files of the given commit.
+    Commit(Hash),
+    /// Persisting the page access heat map.
+    HeatMap,
+    /// Loading the given contract ahead of it being called.
+    Prefetch(ContractId),
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Queued {
+    exclusive: Vec<Exclusive>,
+    job: Job,
+}
+
+struct State {
+    queue: BTreeMap<(Priority, u64), Queued>,
+    next_seq: u64,
+    busy: BTreeSet<Exclusive>,
+    running: usize,
+    workers: usize,
+    concurrency: usize,
+    paused: bool,
+    shutdown: bool,
+}
+
+impl State {
+    /// Removes and returns the first job that can currently run, if any.
+    fn next_job(&mut self) -> Option<Queued> {
+        let key = self
+            .queue
+            .iter()
+            .find(|((priority, _), queued)| {
+                (!self.paused || *priority == Priority::High)
+                    && queued
+                        .exclusive
+                        .iter()
+                        .all(|exclusive| !self.busy.contains(exclusive))
+            })
+            .map(|(key, _)| *key)?;
+
+        self.queue.remove(&key)
+    }
+}
+
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+/// Runs the store's commits, finalizations, and deletions on a pool of worker
+/// threads, in order of [`Priority`].
+///
+/// The store's sync loop submits work to the scheduler instead of performing
+/// it itself, meaning that a long running deletion does not hold back the
+/// acknowledgment of a commit. Operations touching the same commit are never
+/// run concurrently, and neither are two commit writes.
+///
+/// Background work - anything with a priority lower than [`Priority::High`] -
+/// can be [`pause`]d and [`resume`]d. While paused, calls waiting on such work,
+/// like [`VM::delete_commit`], will block until the scheduler is resumed.
+///
+/// [`pause`]: Scheduler::pause
+/// [`resume`]: Scheduler::resume
+/// [`VM::delete_commit`]: crate::VM::delete_commit
+#[derive(Clone)]
+pub struct Scheduler {
+    shared: Arc<Shared>,
+}
+
+impl Debug for Scheduler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let state = self.state();
+        f.debug_struct("Scheduler")
+            .field("queue_depth", &state.queue.len())
+            .field("running", &state.running)
+            .field("concurrency", &state.concurrency)
+            .field("paused", &state.paused)
+            .finish()
+    }
+}
+
+impl Scheduler {
+    pub(crate) fn new() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    queue: BTreeMap::new(),
+                    next_seq: 0,
+                    busy: BTreeSet::new(),
+                    running: 0,
+                    workers: 0,
+                    concurrency: DEFAULT_CONCURRENCY,
+                    paused: false,
+                    shutdown: false,
+                }),
+                cond: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Returns the number of jobs waiting to be run.
+    pub fn queue_depth(&self) -> usize {
+        self.state().queue.len()
+    }
+
+    /// Returns the number of jobs with the given `priority` waiting to be run.
+    pub fn queue_depth_at(&self, priority: Priority) -> usize {
+        self.state()
+            .queue
+            .keys()
+            .filter(|(p, _)| *p == priority)
+            .count()
+    }
+
+    /// Returns the number of jobs currently running.
+    pub fn running(&self) -> usize {
+        self.state().running
+    }
+
+    /// Stop picking up background work. Jobs already running are unaffected.
+    pub fn pause(&self) {
+        self.state().paused = true;
+    }
+
+    /// Resume picking up background work after a [`pause`].
+    ///
+    /// [`pause`]: Scheduler::pause
+    pub fn resume(&self) {
+        self.state().paused = false;
+        self.shared.cond.notify_all();
+    }
+
+    /// Returns true if background work is paused.
+    pub fn is_paused(&self) -> bool {
+        self.state().paused
+    }
+
+    /// Returns the maximum number of jobs run concurrently.
+    pub fn concurrency(&self) -> usize {
+        self.state().concurrency
+    }
+
+    /// Sets the maximum number of jobs run concurrently.
+    ///
+    /// A `concurrency` of zero is treated as one. When lowering the
+    /// concurrency, surplus workers exit once they finish their current job.
+    pub fn set_concurrency(&self, concurrency: usize) {
+        let mut state = self.state();
+        state.concurrency = concurrency.max(1);
+        self.spawn_workers(&mut state);
+        self.shared.cond.notify_all();
+    }
+
+    /// Submit a job with the given `priority`, to be run once no other job
+    /// holds the same `exclusive` resource.
+    pub(crate) fn submit<F>(
+        &self,
+        priority: Priority,
+        exclusive: Exclusive,
+        job: F,
+    ) where
+        F: 'static + FnOnce() + Send,
+    {
+        self.submit_all(priority, vec![exclusive], job);
+    }
+
+    /// Submit a job with the given `priority`, to be run once no other job
+    /// holds any of the given `exclusive` resources. The resources are
+    /// acquired all at once, so jobs holding several never deadlock.
+    pub(crate) fn submit_all<F>(
+        &self,
+        priority: Priority,
+        exclusive: Vec<Exclusive>,
+        job: F,
+    ) where
+        F: 'static + FnOnce() + Send,
+    {
+        let mut state = self.state();
+
+        let seq = state.next_seq;
+        state.next_seq += 1;
+
+        state.queue.insert(
+            (priority, seq),
+            Queued {
+                exclusive,
+                job: Box::new(job),
+            },
+        );
+
+        self.spawn_workers(&mut state);
+        self.shared.cond.notify_all();
+    }
+
+    /// Let the workers drain the queue and exit.
+    pub(crate) fn shutdown(&self) {
+        self.state().shutdown = true;
+        self.shared.cond.notify_all();
+    }
+
+    /// Waits for the queue to be drained and for every job to finish, for at
+    /// most the given `timeout`. Returns false if it timed out.
+    pub(crate) fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        let mut state = self.state();
+        while !state.queue.is_empty() || state.running > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            (state, _) = self
+                .shared
+                .cond
+                .wait_timeout(state, deadline - now)
+                .unwrap();
+        }
+
+        true
+    }
+
+    fn state(&self) -> MutexGuard<State> {
+        self.shared.state.lock().unwrap()
+    }
+
+    fn spawn_workers(&self, state: &mut State) {
+        while state.workers < state.concurrency {
+            let shared = self.shared.clone();
+
+            // The threads are given a name to allow for easily identifying
+            // them while debugging.
+            let spawned = thread::Builder::new()
+                .name(String::from("PiecrustWorker"))
+                .spawn(move || worker(shared));
+
+            match spawned {
+                Ok(_) => state.workers += 1,
+                Err(err) => {
+                    tracing::trace!("spawning worker failed: {err}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn worker(shared: Arc<Shared>) {
+    let mut state = shared.state.lock().unwrap();
+
+    loop {
+        if state.workers > state.concurrency {
+            break;
+        }
+
+        match state.next_job() {
+            Some(Queued { exclusive, job }) => {
+                state.busy.extend(exclusive.iter().copied());
+                state.running += 1;
+                drop(state);
+
+                // A panicking job would otherwise take the worker down with
+                // it, leaving its resources busy forever. Whoever waits on
+                // the job learns of the panic through its dropped replier.
+                if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                    tracing::error!("scheduled job panicked");
+                }
+
+                state = shared.state.lock().unwrap();
+                for exclusive in &exclusive {
+                    state.busy.remove(exclusive);
+                }
+                state.running -= 1;
+                shared.cond.notify_all();
+            }
+            None => {
+                if state.shutdown && state.queue.is_empty() {
+                    break;
+                }
+                state = shared.cond.wait(state).unwrap();
+            }
+        }
+    }
+
+    state.workers -= 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+
+    #[test]
+    fn panicking_job() {
+        let scheduler = Scheduler::new();
+        scheduler.set_concurrency(1);
+
+        let hash = Hash::from([1u8; 32]);
+
+        scheduler.submit(Priority::High, Exclusive::Commit(hash), || {
+            panic!("job panicked");
+        });
+
+        // The resource is released, and the worker is still around to run
+        // the next job
+        let (sender, receiver) = mpsc::channel();
+        scheduler.submit(Priority::High, Exclusive::Commit(hash), move || {
+            sender.send(()).unwrap();
+        });
+
+        receiver
+            .recv_timeout(Duration::from_secs(10))
+            .expect("the job after the panicking one should run");
+        assert!(scheduler.wait_idle(Duration::from_secs(10)));
+        assert_eq!(scheduler.running(), 0);
+        assert_eq!(scheduler.state().workers, 1);
+    }
+}
@@ -5,21 +5,26 @@
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
 use std::collections::btree_map::Entry::{Occupied, Vacant};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Debug;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc, Arc, Mutex};
 use std::{io, mem};
 
 use dusk_wasmtime::Engine;
 use piecrust_uplink::ContractId;
 
-use crate::contract::ContractMetadata;
-use crate::store::tree::{Hash, PageOpening};
+use crate::contract::{ContractMetadata, Provenance};
+use crate::store::reply::{reply_channel, Reply};
+use crate::store::scheduler::{Exclusive, Priority, Scheduler};
+use crate::store::tree::{Hash, MerkleProof, PageOpening};
 use crate::store::{
-    base_from_path, Bytecode, Call, Commit, CommitStore, Memory, Metadata,
-    Module, BASE_FILE, BYTECODE_DIR, ELEMENT_FILE, MAIN_DIR, MEMORY_DIR,
-    METADATA_EXTENSION, OBJECTCODE_EXTENSION, PAGE_SIZE,
+    base_from_path, Bytecode, Call, Commit, CommitStore, ContractCode, HeatMap,
+    HeldLocks, Memory, Metadata, Module, BASE_FILE, BYTECODE_DIR, CODE_DIR,
+    ELEMENT_FILE, MAIN_DIR, MEMORY_DIR, METADATA_EXTENSION,
+    OBJECTCODE_EXTENSION, PAGE_SIZE, REMOVED_FILE,
 };
 use crate::Error;
 
@@ -30,6 +35,86 @@ pub struct ContractDataEntry {
     pub metadata: Metadata,
     pub memory: Memory,
     pub is_new: bool,
+    /// The pages of the contract in the fallback commit it was loaded from,
+    /// or `None` if it wasn't loaded from a fallback.
+    pub fallback_pages: Option<BTreeSet<usize>>,
+    /// The range of the memory holding the contract's argument buffer, once
+    /// it is instantiated.
+    pub arg_buf: Option<Range<usize>>,
+}
+
+impl ContractDataEntry {
+    /// Returns whether the contract was deployed or written to.
+    ///
+    /// A contract loaded from a fallback commit only counts as written to once
+    /// the contents of its memory change, since it's otherwise left out of the
+    /// session's commit. Changes to its argument buffer alone don't count,
+    /// since every call writes to it.
+    pub(crate) fn is_written(&self) -> bool {
+        if self.is_new {
+            return true;
+        }
+        let arg_buf = self.arg_buf.clone().unwrap_or_default();
+        let mut dirty_pages = self.memory.all_dirty_pages();
+        match self.fallback_pages {
+            None => dirty_pages.next().is_some(),
+            Some(_) => dirty_pages.any(|(dirty, clean, page_index)| {
+                let start = page_index * dirty.len();
+                let end = start + dirty.len();
+                let skip_start = arg_buf.start.clamp(start, end) - start;
+                let skip_end = arg_buf.end.clamp(start, end) - start;
+                dirty[..skip_start] != clean[..skip_start]
+                    || dirty[skip_end..] != clean[skip_end..]
+            }),
+        }
+    }
+
+    /// Inserts the contract's memory into the given `commit`.
+    ///
+    /// A contract loaded from a fallback commit is left out until it is
+    /// written to, at which point all of its pages are inserted, since none of
+    /// them are in the commit's chain.
+    pub(crate) fn insert_into(
+        &self,
+        commit: &mut Commit,
+        contract: ContractId,
+        elide_zero_pages: bool,
+    ) {
+        match &self.fallback_pages {
+            None => commit.insert(contract, &self.memory, elide_zero_pages),
+            Some(_) if !self.is_written() => {}
+            Some(_) => {
+                let memory = &self.memory;
+                let pages = self
+                    .revived_pages()
+                    .into_iter()
+                    .map(|i| (&memory[i * PAGE_SIZE..][..PAGE_SIZE], i));
+                commit.insert_pages(contract, memory, pages, elide_zero_pages);
+            }
+        }
+    }
+
+    /// Returns the indices of all the pages of a contract loaded from a
+    /// fallback commit, both those it had there and those written since.
+    pub(crate) fn revived_pages(&self) -> BTreeSet<usize> {
+        let mut pages = self.fallback_pages.clone().unwrap_or_default();
+        pages.extend(
+            self.memory
+                .all_dirty_pages()
+                .map(|(_, _, page_index)| *page_index),
+        );
+        pages
+    }
+}
+
+/// The contracts loaded in a [`ContractSession`], the lengths of their
+/// memories, and the contracts removed, when [`snap_contracts`] was called.
+///
+/// [`snap_contracts`]: ContractSession::snap_contracts
+#[derive(Debug)]
+pub struct ContractsSnapshot {
+    lens: BTreeMap<ContractId, usize>,
+    removed: BTreeSet<ContractId>,
 }
 
 /// The representation of a session with a [`ContractStore`].
@@ -43,14 +128,54 @@ pub struct ContractDataEntry {
 /// [`commit`]: ContractSession::commit
 pub struct ContractSession {
     contracts: BTreeMap<ContractId, ContractDataEntry>,
+    // Contracts removed in the session. Those loaded are kept in `contracts`,
+    // so their removal can be reverted.
+    removed: BTreeSet<ContractId>,
     engine: Engine,
 
     base: Option<Commit>,
+    fallbacks: Vec<Commit>,
     root_dir: PathBuf,
 
     call: mpsc::Sender<Call>,
 
     commit_store: Arc<Mutex<CommitStore>>,
+    heat_map: Option<HeatMap>,
+
+    scheduler: Scheduler,
+    prefetches: BTreeMap<ContractId, Prefetch>,
+    locks: HeldLocks,
+
+    elide_zero_pages: bool,
+    root_cache: Mutex<Option<RootCache>>,
+}
+
+/// The state of a [`ContractSession`] as of the last time its root was
+/// computed, so that only the pages written since are hashed the next time.
+struct RootCache {
+    commit: Commit,
+    /// The pages written to each contract since, or `None` if all its dirty
+    /// pages are to be hashed again.
+    written: BTreeMap<ContractId, Option<BTreeSet<usize>>>,
+}
+
+/// A contract being loaded in the background by the [`Scheduler`].
+struct Prefetch {
+    // Set by whichever of the job or the session gets to the contract first.
+    claimed: Arc<AtomicBool>,
+    receiver: mpsc::Receiver<io::Result<ContractDataEntry>>,
+}
+
+impl Prefetch {
+    /// Returns the prefetched contract, waiting for the job to finish loading
+    /// it if it has already started. Returns `None` if the job has not started
+    /// yet, in which case it will no longer load the contract.
+    fn take(self) -> Option<io::Result<ContractDataEntry>> {
+        if !self.claimed.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        self.receiver.recv().ok()
+    }
 }
 
 impl Debug for ContractSession {
@@ -58,26 +183,56 @@ impl Debug for ContractSession {
         f.debug_struct("ContractSession")
             .field("contracts", &self.contracts)
             .field("base", &self.base)
+            .field("fallbacks", &self.fallbacks)
             .field("root_dir", &self.root_dir)
             .finish()
     }
 }
 
 impl ContractSession {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new<P: AsRef<Path>>(
         root_dir: P,
         engine: Engine,
         base: Option<Commit>,
         call: mpsc::Sender<Call>,
         commit_store: Arc<Mutex<CommitStore>>,
+        heat_map: Option<HeatMap>,
+        scheduler: Scheduler,
+        locks: HeldLocks,
+        elide_zero_pages: bool,
     ) -> Self {
         Self {
             contracts: BTreeMap::new(),
+            removed: BTreeSet::new(),
             engine,
             base,
+            fallbacks: Vec::new(),
             root_dir: root_dir.as_ref().into(),
             call,
             commit_store,
+            heat_map,
+            scheduler,
+            prefetches: BTreeMap::new(),
+            locks,
+            elide_zero_pages,
+            root_cache: Mutex::new(None),
+        }
+    }
+
+    /// Returns whether pages containing only zeroes are left out of the
+    /// session's commit and root.
+    pub fn elide_zero_pages(&self) -> bool {
+        self.elide_zero_pages
+    }
+
+    /// Returns the hash of the instruction costs the session's base commit -
+    /// or the finalized state, if it has no base - was made with, if they
+    /// were recorded.
+    pub fn base_instruction_costs(&self) -> Option<[u8; 32]> {
+        match &self.base {
+            Some(base) => base.instruction_costs,
+            None => self.commit_store.lock().unwrap().main_instruction_costs,
         }
     }
 
@@ -91,19 +246,140 @@ impl ContractSession {
     ///
     /// [`contract`]: ContractSession::contract
     pub fn root(&self) -> Hash {
-        tracing::trace!("root called commit cloning");
-        let mut commit = self
-            .base
-            .as_ref()
-            .map(|c| c.fast_clone(&mut self.contracts.keys()))
-            .unwrap_or(Commit::new(&self.commit_store, None));
-        for (contract, entry) in &self.contracts {
-            commit.insert(*contract, &entry.memory);
-        }
-        let root = commit.root();
+        let root = self.with_cached_commit(|commit| *commit.root());
         tracing::trace!("root call finished");
 
-        *root
+        root
+    }
+
+    /// Calls `f` with the commit of the session's current state, bringing the
+    /// cached one up to date with the pages written since it was last used.
+    fn with_cached_commit<T>(&self, f: impl FnOnce(&Commit) -> T) -> T {
+        let mut root_cache = self.root_cache.lock().unwrap();
+
+        let cache = match root_cache.as_mut() {
+            Some(cache) => {
+                tracing::trace!("root called with cached commit");
+                for (contract, written) in mem::take(&mut cache.written) {
+                    let Some(entry) = self.contracts.get(&contract) else {
+                        continue;
+                    };
+                    if self.removed.contains(&contract) {
+                        continue;
+                    }
+                    let memory = &entry.memory;
+                    match written {
+                        // A contract from a fallback commit enters the commit
+                        // whole, if at all
+                        Some(page_indices)
+                            if entry.fallback_pages.is_none() =>
+                        {
+                            let pages = page_indices.into_iter().map(|i| {
+                                (&memory[i * PAGE_SIZE..][..PAGE_SIZE], i)
+                            });
+                            cache.commit.insert_pages(
+                                contract,
+                                memory,
+                                pages,
+                                self.elide_zero_pages,
+                            );
+                        }
+                        _ => entry.insert_into(
+                            &mut cache.commit,
+                            contract,
+                            self.elide_zero_pages,
+                        ),
+                    }
+                }
+                cache
+            }
+            None => {
+                tracing::trace!("root called commit cloning");
+                let mut commit = self
+                    .base
+                    .as_ref()
+                    .map(|c| c.fast_clone(&mut self.contracts.keys()))
+                    .unwrap_or(Commit::new(&self.commit_store, None));
+                for (contract, entry) in &self.contracts {
+                    entry.insert_into(
+                        &mut commit,
+                        *contract,
+                        self.elide_zero_pages,
+                    );
+                }
+                for contract in &self.removed {
+                    commit.remove_contract(contract);
+                }
+                root_cache.insert(RootCache {
+                    commit,
+                    written: BTreeMap::new(),
+                })
+            }
+        };
+
+        f(&cache.commit)
+    }
+
+    /// Records that the given pages of the `contract`'s memory were written
+    /// to, or that its memory grew, so that the next call to [`root`] hashes
+    /// them again.
+    ///
+    /// Every write to a memory in the session must be recorded either here,
+    /// or by the session discarding its cached root itself.
+    ///
+    /// [`root`]: ContractSession::root
+    pub fn record_written(
+        &mut self,
+        contract: ContractId,
+        page_indices: impl IntoIterator<Item = usize>,
+    ) {
+        // A contract from a fallback commit enters the commit whole
+        let from_fallback = self
+            .contracts
+            .get(&contract)
+            .map_or(false, |entry| entry.fallback_pages.is_some());
+        if from_fallback {
+            self.record_replaced(contract);
+            return;
+        }
+
+        if let Some(cache) = self.root_cache.get_mut().unwrap() {
+            if let Some(written) = cache
+                .written
+                .entry(contract)
+                .or_insert_with(|| Some(BTreeSet::new()))
+            {
+                written.extend(page_indices);
+            }
+        }
+    }
+
+    /// Records the range of the `contract`'s memory holding its argument
+    /// buffer, once it is instantiated.
+    pub fn record_arg_buf(
+        &mut self,
+        contract: ContractId,
+        arg_buf: Range<usize>,
+    ) {
+        if let Some(entry) = self.contracts.get_mut(&contract) {
+            entry.arg_buf = Some(arg_buf);
+        }
+    }
+
+    /// Records that the `contract`'s memory was replaced, so that the next
+    /// call to [`root`] hashes all of its dirty pages again.
+    ///
+    /// [`root`]: ContractSession::root
+    fn record_replaced(&mut self, contract: ContractId) {
+        if let Some(cache) = self.root_cache.get_mut().unwrap() {
+            cache.written.insert(contract, None);
+        }
+    }
+
+    /// Discards the cached root, for when the memories of the session change
+    /// in ways that can't be tracked page by page, such as being reverted.
+    fn discard_root_cache(&mut self) {
+        *self.root_cache.get_mut().unwrap() = None;
     }
 
     /// Returns an iterator through all the pages of a contract, together with a
@@ -118,7 +394,10 @@ impl ContractSession {
             .clone()
             .unwrap_or(Commit::new(&self.commit_store, None));
         for (contract, entry) in &self.contracts {
-            commit.insert(*contract, &entry.memory);
+            entry.insert_into(&mut commit, *contract, self.elide_zero_pages);
+        }
+        for contract in &self.removed {
+            commit.remove_contract(contract);
         }
 
         let contract_data = self.contracts.get(&contract)?;
@@ -134,6 +413,25 @@ impl ContractSession {
         Some(inclusion_proofs)
     }
 
+    /// Returns a proof of the state of a contract's memory, or `None` if the
+    /// contract doesn't exist.
+    ///
+    /// The proof is taken from the same cached commit as the [`root`], so
+    /// only the pages written since either was last called are hashed.
+    ///
+    /// [`root`]: ContractSession::root
+    pub fn prove_contract(&self, contract: ContractId) -> Option<MerkleProof> {
+        self.with_cached_commit(|commit| {
+            // The cached commit only holds the elements of the contracts
+            // loaded in the session, the others are in its base.
+            let element = commit
+                .index_get(&contract)
+                .or_else(|| self.base.as_ref()?.index_get(&contract))?;
+            let memory_root = *element.tree().root();
+            commit.contract_proof(&contract, memory_root)
+        })
+    }
+
     /// Commits the given session to disk, consuming the session and adding it
     /// to the [`ContractStore`] it was created from.
     ///
@@ -148,26 +446,60 @@ impl ContractSession {
     ///
     /// [`contract`]: ContractSession::contract
     pub fn commit(&mut self) -> io::Result<Hash> {
+        self.commit_async().wait()
+    }
+
+    /// Commits only the changes to the given `contracts` to disk, discarding
+    /// the changes to every other contract. See [`commit`] for more details.
+    ///
+    /// [`commit`]: ContractSession::commit
+    pub fn commit_partial(
+        &mut self,
+        contracts: &[ContractId],
+    ) -> io::Result<Hash> {
+        self.contracts
+            .retain(|contract, _| contracts.contains(contract));
+        self.removed.retain(|contract| contracts.contains(contract));
+        self.discard_root_cache();
+        self.commit()
+    }
+
+    /// Commits the given session to disk, without blocking the current thread
+    /// until the commit is written.
+    ///
+    /// The contents of the session are handed off immediately, and the
+    /// returned [`Reply`] resolves to the root of the commit once it is
+    /// written. The session should be kept alive until then, so its base
+    /// commit is held while the commit is written. See [`commit`] for more
+    /// details.
+    ///
+    /// [`commit`]: ContractSession::commit
+    pub fn commit_async(&mut self) -> Reply<io::Result<Hash>> {
         tracing::trace!("commit started");
-        let (replier, receiver) = mpsc::sync_channel(1);
+        self.record_heat();
+
+        let (replier, reply) = reply_channel();
 
         let mut contracts = BTreeMap::new();
         let base = self.base.clone();
 
         mem::swap(&mut self.contracts, &mut contracts);
+        let removed = mem::take(&mut self.removed);
+        contracts.retain(|contract, _| !removed.contains(contract));
+        self.discard_root_cache();
 
         self.call
             .send(Call::Commit {
                 contracts,
+                removed,
                 base,
+                elide_zero_pages: self.elide_zero_pages,
                 replier,
             })
             .expect("The receiver should never drop before sending");
         tracing::trace!("commit sent");
 
-        receiver
-            .recv()
-            .expect("The receiver should always receive a reply")
+        reply
     }
 
     /// Returns path to a file representing a given commit and page.
@@ -208,7 +540,9 @@ impl ContractSession {
     /// Returns path to a file representing a given commit and element.
     ///
     /// Requires a contract's leaf path and a main state path.
-    /// Progresses recursively via bases of commits.
+    /// Progresses recursively via bases of commits, stopping at the first
+    /// commit that removed the contract, in which case the path of its
+    /// removal marker is returned instead.
     pub fn find_element(
         commit: Option<Hash>,
         leaf_path: impl AsRef<Path>,
@@ -228,8 +562,12 @@ impl ContractSession {
                 let hash_hex = hex::encode(hash.as_bytes());
                 let path =
                     leaf_path.as_ref().join(&hash_hex).join(ELEMENT_FILE);
+                let removed_path =
+                    leaf_path.as_ref().join(&hash_hex).join(REMOVED_FILE);
                 if path.is_file() {
                     Some((path, depth + 1))
+                } else if removed_path.is_file() {
+                    Some((removed_path, depth + 1))
                 } else {
                     let base_info_path =
                         main_path.as_ref().join(hash_hex).join(BASE_FILE);
@@ -253,108 +591,396 @@ impl ContractSession {
     ///
     /// - The contract has been [`deploy`]ed in this session
     /// - The contract was deployed to the base commit
+    /// - The contract is in one of the session's [`fallbacks`]
+    ///
+    /// Contracts [`destroy`]ed in this session don't exist.
     ///
     /// [`deploy`]: ContractSession::deploy
+    /// [`destroy`]: ContractSession::destroy
+    /// [`fallbacks`]: ContractSession::fallbacks
     pub fn contract(
         &mut self,
         contract: ContractId,
     ) -> io::Result<Option<ContractDataEntry>> {
+        if self.removed.contains(&contract) {
+            return Ok(None);
+        }
+
         let commit_id = self.base.as_ref().map(|commit| *commit.root());
         match self.contracts.entry(contract) {
             Vacant(entry) => match &self.base {
-                None => Ok(None),
-                Some(base_commit) => {
-                    match base_commit.index_get(&contract).is_some() {
-                        true => {
-                            let base_dir = self.root_dir.join(MAIN_DIR);
-
-                            let contract_hex = hex::encode(contract);
-
-                            let bytecode_path =
-                                base_dir.join(BYTECODE_DIR).join(&contract_hex);
-                            let module_path = bytecode_path
-                                .with_extension(OBJECTCODE_EXTENSION);
-                            let metadata_path = bytecode_path
-                                .with_extension(METADATA_EXTENSION);
-                            let memory_path =
-                                base_dir.join(MEMORY_DIR).join(contract_hex);
-
-                            let bytecode = Bytecode::from_file(bytecode_path)?;
-                            let module =
-                                Module::from_file(&self.engine, module_path)?;
-                            let metadata = Metadata::from_file(metadata_path)?;
-
-                            let memory = match base_commit.index_get(&contract)
-                            {
-                                Some(elem) => {
-                                    let page_indices =
-                                        elem.page_indices().clone();
-                                    Memory::from_files(
-                                        module.is_64(),
-                                        move |page_index: usize| {
-                                            match page_indices
-                                                .contains(&page_index)
-                                            {
-                                                true => Some(
-                                                    Self::find_page(
-                                                        page_index,
-                                                        commit_id,
-                                                        &memory_path,
-                                                        &base_dir,
-                                                    )
-                                                    .unwrap_or(
-                                                        memory_path.join(
-                                                            format!(
-                                                                "{page_index}"
-                                                            ),
-                                                        ),
-                                                    ),
-                                                ),
-                                                false => None,
-                                            }
-                                        },
-                                        elem.len(),
-                                    )?
-                                }
-                                None => Memory::new(module.is_64())?,
-                            };
-
-                            let contract = entry
-                                .insert(ContractDataEntry {
-                                    bytecode,
-                                    module,
-                                    metadata,
-                                    memory,
-                                    is_new: false,
-                                })
-                                .clone();
-
-                            Ok(Some(contract))
-                        }
-                        false => Ok(None),
+                None => Self::load_fallback(
+                    &self.engine,
+                    &self.commit_store,
+                    &self.root_dir,
+                    &self.fallbacks,
+                    contract,
+                )
+                .map(|data| data.map(|data| entry.insert(data).clone())),
+                Some(base_commit) => match base_commit.index_get(&contract) {
+                    Some(elem) => {
+                        let prefetched = self
+                            .prefetches
+                            .remove(&contract)
+                            .and_then(Prefetch::take);
+
+                        let data = match prefetched {
+                            Some(data) => data?,
+                            None => Self::load_contract(
+                                &self.engine,
+                                &self.commit_store,
+                                &self.root_dir,
+                                commit_id,
+                                contract,
+                                elem.page_indices().clone(),
+                                elem.len(),
+                            )?,
+                        };
+
+                        Ok(Some(entry.insert(data).clone()))
                     }
-                }
+                    None => Self::load_fallback(
+                        &self.engine,
+                        &self.commit_store,
+                        &self.root_dir,
+                        &self.fallbacks,
+                        contract,
+                    )
+                    .map(|data| data.map(|data| entry.insert(data).clone())),
+                },
             },
             Occupied(entry) => Ok(Some(entry.get().clone())),
         }
     }
 
+    /// Adds a commit to read contracts from when they are not in the base
+    /// commit, after any fallbacks added before it.
+    ///
+    /// Contracts read from a fallback are only written to the session's
+    /// commit if they are written to, and then in full. This allows for
+    /// contracts to live in older commits - such as archival snapshots - and
+    /// be brought back into the newest state when they are used.
+    ///
+    /// The fallback must already be held in the store, and is released when
+    /// the session drops.
+    pub(crate) fn add_fallback(&mut self, fallback: Commit) {
+        self.fallbacks.push(fallback);
+        self.discard_root_cache();
+    }
+
+    /// Loads the given `contract` from the first of the `fallbacks` it is in,
+    /// if any.
+    fn load_fallback(
+        engine: &Engine,
+        commit_store: &Mutex<CommitStore>,
+        root_dir: &Path,
+        fallbacks: &[Commit],
+        contract: ContractId,
+    ) -> io::Result<Option<ContractDataEntry>> {
+        for fallback in fallbacks {
+            if let Some(elem) = fallback.index_get(&contract) {
+                let page_indices = elem.page_indices().clone();
+                let mut data = Self::load_contract(
+                    engine,
+                    commit_store,
+                    root_dir,
+                    Some(*fallback.root()),
+                    contract,
+                    page_indices.clone(),
+                    elem.len(),
+                )?;
+                data.fallback_pages = Some(page_indices);
+                return Ok(Some(data));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Start loading the given contracts in the background, ahead of them
+    /// being called.
+    ///
+    /// Contracts that are already loaded, or that don't exist in the base
+    /// commit, are ignored.
+    pub fn prefetch(&mut self, contracts: &[ContractId]) {
+        let base_commit = match &self.base {
+            Some(base_commit) => base_commit,
+            None => return,
+        };
+        let commit_id = Some(*base_commit.root());
+
+        for contract in contracts {
+            if self.contracts.contains_key(contract)
+                || self.prefetches.contains_key(contract)
+            {
+                continue;
+            }
+
+            let elem = match base_commit.index_get(contract) {
+                Some(elem) => elem,
+                None => continue,
+            };
+
+            let claimed = Arc::new(AtomicBool::new(false));
+            let (sender, receiver) = mpsc::sync_channel(1);
+
+            let job_claimed = claimed.clone();
+            let engine = self.engine.clone();
+            let commit_store = self.commit_store.clone();
+            let root_dir = self.root_dir.clone();
+            let contract = *contract;
+            let page_indices = elem.page_indices().clone();
+            let len = elem.len();
+
+            self.scheduler.submit(
+                Priority::High,
+                Exclusive::Prefetch(contract),
+                move || {
+                    // The session may have loaded the contract itself already
+                    if job_claimed.swap(true, Ordering::AcqRel) {
+                        return;
+                    }
+                    let _ = sender.send(Self::load_contract(
+                        &engine,
+                        &commit_store,
+                        &root_dir,
+                        commit_id,
+                        contract,
+                        page_indices,
+                        len,
+                    ));
+                },
+            );
+
+            self.prefetches
+                .insert(contract, Prefetch { claimed, receiver });
+        }
+    }
+
+    /// Loads the bytecode, module, metadata, and memory of a contract from the
+    /// store, as of the given commit.
+    fn load_contract(
+        engine: &Engine,
+        commit_store: &Mutex<CommitStore>,
+        root_dir: &Path,
+        commit_id: Option<Hash>,
+        contract: ContractId,
+        page_indices: BTreeSet<usize>,
+        len: usize,
+    ) -> io::Result<ContractDataEntry> {
+        let base_dir = root_dir.join(MAIN_DIR);
+
+        let contract_hex = hex::encode(contract);
+
+        let bytecode_path = base_dir.join(BYTECODE_DIR).join(&contract_hex);
+        let module_path = bytecode_path.with_extension(OBJECTCODE_EXTENSION);
+        let metadata_path = bytecode_path.with_extension(METADATA_EXTENSION);
+        let memory_path = base_dir.join(MEMORY_DIR).join(contract_hex);
+
+        let cached = commit_id.and_then(|root| {
+            commit_store
+                .lock()
+                .unwrap()
+                .get_code(&root, &contract)
+                .cloned()
+        });
+        let ContractCode {
+            bytecode,
+            module,
+            metadata,
+        } = match cached {
+            Some(code) => code,
+            None => {
+                let bytecode = Bytecode::from_file(bytecode_path)?;
+                let module = Module::from_file(engine, module_path)?;
+                let mut metadata =
+                    Metadata::from_file(metadata_path, contract)?;
+                metadata.set_provenance(Provenance::from_bytecode(
+                    bytecode.as_ref(),
+                ));
+
+                let code = ContractCode {
+                    bytecode,
+                    module,
+                    metadata,
+                };
+                if let Some(root) = commit_id {
+                    commit_store.lock().unwrap().insert_code(
+                        root,
+                        contract,
+                        code.clone(),
+                    );
+                }
+                code
+            }
+        };
+
+        let memory = Memory::from_files(
+            module.is_64(),
+            move |page_index: usize| match page_indices.contains(&page_index) {
+                true => Some((
+                    Self::find_page(
+                        page_index,
+                        commit_id,
+                        &memory_path,
+                        &base_dir,
+                    )
+                    .unwrap_or(memory_path.join(format!("{page_index}"))),
+                    0,
+                )),
+                false => None,
+            },
+            len,
+        )?;
+
+        Ok(ContractDataEntry {
+            bytecode,
+            module,
+            metadata,
+            memory,
+            is_new: false,
+            fallback_pages: None,
+            arg_buf: None,
+        })
+    }
+
     /// Remove the given contract from the session.
     pub fn remove_contract(&mut self, contract: &ContractId) {
         self.contracts.remove(contract);
+        self.discard_root_cache();
+    }
+
+    /// Removes the given contract from the state, leaving its bytecode and
+    /// memory out of the session's root and commit.
+    ///
+    /// Unlike [`remove_contract`], which drops the changes made to a contract
+    /// in the session, this removes the contract itself. It may then be
+    /// deployed again under the same ID.
+    ///
+    /// [`remove_contract`]: ContractSession::remove_contract
+    pub fn destroy(&mut self, contract: ContractId) {
+        self.removed.insert(contract);
+        self.discard_root_cache();
+    }
+
+    /// Returns whether the given contract was destroyed in this session.
+    pub fn is_destroyed(&self, contract: &ContractId) -> bool {
+        self.removed.contains(contract)
+    }
+
+    /// Snapshots the memories of all contracts loaded in the session, so that
+    /// any changes made to them after can be undone together using
+    /// [`revert_contracts`], or kept using [`apply_contracts`].
+    ///
+    /// [`revert_contracts`]: ContractSession::revert_contracts
+    /// [`apply_contracts`]: ContractSession::apply_contracts
+    pub fn snap_contracts(&mut self) -> io::Result<ContractsSnapshot> {
+        let mut lens = BTreeMap::new();
+
+        for (contract, entry) in &mut self.contracts {
+            entry.memory.snap()?;
+            lens.insert(*contract, entry.memory.current_len);
+        }
+
+        Ok(ContractsSnapshot {
+            lens,
+            removed: self.removed.clone(),
+        })
+    }
+
+    /// Reverts the session's contracts to the given `snapshot`.
+    ///
+    /// Contracts loaded after the snapshot was taken - deployed or otherwise -
+    /// are removed from the session, and so return to their state in the base
+    /// commit, if any.
+    pub fn revert_contracts(
+        &mut self,
+        snapshot: ContractsSnapshot,
+    ) -> io::Result<()> {
+        let ContractsSnapshot { lens, removed } = snapshot;
+        self.contracts
+            .retain(|contract, _| lens.contains_key(contract));
+        self.removed = removed;
+        self.discard_root_cache();
+
+        for (contract, len) in lens {
+            let entry = self
+                .contracts
+                .get_mut(&contract)
+                .expect("Contracts in the snapshot should be loaded");
+            entry.memory.revert()?;
+            entry.memory.current_len = len;
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the changes made to the session's contracts since the given
+    /// `snapshot` was taken.
+    pub fn apply_contracts(
+        &mut self,
+        snapshot: ContractsSnapshot,
+    ) -> io::Result<()> {
+        for contract in snapshot.lens.keys() {
+            let entry = self
+                .contracts
+                .get_mut(contract)
+                .expect("Contracts in the snapshot should be loaded");
+            entry.memory.apply()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the IDs of all contracts in the session, both those in the base
+    /// commit and those deployed in the session itself.
+    pub fn contract_ids(&self) -> BTreeSet<ContractId> {
+        let mut contract_ids = self
+            .base
+            .as_ref()
+            .map(Commit::contract_ids)
+            .unwrap_or_default();
+        contract_ids.extend(self.contracts.keys());
+        contract_ids.retain(|contract| !self.removed.contains(contract));
+        contract_ids
+    }
+
+    /// Returns the memory of the given contract, up to its current length,
+    /// loading the contract from the base commit if necessary.
+    pub fn contract_memory(
+        &mut self,
+        contract_id: ContractId,
+    ) -> io::Result<Option<&[u8]>> {
+        if self.contract(contract_id)?.is_none() {
+            return Ok(None);
+        }
+        Ok(self.contracts.get(&contract_id).map(|entry| {
+            let memory = &entry.memory;
+            &memory[..memory.current_len]
+        }))
     }
 
     /// Checks if contract is deployed
     pub fn contract_deployed(&mut self, contract_id: ContractId) -> bool {
-        if self.contracts.contains_key(&contract_id) {
+        if self.removed.contains(&contract_id) {
+            false
+        } else if self.contracts.contains_key(&contract_id) {
             true
         } else if let Some(base_commit) = &self.base {
             base_commit.index_get(&contract_id).is_some()
+                || self.in_fallbacks(&contract_id)
         } else {
-            false
+            self.in_fallbacks(&contract_id)
         }
     }
 
+    /// Returns whether the given contract is in any of the session's
+    /// fallbacks.
+    fn in_fallbacks(&self, contract_id: &ContractId) -> bool {
+        self.fallbacks
+            .iter()
+            .any(|fallback| fallback.index_get(contract_id).is_some())
+    }
+
     /// Deploys bytecode to the contract store with the given its `contract_id`.
     ///
     /// See [`deploy`] for deploying bytecode without specifying a contract ID.
@@ -368,20 +994,24 @@ impl ContractSession {
         metadata: ContractMetadata,
         metadata_bytes: B,
     ) -> io::Result<()> {
-        let bytecode = Bytecode::new(bytecode)?;
+        let code_dir = self.root_dir.join(MAIN_DIR).join(CODE_DIR);
+        let bytecode = Bytecode::shared(&code_dir, bytecode)?;
         let module = Module::new(&self.engine, module)?;
         let metadata = Metadata::new(metadata_bytes, metadata)?;
         let memory = Memory::new(module.is_64())?;
 
         // If the position is already filled in the tree, the contract cannot be
         // inserted.
-        if let Some(base) = self.base.as_ref() {
-            if base.index_get(&contract_id).is_some() {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Existing contract '{contract_id}'"),
-                ));
-            }
+        let in_base = self
+            .base
+            .as_ref()
+            .map_or(false, |base| base.index_get(&contract_id).is_some());
+        let removed = self.removed.remove(&contract_id);
+        if !removed && (in_base || self.in_fallbacks(&contract_id)) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Existing contract '{contract_id}'"),
+            ));
         }
 
         self.contracts.insert(
@@ -392,8 +1022,11 @@ impl ContractSession {
                 metadata,
                 memory,
                 is_new: true,
+                fallback_pages: None,
+                arg_buf: None,
             },
         );
+        self.record_replaced(contract_id);
 
         Ok(())
     }
@@ -418,19 +1051,109 @@ impl ContractSession {
         // metadata.
         new_contract_data.metadata.set_data(ContractMetadata {
             contract_id: old_contract,
-            owner: new_contract_data.metadata.data().owner.clone(),
+            ..new_contract_data.metadata.data().clone()
         })?;
 
         self.contracts.insert(old_contract, new_contract_data);
+        self.discard_root_cache();
 
         Ok(())
     }
 
+    /// Locks the given `contracts`, signaling to other sessions that this one
+    /// intends to touch them. See [`ContractLocks`] for more details.
+    ///
+    /// Errors with the contracts held by other sessions if any of them is, in
+    /// which case none of the given contracts are locked.
+    ///
+    /// [`ContractLocks`]: crate::store::ContractLocks
+    pub fn lock_contracts(
+        &mut self,
+        contracts: &[ContractId],
+    ) -> Result<(), Vec<ContractId>> {
+        self.locks.acquire(contracts)
+    }
+
+    /// Returns the contracts locked by the session.
+    pub fn locked_contracts(&self) -> &BTreeSet<ContractId> {
+        self.locks.contracts()
+    }
+
+    /// Moves the contracts loaded in the `other` session into this one, making
+    /// this session's commit include the changes made by both.
+    ///
+    /// Both sessions must share the same base. A contract loaded in both,
+    /// and written to by either, is a conflict, as is a contract removed in
+    /// one and loaded or removed in the other. The merge is then refused
+    /// with [`Error::MergeConflict`], leaving both sessions untouched. The
+    /// locks held by `other` are taken over by this session.
+    pub fn merge(&mut self, other: &mut ContractSession) -> Result<(), Error> {
+        let base = self.base.as_ref().map(|commit| *commit.root());
+        let other_base = other.base.as_ref().map(|commit| *commit.root());
+        if base != other_base {
+            return Err(Error::SessionError(
+                "Merged sessions must share the same base".into(),
+            ));
+        }
+
+        let mut conflicts: BTreeSet<_> = other
+            .contracts
+            .iter()
+            .filter_map(|(contract, other_entry)| {
+                let entry = self.contracts.get(contract)?;
+                (entry.is_written() || other_entry.is_written())
+                    .then_some(*contract)
+            })
+            .collect();
+        // Removing a contract conflicts with the other session loading it
+        conflicts.extend(other.removed.iter().filter(|contract| {
+            self.contracts.contains_key(contract)
+                || self.removed.contains(contract)
+        }));
+        conflicts.extend(
+            self.removed
+                .iter()
+                .filter(|contract| other.contracts.contains_key(contract)),
+        );
+        if !conflicts.is_empty() {
+            return Err(Error::MergeConflict(conflicts.into_iter().collect()));
+        }
+
+        for (contract, entry) in mem::take(&mut other.contracts) {
+            if let Vacant(vacant) = self.contracts.entry(contract) {
+                vacant.insert(entry);
+                self.record_replaced(contract);
+            }
+        }
+        if !other.removed.is_empty() {
+            self.removed.append(&mut other.removed);
+            self.discard_root_cache();
+        }
+        self.locks.absorb(&mut other.locks);
+
+        Ok(())
+    }
+
+    /// Records the pages accessed during the session in the heat map, if the
+    /// session was sampled. This happens at most once.
+    fn record_heat(&mut self) {
+        if let Some(heat_map) = self.heat_map.take() {
+            heat_map.record(
+                self.contracts
+                    .iter()
+                    .map(|(contract, entry)| (*contract, &entry.memory)),
+            );
+        }
+    }
+
     /// Provides metadata of the contract with a given `contract_id`.
     pub fn contract_metadata(
         &mut self,
         contract_id: &ContractId,
     ) -> Option<&ContractMetadata> {
+        if self.removed.contains(contract_id) {
+            return None;
+        }
         let _ = self.contract(*contract_id);
         self.contracts
             .get(contract_id)
@@ -440,9 +1163,15 @@ impl ContractSession {
 
 impl Drop for ContractSession {
     fn drop(&mut self) {
+        self.record_heat();
+
         if let Some(base) = self.base.take() {
             let root = base.root();
             let _ = self.call.send(Call::SessionDrop(*root));
         }
+        for fallback in mem::take(&mut self.fallbacks) {
+            let root = fallback.root();
+            let _ = self.call.send(Call::SessionDrop(*root));
+        }
     }
 }
@@ -14,6 +14,8 @@ use piecrust_uplink::ContractId;
 use rkyv::{Archive, Deserialize, Serialize};
 use std::io::{self, ErrorKind, Read, Write};
 
+use crate::root::Root;
+
 // There are max `2^16` pages in a 32-bit memory
 const P32_HEIGHT: usize = 8;
 const P32_ARITY: usize = 4;
@@ -53,6 +55,17 @@ impl PageTree {
         }
     }
 
+    pub fn remove(&mut self, position: u64) {
+        match self {
+            Self::Wasm32(tree) => {
+                tree.remove(position);
+            }
+            Self::Wasm64(tree) => {
+                tree.remove(position);
+            }
+        }
+    }
+
     pub fn root(&self) -> Ref<Hash> {
         match self {
             Self::Wasm32(tree) => tree.root(),
@@ -72,6 +85,13 @@ impl PageTree {
             }
         }
     }
+
+    /// Returns true if the given `page` is the one at `position` in the tree.
+    pub fn contains_page(&self, position: u64, page: &[u8]) -> bool {
+        self.opening(position)
+            .map(|opening| opening.verify(page))
+            .unwrap_or(false)
+    }
 }
 
 pub type Tree = dusk_merkle::Tree<Hash, C_HEIGHT, C_ARITY>;
@@ -80,6 +100,8 @@ pub type Tree = dusk_merkle::Tree<Hash, C_HEIGHT, C_ARITY>;
 #[archive_attr(derive(CheckBytes))]
 pub struct NewContractIndex {
     inner_contracts: BTreeMap<ContractId, ContractIndexElement>,
+    /// Contracts removed in this commit, hiding them in its bases.
+    removed: BTreeSet<ContractId>,
 }
 
 impl NewContractIndex {
@@ -115,7 +137,9 @@ impl ContractsMerkle {
     pub fn insert(&mut self, pos: u64, hash: Hash) -> u64 {
         let new_pos = match self.dict.get(&pos) {
             None => {
-                let new_pos = (self.dict.len() + 1) as u64;
+                // Positions freed by removed contracts are only reused once
+                // they are the last ones in the tree.
+                let new_pos = self.tree_pos.last_pos().map_or(1, |p| p + 1);
                 self.dict.insert(pos, new_pos);
                 new_pos
             }
@@ -132,6 +156,14 @@ impl ContractsMerkle {
         self.tree_pos.insert(int_pos as u32, (hash, pos));
     }
 
+    /// Removes the leaf at the given position, if any.
+    pub fn remove(&mut self, pos: u64) {
+        if let Some(int_pos) = self.dict.remove(&pos) {
+            self.inner_tree.remove(int_pos);
+            self.tree_pos.remove(int_pos as u32);
+        }
+    }
+
     pub fn opening(&self, pos: u64) -> Option<TreeOpening> {
         let new_pos = self.dict.get(&pos)?;
         self.inner_tree.opening(*new_pos)
@@ -177,6 +209,15 @@ impl TreePos {
         self.tree_pos.insert(k, v);
     }
 
+    pub fn remove(&mut self, k: u32) {
+        self.tree_pos.remove(&k);
+    }
+
+    /// Returns the last position taken, if any.
+    pub fn last_pos(&self) -> Option<u64> {
+        self.tree_pos.keys().next_back().map(|k| u64::from(*k))
+    }
+
     pub fn marshall<W: Write>(&self, w: &mut W) -> io::Result<()> {
         const CHUNK_SIZE: usize = 8192;
         const ELEM_SIZE: usize = 4 + 32 + 4;
@@ -312,6 +353,13 @@ impl ContractIndexElement {
         self.page_indices.insert(page_index);
         self.tree.insert(page_index_u64, page_hash);
     }
+
+    /// Removes the page with the given index, making it implicitly zero.
+    pub fn remove_page_index(&mut self, page_index: usize) {
+        if self.page_indices.remove(&page_index) {
+            self.tree.remove(page_index as u64);
+        }
+    }
 }
 
 impl Default for NewContractIndex {
@@ -324,6 +372,7 @@ impl NewContractIndex {
     pub fn new() -> Self {
         Self {
             inner_contracts: BTreeMap::new(),
+            removed: BTreeSet::new(),
         }
     }
 
@@ -339,9 +388,26 @@ impl NewContractIndex {
         contract_id: &ContractId,
         element: ContractIndexElement,
     ) {
+        self.removed.remove(contract_id);
         self.inner_contracts.insert(*contract_id, element);
     }
 
+    /// Marks the given contract as removed, dropping its element.
+    pub fn mark_removed(&mut self, contract_id: &ContractId) {
+        self.inner_contracts.remove(contract_id);
+        self.removed.insert(*contract_id);
+    }
+
+    /// Returns whether the given contract was removed.
+    pub fn is_removed(&self, contract_id: &ContractId) -> bool {
+        self.removed.contains(contract_id)
+    }
+
+    /// Returns the contracts removed.
+    pub fn removed(&self) -> &BTreeSet<ContractId> {
+        &self.removed
+    }
+
     pub fn get(&self, contract: &ContractId) -> Option<&ContractIndexElement> {
         self.inner_contracts.get(contract)
     }
@@ -363,6 +429,10 @@ impl NewContractIndex {
         self.inner_contracts.iter()
     }
 
+    pub fn contract_ids(&self) -> impl Iterator<Item = &ContractId> {
+        self.inner_contracts.keys()
+    }
+
     pub fn move_into(self, target: &mut Self) {
         for (contract_id, element) in self.inner_contracts.into_iter() {
             target.insert_contract_index(&contract_id, element);
@@ -405,6 +475,7 @@ type TreeOpening = dusk_merkle::Opening<Hash, C_HEIGHT, C_ARITY>;
 #[derive(Debug, Clone, Archive, Deserialize, Serialize)]
 #[archive_attr(derive(CheckBytes))]
 pub struct PageOpening {
+    pub contract_id: ContractId,
     pub tree: TreeOpening,
     pub inner: InnerPageOpening,
 }
@@ -430,10 +501,85 @@ impl PageOpening {
     /// [`root`]: PageOpening::root
     /// [`Session::root`]: crate::Session::root
     pub fn verify(&self, page: &[u8]) -> bool {
-        self.inner.verify(page) & self.tree.verify(*self.inner.root())
+        let leaf = contract_leaf(&self.contract_id, self.inner.root());
+        self.inner.verify(page) & self.tree.verify(leaf)
+    }
+}
+
+/// A Merkle proof of the state of a contract's memory in a commit.
+///
+/// The proof opens the leaf of the contract in the state tree, which binds the
+/// [`contract_id`] to the root of the tree of the contract's memory pages.
+/// Individual pages can then be checked against the [`memory_root`], using the
+/// openings returned by [`Session::memory_pages`].
+///
+/// [`memory_root`]: MerkleProof::memory_root
+/// [`contract_id`]: MerkleProof::contract_id
+/// [`Session::memory_pages`]: crate::Session::memory_pages
+#[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive_attr(derive(CheckBytes))]
+pub struct MerkleProof {
+    contract_id: ContractId,
+    memory_root: Hash,
+    tree: TreeOpening,
+}
+
+impl MerkleProof {
+    pub(crate) fn new(
+        contract_id: ContractId,
+        memory_root: Hash,
+        tree: TreeOpening,
+    ) -> Self {
+        Self {
+            contract_id,
+            memory_root,
+            tree,
+        }
+    }
+
+    /// The ID of the contract the proof was created for.
+    pub fn contract_id(&self) -> &ContractId {
+        &self.contract_id
+    }
+
+    /// The root of the tree of the contract's memory pages.
+    pub fn memory_root(&self) -> [u8; 32] {
+        self.memory_root.into()
+    }
+
+    /// The root of the state tree when this proof was created.
+    pub fn root(&self) -> Root {
+        (*self.tree.root()).into()
     }
 }
 
+/// Verify that the given `proof` is of a contract's memory in the state with
+/// the given `root`, and that the memory belongs to the contract the proof
+/// claims.
+///
+/// This is meant to be used by light clients to check a contract's memory
+/// against a published state root, such as the one returned by
+/// [`Session::root`].
+///
+/// [`Session::root`]: crate::Session::root
+pub fn verify_proof(root: impl Into<Root>, proof: &MerkleProof) -> bool {
+    let leaf = contract_leaf(&proof.contract_id, &proof.memory_root);
+    proof.root() == root.into() && proof.tree.verify(leaf)
+}
+
+/// Returns the leaf of a `contract` in the state tree, given the root of the
+/// tree of its memory pages.
+///
+/// Hashing the ID of the contract into its leaf means that an opening of the
+/// leaf proves which contract the memory belongs to, and not only that it is
+/// in the state.
+pub(crate) fn contract_leaf(contract: &ContractId, memory_root: &Hash) -> Hash {
+    let mut hasher = Hasher::new();
+    hasher.update(contract.as_bytes());
+    hasher.update(memory_root.as_bytes());
+    hasher.finalize()
+}
+
 #[derive(
     Debug,
     Clone,
@@ -576,4 +722,35 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn proof_bound_to_contract() {
+        let first = ContractId::from_bytes([1; 32]);
+        let second = ContractId::from_bytes([2; 32]);
+
+        // Both contracts have the same memory
+        let mut page_tree = PageTree::new(false);
+        page_tree.insert(0, Hash::new(&[1; 64]));
+        let memory_root = *page_tree.root();
+
+        let mut merkle = ContractsMerkle::default();
+        for contract in [first, second] {
+            let leaf = contract_leaf(&contract, &memory_root);
+            merkle.insert(position_from_contract(&contract), leaf);
+        }
+        let root = *merkle.root();
+
+        let opening = merkle
+            .opening(position_from_contract(&first))
+            .expect("There must be an opening for the contract");
+
+        let proof = MerkleProof::new(first, memory_root, opening);
+        assert!(verify_proof(root, &proof));
+
+        let forged = MerkleProof::new(second, memory_root, opening);
+        assert!(
+            !verify_proof(root, &forged),
+            "The proof must not be valid for another contract"
+        );
+    }
 }
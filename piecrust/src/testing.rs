@@ -0,0 +1,467 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+//! A harness for testing contracts, taking care of the setup integration
+//! tests share.
+//!
+//! [`TestSession`] opens a session on an ephemeral VM, deploys contracts by
+//! name - finding their bytecode with [`contract_bytecode`] - and calls them
+//! with a generous gas limit. It keeps the block height in the `height`
+//! metadata item, to be moved forward with [`advance_height`], and offers
+//! fixtures for balances and metadata. [`emitted`] and [`assert_emitted`]
+//! check the events in a receipt.
+//!
+//! [`check_contract_hashes`] compares the in-tree contracts against the hashes
+//! recorded for them, and [`rebuild_contracts`] builds them anew before doing
+//! so.
+//!
+//! Only available with the `testing` feature enabled.
+//!
+//! # Example
+//! ```ignore
+//! use piecrust::testing::TestSession;
+//!
+//! let mut session = TestSession::new()?;
+//! let id = session.deploy("counter")?;
+//!
+//! session.call::<_, ()>(id, "increment", &())?;
+//! session.advance_height(10)?;
+//!
+//! assert_eq!(session.call::<_, i64>(id, "read_value", &())?.data, 0xfd);
+//! ```
+//!
+//! [`advance_height`]: TestSession::advance_height
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, fs, io};
+
+use bytecheck::CheckBytes;
+use piecrust_uplink::{ContractEvent, ContractId, Event};
+use rkyv::validation::validators::DefaultValidator;
+use rkyv::{check_archived_root, Archive, Deserialize, Infallible, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::contract::ContractData;
+use crate::error::Error;
+use crate::session::{CallReceipt, Session, SessionData, SessionDataBuilder};
+use crate::types::StandardBufSerializer;
+use crate::vm::VM;
+
+/// The owner of the contracts deployed by [`TestSession::deploy`].
+pub const OWNER: [u8; 32] = [0u8; 32];
+
+/// The gas limit of the deployments and calls made by a [`TestSession`].
+pub const GAS_LIMIT: u64 = 1_000_000_000;
+
+/// The name of the metadata item holding the block height of a
+/// [`TestSession`].
+pub const HEIGHT_META: &str = "height";
+
+/// The environment variable naming a directory to look for contract bytecode
+/// in, before the defaults of [`contract_bytecode`].
+pub const CONTRACTS_DIR_VAR: &str = "PIECRUST_CONTRACTS_DIR";
+
+/// The file the hashes of the in-tree contracts are recorded in, relative to
+/// the root of the repository.
+pub const CONTRACT_HASHES_FILE: &str = "contracts/hashes.sha256";
+
+/// The directories of a target directory contracts are looked for in, in
+/// order.
+const TARGET_SUBDIRS: &[&str] = &[
+    "stripped",
+    "wasm64-unknown-unknown/release",
+    "wasm32-unknown-unknown/release",
+];
+
+/// Reads the bytecode of the contract with the given `name`, as built by
+/// cargo.
+///
+/// The bytecode is looked for as `<name>.wasm` - with any dashes in the name
+/// replaced by underscores - first in the directory named by
+/// [`CONTRACTS_DIR_VAR`], if set, and then in the `stripped` directory and
+/// the release directories of the `wasm64` and `wasm32` targets of the target
+/// directory. The target directory is the one given by `CARGO_TARGET_DIR`, or
+/// the closest `target` directory to the crate being tested.
+///
+/// # Errors
+/// If the bytecode is found nowhere, or can't be read.
+pub fn contract_bytecode(name: &str) -> io::Result<Vec<u8>> {
+    let file_name = format!("{}.wasm", name.replace('-', "_"));
+
+    let mut dirs = Vec::new();
+    if let Some(dir) = env::var_os(CONTRACTS_DIR_VAR) {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(target_dir) = target_dir() {
+        dirs.extend(
+            TARGET_SUBDIRS.iter().map(|subdir| target_dir.join(subdir)),
+        );
+    }
+
+    for dir in &dirs {
+        let path = dir.join(&file_name);
+        if path.is_file() {
+            return fs::read(path);
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Bytecode for contract {name} not found in {dirs:?}"),
+    ))
+}
+
+fn target_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("CARGO_TARGET_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+
+    let start = env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .or_else(|| env::current_dir().ok())?;
+
+    start
+        .ancestors()
+        .map(|dir| dir.join("target"))
+        .find(|dir| dir.is_dir())
+}
+
+/// The differences between the in-tree contracts, as last built, and the
+/// hashes recorded for them in [`CONTRACT_HASHES_FILE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContractHashDiff {
+    /// Contracts whose bytecode differs from the one recorded.
+    pub changed: Vec<String>,
+    /// Contracts built without a hash being recorded for them.
+    pub unrecorded: Vec<String>,
+    /// Contracts with a recorded hash that weren't built.
+    pub missing: Vec<String>,
+}
+
+impl ContractHashDiff {
+    /// Returns true if every contract matches its recorded hash.
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty()
+            && self.unrecorded.is_empty()
+            && self.missing.is_empty()
+    }
+}
+
+impl fmt::Display for ContractHashDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "All contracts match their recorded hashes");
+        }
+
+        let mut lines = Vec::new();
+        lines
+            .extend(self.changed.iter().map(|name| format!("changed: {name}")));
+        lines.extend(
+            self.unrecorded
+                .iter()
+                .map(|name| format!("unrecorded: {name}")),
+        );
+        lines
+            .extend(self.missing.iter().map(|name| format!("missing: {name}")));
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// Compares the contracts in the `stripped` directory of the target directory,
+/// as built by `make contracts`, against the hashes recorded in
+/// [`CONTRACT_HASHES_FILE`].
+///
+/// The repository is taken to be the parent of the target directory, found as
+/// in [`contract_bytecode`].
+///
+/// # Errors
+/// If the target directory can't be found, or either the hashes or the
+/// contracts can't be read - including when no hashes are recorded.
+pub fn check_contract_hashes() -> io::Result<ContractHashDiff> {
+    let target_dir = target_dir().ok_or_else(no_target_dir)?;
+    let repo_dir = target_dir.parent().ok_or_else(no_target_dir)?;
+
+    let recorded = fs::read_to_string(repo_dir.join(CONTRACT_HASHES_FILE))?;
+    let recorded = parse_contract_hashes(&recorded)?;
+    let built = hash_contracts(&target_dir.join("stripped"))?;
+
+    let mut diff = ContractHashDiff::default();
+    for (name, hash) in &built {
+        match recorded.get(name) {
+            Some(recorded_hash) if recorded_hash == hash => {}
+            Some(_) => diff.changed.push(name.clone()),
+            None => diff.unrecorded.push(name.clone()),
+        }
+    }
+    diff.missing = recorded
+        .into_keys()
+        .filter(|name| !built.contains_key(name))
+        .collect();
+
+    Ok(diff)
+}
+
+/// Rebuilds the in-tree contracts by running `make contracts` at the root of
+/// the repository, and then compares them against their recorded hashes with
+/// [`check_contract_hashes`].
+///
+/// # Errors
+/// If `make` can't be run or fails, or comparing the hashes fails.
+pub fn rebuild_contracts() -> io::Result<ContractHashDiff> {
+    let target_dir = target_dir().ok_or_else(no_target_dir)?;
+    let repo_dir = target_dir.parent().ok_or_else(no_target_dir)?;
+
+    let status = Command::new("make")
+        .arg("contracts")
+        .current_dir(repo_dir)
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("Building the contracts failed: {status}"),
+        ));
+    }
+
+    check_contract_hashes()
+}
+
+/// Parses the lines of a hashes file, as written by `sha256sum`, into the
+/// hashes of each file named.
+fn parse_contract_hashes(
+    contents: &str,
+) -> io::Result<BTreeMap<String, String>> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (hash, name) =
+                line.split_once(char::is_whitespace).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Malformed hash line: {line}"),
+                    )
+                })?;
+            let name = name.trim_start().trim_start_matches('*');
+            Ok((name.to_string(), hash.to_lowercase()))
+        })
+        .collect()
+}
+
+/// Hashes the `.wasm` files in the given directory, by file name.
+fn hash_contracts(dir: &Path) -> io::Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(true, |ext| ext != "wasm") {
+            continue;
+        }
+
+        let name = path
+            .file_name()
+            .expect("Files read from a directory are named")
+            .to_string_lossy()
+            .into_owned();
+        let hash = Sha256::digest(fs::read(&path)?);
+        hashes.insert(name, hex::encode(hash));
+    }
+
+    Ok(hashes)
+}
+
+fn no_target_dir() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "No target directory found")
+}
+
+/// A session opened on an ephemeral VM for testing contracts.
+///
+/// Dereferences to the underlying [`Session`], giving access to everything
+/// not covered by the harness itself.
+pub struct TestSession {
+    session: Session,
+    height: u64,
+    // Dropped last, since the session uses its directory.
+    _vm: VM,
+}
+
+impl TestSession {
+    /// Opens a session on a new ephemeral VM, at height 0.
+    ///
+    /// # Errors
+    /// If creating the VM or opening the session fails.
+    pub fn new() -> Result<Self, Error> {
+        Self::with_data(SessionData::builder())
+    }
+
+    /// Opens a session on a new ephemeral VM with the given `data`.
+    ///
+    /// The height is taken from the [`HEIGHT_META`] item of the data, and set
+    /// to 0 if there is none.
+    ///
+    /// # Errors
+    /// If creating the VM or opening the session fails.
+    pub fn with_data(data: SessionDataBuilder) -> Result<Self, Error> {
+        let vm = VM::ephemeral()?;
+        let mut session = vm.session(data)?;
+
+        let height = match session.meta(HEIGHT_META) {
+            Some(bytes) => check_archived_root::<u64>(&bytes)?
+                .deserialize(&mut Infallible)?,
+            None => {
+                session.set_meta(HEIGHT_META, 0u64)?;
+                0
+            }
+        };
+
+        Ok(Self {
+            session,
+            height,
+            _vm: vm,
+        })
+    }
+
+    /// Deploys the contract with the given `name`, owned by [`OWNER`] and
+    /// without an initializer argument.
+    ///
+    /// # Panics
+    /// If the bytecode of the contract can't be found by
+    /// [`contract_bytecode`].
+    pub fn deploy(&mut self, name: &str) -> Result<ContractId, Error> {
+        self.deploy_with(name, ContractData::builder().owner(OWNER))
+    }
+
+    /// Deploys the contract with the given `name` and `deploy_data`.
+    ///
+    /// # Panics
+    /// If the bytecode of the contract can't be found by
+    /// [`contract_bytecode`], or `deploy_data` does not specify an owner.
+    pub fn deploy_with<'a, A, D>(
+        &mut self,
+        name: &str,
+        deploy_data: D,
+    ) -> Result<ContractId, Error>
+    where
+        A: 'a + for<'b> Serialize<StandardBufSerializer<'b>>,
+        D: Into<ContractData<'a, A>>,
+    {
+        let bytecode = contract_bytecode(name)
+            .unwrap_or_else(|err| panic!("Failed reading bytecode: {err}"));
+        self.session.deploy(&bytecode, deploy_data, GAS_LIMIT)
+    }
+
+    /// Calls the given function of a contract with [`GAS_LIMIT`].
+    ///
+    /// See [`Session::call`] for more details.
+    pub fn call<A, R>(
+        &mut self,
+        contract: ContractId,
+        fn_name: &str,
+        fn_arg: &A,
+    ) -> Result<CallReceipt<R>, Error>
+    where
+        A: for<'b> Serialize<StandardBufSerializer<'b>>,
+        A::Archived: for<'b> CheckBytes<DefaultValidator<'b>>,
+        R: Archive,
+        R::Archived: Deserialize<R, Infallible>
+            + for<'b> CheckBytes<DefaultValidator<'b>>,
+    {
+        self.session.call(contract, fn_name, fn_arg, GAS_LIMIT)
+    }
+
+    /// Returns the current block height.
+    pub fn height(&self) -> u64 {
+        self.height
+    }
+
+    /// Sets the block height, as seen by contracts in the [`HEIGHT_META`]
+    /// item.
+    pub fn set_height(&mut self, height: u64) -> Result<(), Error> {
+        self.session.set_meta(HEIGHT_META, height)?;
+        self.height = height;
+        Ok(())
+    }
+
+    /// Moves the block height forward by the given number of `blocks`,
+    /// returning the new height.
+    pub fn advance_height(&mut self, blocks: u64) -> Result<u64, Error> {
+        self.set_height(self.height + blocks)?;
+        Ok(self.height)
+    }
+
+    /// Adds the given `amount` to the balance of a contract, returning its
+    /// new balance.
+    pub fn fund(&mut self, contract: ContractId, amount: u64) -> u64 {
+        let balance = self.session.balance(&contract) + amount;
+        self.session.set_balance(contract, balance);
+        balance
+    }
+
+    /// Sets the given metadata items, for fixtures shared between tests.
+    pub fn with_meta<I, V>(mut self, items: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (&'static str, V)>,
+        V: for<'a> Serialize<StandardBufSerializer<'a>>,
+    {
+        for (name, value) in items {
+            self.session.set_meta(name, value)?;
+        }
+        Ok(self)
+    }
+}
+
+impl Deref for TestSession {
+    type Target = Session;
+
+    fn deref(&self) -> &Self::Target {
+        &self.session
+    }
+}
+
+impl DerefMut for TestSession {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.session
+    }
+}
+
+/// Decodes the `events` of the given type, in the order they were emitted.
+pub fn emitted<E>(events: &[Event]) -> Vec<E>
+where
+    E: ContractEvent,
+    E::Archived:
+        for<'a> CheckBytes<DefaultValidator<'a>> + Deserialize<E, Infallible>,
+{
+    events.iter().filter_map(Event::decode).collect()
+}
+
+/// Asserts that the `expected` event was emitted by the given `source`
+/// contract, among `events`.
+///
+/// # Panics
+/// If no such event was emitted, listing the events of the same type that
+/// were.
+pub fn assert_emitted<E>(events: &[Event], source: ContractId, expected: &E)
+where
+    E: ContractEvent + PartialEq + Debug,
+    E::Archived:
+        for<'a> CheckBytes<DefaultValidator<'a>> + Deserialize<E, Infallible>,
+{
+    let found: Vec<E> = events
+        .iter()
+        .filter(|event| event.source == source)
+        .filter_map(Event::decode)
+        .collect();
+
+    assert!(
+        found.contains(expected),
+        "Expected {expected:?} from {source:?} with topic {:?}, found {found:?}",
+        E::TOPIC
+    );
+}
@@ -0,0 +1,106 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+use piecrust_uplink::ContractId;
+
+/// An upgrade of a contract, as presented to an [`UpgradeAuthorizer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpgradeRequest<'a> {
+    /// The contract upgraded.
+    pub contract: ContractId,
+    /// The owner of the contract, as recorded in its metadata.
+    pub owner: &'a [u8],
+    /// The bytecode the contract is upgraded to.
+    pub bytecode: &'a [u8],
+    /// The name of the migration function called on the new bytecode.
+    pub migration_fn: &'a str,
+    /// The serialized argument of the migration function.
+    pub fn_arg: &'a [u8],
+}
+
+impl UpgradeRequest<'_> {
+    /// The message the owner signs to authorize the upgrade.
+    ///
+    /// It commits to everything the upgrade does - the contract, the new
+    /// bytecode, and the migration function called with its argument - so
+    /// that a signature can't be used to authorize any other upgrade.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.contract.as_bytes());
+        hasher.update(blake3::hash(self.bytecode).as_bytes());
+        hasher.update(&(self.migration_fn.len() as u64).to_le_bytes());
+        hasher.update(self.migration_fn.as_bytes());
+        hasher.update(self.fn_arg);
+        hasher.finalize().into()
+    }
+}
+
+/// Verifies that an upgrade of a contract is authorized by its owner.
+///
+/// The VM attaches no meaning to the bytes of an owner, so it is up to the
+/// host to say what an authorization is - typically a signature over the
+/// [`digest`] of the request, checked against the owner as a public key. It
+/// is set on a VM using [`VM::set_upgrade_authorizer`], and upgrades are
+/// refused in sessions spawned without one.
+///
+/// Implementers of `Fn(&UpgradeRequest, &[u8]) -> bool` can be used as an
+/// `UpgradeAuthorizer`.
+///
+/// [`digest`]: UpgradeRequest::digest
+/// [`VM::set_upgrade_authorizer`]: crate::VM::set_upgrade_authorizer
+pub trait UpgradeAuthorizer: Send + Sync {
+    /// Returns true if the given `authorization` proves that the owner of
+    /// the contract consents to the upgrade `request`.
+    fn authorize(&self, request: &UpgradeRequest, authorization: &[u8])
+        -> bool;
+}
+
+impl<F> UpgradeAuthorizer for F
+where
+    F: Send + Sync + Fn(&UpgradeRequest, &[u8]) -> bool,
+{
+    fn authorize(
+        &self,
+        request: &UpgradeRequest,
+        authorization: &[u8],
+    ) -> bool {
+        self(request, authorization)
+    }
+}
+
+/// The authorizer set on a VM, if any, shared with its sessions.
+#[derive(Clone, Default)]
+pub(crate) struct Authorizer(Option<Arc<dyn UpgradeAuthorizer>>);
+
+impl Authorizer {
+    pub fn new(authorizer: impl 'static + UpgradeAuthorizer) -> Self {
+        Self(Some(Arc::new(authorizer)))
+    }
+
+    /// Returns true if there is an authorizer, and it authorizes the given
+    /// `request`.
+    pub fn authorize(
+        &self,
+        request: &UpgradeRequest,
+        authorization: &[u8],
+    ) -> bool {
+        match &self.0 {
+            Some(authorizer) => authorizer.authorize(request, authorization),
+            None => false,
+        }
+    }
+}
+
+impl Debug for Authorizer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Authorizer")
+            .field(&self.0.is_some())
+            .finish()
+    }
+}
@@ -4,26 +4,54 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
-use std::any::Any;
+use std::any::{type_name, Any};
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::BTreeMap;
 use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
 use std::path::Path;
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
+use bytecheck::CheckBytes;
+
+#[cfg(feature = "perfmap")]
+use dusk_wasmtime::ProfilingStrategy;
 use dusk_wasmtime::{
     Config, Engine, ModuleVersionStrategy, OperatorCost, OptLevel, Strategy,
     WasmBacktraceDetails,
 };
-use tempfile::tempdir;
+use piecrust_uplink::{ContractId, SCRATCH_BUF_BYTES};
+use rkyv::ser::serializers::{
+    BufferScratch, BufferSerializer, CompositeSerializer,
+};
+use rkyv::ser::Serializer;
+use rkyv::validation::validators::DefaultValidator;
+use rkyv::{check_archived_root, Archive, Deserialize, Infallible, Serialize};
+use tempfile::{tempdir, TempDir};
 
-use crate::config::BYTE_STORE_COST;
+use crate::contract::{ValidationConfig, WasmFeatures};
+use crate::environment::Environment;
+use crate::gas::{self, GasReport, GasSchedule, InstructionCosts, ReplayCall};
+use crate::maintenance::{self, MaintenancePlan, MaintenanceReceipt};
+use crate::root::Root;
 use crate::session::{Session, SessionData};
-use crate::store::ContractStore;
+use crate::store::{
+    CommitDiff, CommitInfo, ContractSession, ContractStore, FsBackend,
+    FsckLevel, FsckReport, Hash, HeatMap, Scheduler, StorageBackend,
+};
+use crate::types::StandardBufSerializer;
+use crate::upgrade::{Authorizer, UpgradeAuthorizer};
 use crate::Error::{self, PersistenceError};
 
-fn config() -> Config {
+/// The native stack a contract's WASM may use in each call into it.
+pub(crate) const MAX_WASM_STACK: usize = 0x80000;
+
+fn config(features: WasmFeatures, costs: &InstructionCosts) -> Config {
     let mut config = Config::new();
 
     // Neither WASM backtrace, nor native unwind info.
@@ -33,13 +61,15 @@ fn config() -> Config {
     config.native_unwind_info(false);
 
     // 512KiB of max stack is the default, but we want to be explicit about it.
-    config.max_wasm_stack(0x80000);
+    config.max_wasm_stack(MAX_WASM_STACK);
     config.consume_fuel(true);
+    // Interrupt calls running past the timeout set by their session
+    config.epoch_interruption(true);
 
     config.strategy(Strategy::Cranelift);
     config.cranelift_opt_level(OptLevel::SpeedAndSize);
-    // We need entirely deterministic computation
-    config.cranelift_nan_canonicalization(true);
+    // NaNs are canonicalized by rewriting the bytecode when deployed, as set
+    // in the validation config, so it's not done by the engine.
 
     // Host memory creator is set in the session.
     // config.with_host_memory()
@@ -56,37 +86,83 @@ fn config() -> Config {
     config.generate_address_map(false);
     config.macos_use_mach_ports(false);
 
+    // Write the address ranges of compiled functions to
+    // `/tmp/perf-<pid>.map`, for profilers to pick up.
+    #[cfg(feature = "perfmap")]
+    config.profiler(ProfilingStrategy::PerfMap);
+
     // Support 64-bit memories
     config.wasm_memory64(true);
+    // Support a scratch memory next to the contract's main memory
+    config.wasm_multi_memory(true);
+    // Set every other proposal explicitly, since the defaults may change
+    // between versions of the engine
+    features.apply(&mut config);
+
+    // Costs too large for the engine are capped, which makes them
+    // impossible to pay for anyway.
+    let cost = |cost: u64| i64::try_from(cost).unwrap_or(i64::MAX);
 
-    const BYTE4_STORE_COST: i64 = 4 * BYTE_STORE_COST;
-    const BYTE8_STORE_COST: i64 = 8 * BYTE_STORE_COST;
-    const BYTE16_STORE_COST: i64 = 16 * BYTE_STORE_COST;
+    let byte4_store_cost = cost(costs.store_byte.saturating_mul(4));
+    let byte8_store_cost = cost(costs.store_byte.saturating_mul(8));
+    let byte16_store_cost = cost(costs.store_byte.saturating_mul(16));
+    let load_cost = cost(costs.load);
+    let call_cost = cost(costs.call);
 
     config.operator_cost(OperatorCost {
-        I32Store: BYTE4_STORE_COST,
-        F32Store: BYTE4_STORE_COST,
-        I32Store8: BYTE4_STORE_COST,
-        I32Store16: BYTE4_STORE_COST,
-        I32AtomicStore: BYTE4_STORE_COST,
-        I32AtomicStore8: BYTE4_STORE_COST,
-        I32AtomicStore16: BYTE4_STORE_COST,
-
-        I64Store: BYTE8_STORE_COST,
-        F64Store: BYTE8_STORE_COST,
-        I64Store8: BYTE8_STORE_COST,
-        I64Store16: BYTE8_STORE_COST,
-        I64Store32: BYTE8_STORE_COST,
-        I64AtomicStore: BYTE8_STORE_COST,
-        I64AtomicStore8: BYTE8_STORE_COST,
-        I64AtomicStore16: BYTE8_STORE_COST,
-        I64AtomicStore32: BYTE8_STORE_COST,
-
-        V128Store: BYTE16_STORE_COST,
-        V128Store8Lane: BYTE16_STORE_COST,
-        V128Store16Lane: BYTE16_STORE_COST,
-        V128Store32Lane: BYTE16_STORE_COST,
-        V128Store64Lane: BYTE16_STORE_COST,
+        I32Store: byte4_store_cost,
+        F32Store: byte4_store_cost,
+        I32Store8: byte4_store_cost,
+        I32Store16: byte4_store_cost,
+        I32AtomicStore: byte4_store_cost,
+        I32AtomicStore8: byte4_store_cost,
+        I32AtomicStore16: byte4_store_cost,
+
+        I64Store: byte8_store_cost,
+        F64Store: byte8_store_cost,
+        I64Store8: byte8_store_cost,
+        I64Store16: byte8_store_cost,
+        I64Store32: byte8_store_cost,
+        I64AtomicStore: byte8_store_cost,
+        I64AtomicStore8: byte8_store_cost,
+        I64AtomicStore16: byte8_store_cost,
+        I64AtomicStore32: byte8_store_cost,
+
+        V128Store: byte16_store_cost,
+        V128Store8Lane: byte16_store_cost,
+        V128Store16Lane: byte16_store_cost,
+        V128Store32Lane: byte16_store_cost,
+        V128Store64Lane: byte16_store_cost,
+
+        I32Load: load_cost,
+        F32Load: load_cost,
+        I32Load8S: load_cost,
+        I32Load8U: load_cost,
+        I32Load16S: load_cost,
+        I32Load16U: load_cost,
+        I32AtomicLoad: load_cost,
+        I32AtomicLoad8U: load_cost,
+        I32AtomicLoad16U: load_cost,
+
+        I64Load: load_cost,
+        F64Load: load_cost,
+        I64Load8S: load_cost,
+        I64Load8U: load_cost,
+        I64Load16S: load_cost,
+        I64Load16U: load_cost,
+        I64Load32S: load_cost,
+        I64Load32U: load_cost,
+        I64AtomicLoad: load_cost,
+        I64AtomicLoad8U: load_cost,
+        I64AtomicLoad16U: load_cost,
+        I64AtomicLoad32U: load_cost,
+
+        V128Load: load_cost,
+
+        Call: call_cost,
+        CallIndirect: call_cost,
+
+        MemoryGrow: cost(costs.memory_grow),
 
         ..Default::default()
     });
@@ -101,7 +177,8 @@ fn config() -> Config {
 ///
 /// These sessions are synchronized with the help of a sync loop. [`Deletions`]
 /// are assured to not delete any commits used as a base for sessions until
-/// these are dropped. A handle to this loop is available at [`sync_thread`].
+/// these are dropped. A handle to this loop is available at [`sync_thread`],
+/// and the work it hands off is run by the [`scheduler`].
 ///
 /// Users are encouraged to instantiate a `VM` once during the lifetime of their
 /// program and spawn sessions as needed.
@@ -112,34 +189,101 @@ fn config() -> Config {
 /// [`session`]: VM::session
 /// [`Deletions`]: VM::delete_commit
 /// [`sync_thread`]: VM::sync_thread
+/// [`scheduler`]: VM::scheduler
 pub struct VM {
     engine: Engine,
+    instruction_costs: InstructionCosts,
     host_queries: HostQueries,
+    upgrade_authorizer: Authorizer,
+    validation: ValidationConfig,
     store: ContractStore,
+    // The directory of an ephemeral VM, removed once the VM drops. Kept last
+    // so the store is dropped before it.
+    _tmp_dir: Option<TempDir>,
 }
 
 impl Debug for VM {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("VM")
             .field("config", self.engine.config())
+            .field("instruction_costs", &self.instruction_costs)
             .field("host_queries", &self.host_queries)
+            .field("upgrade_authorizer", &self.upgrade_authorizer)
+            .field("validation", &self.validation)
             .field("store", &self.store)
             .finish()
     }
 }
 
-impl VM {
-    /// Creates a new `VM`, reading the given `dir`ectory for existing commits
+/// Configures and builds a [`VM`].
+///
+/// It is obtained using [`VM::builder`]. The configuration is that of the
+/// engine compiling and running contracts, and is fixed for the lifetime of
+/// the `VM`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VmBuilder {
+    features: WasmFeatures,
+    instruction_costs: InstructionCosts,
+}
+
+impl VmBuilder {
+    /// Accept only the given WASM `features` in contracts.
+    pub fn features(mut self, features: WasmFeatures) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Meter the execution of contracts using the given instruction `costs`.
+    pub fn instruction_costs(mut self, costs: InstructionCosts) -> Self {
+        self.instruction_costs = costs;
+        self
+    }
+
+    /// Builds the `VM`, reading the given `dir`ectory for existing commits
     /// and bytecode.
     ///
-    /// The directory will be used to save any future session commits made by
-    /// this `VM` instance.
+    /// The hash of the instruction costs is persisted in the directory the
+    /// first time it is used, and building a `VM` with different costs on the
+    /// same directory fails. This prevents commits from being replayed under a
+    /// different schedule than the one they were made with.
+    ///
+    /// Modules compiled with a different configuration are compiled again from
+    /// their bytecode.
     ///
     /// # Errors
-    /// If the directory contains unparseable or inconsistent data.
-    pub fn new<P: AsRef<Path>>(root_dir: P) -> Result<Self, Error> {
+    /// If the directory contains unparseable or inconsistent data, a contract
+    /// using a denied feature, or was used with different instruction costs.
+    pub fn build<P: AsRef<Path>>(self, root_dir: P) -> Result<VM, Error> {
+        let backend = FsBackend::new(root_dir.as_ref());
+        self.build_with_backend(root_dir, backend)
+    }
+
+    /// Builds the `VM` like [`build`], but writing the commits it makes to -
+    /// and deleting them from - the given `backend`.
+    ///
+    /// The directory is still read for existing commits and bytecode, and
+    /// keeps the commit index and heat map. Since sessions load contracts from
+    /// the directory, they can only be spawned off commits written to a
+    /// backend storing them in its layout, such as an [`FsBackend`] on the
+    /// same directory.
+    ///
+    /// # Errors
+    /// In the same cases as [`build`].
+    ///
+    /// [`build`]: VmBuilder::build
+    pub fn build_with_backend<P, B>(
+        self,
+        root_dir: P,
+        backend: B,
+    ) -> Result<VM, Error>
+    where
+        P: AsRef<Path>,
+        B: StorageBackend + Send + 'static,
+    {
         tracing::trace!("vm::new");
-        let config = config();
+        let root_dir = root_dir.as_ref();
+
+        let config = config(self.features, &self.instruction_costs);
 
         let engine = Engine::new(&config).expect(
             "Configuration should be valid since its set at compile time",
@@ -148,47 +292,116 @@ impl VM {
         tracing::trace!("before ContractStore::new");
         let mut store = ContractStore::new(engine.clone(), root_dir)
             .map_err(|err| PersistenceError(Arc::new(err)))?;
+        store.set_instruction_costs(self.instruction_costs.hash());
         tracing::trace!("before ContractStore::finish_new");
         store
-            .finish_new()
+            .finish_new(backend)
             .map_err(|err| PersistenceError(Arc::new(err)))?;
         tracing::trace!("after ContractStore::finish_new");
 
-        Ok(Self {
+        Ok(VM {
             engine,
+            instruction_costs: self.instruction_costs,
             host_queries: HostQueries::default(),
+            upgrade_authorizer: Authorizer::default(),
+            validation: ValidationConfig::default(),
             store,
+            _tmp_dir: None,
         })
     }
 
-    /// Creates a new `VM` using a new temporary directory.
+    /// Builds the `VM` using a new temporary directory.
     ///
     /// Any session commits made by this machine should be considered discarded
-    /// once this `VM` instance drops.
+    /// once the `VM` instance drops.
     ///
     /// # Errors
     /// If creating a temporary directory fails.
-    pub fn ephemeral() -> Result<Self, Error> {
+    pub fn build_ephemeral(self) -> Result<VM, Error> {
         let tmp = tempdir().map_err(|err| PersistenceError(Arc::new(err)))?;
-        let tmp = tmp.path().to_path_buf();
 
-        let config = config();
+        let mut vm = self.build(tmp.path())?;
+        vm._tmp_dir = Some(tmp);
+        Ok(vm)
+    }
+}
 
-        let engine = Engine::new(&config).expect(
-            "Configuration should be valid since its set at compile time",
-        );
+impl VM {
+    /// Returns a builder for a `VM`, allowing for configuring the engine it
+    /// uses.
+    pub fn builder() -> VmBuilder {
+        VmBuilder::default()
+    }
 
-        let mut store = ContractStore::new(engine.clone(), tmp)
-            .map_err(|err| PersistenceError(Arc::new(err)))?;
-        store
-            .finish_new()
-            .map_err(|err| PersistenceError(Arc::new(err)))?;
+    /// Creates a new `VM`, reading the given `dir`ectory for existing commits
+    /// and bytecode.
+    ///
+    /// The directory will be used to save any future session commits made by
+    /// this `VM` instance.
+    ///
+    /// # Errors
+    /// If the directory contains unparseable or inconsistent data.
+    pub fn new<P: AsRef<Path>>(root_dir: P) -> Result<Self, Error> {
+        Self::builder().build(root_dir)
+    }
 
-        Ok(Self {
-            engine,
-            host_queries: HostQueries::default(),
-            store,
-        })
+    /// Creates a new `VM` reading the given `dir`ectory for existing commits
+    /// and bytecode, and writing the commits it makes to the given `backend`.
+    ///
+    /// See [`VmBuilder::build_with_backend`] for more details.
+    ///
+    /// # Errors
+    /// If the directory contains unparseable or inconsistent data.
+    pub fn with_backend<P, B>(root_dir: P, backend: B) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+        B: StorageBackend + Send + 'static,
+    {
+        Self::builder().build_with_backend(root_dir, backend)
+    }
+
+    /// Creates a new `VM` accepting only the given WASM `features` in
+    /// contracts, reading the given `dir`ectory for existing commits and
+    /// bytecode.
+    ///
+    /// Modules compiled with a different set of features are compiled again
+    /// from their bytecode.
+    ///
+    /// # Errors
+    /// If the directory contains unparseable or inconsistent data, or a
+    /// contract using a denied feature.
+    pub fn new_with_features<P: AsRef<Path>>(
+        root_dir: P,
+        features: WasmFeatures,
+    ) -> Result<Self, Error> {
+        Self::builder().features(features).build(root_dir)
+    }
+
+    /// Creates a new `VM` using a new temporary directory.
+    ///
+    /// Any session commits made by this machine should be considered discarded
+    /// once this `VM` instance drops.
+    ///
+    /// # Errors
+    /// If creating a temporary directory fails.
+    pub fn ephemeral() -> Result<Self, Error> {
+        Self::builder().build_ephemeral()
+    }
+
+    /// Creates a new `VM` accepting only the given WASM `features` in
+    /// contracts, using a new temporary directory.
+    ///
+    /// # Errors
+    /// If creating a temporary directory fails.
+    pub fn ephemeral_with_features(
+        features: WasmFeatures,
+    ) -> Result<Self, Error> {
+        Self::builder().features(features).build_ephemeral()
+    }
+
+    /// Returns the [`InstructionCosts`] contracts are metered with.
+    pub fn instruction_costs(&self) -> &InstructionCosts {
+        &self.instruction_costs
     }
 
     /// Registers a [host `query`] with the given `name`.
@@ -205,10 +418,104 @@ impl VM {
         self.host_queries.insert(name, query);
     }
 
+    /// Registers a [host `query`] with the given `name`, charging the contract
+    /// the gas given by `price` for the length of the argument, in bytes,
+    /// before the query is executed.
+    ///
+    /// The price is added to any the query charges itself. The query will be
+    /// available to any session spawned *after* this was called.
+    ///
+    /// [host `query`]: HostQuery
+    pub fn register_priced_host_query<Q, S, P>(
+        &mut self,
+        name: S,
+        price: P,
+        query: Q,
+    ) where
+        Q: 'static + HostQuery,
+        S: Into<Cow<'static, str>>,
+        P: 'static + Send + Sync + Fn(usize) -> u64,
+    {
+        self.host_queries.insert_priced(name, price, query);
+    }
+
+    /// Registers a host query with the given `name`, taking an argument of
+    /// type `A` and returning a value of type `R`.
+    ///
+    /// The argument is deserialized from the contract's argument buffer, and
+    /// passed to `cost` to compute the gas the contract is charged. If the
+    /// contract can afford it, the argument is passed to `handler`, and its
+    /// return serialized back to the argument buffer.
+    ///
+    /// An argument that fails to deserialize is priced at [`u64::MAX`], making
+    /// the contract run out of gas.
+    ///
+    /// The types of the argument and return are recorded, and can be listed
+    /// with [`host_queries`]. The query will be available to any session
+    /// spawned *after* this was called.
+    ///
+    /// [`host_queries`]: VM::host_queries
+    pub fn register_typed_host_query<A, R, S, C, H>(
+        &mut self,
+        name: S,
+        cost: C,
+        handler: H,
+    ) where
+        A: 'static + Archive,
+        A::Archived: Deserialize<A, Infallible>
+            + for<'b> CheckBytes<DefaultValidator<'b>>,
+        R: 'static + for<'a> Serialize<StandardBufSerializer<'a>>,
+        S: Into<Cow<'static, str>>,
+        C: 'static + Send + Sync + Fn(&A) -> u64,
+        H: 'static + Send + Sync + Fn(A) -> R,
+    {
+        self.host_queries.insert_typed(name, cost, handler);
+    }
+
+    /// Returns the names of the registered host queries, in order, together
+    /// with their signatures if they were registered with one.
+    ///
+    /// See [`register_typed_host_query`].
+    ///
+    /// [`register_typed_host_query`]: VM::register_typed_host_query
+    pub fn host_queries(
+        &self,
+    ) -> impl Iterator<Item = (&str, Option<HostQuerySignature>)> {
+        self.host_queries.iter()
+    }
+
+    /// Sets the [`UpgradeAuthorizer`] checking that upgrades made with
+    /// [`Session::upgrade`] are authorized by the owner of the contract.
+    ///
+    /// The authorizer will be used by any session spawned *after* this was
+    /// called. Sessions spawned without one refuse all upgrades.
+    ///
+    /// [`Session::upgrade`]: crate::Session::upgrade
+    pub fn set_upgrade_authorizer<A>(&mut self, authorizer: A)
+    where
+        A: 'static + UpgradeAuthorizer,
+    {
+        self.upgrade_authorizer = Authorizer::new(authorizer);
+    }
+
+    /// Sets the rules bytecode must follow to be deployed in sessions spawned
+    /// *after* this is called.
+    ///
+    /// See [`ValidationConfig`] for more details.
+    pub fn set_validation_config(&mut self, config: ValidationConfig) {
+        self.validation = config;
+    }
+
+    /// Returns the rules bytecode must follow to be deployed.
+    pub fn validation_config(&self) -> ValidationConfig {
+        self.validation
+    }
+
     /// Spawn a [`Session`].
     ///
     /// # Errors
-    /// If base commit is provided but does not exist.
+    /// If base commit or any of the fallback commits is provided but does not
+    /// exist.
     ///
     /// [`Session`]: Session
     pub fn session(
@@ -216,40 +523,519 @@ impl VM {
         data: impl Into<SessionData>,
     ) -> Result<Session, Error> {
         let data = data.into();
-        let contract_session = match data.base {
+        let mut contract_session = match data.base {
             Some(base) => self
                 .store
                 .session(base.into())
                 .map_err(|err| PersistenceError(Arc::new(err)))?,
             _ => self.store.genesis_session(),
         };
+        self.check_instruction_costs(&contract_session)?;
+        let fallbacks: Vec<Hash> =
+            data.fallbacks.iter().map(|root| (*root).into()).collect();
+        self.store
+            .add_fallbacks(&mut contract_session, &fallbacks)
+            .map_err(|err| PersistenceError(Arc::new(err)))?;
+        Ok(Session::new(
+            self.engine.clone(),
+            self.instruction_costs,
+            contract_session,
+            self.host_queries.clone(),
+            self.upgrade_authorizer.clone(),
+            self.validation,
+            data,
+        ))
+    }
+
+    /// Spawn a [`Session`], without blocking the current thread while its
+    /// base commit is being looked up.
+    ///
+    /// # Errors
+    /// If base commit or any of the fallback commits is provided but does not
+    /// exist.
+    ///
+    /// [`Session`]: Session
+    pub async fn session_async(
+        &self,
+        data: impl Into<SessionData>,
+    ) -> Result<Session, Error> {
+        let data = data.into();
+        let mut contract_session = match data.base {
+            Some(base) => self
+                .store
+                .session_async(base.into())
+                .await
+                .map_err(|err| PersistenceError(Arc::new(err)))?,
+            _ => self.store.genesis_session(),
+        };
+        self.check_instruction_costs(&contract_session)?;
+        let fallbacks: Vec<Hash> =
+            data.fallbacks.iter().map(|root| (*root).into()).collect();
+        self.store
+            .add_fallbacks_async(&mut contract_session, &fallbacks)
+            .await
+            .map_err(|err| PersistenceError(Arc::new(err)))?;
         Ok(Session::new(
             self.engine.clone(),
+            self.instruction_costs,
             contract_session,
             self.host_queries.clone(),
+            self.upgrade_authorizer.clone(),
+            self.validation,
             data,
         ))
     }
 
+    /// Errors if the base of the given session was committed with different
+    /// instruction costs than this `VM` charges, since building on it would
+    /// diverge from what others building on it compute.
+    fn check_instruction_costs(
+        &self,
+        contract_session: &ContractSession,
+    ) -> Result<(), Error> {
+        let expected = self.instruction_costs.hash();
+        match contract_session.base_instruction_costs() {
+            Some(found) if found != expected => {
+                Err(Error::InstructionCostsMismatch { expected, found })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Replays the given `calls` under both a `baseline` and a `proposed`
+    /// [`GasSchedule`], and reports the difference in the gas spent by each
+    /// call, contract, and function.
+    ///
+    /// The calls are replayed in order, each in a separate session per
+    /// schedule, spawned with the data returned by `session_data` for that
+    /// schedule. Nothing is committed.
+    ///
+    /// # Errors
+    /// If either session cannot be spawned. Calls that fail are included in
+    /// the report.
+    pub fn gas_report<F, D>(
+        &self,
+        session_data: F,
+        calls: &[ReplayCall],
+        baseline: GasSchedule,
+        proposed: GasSchedule,
+    ) -> Result<GasReport, Error>
+    where
+        F: Fn(GasSchedule) -> D,
+        D: Into<SessionData>,
+    {
+        let mut session = self.session(session_data(baseline))?;
+        let baseline = gas::replay(&mut session, calls);
+        drop(session);
+
+        let mut session = self.session(session_data(proposed))?;
+        let proposed = gas::replay(&mut session, calls);
+
+        Ok(GasReport::new(baseline, proposed))
+    }
+
+    /// Runs the calls in the given maintenance `plan` on top of the commit
+    /// with the given `root`, in order, and commits the result.
+    ///
+    /// The calls are made by the host, as opposed to by any contract, so that
+    /// recurring work - such as the hooks of a set of contracts at the end of
+    /// each epoch - is done the same way by every node. A call that fails is
+    /// reverted, without stopping the rest of the plan.
+    ///
+    /// # Errors
+    /// If the commit doesn't exist, or the result can't be committed.
+    pub fn run_maintenance(
+        &self,
+        root: impl Into<Root>,
+        plan: MaintenancePlan,
+    ) -> Result<MaintenanceReceipt, Error> {
+        let (mut data, calls) = plan.into_parts();
+        data.base = Some(root.into().into());
+
+        let mut session = self.session(data)?;
+        let (calls, gas_spent) = maintenance::run(&mut session, calls);
+        let root = session.commit()?;
+
+        Ok(MaintenanceReceipt {
+            root,
+            calls,
+            gas_spent,
+        })
+    }
+
+    /// Returns a description of everything affecting the determinism of
+    /// execution in sessions spawned by this `VM`, using the default
+    /// [`GasSchedule`].
+    ///
+    /// See [`Session::environment`] for the environment of a session
+    /// configured with a different schedule.
+    ///
+    /// [`GasSchedule`]: crate::GasSchedule
+    pub fn environment(&self) -> Environment {
+        Environment::new(
+            &self.engine,
+            &GasSchedule::default(),
+            &self.instruction_costs,
+            &self.host_queries,
+            self.validation,
+            self.store.elide_zero_pages(),
+        )
+    }
+
+    /// Refuse to write commits while the disk the VM's directory is in has less
+    /// than the given number of `bytes` free. Zero, the default, disables the
+    /// check.
+    ///
+    /// Refused commits fail with [`Error::StoreFull`], as do commits that run
+    /// out of disk space while being written. Either way, nothing is left of
+    /// the failed commit on disk, and the VM can keep being used once space
+    /// is freed.
+    pub fn set_min_free_space(&self, bytes: u64) {
+        self.store.set_min_free_space(bytes);
+    }
+
+    /// Leave pages containing only zeroes out of commits, instead of writing
+    /// and hashing them like any other page. Off by default.
+    ///
+    /// When on, a page explicitly written with zeroes is indistinguishable
+    /// from one that was never written: neither is stored on disk, and both
+    /// contribute the same to the state root. This keeps fresh contracts with
+    /// mostly empty memories cheap to commit, but changes the roots of
+    /// commits touching zero pages, so all deployments expected to agree on
+    /// the state must set it the same way. It is reflected in the
+    /// [`environment`], and applies to sessions spawned after it is set.
+    ///
+    /// [`environment`]: VM::environment
+    pub fn set_elide_zero_pages(&self, elide: bool) {
+        self.store.set_elide_zero_pages(elide);
+    }
+
+    /// Set the granularity, in bytes, at which [`diff_commits`] reports
+    /// changed memory pages. Defaults to 64KiB, the size of a WASM page.
+    ///
+    /// Memories are always stored and hashed in 64KiB pages, but contracts
+    /// making small writes scattered across their memory produce much smaller
+    /// diffs when tracked at a finer granularity, such as 4KiB. This doesn't
+    /// affect the state root.
+    ///
+    /// # Panics
+    /// If `bytes` is not a power of two between 4KiB and 64KiB.
+    ///
+    /// [`diff_commits`]: VM::diff_commits
+    pub fn set_tracking_page_size(&self, bytes: usize) {
+        self.store.set_tracking_page_size(bytes);
+    }
+
+    /// Set the directory commits are moved to when [cooled], typically on a
+    /// slower and cheaper volume than the VM's directory.
+    ///
+    /// [cooled]: VM::cool_commit
+    pub fn set_cold_dir<P: AsRef<Path>>(&self, dir: P) {
+        self.store.set_cold_dir(dir);
+    }
+
+    /// Moves the files of the given commit to the [cold directory].
+    ///
+    /// Cold commits keep being usable as any other, with their files read from
+    /// the cold directory instead. This allows keeping older commits around
+    /// without them taking up space on the VM's volume.
+    ///
+    /// Errors if no cold directory is set, or if the commit is being used as
+    /// the base of a session.
+    ///
+    /// [cold directory]: VM::set_cold_dir
+    pub fn cool_commit(&self, root: impl Into<Root>) -> Result<(), Error> {
+        self.store
+            .cool_commit(root.into().into())
+            .map_err(|err| PersistenceError(Arc::new(err)))
+    }
+
+    /// Moves the files of the given commit back into the VM's directory, if
+    /// they were [cooled].
+    ///
+    /// [cooled]: VM::cool_commit
+    pub fn warm_commit(&self, root: impl Into<Root>) -> Result<(), Error> {
+        self.store
+            .warm_commit(root.into().into())
+            .map_err(|err| PersistenceError(Arc::new(err)))
+    }
+
+    /// Returns whether the given commit is [cold].
+    ///
+    /// [cold]: VM::cool_commit
+    pub fn is_commit_cold(&self, root: impl Into<Root>) -> Result<bool, Error> {
+        self.store
+            .is_commit_cold(root.into().into())
+            .map_err(|err| PersistenceError(Arc::new(err)))
+    }
+
     /// Return all existing commits.
-    pub fn commits(&self) -> Vec<[u8; 32]> {
+    pub fn commits(&self) -> Vec<Root> {
         self.store.commits().into_iter().map(Into::into).collect()
     }
 
+    /// Return all existing commits, without blocking the current thread.
+    pub fn commits_async(&self) -> impl Future<Output = Vec<Root>> {
+        let reply = self.store.commits_async();
+        async move { reply.await.into_iter().map(Into::into).collect() }
+    }
+
+    /// Return all existing commits, along with their parent, creation time,
+    /// number of contracts, and size on disk.
+    pub fn commit_infos(&self) -> Vec<CommitInfo> {
+        self.store.commit_infos()
+    }
+
+    /// Return all existing commits along with their information, without
+    /// blocking the current thread.
+    ///
+    /// See [`commit_infos`] for more details.
+    ///
+    /// [`commit_infos`]: VM::commit_infos
+    pub fn commit_infos_async(&self) -> impl Future<Output = Vec<CommitInfo>> {
+        self.store.commit_infos_async()
+    }
+
+    /// Returns the differences between the states of the `old` and `new`
+    /// commits - the contracts added, removed, and modified, together with the
+    /// memory pages that changed - without spawning any sessions.
+    ///
+    /// Pages are reported at the [tracking page size].
+    ///
+    /// # Errors
+    /// If either commit doesn't exist, or the pages of a modified contract
+    /// can't be read.
+    ///
+    /// [tracking page size]: VM::set_tracking_page_size
+    pub fn diff_commits(
+        &self,
+        old: impl Into<Root>,
+        new: impl Into<Root>,
+    ) -> Result<CommitDiff, Error> {
+        self.store
+            .diff_commits(old.into().into(), new.into().into())
+            .map_err(|err| PersistenceError(Arc::new(err)))
+    }
+
     /// Deletes the given commit from disk.
-    pub fn delete_commit(&self, root: [u8; 32]) -> Result<(), Error> {
+    pub fn delete_commit(&self, root: impl Into<Root>) -> Result<(), Error> {
+        self.store
+            .delete_commit(root.into().into())
+            .map_err(|err| PersistenceError(Arc::new(err)))
+    }
+
+    /// Deletes the given commit from disk, unless other commits were written
+    /// on top of it.
+    ///
+    /// Deleting a commit that others depend on leaves them unable to load the
+    /// memory pages they share with it.
+    ///
+    /// # Errors
+    /// [`Error::CommitHasDependents`] listing the commits depending on the
+    /// given one, if any.
+    pub fn delete_commit_checked(
+        &self,
+        root: impl Into<Root>,
+    ) -> Result<(), Error> {
         self.store
-            .delete_commit(root.into())
+            .delete_commit_checked(root.into().into())
+            .map_err(Error::from_store)
+    }
+
+    /// Deletes the given commit from disk, together with all the commits
+    /// written on top of it, either directly or through other commits.
+    pub fn delete_commit_cascade(
+        &self,
+        root: impl Into<Root>,
+    ) -> Result<(), Error> {
+        self.store
+            .delete_commit_cascade(root.into().into())
             .map_err(|err| PersistenceError(Arc::new(err)))
     }
 
+    /// Deletes the given commit from disk, without blocking the current
+    /// thread.
+    ///
+    /// The deletion is requested immediately, and the returned future resolves
+    /// once it is carried out.
+    pub fn delete_commit_async(
+        &self,
+        root: impl Into<Root>,
+    ) -> impl Future<Output = Result<(), Error>> {
+        let reply = self.store.delete_commit_async(root.into().into());
+        async move { reply.await.map_err(|err| PersistenceError(Arc::new(err))) }
+    }
+
     /// Finalizes the given commit on disk.
-    pub fn finalize_commit(&self, root: [u8; 32]) -> Result<(), Error> {
+    pub fn finalize_commit(&self, root: impl Into<Root>) -> Result<(), Error> {
+        self.store
+            .finalize_commit(root.into().into())
+            .map_err(|err| PersistenceError(Arc::new(err)))
+    }
+
+    /// Finalizes the given commit on disk, without blocking the current
+    /// thread.
+    ///
+    /// The finalization is requested immediately, and the returned future
+    /// resolves once it is carried out.
+    pub fn finalize_commit_async(
+        &self,
+        root: impl Into<Root>,
+    ) -> impl Future<Output = Result<(), Error>> {
+        let reply = self.store.finalize_commit_async(root.into().into());
+        async move { reply.await.map_err(|err| PersistenceError(Arc::new(err))) }
+    }
+
+    /// Rebuilds the memory of the given `contract` as of the given commit,
+    /// restoring pages that are missing or corrupt on disk from the copies
+    /// left by other commits.
+    ///
+    /// This is meant to recover from a partial restore of the disk. Returns
+    /// the number of pages restored.
+    pub fn reconstruct(
+        &self,
+        root: impl Into<Root>,
+        contract: ContractId,
+    ) -> Result<usize, Error> {
+        self.store
+            .reconstruct(root.into().into(), contract)
+            .map_err(|err| PersistenceError(Arc::new(err)))
+    }
+
+    /// Folds the chain of commits from `from` up to `to` into `to`, so that
+    /// it no longer depends on the commits in between.
+    ///
+    /// Long-running nodes accumulate long chains of commits, each only storing
+    /// the memory pages it changed. Squashing copies the pages `to` would find
+    /// in the chain into its own directories, speeding up loading contracts
+    /// from it, and allowing the commits in between to be deleted.
+    ///
+    /// Deletions, finalizations, and moves of the commits in the chain wait
+    /// for the squash to finish.
+    ///
+    /// Errors if `from` is not an ancestor of `to`, or if any commit in the
+    /// chain is already being removed.
+    pub fn squash_commits(
+        &self,
+        from: impl Into<Root>,
+        to: impl Into<Root>,
+    ) -> Result<(), Error> {
+        self.store
+            .squash_commits(from.into().into(), to.into().into())
+            .map_err(|err| PersistenceError(Arc::new(err)))
+    }
+
+    /// Writes the commit with the given `root` to the `writer`, for syncing
+    /// state to another node.
+    ///
+    /// The stream contains everything needed to recreate the commit - the
+    /// bytecode, metadata, and memory of each contract, and the positions in
+    /// the contracts tree - and can be read back using [`import_commit`].
+    ///
+    /// [`import_commit`]: VM::import_commit
+    pub fn export_commit<W: Write>(
+        &self,
+        root: impl Into<Root>,
+        writer: W,
+    ) -> Result<(), Error> {
+        self.store
+            .export_commit(root.into().into(), writer)
+            .map_err(|err| PersistenceError(Arc::new(err)))
+    }
+
+    /// Writes the commit with the given `root` to the given storage
+    /// `backend`, such as one archiving commits away from the VM's directory.
+    ///
+    /// The commit is written whole - with every page of every contract in it
+    /// and without a base - so it can be restored from the backend alone.
+    pub fn archive_commit<B: StorageBackend + ?Sized>(
+        &self,
+        root: impl Into<Root>,
+        backend: &mut B,
+    ) -> Result<(), Error> {
+        self.store
+            .archive_commit(root.into().into(), backend)
+            .map_err(|err| PersistenceError(Arc::new(err)))
+    }
+
+    /// Reads a commit written by [`export_commit`] from the `reader`, adding
+    /// it to the VM. Returns the root of the imported commit.
+    ///
+    /// The memory pages and contract elements are checked against the commit
+    /// root in the stream, and the import fails if the resulting commit has a
+    /// different one. Nothing is left behind in the store on failure.
+    ///
+    /// [`export_commit`]: VM::export_commit
+    pub fn import_commit<R: Read>(&self, reader: R) -> Result<Root, Error> {
+        self.store
+            .import_commit(reader)
+            .map(Into::into)
+            .map_err(|err| PersistenceError(Arc::new(err)))
+    }
+
+    /// Checks the integrity of every commit on disk, reporting what is wrong
+    /// with each commit that would fail to load or to be opened by a session.
+    ///
+    /// At [`FsckLevel::Quick`] the files of each commit are checked to be
+    /// present, and the index of each contract to agree with the tree of its
+    /// commit. [`FsckLevel::Full`] additionally reads every memory page,
+    /// checking it against the leaf it is recorded with, and checks that the
+    /// bytecode of each contract is still linked to the shared code it was
+    /// deployed with.
+    ///
+    /// The VM may be used while the check is running. Deletions and
+    /// finalizations requested in the meantime are carried out once it
+    /// finishes.
+    pub fn fsck(&self, level: FsckLevel) -> FsckReport {
+        self.store.fsck(level)
+    }
+
+    /// Checks the integrity of every commit on disk, like [`fsck`], and then
+    /// deletes the commits found broken, together with all the commits
+    /// written on top of them.
+    ///
+    /// The roots of the deleted commits are listed in the report.
+    ///
+    /// [`fsck`]: VM::fsck
+    pub fn fsck_repair(&self, level: FsckLevel) -> Result<FsckReport, Error> {
         self.store
-            .finalize_commit(root.into())
+            .fsck_repair(level)
             .map_err(|err| PersistenceError(Arc::new(err)))
     }
 
+    /// Returns the engine used to compile and run contracts.
+    #[cfg(feature = "internals")]
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Returns the store holding the VM's commits.
+    #[cfg(feature = "internals")]
+    pub fn contract_store(&self) -> &ContractStore {
+        &self.store
+    }
+
+    /// Shuts the `VM` down, waiting for at most the given `timeout` for the
+    /// commits, finalizations, and deletions in flight to finish.
+    ///
+    /// No more sessions can be spawned once the `VM` is consumed, but the ones
+    /// already spawned keep working, and the `VM` is only done once they are
+    /// all dropped. After that, the state it keeps on disk is flushed and its
+    /// sync loop is joined.
+    ///
+    /// # Errors
+    /// If the work in flight does not finish in time, with
+    /// [`Error::ShutdownTimeout`], in which case it is left to finish in the
+    /// background. If flushing to disk fails.
+    pub fn shutdown(mut self, timeout: Duration) -> Result<(), Error> {
+        self.store
+            .shutdown(timeout)
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::TimedOut => Error::ShutdownTimeout(timeout),
+                _ => PersistenceError(Arc::new(err)),
+            })
+    }
+
     /// Return the root directory of the virtual machine.
     ///
     /// This is either the directory passed in by using [`new`], or the
@@ -265,6 +1051,25 @@ impl VM {
     pub fn sync_thread(&self) -> &thread::Thread {
         self.store.sync_loop()
     }
+
+    /// Returns the scheduler running commits, finalizations, and deletions.
+    ///
+    /// It can be used to query the depth of the queue, to pause and resume
+    /// background work, and to set the number of jobs run concurrently.
+    pub fn scheduler(&self) -> &Scheduler {
+        self.store.scheduler()
+    }
+
+    /// Returns the heat map of page accesses across sessions.
+    ///
+    /// Recording is disabled by default, and can be enabled with a sample rate
+    /// to bound its overhead. The summaries it persists are kept in the root
+    /// directory, and are loaded back when a `VM` is created with [`new`].
+    ///
+    /// [`new`]: VM::new
+    pub fn heat_map(&self) -> &HeatMap {
+        self.store.heat_map()
+    }
 }
 
 #[derive(Default, Clone)]
@@ -287,8 +1092,60 @@ impl HostQueries {
         self.map.insert(name.into(), Arc::new(query));
     }
 
-    pub fn get(&self, name: &str) -> Option<&dyn HostQuery> {
-        self.map.get(name).map(|q| q.as_ref())
+    pub fn insert_priced<Q, S, P>(&mut self, name: S, price: P, query: Q)
+    where
+        Q: 'static + HostQuery,
+        S: Into<Cow<'static, str>>,
+        P: 'static + Send + Sync + Fn(usize) -> u64,
+    {
+        self.insert(name, PricedHostQuery { price, query });
+    }
+
+    pub fn insert_typed<A, R, S, C, H>(&mut self, name: S, cost: C, handler: H)
+    where
+        A: 'static + Archive,
+        A::Archived: Deserialize<A, Infallible>
+            + for<'b> CheckBytes<DefaultValidator<'b>>,
+        R: 'static + for<'a> Serialize<StandardBufSerializer<'a>>,
+        S: Into<Cow<'static, str>>,
+        C: 'static + Send + Sync + Fn(&A) -> u64,
+        H: 'static + Send + Sync + Fn(A) -> R,
+    {
+        self.insert(
+            name,
+            TypedHostQuery {
+                cost,
+                handler,
+                _marker: PhantomData,
+            },
+        );
+    }
+
+    /// Removes the query with the given `name`, returning whether there was
+    /// one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.map.remove(name).is_some()
+    }
+
+    /// Returns the query with the given `name`, shared so it may be executed
+    /// while the session is borrowed otherwise.
+    pub(crate) fn get_shared(&self, name: &str) -> Option<Arc<dyn HostQuery>> {
+        self.map.get(name).cloned()
+    }
+
+    /// Returns the names of the registered queries, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.map.keys().map(|name| name.as_ref())
+    }
+
+    /// Returns the names of the registered queries, in order, together with
+    /// their signatures.
+    pub fn iter(
+        &self,
+    ) -> impl Iterator<Item = (&str, Option<HostQuerySignature>)> {
+        self.map
+            .iter()
+            .map(|(name, query)| (name.as_ref(), query.signature()))
     }
 }
 
@@ -327,6 +1184,115 @@ pub trait HostQuery: Send + Sync {
     ///
     /// [`deserialize_and_price`]: HostQuery::deserialize_and_price
     fn execute(&self, arg: &Box<dyn Any>, arg_buf: &mut [u8]) -> u32;
+
+    /// The types of the argument and return of the query, if declared.
+    fn signature(&self) -> Option<HostQuerySignature> {
+        None
+    }
+}
+
+/// The types of the argument and return of a host query.
+///
+/// The types are given by their names, as returned by
+/// [`std::any::type_name`], and are meant for introspection only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostQuerySignature {
+    /// The name of the type of the argument.
+    pub arg: &'static str,
+    /// The name of the type of the return.
+    pub ret: &'static str,
+}
+
+/// A host query charged for the length of its argument, registered using
+/// [`VM::register_priced_host_query`].
+struct PricedHostQuery<P, Q> {
+    price: P,
+    query: Q,
+}
+
+impl<P, Q> HostQuery for PricedHostQuery<P, Q>
+where
+    P: Send + Sync + Fn(usize) -> u64,
+    Q: HostQuery,
+{
+    fn deserialize_and_price(
+        &self,
+        arg_buf: &[u8],
+        arg: &mut Box<dyn Any>,
+    ) -> u64 {
+        let price = (self.price)(arg_buf.len());
+        price.saturating_add(self.query.deserialize_and_price(arg_buf, arg))
+    }
+
+    fn execute(&self, arg: &Box<dyn Any>, arg_buf: &mut [u8]) -> u32 {
+        self.query.execute(arg, arg_buf)
+    }
+
+    fn signature(&self) -> Option<HostQuerySignature> {
+        self.query.signature()
+    }
+}
+
+/// A host query with a typed argument and return, registered using
+/// [`VM::register_typed_host_query`].
+struct TypedHostQuery<A, R, C, H> {
+    cost: C,
+    handler: H,
+    _marker: PhantomData<fn(A) -> R>,
+}
+
+impl<A, R, C, H> HostQuery for TypedHostQuery<A, R, C, H>
+where
+    A: 'static + Archive,
+    A::Archived:
+        Deserialize<A, Infallible> + for<'b> CheckBytes<DefaultValidator<'b>>,
+    R: 'static + for<'a> Serialize<StandardBufSerializer<'a>>,
+    C: Send + Sync + Fn(&A) -> u64,
+    H: Send + Sync + Fn(A) -> R,
+{
+    fn deserialize_and_price(
+        &self,
+        arg_buf: &[u8],
+        arg: &mut Box<dyn Any>,
+    ) -> u64 {
+        let ta = match check_archived_root::<A>(arg_buf) {
+            Ok(ta) => ta,
+            Err(_) => return u64::MAX,
+        };
+        let a: A = match ta.deserialize(&mut Infallible) {
+            Ok(a) => a,
+            Err(_) => return u64::MAX,
+        };
+
+        let cost = (self.cost)(&a);
+        *arg = Box::new(RefCell::new(Some(a)));
+        cost
+    }
+
+    fn execute(&self, arg: &Box<dyn Any>, arg_buf: &mut [u8]) -> u32 {
+        let a = arg
+            .downcast_ref::<RefCell<Option<A>>>()
+            .and_then(|a| a.borrow_mut().take())
+            .expect("The argument should have been deserialized");
+
+        let ret = (self.handler)(a);
+
+        let mut sbuf = [0u8; SCRATCH_BUF_BYTES];
+        let scratch = BufferScratch::new(&mut sbuf);
+        let ser = BufferSerializer::new(arg_buf);
+        let mut ser = CompositeSerializer::new(ser, scratch, Infallible);
+
+        ser.serialize_value(&ret)
+            .expect("The return should fit in the argument buffer");
+        ser.pos() as u32
+    }
+
+    fn signature(&self) -> Option<HostQuerySignature> {
+        Some(HostQuerySignature {
+            arg: type_name::<A>(),
+            ret: type_name::<R>(),
+        })
+    }
 }
 
 /// An implementer of `Fn(&mut [u8], u32) -> u32` can be used as a `HostQuery`,
@@ -0,0 +1,241 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractError, Error, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+/// The value returned by `read_value` in the 32-bit contract.
+const VALUE_32: i64 = 0x32;
+
+/// Appends a vector of the given items, all short enough for their lengths to
+/// be encoded in a single byte.
+fn push_vec(bytes: &mut Vec<u8>, items: &[&[u8]]) {
+    bytes.push(items.len() as u8);
+    for item in items {
+        bytes.extend_from_slice(item);
+    }
+}
+
+fn push_section(bytes: &mut Vec<u8>, id: u8, items: &[&[u8]]) {
+    let mut content = Vec::new();
+    push_vec(&mut content, items);
+
+    assert!(content.len() < 0x80);
+    bytes.push(id);
+    bytes.push(content.len() as u8);
+    bytes.extend(content);
+}
+
+fn name(name: &str) -> Vec<u8> {
+    let mut bytes = vec![name.len() as u8];
+    bytes.extend_from_slice(name.as_bytes());
+    bytes
+}
+
+fn export(export_name: &str, kind: u8, index: u8) -> Vec<u8> {
+    let mut bytes = name(export_name);
+    bytes.extend([kind, index]);
+    bytes
+}
+
+fn body(code: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![code.len() as u8 + 1, 0x00];
+    bytes.extend_from_slice(code);
+    bytes
+}
+
+/// Assembles a 32-bit contract importing `c` to call other contracts.
+///
+/// It has two functions: `read_value` returns [`VALUE_32`] as an `i64`, just
+/// like the `read_value` of the counter contract, while `forward` takes the ID
+/// of a contract and returns whatever calling `read_value` on it returns.
+fn contract_32() -> Vec<u8> {
+    let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+
+    push_section(
+        &mut bytes,
+        1,
+        &[
+            // (func (param i32) (result i32))
+            &[0x60, 0x01, 0x7f, 0x01, 0x7f],
+            // (func (param i32 i32 i32 i32 i64) (result i32)), of `c`
+            &[0x60, 0x05, 0x7f, 0x7f, 0x7f, 0x7f, 0x7e, 0x01, 0x7f],
+        ],
+    );
+    let mut import_c = name("env");
+    import_c.extend(name("c"));
+    import_c.extend([0x00, 0x01]);
+    push_section(&mut bytes, 2, &[&import_c]);
+    push_section(&mut bytes, 3, &[&[0x00], &[0x00]]);
+    // Two pages of memory, the first for the argument buffer
+    push_section(&mut bytes, 5, &[&[0x00, 0x02]]);
+    // (global i32 (i32.const 0)), pointing at the argument buffer
+    push_section(&mut bytes, 6, &[&[0x7f, 0x00, 0x41, 0x00, 0x0b]]);
+    push_section(
+        &mut bytes,
+        7,
+        &[
+            &export("memory", 0x02, 0),
+            &export("A", 0x03, 0),
+            &export("read_value", 0x00, 1),
+            &export("forward", 0x00, 2),
+        ],
+    );
+    push_section(
+        &mut bytes,
+        10,
+        &[
+            &body(&[
+                0x41, 0x00, // i32.const 0
+                0x42, 0x32, // i64.const VALUE_32
+                0x37, 0x03, 0x00, // i64.store
+                0x41, 0x08, // i32.const 8
+                0x0b, // end
+            ]),
+            &body(&[
+                0x41, 0x00, // i32.const 0, the callee in the argument
+                0x41, 0x80, 0x80, 0x04, // i32.const 0x10000, the name
+                0x41, 0x0a, // i32.const 10, the length of the name
+                0x41, 0x00, // i32.const 0, the length of the argument
+                0x42, 0x00, // i64.const 0, all gas left
+                0x10, 0x00, // call $c
+                0x0b, // end
+            ]),
+        ],
+    );
+    // "read_value", at the start of the second page
+    let mut data = vec![0x00, 0x41, 0x80, 0x80, 0x04, 0x0b];
+    data.extend(name("read_value"));
+    push_section(&mut bytes, 11, &[&data]);
+
+    bytes
+}
+
+#[test]
+fn call_64_from_32() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let id_32 = session.deploy(
+        &contract_32(),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let value: i64 = session.call(id_32, "forward", &counter_id, LIMIT)?.data;
+    assert_eq!(value, 0xfc);
+
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    let value: i64 = session.call(id_32, "forward", &counter_id, LIMIT)?.data;
+    assert_eq!(value, 0xfd);
+
+    Ok(())
+}
+
+#[test]
+fn call_32_from_64() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let id_32 = session.deploy(
+        &contract_32(),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let value: i64 = session
+        .call(center_id, "query_counter", &id_32, LIMIT)?
+        .data;
+    assert_eq!(value, VALUE_32);
+
+    Ok(())
+}
+
+#[test]
+fn call_through_both_widths() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let id_32 = session.deploy(
+        &contract_32(),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    // 64-bit, to 32-bit, to 64-bit, with the argument and the return passed
+    // through argument buffers of both widths
+    let forward = (
+        id_32,
+        String::from("forward"),
+        counter_id.as_bytes().to_vec(),
+    );
+    let res = session
+        .call::<_, Result<Vec<u8>, ContractError>>(
+            center_id,
+            "delegate_query",
+            &forward,
+            LIMIT,
+        )?
+        .data
+        .expect("ICC should succeed");
+
+    let value: i64 =
+        rkyv::from_bytes(&res).expect("Deserialization to succeed");
+    assert_eq!(value, 0xfc);
+
+    Ok(())
+}
+
+#[test]
+fn call_across_widths_after_commit() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let id_32 = session.deploy(
+        &contract_32(),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    // The width of each contract is detected again when it is loaded from
+    // the commit
+    let root = session.commit()?;
+    let mut session = vm.session(SessionData::builder().base(root))?;
+
+    let value: i64 = session.call(id_32, "forward", &counter_id, LIMIT)?.data;
+    assert_eq!(value, 0xfc);
+
+    Ok(())
+}
@@ -0,0 +1,197 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractError, Error, SessionData, VM,
+};
+use piecrust_uplink::{ARGBUF_LEN, MAX_ARGBUF_LEN, MIN_ARGBUF_LEN};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+/// The size of the argument buffer `small_argbuf` is built with.
+const SMALL_ARGBUF_LEN: usize = 4096;
+
+/// Returns a module with the argument buffer at the start of its memory,
+/// declaring its size with the given little endian bytes.
+fn module_declaring(len_bytes: [u8; 4]) -> Vec<u8> {
+    let mut module = vec![
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x05, 0x03, 0x01, 0x00, 0x02, // memory section
+        0x06, 0x0b, 0x02, 0x7f, 0x00, 0x41, 0x00, 0x0b, 0x7f, 0x00, 0x41, 0x10,
+        0x0b, // global section
+        0x07, 0x13, 0x03, 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00,
+        0x01, b'A', 0x03, 0x00, 0x02, b'A', b'L', 0x03,
+        0x01, // export section
+        0x0b, 0x0a, 0x01, 0x00, 0x41, 0x10, 0x0b, 0x04, // data section
+    ];
+    module.extend(len_bytes);
+    module
+}
+
+/// A module whose functions return lengths without writing anything to its
+/// argument buffer. `overflow` returns 1000000, more than the buffer holds,
+/// and `large` returns 8192.
+const MISBEHAVING: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+    0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type section
+    0x03, 0x03, 0x02, 0x00, 0x00, // function section
+    0x05, 0x03, 0x01, 0x00, 0x02, // memory section
+    0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x00, 0x0b, // global section
+    0x07, 0x21, 0x04, 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00,
+    0x01, b'A', 0x03, 0x00, 0x08, b'o', b'v', b'e', b'r', b'f', b'l', b'o',
+    b'w', 0x00, 0x00, 0x05, b'l', b'a', b'r', b'g', b'e', 0x00,
+    0x01, // export section
+    0x0a, 0x0f, 0x02, 0x06, 0x00, 0x41, 0xc0, 0x84, 0x3d, 0x0b, 0x06, 0x00,
+    0x41, 0x80, 0xc0, 0x00, 0x0b, // code section
+];
+
+#[test]
+fn declared_arg_buf_len() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let default_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    // Built with `PIECRUST_ARGBUF_LEN`, and stripped like every other contract
+    let small_id = session.deploy(
+        contract_bytecode!("small_argbuf"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let len = session
+        .call::<_, u64>(small_id, "arg_buf_len", &(), LIMIT)?
+        .data;
+    assert_eq!(len as usize, SMALL_ARGBUF_LEN);
+
+    let too_large = vec![0u8; SMALL_ARGBUF_LEN + 1];
+    session.call_raw(default_id, "read_value", too_large.clone(), LIMIT)?;
+    session
+        .call_raw(small_id, "echo", too_large, LIMIT)
+        .expect_err("Argument should not fit the declared buffer");
+
+    let bytes = vec![0xab; SMALL_ARGBUF_LEN / 2];
+    let echoed = session
+        .call::<_, Vec<u8>>(small_id, "echo", &bytes, LIMIT)?
+        .data;
+    assert_eq!(echoed, bytes);
+
+    Ok(())
+}
+
+#[test]
+fn invalid_arg_buf_len() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    session.deploy(
+        &module_declaring((MIN_ARGBUF_LEN as u32).to_le_bytes()),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let too_small = (MIN_ARGBUF_LEN as u32 - 8).to_le_bytes();
+    let too_large = (MAX_ARGBUF_LEN as u32 + 8).to_le_bytes();
+    let unaligned = (ARGBUF_LEN as u32 + 1).to_le_bytes();
+
+    for len_bytes in [too_small, too_large, unaligned] {
+        let err = session
+            .deploy(
+                &module_declaring(len_bytes),
+                ContractData::builder().owner(OWNER),
+                LIMIT,
+            )
+            .expect_err("Deploying an invalid declaration should fail");
+        assert!(matches!(err, Error::InvalidArgumentBuffer));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn return_overflow() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let small_id = session.deploy(
+        contract_bytecode!("small_argbuf"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let bad_id = session.deploy(
+        MISBEHAVING,
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    type Ret = Result<Vec<u8>, ContractError>;
+
+    // Returning more than the callee's own buffer holds
+    let ret = session
+        .call::<_, Ret>(
+            center_id,
+            "delegate_query",
+            &(bad_id, String::from("overflow"), Vec::<u8>::new()),
+            LIMIT,
+        )?
+        .data;
+    match ret {
+        Err(ContractError::ReturnOverflow {
+            contract,
+            len,
+            max_len,
+        }) => {
+            assert_eq!(contract, bad_id);
+            assert_eq!(len, 1_000_000);
+            assert_eq!(max_len as usize, ARGBUF_LEN);
+        }
+        ret => panic!("Expected a return overflow, got {ret:?}"),
+    }
+
+    // Returning more than the caller's buffer holds
+    let ret = session
+        .call::<_, Ret>(
+            small_id,
+            "delegate_query",
+            &(bad_id, String::from("large"), Vec::<u8>::new()),
+            LIMIT,
+        )?
+        .data;
+    match ret {
+        Err(ContractError::ReturnOverflow {
+            contract,
+            len,
+            max_len,
+        }) => {
+            assert_eq!(contract, bad_id);
+            assert_eq!(len, 8192);
+            assert_eq!(max_len as usize, SMALL_ARGBUF_LEN);
+        }
+        ret => panic!("Expected a return overflow, got {ret:?}"),
+    }
+
+    // The same return fits a caller with the default buffer
+    let ret = session
+        .call::<_, Ret>(
+            center_id,
+            "delegate_query",
+            &(bad_id, String::from("large"), Vec::<u8>::new()),
+            LIMIT,
+        )?
+        .data;
+    assert_eq!(ret.expect("Return should fit").len(), 8192);
+
+    Ok(())
+}
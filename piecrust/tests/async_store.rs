@@ -0,0 +1,91 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+const COUNTER_ID: ContractId = ContractId::from_bytes([1; 32]);
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Drives a future to completion on the current thread, parking it while the
+/// future is pending.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn async_commit_and_delete() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    block_on(async {
+        let mut session = vm.session_async(SessionData::builder()).await?;
+        session.deploy(
+            contract_bytecode!("counter"),
+            ContractData::builder().owner(OWNER).contract_id(COUNTER_ID),
+            LIMIT,
+        )?;
+        let root = session.commit_async().await?;
+
+        assert_eq!(vm.commits_async().await, vec![root]);
+
+        let mut session =
+            vm.session_async(SessionData::builder().base(root)).await?;
+        session.call::<_, ()>(COUNTER_ID, "increment", &(), LIMIT)?;
+        let next_root = session.commit_async().await?;
+
+        assert_eq!(vm.commits().len(), 2);
+
+        let mut session = vm
+            .session_async(SessionData::builder().base(next_root))
+            .await?;
+        assert_eq!(
+            session
+                .call::<_, i64>(COUNTER_ID, "read_value", &(), LIMIT)?
+                .data,
+            0xfd
+        );
+        drop(session);
+
+        vm.delete_commit_async(root).await?;
+        assert_eq!(vm.commits_async().await, vec![next_root]);
+
+        assert!(
+            vm.session_async(SessionData::builder().base(root))
+                .await
+                .is_err(),
+            "Sessions should not be opened on a deleted commit"
+        );
+
+        Ok(())
+    })
+}
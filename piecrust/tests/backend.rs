@@ -0,0 +1,211 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, FsBackend,
+    MemoryBackend, SessionData, StorageBackend, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn archive_to_memory() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    let root = session.commit()?;
+
+    let mut backend = MemoryBackend::new();
+    vm.archive_commit(root, &mut backend)?;
+
+    assert_eq!(backend.commits(), [root]);
+    assert_eq!(backend.contracts(root), [id]);
+    assert_eq!(
+        backend.bytecode(id),
+        Some(&contract_bytecode!("counter")[..])
+    );
+    assert!(backend.element(root, id).is_some());
+    assert!(!backend.page_indices(root, id).is_empty());
+    assert_eq!(
+        backend.instruction_costs(root),
+        Some(vm.environment().instruction_costs_hash)
+    );
+
+    backend
+        .delete(root.into())
+        .expect("Deleting from memory works");
+    assert!(backend.commits().is_empty());
+    assert!(backend.page_indices(root, id).is_empty());
+    assert!(backend.instruction_costs(root).is_none());
+    assert!(
+        backend.bytecode(id).is_some(),
+        "Bytecode is shared between commits"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn archive_and_restore() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    let root = session.commit()?;
+
+    let archive_dir = tempfile::tempdir().expect("Creating a tempdir works");
+    let mut backend = FsBackend::new(archive_dir.path());
+    vm.archive_commit(root, &mut backend)?;
+
+    // A VM reading the archive finds the commit whole
+    let vm = VM::new(archive_dir.path())?;
+    assert_eq!(vm.commits(), [root]);
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let value = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert_eq!(value.data, 0xfd);
+
+    Ok(())
+}
+
+/// A [`MemoryBackend`] that can still be inspected once handed to a VM.
+#[derive(Clone, Default)]
+struct SharedBackend(Arc<Mutex<MemoryBackend>>);
+
+impl StorageBackend for SharedBackend {
+    fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    fn join(&mut self, _fork: Self) {}
+
+    fn remove_partial(&mut self, commit: [u8; 32], contracts: &[ContractId]) {
+        self.0.lock().unwrap().remove_partial(commit, contracts)
+    }
+
+    fn put_bytecode(
+        &mut self,
+        contract: ContractId,
+        bytecode: &[u8],
+        module: &[u8],
+        metadata: &[u8],
+    ) -> io::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .put_bytecode(contract, bytecode, module, metadata)
+    }
+
+    fn put_memory_page(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+        page_index: usize,
+        page: &[u8],
+    ) -> io::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .put_memory_page(commit, contract, page_index, page)
+    }
+
+    fn put_element(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+        element: &[u8],
+    ) -> io::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .put_element(commit, contract, element)
+    }
+
+    fn put_removal(
+        &mut self,
+        commit: [u8; 32],
+        contract: ContractId,
+    ) -> io::Result<()> {
+        self.0.lock().unwrap().put_removal(commit, contract)
+    }
+
+    fn put_instruction_costs(
+        &mut self,
+        commit: [u8; 32],
+        hash: [u8; 32],
+    ) -> io::Result<()> {
+        self.0.lock().unwrap().put_instruction_costs(commit, hash)
+    }
+
+    fn link_base(
+        &mut self,
+        commit: [u8; 32],
+        base_info: &[u8],
+        tree_pos: &[u8],
+    ) -> io::Result<()> {
+        self.0
+            .lock()
+            .unwrap()
+            .link_base(commit, base_info, tree_pos)
+    }
+
+    fn delete(&mut self, commit: [u8; 32]) -> io::Result<()> {
+        self.0.lock().unwrap().delete(commit)
+    }
+}
+
+#[test]
+fn commit_to_memory() -> Result<(), Error> {
+    let backend = SharedBackend::default();
+
+    let tmp = tempfile::tempdir().expect("Creating a tempdir works");
+    let vm = VM::with_backend(tmp.path(), backend.clone())?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    let root = session.commit()?;
+
+    {
+        let backend = backend.0.lock().unwrap();
+        assert_eq!(backend.commits(), [root]);
+        assert_eq!(backend.contracts(root), [id]);
+        assert_eq!(
+            backend.bytecode(id),
+            Some(&contract_bytecode!("counter")[..])
+        );
+        assert!(!backend.page_indices(root, id).is_empty());
+    }
+    assert_eq!(vm.commits(), [root]);
+
+    // Nothing of the commit is written to the directory
+    let commit_dir = vm.root_dir().join("main").join(hex::encode(root));
+    assert!(!commit_dir.exists());
+
+    vm.delete_commit(root)?;
+    assert!(backend.0.lock().unwrap().commits().is_empty());
+
+    Ok(())
+}
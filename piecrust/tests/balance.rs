@@ -0,0 +1,168 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractError, ContractId, Error,
+    GasSchedule, Session, SessionData, TransferError, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+fn deploy(session: &mut Session) -> Result<(ContractId, ContractId), Error> {
+    let vault_id = session.deploy(
+        contract_bytecode!("vault"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let other_id = session.deploy(
+        contract_bytecode!("vault"),
+        ContractData::builder().owner(OWNER).nonce(1),
+        LIMIT,
+    )?;
+    Ok((vault_id, other_id))
+}
+
+fn transfer(
+    session: &mut Session,
+    from: ContractId,
+    to: ContractId,
+    amount: u64,
+) -> Result<Result<(), TransferError>, Error> {
+    Ok(session
+        .call::<_, Result<(), TransferError>>(
+            from,
+            "transfer",
+            &(to, amount),
+            LIMIT,
+        )?
+        .data)
+}
+
+#[test]
+fn transfer_between_contracts() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let (vault_id, other_id) = deploy(&mut session)?;
+
+    session.set_balance(vault_id, 100);
+    assert_eq!(
+        session
+            .call::<_, u64>(vault_id, "balance", &(), LIMIT)?
+            .data,
+        100
+    );
+
+    transfer(&mut session, vault_id, other_id, 30)?
+        .expect("The transfer should succeed");
+    assert_eq!(session.balance(&vault_id), 70);
+    assert_eq!(session.balance(&other_id), 30);
+    assert_eq!(
+        session
+            .call::<_, u64>(other_id, "balance", &(), LIMIT)?
+            .data,
+        30
+    );
+
+    assert_eq!(
+        transfer(&mut session, other_id, vault_id, 31)?,
+        Err(TransferError::InsufficientBalance)
+    );
+    assert_eq!(
+        transfer(&mut session, vault_id, ContractId::from_bytes([1; 32]), 1)?,
+        Err(TransferError::RecipientDoesNotExist)
+    );
+
+    let balances: Vec<_> = session.balances().collect();
+    assert_eq!(balances.len(), 2);
+    assert!(balances.contains(&(&vault_id, 70)));
+    assert!(balances.contains(&(&other_id, 30)));
+
+    Ok(())
+}
+
+#[test]
+fn transfer_undone_on_failure() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let (vault_id, other_id) = deploy(&mut session)?;
+
+    session.set_balance(vault_id, 100);
+
+    session
+        .call::<_, ()>(
+            vault_id,
+            "transfer_and_panic",
+            &(other_id, 10u64),
+            LIMIT,
+        )
+        .expect_err("The call should panic");
+    assert_eq!(session.balance(&vault_id), 100);
+    assert_eq!(session.balance(&other_id), 0);
+
+    // A failed inter-contract call undoes only the transfers of the callee
+    session.set_balance(other_id, 50);
+    let result = session
+        .call::<_, Result<(), ContractError>>(
+            vault_id,
+            "call_transfer_and_panic",
+            &(other_id, vault_id, 20u64),
+            LIMIT,
+        )?
+        .data;
+    assert!(matches!(result, Err(ContractError::Panic(_))));
+    assert_eq!(session.balance(&vault_id), 100);
+    assert_eq!(session.balance(&other_id), 50);
+
+    let checkpoint = session.checkpoint()?;
+    transfer(&mut session, vault_id, other_id, 100)?
+        .expect("The transfer should succeed");
+    session.revert_to(checkpoint)?;
+    assert_eq!(session.balance(&vault_id), 100);
+    assert_eq!(session.balance(&other_id), 50);
+
+    Ok(())
+}
+
+#[test]
+fn transfer_cost() -> Result<(), Error> {
+    const COST: u64 = 1000;
+
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let (vault_id, other_id) = deploy(&mut session)?;
+    session.set_balance(vault_id, 100);
+
+    let base_spent = session
+        .call::<_, Result<(), TransferError>>(
+            vault_id,
+            "transfer",
+            &(other_id, 1u64),
+            LIMIT,
+        )?
+        .gas_spent;
+
+    let schedule = GasSchedule {
+        transfer_cost: COST,
+        ..GasSchedule::default()
+    };
+    let mut session =
+        vm.session(SessionData::builder().gas_schedule(schedule))?;
+    let (vault_id, other_id) = deploy(&mut session)?;
+    session.set_balance(vault_id, 100);
+
+    let spent = session
+        .call::<_, Result<(), TransferError>>(
+            vault_id,
+            "transfer",
+            &(other_id, 1u64),
+            LIMIT,
+        )?
+        .gas_spent;
+    assert_eq!(spent, base_spent + COST);
+
+    Ok(())
+}
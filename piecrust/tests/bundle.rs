@@ -0,0 +1,86 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, DeploySpec, Error,
+    SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+const COUNTER_ID: ContractId = ContractId::from_bytes([1; 32]);
+const FORWARDER_ID: ContractId = ContractId::from_bytes([2; 32]);
+
+#[test]
+fn bundle_with_placeholders() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let ids = session.deploy_bundle(vec![
+        DeploySpec::new(contract_bytecode!("counter"), OWNER, LIMIT),
+        DeploySpec::new(contract_bytecode!("forwarder"), OWNER, LIMIT)
+            .init_arg(DeploySpec::placeholder(0).to_bytes()),
+    ])?;
+    assert_eq!(ids.len(), 2);
+
+    // The forwarder incremented the counter when it was initialized
+    let value = session.call::<_, i64>(ids[1], "read_value", &(), LIMIT)?;
+    assert_eq!(value.data, 0xfd);
+    let value = session.call::<_, i64>(ids[0], "read_value", &(), LIMIT)?;
+    assert_eq!(value.data, 0xfd);
+
+    Ok(())
+}
+
+#[test]
+fn bundle_all_or_nothing() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(COUNTER_ID),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+
+    // The forwarder increments the existing counter when initialized, but
+    // deploying another counter with the same ID fails
+    session
+        .deploy_bundle(vec![
+            DeploySpec::new(contract_bytecode!("forwarder"), OWNER, LIMIT)
+                .contract_id(FORWARDER_ID)
+                .init_arg(COUNTER_ID.to_bytes()),
+            DeploySpec::new(contract_bytecode!("counter"), OWNER, LIMIT)
+                .contract_id(COUNTER_ID),
+        ])
+        .expect_err("Deploying the bundle should fail");
+
+    assert!(
+        session
+            .call::<_, i64>(FORWARDER_ID, "read_value", &(), LIMIT)
+            .is_err(),
+        "The forwarder should not be deployed"
+    );
+    let value = session.call::<_, i64>(COUNTER_ID, "read_value", &(), LIMIT)?;
+    assert_eq!(value.data, 0xfc, "The increment should be undone");
+
+    // The session is still usable after the failed bundle
+    let forwarder =
+        DeploySpec::new(contract_bytecode!("forwarder"), OWNER, LIMIT)
+            .contract_id(FORWARDER_ID)
+            .init_arg(COUNTER_ID.to_bytes());
+    session.deploy_bundle(vec![forwarder])?;
+
+    let value =
+        session.call::<_, i64>(FORWARDER_ID, "read_value", &(), LIMIT)?;
+    assert_eq!(value.data, 0xfd);
+
+    Ok(())
+}
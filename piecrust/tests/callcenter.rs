@@ -42,6 +42,40 @@ pub fn cc_read_counter() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+pub fn cc_multi_call() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+
+    let missing_id = ContractId::from_bytes([0xff; 32]);
+    let counter_ids = vec![counter_id, missing_id, counter_id];
+
+    let values: Vec<Result<i64, ContractError>> = session
+        .call(center_id, "query_counters", &counter_ids, LIMIT)?
+        .data;
+
+    assert_eq!(values.len(), 3);
+    assert!(matches!(values[0], Ok(0xfd)));
+    assert!(matches!(values[1], Err(ContractError::DoesNotExist)));
+    assert!(matches!(values[2], Ok(0xfd)));
+
+    Ok(())
+}
+
 #[test]
 pub fn cc_direct() -> Result<(), Error> {
     let vm = VM::ephemeral()?;
@@ -302,6 +336,55 @@ pub fn cc_callstack() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+pub fn cc_callstack_frames() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let frames: Vec<(ContractId, String, u64)> = session
+        .call(center_id, "return_callstack_frames", &(), LIMIT)?
+        .data;
+    assert_eq!(
+        frames,
+        vec![(center_id, String::from("return_callstack_frames"), LIMIT)]
+    );
+
+    let res = session
+        .call::<_, Result<Vec<u8>, ContractError>>(
+            center_id,
+            "delegate_query",
+            &(
+                center_id,
+                String::from("return_callstack_frames"),
+                Vec::<u8>::new(),
+            ),
+            LIMIT,
+        )?
+        .data
+        .expect("ICC should succeed");
+
+    let frames: Vec<(ContractId, String, u64)> =
+        rkyv::from_bytes(&res).expect("Deserialization to succeed");
+
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].0, center_id);
+    assert_eq!(frames[0].1, "return_callstack_frames");
+    assert!(frames[0].2 < LIMIT, "The callee gets part of the gas");
+    assert_eq!(
+        frames[1],
+        (center_id, String::from("delegate_query"), LIMIT)
+    );
+
+    Ok(())
+}
+
 #[test]
 pub fn cc_self_id() -> Result<(), Error> {
     let vm = VM::ephemeral()?;
@@ -0,0 +1,131 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{contract_bytecode, ContractData, Error, SessionData, VM};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn nested_checkpoints() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let outer = session.checkpoint()?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+
+    let inner = session.checkpoint()?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    assert_eq!(
+        session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data,
+        0xfe
+    );
+
+    session.revert_to(inner)?;
+    assert_eq!(
+        session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data,
+        0xfd
+    );
+
+    // The changes made before a checkpoint in place are part of the root
+    let root = session.root();
+    session.release(outer)?;
+    assert_eq!(session.root(), root);
+
+    match session.revert_to(inner) {
+        Err(Error::UnknownCheckpoint(checkpoint)) => {
+            assert_eq!(checkpoint, inner)
+        }
+        res => panic!("Reverting twice should fail: {res:?}"),
+    }
+
+    let root = session.commit()?;
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(
+        session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data,
+        0xfd
+    );
+
+    Ok(())
+}
+
+#[test]
+fn revert_deployments() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.root();
+
+    let checkpoint = session.checkpoint()?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    let box_id = session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(box_id, "set", &0x11i16, LIMIT)?;
+    assert_eq!(session.host_events().len(), 2);
+
+    session.revert_to(checkpoint)?;
+
+    assert_eq!(session.root(), root);
+    assert_eq!(session.host_events().len(), 1);
+    session
+        .call::<_, Option<i16>>(box_id, "get", &(), LIMIT)
+        .expect_err("The contract deployed should be gone");
+
+    Ok(())
+}
+
+#[test]
+fn simulate() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let eventer_id = session.deploy(
+        contract_bytecode!("eventer"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.root();
+
+    let receipt =
+        session.simulate::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    assert!(receipt.gas_spent > 0);
+    assert_eq!(session.root(), root);
+    assert_eq!(
+        session
+            .call::<_, i64>(counter_id, "read_value", &(), LIMIT)?
+            .data,
+        0xfc
+    );
+
+    // Calls write their return to the argument buffer, changing the root
+    let root = session.root();
+
+    let receipt =
+        session.simulate::<_, ()>(eventer_id, "emit_events", &4u32, LIMIT)?;
+    assert_eq!(receipt.events.len(), 4);
+    assert_eq!(session.root(), root);
+
+    Ok(())
+}
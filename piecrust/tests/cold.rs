@@ -0,0 +1,138 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fs;
+use std::path::Path;
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, Root, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+const COUNTER_ID: ContractId = ContractId::from_bytes([1; 32]);
+
+/// Deploys the counter, and increments it in a second commit on top of the
+/// first. Returns the roots of both commits.
+fn two_commits(vm: &VM) -> Result<(Root, Root), Error> {
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(COUNTER_ID),
+        LIMIT,
+    )?;
+    let first = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(first))?;
+    session.call::<_, ()>(COUNTER_ID, "increment", &(), LIMIT)?;
+    let second = session.commit()?;
+
+    Ok((first, second))
+}
+
+fn read_value(vm: &VM, root: Root) -> Result<i64, Error> {
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    Ok(session
+        .call::<_, i64>(COUNTER_ID, "read_value", &(), LIMIT)?
+        .data)
+}
+
+fn is_empty_dir(dir: &Path) -> bool {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries.flatten().all(|entry| {
+                entry.path().is_dir() && is_empty_dir(&entry.path())
+            })
+        })
+        .unwrap_or(true)
+}
+
+#[test]
+fn cool_and_warm() -> Result<(), Error> {
+    let cold_dir = tempfile::tempdir().expect("Creating a tempdir works");
+
+    let vm = VM::ephemeral()?;
+    let (first, second) = two_commits(&vm)?;
+
+    assert!(
+        vm.cool_commit(first).is_err(),
+        "Cooling should fail without a cold directory"
+    );
+
+    vm.set_cold_dir(cold_dir.path());
+    vm.cool_commit(first)?;
+    vm.cool_commit(second)?;
+
+    assert!(vm.is_commit_cold(first)?);
+    assert!(vm.is_commit_cold(second)?);
+    assert!(!is_empty_dir(cold_dir.path()));
+
+    assert_eq!(read_value(&vm, first)?, 0xfc);
+    assert_eq!(read_value(&vm, second)?, 0xfd);
+
+    // Cold commits are found when the VM is restarted
+    let vm = VM::new(vm.root_dir())?;
+    assert_eq!(vm.commits().len(), 2);
+    assert_eq!(read_value(&vm, second)?, 0xfd);
+
+    vm.warm_commit(first)?;
+    vm.warm_commit(second)?;
+
+    assert!(!vm.is_commit_cold(first)?);
+    assert!(!vm.is_commit_cold(second)?);
+    assert!(is_empty_dir(cold_dir.path()));
+
+    assert_eq!(read_value(&vm, first)?, 0xfc);
+    assert_eq!(read_value(&vm, second)?, 0xfd);
+
+    Ok(())
+}
+
+#[test]
+fn cool_in_use() -> Result<(), Error> {
+    let cold_dir = tempfile::tempdir().expect("Creating a tempdir works");
+
+    let vm = VM::ephemeral()?;
+    vm.set_cold_dir(cold_dir.path());
+
+    let (first, _) = two_commits(&vm)?;
+
+    let session = vm.session(SessionData::builder().base(first))?;
+    assert!(
+        vm.cool_commit(first).is_err(),
+        "Cooling a commit in use should fail"
+    );
+    drop(session);
+
+    vm.cool_commit(first)?;
+    assert!(vm.is_commit_cold(first)?);
+
+    Ok(())
+}
+
+#[test]
+fn delete_and_finalize_cold() -> Result<(), Error> {
+    let cold_dir = tempfile::tempdir().expect("Creating a tempdir works");
+
+    let vm = VM::ephemeral()?;
+    vm.set_cold_dir(cold_dir.path());
+
+    let (first, second) = two_commits(&vm)?;
+    vm.cool_commit(first)?;
+    vm.cool_commit(second)?;
+
+    vm.delete_commit(second)?;
+    vm.finalize_commit(first)?;
+
+    assert!(vm.commits().is_empty());
+    assert!(
+        is_empty_dir(cold_dir.path()),
+        "Nothing should be left in the cold directory"
+    );
+
+    Ok(())
+}
@@ -5,7 +5,7 @@
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
 use piecrust::{
-    contract_bytecode, ContractData, Error, Session, SessionData, VM,
+    contract_bytecode, ContractData, Error, Root, Session, SessionData, VM,
 };
 use piecrust_uplink::ContractId;
 use std::thread;
@@ -297,7 +297,7 @@ fn increment_counter_and_commit(
     mut session: Session,
     id: ContractId,
     count: usize,
-) -> Result<[u8; 32], Error> {
+) -> Result<Root, Error> {
     for _ in 0..count {
         session.call::<(), ()>(id, "increment", &(), LIMIT)?;
     }
@@ -338,7 +338,7 @@ fn concurrent_sessions() -> Result<(), Error> {
         }));
     }
 
-    let mut roots: Vec<[u8; 32]> = threads
+    let mut roots: Vec<Root> = threads
         .into_iter()
         .map(|handle| {
             handle.join().unwrap().expect("Committing should succeed")
@@ -375,7 +375,7 @@ fn concurrent_sessions() -> Result<(), Error> {
         vm.delete_commit(root)?;
     }
 
-    let mut roots: Vec<[u8; 32]> = threads
+    let mut roots: Vec<Root> = threads
         .into_iter()
         .map(|handle| {
             handle.join().unwrap().expect("Committing should succeed")
@@ -423,3 +423,341 @@ fn session_move() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn session_move_between_calls() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    // Moving a session around between calls, including ones instantiating
+    // several contracts, used to be a common source of SIGSEGVs.
+    session.call::<_, ()>(
+        center_id,
+        "increment_counter",
+        &counter_id,
+        LIMIT,
+    )?;
+
+    let mut session = Box::new(session);
+    session.call::<_, ()>(
+        center_id,
+        "increment_counter",
+        &counter_id,
+        LIMIT,
+    )?;
+
+    let mut sessions = vec![*session];
+    sessions[0].call::<_, ()>(
+        center_id,
+        "increment_counter",
+        &counter_id,
+        LIMIT,
+    )?;
+    let mut session = sessions.pop().expect("There should be a session");
+
+    let mut session = thread::spawn(move || -> Result<Session, Error> {
+        session.call::<_, ()>(
+            center_id,
+            "increment_counter",
+            &counter_id,
+            LIMIT,
+        )?;
+        Ok(session)
+    })
+    .join()
+    .expect("The thread should not panic")?;
+
+    let value = session
+        .call::<_, i64>(center_id, "query_counter", &counter_id, LIMIT)?
+        .data;
+    assert_eq!(value, 0xfc + 4);
+
+    Ok(())
+}
+
+#[test]
+fn commit_refused_below_min_free_space() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    vm.set_min_free_space(u64::MAX);
+    let err = session.commit().expect_err("the disk can't be this large");
+    assert!(
+        matches!(err, Error::StoreFull(_)),
+        "unexpected error: {err}"
+    );
+    assert!(
+        vm.commits().is_empty(),
+        "Nothing should have been committed"
+    );
+
+    vm.set_min_free_space(0);
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+    assert_eq!(vm.commits(), vec![root]);
+
+    let vm = VM::new(vm.root_dir())?;
+    assert_eq!(vm.commits(), vec![root]);
+
+    Ok(())
+}
+
+#[test]
+fn commit_partial() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let box_id = session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let base = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(base))?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    session.call::<i16, ()>(box_id, "set", &0x11, LIMIT)?;
+    let scratch_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).nonce(1),
+        LIMIT,
+    )?;
+    let root = session.commit_partial(&[counter_id])?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(
+        session
+            .call::<_, i64>(counter_id, "read_value", &(), LIMIT)?
+            .data,
+        0xfd
+    );
+    assert_eq!(
+        session
+            .call::<_, Option<i16>>(box_id, "get", &(), LIMIT)?
+            .data,
+        None,
+        "Changes to the box should be discarded"
+    );
+    assert!(
+        session
+            .call::<_, i64>(scratch_id, "read_value", &(), LIMIT)
+            .is_err(),
+        "The scratch contract should not be committed"
+    );
+
+    let session = vm.session(SessionData::builder().base(root))?;
+    let err = session
+        .commit_partial(&[scratch_id])
+        .expect_err("Committing a missing contract should fail");
+    assert!(matches!(err, Error::ContractDoesNotExist(id) if id == scratch_id));
+
+    Ok(())
+}
+
+#[test]
+fn commit_many_contracts() -> Result<(), Error> {
+    // Enough contracts for the commit to be written by multiple threads
+    const N_CONTRACTS: u64 = 12;
+
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let mut ids = Vec::new();
+    for nonce in 0..N_CONTRACTS {
+        let id = session.deploy(
+            contract_bytecode!("counter"),
+            ContractData::builder().owner(OWNER).nonce(nonce),
+            LIMIT,
+        )?;
+        ids.push(id);
+    }
+    let base = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(base))?;
+    for (i, id) in ids.iter().enumerate() {
+        for _ in 0..i % 3 {
+            session.call::<_, ()>(*id, "increment", &(), LIMIT)?;
+        }
+    }
+    let root = session.root();
+    assert_eq!(session.commit()?, root);
+
+    // Every contract is read back as written, once the store is reloaded
+    let vm = VM::new(vm.root_dir())?;
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    for (i, id) in ids.iter().enumerate() {
+        assert_eq!(
+            session.call::<_, i64>(*id, "read_value", &(), LIMIT)?.data,
+            0xfc + (i % 3) as i64
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn sessions_sharing_base() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let base = session.commit()?;
+
+    // Both sessions load the contract off the same base, sharing its code
+    let mut session_1 = vm.session(SessionData::builder().base(base))?;
+    let mut session_2 = vm.session(SessionData::builder().base(base))?;
+
+    session_1.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    session_1.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    session_2.call::<_, ()>(id, "increment", &(), LIMIT)?;
+
+    assert_eq!(
+        session_1.call::<_, i64>(id, "read_value", &(), LIMIT)?.data,
+        0xfe
+    );
+    assert_eq!(
+        session_2.call::<_, i64>(id, "read_value", &(), LIMIT)?.data,
+        0xfd,
+        "The memories of the sessions should be independent"
+    );
+
+    let root_1 = session_1.commit()?;
+    let root_2 = session_2.commit()?;
+    assert_ne!(root_1, root_2);
+
+    Ok(())
+}
+
+#[test]
+fn commit_infos() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let base_root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(base_root))?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let diff_root = session.commit()?;
+
+    let commits = vm.commit_infos();
+    assert_eq!(commits.len(), 2);
+
+    let base = commits
+        .iter()
+        .find(|info| info.root == base_root)
+        .expect("The base commit should be listed");
+    assert!(!base.is_diff());
+    assert_eq!(base.parent, None);
+    assert_eq!(base.contracts, 1);
+    assert!(base.size > 0);
+    assert!(base.created.is_some());
+
+    let diff = commits
+        .iter()
+        .find(|info| info.root == diff_root)
+        .expect("The diff commit should be listed");
+    assert!(diff.is_diff());
+    assert_eq!(diff.parent, Some(base_root));
+    assert_eq!(diff.contracts, 2);
+    assert!(diff.size > 0);
+
+    Ok(())
+}
+
+#[test]
+fn delete_commit_with_dependents() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let base_root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(base_root))?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    let child_root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(child_root))?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    let grandchild_root = session.commit()?;
+
+    // Deploying the same contract in another genesis session would yield the
+    // base commit again, so the unrelated commit holds a different contract
+    let mut session = vm.session(SessionData::builder())?;
+    let box_id = session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(box_id, "set", &0x11i16, LIMIT)?;
+    let unrelated_root = session.commit()?;
+    assert_ne!(unrelated_root, base_root);
+
+    match vm.delete_commit_checked(base_root) {
+        Err(Error::CommitHasDependents(dependents)) => {
+            let mut dependents = dependents;
+            dependents.sort();
+            let mut expected = vec![child_root, grandchild_root];
+            expected.sort();
+            assert_eq!(dependents, expected);
+        }
+        res => panic!("Expected the commit to have dependents, got {res:?}"),
+    }
+    assert_eq!(vm.commits().len(), 4, "No commit should be deleted");
+
+    vm.delete_commit_checked(grandchild_root)?;
+    assert!(!vm.commits().contains(&grandchild_root));
+
+    vm.delete_commit_cascade(base_root)?;
+    assert_eq!(
+        vm.commits(),
+        vec![unrelated_root],
+        "The commit and its dependents should be deleted"
+    );
+
+    Ok(())
+}
@@ -109,3 +109,41 @@ fn increment_panic() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn increment_fallible() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let counter_id = session.deploy(
+        contract_bytecode!("fallible_counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    assert_eq!(
+        session
+            .call::<_, i64>(counter_id, "increment_below", &0xfdi64, LIMIT)?
+            .data,
+        0xfd
+    );
+
+    match session.call::<_, i64>(counter_id, "increment_below", &0xfdi64, LIMIT)
+    {
+        Err(Error::Panic(panic_msg)) => {
+            assert_eq!(panic_msg, String::from("Value over the maximum"));
+        }
+        _ => panic!("Expected a panic error"),
+    }
+
+    // The failed increment is undone
+    assert_eq!(
+        session
+            .call::<_, i64>(counter_id, "read_value", &(), LIMIT)?
+            .data,
+        0xfd
+    );
+
+    Ok(())
+}
@@ -4,7 +4,10 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
-use piecrust::{contract_bytecode, ContractData, Error, SessionData, VM};
+use piecrust::{
+    contract_bytecode, ContractData, DebugFrame, Debugger, Error, SessionData,
+    ARGBUF_LEN, VM,
+};
 
 const OWNER: [u8; 32] = [0u8; 32];
 const LIMIT: u64 = 1_000_000;
@@ -29,3 +32,66 @@ pub fn debug() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[derive(Default)]
+struct Recorder {
+    events: Vec<String>,
+    arg_lens: Vec<usize>,
+}
+
+impl Debugger for Recorder {
+    fn on_enter(&mut self, frame: &DebugFrame) {
+        self.events.push(format!("enter {}", frame.fn_name()));
+        assert_eq!(frame.depth(), 1);
+        assert_eq!(frame.gas_remaining(), frame.gas_limit());
+
+        let len = frame.with_arg_buf(|buf| buf.len());
+        let mem_len = frame.with_memory(|memory| memory.len());
+        assert!(mem_len > len);
+        self.arg_lens.push(len);
+    }
+
+    fn on_exit(&mut self, frame: &DebugFrame, success: bool) {
+        self.events
+            .push(format!("exit {} {success}", frame.fn_name()));
+    }
+
+    fn on_host_call(&mut self, frame: &DebugFrame, name: &str) {
+        assert!(frame.gas_remaining() < frame.gas_limit());
+        self.events.push(format!("host {name}"));
+    }
+}
+
+#[test]
+pub fn call_with_debugger() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("debugger"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let mut recorder = Recorder::default();
+    session.call_with_debugger::<_, ()>(
+        id,
+        "debug",
+        &String::from("Hello world"),
+        LIMIT,
+        &mut recorder,
+    )?;
+
+    let n_events = recorder.events.len();
+    assert_eq!(recorder.events[0], "enter debug");
+    assert_eq!(recorder.events[n_events - 1], "exit debug true");
+    assert!(recorder.events.contains(&String::from("host hdebug")));
+    assert_eq!(recorder.arg_lens, [ARGBUF_LEN]);
+
+    // The debugger is only called back during the call it was given to
+    session.call::<_, ()>(id, "debug", &String::from("Hello again"), LIMIT)?;
+    assert_eq!(recorder.events.len(), n_events);
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, Session, SessionData,
+    VM,
+};
+use piecrust_uplink::ContractError;
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+fn deploy_deferrer(session: &mut Session) -> Result<ContractId, Error> {
+    session.deploy(
+        contract_bytecode!("deferrer"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )
+}
+
+fn read_value(session: &mut Session, id: ContractId) -> Result<i64, Error> {
+    Ok(session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data)
+}
+
+#[test]
+fn deferred_after_call() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let id = deploy_deferrer(&mut session)?;
+
+    let receipt =
+        session.call::<_, i64>(id, "defer_increment", &3i64, LIMIT)?;
+    assert_eq!(
+        receipt.data, 0xfc,
+        "The increment should run after the call"
+    );
+    assert_eq!(read_value(&mut session, id)?, 0xff);
+
+    assert_eq!(receipt.deferred.len(), 1);
+    let deferred = &receipt.deferred[0];
+    assert_eq!(deferred.contract, id);
+    assert_eq!(deferred.fn_name, "increment");
+    assert!(deferred.result.is_ok());
+    assert!(deferred.gas_spent > 0);
+    assert!(receipt.gas_spent > deferred.gas_spent);
+
+    Ok(())
+}
+
+#[test]
+fn deferred_chain() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let id = deploy_deferrer(&mut session)?;
+
+    let receipt = session.call::<_, ()>(id, "defer_twice", &2i64, LIMIT)?;
+    assert_eq!(read_value(&mut session, id)?, 0xfe);
+
+    let fn_names: Vec<_> = receipt
+        .deferred
+        .iter()
+        .map(|deferred| deferred.fn_name.as_str())
+        .collect();
+    assert_eq!(fn_names, ["defer_increment", "increment"]);
+
+    Ok(())
+}
+
+#[test]
+fn deferred_dropped_on_failure() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let id = deploy_deferrer(&mut session)?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    // The outermost call fails
+    session
+        .call::<_, ()>(id, "defer_and_panic", &1i64, LIMIT)
+        .expect_err("The call should panic");
+    assert_eq!(read_value(&mut session, id)?, 0xfc);
+
+    // A nested call fails, but the outermost succeeds
+    let receipt = session.call::<_, Result<Vec<u8>, ContractError>>(
+        center_id,
+        "delegate_query",
+        &(
+            id,
+            String::from("defer_and_panic"),
+            1i64.to_le_bytes().to_vec(),
+        ),
+        LIMIT,
+    )?;
+    assert!(receipt.data.is_err());
+    assert!(receipt.deferred.is_empty());
+    assert_eq!(read_value(&mut session, id)?, 0xfc);
+
+    // A deferred call fails, and the calls after it still run
+    let receipt = session.call::<_, ()>(
+        id,
+        "defer_panic_then_increment",
+        &1i64,
+        LIMIT,
+    )?;
+    assert_eq!(receipt.deferred.len(), 2);
+    assert!(receipt.deferred[0].result.is_err());
+    assert!(receipt.deferred[1].result.is_ok());
+    assert_eq!(read_value(&mut session, id)?, 0xfd);
+
+    Ok(())
+}
@@ -6,7 +6,7 @@
 
 use piecrust::{
     contract_bytecode, ContractData, ContractError, ContractId, Error,
-    SessionData, VM,
+    GasSchedule, SessionData, VM,
 };
 
 const OWNER: [u8; 32] = [0u8; 32];
@@ -84,3 +84,96 @@ fn call_non_deployed() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn deploy_receipt() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let receipt = session.deploy_with_receipt(
+        contract_bytecode!("initializer"),
+        ContractData::builder().owner(OWNER).init_arg(&0xabu8),
+        LIMIT,
+    )?;
+
+    assert_eq!(receipt.init_gas_limit, LIMIT);
+    assert!(receipt.init_gas_spent > 0, "init should spend gas");
+    assert!(receipt.init_gas_spent <= LIMIT);
+    assert_eq!(receipt.deploy_gas_limit, LIMIT);
+    assert_eq!(receipt.deploy_gas_spent, 0);
+    assert!(
+        receipt.page_stats.pages_written > 0,
+        "init should dirty pages"
+    );
+    assert!(receipt.events.is_empty());
+
+    let value: u8 = session
+        .call(receipt.contract_id, "read_value", &(), LIMIT)?
+        .data;
+    assert_eq!(value, 0xab);
+
+    // Contracts without an `init` method spend nothing when deployed
+    let receipt = session.deploy_with_receipt(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    assert_eq!(receipt.init_gas_spent, 0);
+    assert_eq!(receipt.page_stats.pages_written, 0);
+
+    Ok(())
+}
+
+#[test]
+fn deploy_gas() -> Result<(), Error> {
+    const BYTE_COST: u64 = 2;
+
+    let vm = VM::ephemeral()?;
+
+    let schedule = GasSchedule {
+        deploy_byte_cost: BYTE_COST,
+        ..GasSchedule::default()
+    };
+    let mut session =
+        vm.session(SessionData::builder().gas_schedule(schedule))?;
+
+    let bytecode = contract_bytecode!("initializer");
+    let deploy_cost = BYTE_COST * bytecode.len() as u64;
+
+    // Bytecode costing more than the deploy limit is refused, whatever the
+    // limit for initialization.
+    let err = session
+        .deploy_with_receipt(
+            bytecode,
+            ContractData::builder()
+                .owner(OWNER)
+                .init_arg(&0xabu8)
+                .deploy_limit(deploy_cost - 1),
+            LIMIT,
+        )
+        .expect_err("Deploying should run out of gas");
+    assert!(matches!(err, Error::OutOfGas));
+
+    let receipt = session.deploy_with_receipt(
+        bytecode,
+        ContractData::builder()
+            .owner(OWNER)
+            .init_arg(&0xabu8)
+            .deploy_limit(deploy_cost)
+            .init_limit(LIMIT / 2),
+        LIMIT,
+    )?;
+
+    assert_eq!(receipt.deploy_gas_spent, deploy_cost);
+    assert_eq!(receipt.deploy_gas_limit, deploy_cost);
+    assert_eq!(receipt.init_gas_limit, LIMIT / 2);
+    assert!(receipt.init_gas_spent > 0, "init should spend gas");
+
+    let value: u8 = session
+        .call(receipt.contract_id, "read_value", &(), LIMIT)?
+        .data;
+    assert_eq!(value, 0xab);
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{contract_bytecode, ContractData, Error, SessionData, VM};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn diff_commits() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let old_root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(old_root))?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    let fibonacci_id = session.deploy(
+        contract_bytecode!("fibonacci"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let new_root = session.commit()?;
+
+    let diff = vm.diff_commits(old_root, new_root)?;
+
+    assert_eq!(
+        diff.added.iter().copied().collect::<Vec<_>>(),
+        [fibonacci_id]
+    );
+    assert!(diff.removed.is_empty());
+    assert_eq!(
+        diff.modified.keys().copied().collect::<Vec<_>>(),
+        [counter_id],
+        "Only the incremented counter should be modified"
+    );
+
+    let counter_diff = &diff.modified[&counter_id];
+    assert_eq!(counter_diff.old_len, counter_diff.new_len);
+    assert!(!counter_diff.changed_pages.is_empty());
+
+    let diff = vm.diff_commits(new_root, old_root)?;
+    assert!(diff.added.is_empty());
+    assert_eq!(
+        diff.removed.iter().copied().collect::<Vec<_>>(),
+        [fibonacci_id]
+    );
+
+    assert!(vm.diff_commits(old_root, old_root)?.is_empty());
+
+    vm.diff_commits(old_root, [0u8; 32])
+        .expect_err("Diffing with a non-existent commit should error");
+
+    Ok(())
+}
+
+#[test]
+fn diff_tracking_page_size() -> Result<(), Error> {
+    const TRACKING_PAGE_SIZE: usize = 0x1000;
+
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let old_root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(old_root))?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    let new_root = session.commit()?;
+
+    let coarse = vm.diff_commits(old_root, new_root)?;
+    let coarse = &coarse.modified[&counter_id];
+
+    vm.set_tracking_page_size(TRACKING_PAGE_SIZE);
+    let fine = vm.diff_commits(old_root, new_root)?;
+    let fine = &fine.modified[&counter_id];
+
+    assert_eq!(fine.page_size, TRACKING_PAGE_SIZE);
+    assert!(
+        fine.changed_pages.len() * fine.page_size
+            < coarse.changed_pages.len() * coarse.page_size,
+        "Finer tracking should report fewer changed bytes"
+    );
+
+    let pages_per_page = coarse.page_size / fine.page_size;
+    for page in &fine.changed_pages {
+        assert!(
+            coarse.changed_pages.contains(&(page / pages_per_page)),
+            "Every fine page should be within a coarse one"
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{Error, GasSchedule, SessionData, VM};
+
+fn noop(_buf: &mut [u8], len: u32) -> u32 {
+    len
+}
+
+#[test]
+fn environment() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let other_vm = VM::ephemeral()?;
+
+    let env = vm.environment();
+    assert_eq!(env, other_vm.environment());
+    assert_eq!(env.digest(), other_vm.environment().digest());
+    assert_eq!(env.piecrust_version, env!("CARGO_PKG_VERSION"));
+
+    let displayed = env.to_string();
+    assert_eq!(displayed.lines().count(), 10);
+    assert!(displayed.contains(&hex::encode(env.engine_hash)));
+
+    let session = vm.session(SessionData::builder())?;
+    let session_env = session.environment();
+    assert_eq!(session_env.gas_schedule_hash, env.gas_schedule_hash);
+    assert_eq!(session_env.host_queries_hash, env.host_queries_hash);
+    drop(session);
+
+    let schedule = GasSchedule {
+        call_depth_surcharge: 1,
+        ..GasSchedule::default()
+    };
+    let session = vm.session(SessionData::builder().gas_schedule(schedule))?;
+    assert_eq!(session.environment().gas_schedule_hash, schedule.hash());
+    assert_ne!(
+        session.environment().gas_schedule_hash,
+        env.gas_schedule_hash
+    );
+
+    Ok(())
+}
+
+#[test]
+fn host_queries_hash() -> Result<(), Error> {
+    let mut vm = VM::ephemeral()?;
+    let env = vm.environment();
+
+    vm.register_host_query("noop", noop);
+    let registered_env = vm.environment();
+
+    assert_ne!(env.host_queries_hash, registered_env.host_queries_hash);
+    assert_ne!(env.digest(), registered_env.digest());
+    assert_eq!(env.engine_hash, registered_env.engine_hash);
+
+    Ok(())
+}
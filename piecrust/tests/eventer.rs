@@ -4,7 +4,12 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
-use piecrust::{contract_bytecode, ContractData, Error, SessionData, VM};
+use bytecheck::CheckBytes;
+use piecrust::{
+    contract_bytecode, contract_event, ContractData, ContractEvent, Error,
+    LogLevel, SessionData, VM,
+};
+use rkyv::{Archive, Deserialize, Serialize};
 
 const OWNER: [u8; 32] = [0u8; 32];
 const LIMIT: u64 = 1_000_000;
@@ -56,6 +61,58 @@ pub fn vm_center_events() -> Result<(), Error> {
     Ok(())
 }
 
+contract_event! {
+    #[topic = "typed_number"]
+    #[derive(Debug, PartialEq, Archive, Serialize, Deserialize)]
+    #[archive_attr(derive(CheckBytes))]
+    pub struct TypedNumber {
+        pub num: u32,
+    }
+}
+
+#[test]
+pub fn typed_events() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let eventer_id = session.deploy(
+        contract_bytecode!("eventer"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    const EVENT_NUM: u32 = 5;
+
+    let receipt = session.call::<_, ()>(
+        eventer_id,
+        "emit_events_typed",
+        &EVENT_NUM,
+        LIMIT,
+    )?;
+
+    let events = receipt.events;
+    assert_eq!(events.len() as u32, EVENT_NUM);
+
+    for (i, event) in events.iter().enumerate() {
+        assert_eq!(event.topic, TypedNumber::TOPIC);
+        assert_eq!(event.schema, Some(TypedNumber::SCHEMA_HASH));
+        assert_eq!(
+            event.decode::<TypedNumber>(),
+            Some(TypedNumber { num: i as u32 })
+        );
+    }
+
+    let receipt =
+        session.call::<_, ()>(eventer_id, "emit_events", &EVENT_NUM, LIMIT)?;
+    for event in receipt.events {
+        assert_eq!(event.schema, None, "Untyped events carry no schema");
+        assert!(!event.is::<TypedNumber>());
+    }
+
+    Ok(())
+}
+
 #[test]
 pub fn event_costs() -> Result<(), Error> {
     let vm = VM::ephemeral()?;
@@ -107,3 +164,104 @@ pub fn event_costs() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+pub fn contract_logs() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let eventer_id = session.deploy(
+        contract_bytecode!("eventer"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    let msg = String::from("hello");
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let receipt =
+        session.call::<_, ()>(eventer_id, "log_levels", &msg, LIMIT)?;
+
+    let levels: Vec<_> = receipt.logs.iter().map(|log| log.level).collect();
+    assert_eq!(
+        levels,
+        [
+            LogLevel::Error,
+            LogLevel::Warn,
+            LogLevel::Info,
+            LogLevel::Debug,
+            LogLevel::Trace,
+        ]
+    );
+    for log in &receipt.logs {
+        assert_eq!(log.source, eventer_id);
+        assert_eq!(log.msg, msg);
+    }
+    let gas_spent = receipt.gas_spent;
+
+    // Logs are kept per call
+    let receipt =
+        session.call::<_, ()>(eventer_id, "log_levels", &msg, LIMIT)?;
+    assert_eq!(receipt.logs.len(), 5);
+
+    let mut session = vm.session(
+        SessionData::builder()
+            .base(root)
+            .log_level(LogLevel::Info)
+            .max_logs(2),
+    )?;
+    let receipt =
+        session.call::<_, ()>(eventer_id, "log_levels", &msg, LIMIT)?;
+
+    let levels: Vec<_> = receipt.logs.iter().map(|log| log.level).collect();
+    assert_eq!(levels, [LogLevel::Error, LogLevel::Warn]);
+    assert_eq!(
+        receipt.gas_spent, gas_spent,
+        "Filtering logs should not change the gas spent"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn subscribe_events() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let eventer_id = session.deploy(
+        contract_bytecode!("eventer"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    const EVENT_NUM: u32 = 5;
+
+    let receiver = session.subscribe_events();
+    let receipt =
+        session.call::<_, ()>(eventer_id, "emit_events", &EVENT_NUM, LIMIT)?;
+    assert!(
+        receipt.events.is_empty(),
+        "Events should be sent to the subscriber instead"
+    );
+
+    let events: Vec<_> = receiver.try_iter().collect();
+    assert_eq!(events.len() as u32, EVENT_NUM);
+
+    for i in 0..EVENT_NUM {
+        let index = i as usize;
+        assert_eq!(events[index].source, eventer_id);
+        assert_eq!(events[index].topic, "number");
+        assert_eq!(events[index].data, i.to_le_bytes());
+    }
+
+    // Dropping the receiver unsubscribes
+    drop(receiver);
+    let receipt =
+        session.call::<_, ()>(eventer_id, "emit_events", &EVENT_NUM, LIMIT)?;
+    assert_eq!(receipt.events.len() as u32, EVENT_NUM);
+
+    Ok(())
+}
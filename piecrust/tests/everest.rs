@@ -4,7 +4,26 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
-use piecrust::{contract_bytecode, ContractData, Error, SessionData, VM};
+use piecrust::{
+    contract_bytecode, session_meta, ContractData, Error, SessionData,
+    SessionMeta, VM,
+};
+use rkyv::{Archive, Deserialize, Serialize};
+
+session_meta! {
+    #[name = "height"]
+    #[derive(Archive, Serialize, Deserialize)]
+    pub struct BlockHeight(pub u64);
+}
+
+session_meta! {
+    #[name = "height"]
+    #[derive(Archive, Serialize, Deserialize)]
+    pub struct NewBlockHeight {
+        pub height: u64,
+        pub epoch: u64,
+    }
+}
 
 const OWNER: [u8; 32] = [0u8; 32];
 const LIMIT: u64 = 1_000_000;
@@ -42,3 +61,80 @@ pub fn meta_data_optionality() -> Result<(), Error> {
     assert!(height.is_none());
     Ok(())
 }
+
+#[test]
+pub fn typed_height() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    const HEIGHT: u64 = 29_000u64;
+    let mut session =
+        vm.session(SessionData::builder().insert_typed(BlockHeight(HEIGHT))?)?;
+    assert_eq!(
+        session.meta_schema("height"),
+        Some(BlockHeight::SCHEMA_HASH)
+    );
+
+    let id = session.deploy(
+        contract_bytecode!("everest"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let height: Option<u64> =
+        session.call(id, "get_typed_height", &(), LIMIT)?.data;
+    assert_eq!(height, Some(HEIGHT));
+
+    session.set_meta_typed(BlockHeight(HEIGHT + 1))?;
+    let height: Option<u64> =
+        session.call(id, "get_typed_height", &(), LIMIT)?.data;
+    assert_eq!(height, Some(HEIGHT + 1));
+
+    session.remove_meta("height");
+    assert_eq!(session.meta_schema("height"), None);
+    let height: Option<u64> =
+        session.call(id, "get_typed_height", &(), LIMIT)?.data;
+    assert_eq!(height, None);
+
+    Ok(())
+}
+
+#[test]
+pub fn typed_height_schema_mismatch() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session =
+        vm.session(SessionData::builder().insert_typed(NewBlockHeight {
+            height: 29_000,
+            epoch: 2,
+        })?)?;
+
+    let id = session.deploy(
+        contract_bytecode!("everest"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    match session.call::<_, Option<u64>>(id, "get_typed_height", &(), LIMIT) {
+        Err(Error::MetaSchemaMismatch {
+            name,
+            expected,
+            found,
+        }) => {
+            assert_eq!(name, "height");
+            assert_eq!(expected, BlockHeight::SCHEMA_HASH);
+            assert_eq!(found, Some(NewBlockHeight::SCHEMA_HASH));
+        }
+        res => panic!("Reading another layout should fail: {res:?}"),
+    }
+
+    // Items set untyped carry no schema, and can't be read as typed
+    session.set_meta("height", 29_000u64)?;
+    match session.call::<_, Option<u64>>(id, "get_typed_height", &(), LIMIT) {
+        Err(Error::MetaSchemaMismatch { found, .. }) => {
+            assert_eq!(found, None)
+        }
+        res => panic!("Reading an untyped item should fail: {res:?}"),
+    }
+
+    Ok(())
+}
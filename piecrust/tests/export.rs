@@ -0,0 +1,151 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, Root, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+const COUNTER_ID: ContractId = ContractId::from_bytes([1; 32]);
+
+/// Deploys the counter and the box, incrementing the counter in a second
+/// commit on top of the first. Returns the root of the second commit.
+fn two_commits(vm: &VM) -> Result<Root, Error> {
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(COUNTER_ID),
+        LIMIT,
+    )?;
+    session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    session.call::<_, ()>(COUNTER_ID, "increment", &(), LIMIT)?;
+    session.commit()
+}
+
+/// Reads the value of the counter in the commit with the given `root`, and
+/// increments it in a new commit. Returns the value and the new root.
+fn increment(vm: &VM, root: Root) -> Result<(i64, Root), Error> {
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let value = session
+        .call::<_, i64>(COUNTER_ID, "read_value", &(), LIMIT)?
+        .data;
+    session.call::<_, ()>(COUNTER_ID, "increment", &(), LIMIT)?;
+    Ok((value, session.commit()?))
+}
+
+#[test]
+fn export_import() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let root = two_commits(&vm)?;
+
+    let mut stream = Vec::new();
+    vm.export_commit(root, &mut stream)?;
+
+    let other_vm = VM::ephemeral()?;
+    let imported_root = other_vm.import_commit(stream.as_slice())?;
+
+    assert_eq!(imported_root, root, "The root should be preserved");
+    assert_eq!(other_vm.commits(), vec![root]);
+
+    // Calls write their return to the argument buffer, in the contract's
+    // memory, so both VMs must make the same calls to agree on the root
+    let (value, next_root) = increment(&other_vm, root)?;
+    assert_eq!(value, 0xfd);
+
+    assert_eq!(
+        increment(&vm, root)?,
+        (value, next_root),
+        "Both VMs should continue from the same state"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn import_survives_restart() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let root = two_commits(&vm)?;
+
+    let mut stream = Vec::new();
+    vm.export_commit(root, &mut stream)?;
+
+    let other_vm = VM::ephemeral()?;
+    other_vm.import_commit(stream.as_slice())?;
+
+    let other_vm = VM::new(other_vm.root_dir())?;
+    assert_eq!(other_vm.commits(), vec![root]);
+
+    let mut session = other_vm.session(SessionData::builder().base(root))?;
+    assert_eq!(
+        session
+            .call::<_, i64>(COUNTER_ID, "read_value", &(), LIMIT)?
+            .data,
+        0xfd
+    );
+
+    Ok(())
+}
+
+#[test]
+fn import_twice() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let root = two_commits(&vm)?;
+
+    let mut stream = Vec::new();
+    vm.export_commit(root, &mut stream)?;
+
+    assert_eq!(vm.import_commit(stream.as_slice())?, root);
+    assert_eq!(vm.commits().len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn import_corrupt() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let root = two_commits(&vm)?;
+
+    let mut stream = Vec::new();
+    vm.export_commit(root, &mut stream)?;
+
+    let other_vm = VM::ephemeral()?;
+
+    // The stream ends halfway through
+    let truncated = &stream[..stream.len() / 2];
+    assert!(other_vm.import_commit(truncated).is_err());
+
+    // A byte of the last page is flipped
+    let mut corrupt = stream.clone();
+    let last = corrupt.len() - 1;
+    corrupt[last] ^= 0xff;
+    assert!(other_vm.import_commit(corrupt.as_slice()).is_err());
+
+    assert!(other_vm.commits().is_empty());
+
+    // Nothing is left behind after a failed import
+    assert_eq!(other_vm.import_commit(stream.as_slice())?, root);
+
+    Ok(())
+}
+
+#[test]
+fn export_missing_commit() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut stream = Vec::new();
+    assert!(vm.export_commit([0; 32], &mut stream).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, ExportInfo, FnSchema,
+    SessionData, VM,
+};
+use piecrust_uplink::ABI_SECTION;
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+fn export<'a>(exports: &'a [ExportInfo], name: &str) -> &'a ExportInfo {
+    exports
+        .iter()
+        .find(|export| export.name == name)
+        .unwrap_or_else(|| panic!("{name} should be exported"))
+}
+
+fn schema(arg: &str, ret: &str, mutates: bool) -> Option<FnSchema> {
+    Some(FnSchema {
+        arg: arg.into(),
+        ret: ret.into(),
+        mutates,
+    })
+}
+
+#[test]
+fn exports_with_schema() -> Result<(), Error> {
+    let bytecode = contract_bytecode!("pure_counter");
+
+    // The section must survive the contract being stripped
+    let section_name = ABI_SECTION.as_bytes();
+    assert!(
+        bytecode
+            .windows(section_name.len())
+            .any(|window| window == section_name),
+        "the stripped contract should keep its {ABI_SECTION} section"
+    );
+
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        bytecode,
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let exports = session.contract_exports(id)?;
+
+    assert_eq!(
+        export(&exports, "read_value").schema,
+        schema("()", "i64", false)
+    );
+    assert_eq!(
+        export(&exports, "read_value_plus").schema,
+        schema("i64", "i64", false)
+    );
+    assert_eq!(
+        export(&exports, "read_other").schema,
+        schema("ContractId", "Result<i64, ContractError>", false)
+    );
+    assert_eq!(
+        export(&exports, "increment").schema,
+        schema("()", "()", true)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn exports_without_schema() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let exports = session.contract_exports(id)?;
+
+    assert_eq!(export(&exports, "read_value").schema, None);
+    assert_eq!(export(&exports, "increment").schema, None);
+    assert!(exports.iter().all(|export| export.name != "memory"));
+
+    let missing = ContractId::from_bytes([1; 32]);
+    match session.contract_exports(missing) {
+        Err(Error::ContractDoesNotExist(contract)) => {
+            assert_eq!(contract, missing)
+        }
+        res => panic!("A missing contract has no exports: {res:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn exports_from_bytecode() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let bytecode = contract_bytecode!("pure_counter");
+    let id = session.deploy(
+        bytecode,
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    assert_eq!(
+        ExportInfo::from_bytecode(bytecode),
+        session.contract_exports(id)?
+    );
+    assert!(ExportInfo::from_bytecode(b"not wasm").is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{contract_bytecode, ContractData, Error, SessionData, VM};
+use piecrust_uplink::ContractId;
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIVE_ID: ContractId = ContractId::from_bytes([1; 32]);
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn fallback() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let archived = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(archived, "increment", &(), LIMIT)?;
+    let archive = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let live = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(LIVE_ID),
+        LIMIT,
+    )?;
+    let head = session.commit()?;
+
+    // Without the fallback, the archived contract is not in the head
+    let mut session = vm.session(SessionData::builder().base(head))?;
+    session
+        .call::<_, i64>(archived, "read_value", &(), LIMIT)
+        .expect_err("The archived contract should not be in the head");
+
+    // Reading a contract from a fallback leaves the state untouched
+    let mut session =
+        vm.session(SessionData::builder().base(head).fallbacks([archive]))?;
+    assert_eq!(
+        session
+            .call::<_, i64>(archived, "read_value", &(), LIMIT)?
+            .data,
+        0xfd
+    );
+    assert_eq!(session.root(), head);
+
+    // Writing to it brings it into the new commit in full
+    session.call::<_, ()>(archived, "increment", &(), LIMIT)?;
+    session.call::<_, ()>(live, "increment", &(), LIMIT)?;
+    let root = session.root();
+    assert_eq!(session.commit()?, root);
+
+    vm.delete_commit(archive)?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(
+        session
+            .call::<_, i64>(archived, "read_value", &(), LIMIT)?
+            .data,
+        0xfe
+    );
+    assert_eq!(
+        session.call::<_, i64>(live, "read_value", &(), LIMIT)?.data,
+        0xfd
+    );
+
+    // A fallback that doesn't exist is refused
+    vm.session(SessionData::builder().base(root).fallbacks([archive]))
+        .expect_err("A deleted fallback should be refused");
+
+    Ok(())
+}
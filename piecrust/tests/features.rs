@@ -0,0 +1,205 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, Error, InvalidReason, SessionData,
+    ValidationConfig, WasmFeature, WasmFeatures, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+/// A module with the argument buffer at the start of its memory, with a
+/// function filling it using the bulk memory `memory.fill` instruction.
+const MEMORY_FILL: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+    0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+    0x03, 0x02, 0x01, 0x00, // function section
+    0x05, 0x03, 0x01, 0x00, 0x01, // memory section
+    0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x00, 0x0b, // global section
+    0x07, 0x0e, 0x02, 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00,
+    0x01, b'A', 0x03, 0x00, // export section
+    0x0a, 0x0d, 0x01, 0x0b, 0x00, 0x41, 0x00, 0x41, 0x00, 0x41, 0x00, 0xfc,
+    0x0b, 0x00, 0x0b, // code section
+];
+
+/// A module exporting a memory, with a function returning an `f32` constant.
+const FLOAT_CONST: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+    0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7d, // type section
+    0x03, 0x02, 0x01, 0x00, // function section
+    0x05, 0x03, 0x01, 0x00, 0x01, // memory section
+    0x07, 0x0a, 0x01, 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02,
+    0x00, // export section
+    0x0a, 0x09, 0x01, 0x07, 0x00, 0x43, 0x00, 0x00, 0x00, 0x00,
+    0x0b, // code section
+];
+
+/// A module with the argument buffer at the start of its memory, exporting a
+/// function dividing the `f32` in the buffer by itself, and writing the bits
+/// of the result back.
+const FLOAT_DIV: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+    0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type section
+    0x03, 0x02, 0x01, 0x00, // function section
+    0x05, 0x03, 0x01, 0x00, 0x02, // memory section
+    0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x00, 0x0b, // global section
+    0x07, 0x14, 0x03, 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00,
+    0x01, b'A', 0x03, 0x00, 0x03, b'd', b'i', b'v', 0x00,
+    0x00, // export section
+    0x0a, 0x17, 0x01, 0x15, 0x00, 0x41, 0x00, 0x41, 0x00, 0x2a, 0x02, 0x00,
+    0x41, 0x00, 0x2a, 0x02, 0x00, 0x95, 0xbc, 0x36, 0x02, 0x00, 0x41, 0x04,
+    0x0b, // code section
+];
+
+#[test]
+fn denied_feature() -> Result<(), Error> {
+    let features = WasmFeatures::default().deny(WasmFeature::BulkMemory);
+    assert!(!features.is_allowed(WasmFeature::BulkMemory));
+    assert!(
+        !features.is_allowed(WasmFeature::ReferenceTypes),
+        "Reference types depend on bulk memory"
+    );
+
+    let vm = VM::ephemeral_with_features(features)?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let err = session
+        .deploy(MEMORY_FILL, ContractData::builder().owner(OWNER), LIMIT)
+        .expect_err("Deploying a contract using bulk memory should fail");
+    assert!(
+        matches!(err, Error::DeniedWasmFeature(WasmFeature::BulkMemory)),
+        "The error should name the denied feature, got: {err}"
+    );
+
+    // Contracts not using the feature are still deployed
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let value = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert_eq!(value.data, 0xfc);
+
+    Ok(())
+}
+
+#[test]
+fn validate_bytecode() {
+    let config = ValidationConfig::default();
+    assert_eq!(config.validate(MEMORY_FILL), Ok(()));
+    assert_eq!(config.validate(FLOAT_CONST), Ok(()));
+
+    let config = ValidationConfig::strict();
+    assert_eq!(
+        config.validate(MEMORY_FILL),
+        Err(vec![InvalidReason::BulkMemory])
+    );
+    assert_eq!(
+        config.validate(FLOAT_CONST),
+        Err(vec![InvalidReason::Floats])
+    );
+    assert!(!config.is_allowed(InvalidReason::Threads));
+}
+
+#[test]
+fn invalid_bytecode_rejected() -> Result<(), Error> {
+    let mut vm = VM::ephemeral()?;
+    vm.set_validation_config(ValidationConfig::default().floats(false));
+    assert_eq!(vm.environment().validation, vm.validation_config());
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let err = session
+        .deploy(FLOAT_CONST, ContractData::builder().owner(OWNER), LIMIT)
+        .expect_err("Deploying a contract using floats should fail");
+    match err {
+        Error::InvalidBytecode(reasons) => {
+            assert_eq!(reasons, vec![InvalidReason::Floats])
+        }
+        err => panic!("The error should list the reasons, got: {err}"),
+    }
+
+    let err = session
+        .deploy(
+            contract_bytecode!("counter_float"),
+            ContractData::builder().owner(OWNER),
+            LIMIT,
+        )
+        .expect_err("Deploying a contract using floats should fail");
+    match err {
+        Error::InvalidBytecode(reasons) => {
+            assert!(reasons.contains(&InvalidReason::Floats))
+        }
+        err => panic!("The error should list the reasons, got: {err}"),
+    }
+
+    // Contracts not using floats are still deployed
+    session.deploy(MEMORY_FILL, ContractData::builder().owner(OWNER), LIMIT)?;
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let value = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert_eq!(value.data, 0xfc);
+
+    Ok(())
+}
+
+#[test]
+fn nan_canonicalized() -> Result<(), Error> {
+    const CANONICAL_NAN: u32 = 0x7fc0_0000;
+
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        FLOAT_DIV,
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let bits = session.call::<_, u32>(id, "div", &2.0f32.to_bits(), LIMIT)?;
+    assert_eq!(f32::from_bits(bits.data), 1.0);
+
+    // Both the sign and the payload of a NaN depend on the platform, unless
+    // they are canonicalized
+    let bits = session.call::<_, u32>(id, "div", &0.0f32.to_bits(), LIMIT)?;
+    assert_eq!(bits.data, CANONICAL_NAN);
+
+    let bits = session.call::<_, u32>(id, "div", &0xffc0_1234u32, LIMIT)?;
+    assert_eq!(bits.data, CANONICAL_NAN);
+
+    Ok(())
+}
+
+#[test]
+fn nan_canonicalization_disabled() -> Result<(), Error> {
+    const CANONICAL_NAN: u32 = 0x7fc0_0000;
+
+    let mut vm = VM::ephemeral()?;
+    vm.set_validation_config(
+        ValidationConfig::default().nan_canonicalization(false),
+    );
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        FLOAT_DIV,
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let bits = session.call::<_, u32>(id, "div", &2.0f32.to_bits(), LIMIT)?;
+    assert_eq!(f32::from_bits(bits.data), 1.0);
+
+    // The payload of the NaN divided is propagated as is
+    let bits = session.call::<_, u32>(id, "div", &0xffc0_1234u32, LIMIT)?;
+    assert!(f32::from_bits(bits.data).is_nan());
+    assert_ne!(bits.data, CANONICAL_NAN);
+
+    Ok(())
+}
@@ -5,8 +5,13 @@
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
 use std::sync::mpsc;
+use std::thread;
 
-use piecrust::{contract_bytecode, ContractData, Error, SessionData, VM};
+use piecrust::{
+    contract_bytecode, ContractData, Error, FeedPolicy, GasSchedule,
+    SessionData, VM,
+};
+use piecrust_uplink::ContractError;
 
 const OWNER: [u8; 32] = [0u8; 32];
 const LIMIT: u64 = 1_000_000;
@@ -129,3 +134,182 @@ fn feed_out_of_gas() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn bounded_feed_block() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("feeder"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    const FEED_NUM: u32 = 50;
+
+    let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(1);
+
+    let consumer = thread::spawn(move || {
+        receiver
+            .into_iter()
+            .map(|data| {
+                rkyv::from_bytes(&data).expect("Fed data should be a number")
+            })
+            .collect::<Vec<u32>>()
+    });
+
+    session.bounded_feeder_call::<_, ()>(
+        id,
+        "feed_num",
+        &FEED_NUM,
+        LIMIT,
+        sender,
+        FeedPolicy::Block,
+    )?;
+
+    let numbers = consumer.join().expect("Consumer should not panic");
+    assert_eq!(
+        numbers,
+        (0..FEED_NUM).collect::<Vec<_>>(),
+        "All numbers should be fed when blocking"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn bounded_feed_drop() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("feeder"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    const FEED_NUM: u32 = 10;
+    const CAPACITY: usize = 3;
+
+    let (sender, receiver) = mpsc::sync_channel(CAPACITY);
+
+    session.bounded_feeder_call::<_, ()>(
+        id,
+        "feed_num",
+        &FEED_NUM,
+        LIMIT,
+        sender,
+        FeedPolicy::Drop,
+    )?;
+
+    let numbers = receiver
+        .into_iter()
+        .map(|data| {
+            rkyv::from_bytes(&data).expect("Fed data should be a number")
+        })
+        .collect::<Vec<u32>>();
+
+    assert_eq!(
+        numbers,
+        (0..CAPACITY as u32).collect::<Vec<_>>(),
+        "Data fed past the capacity should be dropped"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn feed_surcharge() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("feeder"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    const FEED_NUM: u32 = 10;
+    const SURCHARGE: u64 = 1000;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let (sender, _receiver) = mpsc::channel();
+    let base_spent = session
+        .feeder_call::<_, ()>(id, "feed_num", &FEED_NUM, LIMIT, sender)?
+        .gas_spent;
+
+    let schedule = GasSchedule {
+        feed_surcharge: SURCHARGE,
+        ..GasSchedule::default()
+    };
+    let mut session =
+        vm.session(SessionData::builder().base(root).gas_schedule(schedule))?;
+    let (sender, _receiver) = mpsc::channel();
+    let spent = session
+        .feeder_call::<_, ()>(id, "feed_num", &FEED_NUM, LIMIT, sender)?
+        .gas_spent;
+
+    assert_eq!(spent, base_spent + FEED_NUM as u64 * SURCHARGE);
+
+    Ok(())
+}
+
+#[test]
+fn call_feed() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let feeder_id = session.deploy(
+        contract_bytecode!("feeder"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    // More numbers than fit in the argument buffer at once
+    const FEED_NUM: u32 = 20_000;
+    const GAS_LIMIT: u64 = 1_000_000_000;
+    const SURCHARGE: u64 = 1000;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let receipt = session.call::<_, Result<(u32, u64), ContractError>>(
+        center_id,
+        "sum_fed",
+        &(feeder_id, FEED_NUM),
+        GAS_LIMIT,
+    )?;
+    let (count, sum) = receipt.data.expect("Feed call should succeed");
+    assert_eq!(count, FEED_NUM);
+    assert_eq!(sum, (0..FEED_NUM as u64).sum::<u64>());
+
+    // Both feeding and reading each item are charged
+    let schedule = GasSchedule {
+        feed_surcharge: SURCHARGE,
+        ..GasSchedule::default()
+    };
+    let mut session =
+        vm.session(SessionData::builder().base(root).gas_schedule(schedule))?;
+    let spent = session
+        .call::<_, Result<(u32, u64), ContractError>>(
+            center_id,
+            "sum_fed",
+            &(feeder_id, FEED_NUM),
+            GAS_LIMIT,
+        )?
+        .gas_spent;
+
+    assert_eq!(spent, receipt.gas_spent + 2 * FEED_NUM as u64 * SURCHARGE);
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::collections::BTreeSet;
+use std::fs;
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, FsckCheck, FsckLevel,
+    Root, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+const COUNTER_ID: ContractId = ContractId::from_bytes([1; 32]);
+
+/// Commits the counter, and then an increment on top of it, returning both
+/// roots.
+fn commit_twice(vm: &VM) -> Result<(Root, Root), Error> {
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(COUNTER_ID),
+        LIMIT,
+    )?;
+    let base = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(base))?;
+    session.call::<_, ()>(COUNTER_ID, "increment", &(), LIMIT)?;
+    let diff = session.commit()?;
+
+    Ok((base, diff))
+}
+
+#[test]
+fn fsck_ok() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let (base, _) = commit_twice(&vm)?;
+
+    // A session may be open while checking
+    let _session = vm.session(SessionData::builder().base(base))?;
+
+    for level in [FsckLevel::Quick, FsckLevel::Full] {
+        let report = vm.fsck(level);
+        assert!(report.is_ok(), "{report}");
+        assert_eq!(report.commits, 2);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn fsck_corrupt_page() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let (base, diff) = commit_twice(&vm)?;
+
+    // Flip a byte of every page written by the first commit
+    let pages_dir = vm
+        .root_dir()
+        .join("main")
+        .join("memory")
+        .join(hex::encode(COUNTER_ID))
+        .join(hex::encode(base));
+    for entry in fs::read_dir(pages_dir).unwrap() {
+        let path = entry.unwrap().path();
+        let mut page = fs::read(&path).unwrap();
+        page[0] ^= 0xff;
+        fs::write(path, page).unwrap();
+    }
+
+    // The pages are all there, so only reading them finds the corruption
+    let report = vm.fsck(FsckLevel::Quick);
+    assert!(report.is_ok(), "{report}");
+
+    let report = vm.fsck(FsckLevel::Full);
+    assert!(report.findings_with(FsckCheck::PageHash).next().is_some());
+    assert!(report.broken_commits().contains(&base), "{report}");
+
+    // The report is one line per finding, after the line with the counts
+    let report_str = report.to_string();
+    assert_eq!(report_str.lines().count(), report.findings.len() + 1);
+
+    // Repairing drops the broken commit, along with the one written on top
+    let report = vm.fsck_repair(FsckLevel::Full)?;
+    assert_eq!(report.dropped.len(), 2);
+    assert!(report.dropped.contains(&base));
+    assert!(report.dropped.contains(&diff));
+    assert!(vm.commits().is_empty());
+
+    let report = vm.fsck(FsckLevel::Full);
+    assert!(report.is_ok(), "{report}");
+    assert_eq!(report.commits, 0);
+
+    Ok(())
+}
+
+#[test]
+fn fsck_missing_files() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let (base, diff) = commit_twice(&vm)?;
+
+    let main_dir = vm.root_dir().join("main");
+
+    // The shared code the bytecode of the counter is linked to
+    for entry in fs::read_dir(main_dir.join("code")).unwrap() {
+        fs::remove_file(entry.unwrap().path()).unwrap();
+    }
+
+    let report = vm.fsck(FsckLevel::Quick);
+    assert!(report.is_ok(), "{report}");
+
+    let report = vm.fsck(FsckLevel::Full);
+    assert!(
+        report.findings_with(FsckCheck::SharedCode).next().is_some(),
+        "{report}"
+    );
+
+    // The metadata of the counter, which both commits include
+    let counter_hex = hex::encode(COUNTER_ID);
+    fs::remove_file(main_dir.join("bytecode").join(format!("{counter_hex}.m")))
+        .unwrap();
+
+    let report = vm.fsck(FsckLevel::Quick);
+    let missing = report.findings_with(FsckCheck::BytecodeFiles).count();
+    assert_eq!(missing, 2, "{report}");
+    assert_eq!(report.broken_commits(), BTreeSet::from([base, diff]));
+
+    Ok(())
+}
+
+#[test]
+fn checks_have_distinct_codes() {
+    for (i, check) in FsckCheck::ALL.iter().enumerate() {
+        for other in &FsckCheck::ALL[i + 1..] {
+            assert_ne!(check.code(), other.code());
+        }
+    }
+}
@@ -0,0 +1,422 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, GasSchedule,
+    InstructionCosts, ReplayCall, Root, SessionData, TransferError, VM,
+};
+use piecrust_uplink::ARGBUF_LEN;
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+const GROWER_LIMIT: u64 = 40_000_000;
+
+fn deploy(vm: &VM) -> Result<(Root, ContractId, ContractId), Error> {
+    let mut session = vm.session(SessionData::builder())?;
+
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    Ok((session.commit()?, counter_id, center_id))
+}
+
+#[test]
+fn default_schedule_is_free() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let session = vm.session(SessionData::builder())?;
+
+    assert_eq!(*session.gas_schedule(), GasSchedule::default());
+    assert_eq!(session.gas_schedule().call_surcharge(8, 8), 0);
+    assert_eq!(session.gas_schedule().refund_cap(LIMIT), 0);
+
+    Ok(())
+}
+
+#[test]
+fn depth_surcharge() -> Result<(), Error> {
+    const SURCHARGE: u64 = 1000;
+
+    let vm = VM::ephemeral()?;
+    let (root, _, center_id) = deploy(&vm)?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let receipt = session.call::<_, Vec<ContractId>>(
+        center_id,
+        "call_self_n_times",
+        &3u32,
+        LIMIT,
+    )?;
+    let base_spent = receipt.gas_spent;
+
+    let schedule = GasSchedule {
+        call_depth_surcharge: SURCHARGE,
+        ..GasSchedule::default()
+    };
+    let mut session =
+        vm.session(SessionData::builder().base(root).gas_schedule(schedule))?;
+    let receipt = session.call::<_, Vec<ContractId>>(
+        center_id,
+        "call_self_n_times",
+        &3u32,
+        LIMIT,
+    )?;
+
+    // The calls reach depths one, two, and three
+    assert_eq!(receipt.gas_spent, base_spent + SURCHARGE * (1 + 2 + 3));
+
+    Ok(())
+}
+
+#[test]
+fn breadth_surcharge() -> Result<(), Error> {
+    const SURCHARGE: u64 = 1000;
+
+    let vm = VM::ephemeral()?;
+    let (root, counter_id, center_id) = deploy(&vm)?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let receipt = session.call::<_, ()>(
+        center_id,
+        "increment_counter",
+        &counter_id,
+        LIMIT,
+    )?;
+    let base_spent = receipt.gas_spent;
+
+    let schedule = GasSchedule {
+        call_breadth_surcharge: SURCHARGE,
+        ..GasSchedule::default()
+    };
+    let mut session =
+        vm.session(SessionData::builder().base(root).gas_schedule(schedule))?;
+    let receipt = session.call::<_, ()>(
+        center_id,
+        "increment_counter",
+        &counter_id,
+        LIMIT,
+    )?;
+
+    // Both the callcenter and the counter are instantiated
+    assert_eq!(receipt.gas_spent, base_spent + SURCHARGE * 2);
+
+    Ok(())
+}
+
+#[test]
+fn host_call_cost() -> Result<(), Error> {
+    const COST: u64 = 1000;
+
+    let vm = VM::ephemeral()?;
+    let (root, counter_id, center_id) = deploy(&vm)?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let receipt = session.call::<_, ()>(
+        center_id,
+        "increment_counter",
+        &counter_id,
+        LIMIT,
+    )?;
+    let base_spent = receipt.gas_spent;
+
+    let schedule = GasSchedule {
+        host_call_cost: COST,
+        ..GasSchedule::default()
+    };
+    let mut session =
+        vm.session(SessionData::builder().base(root).gas_schedule(schedule))?;
+    let receipt = session.call::<_, ()>(
+        center_id,
+        "increment_counter",
+        &counter_id,
+        LIMIT,
+    )?;
+
+    // The callcenter calls the host once, to call the counter
+    assert_eq!(receipt.gas_spent, base_spent + COST);
+
+    Ok(())
+}
+
+#[test]
+fn instruction_costs() -> Result<(), Error> {
+    let costs = InstructionCosts {
+        store_byte: 100,
+        ..InstructionCosts::default()
+    };
+
+    let vm = VM::ephemeral()?;
+    let (root, counter_id, _) = deploy(&vm)?;
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let base_spent = session
+        .call::<_, ()>(counter_id, "increment", &(), LIMIT)?
+        .gas_spent;
+
+    let costly_vm = VM::builder().instruction_costs(costs).build_ephemeral()?;
+    assert_eq!(*costly_vm.instruction_costs(), costs);
+    assert_ne!(
+        costly_vm.environment().instruction_costs_hash,
+        vm.environment().instruction_costs_hash
+    );
+
+    let (root, counter_id, _) = deploy(&costly_vm)?;
+    let mut session = costly_vm.session(SessionData::builder().base(root))?;
+    let spent = session
+        .call::<_, ()>(counter_id, "increment", &(), LIMIT)?
+        .gas_spent;
+
+    assert!(spent > base_spent, "storing should cost more");
+
+    Ok(())
+}
+
+#[test]
+fn instruction_costs_persisted() -> Result<(), Error> {
+    let tmp = tempfile::tempdir().expect("Creating a tempdir works");
+
+    let vm = VM::new(tmp.path())?;
+    let (root, counter_id, _) = deploy(&vm)?;
+    drop(vm);
+
+    let costs = InstructionCosts {
+        call: 10,
+        ..InstructionCosts::default()
+    };
+    let costly_vm = VM::builder().instruction_costs(costs).build(tmp.path())?;
+
+    // Building on a commit made with other costs fails, rather than
+    // diverging from what was computed on it before
+    match costly_vm.session(SessionData::builder().base(root)) {
+        Err(Error::InstructionCostsMismatch { expected, found }) => {
+            assert_eq!(expected, costs.hash());
+            assert_eq!(found, InstructionCosts::default().hash());
+        }
+        res => panic!("Expected an instruction costs mismatch: {res:?}"),
+    }
+
+    // Commits made with the new costs record them in turn, including once
+    // the VM is restarted.
+    let mut session = costly_vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let costly_root = session.commit()?;
+    drop(costly_vm);
+
+    let vm = VM::new(tmp.path())?;
+    assert!(matches!(
+        vm.session(SessionData::builder().base(costly_root)),
+        Err(Error::InstructionCostsMismatch { .. })
+    ));
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    let next_root = session.commit()?;
+
+    // The costs stay recorded once a commit is finalized, for the sessions
+    // spawned off the finalized state
+    vm.finalize_commit(next_root)?;
+    vm.session(SessionData::builder())?;
+    drop(vm);
+
+    let costly_vm = VM::builder().instruction_costs(costs).build(tmp.path())?;
+    assert!(matches!(
+        costly_vm.session(SessionData::builder()),
+        Err(Error::InstructionCostsMismatch { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn gas_report() -> Result<(), Error> {
+    const SURCHARGE: u64 = 1000;
+
+    let vm = VM::ephemeral()?;
+    let (root, counter_id, center_id) = deploy(&vm)?;
+
+    let counter_arg = rkyv::to_bytes::<_, 32>(&counter_id)
+        .expect("Serializing a contract ID works")
+        .to_vec();
+    let calls = [
+        ReplayCall {
+            contract: counter_id,
+            fn_name: String::from("increment"),
+            fn_arg: vec![],
+            gas_limit: LIMIT,
+        },
+        ReplayCall {
+            contract: center_id,
+            fn_name: String::from("increment_counter"),
+            fn_arg: counter_arg,
+            gas_limit: LIMIT,
+        },
+    ];
+
+    let proposed = GasSchedule {
+        call_breadth_surcharge: SURCHARGE,
+        ..GasSchedule::default()
+    };
+    let report = vm.gas_report(
+        |schedule| SessionData::builder().base(root).gas_schedule(schedule),
+        &calls,
+        GasSchedule::default(),
+        proposed,
+    )?;
+
+    assert_eq!(report.calls.len(), 2);
+    assert!(report.calls.iter().all(|c| c.baseline_ok && c.proposed_ok));
+
+    // Calling the counter directly makes no inter-contract calls
+    assert_eq!(report.calls[0].gas.delta(), 0);
+    // Both the callcenter and the counter are instantiated
+    assert_eq!(report.calls[1].gas.delta(), SURCHARGE as i128 * 2);
+    assert_eq!(report.total().delta(), SURCHARGE as i128 * 2);
+
+    // The surcharge is paid by the caller
+    assert_eq!(report.contracts[&counter_id].delta(), 0);
+    assert_eq!(report.contracts[&center_id].delta(), SURCHARGE as i128 * 2);
+
+    // The counter's gas is all spent in `increment`, whether called directly
+    // or by the callcenter
+    let increment = (counter_id, String::from("increment"));
+    assert_eq!(report.functions[&increment], report.contracts[&counter_id]);
+
+    Ok(())
+}
+
+/// Deploys the grower and fills it with a few buffers of data, returning the
+/// root of the commit.
+fn deploy_filled_grower(vm: &VM) -> Result<(Root, ContractId), Error> {
+    let mut session = vm.session(SessionData::builder())?;
+
+    let grower_id = session.deploy(
+        contract_bytecode!("grower"),
+        ContractData::builder().owner(OWNER),
+        GROWER_LIMIT,
+    )?;
+    for _ in 0..4 {
+        session.call_raw(
+            grower_id,
+            "append",
+            [0xffu8; ARGBUF_LEN],
+            GROWER_LIMIT,
+        )?;
+    }
+
+    Ok((session.commit()?, grower_id))
+}
+
+#[test]
+fn page_free_refund() -> Result<(), Error> {
+    const REFUND: u64 = 1;
+
+    let vm = VM::ephemeral()?;
+    let (root, grower_id) = deploy_filled_grower(&vm)?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let receipt =
+        session.call_raw(grower_id, "clear", [0u8; 0], GROWER_LIMIT)?;
+    assert_eq!(receipt.gas_refunded, 0);
+    let base_spent = receipt.gas_spent;
+
+    let schedule = GasSchedule {
+        page_free_refund: REFUND,
+        max_refund_percent: 100,
+        ..GasSchedule::default()
+    };
+    let mut session =
+        vm.session(SessionData::builder().base(root).gas_schedule(schedule))?;
+    let receipt =
+        session.call_raw(grower_id, "clear", [0u8; 0], GROWER_LIMIT)?;
+
+    assert!(receipt.gas_refunded > 0, "Clearing should free pages");
+    assert_eq!(receipt.gas_spent + receipt.gas_refunded, base_spent);
+
+    // Nothing is freed the second time around
+    let receipt =
+        session.call_raw(grower_id, "clear", [0u8; 0], GROWER_LIMIT)?;
+    assert_eq!(receipt.gas_refunded, 0);
+
+    Ok(())
+}
+
+#[test]
+fn refund_cap() -> Result<(), Error> {
+    const MAX_REFUND_PERCENT: u64 = 25;
+
+    let vm = VM::ephemeral()?;
+    let (root, grower_id) = deploy_filled_grower(&vm)?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let base_spent = session
+        .call_raw(grower_id, "clear", [0u8; 0], GROWER_LIMIT)?
+        .gas_spent;
+
+    let schedule = GasSchedule {
+        page_free_refund: GROWER_LIMIT,
+        max_refund_percent: MAX_REFUND_PERCENT,
+        ..GasSchedule::default()
+    };
+    let mut session =
+        vm.session(SessionData::builder().base(root).gas_schedule(schedule))?;
+    let receipt =
+        session.call_raw(grower_id, "clear", [0u8; 0], GROWER_LIMIT)?;
+
+    assert_eq!(receipt.gas_refunded, base_spent * MAX_REFUND_PERCENT / 100);
+    assert_eq!(receipt.gas_spent, base_spent - receipt.gas_refunded);
+
+    Ok(())
+}
+
+#[test]
+fn self_destruct_refund() -> Result<(), Error> {
+    const REFUND: u64 = 10;
+
+    let vm = VM::ephemeral()?;
+    let schedule = GasSchedule {
+        self_destruct_refund: REFUND,
+        max_refund_percent: 100,
+        ..GasSchedule::default()
+    };
+    let mut session =
+        vm.session(SessionData::builder().gas_schedule(schedule))?;
+
+    let vault_id = session.deploy(
+        contract_bytecode!("vault"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let other_id = session.deploy(
+        contract_bytecode!("vault"),
+        ContractData::builder().owner(OWNER).nonce(1),
+        LIMIT,
+    )?;
+
+    session
+        .call::<_, ()>(vault_id, "self_destruct_and_panic", &other_id, LIMIT)
+        .expect_err("The call should panic");
+
+    let receipt = session.call::<_, Result<(), TransferError>>(
+        vault_id,
+        "self_destruct",
+        &other_id,
+        LIMIT,
+    )?;
+    receipt.data.expect("Self-destructing should succeed");
+    assert_eq!(receipt.gas_refunded, REFUND);
+
+    Ok(())
+}
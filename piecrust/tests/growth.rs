@@ -4,7 +4,10 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
-use piecrust::{contract_bytecode, ContractData, Error, SessionData, VM};
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, Session, SessionData,
+    VM,
+};
 use piecrust_uplink::ARGBUF_LEN;
 
 const OWNER: [u8; 32] = [0u8; 32];
@@ -108,3 +111,205 @@ fn error_reverts_growth() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn growth_across_calls_and_commits() -> Result<(), Error> {
+    const CHUNK_LEN: usize = 16 * 1024;
+    const N_CHUNKS: usize = 32;
+
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let grower_id = session.deploy(
+        contract_bytecode!("grower"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let mut grown = false;
+
+    // Append to the grower through the callcenter, growing its memory in an
+    // inter-contract call.
+    for _ in 0..N_CHUNKS {
+        let old_len = session.memory_len(grower_id)?.expect("grower exists");
+
+        let receipt = session.call::<_, Vec<u8>>(
+            center_id,
+            "delegate_transaction",
+            &(grower_id, String::from("append"), vec![42u8; CHUNK_LEN]),
+            LIMIT,
+        )?;
+
+        let new_len = session.memory_len(grower_id)?.expect("grower exists");
+        let growth = receipt
+            .memory_growth
+            .iter()
+            .find(|growth| growth.contract == grower_id);
+
+        if new_len > old_len {
+            let growth = growth.expect("Growth should be in the receipt");
+            assert_eq!(growth.old_len, old_len);
+            assert_eq!(growth.new_len, new_len);
+            grown = true;
+        } else {
+            assert!(growth.is_none(), "No growth should be reported");
+        }
+    }
+    assert!(grown, "The grower's memory should have grown");
+
+    let len = session.memory_len(grower_id)?;
+    let root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(session.memory_len(grower_id)?, len);
+    assert_eq!(grower_len(&mut session, grower_id)?, N_CHUNKS * CHUNK_LEN);
+
+    // Growing further after the commit works on the larger memory.
+    for _ in 0..N_CHUNKS {
+        session.call_raw(grower_id, "append", [42; ARGBUF_LEN], LIMIT)?;
+    }
+    assert_eq!(
+        grower_len(&mut session, grower_id)?,
+        N_CHUNKS * (CHUNK_LEN + ARGBUF_LEN)
+    );
+    assert!(session.memory_len(grower_id)? > len);
+
+    Ok(())
+}
+
+fn grower_len(
+    session: &mut Session,
+    grower_id: ContractId,
+) -> Result<usize, Error> {
+    let len_data = session.call_raw(grower_id, "len", [], LIMIT)?.data;
+
+    let mut len_bytes = [0; 4];
+    len_bytes.copy_from_slice(&len_data);
+    Ok(u32::from_le_bytes(len_bytes) as usize)
+}
+
+#[test]
+fn page_stats() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let grower_id = session.deploy(
+        contract_bytecode!("grower"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let mut grown = false;
+
+    for _ in 0..8 {
+        let receipt = session.call::<_, Vec<u8>>(
+            center_id,
+            "delegate_transaction",
+            &(grower_id, String::from("append"), vec![42u8; 16 * 1024]),
+            LIMIT,
+        )?;
+        let stats = receipt.page_stats;
+
+        // The callcenter's argument buffer and the grower's memory are both
+        // written to.
+        assert!(stats.pages_written >= 2);
+        assert!(stats.pages_read >= stats.pages_written);
+
+        let bytes_grown: usize = receipt
+            .memory_growth
+            .iter()
+            .map(|growth| growth.new_len - growth.old_len)
+            .sum();
+        assert_eq!(stats.bytes_grown, bytes_grown);
+
+        grown |= bytes_grown > 0;
+    }
+    assert!(grown, "The grower's memory should have grown");
+
+    // Reading the grower back touches pages without writing the data
+    let receipt = session.call_raw(grower_id, "len", [], LIMIT)?;
+    let stats = receipt.page_stats;
+    assert!(stats.pages_read >= 1);
+    assert_eq!(stats.bytes_grown, 0);
+
+    Ok(())
+}
+
+#[test]
+fn memory_limit() -> Result<(), Error> {
+    const WASM_PAGE_SIZE: usize = 0x10000;
+
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let grower_id = session.deploy(
+        contract_bytecode!("grower"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let len = session.memory_len(grower_id)?.expect("grower exists");
+    let max_pages = len / WASM_PAGE_SIZE + 1;
+
+    let root = session.commit()?;
+    let mut session = vm.session(
+        SessionData::builder()
+            .base(root)
+            .max_memory_pages(max_pages),
+    )?;
+
+    // Appending eventually grows the memory past the limit, failing the call
+    // well before running out of gas.
+    let err = (0..64)
+        .find_map(|_| {
+            session
+                .call_raw(grower_id, "append", [42; ARGBUF_LEN], LIMIT)
+                .err()
+        })
+        .expect("Growing past the limit should fail");
+    assert!(
+        matches!(err, Error::MemoryLimitExceeded(pages) if pages == max_pages)
+    );
+
+    let len = session.memory_len(grower_id)?.expect("grower exists");
+    assert!(len <= max_pages * WASM_PAGE_SIZE);
+
+    // Growing in an inter-contract call aborts the whole call, rather than
+    // being handled by the caller. The caller frees the argument it passes on
+    // after each call, so it is the callee's vector that grows past the limit.
+    let err = (0..64)
+        .find_map(|_| {
+            session
+                .call::<_, Vec<u8>>(
+                    center_id,
+                    "delegate_transaction",
+                    &(grower_id, String::from("append"), vec![42u8; 16 * 1024]),
+                    LIMIT,
+                )
+                .err()
+        })
+        .expect("Growing past the limit should fail");
+    assert!(
+        matches!(err, Error::MemoryLimitExceeded(pages) if pages == max_pages)
+    );
+
+    Ok(())
+}
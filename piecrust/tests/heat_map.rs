@@ -0,0 +1,99 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{contract_bytecode, ContractData, Error, SessionData, VM};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn disabled_by_default() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    assert!(!vm.heat_map().is_enabled());
+
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.commit()?;
+
+    let summary = vm.heat_map().summary();
+    assert_eq!(summary.sessions, 0);
+    assert!(summary.contracts.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn sampled_sessions() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    vm.heat_map().enable(1);
+
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    // Sessions are recorded when dropped, even without committing
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    drop(session);
+
+    let summary = vm.heat_map().summary();
+    assert_eq!(summary.sessions, 2);
+    assert_eq!(summary.contracts.len(), 1);
+
+    let contract = &summary.contracts[0];
+    assert_eq!(contract.contract_id, id);
+    assert_eq!(contract.sessions, 2);
+    assert!(!contract.pages.is_empty());
+    assert!(contract.pages.iter().all(|page| page.accesses <= 2));
+
+    // Only one in every two sessions is recorded
+    vm.heat_map().enable(2);
+    for _ in 0..4 {
+        let mut session = vm.session(SessionData::builder().base(root))?;
+        session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    }
+    assert_eq!(vm.heat_map().summary().sessions, 4);
+
+    vm.heat_map().disable();
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    drop(session);
+    assert_eq!(vm.heat_map().summary().sessions, 4);
+
+    Ok(())
+}
+
+#[test]
+fn persisted_across_restarts() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    vm.heat_map().enable(1);
+
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    session.commit()?;
+
+    vm.heat_map().persist().expect("Persisting should succeed");
+    let summary = vm.heat_map().summary();
+
+    let vm2 = VM::new(vm.root_dir())?;
+    assert_eq!(vm2.heat_map().summary(), summary);
+    assert_eq!(vm2.heat_map().summary().hottest()[0].contract_id, id);
+
+    Ok(())
+}
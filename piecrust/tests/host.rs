@@ -7,7 +7,8 @@
 use dusk_plonk::prelude::*;
 use once_cell::sync::Lazy;
 use piecrust::{
-    contract_bytecode, ContractData, Error, HostQuery, SessionData, VM,
+    contract_bytecode, ContractData, Error, HostQuery, HostQuerySignature,
+    SessionData, VM,
 };
 use rand::rngs::OsRng;
 use rkyv::Deserialize;
@@ -128,6 +129,110 @@ pub fn host_very_expensive_oog() -> Result<(), Error> {
     Ok(())
 }
 
+fn typed_hash_vm(cost: u64) -> Result<VM, Error> {
+    let mut vm = VM::ephemeral()?;
+    vm.register_typed_host_query(
+        "hash",
+        move |_: &Vec<u8>| cost,
+        |bytes: Vec<u8>| *blake3::hash(&bytes).as_bytes(),
+    );
+    vm.register_host_query("very_expensive", VeryExpensiveQuery);
+    Ok(vm)
+}
+
+#[test]
+pub fn host_typed_query() -> Result<(), Error> {
+    const COST: u64 = 10_000;
+
+    let mut gas_spent = Vec::new();
+
+    for cost in [0, COST] {
+        let vm = typed_hash_vm(cost)?;
+
+        let mut session = vm.session(SessionData::builder())?;
+
+        let id = session.deploy(
+            contract_bytecode!("host"),
+            ContractData::builder().owner(OWNER),
+            LIMIT,
+        )?;
+
+        let v = vec![0u8, 1, 2];
+        let receipt = session
+            .call::<_, [u8; 32]>(id, "host_hash", &v, LIMIT)
+            .expect("query should succeed");
+        assert_eq!(blake3::hash(&[0u8, 1, 2]).as_bytes(), &receipt.data);
+
+        gas_spent.push(receipt.gas_spent);
+    }
+
+    assert_eq!(
+        gas_spent[1] - gas_spent[0],
+        COST,
+        "The contract should be charged the cost of the query"
+    );
+
+    let vm = typed_hash_vm(COST)?;
+    let queries: Vec<_> = vm.host_queries().collect();
+    assert_eq!(
+        queries,
+        vec![
+            (
+                "hash",
+                Some(HostQuerySignature {
+                    arg: std::any::type_name::<Vec<u8>>(),
+                    ret: std::any::type_name::<[u8; 32]>(),
+                })
+            ),
+            ("very_expensive", None),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn host_priced_query() -> Result<(), Error> {
+    const BYTE_PRICE: u64 = 100;
+
+    let v = vec![0u8; 100];
+    let arg_len = rkyv::to_bytes::<_, 256>(&v).unwrap().len() as u64;
+
+    let mut gas_spent = Vec::new();
+
+    for byte_price in [0, BYTE_PRICE] {
+        let mut vm = VM::ephemeral()?;
+        vm.register_priced_host_query(
+            "hash",
+            move |len| len as u64 * byte_price,
+            hash,
+        );
+
+        let mut session = vm.session(SessionData::builder())?;
+
+        let id = session.deploy(
+            contract_bytecode!("host"),
+            ContractData::builder().owner(OWNER),
+            LIMIT,
+        )?;
+
+        let receipt = session
+            .call::<_, [u8; 32]>(id, "host_hash", &v, LIMIT)
+            .expect("query should succeed");
+        assert_eq!(blake3::hash(&v).as_bytes(), &receipt.data);
+
+        gas_spent.push(receipt.gas_spent);
+    }
+
+    assert_eq!(
+        gas_spent[1] - gas_spent[0],
+        arg_len * BYTE_PRICE,
+        "The contract should be charged for each byte of the argument"
+    );
+
+    Ok(())
+}
+
 /// Proves that we know a number `c` such that `a + b = c`.
 #[derive(Default)]
 struct TestCircuit {
@@ -227,3 +332,61 @@ pub fn host_proof() -> Result<(), Error> {
 
     Ok(())
 }
+
+fn always_valid(buf: &mut [u8], _len: u32) -> u32 {
+    let valid_bytes = rkyv::to_bytes::<_, 8>(&true).unwrap();
+    buf[..valid_bytes.len()].copy_from_slice(&valid_bytes);
+    valid_bytes.len() as u32
+}
+
+#[test]
+pub fn host_session_query() -> Result<(), Error> {
+    let vm = new_ephemeral_vm()?;
+
+    let mut consensus = vm.session(SessionData::builder())?;
+    let id = consensus.deploy(
+        contract_bytecode!("host"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = consensus.commit()?;
+
+    let mut consensus = vm.session(SessionData::builder().base(root))?;
+    let mut simulation = vm.session(SessionData::builder().base(root))?;
+
+    simulation.register_host_query("verify_proof", always_valid);
+    simulation.register_host_query("mock_only", always_valid);
+    assert!(simulation.remove_host_query("hash"));
+    assert!(!simulation.remove_host_query("hash"));
+
+    let wrong_proof = (Proof::default(), vec![BlsScalar::default()]);
+
+    let receipt =
+        simulation.call::<_, String>(id, "host_verify", &wrong_proof, LIMIT)?;
+    assert_eq!(receipt.data, "PROOF IS VALID");
+
+    let receipt =
+        consensus.call::<_, String>(id, "host_verify", &wrong_proof, LIMIT)?;
+    assert_eq!(receipt.data, "PROOF IS INVALID");
+
+    let v = vec![0u8, 1, 2];
+    match simulation.call::<_, [u8; 32]>(id, "host_hash", &v, LIMIT) {
+        Err(Error::MissingHostQuery(name)) => assert_eq!(name, "hash"),
+        res => panic!("The removed query should be missing: {res:?}"),
+    }
+    consensus.call::<_, [u8; 32]>(id, "host_hash", &v, LIMIT)?;
+
+    let simulation_names: Vec<_> =
+        simulation.host_queries().map(|(name, _)| name).collect();
+    let consensus_names: Vec<_> =
+        consensus.host_queries().map(|(name, _)| name).collect();
+    let vm_names: Vec<_> = vm.host_queries().map(|(name, _)| name).collect();
+
+    assert_eq!(
+        simulation_names,
+        ["mock_only", "verify_proof", "very_expensive"]
+    );
+    assert_eq!(consensus_names, vm_names);
+
+    Ok(())
+}
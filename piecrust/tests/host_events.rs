@@ -0,0 +1,138 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, Error, HostEvent, SessionData,
+    TransferError, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const NEW_OWNER: [u8; 32] = [1u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn deploy_events() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    assert!(session.host_events().is_empty());
+
+    let bytecode = contract_bytecode!("counter");
+    let id = session.deploy(
+        bytecode,
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    assert_eq!(
+        session.host_events(),
+        [HostEvent::Deploy {
+            contract_id: id,
+            owner: OWNER.to_vec(),
+            bytecode_hash: blake3::hash(bytecode).into(),
+        }]
+    );
+
+    // Failed deployments are not recorded
+    let result = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(id),
+        LIMIT,
+    );
+    assert!(result.is_err());
+    assert_eq!(session.host_events().len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn migrate_events() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    let session = vm.session(SessionData::builder().base(root))?;
+    let bytecode = contract_bytecode!("double_counter");
+    let session = session.migrate(
+        id,
+        bytecode,
+        ContractData::builder().owner(NEW_OWNER),
+        LIMIT,
+        |_, _| Ok(()),
+    )?;
+
+    assert_eq!(
+        session.host_events(),
+        [
+            HostEvent::Migrate {
+                contract_id: id,
+                bytecode_hash: blake3::hash(bytecode).into(),
+            },
+            HostEvent::OwnerChange {
+                contract_id: id,
+                old_owner: OWNER.to_vec(),
+                new_owner: NEW_OWNER.to_vec(),
+            },
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn receipt_events() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let bytecode = contract_bytecode!("vault");
+    let receipt = session.deploy_with_receipt(
+        bytecode,
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let vault_id = receipt.contract_id;
+    assert_eq!(
+        receipt.host_events,
+        [HostEvent::Deploy {
+            contract_id: vault_id,
+            owner: OWNER.to_vec(),
+            bytecode_hash: blake3::hash(bytecode).into(),
+        }]
+    );
+
+    let other_id = session.deploy(
+        bytecode,
+        ContractData::builder().owner(OWNER).nonce(1),
+        LIMIT,
+    )?;
+
+    // Calls only include the host events emitted while they were executed
+    let receipt = session.call::<_, u64>(vault_id, "balance", &(), LIMIT)?;
+    assert!(receipt.host_events.is_empty());
+
+    let receipt = session.call::<_, Result<(), TransferError>>(
+        vault_id,
+        "self_destruct",
+        &other_id,
+        LIMIT,
+    )?;
+    receipt.data.expect("Self-destructing should succeed");
+    assert_eq!(
+        receipt.host_events,
+        [HostEvent::Remove {
+            contract_id: vault_id
+        }]
+    );
+    assert_eq!(session.host_events().len(), 3);
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::io::Read;
+
+use piecrust::{
+    contract_bytecode, ContractData, Error, Session, SessionData, VM,
+};
+use piecrust_uplink::ContractId;
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+fn read_memory(
+    session: &mut Session,
+    contract_id: ContractId,
+) -> Result<Vec<u8>, Error> {
+    let mut reader = session
+        .contract_memory(contract_id)?
+        .expect("The contract should exist");
+
+    let mut memory = Vec::new();
+    reader
+        .read_to_end(&mut memory)
+        .expect("Reading the memory should succeed");
+
+    Ok(memory)
+}
+
+#[test]
+fn contract_ids() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    assert_eq!(session.contract_ids().count(), 0);
+
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let box_id = session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let fibonacci_id = session.deploy(
+        contract_bytecode!("fibonacci"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let mut expected = vec![counter_id, box_id, fibonacci_id];
+    expected.sort();
+    assert_eq!(session.contract_ids().collect::<Vec<_>>(), expected);
+
+    let root = session.commit()?;
+
+    let session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(
+        session.contract_ids().collect::<Vec<_>>(),
+        expected,
+        "Contracts inherited from a base commit should be included"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn contract_memory() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+
+    let before = read_memory(&mut session, id)?;
+    assert_eq!(Some(before.len()), session.memory_len(id)?);
+
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+
+    let after = read_memory(&mut session, id)?;
+    assert_eq!(before.len(), after.len());
+    assert_ne!(before, after, "The increment should show in the memory");
+
+    assert!(
+        session
+            .contract_memory(ContractId::from_bytes([0xff; 32]))?
+            .is_none(),
+        "A contract that doesn't exist should have no memory"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,300 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::thread;
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractError, ContractId, Error,
+    SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn max_instances() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    let mut session =
+        vm.session(SessionData::builder().base(root).max_instances(1))?;
+
+    // Calling itself does not instantiate another contract
+    let callstack = session
+        .call::<_, Vec<ContractId>>(
+            center_id,
+            "call_self_n_times",
+            &2u32,
+            LIMIT,
+        )?
+        .data;
+    assert_eq!(callstack.len(), 3);
+
+    let result = session.call::<_, ()>(
+        center_id,
+        "increment_counter",
+        &counter_id,
+        LIMIT,
+    );
+    assert!(matches!(result, Err(Error::TooManyInstances(1))));
+
+    let mut session =
+        vm.session(SessionData::builder().base(root).max_instances(2))?;
+    session.call::<_, ()>(
+        center_id,
+        "increment_counter",
+        &counter_id,
+        LIMIT,
+    )?;
+    assert_eq!(
+        session
+            .call::<_, i64>(counter_id, "read_value", &(), LIMIT)?
+            .data,
+        0xfd
+    );
+
+    Ok(())
+}
+
+#[test]
+fn max_call_depth() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    let mut session =
+        vm.session(SessionData::builder().base(root).max_call_depth(3))?;
+
+    let callstack = session
+        .call::<_, Vec<ContractId>>(
+            center_id,
+            "call_self_n_times",
+            &2u32,
+            LIMIT,
+        )?
+        .data;
+    assert_eq!(callstack.len(), 3);
+
+    let result = session.call::<_, Vec<ContractId>>(
+        center_id,
+        "call_self_n_times",
+        &3u32,
+        LIMIT,
+    );
+    assert!(
+        matches!(result, Err(Error::Panic(_))),
+        "The contract should panic on the failed call"
+    );
+
+    let result = session
+        .call::<_, Result<Vec<ContractId>, ContractError>>(
+            center_id,
+            "try_call_self_n_times",
+            &3u32,
+            LIMIT,
+        )?
+        .data;
+    assert!(
+        matches!(result, Err(ContractError::CallDepthExceeded)),
+        "The failed call should be handled by the contract"
+    );
+
+    let depth = session
+        .call::<_, u32>(center_id, "call_self_within_depth", &10u32, LIMIT)?
+        .data;
+    assert_eq!(depth, 3, "The contract should stop at the maximum depth");
+
+    Ok(())
+}
+
+#[test]
+fn calls_independent_of_host_stack() -> Result<(), Error> {
+    const N: u32 = 16;
+
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    // Calls are made on a thread of their own, so the thread calling the
+    // session needs little stack for a chain of calls of any depth
+    let callstack = thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn_scoped(scope, || {
+                session
+                    .call::<_, Vec<ContractId>>(
+                        center_id,
+                        "call_self_n_times",
+                        &N,
+                        LIMIT,
+                    )
+                    .map(|receipt| receipt.data)
+            })
+            .expect("Spawning the thread should succeed")
+            .join()
+            .expect("The thread should not panic")
+    })?;
+    assert_eq!(callstack.len(), N as usize + 1);
+
+    Ok(())
+}
+
+#[test]
+fn session_moved_between_calls() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let callstack = session
+        .call::<_, Vec<ContractId>>(
+            center_id,
+            "call_self_n_times",
+            &4u32,
+            LIMIT,
+        )?
+        .data;
+    assert_eq!(callstack.len(), 5);
+
+    // Moving the session moves none of the instances it owns, including
+    // those of the contracts re-entered by a nested call
+    let mut session = Box::new(session);
+    session.call::<_, ()>(
+        center_id,
+        "increment_counter",
+        &counter_id,
+        LIMIT,
+    )?;
+
+    let mut session = thread::spawn(move || {
+        session.call::<_, ()>(
+            center_id,
+            "increment_counter",
+            &counter_id,
+            LIMIT,
+        )?;
+        let callstack = session
+            .call::<_, Vec<ContractId>>(
+                center_id,
+                "call_self_n_times",
+                &4u32,
+                LIMIT,
+            )?
+            .data;
+        assert_eq!(callstack, vec![center_id; 5]);
+        Ok::<_, Error>(session)
+    })
+    .join()
+    .expect("The thread should not panic")?;
+
+    let value = session
+        .call::<_, i64>(center_id, "query_counter", &counter_id, LIMIT)?
+        .data;
+    assert_eq!(value, 0xfe);
+
+    Ok(())
+}
+
+#[test]
+fn instance_across_calls() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    // The counter is instantiated once, and called three times
+    let values = session
+        .call::<_, Vec<Result<i64, ContractError>>>(
+            center_id,
+            "query_counters",
+            &vec![counter_id; 3],
+            LIMIT,
+        )?
+        .data;
+    assert_eq!(values.len(), 3);
+    for value in values {
+        assert_eq!(value.expect("Querying should succeed"), 0xfc);
+    }
+
+    // Returns of a contract calling itself are read from, and written to, the
+    // same instance
+    let called_self = session
+        .call::<_, Result<bool, ContractError>>(
+            center_id,
+            "call_self",
+            &(),
+            LIMIT,
+        )?
+        .data;
+    assert!(matches!(called_self, Ok(true)));
+
+    // A failed call drops every instance, and the next call creates them anew
+    session
+        .call::<_, ()>(center_id, "panik", &(), LIMIT)
+        .expect_err("Panicking should fail the call");
+
+    session.call::<_, ()>(
+        center_id,
+        "increment_counter",
+        &counter_id,
+        LIMIT,
+    )?;
+    let values = session
+        .call::<_, Vec<Result<i64, ContractError>>>(
+            center_id,
+            "query_counters",
+            &vec![counter_id; 2],
+            LIMIT,
+        )?
+        .data;
+    assert_eq!(values.len(), 2);
+    for value in values {
+        assert_eq!(value.expect("Querying should succeed"), 0xfd);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::sync::{Arc, Mutex};
+
+use piecrust::{
+    contract_bytecode, CallInterceptor, CallOutcome, ContractData,
+    ContractError, ContractId, Error, InterceptedCall, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+type Seen = Arc<Mutex<Vec<(ContractId, String, usize, Option<bool>)>>>;
+
+/// Records every call it sees, with whether it succeeded once it ends.
+struct Recorder(Seen);
+
+impl CallInterceptor for Recorder {
+    fn before_call(&mut self, call: &InterceptedCall) -> Result<(), String> {
+        let mut seen = self.0.lock().unwrap();
+        seen.push((call.contract, call.fn_name.into(), call.depth, None));
+        Ok(())
+    }
+
+    fn after_call(&mut self, call: &InterceptedCall, outcome: &CallOutcome) {
+        let mut seen = self.0.lock().unwrap();
+        seen.push((
+            call.contract,
+            call.fn_name.into(),
+            call.depth,
+            Some(outcome.success),
+        ));
+    }
+}
+
+/// Denies every call to a contract.
+struct Deny(ContractId);
+
+impl CallInterceptor for Deny {
+    fn before_call(&mut self, call: &InterceptedCall) -> Result<(), String> {
+        if call.contract == self.0 {
+            return Err(String::from("denied"));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn calls_intercepted() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let seen = Seen::default();
+    session.set_call_interceptor(Box::new(Recorder(seen.clone())));
+
+    let value: i64 = session
+        .call(center_id, "query_counter", &counter_id, LIMIT)?
+        .data;
+    assert_eq!(value, 0xfc);
+
+    let query = String::from("query_counter");
+    let read = String::from("read_value");
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            (center_id, query.clone(), 0, None),
+            (counter_id, read.clone(), 1, None),
+            (counter_id, read, 1, Some(true)),
+            (center_id, query, 0, Some(true)),
+        ]
+    );
+
+    assert!(session.remove_call_interceptor().is_some());
+    seen.lock().unwrap().clear();
+
+    session.call::<_, i64>(counter_id, "read_value", &(), LIMIT)?;
+    assert!(seen.lock().unwrap().is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn calls_denied() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    session.set_call_interceptor(Box::new(Deny(counter_id)));
+
+    let err = session
+        .call::<_, i64>(counter_id, "read_value", &(), LIMIT)
+        .expect_err("Calling a denied contract should fail");
+    assert!(matches!(err, Error::CallDenied(_)));
+
+    // Contracts calling a denied contract see the call fail
+    let counter_ids = vec![counter_id, counter_id];
+    let values: Vec<Result<i64, ContractError>> = session
+        .call(center_id, "query_counters", &counter_ids, LIMIT)?
+        .data;
+    assert!(values.iter().all(Result::is_err));
+
+    session.remove_call_interceptor();
+    let value = session.call::<_, i64>(counter_id, "read_value", &(), LIMIT)?;
+    assert_eq!(value.data, 0xfc);
+
+    Ok(())
+}
@@ -0,0 +1,155 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, Journal, JournalEntry,
+    Root, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+fn deploy_counter(vm: &VM) -> Result<(Root, ContractId), Error> {
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    Ok((session.commit()?, id))
+}
+
+#[test]
+fn replay_journal() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let (base, counter_id) = deploy_counter(&vm)?;
+
+    let mut session = vm.session(
+        SessionData::builder()
+            .base(base)
+            .insert("height", 42u64)?
+            .journal(),
+    )?;
+
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+
+    let checkpoint = session.checkpoint()?;
+    let box_id = session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(box_id, "set", &0x11i16, LIMIT)?;
+    session.revert_to(checkpoint)?;
+
+    session.set_meta("height", 43u64)?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    session
+        .call::<_, ()>(counter_id, "increment", &(), 1)
+        .expect_err("Calling with too little gas should fail");
+
+    let journal = session.take_journal().expect("The session keeps a journal");
+    assert!(session.journal().is_none());
+    assert_eq!(journal.root, Some(session.root()));
+    assert_eq!(journal.entries.len(), 8);
+
+    let bytes = journal.to_bytes().expect("Serializing should succeed");
+    let journal =
+        Journal::from_bytes(&bytes).expect("Deserializing should succeed");
+
+    let mut replayed = vm.session(SessionData::builder().base(base))?;
+    let root = replayed.replay(&journal)?;
+
+    assert_eq!(root, session.root());
+    assert_eq!(replayed.meta("height"), session.meta("height"));
+
+    // The call that ran out of gas is replayed too, but fails again, leaving
+    // the counter incremented only twice.
+    for session in [&mut session, &mut replayed] {
+        assert_eq!(
+            session
+                .call::<_, i64>(counter_id, "read_value", &(), LIMIT)?
+                .data,
+            0xfe
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn replay_divergence() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let (base, counter_id) = deploy_counter(&vm)?;
+
+    let mut session =
+        vm.session(SessionData::builder().base(base).journal())?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    let mut journal =
+        session.take_journal().expect("The session keeps a journal");
+
+    let mut replayed = vm.session(SessionData::builder())?;
+    match replayed.replay(&journal) {
+        Err(Error::ReplayBaseMismatch { expected, found }) => {
+            assert_eq!(expected, Some(base));
+            assert_eq!(found, None);
+        }
+        res => panic!("Replaying on another base should fail: {res:?}"),
+    }
+
+    journal.entries.push(JournalEntry::Call {
+        contract: counter_id,
+        fn_name: "increment".into(),
+        fn_arg: Vec::new(),
+        gas_limit: LIMIT,
+    });
+
+    let mut replayed = vm.session(SessionData::builder().base(base))?;
+    match replayed.replay(&journal) {
+        Err(Error::ReplayDivergence { expected, found }) => {
+            assert_eq!(expected, session.root());
+            assert_eq!(found, replayed.root());
+        }
+        res => panic!("Replaying a different journal should fail: {res:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn replay_merged() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let (base, counter_id) = deploy_counter(&vm)?;
+
+    let mut session =
+        vm.session(SessionData::builder().base(base).journal())?;
+    let mut other = vm.session(SessionData::builder().base(base).journal())?;
+
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+
+    // Checkpoints left in place in the other session are released by merging
+    other.checkpoint()?;
+    let box_id = other.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    other.call::<_, ()>(box_id, "set", &0x11i16, LIMIT)?;
+    let checkpoint = other.checkpoint()?;
+    other.call::<_, ()>(box_id, "set", &0x22i16, LIMIT)?;
+    other.revert_to(checkpoint)?;
+
+    let checkpoint = session.checkpoint()?;
+    session.merge(other)?;
+    session.release(checkpoint)?;
+
+    let journal = session.take_journal().expect("The session keeps a journal");
+
+    let mut replayed = vm.session(SessionData::builder().base(base))?;
+    assert_eq!(replayed.replay(&journal)?, session.root());
+
+    Ok(())
+}
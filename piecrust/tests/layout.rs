@@ -0,0 +1,154 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fs;
+use std::path::PathBuf;
+
+use piecrust::{
+    check_layout, contract_bytecode, layout_spec, ContractData, ContractId,
+    Error, LayoutRule, Root, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+const COUNTER_ID: ContractId = ContractId::from_bytes([1; 32]);
+
+/// Commits the counter, and then an increment on top of it, returning both
+/// roots.
+fn commit_twice(vm: &VM) -> Result<(Root, Root), Error> {
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(COUNTER_ID),
+        LIMIT,
+    )?;
+    let base = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(base))?;
+    session.call::<_, ()>(COUNTER_ID, "increment", &(), LIMIT)?;
+    let diff = session.commit()?;
+
+    Ok((base, diff))
+}
+
+#[test]
+fn layout_ok() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let (base, _) = commit_twice(&vm)?;
+
+    let report = check_layout(vm.root_dir()).expect("checking should succeed");
+    assert!(report.is_ok(), "{report}");
+    assert_eq!(report.commits, 2);
+    assert_eq!(report.contracts, 1);
+
+    vm.finalize_commit(base)?;
+
+    let report = check_layout(vm.root_dir()).expect("checking should succeed");
+    assert!(report.is_ok(), "{report}");
+    assert_eq!(report.commits, 1);
+
+    Ok(())
+}
+
+#[test]
+fn layout_issues() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let (base, _) = commit_twice(&vm)?;
+
+    let main_dir = vm.root_dir().join("main");
+    let counter_hex = hex::encode(COUNTER_ID);
+
+    // An unknown entry, a leftover temporary file, and missing metadata
+    fs::write(main_dir.join("junk"), b"junk").unwrap();
+    fs::write(main_dir.join("bytecode").join("x.tmp"), b"").unwrap();
+    fs::remove_file(main_dir.join("bytecode").join(format!("{counter_hex}.m")))
+        .unwrap();
+
+    // A commit directory of the counter belonging to no commit
+    let orphan: PathBuf = main_dir
+        .join("memory")
+        .join(&counter_hex)
+        .join(hex::encode([7u8; 32]));
+    fs::create_dir_all(orphan).unwrap();
+
+    // The pages written by the first commit
+    let pages_dir = main_dir
+        .join("memory")
+        .join(&counter_hex)
+        .join(hex::encode(base));
+    for entry in fs::read_dir(pages_dir).unwrap() {
+        fs::remove_file(entry.unwrap().path()).unwrap();
+    }
+
+    let report = check_layout(vm.root_dir()).expect("checking should succeed");
+    assert!(!report.is_ok());
+
+    for rule in [
+        LayoutRule::EntryName,
+        LayoutRule::Temporary,
+        LayoutRule::BytecodeFiles,
+        LayoutRule::Orphan,
+        LayoutRule::Page,
+    ] {
+        assert!(
+            report.issues_with(rule).next().is_some(),
+            "{rule} should be violated:\n{report}"
+        );
+    }
+
+    // The report is one line per issue, after the line with the counts
+    let report_str = report.to_string();
+    assert_eq!(report_str.lines().count(), report.issues.len() + 1);
+
+    Ok(())
+}
+
+#[test]
+fn store_version() -> Result<(), Error> {
+    let tmp = tempfile::tempdir().expect("Creating a tempdir works");
+
+    let vm = VM::new(tmp.path())?;
+    commit_twice(&vm)?;
+    drop(vm);
+
+    let version_path = tmp.path().join("version");
+    let version = fs::read_to_string(&version_path).unwrap();
+
+    // A store of another version is reported, and refused by the VM
+    fs::write(&version_path, b"1").unwrap();
+    let report = check_layout(tmp.path()).expect("checking should succeed");
+    assert_eq!(
+        report.issues_with(LayoutRule::Version).count(),
+        1,
+        "{report}"
+    );
+    VM::new(tmp.path()).expect_err("Opening the store should fail");
+
+    // One recording no version at all is reported, but migrated when opened
+    fs::remove_file(&version_path).unwrap();
+    let report = check_layout(tmp.path()).expect("checking should succeed");
+    assert_eq!(
+        report.issues_with(LayoutRule::Version).count(),
+        1,
+        "{report}"
+    );
+    VM::new(tmp.path())?;
+
+    assert_eq!(fs::read_to_string(&version_path).unwrap(), version);
+    let report = check_layout(tmp.path()).expect("checking should succeed");
+    assert!(report.is_ok(), "{report}");
+
+    Ok(())
+}
+
+#[test]
+fn spec_lists_every_rule() {
+    let spec = layout_spec();
+    for rule in LayoutRule::ALL {
+        assert!(spec.contains(rule.code()), "{rule} should be documented");
+    }
+}
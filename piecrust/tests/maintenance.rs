@@ -0,0 +1,69 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, Error, MaintenancePlan, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn run_maintenance() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    let plan = MaintenancePlan::new(SessionData::builder())
+        .call(id, "increment", &(), LIMIT)?
+        .call(id, "non_existent", &(), LIMIT)?
+        .call(id, "increment", &(), LIMIT)?;
+    assert_eq!(plan.calls().len(), 3);
+
+    let receipt = vm.run_maintenance(root, plan)?;
+
+    assert_eq!(receipt.calls.len(), 3);
+    assert!(receipt.calls[0].is_ok());
+    assert!(
+        receipt.calls[1].is_err(),
+        "Calling a non-existent function should fail"
+    );
+    assert!(
+        receipt.calls[2].is_ok(),
+        "A failed call should not stop the rest of the plan"
+    );
+
+    let spent_ok: u64 = receipt
+        .calls
+        .iter()
+        .filter_map(|call| call.as_ref().ok())
+        .map(|receipt| receipt.gas_spent)
+        .sum();
+    assert!(receipt.gas_spent >= spent_ok);
+
+    assert_ne!(receipt.root, root);
+
+    let mut session = vm.session(SessionData::builder().base(receipt.root))?;
+    assert_eq!(
+        session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data,
+        0xfe
+    );
+
+    // The commit the plan was run on is left untouched
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(
+        session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data,
+        0xfc
+    );
+
+    Ok(())
+}
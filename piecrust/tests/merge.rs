@@ -0,0 +1,102 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, Root, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+const COUNTER_1: ContractId = ContractId::from_bytes([1; 32]);
+const COUNTER_2: ContractId = ContractId::from_bytes([2; 32]);
+
+/// Commits two counters, returning the root of the commit.
+fn deploy_counters(vm: &VM) -> Result<Root, Error> {
+    let mut session = vm.session(SessionData::builder())?;
+
+    for id in [COUNTER_1, COUNTER_2] {
+        session.deploy(
+            contract_bytecode!("counter"),
+            ContractData::builder().owner(OWNER).contract_id(id),
+            LIMIT,
+        )?;
+    }
+
+    session.commit()
+}
+
+#[test]
+fn disjoint_sessions_merged() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let base = deploy_counters(&vm)?;
+
+    let mut session_1 = vm.session(SessionData::builder().base(base))?;
+    let mut session_2 = vm.session(SessionData::builder().base(base))?;
+
+    session_1.lock_contracts(&[COUNTER_1])?;
+    session_2.lock_contracts(&[COUNTER_2])?;
+
+    match session_2.lock_contracts(&[COUNTER_1, COUNTER_2]) {
+        Err(Error::ContractsLocked(locked)) => {
+            assert_eq!(locked, vec![COUNTER_1])
+        }
+        res => panic!("Locking a held contract should fail: {res:?}"),
+    }
+
+    session_1.call::<_, ()>(COUNTER_1, "increment", &(), LIMIT)?;
+    session_2.call::<_, ()>(COUNTER_2, "increment", &(), LIMIT)?;
+    session_2.call::<_, ()>(COUNTER_2, "increment", &(), LIMIT)?;
+
+    session_1.merge(session_2)?;
+    assert_eq!(
+        session_1.locked_contracts().copied().collect::<Vec<_>>(),
+        vec![COUNTER_1, COUNTER_2]
+    );
+
+    let root = session_1.commit()?;
+
+    // The locks are released once the session is committed
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    session.lock_contracts(&[COUNTER_1, COUNTER_2])?;
+
+    let value_1: i64 = session.call(COUNTER_1, "read_value", &(), LIMIT)?.data;
+    let value_2: i64 = session.call(COUNTER_2, "read_value", &(), LIMIT)?.data;
+    assert_eq!((value_1, value_2), (0xfd, 0xfe));
+
+    Ok(())
+}
+
+#[test]
+fn conflicting_sessions_not_merged() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let base = deploy_counters(&vm)?;
+
+    let mut session_1 = vm.session(SessionData::builder().base(base))?;
+    let mut session_2 = vm.session(SessionData::builder().base(base))?;
+
+    // Both sessions touch the first counter, one only reading it
+    session_1.call::<_, ()>(COUNTER_1, "increment", &(), LIMIT)?;
+    session_2.call::<_, i64>(COUNTER_1, "read_value", &(), LIMIT)?;
+    session_2.call::<_, ()>(COUNTER_2, "increment", &(), LIMIT)?;
+
+    match session_1.merge(session_2) {
+        Err(Error::MergeConflict(conflicts)) => {
+            assert_eq!(conflicts, vec![COUNTER_1])
+        }
+        res => panic!("Merging conflicting sessions should fail: {res:?}"),
+    }
+
+    // The session is left untouched by the failed merge
+    let value: i64 = session_1.call(COUNTER_2, "read_value", &(), LIMIT)?.data;
+    assert_eq!(value, 0xfc);
+
+    // Sessions with different bases can't be merged
+    let session_3 = vm.session(SessionData::builder())?;
+    assert!(session_1.merge(session_3).is_err());
+
+    Ok(())
+}
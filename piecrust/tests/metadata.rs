@@ -123,3 +123,49 @@ fn owner_of() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn metadata_nonce_and_init_arg() -> Result<(), Error> {
+    const OWNER: [u8; 32] = [0u8; 32];
+    const NONCE: u64 = 42;
+
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("initializer"),
+        ContractData::builder().owner(OWNER).init_arg(&0xabu8),
+        LIMIT,
+    )?;
+    let nonce_id = session.deploy(
+        contract_bytecode!("initializer"),
+        ContractData::builder()
+            .owner(OWNER)
+            .init_arg(&0xcdu8)
+            .nonce(NONCE),
+        LIMIT,
+    )?;
+    assert_ne!(id, nonce_id, "The nonce should change the contract ID");
+
+    let commit_id = session.commit()?;
+    let vm = VM::new(vm.root_dir())?;
+    let mut session = vm.session(SessionData::builder().base(commit_id))?;
+
+    let metadata = session
+        .contract_metadata(&id)
+        .expect("The contract should exist");
+    assert_eq!(metadata.contract_id, id);
+    assert_eq!(metadata.owner, OWNER);
+    assert_eq!(metadata.nonce, 0);
+    assert_eq!(metadata.init_arg.as_deref(), Some(&[0xab][..]));
+
+    let metadata = session
+        .contract_metadata(&nonce_id)
+        .expect("The contract should exist");
+    assert_eq!(metadata.contract_id, nonce_id);
+    assert_eq!(metadata.nonce, NONCE);
+    assert_eq!(metadata.init_arg.as_deref(), Some(&[0xcd][..]));
+
+    Ok(())
+}
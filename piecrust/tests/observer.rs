@@ -0,0 +1,114 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, Session, SessionData,
+    VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+fn deploy(session: &mut Session) -> Result<(ContractId, ContractId), Error> {
+    let eventer_id = session.deploy(
+        contract_bytecode!("eventer"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let observer_id = session.deploy(
+        contract_bytecode!("observer"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    Ok((eventer_id, observer_id))
+}
+
+fn read_count(
+    session: &mut Session,
+    id: ContractId,
+) -> Result<(u32, Option<String>), Error> {
+    Ok(session
+        .call::<_, (u32, Option<String>)>(id, "read_count", &(), LIMIT)?
+        .data)
+}
+
+#[test]
+fn observer_notified() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let (eventer_id, observer_id) = deploy(&mut session)?;
+
+    session.call::<_, ()>(observer_id, "observe", &eventer_id, LIMIT)?;
+
+    let receipt =
+        session.call::<_, ()>(eventer_id, "emit_events", &3u32, LIMIT)?;
+    assert_eq!(receipt.events.len(), 3);
+    assert_eq!(receipt.notifications.len(), 3);
+    for notification in &receipt.notifications {
+        assert_eq!(notification.observer, observer_id);
+        assert_eq!(notification.source, eventer_id);
+        assert_eq!(notification.topic, "number");
+        assert!(notification.result.is_ok());
+        assert!(notification.gas_spent > 0);
+    }
+    let notified_spent: u64 =
+        receipt.notifications.iter().map(|n| n.gas_spent).sum();
+    assert!(receipt.gas_spent > notified_spent);
+
+    assert_eq!(
+        read_count(&mut session, observer_id)?,
+        (3, Some(String::from("number")))
+    );
+
+    // Once unobserved, the observer is no longer notified
+    session.call::<_, ()>(observer_id, "unobserve", &eventer_id, LIMIT)?;
+
+    let receipt =
+        session.call::<_, ()>(eventer_id, "emit_events", &3u32, LIMIT)?;
+    assert!(receipt.notifications.is_empty());
+    assert_eq!(read_count(&mut session, observer_id)?.0, 3);
+
+    Ok(())
+}
+
+#[test]
+fn observe_dropped_on_failure() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let (eventer_id, observer_id) = deploy(&mut session)?;
+
+    session
+        .call::<_, ()>(observer_id, "observe_and_panic", &eventer_id, LIMIT)
+        .expect_err("The call should panic");
+
+    let receipt =
+        session.call::<_, ()>(eventer_id, "emit_events", &3u32, LIMIT)?;
+    assert!(receipt.notifications.is_empty());
+    assert_eq!(read_count(&mut session, observer_id)?, (0, None));
+
+    Ok(())
+}
+
+#[test]
+fn notification_gas_limit() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session =
+        vm.session(SessionData::builder().notification_gas_limit(1))?;
+    let (eventer_id, observer_id) = deploy(&mut session)?;
+
+    session.call::<_, ()>(observer_id, "observe", &eventer_id, LIMIT)?;
+
+    // The notifications run out of gas, but the call still succeeds
+    let receipt =
+        session.call::<_, ()>(eventer_id, "emit_events", &2u32, LIMIT)?;
+    assert_eq!(receipt.notifications.len(), 2);
+    for notification in &receipt.notifications {
+        assert!(notification.result.is_err());
+    }
+    assert_eq!(read_count(&mut session, observer_id)?, (0, None));
+
+    Ok(())
+}
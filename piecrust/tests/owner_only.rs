@@ -0,0 +1,111 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, Error, SessionData, SENDER_META, VM,
+};
+
+const OWNER: [u8; 32] = [1u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn owner_only_from_host() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder()
+            .owner(OWNER)
+            .owner_only(["increment"]),
+        LIMIT,
+    )?;
+
+    // Without a sender, or with one that isn't the owner, the call is refused
+    let err = session
+        .call::<_, ()>(id, "increment", &(), LIMIT)
+        .expect_err("Calling without a sender should fail");
+    assert!(matches!(err, Error::UnauthorizedCall(contract, ref fn_name)
+        if contract == id && fn_name == "increment"));
+
+    session.set_meta(SENDER_META, [2u8; 32])?;
+    session
+        .call::<_, ()>(id, "increment", &(), LIMIT)
+        .expect_err("Calling with another sender should fail");
+
+    // Functions that aren't owner-only can be called by anyone
+    let value = session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data;
+    assert_eq!(value, 0xfc);
+
+    session.set_meta(SENDER_META, OWNER)?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+
+    // The owner-only functions are kept across commits
+    let root = session.commit()?;
+    let mut session = vm.session(SessionData::builder().base(root))?;
+
+    session
+        .call::<_, ()>(id, "increment", &(), LIMIT)
+        .expect_err("Calling without a sender should fail");
+
+    session.set_meta(SENDER_META, OWNER)?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+
+    let value = session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data;
+    assert_eq!(value, 0xfe);
+
+    Ok(())
+}
+
+#[test]
+fn owner_only_from_contract() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let owned_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder()
+            .owner(center_id.as_bytes())
+            .owner_only(["increment"]),
+        LIMIT,
+    )?;
+    let other_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder()
+            .owner(OWNER)
+            .owner_only(["increment"])
+            .nonce(1),
+        LIMIT,
+    )?;
+
+    // The sender of an inter-contract call is the calling contract, no matter
+    // the sender declared for the call made by the host.
+    session.set_meta(SENDER_META, OWNER)?;
+
+    session.call::<_, ()>(center_id, "increment_counter", &owned_id, LIMIT)?;
+    session
+        .call::<_, ()>(center_id, "increment_counter", &other_id, LIMIT)
+        .expect_err("The callcenter doesn't own the contract");
+
+    let value = session
+        .call::<_, i64>(owned_id, "read_value", &(), LIMIT)?
+        .data;
+    assert_eq!(value, 0xfd);
+
+    let value = session
+        .call::<_, i64>(other_id, "read_value", &(), LIMIT)?
+        .data;
+    assert_eq!(value, 0xfc);
+
+    Ok(())
+}
@@ -4,8 +4,13 @@
 //
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+
 use piecrust::{
-    contract_bytecode, ContractData, ContractId, Error, SessionData, VM,
+    contract_bytecode, ContractData, ContractId, Error, Root, Session,
+    SessionData, UpgradeRequest, VM,
 };
 
 const OWNER: [u8; 32] = [0u8; 32];
@@ -316,3 +321,404 @@ fn migration_self_id_remains_same() -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Stands in for a signature scheme in the upgrade tests: the authorization
+/// of an upgrade is its digest, with each byte xored with one of the owner's.
+fn sign(request: &UpgradeRequest) -> Vec<u8> {
+    request
+        .digest()
+        .iter()
+        .zip(request.owner.iter().cycle())
+        .map(|(digest, owner)| digest ^ owner)
+        .collect()
+}
+
+/// Returns the authorization of the given `owner` for upgrading `contract`
+/// to the `double_counter`, passing it the contract's ID as the argument of
+/// the `migration_fn`.
+fn authorize(
+    owner: &[u8],
+    contract: ContractId,
+    migration_fn: &str,
+) -> Result<Vec<u8>, Error> {
+    let fn_arg = Session::serialize_data(&contract)?;
+    Ok(sign(&UpgradeRequest {
+        contract,
+        owner,
+        bytecode: contract_bytecode!("double_counter"),
+        migration_fn,
+        fn_arg: &fn_arg,
+    }))
+}
+
+fn upgrading_vm() -> Result<VM, Error> {
+    let mut vm = VM::ephemeral()?;
+    vm.set_upgrade_authorizer(
+        |request: &UpgradeRequest, authorization: &[u8]| {
+            authorization == sign(request)
+        },
+    );
+    Ok(vm)
+}
+
+#[test]
+fn upgrade() -> Result<(), Error> {
+    let vm = upgrading_vm()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let contract = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(contract, "increment", &(), LIMIT)?;
+
+    let root = session.commit()?;
+    let mut session = vm.session(SessionData::builder().base(root))?;
+
+    let receipt = session.upgrade::<_, i64>(
+        contract,
+        contract_bytecode!("double_counter"),
+        authorize(&OWNER, contract, "migrate_from")?,
+        "migrate_from",
+        &contract,
+        LIMIT,
+    )?;
+    assert_eq!(receipt.data, 0xfd);
+
+    let contract_ids: Vec<_> = session.contract_ids().collect();
+    assert_eq!(contract_ids, [contract]);
+
+    let root = session.commit()?;
+    let mut session = vm.session(SessionData::builder().base(root))?;
+
+    let values = session
+        .call::<_, (i64, i64)>(contract, "read_values", &(), LIMIT)?
+        .data;
+    assert_eq!(values, (0xfd, 0xcf));
+
+    Ok(())
+}
+
+#[test]
+fn upgrade_unauthorized_or_failed() -> Result<(), Error> {
+    const OTHER_OWNER: [u8; 32] = [1u8; 32];
+
+    let vm = upgrading_vm()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let contract = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.root();
+
+    let upgrade = |session: &mut Session, authorization: Vec<u8>| {
+        session.upgrade::<_, i64>(
+            contract,
+            contract_bytecode!("double_counter"),
+            authorization,
+            "migrate_from",
+            &contract,
+            LIMIT,
+        )
+    };
+    let unauthorized = |result| matches!(result, Err(Error::UpgradeUnauthorized(id)) if id == contract);
+
+    // Authorized by someone other than the owner
+    let authorization = authorize(&OTHER_OWNER, contract, "migrate_from")?;
+    assert!(unauthorized(upgrade(&mut session, authorization)));
+
+    // Authorized by the owner, but for a different upgrade
+    let authorization = authorize(&OWNER, contract, "migrate")?;
+    assert!(unauthorized(upgrade(&mut session, authorization)));
+
+    // The owner itself is no authorization
+    assert!(unauthorized(upgrade(&mut session, OWNER.to_vec())));
+
+    session
+        .upgrade::<_, ()>(
+            contract,
+            contract_bytecode!("double_counter"),
+            sign(&UpgradeRequest {
+                contract,
+                owner: &OWNER,
+                bytecode: contract_bytecode!("double_counter"),
+                migration_fn: "missing",
+                fn_arg: &Session::serialize_data(&())?,
+            }),
+            "missing",
+            &(),
+            LIMIT,
+        )
+        .expect_err("Calling a missing migration function should fail");
+
+    session
+        .upgrade::<_, (i64, i64)>(
+            contract,
+            contract_bytecode!("double_counter"),
+            authorize(&OWNER, contract, "migrate_from")?,
+            "migrate_from",
+            &contract,
+            LIMIT,
+        )
+        .expect_err("Deserializing the wrong migration result should fail");
+
+    // The failed upgrades leave the old contract in place
+    assert_eq!(session.root(), root);
+    let contract_ids: Vec<_> = session.contract_ids().collect();
+    assert_eq!(contract_ids, [contract]);
+    assert_eq!(
+        session
+            .call::<_, i64>(contract, "read_value", &(), LIMIT)?
+            .data,
+        0xfc
+    );
+
+    // Without an authorizer, no upgrade is authorized
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let contract = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let authorization = authorize(&OWNER, contract, "migrate_from")?;
+    assert!(unauthorized(upgrade(&mut session, authorization)));
+
+    Ok(())
+}
+
+/// Returns the roots of the commits in the given `vm`, along with their bases.
+fn commits_with_parents(vm: &VM) -> BTreeMap<Root, Option<Root>> {
+    vm.commit_infos()
+        .into_iter()
+        .map(|info| (info.root, info.parent))
+        .collect()
+}
+
+#[test]
+fn commit_index() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let index_path = vm.root_dir().join("commits");
+
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let base = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(base))?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    let child = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(base))?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    let deleted = session.commit()?;
+    vm.delete_commit(deleted)?;
+
+    let commits = commits_with_parents(&vm);
+    assert_eq!(commits, BTreeMap::from([(base, None), (child, Some(base))]));
+
+    // The commits are loaded from the index
+    assert!(index_path.is_file());
+    let vm = VM::new(vm.root_dir())?;
+    assert_eq!(commits_with_parents(&vm), commits);
+
+    let mut session = vm.session(SessionData::builder().base(child))?;
+    assert_eq!(
+        session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data,
+        0xfd
+    );
+
+    // A corrupt index is discarded in favor of scanning the directory, and
+    // written again
+    let mut index = fs::read(&index_path).expect("Reading the index");
+    index[1] ^= 0xff;
+    fs::write(&index_path, &index).expect("Corrupting the index");
+    let vm = VM::new(vm.root_dir())?;
+    assert_eq!(commits_with_parents(&vm), commits);
+    assert_ne!(fs::read(&index_path).expect("Reading the index"), index);
+
+    // So is a missing one
+    fs::remove_file(&index_path).expect("Removing the index");
+    let vm = VM::new(vm.root_dir())?;
+    assert_eq!(commits_with_parents(&vm), commits);
+    assert!(index_path.is_file());
+
+    Ok(())
+}
+
+#[test]
+fn commit_missing_from_index() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let index_path = vm.root_dir().join("commits");
+
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let base = session.commit()?;
+    let index = fs::read(&index_path).expect("Reading the index");
+
+    let mut session = vm.session(SessionData::builder().base(base))?;
+    let id = session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let unindexed = session.commit()?;
+
+    // Crashing after the commit is written, but before it's indexed, leaves
+    // the index without it
+    fs::write(&index_path, index).expect("Writing the index");
+
+    let vm = VM::new(vm.root_dir())?;
+    let commits = commits_with_parents(&vm);
+    assert_eq!(
+        commits,
+        BTreeMap::from([(base, None), (unindexed, Some(base))])
+    );
+
+    // And the rewritten index includes it
+    let vm = VM::new(vm.root_dir())?;
+    assert_eq!(commits_with_parents(&vm), commits);
+
+    let mut session = vm.session(SessionData::builder().base(unindexed))?;
+    assert_eq!(
+        session.call::<_, Option<i16>>(id, "get", &(), LIMIT)?.data,
+        None
+    );
+
+    Ok(())
+}
+
+#[test]
+fn shared_bytecode() -> Result<(), Error> {
+    const FIRST_ID: ContractId = ContractId::from_bytes([1; 32]);
+    const SECOND_ID: ContractId = ContractId::from_bytes([2; 32]);
+
+    let vm = VM::ephemeral()?;
+    let main_dir = vm.root_dir().join("main");
+
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(FIRST_ID),
+        LIMIT,
+    )?;
+    let base = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(base))?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(SECOND_ID),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(SECOND_ID, "increment", &(), LIMIT)?;
+    let root = session.commit()?;
+
+    // Both contracts link to the same bytecode and module
+    let inode = |contract: ContractId, extension: &str| {
+        let mut path = main_dir.join("bytecode").join(hex::encode(contract));
+        path.set_extension(extension);
+        fs::metadata(path).expect("Reading the metadata").ino()
+    };
+    assert_eq!(inode(FIRST_ID, ""), inode(SECOND_ID, ""));
+    assert_eq!(inode(FIRST_ID, "a"), inode(SECOND_ID, "a"));
+    assert_ne!(inode(FIRST_ID, "m"), inode(SECOND_ID, "m"));
+
+    let code_dir = main_dir.join("code");
+    assert_eq!(fs::read_dir(code_dir).expect("Reading code").count(), 2);
+
+    let vm = VM::new(vm.root_dir())?;
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(
+        session
+            .call::<_, i64>(FIRST_ID, "read_value", &(), LIMIT)?
+            .data,
+        0xfc
+    );
+    assert_eq!(
+        session
+            .call::<_, i64>(SECOND_ID, "read_value", &(), LIMIT)?
+            .data,
+        0xfd
+    );
+
+    Ok(())
+}
+
+#[test]
+fn migrate_unversioned_store() -> Result<(), Error> {
+    const FIRST_ID: ContractId = ContractId::from_bytes([1; 32]);
+    const SECOND_ID: ContractId = ContractId::from_bytes([2; 32]);
+
+    let tmp = tempfile::tempdir().expect("Creating a tempdir works");
+    let main_dir = tmp.path().join("main");
+
+    let vm = VM::new(tmp.path())?;
+    let mut session = vm.session(SessionData::builder())?;
+    for contract_id in [FIRST_ID, SECOND_ID] {
+        session.deploy(
+            contract_bytecode!("counter"),
+            ContractData::builder()
+                .owner(OWNER)
+                .contract_id(contract_id),
+            LIMIT,
+        )?;
+    }
+    session.call::<_, ()>(SECOND_ID, "increment", &(), LIMIT)?;
+    let root = session.commit()?;
+    drop(vm);
+
+    // Lay the store out as it was before versions were recorded, with no
+    // index and a copy of the bytecode for every contract.
+    fs::remove_file(tmp.path().join("version")).unwrap();
+    fs::remove_file(tmp.path().join("commits")).unwrap();
+    fs::remove_dir_all(main_dir.join("code")).unwrap();
+    for contract in [FIRST_ID, SECOND_ID] {
+        let path = main_dir.join("bytecode").join(hex::encode(contract));
+        for path in [path.clone(), path.with_extension("a")] {
+            let bytes = fs::read(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+            fs::write(&path, bytes).unwrap();
+        }
+    }
+
+    let vm = VM::new(tmp.path())?;
+    assert_eq!(vm.commits(), vec![root]);
+
+    let version = fs::read_to_string(tmp.path().join("version")).unwrap();
+    assert_eq!(version, "3");
+
+    let inode = |contract: ContractId, extension: &str| {
+        let mut path = main_dir.join("bytecode").join(hex::encode(contract));
+        path.set_extension(extension);
+        fs::metadata(path).expect("Reading the metadata").ino()
+    };
+    assert_eq!(inode(FIRST_ID, ""), inode(SECOND_ID, ""));
+    assert_eq!(inode(FIRST_ID, "a"), inode(SECOND_ID, "a"));
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(
+        session
+            .call::<_, i64>(FIRST_ID, "read_value", &(), LIMIT)?
+            .data,
+        0xfc
+    );
+    assert_eq!(
+        session
+            .call::<_, i64>(SECOND_ID, "read_value", &(), LIMIT)?
+            .data,
+        0xfd
+    );
+
+    Ok(())
+}
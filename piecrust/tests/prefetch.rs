@@ -0,0 +1,92 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, Session, SessionData,
+    VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn hint_next_contracts() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let box_id = session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    let root = session.commit()?;
+
+    let missing_id = ContractId::from_bytes([42; 32]);
+
+    // Reading the counter writes to its memory, so both sessions make the
+    // exact same calls, with the hints being the only difference
+    let calls = |session: &mut Session, hint: bool| {
+        if hint {
+            session.hint_next_contracts(&[counter_id, box_id, missing_id]);
+        }
+        let before = session
+            .call::<_, i64>(counter_id, "read_value", &(), LIMIT)?
+            .data;
+        session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+
+        // Hinting at an already loaded contract is a no-op
+        if hint {
+            session.hint_next_contracts(&[counter_id]);
+        }
+        let after = session
+            .call::<_, i64>(counter_id, "read_value", &(), LIMIT)?
+            .data;
+
+        session.call::<i16, ()>(box_id, "set", &0x11, LIMIT)?;
+        Ok::<_, Error>((before, after))
+    };
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let values = calls(&mut session, true)?;
+    assert_eq!(values, (0xfd, 0xfe));
+    let hinted_root = session.commit()?;
+
+    // The same calls without hints lead to the same root
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(calls(&mut session, false)?, values);
+    assert_eq!(session.commit()?, hinted_root);
+
+    Ok(())
+}
+
+#[test]
+fn hint_then_drop() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    for _ in 0..8 {
+        let mut session = vm.session(SessionData::builder().base(root))?;
+        session.hint_next_contracts(&[counter_id]);
+    }
+
+    vm.delete_commit(root)?;
+    assert!(vm.commits().is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,181 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, Error, Producer, SessionData, Version,
+    UPLINK_VERSION_SECTION, VM,
+};
+use piecrust_uplink::UPLINK_VERSION;
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+fn push_name(buf: &mut Vec<u8>, name: &str) {
+    // All lengths used here are small enough to fit in a single LEB128 byte.
+    assert!(name.len() < 0x80);
+    buf.push(name.len() as u8);
+    buf.extend_from_slice(name.as_bytes());
+}
+
+fn push_custom_section(bytecode: &mut Vec<u8>, name: &str, contents: &[u8]) {
+    let mut payload = Vec::new();
+    push_name(&mut payload, name);
+    payload.extend_from_slice(contents);
+
+    assert!(payload.len() < 0x80);
+    bytecode.push(0);
+    bytecode.push(payload.len() as u8);
+    bytecode.extend(payload);
+}
+
+/// A module with no custom sections, exporting only what the host requires.
+const NO_PROVENANCE: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+    0x05, 0x03, 0x01, 0x00, 0x02, // memory section
+    0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x00, 0x0b, // global section
+    0x07, 0x0e, 0x02, 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00,
+    0x01, b'A', 0x03, 0x00, // export section
+];
+
+fn bytecode_with_provenance(uplink_version: &str) -> Vec<u8> {
+    let mut bytecode = NO_PROVENANCE.to_vec();
+
+    let mut producers = vec![2];
+    push_name(&mut producers, "language");
+    producers.push(1);
+    push_name(&mut producers, "Rust");
+    push_name(&mut producers, "");
+    push_name(&mut producers, "processed-by");
+    producers.push(1);
+    push_name(&mut producers, "rustc");
+    push_name(&mut producers, "1.75.0-nightly");
+
+    push_custom_section(&mut bytecode, "producers", &producers);
+    push_custom_section(
+        &mut bytecode,
+        UPLINK_VERSION_SECTION,
+        uplink_version.as_bytes(),
+    );
+
+    bytecode
+}
+
+#[test]
+fn provenance() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        &bytecode_with_provenance("0.17.3"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let provenance = &session
+        .contract_metadata(&id)
+        .expect("contract should exist")
+        .provenance;
+
+    assert_eq!(
+        provenance.language,
+        [Producer {
+            name: "Rust".into(),
+            version: "".into(),
+        }]
+    );
+    assert_eq!(
+        provenance.processed_by,
+        [Producer {
+            name: "rustc".into(),
+            version: "1.75.0-nightly".into(),
+        }]
+    );
+    assert!(provenance.sdk.is_empty());
+    assert_eq!(provenance.uplink_version(), Some(Version::new(0, 17, 3)));
+
+    // provenance is read back from the bytecode in later sessions
+    let root = session.commit()?;
+    let mut session = vm.session(SessionData::builder().base(root))?;
+
+    let provenance = &session
+        .contract_metadata(&id)
+        .expect("contract should exist")
+        .provenance;
+    assert_eq!(provenance.uplink_version.as_deref(), Some("0.17.3"));
+
+    Ok(())
+}
+
+#[test]
+fn built_contract_provenance() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    // Stripping keeps the sections provenance is read from
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("micro"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let provenance = &session
+        .contract_metadata(&id)
+        .expect("contract should exist")
+        .provenance;
+
+    assert_eq!(provenance.uplink_version.as_deref(), Some(UPLINK_VERSION));
+    assert!(provenance.language.iter().any(|p| p.name == "Rust"));
+    assert!(provenance.processed_by.iter().any(|p| p.name == "rustc"));
+
+    Ok(())
+}
+
+#[test]
+fn min_uplink_version() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session =
+        vm.session(SessionData::builder().min_uplink_version((0, 18, 0)))?;
+
+    let result = session.deploy(
+        &bytecode_with_provenance("0.17.3"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    );
+    assert!(matches!(
+        result,
+        Err(Error::UnsupportedUplinkVersion { found: Some(_), .. })
+    ));
+
+    let result = session.deploy(
+        NO_PROVENANCE,
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    );
+    assert!(matches!(
+        result,
+        Err(Error::UnsupportedUplinkVersion { found: None, .. })
+    ));
+
+    session.deploy(
+        &bytecode_with_provenance("0.18.0"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    // Contracts built in-tree are accepted at the version they were built with
+    let version =
+        Version::parse(UPLINK_VERSION).expect("uplink version should parse");
+    let mut session =
+        vm.session(SessionData::builder().min_uplink_version(version))?;
+    session.deploy(
+        contract_bytecode!("micro"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,251 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractError, Error, SessionData,
+    SENDER_META, VM,
+};
+use piecrust_uplink::PURE_SECTION;
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn pure_from_section() -> Result<(), Error> {
+    let bytecode = contract_bytecode!("pure_counter");
+
+    // The section must survive the contract being stripped
+    let section_name = PURE_SECTION.as_bytes();
+    assert!(
+        bytecode
+            .windows(section_name.len())
+            .any(|window| window == section_name),
+        "the stripped contract should keep its {PURE_SECTION} section"
+    );
+
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        bytecode,
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let metadata = session.contract_metadata(&id).expect("Contract exists");
+    assert_eq!(
+        metadata.pure,
+        vec!["read_value", "read_value_plus", "read_other"]
+    );
+
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(!receipt.cached);
+    assert_eq!(receipt.data, 0xfc);
+    let gas_spent = receipt.gas_spent;
+
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(receipt.cached);
+    assert_eq!(receipt.data, 0xfc);
+    assert_eq!(receipt.gas_spent, gas_spent);
+    assert_eq!(receipt.call_tree.iter().count(), 0);
+
+    // Results are cached per argument
+    let receipt =
+        session.call::<_, i64>(id, "read_value_plus", &1i64, LIMIT)?;
+    assert!(!receipt.cached);
+    assert_eq!(receipt.data, 0xfd);
+    let receipt =
+        session.call::<_, i64>(id, "read_value_plus", &2i64, LIMIT)?;
+    assert!(!receipt.cached);
+    assert_eq!(receipt.data, 0xfe);
+    let receipt =
+        session.call::<_, i64>(id, "read_value_plus", &1i64, LIMIT)?;
+    assert!(receipt.cached);
+    assert_eq!(receipt.data, 0xfd);
+
+    // Functions that aren't pure are always executed, and changing the state
+    // of the contract outdates its cached results
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(!receipt.cached);
+    assert_eq!(receipt.data, 0xfd);
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(receipt.cached);
+    assert_eq!(receipt.data, 0xfd);
+
+    Ok(())
+}
+
+#[test]
+fn pure_from_builder() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).pure(["read_value"]),
+        LIMIT,
+    )?;
+
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(!receipt.cached);
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(receipt.cached);
+
+    // The declaration is persisted with the contract's metadata
+    let root = session.commit()?;
+    let mut session = vm.session(SessionData::builder().base(root))?;
+
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(!receipt.cached);
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(receipt.cached);
+    assert_eq!(receipt.data, 0xfc);
+
+    Ok(())
+}
+
+#[test]
+fn pure_outdated_by_called_contracts() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let pure_id = session.deploy(
+        contract_bytecode!("pure_counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    type ReadResult = Result<i64, ContractError>;
+
+    let receipt = session.call::<_, ReadResult>(
+        pure_id,
+        "read_other",
+        &counter_id,
+        LIMIT,
+    )?;
+    assert!(!receipt.cached);
+    assert!(matches!(receipt.data, Ok(0xfc)));
+
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+
+    let receipt = session.call::<_, ReadResult>(
+        pure_id,
+        "read_other",
+        &counter_id,
+        LIMIT,
+    )?;
+    assert!(!receipt.cached);
+    assert!(matches!(receipt.data, Ok(0xfd)));
+
+    let receipt = session.call::<_, ReadResult>(
+        pure_id,
+        "read_other",
+        &counter_id,
+        LIMIT,
+    )?;
+    assert!(receipt.cached);
+    assert!(matches!(receipt.data, Ok(0xfd)));
+
+    Ok(())
+}
+
+#[test]
+fn pure_outdated_by_session_changes() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("pure_counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let checkpoint = session.checkpoint()?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert_eq!(receipt.data, 0xfd);
+
+    session.revert_to(checkpoint)?;
+
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(!receipt.cached);
+    assert_eq!(receipt.data, 0xfc);
+
+    session.set_meta("height", 1u64)?;
+
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(!receipt.cached);
+
+    // A result spending more than the gas limit is not returned from the
+    // cache, but executed within the limit instead
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(receipt.cached);
+    let gas_limit = receipt.gas_spent - 1;
+    match session.call::<_, i64>(id, "read_value", &(), gas_limit) {
+        Ok(receipt) => {
+            assert!(!receipt.cached);
+            assert!(receipt.gas_spent <= gas_limit);
+        }
+        Err(Error::OutOfGas) => {}
+        Err(err) => panic!("The call should be executed, got: {err:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn pure_cache_capacity() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session =
+        vm.session(SessionData::builder().pure_cache_capacity(0))?;
+
+    let id = session.deploy(
+        contract_bytecode!("pure_counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(!receipt.cached);
+
+    Ok(())
+}
+
+#[test]
+fn pure_owner_only() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("pure_counter"),
+        ContractData::builder()
+            .owner(OWNER)
+            .owner_only(["read_value"]),
+        LIMIT,
+    )?;
+
+    session.set_meta(SENDER_META, OWNER)?;
+    session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    let receipt = session.call::<_, i64>(id, "read_value", &(), LIMIT)?;
+    assert!(receipt.cached);
+
+    // Cached results are only returned to the owner
+    session.set_meta(SENDER_META, [1u8; 32])?;
+    session
+        .call::<_, i64>(id, "read_value", &(), LIMIT)
+        .expect_err("Calling with another sender should fail");
+
+    Ok(())
+}
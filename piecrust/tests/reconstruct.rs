@@ -0,0 +1,99 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, Root, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+const COUNTER_ID: ContractId = ContractId::from_bytes([1; 32]);
+
+/// Deploys the counter in two distinct commits, returning their roots.
+fn deploy_twice(vm: &VM) -> Result<(Root, Root), Error> {
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(COUNTER_ID),
+        LIMIT,
+    )?;
+    let root_1 = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(COUNTER_ID),
+        LIMIT,
+    )?;
+    session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root_2 = session.commit()?;
+
+    Ok((root_1, root_2))
+}
+
+/// Removes the counter's memory pages written by the given commit, returning
+/// how many were removed.
+fn remove_pages(root_dir: &Path, root: Root) -> usize {
+    let dir: PathBuf = root_dir
+        .join("main")
+        .join("memory")
+        .join(hex::encode(COUNTER_ID))
+        .join(hex::encode(root));
+
+    let mut removed = 0;
+    for entry in fs::read_dir(dir).expect("Memory directory should exist") {
+        fs::remove_file(entry.unwrap().path()).unwrap();
+        removed += 1;
+    }
+    removed
+}
+
+#[test]
+fn reconstruct() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let (root_1, root_2) = deploy_twice(&vm)?;
+
+    let removed = remove_pages(vm.root_dir(), root_1);
+    assert!(removed > 0);
+
+    assert_eq!(vm.reconstruct(root_1, COUNTER_ID)?, removed);
+    assert_eq!(vm.reconstruct(root_1, COUNTER_ID)?, 0);
+
+    let mut session = vm.session(SessionData::builder().base(root_1))?;
+    let value: i64 = session.call(COUNTER_ID, "read_value", &(), LIMIT)?.data;
+    assert_eq!(value, 0xfc);
+
+    remove_pages(vm.root_dir(), root_1);
+    remove_pages(vm.root_dir(), root_2);
+    assert!(vm.reconstruct(root_1, COUNTER_ID).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn reconstruct_on_load() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let (root_1, _) = deploy_twice(&vm)?;
+
+    remove_pages(vm.root_dir(), root_1);
+
+    let vm2 = VM::new(vm.root_dir())?;
+    assert!(vm2.commits().contains(&root_1));
+
+    let mut session = vm2.session(SessionData::builder().base(root_1))?;
+    let value: i64 = session.call(COUNTER_ID, "read_value", &(), LIMIT)?.data;
+    assert_eq!(value, 0xfc);
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, HostEvent, Session,
+    SessionData, TransferError, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+fn deploy_counter(session: &mut Session) -> Result<ContractId, Error> {
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )
+}
+
+fn deploy_vaults(
+    session: &mut Session,
+) -> Result<(ContractId, ContractId), Error> {
+    let vault_id = session.deploy(
+        contract_bytecode!("vault"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let other_id = session.deploy(
+        contract_bytecode!("vault"),
+        ContractData::builder().owner(OWNER).nonce(1),
+        LIMIT,
+    )?;
+    Ok((vault_id, other_id))
+}
+
+#[test]
+fn remove_from_host() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = deploy_counter(&mut session)?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    let root_before = session.root();
+
+    session.remove_contract(id)?;
+
+    let err = session
+        .call::<_, i64>(id, "read_value", &(), LIMIT)
+        .expect_err("Calling a removed contract should fail");
+    assert!(matches!(err, Error::ContractDoesNotExist(c) if c == id));
+    assert!(matches!(
+        session.remove_contract(id),
+        Err(Error::ContractDoesNotExist(_))
+    ));
+
+    assert_eq!(session.contract_ids().count(), 0);
+    assert_ne!(session.root(), root_before);
+    assert_eq!(
+        session.host_events().last(),
+        Some(&HostEvent::Remove { contract_id: id })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn remove_persists() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = deploy_counter(&mut session)?;
+    let root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    session.remove_contract(id)?;
+    let removed_root = session.commit()?;
+    assert_ne!(root, removed_root);
+
+    // The removal is kept across commits and reloads, while the commit it was
+    // removed in still holds the contract
+    let vm = VM::new(vm.root_dir())?;
+
+    let mut session = vm.session(SessionData::builder().base(removed_root))?;
+    session
+        .call::<_, i64>(id, "read_value", &(), LIMIT)
+        .expect_err("Calling a removed contract should fail");
+    assert_eq!(session.contract_ids().count(), 0);
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let value = session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data;
+    assert_eq!(value, 0xfc);
+
+    // A removed contract may be deployed again under the same ID, starting
+    // over from a fresh state
+    let mut session = vm.session(SessionData::builder().base(removed_root))?;
+    assert_eq!(deploy_counter(&mut session)?, id);
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    let redeployed_root = session.commit()?;
+
+    let mut session =
+        vm.session(SessionData::builder().base(redeployed_root))?;
+    let value = session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data;
+    assert_eq!(value, 0xfd);
+
+    Ok(())
+}
+
+#[test]
+fn remove_reverted() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = deploy_counter(&mut session)?;
+    let root = session.root();
+
+    let checkpoint = session.checkpoint()?;
+    session.remove_contract(id)?;
+    session.revert_to(checkpoint)?;
+
+    assert_eq!(session.root(), root);
+    let value = session.call::<_, i64>(id, "read_value", &(), LIMIT)?.data;
+    assert_eq!(value, 0xfc);
+
+    Ok(())
+}
+
+#[test]
+fn self_destruct() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let (vault_id, other_id) = deploy_vaults(&mut session)?;
+
+    session.set_balance(vault_id, 100);
+
+    assert_eq!(
+        session
+            .call::<_, Result<(), TransferError>>(
+                vault_id,
+                "self_destruct",
+                &vault_id,
+                LIMIT,
+            )?
+            .data,
+        Err(TransferError::RecipientDoesNotExist)
+    );
+
+    session
+        .call::<_, Result<(), TransferError>>(
+            vault_id,
+            "self_destruct",
+            &other_id,
+            LIMIT,
+        )?
+        .data
+        .expect("Self-destructing should succeed");
+
+    assert_eq!(session.balance(&vault_id), 0);
+    assert_eq!(session.balance(&other_id), 100);
+
+    session
+        .call::<_, u64>(vault_id, "balance", &(), LIMIT)
+        .expect_err("Calling a destroyed contract should fail");
+    assert_eq!(session.contract_ids().collect::<Vec<_>>(), vec![other_id]);
+
+    Ok(())
+}
+
+#[test]
+fn self_destruct_undone_on_failure() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let (vault_id, other_id) = deploy_vaults(&mut session)?;
+
+    session.set_balance(vault_id, 100);
+
+    session
+        .call::<_, ()>(vault_id, "self_destruct_and_panic", &other_id, LIMIT)
+        .expect_err("The call should panic");
+
+    assert_eq!(session.balance(&vault_id), 100);
+    assert_eq!(session.balance(&other_id), 0);
+    assert_eq!(
+        session
+            .call::<_, u64>(vault_id, "balance", &(), LIMIT)?
+            .data,
+        100
+    );
+
+    Ok(())
+}
@@ -5,7 +5,8 @@
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
 use piecrust::{
-    contract_bytecode, ContractData, Error, PageOpening, SessionData, VM,
+    contract_bytecode, verify_proof, ContractData, ContractId, Error,
+    PageOpening, ParseRootError, Root, SessionData, VM,
 };
 
 const OWNER: [u8; 32] = [0u8; 32];
@@ -119,3 +120,148 @@ pub fn inclusion_proofs() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+pub fn contract_state_proofs() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let box_id = session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root_1 = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root_1))?;
+
+    let proof = session
+        .prove_contract_state(counter_id)
+        .expect("There must be a proof for the contract");
+    assert_eq!(proof.contract_id(), &counter_id);
+    assert!(
+        verify_proof(root_1, &proof),
+        "The proof must be valid for the root"
+    );
+
+    assert!(
+        session
+            .prove_contract_state(ContractId::from_bytes([42; 32]))
+            .is_none(),
+        "There must be no proof for a non-existing contract"
+    );
+
+    session.call::<i16, ()>(box_id, "set", &0x11, LIMIT)?;
+    let root_2 = session.root();
+
+    let box_proof = session
+        .prove_contract_state(box_id)
+        .expect("There must be a proof for the contract");
+    assert!(
+        verify_proof(root_2, &box_proof),
+        "The proof must be valid for the new root"
+    );
+    assert!(
+        !verify_proof(root_1, &box_proof),
+        "The proof must be invalid for the old root"
+    );
+
+    // The proof of the unchanged contract is still valid for the new root
+    let proof = session
+        .prove_contract_state(counter_id)
+        .expect("There must be a proof for the contract");
+    assert!(verify_proof(root_2, &proof));
+
+    assert_eq!(session.commit()?, root_2);
+
+    Ok(())
+}
+
+#[test]
+pub fn typed_roots() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    let hex = root.to_string();
+    assert_eq!(hex.parse(), Ok(root));
+    assert_eq!(format!("{root:#}").parse(), Ok(root));
+    assert_eq!(
+        "0x00".parse::<Root>(),
+        Err(ParseRootError::InvalidLength(2))
+    );
+    assert_eq!(
+        hex.replace(&hex[..1], "g").parse::<Root>(),
+        Err(ParseRootError::InvalidCharacter('g'))
+    );
+
+    // Roots are accepted wherever a root is expected
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(session.root(), root);
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    let next_root = session.commit()?;
+
+    vm.finalize_commit(next_root)?;
+
+    let id_hex = id.to_string();
+    assert_eq!(id_hex.parse::<ContractId>(), Ok(id));
+    assert_eq!(format!("{id:#}").parse::<ContractId>(), Ok(id));
+
+    Ok(())
+}
+
+#[test]
+pub fn incremental_root() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let box_id = session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let base = session.commit()?;
+
+    // Computing the root between calls only hashes what each call wrote
+    let mut session = vm.session(SessionData::builder().base(base))?;
+    assert_eq!(session.root(), base);
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    let root_1 = session.root();
+    session.call::<i16, ()>(box_id, "set", &0x11, LIMIT)?;
+    let root_2 = session.root();
+
+    let checkpoint = session.checkpoint()?;
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    assert_ne!(session.root(), root_2);
+    session.revert_to(checkpoint)?;
+    assert_eq!(
+        session.root(),
+        root_2,
+        "Reverting to a checkpoint should revert the root"
+    );
+
+    // The same calls without computing intermediate roots
+    let mut fresh = vm.session(SessionData::builder().base(base))?;
+    fresh.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    assert_eq!(fresh.root(), root_1);
+    fresh.call::<i16, ()>(box_id, "set", &0x11, LIMIT)?;
+    assert_eq!(fresh.commit()?, root_2);
+
+    assert_eq!(session.commit()?, root_2);
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::thread;
+use std::time::Duration;
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+#[test]
+fn commits_while_paused() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root_1 = session.commit()?;
+
+    vm.scheduler().pause();
+    assert!(vm.scheduler().is_paused());
+
+    thread::scope(|s| {
+        let deletion = s.spawn(|| vm.delete_commit(root_1));
+
+        while vm.scheduler().queue_depth() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // Commits still go through, even with the deletion queued. The
+        // counter is incremented so the commit differs from the one queued
+        // for deletion.
+        let mut session = vm.session(SessionData::builder())?;
+        let counter_id = session.deploy(
+            contract_bytecode!("counter"),
+            ContractData::builder()
+                .owner(OWNER)
+                .contract_id(ContractId::from_bytes([1; 32])),
+            LIMIT,
+        )?;
+        session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+        let root_2 = session.commit()?;
+        assert_ne!(root_2, root_1);
+
+        assert_eq!(vm.scheduler().queue_depth(), 1);
+        assert!(vm.commits().contains(&root_2));
+
+        vm.scheduler().resume();
+        deletion.join().expect("Deletion should not panic")?;
+
+        assert_eq!(vm.scheduler().queue_depth(), 0);
+        assert!(!vm.commits().contains(&root_1));
+        assert!(vm.commits().contains(&root_2));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn set_concurrency() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    vm.scheduler().set_concurrency(0);
+    assert_eq!(vm.scheduler().concurrency(), 1);
+
+    vm.scheduler().set_concurrency(4);
+    assert_eq!(vm.scheduler().concurrency(), 4);
+
+    let mut roots = Vec::new();
+    for i in 0..4 {
+        let mut session = vm.session(SessionData::builder())?;
+        session.deploy(
+            contract_bytecode!("counter"),
+            ContractData::builder()
+                .owner(OWNER)
+                .contract_id(ContractId::from_bytes([i; 32])),
+            LIMIT,
+        )?;
+        roots.push(session.commit()?);
+    }
+
+    for root in roots {
+        vm.delete_commit(root)?;
+    }
+    assert!(vm.commits().is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,159 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use piecrust::{ContractData, Error, SessionData, SCRATCH_MEMORY, VM};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+/// Appends a vector of the given items, all short enough for their lengths to
+/// be encoded in a single byte.
+fn push_vec(bytes: &mut Vec<u8>, items: &[&[u8]]) {
+    bytes.push(items.len() as u8);
+    for item in items {
+        bytes.extend_from_slice(item);
+    }
+}
+
+fn push_section(bytes: &mut Vec<u8>, id: u8, items: &[&[u8]]) {
+    let mut content = Vec::new();
+    push_vec(&mut content, items);
+
+    assert!(content.len() < 0x80);
+    bytes.push(id);
+    bytes.push(content.len() as u8);
+    bytes.extend(content);
+}
+
+fn export(name: &str, kind: u8, index: u8) -> Vec<u8> {
+    let mut bytes = vec![name.len() as u8];
+    bytes.extend_from_slice(name.as_bytes());
+    bytes.extend([kind, index]);
+    bytes
+}
+
+fn body(code: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![code.len() as u8 + 1, 0x00];
+    bytes.extend_from_slice(code);
+    bytes
+}
+
+/// Assembles a 32-bit contract with two memories, exported under the given
+/// names in the order of their indices.
+///
+/// It has two functions: `roundtrip` copies its `u32` argument to the start
+/// of memory one, and returns it incremented after reading it back, while
+/// `read_scratch` returns whatever is at the start of memory one.
+fn scratch_contract(memory_names: [&str; 2]) -> Vec<u8> {
+    let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+
+    // (func (param i32) (result i32))
+    push_section(&mut bytes, 1, &[&[0x60, 0x01, 0x7f, 0x01, 0x7f]]);
+    push_section(&mut bytes, 3, &[&[0x00], &[0x00]]);
+    // Two pages of memory zero, for the argument buffer, and one of memory
+    // one
+    push_section(&mut bytes, 5, &[&[0x00, 0x02], &[0x00, 0x01]]);
+    // (global i32 (i32.const 0)), pointing at the argument buffer
+    push_section(&mut bytes, 6, &[&[0x7f, 0x00, 0x41, 0x00, 0x0b]]);
+    push_section(
+        &mut bytes,
+        7,
+        &[
+            &export(memory_names[0], 0x02, 0),
+            &export(memory_names[1], 0x02, 1),
+            &export("A", 0x03, 0),
+            &export("roundtrip", 0x00, 0),
+            &export("read_scratch", 0x00, 1),
+        ],
+    );
+    push_section(
+        &mut bytes,
+        10,
+        &[
+            &body(&[
+                0x41, 0x00, // i32.const 0
+                0x41, 0x00, // i32.const 0
+                0x28, 0x02, 0x00, // i32.load
+                0x36, 0x42, 0x01, 0x00, // i32.store (memory 1)
+                0x41, 0x00, // i32.const 0
+                0x41, 0x00, // i32.const 0
+                0x28, 0x42, 0x01, 0x00, // i32.load (memory 1)
+                0x41, 0x01, // i32.const 1
+                0x6a, // i32.add
+                0x36, 0x02, 0x00, // i32.store
+                0x41, 0x04, // i32.const 4
+                0x0b, // end
+            ]),
+            &body(&[
+                0x41, 0x00, // i32.const 0
+                0x41, 0x00, // i32.const 0
+                0x28, 0x42, 0x01, 0x00, // i32.load (memory 1)
+                0x36, 0x02, 0x00, // i32.store
+                0x41, 0x04, // i32.const 4
+                0x0b, // end
+            ]),
+        ],
+    );
+
+    bytes
+}
+
+#[test]
+fn scratch_memory() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        &scratch_contract(["memory", SCRATCH_MEMORY]),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let value: u32 = session.call(id, "roundtrip", &41u32, LIMIT)?.data;
+    assert_eq!(value, 42);
+
+    // The scratch memory starts zeroed on every call
+    let value: u32 = session.call(id, "read_scratch", &(), LIMIT)?.data;
+    assert_eq!(value, 0);
+
+    let root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    session.call::<_, u32>(id, "roundtrip", &41u32, LIMIT)?;
+    let value: u32 = session.call(id, "read_scratch", &(), LIMIT)?.data;
+    assert_eq!(value, 0);
+
+    // Writes to the scratch memory are never committed, leaving the state
+    // just as it was
+    assert_eq!(session.commit()?, root);
+
+    Ok(())
+}
+
+#[test]
+fn scratch_memory_conventions() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let result = session.deploy(
+        &scratch_contract(["memory", "spare"]),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    );
+    assert!(result.is_err(), "Unnamed second memory should be rejected");
+
+    let result = session.deploy(
+        &scratch_contract([SCRATCH_MEMORY, "memory"]),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    );
+    assert!(
+        matches!(result, Err(Error::InvalidMemory)),
+        "Scratch memory should come after the main memory"
+    );
+
+    Ok(())
+}
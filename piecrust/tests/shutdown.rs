@@ -0,0 +1,58 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::time::Duration;
+
+use piecrust::{contract_bytecode, ContractData, Error, SessionData, VM};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn shutdown() -> Result<(), Error> {
+    let tmp = tempfile::tempdir().expect("Creating a tempdir works");
+
+    let vm = VM::new(tmp.path())?;
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session.call::<_, ()>(id, "increment", &(), LIMIT)?;
+    let root = session.commit()?;
+
+    // The deletion is requested immediately, without waiting for it
+    drop(vm.delete_commit_async(root));
+    vm.shutdown(TIMEOUT)?;
+
+    // The deletion was done before the VM was shut down
+    let vm = VM::new(tmp.path())?;
+    assert!(vm.commits().is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn shutdown_timeout() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let session = vm.session(SessionData::builder())?;
+
+    match vm.shutdown(Duration::from_millis(50)) {
+        Err(Error::ShutdownTimeout(timeout)) => {
+            assert_eq!(timeout, Duration::from_millis(50))
+        }
+        res => {
+            panic!("Shutting down with a live session should time out: {res:?}")
+        }
+    }
+
+    drop(session);
+
+    Ok(())
+}
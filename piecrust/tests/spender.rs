@@ -189,3 +189,82 @@ pub fn limit_and_spent() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+pub fn out_of_gas_trace() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let spender_id = session.deploy(
+        contract_bytecode!("spender"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let callcenter_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    // The spender runs out of gas, and the callcenter recovers from it
+    let receipt = session.call::<_, Result<(), ContractError>>(
+        callcenter_id,
+        "call_spend_with_limit",
+        &(spender_id, 1u64),
+        LIMIT,
+    )?;
+    assert!(matches!(receipt.data, Err(ContractError::OutOfGas)));
+
+    let trace = receipt.out_of_gas.expect("There should be a trace");
+    assert_eq!(trace.frames.len(), 2);
+
+    let spender_frame = &trace.frames[0];
+    assert_eq!(spender_frame.contract_id, spender_id);
+    assert_eq!(spender_frame.fn_name, "spend");
+    assert_eq!(spender_frame.limit, 1);
+    assert_eq!(spender_frame.spent, 1);
+
+    let callcenter_frame = &trace.frames[1];
+    assert_eq!(callcenter_frame.contract_id, callcenter_id);
+    assert_eq!(callcenter_frame.fn_name, "call_spend_with_limit");
+    assert_eq!(callcenter_frame.limit, LIMIT);
+    assert!(callcenter_frame.spent < LIMIT);
+
+    assert!(
+        session.out_of_gas_trace().is_none(),
+        "A successful call should leave no trace on the session"
+    );
+
+    // The call itself runs out of gas
+    let err = session
+        .call::<_, Result<(), ContractError>>(
+            callcenter_id,
+            "call_spend_with_limit",
+            &(spender_id, 1u64),
+            1,
+        )
+        .expect_err("should error with no gas");
+    assert!(matches!(err, Error::OutOfGas));
+
+    let trace = session
+        .out_of_gas_trace()
+        .cloned()
+        .expect("There should be a trace");
+    assert_eq!(trace.frames.len(), 1);
+    assert_eq!(trace.frames[0].contract_id, callcenter_id);
+    assert_eq!(trace.frames[0].fn_name, "call_spend_with_limit");
+    assert_eq!(trace.frames[0].spent, 1);
+
+    // The trace is the same every time the call is made
+    let same_err = session.call::<_, Result<(), ContractError>>(
+        callcenter_id,
+        "call_spend_with_limit",
+        &(spender_id, 1u64),
+        1,
+    );
+    assert!(matches!(same_err, Err(Error::OutOfGas)));
+    assert_eq!(session.out_of_gas_trace(), Some(&trace));
+
+    Ok(())
+}
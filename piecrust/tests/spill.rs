@@ -0,0 +1,162 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::sync::mpsc;
+
+use piecrust::{
+    contract_bytecode, ContractData, Error, GasSchedule, SessionData, VM,
+};
+use piecrust_uplink::ARGBUF_LEN;
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 100_000_000;
+
+/// An input a few times larger than the argument buffer.
+fn large_input() -> Vec<u8> {
+    (0..4 * ARGBUF_LEN + 17).map(|i| i as u8).collect()
+}
+
+#[test]
+fn spilled_input_and_output() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("feeder"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let input = large_input();
+    let receipt =
+        session.call_spilled(id, "echo_spilled", &input[..], LIMIT)?;
+
+    let len: u64 = rkyv::from_bytes(&receipt.data).expect("should be a u64");
+    assert_eq!(len, input.len() as u64);
+
+    let spilled = receipt.spilled.expect("The output should be spilled");
+    assert_eq!(&spilled[..], &input[..]);
+
+    // Nothing is spilled to or from calls that don't spill
+    let receipt = session.call::<_, u64>(id, "echo_spilled", &(), LIMIT)?;
+    assert_eq!(receipt.data, 0);
+    assert!(receipt.spilled.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn spill_threshold() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let input = large_input();
+
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("feeder"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    session
+        .call_raw(id, "feed_spilled", input.clone(), LIMIT)
+        .expect_err("Arguments too large should fail without spilling");
+
+    let data = SessionData::builder().spill_threshold(ARGBUF_LEN);
+    let mut session = vm.session(data)?;
+    let id = session.deploy(
+        contract_bytecode!("feeder"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    // The feeder path streams the spilled input back to the host
+    let (sender, receiver) = mpsc::channel();
+    let receipt = session.feeder_call_raw(
+        id,
+        "feed_spilled",
+        input.clone(),
+        LIMIT,
+        sender,
+    )?;
+
+    let len: u64 = rkyv::from_bytes(&receipt.data).expect("should be a u64");
+    assert_eq!(len, input.len() as u64);
+
+    let fed: Vec<u8> = receiver.into_iter().flatten().collect();
+    assert_eq!(fed, input);
+
+    Ok(())
+}
+
+#[test]
+fn spill_gas() -> Result<(), Error> {
+    const SPILL_BYTE_COST: u64 = 3;
+
+    let vm = VM::ephemeral()?;
+    let input = large_input();
+
+    let mut session = vm.session(SessionData::builder())?;
+    let id = session.deploy(
+        contract_bytecode!("feeder"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let root = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    let base_spent = session
+        .call_spilled(id, "echo_spilled", &input[..], LIMIT)?
+        .gas_spent;
+
+    let schedule = GasSchedule {
+        spill_byte_cost: SPILL_BYTE_COST,
+        ..GasSchedule::default()
+    };
+    let mut session =
+        vm.session(SessionData::builder().base(root).gas_schedule(schedule))?;
+    let spent = session
+        .call_spilled(id, "echo_spilled", &input[..], LIMIT)?
+        .gas_spent;
+
+    // Each byte is charged once when read and once when written
+    let spill_cost = 2 * input.len() as u64 * SPILL_BYTE_COST;
+    assert_eq!(spent, base_spent + spill_cost);
+
+    Ok(())
+}
+
+#[test]
+fn max_spilled_output() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let input = large_input();
+
+    let data = SessionData::builder().max_spilled_output(input.len() - 1);
+    let mut session = vm.session(data)?;
+    let id = session.deploy(
+        contract_bytecode!("feeder"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    session
+        .call_spilled(id, "echo_spilled", &input[..], LIMIT)
+        .expect_err("Spilling past the limit should fail");
+
+    let data = SessionData::builder().max_spilled_output(input.len());
+    let mut session = vm.session(data)?;
+    let id = session.deploy(
+        contract_bytecode!("feeder"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let receipt =
+        session.call_spilled(id, "echo_spilled", &input[..], LIMIT)?;
+    let spilled = receipt.spilled.expect("The output should be spilled");
+    assert_eq!(spilled.len(), input.len());
+
+    Ok(())
+}
@@ -0,0 +1,178 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::thread;
+use std::time::Duration;
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractId, Error, Root, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+
+const COUNTER_ID: ContractId = ContractId::from_bytes([1; 32]);
+const BOX_ID: ContractId = ContractId::from_bytes([2; 32]);
+
+/// Builds a chain of four commits, returning their roots.
+fn chain(vm: &VM) -> Result<[Root; 4], Error> {
+    let mut session = vm.session(SessionData::builder())?;
+    session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER).contract_id(COUNTER_ID),
+        LIMIT,
+    )?;
+    session.deploy(
+        contract_bytecode!("box"),
+        ContractData::builder().owner(OWNER).contract_id(BOX_ID),
+        LIMIT,
+    )?;
+    let root_1 = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root_1))?;
+    session.call::<_, ()>(COUNTER_ID, "increment", &(), LIMIT)?;
+    let root_2 = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root_2))?;
+    session.call::<i16, ()>(BOX_ID, "set", &0x11, LIMIT)?;
+    let root_3 = session.commit()?;
+
+    let mut session = vm.session(SessionData::builder().base(root_3))?;
+    session.call::<_, ()>(COUNTER_ID, "increment", &(), LIMIT)?;
+    let root_4 = session.commit()?;
+
+    Ok([root_1, root_2, root_3, root_4])
+}
+
+fn assert_state(vm: &VM, root: Root) -> Result<(), Error> {
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(
+        session
+            .call::<_, i64>(COUNTER_ID, "read_value", &(), LIMIT)?
+            .data,
+        0xfe
+    );
+    assert_eq!(
+        session
+            .call::<_, Option<i16>>(BOX_ID, "get", &(), LIMIT)?
+            .data,
+        Some(0x11)
+    );
+    Ok(())
+}
+
+#[test]
+fn squash_commits() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let [root_1, root_2, root_3, root_4] = chain(&vm)?;
+
+    vm.squash_commits(root_2, root_4)?;
+    assert_state(&vm, root_4)?;
+
+    // The squashed commit no longer depends on the ones in between
+    vm.delete_commit(root_2)?;
+    vm.delete_commit(root_3)?;
+    assert_state(&vm, root_4)?;
+
+    let session = vm.session(SessionData::builder().base(root_4))?;
+    assert_eq!(session.root(), root_4, "The root should not change");
+    drop(session);
+
+    let vm = VM::new(vm.root_dir())?;
+    let mut commits = vm.commits();
+    commits.sort();
+    let mut expected = vec![root_1, root_4];
+    expected.sort();
+    assert_eq!(commits, expected);
+    assert_state(&vm, root_4)?;
+
+    Ok(())
+}
+
+#[test]
+fn squash_whole_chain() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let [root_1, root_2, root_3, root_4] = chain(&vm)?;
+
+    vm.squash_commits(root_1, root_4)?;
+
+    for root in [root_1, root_2, root_3] {
+        vm.delete_commit(root)?;
+    }
+    assert_eq!(vm.commits(), vec![root_4]);
+    assert_state(&vm, root_4)?;
+
+    let vm = VM::new(vm.root_dir())?;
+    assert_state(&vm, root_4)?;
+
+    Ok(())
+}
+
+#[test]
+fn squash_not_ancestor() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let [root_1, _, root_3, root_4] = chain(&vm)?;
+
+    assert!(vm.squash_commits(root_4, root_1).is_err());
+    assert!(vm.squash_commits([0; 32], root_4).is_err());
+    assert!(vm.squash_commits(root_1, [0; 32]).is_err());
+
+    // Squashing a commit into itself does nothing
+    vm.squash_commits(root_3, root_3)?;
+    assert_state(&vm, root_4)?;
+
+    Ok(())
+}
+
+#[test]
+fn delete_during_squash() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+    let [root_1, root_2, root_3, root_4] = chain(&vm)?;
+
+    // Queue both the squash and the deletion of a commit in between, so they
+    // are picked up together once resumed.
+    vm.scheduler().pause();
+
+    thread::scope(|s| {
+        let squash = s.spawn(|| vm.squash_commits(root_2, root_4));
+        while vm.scheduler().queue_depth() < 1 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let deletion = s.spawn(|| vm.delete_commit(root_3));
+        while vm.scheduler().queue_depth() < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        vm.scheduler().resume();
+        squash.join().expect("Squash should not panic")?;
+        deletion.join().expect("Deletion should not panic")?;
+
+        Ok::<_, Error>(())
+    })?;
+
+    assert_state(&vm, root_4)?;
+
+    let vm = VM::new(vm.root_dir())?;
+    assert_state(&vm, root_4)?;
+
+    // Squashing onto a commit being deleted is refused
+    vm.scheduler().pause();
+
+    thread::scope(|s| {
+        let deletion = s.spawn(|| vm.delete_commit(root_1));
+        while vm.scheduler().queue_depth() < 1 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert!(vm.squash_commits(root_1, root_4).is_err());
+
+        vm.scheduler().resume();
+        deletion.join().expect("Deletion should not panic")
+    })?;
+
+    Ok(())
+}
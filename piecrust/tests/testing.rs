@@ -0,0 +1,118 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use bytecheck::CheckBytes;
+use piecrust::testing::{
+    assert_emitted, check_contract_hashes, contract_bytecode, emitted,
+    TestSession, HEIGHT_META,
+};
+use piecrust::{contract_event, ContractId, Error, SessionData};
+use rkyv::{Archive, Deserialize, Serialize};
+
+contract_event! {
+    #[topic = "typed_number"]
+    #[derive(Debug, PartialEq, Archive, Serialize, Deserialize)]
+    #[archive_attr(derive(CheckBytes))]
+    pub struct TypedNumber {
+        pub num: u32,
+    }
+}
+
+#[test]
+fn deploy_and_call() -> Result<(), Error> {
+    let mut session = TestSession::new()?;
+
+    let id = session.deploy("counter")?;
+    session.call::<_, ()>(id, "increment", &())?;
+
+    assert_eq!(session.call::<_, i64>(id, "read_value", &())?.data, 0xfd);
+
+    assert_eq!(
+        contract_bytecode("counter").expect("The counter should be found"),
+        piecrust::contract_bytecode!("counter")
+    );
+    assert!(contract_bytecode("no_such_contract").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn advance_height() -> Result<(), Error> {
+    let mut session = TestSession::new()?;
+    let id = session.deploy("everest")?;
+
+    assert_eq!(session.height(), 0);
+    assert_eq!(
+        session.call::<_, Option<u64>>(id, "get_height", &())?.data,
+        Some(0)
+    );
+
+    assert_eq!(session.advance_height(10)?, 10);
+    assert_eq!(
+        session.call::<_, Option<u64>>(id, "get_height", &())?.data,
+        Some(10)
+    );
+
+    let session = TestSession::with_data(
+        SessionData::builder().insert(HEIGHT_META, 42u64)?,
+    )?;
+    assert_eq!(session.height(), 42);
+
+    Ok(())
+}
+
+#[test]
+fn fixtures() -> Result<(), Error> {
+    let mut session = TestSession::new()?.with_meta([("chain_id", 7u8)])?;
+    assert!(session.meta("chain_id").is_some());
+
+    let id = ContractId::from_bytes([1; 32]);
+    assert_eq!(session.fund(id, 100), 100);
+    assert_eq!(session.fund(id, 20), 120);
+    assert_eq!(session.balance(&id), 120);
+
+    Ok(())
+}
+
+#[test]
+fn event_assertions() -> Result<(), Error> {
+    let mut session = TestSession::new()?;
+    let id = session.deploy("eventer")?;
+
+    let receipt = session.call::<_, ()>(id, "emit_events_typed", &3u32)?;
+
+    assert_eq!(
+        emitted::<TypedNumber>(&receipt.events),
+        vec![
+            TypedNumber { num: 0 },
+            TypedNumber { num: 1 },
+            TypedNumber { num: 2 }
+        ]
+    );
+    assert_emitted(&receipt.events, id, &TypedNumber { num: 2 });
+
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "Expected TypedNumber { num: 3 }")]
+fn event_assertion_fails() {
+    let mut session = TestSession::new().unwrap();
+    let id = session.deploy("eventer").unwrap();
+
+    let receipt = session
+        .call::<_, ()>(id, "emit_events_typed", &3u32)
+        .unwrap();
+
+    assert_emitted(&receipt.events, id, &TypedNumber { num: 3 });
+}
+
+#[test]
+fn recorded_contract_hashes() {
+    let diff = check_contract_hashes()
+        .expect("The contracts and their hashes should be readable");
+    assert!(diff.is_empty(), "{diff}");
+}
@@ -0,0 +1,161 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::sync::Barrier;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use piecrust::{
+    contract_bytecode, ContractData, ContractError, Error, SessionData, VM,
+};
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 1_000_000;
+// Far more gas than the calls could spend before timing out
+const SPIN_LIMIT: u64 = 1 << 48;
+const TIMEOUT: Duration = Duration::from_millis(100);
+// Enough gas to spin for far longer than the timeout
+const UNTIMED_LIMIT: u64 = 1 << 32;
+
+/// A module with the argument buffer at the start of its memory, exporting a
+/// function looping forever.
+const SPIN: &[u8] = &[
+    0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+    0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f, // type section
+    0x03, 0x02, 0x01, 0x00, // function section
+    0x05, 0x03, 0x01, 0x00, 0x02, // memory section
+    0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x00, 0x0b, // global section
+    0x07, 0x15, 0x03, 0x06, b'm', b'e', b'm', b'o', b'r', b'y', 0x02, 0x00,
+    0x01, b'A', 0x03, 0x00, 0x04, b's', b'p', b'i', b'n', 0x00,
+    0x00, // export section
+    0x0a, 0x0a, 0x01, 0x08, 0x00, 0x03, 0x40, 0x0c, 0x00, 0x0b, 0x00,
+    0x0b, // code section
+];
+
+#[test]
+fn call_timeout() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session =
+        vm.session(SessionData::builder().call_timeout(TIMEOUT))?;
+
+    let spin_id =
+        session.deploy(SPIN, ContractData::builder().owner(OWNER), LIMIT)?;
+    let counter_id = session.deploy(
+        contract_bytecode!("counter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    match session.call::<_, ()>(spin_id, "spin", &(), SPIN_LIMIT) {
+        Err(Error::Timeout(timeout)) => assert_eq!(timeout, TIMEOUT),
+        res => panic!("The call should time out, got: {res:?}"),
+    }
+
+    // Calls within the timeout are unaffected by the ones that timed out
+    session.call::<_, ()>(counter_id, "increment", &(), LIMIT)?;
+    assert_eq!(
+        session
+            .call::<_, i64>(counter_id, "read_value", &(), LIMIT)?
+            .data,
+        0xfd
+    );
+
+    Ok(())
+}
+
+#[test]
+fn nested_call_timeout() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session =
+        vm.session(SessionData::builder().call_timeout(TIMEOUT))?;
+
+    let spin_id =
+        session.deploy(SPIN, ContractData::builder().owner(OWNER), LIMIT)?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    // The caller can't handle the callee timing out, since the whole call is
+    // interrupted
+    let result = session.call::<_, Result<Vec<u8>, ContractError>>(
+        center_id,
+        "delegate_query",
+        &(spin_id, String::from("spin"), Vec::<u8>::new()),
+        SPIN_LIMIT,
+    );
+    match result {
+        Err(Error::Timeout(timeout)) => assert_eq!(timeout, TIMEOUT),
+        res => panic!("The call should time out, got: {res:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn no_timeout_by_default() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let spin_id =
+        session.deploy(SPIN, ContractData::builder().owner(OWNER), LIMIT)?;
+
+    // Without a timeout the call only stops once it runs out of gas
+    match session.call::<_, ()>(spin_id, "spin", &(), LIMIT) {
+        Err(Error::OutOfGas) => {}
+        res => panic!("The call should run out of gas, got: {res:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn timeout_per_session() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+    let spin_id =
+        session.deploy(SPIN, ContractData::builder().owner(OWNER), LIMIT)?;
+    let root = session.commit()?;
+
+    let mut timed =
+        vm.session(SessionData::builder().base(root).call_timeout(TIMEOUT))?;
+    let mut untimed = vm.session(SessionData::builder().base(root))?;
+
+    let barrier = Barrier::new(2);
+    thread::scope(|scope| {
+        let handle = scope.spawn(|| {
+            barrier.wait();
+            let start = Instant::now();
+            let result = untimed
+                .call::<_, ()>(spin_id, "spin", &(), UNTIMED_LIMIT)
+                .map(|receipt| receipt.gas_spent);
+            (result, start.elapsed())
+        });
+
+        barrier.wait();
+        match timed.call::<_, ()>(spin_id, "spin", &(), SPIN_LIMIT) {
+            Err(Error::Timeout(timeout)) => assert_eq!(timeout, TIMEOUT),
+            res => panic!("The call should time out, got: {res:?}"),
+        }
+
+        // The session without a timeout shares the engine, and was running
+        // as the other timed out, but is only stopped by running out of gas
+        let (result, elapsed) =
+            handle.join().expect("The thread should not panic");
+        assert!(elapsed > TIMEOUT, "The call should outlast the timeout");
+        match result {
+            Err(Error::OutOfGas) => {}
+            res => panic!("The call should run out of gas, got: {res:?}"),
+        }
+    });
+
+    Ok(())
+}
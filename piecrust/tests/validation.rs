@@ -5,6 +5,7 @@
 // Copyright (c) DUSK NETWORK. All rights reserved.
 
 use piecrust::{contract_bytecode, ContractData, Error, SessionData, VM};
+use piecrust_uplink::ContractError;
 
 const OWNER: [u8; 32] = [0u8; 32];
 const LIMIT: u64 = 1_000_000;
@@ -28,6 +29,92 @@ fn out_of_bounds() -> Result<(), Error> {
     Ok(())
 }
 
+/// The externs taking a pointer, in the order they are indexed by the
+/// `out_of_bounds_import` function of the C example.
+const POINTER_IMPORTS: [&str; 9] = [
+    "hq",
+    "hd",
+    "c (contract ID)",
+    "c (function name)",
+    "cf",
+    "emit",
+    "emit_event",
+    "defer",
+    "owner",
+];
+
+#[test]
+fn out_of_bounds_imports() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let c_example_id = session.deploy(
+        contract_bytecode!("c_example"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    for (index, import) in POINTER_IMPORTS.iter().enumerate() {
+        let err = session
+            .call::<_, ()>(
+                c_example_id,
+                "out_of_bounds_import",
+                &(index as u8),
+                LIMIT,
+            )
+            .expect_err("An out of bounds access should error");
+
+        assert!(
+            matches!(err, Error::MemoryAccessOutOfBounds { .. }),
+            "{import} should error with an out of bounds access, got {err:?}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn out_of_bounds_in_callee() -> Result<(), Error> {
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let c_example_id = session.deploy(
+        contract_bytecode!("c_example"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+    let center_id = session.deploy(
+        contract_bytecode!("callcenter"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    for (index, import) in POINTER_IMPORTS.iter().enumerate() {
+        let err = session
+            .call::<_, Result<Vec<u8>, ContractError>>(
+                center_id,
+                "delegate_query",
+                &(
+                    c_example_id,
+                    String::from("out_of_bounds_import"),
+                    vec![index as u8],
+                ),
+                LIMIT,
+            )?
+            .data
+            .expect_err("An out of bounds access should error");
+
+        assert!(
+            matches!(err, ContractError::MemoryAccessOutOfBounds),
+            "{import} should error with an out of bounds access, got {err:?}"
+        );
+    }
+
+    Ok(())
+}
+
 #[test]
 fn bad_contract() -> Result<(), Error> {
     let vm = VM::ephemeral()?;
@@ -39,3 +39,37 @@ pub fn vector_push_pop() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+pub fn vector_heap_peak() -> Result<(), Error> {
+    const BYTES: u32 = 4096;
+
+    let vm = VM::ephemeral()?;
+
+    let mut session = vm.session(SessionData::builder())?;
+
+    let id = session.deploy(
+        contract_bytecode!("vector"),
+        ContractData::builder().owner(OWNER),
+        LIMIT,
+    )?;
+
+    let receipt =
+        session.call::<_, (u64, u64)>(id, "alloc_and_free", &BYTES, LIMIT)?;
+    let (used, peak) = receipt.data;
+
+    assert!(peak >= used + BYTES as u64);
+    assert_eq!(receipt.heap_peaks.len(), 1);
+    assert_eq!(receipt.heap_peaks[0].contract, id);
+    assert_eq!(receipt.heap_peaks[0].peak, peak as usize);
+
+    // The peak is reset at the start of each call
+    let receipt =
+        session.call::<_, (u64, u64)>(id, "alloc_and_free", &16u32, LIMIT)?;
+    let (_, small_peak) = receipt.data;
+
+    assert!(small_peak < peak);
+    assert_eq!(receipt.heap_peaks[0].peak, small_peak as usize);
+
+    Ok(())
+}
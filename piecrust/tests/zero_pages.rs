@@ -0,0 +1,113 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+//
+// Copyright (c) DUSK NETWORK. All rights reserved.
+
+use std::fs;
+
+use piecrust::{
+    check_layout, contract_bytecode, ContractData, ContractId, Error, Root,
+    SessionData, VM,
+};
+use piecrust_uplink::ARGBUF_LEN;
+
+const OWNER: [u8; 32] = [0u8; 32];
+const LIMIT: u64 = 40_000_000;
+
+const GROWER_ID: ContractId = ContractId::from_bytes([1; 32]);
+
+/// Deploys the grower and appends buffers of zeroes to it, returning the
+/// root of the commit and the number of pages included in the state.
+fn commit_zeroes(vm: &VM) -> Result<(Root, usize), Error> {
+    let mut session = vm.session(SessionData::builder())?;
+
+    session.deploy(
+        contract_bytecode!("grower"),
+        ContractData::builder().owner(OWNER).contract_id(GROWER_ID),
+        LIMIT,
+    )?;
+    // The vector doesn't start on a page boundary, so it takes a few appends
+    // for some of the pages it spans to contain only zeroes.
+    for _ in 0..4 {
+        session.call_raw(GROWER_ID, "append", [0u8; ARGBUF_LEN], LIMIT)?;
+    }
+
+    let pages = session
+        .memory_pages(GROWER_ID)
+        .expect("The contract should have memory pages")
+        .count();
+    let root = session.commit()?;
+
+    Ok((root, pages))
+}
+
+/// Returns the number of page files written for the grower in the given commit.
+fn page_files(vm: &VM, root: Root) -> usize {
+    let dir = vm
+        .root_dir()
+        .join("main")
+        .join("memory")
+        .join(hex::encode(GROWER_ID))
+        .join(hex::encode(root));
+    fs::read_dir(dir).map_or(0, |entries| entries.count())
+}
+
+fn view_zeroes(vm: &VM, root: Root) -> Result<(), Error> {
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    assert_eq!(session.root(), root);
+
+    let mut view = [0u8; 8];
+    view[4..].copy_from_slice(&(ARGBUF_LEN as u32).to_le_bytes());
+    let receipt = session.call_raw(GROWER_ID, "view", view, LIMIT)?;
+    assert_eq!(receipt.data, [0u8; ARGBUF_LEN]);
+
+    Ok(())
+}
+
+#[test]
+fn zero_pages_elided() -> Result<(), Error> {
+    let tmp = tempfile::tempdir().expect("Creating a temp dir should work");
+
+    let explicit_vm = VM::ephemeral()?;
+    let (explicit_root, explicit_pages) = commit_zeroes(&explicit_vm)?;
+
+    let vm = VM::new(tmp.path())?;
+    vm.set_elide_zero_pages(true);
+    assert!(vm.environment().elide_zero_pages);
+    assert_ne!(vm.environment(), explicit_vm.environment());
+
+    let (root, pages) = commit_zeroes(&vm)?;
+
+    assert_ne!(root, explicit_root, "Zero pages should not be hashed");
+    assert!(pages < explicit_pages, "Zero pages should not be included");
+    assert!(
+        page_files(&vm, root) < page_files(&explicit_vm, explicit_root),
+        "Zero pages should not be written"
+    );
+
+    // Committing the same changes again yields the same root
+    let (same_root, _) = commit_zeroes(&vm)?;
+    assert_eq!(root, same_root);
+
+    view_zeroes(&vm, root)?;
+    drop(vm);
+
+    // The elided pages still read as zeroes once the commit is loaded back,
+    // and the root is unchanged.
+    let vm = VM::new(tmp.path())?;
+    vm.set_elide_zero_pages(true);
+    view_zeroes(&vm, root)?;
+
+    // Pages stay elided once the commit they were left out of is finalized
+    let mut session = vm.session(SessionData::builder().base(root))?;
+    session.call_raw(GROWER_ID, "append", [1u8; ARGBUF_LEN], LIMIT)?;
+    let next_root = session.commit()?;
+    vm.finalize_commit(root)?;
+
+    let report = check_layout(vm.root_dir()).expect("checking should succeed");
+    assert!(report.is_ok(), "{report}");
+    view_zeroes(&vm, next_root)?;
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+#!/bin/sh
+
+# Records or checks the hashes of the contracts built in-tree, ensuring that
+# every developer runs the tests against the same bytecode.
+
+set -e
+
+# The script should check whether the inputs are set, or print usage
+# information otherwise.
+if [ "$1" != "record" ] && [ "$1" != "check" ] || [ -z "$2" ] || [ -z "$3" ]; then
+    echo "Usage: $0 <record|check> <wasm-dir> <hashes-file>"
+    exit 1
+fi
+
+MODE=$1
+WASM_DIR=$2
+HASHES_FILE=$3
+
+if command -v sha256sum > /dev/null; then
+    SHA256="sha256sum"
+else
+    SHA256="shasum -a 256"
+fi
+
+hash_contracts() {
+    (cd "$WASM_DIR" && find . -maxdepth 1 -name "*.wasm" \
+        | sed 's|^\./||' \
+        | LC_ALL=C sort \
+        | xargs $SHA256)
+}
+
+if [ "$MODE" = "record" ]; then
+    hash_contracts > "$HASHES_FILE"
+    echo "Recorded the hashes of $(wc -l < "$HASHES_FILE") contracts"
+    exit 0
+fi
+
+if [ ! -f "$HASHES_FILE" ]; then
+    echo "No recorded hashes at $HASHES_FILE."
+    echo "Run \`make record-contract-hashes\` and commit the file."
+    exit 1
+fi
+
+ACTUAL=$(mktemp)
+trap 'rm -f "$ACTUAL"' EXIT
+
+hash_contracts > "$ACTUAL"
+
+if ! diff -u "$HASHES_FILE" "$ACTUAL"; then
+    echo
+    echo "The contracts built differ from the recorded ones (- recorded, + built)."
+    echo "Make sure the compiler set up by \`make setup-compiler\` is used, or"
+    echo "run \`make record-contract-hashes\` if the change is intended."
+    exit 1
+fi
+
+echo "All contracts match their recorded hashes"
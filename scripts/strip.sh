@@ -51,4 +51,8 @@ if [ ! -d "$EXTRACTED_DIR" ]; then
     tar -xzf "$ARTIFACT_PATH" -C "$EXTRACTED_DIR" --strip-components=1
 fi
 
-"$EXTRACTED_DIR"/wasm-tools strip -a "$1" -o "$2"
+# Strip debug information and symbol names, but keep the `producers` section
+# and the sections emitted by `piecrust-uplink`, since the host reads them.
+DELETED_SECTIONS='^(\.debug_.*|name|target_features|linking|reloc\..*|sourceMappingURL|external_debug_info)$'
+
+"$EXTRACTED_DIR"/wasm-tools strip --delete "$DELETED_SECTIONS" "$1" -o "$2"